@@ -14,9 +14,101 @@ fn main() -> Result<()> {
 		assemble_x86_64_smp_boot()?;
 	}
 
+	generate_sbom()?;
+
 	Ok(())
 }
 
+/// Generates the list of the enabled features and the versions of the dependencies,
+/// which are reported by `/proc/sbom` and `sys_kernel_features`.
+fn generate_sbom() -> Result<()> {
+	let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+	let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+	let lock_file = manifest_dir.join("Cargo.lock");
+
+	let mut features = env::vars()
+		.filter_map(|(key, _)| {
+			key.strip_prefix("CARGO_FEATURE_")
+				.map(|feature| feature.to_lowercase().replace('_', "-"))
+		})
+		.collect::<Vec<_>>();
+	features.sort();
+
+	// Without a lock file, e.g., in a published crate, the versions are unknown.
+	let dependencies = match fs::read_to_string(&lock_file) {
+		Ok(lock) => locked_dependencies(&lock, &env::var("CARGO_PKG_NAME").unwrap()),
+		Err(_) => Vec::new(),
+	};
+
+	let mut sbom = File::create(out_dir.join("sbom.rs"))?;
+	writeln!(
+		&mut sbom,
+		"pub(crate) static FEATURES: &[&str] = &{features:?};"
+	)?;
+	writeln!(
+		&mut sbom,
+		"pub(crate) static DEPENDENCIES: &[(&str, &str)] = &{dependencies:?};"
+	)?;
+	sbom.flush()?;
+
+	println!("cargo:rerun-if-changed={}", lock_file.display());
+	println!("cargo:rerun-if-changed=build.rs");
+	Ok(())
+}
+
+/// Returns the names and versions of the direct dependencies of `package` in a `Cargo.lock`.
+fn locked_dependencies(lock: &str, package: &str) -> Vec<(String, String)> {
+	// every package is a block of `key = value` lines and an array of dependencies
+	let packages = lock
+		.split("[[package]]")
+		.skip(1)
+		.map(|block| {
+			let value = |key: &str| {
+				block.lines().find_map(|line| {
+					line.strip_prefix(key)?
+						.trim_start()
+						.strip_prefix('=')
+						.map(|value| value.trim().trim_matches('"').to_string())
+				})
+			};
+			let dependencies = block
+				.lines()
+				.skip_while(|line| !line.starts_with("dependencies"))
+				.skip(1)
+				.take_while(|line| !line.starts_with(']'))
+				.map(|line| {
+					line.trim()
+						.trim_end_matches(',')
+						.trim_matches('"')
+						.to_string()
+				})
+				.collect::<Vec<_>>();
+			(value("name "), value("version "), dependencies)
+		})
+		.collect::<Vec<_>>();
+
+	let Some((_, _, dependencies)) = packages
+		.iter()
+		.find(|(name, _, _)| name.as_deref() == Some(package))
+	else {
+		return Vec::new();
+	};
+
+	let mut versions = dependencies
+		.iter()
+		.filter_map(|dependency| match dependency.split_once(' ') {
+			// ambiguous names are followed by the version
+			Some((name, version)) => Some((name.to_string(), version.to_string())),
+			None => packages.iter().find_map(|(name, version, _)| {
+				(name.as_deref() == Some(dependency.as_str()))
+					.then(|| (dependency.clone(), version.clone().unwrap_or_default()))
+			}),
+		})
+		.collect::<Vec<_>>();
+	versions.sort();
+	versions
+}
+
 fn assemble_x86_64_smp_boot() -> Result<()> {
 	let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
 
@@ -1,7 +1,12 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(target_arch = "x86_64"), feature(f128))]
+// Prevent LLVM from turning the loops of `mem` into calls to themselves.
+#![no_builtins]
 
 pub mod math;
+pub mod mem;
 
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
 	loop {}
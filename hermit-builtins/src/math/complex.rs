@@ -0,0 +1,670 @@
+//! C-compatible complex functions ([`complex.h`]).
+//!
+//! The functions for `long double complex` are not provided.
+//!
+//! [`complex.h`]: https://en.cppreference.com/w/c/numeric/complex
+
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A complex number with the layout and calling convention of `_Complex`.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Complex<T> {
+	pub re: T,
+	pub im: T,
+}
+
+impl<T> Complex<T> {
+	const fn new(re: T, im: T) -> Self {
+		Self { re, im }
+	}
+}
+
+/// The floating-point types of the components of [`Complex`]
+pub trait Real:
+	Copy
+	+ PartialOrd
+	+ Add<Output = Self>
+	+ Sub<Output = Self>
+	+ Mul<Output = Self>
+	+ Div<Output = Self>
+	+ Neg<Output = Self>
+{
+	const ZERO: Self;
+	const HALF: Self;
+	const ONE: Self;
+	const TWO: Self;
+	const INFINITY: Self;
+	const NAN: Self;
+	const FRAC_PI_2: Self;
+	const LN_2: Self;
+	/// Magnitudes, whose square is guaranteed to be accurate
+	const SQRT_EPSILON: Self;
+	/// Magnitudes, beyond which `1 + x * x` equals `x * x`
+	const LARGE: Self;
+	/// Largest `x`, for which `exp(x)` is finite
+	const EXP_MAX: Self;
+	/// Magnitudes, beyond which `tanh(x)` rounds to 1
+	const TANH_MAX: Self;
+	const MAX: Self;
+
+	fn is_nan(self) -> bool;
+	fn is_infinite(self) -> bool;
+	fn is_finite(self) -> bool;
+	fn abs(self) -> Self;
+	fn copysign(self, sign: Self) -> Self;
+	fn sqrt(self) -> Self;
+	fn hypot(self, other: Self) -> Self;
+	fn atan2(self, other: Self) -> Self;
+	fn exp(self) -> Self;
+	fn ln(self) -> Self;
+	fn ln_1p(self) -> Self;
+	fn asinh(self) -> Self;
+	fn sin(self) -> Self;
+	fn cos(self) -> Self;
+	fn tan(self) -> Self;
+	fn sinh(self) -> Self;
+	fn cosh(self) -> Self;
+}
+
+macro_rules! impl_real {
+	(
+		$t:ident,
+		$exp_max:literal,
+		$tanh_max:literal,
+		$abs:ident,
+		$copysign:ident,
+		$sqrt:ident,
+		$hypot:ident,
+		$atan2:ident,
+		$exp:ident,
+		$ln:ident,
+		$ln_1p:ident,
+		$asinh:ident,
+		$sin:ident,
+		$cos:ident,
+		$tan:ident,
+		$sinh:ident,
+		$cosh:ident
+	) => {
+		impl Real for $t {
+			const ZERO: Self = 0.0;
+			const HALF: Self = 0.5;
+			const ONE: Self = 1.0;
+			const TWO: Self = 2.0;
+			const INFINITY: Self = $t::INFINITY;
+			const NAN: Self = $t::NAN;
+			const FRAC_PI_2: Self = core::$t::consts::FRAC_PI_2;
+			const LN_2: Self = core::$t::consts::LN_2;
+			const SQRT_EPSILON: Self = 1.0 / (1u64 << ($t::MANTISSA_DIGITS / 2)) as $t;
+			const LARGE: Self = (1u64 << ($t::MANTISSA_DIGITS / 2 + 1)) as $t;
+			const EXP_MAX: Self = $exp_max;
+			const TANH_MAX: Self = $tanh_max;
+			const MAX: Self = $t::MAX;
+
+			fn is_nan(self) -> bool {
+				$t::is_nan(self)
+			}
+
+			fn is_infinite(self) -> bool {
+				$t::is_infinite(self)
+			}
+
+			fn is_finite(self) -> bool {
+				$t::is_finite(self)
+			}
+
+			fn abs(self) -> Self {
+				libm::$abs(self)
+			}
+
+			fn copysign(self, sign: Self) -> Self {
+				libm::$copysign(self, sign)
+			}
+
+			fn sqrt(self) -> Self {
+				libm::$sqrt(self)
+			}
+
+			fn hypot(self, other: Self) -> Self {
+				libm::$hypot(self, other)
+			}
+
+			fn atan2(self, other: Self) -> Self {
+				libm::$atan2(self, other)
+			}
+
+			fn exp(self) -> Self {
+				libm::$exp(self)
+			}
+
+			fn ln(self) -> Self {
+				libm::$ln(self)
+			}
+
+			fn ln_1p(self) -> Self {
+				libm::$ln_1p(self)
+			}
+
+			fn asinh(self) -> Self {
+				libm::$asinh(self)
+			}
+
+			fn sin(self) -> Self {
+				libm::$sin(self)
+			}
+
+			fn cos(self) -> Self {
+				libm::$cos(self)
+			}
+
+			fn tan(self) -> Self {
+				libm::$tan(self)
+			}
+
+			fn sinh(self) -> Self {
+				libm::$sinh(self)
+			}
+
+			fn cosh(self) -> Self {
+				libm::$cosh(self)
+			}
+		}
+	};
+}
+
+impl_real!(
+	f64,
+	709.782_712_893_384,
+	22.0,
+	fabs,
+	copysign,
+	sqrt,
+	hypot,
+	atan2,
+	exp,
+	log,
+	log1p,
+	asinh,
+	sin,
+	cos,
+	tan,
+	sinh,
+	cosh
+);
+impl_real!(
+	f32, 88.722_84, 9.0, fabsf, copysignf, sqrtf, hypotf, atan2f, expf, logf, log1pf, asinhf, sinf,
+	cosf, tanf, sinhf, coshf
+);
+
+impl<T: Real> Complex<T> {
+	/// Returns `i * z`.
+	fn mul_i(self) -> Self {
+		Complex::new(-self.im, self.re)
+	}
+
+	/// Returns `-i * z`.
+	fn mul_neg_i(self) -> Self {
+		Complex::new(self.im, -self.re)
+	}
+
+	fn proj(self) -> Self {
+		if self.re.is_infinite() || self.im.is_infinite() {
+			Complex::new(T::INFINITY, T::ZERO.copysign(self.im))
+		} else {
+			self
+		}
+	}
+
+	fn exp(self) -> Self {
+		let Complex { re: x, im: y } = self;
+
+		if y == T::ZERO {
+			return Complex::new(x.exp(), y);
+		}
+		if !y.is_finite() {
+			return if x == T::INFINITY {
+				Complex::new(x, T::NAN)
+			} else if x == -T::INFINITY {
+				Complex::new(T::ZERO, T::ZERO)
+			} else {
+				Complex::new(T::NAN, T::NAN)
+			};
+		}
+
+		if x > T::EXP_MAX {
+			// exp(x) overflows, although the result may still be finite.
+			let half = (x * T::HALF).exp();
+			Complex::new(half * y.cos() * half, half * y.sin() * half)
+		} else {
+			let exp = x.exp();
+			Complex::new(exp * y.cos(), exp * y.sin())
+		}
+	}
+
+	fn ln(self) -> Self {
+		let arg = self.im.atan2(self.re);
+		let (a, b) = if self.re.abs() >= self.im.abs() {
+			(self.re.abs(), self.im.abs())
+		} else {
+			(self.im.abs(), self.re.abs())
+		};
+
+		// log(|z|) = log(1 + (|z|^2 - 1)) / 2 avoids cancellation near |z| = 1.
+		if a >= T::HALF && a <= T::TWO {
+			let re = T::HALF * ((a - T::ONE) * (a + T::ONE) + b * b).ln_1p();
+			return Complex::new(re, arg);
+		}
+		Complex::new(a.hypot(b).ln(), arg)
+	}
+
+	fn pow(self, c: Self) -> Self {
+		let log = self.ln();
+		Complex::new(c.re * log.re - c.im * log.im, c.re * log.im + c.im * log.re).exp()
+	}
+
+	fn sqrt(self) -> Self {
+		let Complex { re: x, im: y } = self;
+
+		if x == T::ZERO && y == T::ZERO {
+			return Complex::new(T::ZERO, y);
+		}
+		if y.is_infinite() {
+			return Complex::new(T::INFINITY, y);
+		}
+		if x.is_nan() {
+			return Complex::new(x, x);
+		}
+		if x.is_infinite() {
+			return match (x < T::ZERO, y.is_nan()) {
+				(true, true) => Complex::new(y, T::INFINITY),
+				(true, false) => Complex::new(T::ZERO, T::INFINITY.copysign(y)),
+				(false, true) => Complex::new(x, y),
+				(false, false) => Complex::new(x, T::ZERO.copysign(y)),
+			};
+		}
+		if y.is_nan() {
+			return Complex::new(y, y);
+		}
+
+		// Scale down large arguments, so that `|x| + |z|` doesn't overflow.
+		let quarter = T::HALF * T::HALF;
+		if x.abs() >= T::MAX * quarter || y.abs() >= T::MAX * quarter {
+			let root = Complex::new(x * quarter, y * quarter).sqrt();
+			return Complex::new(root.re * T::TWO, root.im * T::TWO);
+		}
+
+		let t = ((x.abs() + x.hypot(y)) * T::HALF).sqrt();
+		if x >= T::ZERO {
+			Complex::new(t, y / (T::TWO * t))
+		} else {
+			Complex::new(y.abs() / (T::TWO * t), t.copysign(y))
+		}
+	}
+
+	fn sinh(self) -> Self {
+		let Complex { re: x, im: y } = self;
+
+		if y == T::ZERO {
+			return Complex::new(x.sinh(), y);
+		}
+		if !y.is_finite() {
+			return if x == T::ZERO || x.is_infinite() {
+				Complex::new(x, T::NAN)
+			} else {
+				Complex::new(T::NAN, T::NAN)
+			};
+		}
+
+		if x.abs() > T::EXP_MAX {
+			// sinh(x) and cosh(x) overflow, although the result may still be finite.
+			let half = (x.abs() * T::HALF).exp();
+			let scaled = half * T::HALF;
+			Complex::new(
+				scaled * y.cos() * half * T::ONE.copysign(x),
+				scaled * y.sin() * half,
+			)
+		} else {
+			Complex::new(x.sinh() * y.cos(), x.cosh() * y.sin())
+		}
+	}
+
+	fn cosh(self) -> Self {
+		let Complex { re: x, im: y } = self;
+
+		if y == T::ZERO {
+			// sinh(x) may overflow, but only its sign matters.
+			return Complex::new(x.cosh(), T::ZERO.copysign(x) * y);
+		}
+		if !y.is_finite() {
+			return if x == T::ZERO {
+				Complex::new(T::NAN, x)
+			} else if x.is_infinite() {
+				Complex::new(x * x, T::NAN)
+			} else {
+				Complex::new(T::NAN, T::NAN)
+			};
+		}
+
+		if x.abs() > T::EXP_MAX {
+			let half = (x.abs() * T::HALF).exp();
+			let scaled = half * T::HALF;
+			Complex::new(
+				scaled * y.cos() * half,
+				scaled * y.sin() * half * T::ONE.copysign(x),
+			)
+		} else {
+			Complex::new(x.cosh() * y.cos(), x.sinh() * y.sin())
+		}
+	}
+
+	/// Hyperbolic tangent after Kahan, "Branch Cuts for Complex Elementary Functions"
+	fn tanh(self) -> Self {
+		let Complex { re: x, im: y } = self;
+
+		if x.is_nan() {
+			return Complex::new(x, if y == T::ZERO { y } else { x });
+		}
+		if x.is_infinite() {
+			let im = if y.is_finite() { y.sin() * y.cos() } else { y };
+			return Complex::new(T::ONE.copysign(x), T::ZERO.copysign(im));
+		}
+		if !y.is_finite() {
+			let re = if x == T::ZERO { x } else { T::NAN };
+			return Complex::new(re, T::NAN);
+		}
+
+		if x.abs() >= T::TANH_MAX {
+			let exp = (-T::TWO * x.abs()).exp();
+			return Complex::new(
+				T::ONE.copysign(x),
+				T::TWO * T::TWO * y.sin() * y.cos() * exp,
+			);
+		}
+
+		let t = y.tan();
+		let beta = T::ONE + t * t;
+		let s = x.sinh();
+		let rho = (T::ONE + s * s).sqrt();
+		let denom = T::ONE + beta * s * s;
+		Complex::new((beta * rho * s) / denom, t / denom)
+	}
+
+	fn sin(self) -> Self {
+		self.mul_i().sinh().mul_neg_i()
+	}
+
+	fn cos(self) -> Self {
+		self.mul_i().cosh()
+	}
+
+	fn tan(self) -> Self {
+		self.mul_i().tanh().mul_neg_i()
+	}
+
+	fn asinh(self) -> Self {
+		// Compute the result in the first quadrant and restore the signs
+		// afterwards, as asinh is odd and commutes with the conjugation.
+		if self.re.is_nan() && self.im == T::ZERO {
+			return self;
+		}
+
+		let x = self.re.abs();
+		let y = self.im.abs();
+
+		let w = if x < T::SQRT_EPSILON && y < T::SQRT_EPSILON {
+			Complex::new(x, y)
+		} else if x > T::LARGE || y > T::LARGE || x.is_nan() || y.is_nan() {
+			// asinh(z) = log(2 * z) for large z
+			let log = Complex::new(x, y).ln();
+			Complex::new(log.re + T::LN_2, log.im)
+		} else {
+			// asinh(z) = log(z + sqrt(z * z + 1))
+			let root = Complex::new((x - y) * (x + y) + T::ONE, T::TWO * x * y).sqrt();
+			Complex::new(x + root.re, y + root.im).ln()
+		};
+
+		Complex::new(w.re.copysign(self.re), w.im.copysign(self.im))
+	}
+
+	fn asin(self) -> Self {
+		self.mul_i().asinh().mul_neg_i()
+	}
+
+	fn acos(self) -> Self {
+		let Complex { re: x, im: y } = self;
+
+		if x == T::ZERO && y.is_nan() {
+			return Complex::new(T::FRAC_PI_2, y);
+		}
+		if !x.is_finite() || !y.is_finite() || x.abs() > T::LARGE || y.abs() > T::LARGE {
+			let w = self.asin();
+			return Complex::new(T::FRAC_PI_2 - w.re, -w.im);
+		}
+
+		// Kahan's formulas, which stay accurate near the branch points.
+		let a = Complex::new(T::ONE - x, -y).sqrt();
+		let b = Complex::new(T::ONE + x, y).sqrt();
+		let re = T::TWO * a.re.atan2(b.re);
+		let im = (b.re * a.im - b.im * a.re).asinh();
+		Complex::new(re, im)
+	}
+
+	fn acosh(self) -> Self {
+		let w = self.acos();
+		if T::ONE.copysign(self.im) < T::ZERO {
+			w.mul_neg_i()
+		} else {
+			w.mul_i()
+		}
+	}
+
+	fn atanh(self) -> Self {
+		let Complex { re: x, im: y } = self;
+
+		if y.is_infinite() {
+			return Complex::new(T::ZERO.copysign(x), T::FRAC_PI_2.copysign(y));
+		}
+		if x.is_infinite() {
+			let im = if y.is_nan() {
+				y
+			} else {
+				T::FRAC_PI_2.copysign(y)
+			};
+			return Complex::new(T::ZERO.copysign(x), im);
+		}
+		if x == T::ZERO && y.is_nan() {
+			return self;
+		}
+
+		// atanh(z) = (log(1 + z) - log(1 - z)) / 2. The real part is odd in x,
+		// so it is computed for |x| and rearranged to avoid cancellation.
+		let ax = x.abs();
+		let re = if ax < T::HALF {
+			let one_minus_x = T::ONE - ax;
+			T::HALF * T::HALF * (T::TWO * T::TWO * ax / (one_minus_x * one_minus_x + y * y)).ln_1p()
+		} else {
+			T::HALF * ((T::ONE + ax).hypot(y).ln() - (T::ONE - ax).hypot(y).ln())
+		};
+		let im = T::HALF * (T::TWO * y).atan2((T::ONE - x) * (T::ONE + x) - y * y);
+		Complex::new(re.copysign(x), im)
+	}
+
+	fn atan(self) -> Self {
+		self.mul_i().atanh().mul_neg_i()
+	}
+}
+
+macro_rules! export {
+	($(fn $f64:ident, $f32:ident = $method:ident($($arg:ident),*);)+) => {
+		$(
+			#[unsafe(no_mangle)]
+			pub extern "C" fn $f64(z: Complex<f64>$(, $arg: Complex<f64>)*) -> Complex<f64> {
+				z.$method($($arg),*)
+			}
+
+			#[unsafe(no_mangle)]
+			pub extern "C" fn $f32(z: Complex<f32>$(, $arg: Complex<f32>)*) -> Complex<f32> {
+				z.$method($($arg),*)
+			}
+		)+
+	};
+}
+
+export! {
+	fn cacos, cacosf = acos();
+	fn cacosh, cacoshf = acosh();
+	fn casin, casinf = asin();
+	fn casinh, casinhf = asinh();
+	fn catan, catanf = atan();
+	fn catanh, catanhf = atanh();
+	fn ccos, ccosf = cos();
+	fn ccosh, ccoshf = cosh();
+	fn cexp, cexpf = exp();
+	fn clog, clogf = ln();
+	fn cpow, cpowf = pow(c);
+	fn cproj, cprojf = proj();
+	fn csin, csinf = sin();
+	fn csinh, csinhf = sinh();
+	fn csqrt, csqrtf = sqrt();
+	fn ctan, ctanf = tan();
+	fn ctanh, ctanhf = tanh();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cabs(z: Complex<f64>) -> f64 {
+	libm::hypot(z.re, z.im)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cabsf(z: Complex<f32>) -> f32 {
+	libm::hypotf(z.re, z.im)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn carg(z: Complex<f64>) -> f64 {
+	libm::atan2(z.im, z.re)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cargf(z: Complex<f32>) -> f32 {
+	libm::atan2f(z.im, z.re)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn creal(z: Complex<f64>) -> f64 {
+	z.re
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn crealf(z: Complex<f32>) -> f32 {
+	z.re
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cimag(z: Complex<f64>) -> f64 {
+	z.im
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn cimagf(z: Complex<f32>) -> f32 {
+	z.im
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn conj(z: Complex<f64>) -> Complex<f64> {
+	Complex::new(z.re, -z.im)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn conjf(z: Complex<f32>) -> Complex<f32> {
+	Complex::new(z.re, -z.im)
+}
+
+#[cfg(test)]
+mod tests {
+	use core::f64::consts::{FRAC_PI_2, FRAC_PI_4, LN_2, PI};
+
+	use super::*;
+
+	fn assert_close(z: Complex<f64>, re: f64, im: f64) {
+		let close = if re.is_finite() && im.is_finite() {
+			libm::hypot(z.re - re, z.im - im) <= 1e-15 * libm::hypot(re, im)
+		} else {
+			z == Complex::new(re, im)
+		};
+		assert!(close, "{z:?} is not close to {re} + {im}i");
+	}
+
+	#[test]
+	fn elementary() {
+		assert_close(cexp(Complex::new(0.0, PI)), -1.0, 1.2246467991473532e-16);
+		assert_close(cexp(Complex::new(800.0, 0.5)), f64::INFINITY, f64::INFINITY);
+		assert_close(clog(Complex::new(-1.0, 0.0)), 0.0, PI);
+		assert_close(clog(Complex::new(1.0, 1e-8)), 5e-17, 1e-8);
+		assert_close(csqrt(Complex::new(-4.0, 0.0)), 0.0, 2.0);
+		assert_close(csqrt(Complex::new(-4.0, -0.0)), 0.0, -2.0);
+		assert_close(csqrt(Complex::new(3.0, 4.0)), 2.0, 1.0);
+		assert_close(
+			cpow(Complex::new(0.0, 1.0), Complex::new(0.0, 1.0)),
+			0.20787957635076193,
+			0.0,
+		);
+	}
+
+	#[test]
+	fn trigonometric() {
+		assert_close(
+			csin(Complex::new(1.0, 1.0)),
+			1.2984575814159773,
+			0.6349639147847361,
+		);
+		assert_close(
+			ccos(Complex::new(1.0, 1.0)),
+			0.8337300251311491,
+			-0.9888977057628651,
+		);
+		assert_close(
+			ctan(Complex::new(1.0, 1.0)),
+			0.2717525853195117,
+			1.0839233273386946,
+		);
+		assert_close(ctanh(Complex::new(1000.0, 1.0)), 1.0, 0.0);
+		assert_close(ccos(Complex::new(0.0, 1e10)), f64::INFINITY, -0.0);
+	}
+
+	#[test]
+	fn inverse() {
+		assert_close(casin(Complex::new(2.0, 0.0)), FRAC_PI_2, 1.3169578969248166);
+		assert_close(
+			cacos(Complex::new(1.0, 1e-8)),
+			9.999999991666667e-5,
+			-1.0000000008333334e-4,
+		);
+		assert_close(catan(Complex::new(1.0, 0.0)), FRAC_PI_4, 0.0);
+		assert_close(
+			catanh(Complex::new(-1.0, 1e-8)),
+			-9.556913962256155,
+			0.7853981658974483,
+		);
+		assert_close(casinh(Complex::new(1e300, 0.0)), 691.4686750787736, 0.0);
+		assert_close(cacosh(Complex::new(-1.0, 0.0)), 0.0, PI);
+		assert_close(clog(Complex::new(2.0, 0.0)), LN_2, 0.0);
+	}
+
+	#[test]
+	fn special_values() {
+		let proj = cproj(Complex::new(f64::NEG_INFINITY, -1.0));
+		assert_eq!(proj.re, f64::INFINITY);
+		assert!(proj.im == 0.0 && proj.im.is_sign_negative());
+
+		let root = csqrt(Complex::new(f64::NEG_INFINITY, f64::NAN));
+		assert!(root.re.is_nan() && root.im.is_infinite());
+
+		let tanh = ctanh(Complex::new(0.0, f64::INFINITY));
+		assert!(tanh.re == 0.0 && tanh.im.is_nan());
+
+		let acos = cacos(Complex::new(0.0, f64::NAN));
+		assert!(acos.re == FRAC_PI_2 && acos.im.is_nan());
+	}
+}
@@ -0,0 +1,442 @@
+//! `long double` functions for the IEEE 754 binary128 format.
+//!
+//! Functions, which are exact, such as rounding, scaling or the square root,
+//! are implemented on `f128` and its representation. The remaining functions
+//! are computed in double precision.
+
+use core::ffi::{c_char, c_int, c_long, c_longlong};
+
+const SIGN: u128 = 1 << 127;
+const EXPONENT_BIAS: i32 = 0x3fff;
+const EXPONENT_MAX: u32 = 0x7fff;
+const SIGNIFICAND_BITS: u32 = 112;
+
+/// Functions `long double f(long double, ...)` computed by `double f(double, ...)`
+macro_rules! via_double {
+	($(fn $name:ident($($arg:ident),+) = $f:ident;)+) => {
+		$(
+			#[unsafe(no_mangle)]
+			pub extern "C" fn $name($($arg: f128),+) -> f128 {
+				super::$f($($arg as f64),+) as f128
+			}
+		)+
+	};
+}
+
+via_double! {
+	fn acosl(x) = acos;
+	fn acoshl(x) = acosh;
+	fn asinl(x) = asin;
+	fn asinhl(x) = asinh;
+	fn atanl(x) = atan;
+	fn atan2l(y, x) = atan2;
+	fn atanhl(x) = atanh;
+	fn cbrtl(x) = cbrt;
+	fn cosl(x) = cos;
+	fn coshl(x) = cosh;
+	fn erfl(x) = erf;
+	fn erfcl(x) = erfc;
+	fn expl(x) = exp;
+	fn exp2l(x) = exp2;
+	fn expm1l(x) = expm1;
+	fn fmal(x, y, z) = fma;
+	fn fmodl(x, y) = fmod;
+	fn hypotl(x, y) = hypot;
+	fn lgammal(x) = lgamma;
+	fn logl(x) = log;
+	fn log10l(x) = log10;
+	fn log1pl(x) = log1p;
+	fn log2l(x) = log2;
+	fn powl(x, y) = pow;
+	fn remainderl(x, y) = remainder;
+	fn sinl(x) = sin;
+	fn sinhl(x) = sinh;
+	fn tanl(x) = tan;
+	fn tanhl(x) = tanh;
+	fn tgammal(x) = tgamma;
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn remquol(x: f128, y: f128, quo: &mut c_int) -> f128 {
+	super::remquo(x as f64, y as f64, quo) as f128
+}
+
+/// Returns a quiet NaN. The payload `tag` is ignored.
+#[unsafe(no_mangle)]
+pub extern "C" fn nanl(_tag: *const c_char) -> f128 {
+	f128::NAN
+}
+
+/// Returns the biased exponent of `x`.
+fn biased_exponent(x: f128) -> u32 {
+	(x.to_bits() >> SIGNIFICAND_BITS) as u32 & EXPONENT_MAX
+}
+
+/// Returns `2^n` for `n` in the range of normal numbers.
+fn pow2(n: i32) -> f128 {
+	f128::from_bits(((EXPONENT_BIAS + n) as u128) << SIGNIFICAND_BITS)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fabsl(x: f128) -> f128 {
+	f128::from_bits(x.to_bits() & !SIGN)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn copysignl(x: f128, y: f128) -> f128 {
+	f128::from_bits((x.to_bits() & !SIGN) | (y.to_bits() & SIGN))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fmaxl(x: f128, y: f128) -> f128 {
+	if x.is_nan() || x < y { y } else { x }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fminl(x: f128, y: f128) -> f128 {
+	if x.is_nan() || x > y { y } else { x }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn fdiml(x: f128, y: f128) -> f128 {
+	if x.is_nan() || y.is_nan() {
+		x + y
+	} else if x > y {
+		x - y
+	} else {
+		0.0
+	}
+}
+
+/// Returns `y` with the sign of `x`, if `y` is zero.
+fn keep_zero_sign(y: f128, x: f128) -> f128 {
+	if y == 0.0 { copysignl(y, x) } else { y }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn rintl(x: f128) -> f128 {
+	// Numbers from 2^112 on are integers. Below, adding and subtracting
+	// 2^112 rounds away the fraction.
+	if biased_exponent(x) >= EXPONENT_BIAS as u32 + SIGNIFICAND_BITS {
+		return x;
+	}
+
+	let to_int = pow2(SIGNIFICAND_BITS as i32);
+	let y = if x.is_sign_negative() {
+		x - to_int + to_int
+	} else {
+		x + to_int - to_int
+	};
+	keep_zero_sign(y, x)
+}
+
+// The floating-point environment is not supported, so `nearbyintl` and
+// `rintl` are the same function.
+#[unsafe(no_mangle)]
+pub extern "C" fn nearbyintl(x: f128) -> f128 {
+	rintl(x)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn floorl(x: f128) -> f128 {
+	let y = rintl(x);
+	keep_zero_sign(if y > x { y - 1.0 } else { y }, x)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ceill(x: f128) -> f128 {
+	let y = rintl(x);
+	keep_zero_sign(if y < x { y + 1.0 } else { y }, x)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn truncl(x: f128) -> f128 {
+	if x.is_sign_negative() {
+		ceill(x)
+	} else {
+		floorl(x)
+	}
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn roundl(x: f128) -> f128 {
+	let t = truncl(x);
+	if fabsl(x - t) >= 0.5 {
+		t + copysignl(1.0, x)
+	} else {
+		t
+	}
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn lrintl(x: f128) -> c_long {
+	rintl(x) as c_long
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn llrintl(x: f128) -> c_longlong {
+	rintl(x) as c_longlong
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn lroundl(x: f128) -> c_long {
+	roundl(x) as c_long
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn llroundl(x: f128) -> c_longlong {
+	roundl(x) as c_longlong
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn scalbnl(x: f128, n: c_int) -> f128 {
+	const MIN: i32 = 1 - EXPONENT_BIAS;
+	const MAX: i32 = EXPONENT_BIAS;
+
+	// Scale in up to three steps, so that intermediate results neither
+	// overflow nor underflow prematurely.
+	let mut x = x;
+	let mut n = n;
+	for _ in 0..2 {
+		if n > MAX {
+			x *= pow2(MAX);
+			n -= MAX;
+		} else if n < MIN {
+			x *= pow2(MIN) * pow2(SIGNIFICAND_BITS as i32 + 1);
+			n -= MIN + SIGNIFICAND_BITS as i32 + 1;
+		}
+	}
+	x * pow2(n.clamp(MIN, MAX))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ldexpl(x: f128, n: c_int) -> f128 {
+	scalbnl(x, n)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn scalblnl(x: f128, n: c_long) -> f128 {
+	scalbnl(x, n.clamp(c_int::MIN.into(), c_int::MAX.into()) as c_int)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ilogbl(x: f128) -> c_int {
+	const FP_ILOGB0: c_int = c_int::MIN;
+	const FP_ILOGBNAN: c_int = c_int::MIN;
+
+	match biased_exponent(x) {
+		0 => {
+			let significand = x.to_bits() & ((1 << SIGNIFICAND_BITS) - 1);
+			if significand == 0 {
+				FP_ILOGB0
+			} else {
+				let top = 127 - significand.leading_zeros() as i32;
+				top - SIGNIFICAND_BITS as i32 + 1 - EXPONENT_BIAS
+			}
+		}
+		EXPONENT_MAX if x.is_nan() => FP_ILOGBNAN,
+		EXPONENT_MAX => c_int::MAX,
+		exponent => exponent as i32 - EXPONENT_BIAS,
+	}
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn logbl(x: f128) -> f128 {
+	if x == 0.0 {
+		-f128::INFINITY
+	} else if !x.is_finite() {
+		x * x
+	} else {
+		ilogbl(x) as f128
+	}
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn frexpl(x: f128, e: &mut c_int) -> f128 {
+	match biased_exponent(x) {
+		0 if x == 0.0 => {
+			*e = 0;
+			x
+		}
+		0 => {
+			let y = frexpl(x * pow2(120), e);
+			*e -= 120;
+			y
+		}
+		EXPONENT_MAX => {
+			*e = 0;
+			x
+		}
+		exponent => {
+			*e = exponent as i32 - (EXPONENT_BIAS - 1);
+			let bits = x.to_bits() & !((EXPONENT_MAX as u128) << SIGNIFICAND_BITS);
+			f128::from_bits(bits | (((EXPONENT_BIAS - 1) as u128) << SIGNIFICAND_BITS))
+		}
+	}
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn modfl(x: f128, i: &mut f128) -> f128 {
+	let t = truncl(x);
+	*i = t;
+	if x.is_infinite() {
+		copysignl(0.0, x)
+	} else {
+		copysignl(x - t, x)
+	}
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn sqrtl(x: f128) -> f128 {
+	if x.is_nan() || x == 0.0 || x == f128::INFINITY {
+		return x;
+	}
+	if x < 0.0 {
+		return f128::NAN;
+	}
+
+	if biased_exponent(x) == 0 {
+		// Normalize subnormal numbers by an even power of two.
+		return sqrtl(x * pow2(120)) * pow2(-60);
+	}
+
+	// Make the exponent even and compute the root of the significand bit by
+	// bit, with one more bit for rounding.
+	let mut e = biased_exponent(x) as i32 - EXPONENT_BIAS;
+	let mut m = (x.to_bits() & ((1 << SIGNIFICAND_BITS) - 1)) | (1 << SIGNIFICAND_BITS);
+	if e & 1 != 0 {
+		m <<= 1;
+	}
+	e >>= 1;
+	m <<= 1;
+
+	let mut q = 0u128;
+	let mut s = 0u128;
+	let mut r = 1u128 << (SIGNIFICAND_BITS + 1);
+	while r != 0 {
+		let t = s + r;
+		if t <= m {
+			s = t + r;
+			m -= t;
+			q += r;
+		}
+		m <<= 1;
+		r >>= 1;
+	}
+
+	// The root is never exactly halfway, so a nonzero remainder decides.
+	if m != 0 {
+		q += q & 1;
+	}
+	f128::from_bits((q >> 1) + (((EXPONENT_BIAS - 1 + e) as u128) << SIGNIFICAND_BITS))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn nextafterl(x: f128, y: f128) -> f128 {
+	if x.is_nan() || y.is_nan() {
+		return x + y;
+	}
+	if x == y {
+		return y;
+	}
+	if x == 0.0 {
+		return copysignl(f128::from_bits(1), y);
+	}
+
+	let bits = x.to_bits();
+	if (y > x) == (x > 0.0) {
+		f128::from_bits(bits + 1)
+	} else {
+		f128::from_bits(bits - 1)
+	}
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn nexttowardl(x: f128, y: f128) -> f128 {
+	nextafterl(x, y)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn nexttoward(x: f64, y: f128) -> f64 {
+	if x.is_nan() || y.is_nan() {
+		f64::NAN
+	} else if (x as f128) < y {
+		libm::nextafter(x, f64::INFINITY)
+	} else if (x as f128) > y {
+		libm::nextafter(x, -f64::INFINITY)
+	} else {
+		y as f64
+	}
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn nexttowardf(x: f32, y: f128) -> f32 {
+	if x.is_nan() || y.is_nan() {
+		f32::NAN
+	} else if (x as f128) < y {
+		libm::nextafterf(x, f32::INFINITY)
+	} else if (x as f128) > y {
+		libm::nextafterf(x, -f32::INFINITY)
+	} else {
+		y as f32
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rounding() {
+		assert_eq!(rintl(2.5), 2.0);
+		assert_eq!(rintl(-3.5), -4.0);
+		assert!(rintl(-0.25).is_sign_negative());
+		assert_eq!(floorl(-0.5), -1.0);
+		assert_eq!(ceill(-0.5).to_bits(), (-0.0f128).to_bits());
+		assert_eq!(truncl(-7.75), -7.0);
+		assert_eq!(roundl(2.5), 3.0);
+		assert_eq!(roundl(nextafterl(0.5, 0.0)), 0.0);
+		assert_eq!(llroundl(-2.5), -3);
+		assert_eq!(lrintl(9007199254740993.0), 9007199254740993);
+	}
+
+	#[test]
+	fn exponents() {
+		let min_subnormal = f128::from_bits(1);
+		assert_eq!(ilogbl(min_subnormal), -16494);
+		assert_eq!(logbl(0.75), -1.0);
+		assert_eq!(scalbnl(1.0, -16494), min_subnormal);
+		assert_eq!(scalbnl(min_subnormal, 32000), pow2(32000 - 16494));
+		assert_eq!(scalbnl(1.0, 16384), f128::INFINITY);
+
+		let mut e = 0;
+		assert_eq!(frexpl(min_subnormal * 3.0, &mut e), 0.75);
+		assert_eq!(e, -16492);
+
+		let mut i = 0.0;
+		assert_eq!(modfl(-7.75, &mut i), -0.75);
+		assert_eq!(i, -7.0);
+	}
+
+	#[test]
+	fn square_root() {
+		assert_eq!(sqrtl(2.0).to_bits(), 0x3fff6a09e667f3bcc908b2fb1366ea95);
+		assert_eq!(
+			sqrtl(f128::MAX).to_bits(),
+			0x5ffeffffffffffffffffffffffffffff
+		);
+		assert_eq!(sqrtl(f128::from_bits(1)), pow2(-8247));
+		assert!(sqrtl(-1.0).is_nan());
+	}
+
+	#[test]
+	fn neighbors() {
+		assert_eq!(
+			nextafterl(1.0, 2.0).to_bits(),
+			0x3fff0000000000000000000000000001
+		);
+		assert_eq!(nextafterl(0.0, -1.0), -f128::from_bits(1));
+		assert_eq!(nexttoward(1.0, 1.0 + f128::EPSILON), 1.0 + f64::EPSILON);
+		assert_eq!(nexttowardf(1.0, 0.0), 1.0 - f32::EPSILON / 2.0);
+	}
+}
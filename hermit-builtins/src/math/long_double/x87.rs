@@ -0,0 +1,690 @@
+//! `long double` functions for the x87 extended precision format.
+//!
+//! Rust has no type for the 80-bit format, which is passed on the stack and
+//! returned in `st(0)`, so the functions are written in assembly.
+//! Functions, which x87 computes exactly, such as rounding or the remainder,
+//! use the corresponding instructions. The transcendental functions are
+//! computed in double precision.
+
+use core::arch::global_asm;
+use core::cmp::Ordering;
+
+macro_rules! function {
+	($name:ident, [$($line:literal),+ $(,)?] $(, $($operands:tt)*)?) => {
+		global_asm!(
+			concat!(".pushsection .text.", stringify!($name), ",\"ax\",@progbits"),
+			concat!(".globl ", stringify!($name)),
+			concat!(".type ", stringify!($name), ", @function"),
+			".p2align 4",
+			concat!(stringify!($name), ":"),
+			$($line,)+
+			concat!(".size ", stringify!($name), ", . - ", stringify!($name)),
+			".popsection",
+			$($($operands)*)?
+		);
+	};
+}
+
+/// Functions `long double f(long double)` computed by `double f(double)`
+///
+/// Trailing integer or pointer arguments are passed in registers, which are
+/// left untouched, so these are forwarded as well.
+macro_rules! via_double {
+	($($name:ident = $f:ident;)+) => {
+		$(
+			function!(
+				$name,
+				[
+					"sub rsp, 24",
+					"fld tbyte ptr [rsp + 32]",
+					"fstp qword ptr [rsp]",
+					"movsd xmm0, qword ptr [rsp]",
+					"call {f}",
+					"movsd qword ptr [rsp], xmm0",
+					"fld qword ptr [rsp]",
+					"add rsp, 24",
+					"ret",
+				],
+				f = sym super::$f,
+			);
+		)+
+	};
+}
+
+/// Functions `long double f(long double, long double)` computed by `double f(double, double)`
+macro_rules! via_double2 {
+	($($name:ident = $f:ident;)+) => {
+		$(
+			function!(
+				$name,
+				[
+					"sub rsp, 24",
+					"fld tbyte ptr [rsp + 32]",
+					"fstp qword ptr [rsp]",
+					"fld tbyte ptr [rsp + 48]",
+					"fstp qword ptr [rsp + 8]",
+					"movsd xmm0, qword ptr [rsp]",
+					"movsd xmm1, qword ptr [rsp + 8]",
+					"call {f}",
+					"movsd qword ptr [rsp], xmm0",
+					"fld qword ptr [rsp]",
+					"add rsp, 24",
+					"ret",
+				],
+				f = sym super::$f,
+			);
+		)+
+	};
+}
+
+via_double! {
+	acosl = acos;
+	acoshl = acosh;
+	asinl = asin;
+	asinhl = asinh;
+	atanl = atan;
+	atanhl = atanh;
+	cbrtl = cbrt;
+	cosl = cos;
+	coshl = cosh;
+	erfl = erf;
+	erfcl = erfc;
+	expl = exp;
+	exp2l = exp2;
+	expm1l = expm1;
+	lgammal = lgamma;
+	logl = log;
+	log10l = log10;
+	log1pl = log1p;
+	log2l = log2;
+	sinl = sin;
+	sinhl = sinh;
+	tanl = tan;
+	tanhl = tanh;
+	tgammal = tgamma;
+}
+
+via_double2! {
+	atan2l = atan2;
+	hypotl = hypot;
+	powl = pow;
+}
+
+function!(
+	fmal,
+	[
+		"sub rsp, 40",
+		"fld tbyte ptr [rsp + 48]",
+		"fstp qword ptr [rsp]",
+		"fld tbyte ptr [rsp + 64]",
+		"fstp qword ptr [rsp + 8]",
+		"fld tbyte ptr [rsp + 80]",
+		"fstp qword ptr [rsp + 16]",
+		"movsd xmm0, qword ptr [rsp]",
+		"movsd xmm1, qword ptr [rsp + 8]",
+		"movsd xmm2, qword ptr [rsp + 16]",
+		"call {f}",
+		"movsd qword ptr [rsp], xmm0",
+		"fld qword ptr [rsp]",
+		"add rsp, 40",
+		"ret",
+	],
+	f = sym super::fma,
+);
+
+function!(
+	nanl,
+	[
+		"sub rsp, 24",
+		"call {f}",
+		"movsd qword ptr [rsp], xmm0",
+		"fld qword ptr [rsp]",
+		"add rsp, 24",
+		"ret",
+	],
+	f = sym super::nan,
+);
+
+function!(fabsl, ["fld tbyte ptr [rsp + 8]", "fabs", "ret"]);
+
+function!(copysignl, [
+	"fld tbyte ptr [rsp + 8]",
+	"fabs",
+	"test byte ptr [rsp + 33], 0x80",
+	"jz 2f",
+	"fchs",
+	"2:",
+	"ret",
+]);
+
+function!(sqrtl, ["fld tbyte ptr [rsp + 8]", "fsqrt", "ret"]);
+
+function!(rintl, ["fld tbyte ptr [rsp + 8]", "frndint", "ret"]);
+
+// The floating-point environment is not supported, so `nearbyintl` and
+// `rintl` are the same function.
+function!(nearbyintl, ["fld tbyte ptr [rsp + 8]", "frndint", "ret"]);
+
+/// Functions rounding with the rounding control `$mode`
+macro_rules! round_with {
+	($($name:ident = $mode:literal;)+) => {
+		$(
+			function!(
+				$name,
+				[
+					"sub rsp, 24",
+					"fnstcw word ptr [rsp]",
+					"movzx eax, word ptr [rsp]",
+					"and eax, 0xf3ff",
+					"or eax, {mode}",
+					"mov word ptr [rsp + 2], ax",
+					"fld tbyte ptr [rsp + 32]",
+					"fldcw word ptr [rsp + 2]",
+					"frndint",
+					"fldcw word ptr [rsp]",
+					"add rsp, 24",
+					"ret",
+				],
+				mode = const $mode,
+			);
+		)+
+	};
+}
+
+round_with! {
+	floorl = 0x0400;
+	ceill = 0x0800;
+	truncl = 0x0c00;
+}
+
+// Rounds half away from zero by comparing the truncated fraction with 1/2.
+function!(roundl, [
+	"sub rsp, 24",
+	"fnstcw word ptr [rsp]",
+	"movzx eax, word ptr [rsp]",
+	"or eax, 0x0c00",
+	"mov word ptr [rsp + 2], ax",
+	"fld tbyte ptr [rsp + 32]",
+	"fld st(0)",
+	"fldcw word ptr [rsp + 2]",
+	"frndint",
+	"fldcw word ptr [rsp]",
+	"fxch st(1)",
+	"fsub st(0), st(1)",
+	"fabs",
+	"fadd st(0), st(0)",
+	"fld1",
+	"fcomip st, st(1)",
+	"fstp st(0)",
+	"ja 3f",
+	"fld1",
+	"test byte ptr [rsp + 41], 0x80",
+	"jz 2f",
+	"fchs",
+	"2:",
+	"faddp st(1), st(0)",
+	"3:",
+	"add rsp, 24",
+	"ret",
+]);
+
+function!(lrintl, [
+	"sub rsp, 24",
+	"fld tbyte ptr [rsp + 32]",
+	"fistp qword ptr [rsp]",
+	"mov rax, qword ptr [rsp]",
+	"add rsp, 24",
+	"ret",
+]);
+
+function!(llrintl, [
+	"sub rsp, 24",
+	"fld tbyte ptr [rsp + 32]",
+	"fistp qword ptr [rsp]",
+	"mov rax, qword ptr [rsp]",
+	"add rsp, 24",
+	"ret",
+]);
+
+function!(lroundl, [
+	"sub rsp, 24",
+	"fld tbyte ptr [rsp + 32]",
+	"fstp tbyte ptr [rsp]",
+	"call roundl",
+	"fistp qword ptr [rsp]",
+	"mov rax, qword ptr [rsp]",
+	"add rsp, 24",
+	"ret",
+]);
+
+function!(llroundl, [
+	"sub rsp, 24",
+	"fld tbyte ptr [rsp + 32]",
+	"fstp tbyte ptr [rsp]",
+	"call roundl",
+	"fistp qword ptr [rsp]",
+	"mov rax, qword ptr [rsp]",
+	"add rsp, 24",
+	"ret",
+]);
+
+function!(fmodl, [
+	"fld tbyte ptr [rsp + 24]",
+	"fld tbyte ptr [rsp + 8]",
+	"2:",
+	"fprem",
+	"fnstsw ax",
+	"test ah, 4",
+	"jnz 2b",
+	"fstp st(1)",
+	"ret",
+]);
+
+function!(remainderl, [
+	"fld tbyte ptr [rsp + 24]",
+	"fld tbyte ptr [rsp + 8]",
+	"2:",
+	"fprem1",
+	"fnstsw ax",
+	"test ah, 4",
+	"jnz 2b",
+	"fstp st(1)",
+	"ret",
+]);
+
+// After the reduction is complete, C0, C3 and C1 hold the three least
+// significant bits of the quotient.
+function!(remquol, [
+	"fld tbyte ptr [rsp + 24]",
+	"fld tbyte ptr [rsp + 8]",
+	"2:",
+	"fprem1",
+	"fnstsw ax",
+	"test ah, 4",
+	"jnz 2b",
+	"fstp st(1)",
+	"movzx edx, ah",
+	"mov ecx, edx",
+	"and ecx, 1",
+	"shl ecx, 2",
+	"mov esi, edx",
+	"shr esi, 5",
+	"and esi, 2",
+	"or ecx, esi",
+	"shr edx, 1",
+	"and edx, 1",
+	"or ecx, edx",
+	"mov al, byte ptr [rsp + 17]",
+	"xor al, byte ptr [rsp + 33]",
+	"test al, 0x80",
+	"jz 3f",
+	"neg ecx",
+	"3:",
+	"mov dword ptr [rdi], ecx",
+	"ret",
+]);
+
+function!(scalbnl, [
+	"sub rsp, 24",
+	"mov dword ptr [rsp], edi",
+	"fild dword ptr [rsp]",
+	"fld tbyte ptr [rsp + 32]",
+	"fscale",
+	"fstp st(1)",
+	"add rsp, 24",
+	"ret",
+]);
+
+function!(ldexpl, [
+	"sub rsp, 24",
+	"mov dword ptr [rsp], edi",
+	"fild dword ptr [rsp]",
+	"fld tbyte ptr [rsp + 32]",
+	"fscale",
+	"fstp st(1)",
+	"add rsp, 24",
+	"ret",
+]);
+
+function!(scalblnl, [
+	"sub rsp, 24",
+	"mov qword ptr [rsp], rdi",
+	"fild qword ptr [rsp]",
+	"fld tbyte ptr [rsp + 32]",
+	"fscale",
+	"fstp st(1)",
+	"add rsp, 24",
+	"ret",
+]);
+
+// `fxam` classifies the operand in C3, C2 and C0, which are bits 6, 2 and 0
+// of the upper byte of the status word: 0x04 is normal, 0x44 denormal and
+// 0x05 infinite.
+function!(frexpl, [
+	"fld tbyte ptr [rsp + 8]",
+	"fxam",
+	"fnstsw ax",
+	"and ah, 0x45",
+	"cmp ah, 0x04",
+	"je 2f",
+	"cmp ah, 0x44",
+	"je 2f",
+	"mov dword ptr [rdi], 0",
+	"ret",
+	"2:",
+	"fxtract",
+	"fld1",
+	"fadd st(2), st(0)",
+	"fchs",
+	"fxch st(1)",
+	"fscale",
+	"fstp st(1)",
+	"fxch st(1)",
+	"fistp dword ptr [rdi]",
+	"ret",
+]);
+
+function!(logbl, [
+	"fld tbyte ptr [rsp + 8]",
+	"fxtract",
+	"fstp st(0)",
+	"ret",
+]);
+
+// `fistp` stores the integer indefinite value `INT_MIN` for the exponent of
+// zero and NaN, only infinity has to be handled separately.
+function!(ilogbl, [
+	"sub rsp, 24",
+	"fld tbyte ptr [rsp + 32]",
+	"fxam",
+	"fnstsw ax",
+	"and ah, 0x45",
+	"cmp ah, 0x05",
+	"jne 2f",
+	"fstp st(0)",
+	"mov eax, 0x7fffffff",
+	"add rsp, 24",
+	"ret",
+	"2:",
+	"fxtract",
+	"fstp st(0)",
+	"fistp dword ptr [rsp]",
+	"mov eax, dword ptr [rsp]",
+	"add rsp, 24",
+	"ret",
+]);
+
+// The fraction is `|x - trunc(x)|` with the sign of `x`, and zero for infinite `x`.
+function!(modfl, [
+	"sub rsp, 24",
+	"fnstcw word ptr [rsp]",
+	"movzx eax, word ptr [rsp]",
+	"or eax, 0x0c00",
+	"mov word ptr [rsp + 2], ax",
+	"fld tbyte ptr [rsp + 32]",
+	"fld st(0)",
+	"fldcw word ptr [rsp + 2]",
+	"frndint",
+	"fldcw word ptr [rsp]",
+	"fld st(0)",
+	"fstp tbyte ptr [rdi]",
+	"fxch st(1)",
+	"fsub st(0), st(1)",
+	"fstp st(1)",
+	"fabs",
+	"movzx eax, word ptr [rsp + 40]",
+	"and eax, 0x7fff",
+	"cmp eax, 0x7fff",
+	"jne 2f",
+	"mov rax, qword ptr [rsp + 32]",
+	"shl rax, 1",
+	"jnz 2f",
+	"fstp st(0)",
+	"fldz",
+	"2:",
+	"test byte ptr [rsp + 41], 0x80",
+	"jz 3f",
+	"fchs",
+	"3:",
+	"add rsp, 24",
+	"ret",
+]);
+
+function!(fmaxl, [
+	"fld tbyte ptr [rsp + 24]",
+	"fld tbyte ptr [rsp + 8]",
+	"fucomi st, st(0)",
+	"jp 2f",
+	"fucomi st, st(1)",
+	"jp 3f",
+	"jae 3f",
+	"2:",
+	"fstp st(0)",
+	"ret",
+	"3:",
+	"fstp st(1)",
+	"ret",
+]);
+
+function!(fminl, [
+	"fld tbyte ptr [rsp + 24]",
+	"fld tbyte ptr [rsp + 8]",
+	"fucomi st, st(0)",
+	"jp 2f",
+	"fucomi st, st(1)",
+	"jp 3f",
+	"jbe 3f",
+	"2:",
+	"fstp st(0)",
+	"ret",
+	"3:",
+	"fstp st(1)",
+	"ret",
+]);
+
+function!(fdiml, [
+	"fld tbyte ptr [rsp + 24]",
+	"fld tbyte ptr [rsp + 8]",
+	"fucomi st, st(1)",
+	"jp 3f",
+	"ja 2f",
+	"fstp st(0)",
+	"fstp st(0)",
+	"fldz",
+	"ret",
+	"2:",
+	"fsub st(0), st(1)",
+	"fstp st(1)",
+	"ret",
+	"3:",
+	"fadd st(0), st(1)",
+	"fstp st(1)",
+	"ret",
+]);
+
+function!(
+	nextafterl,
+	[
+		"sub rsp, 24",
+		"lea rdi, [rsp + 32]",
+		"lea rsi, [rsp + 48]",
+		"call {f}",
+		"fld tbyte ptr [rsp + 32]",
+		"add rsp, 24",
+		"ret",
+	],
+	f = sym next_after,
+);
+
+function!(
+	nexttowardl,
+	[
+		"sub rsp, 24",
+		"lea rdi, [rsp + 32]",
+		"lea rsi, [rsp + 48]",
+		"call {f}",
+		"fld tbyte ptr [rsp + 32]",
+		"add rsp, 24",
+		"ret",
+	],
+	f = sym next_after,
+);
+
+function!(
+	nexttoward,
+	[
+		"lea rdi, [rsp + 8]",
+		"jmp {f}",
+	],
+	f = sym next_toward,
+);
+
+function!(
+	nexttowardf,
+	[
+		"lea rdi, [rsp + 8]",
+		"jmp {f}",
+	],
+	f = sym next_toward_f32,
+);
+
+/// The memory representation of an x87 extended precision number
+///
+/// Unlike in the other formats, the integer bit of the significand is explicit.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Extended {
+	significand: u64,
+	sign_exponent: u16,
+}
+
+impl Extended {
+	const INTEGER_BIT: u64 = 1 << 63;
+	const SIGN: u16 = 1 << 15;
+
+	fn from_f64(x: f64) -> Self {
+		let bits = x.to_bits();
+		let sign = if bits >> 63 != 0 { Self::SIGN } else { 0 };
+		let exponent = ((bits >> 52) & 0x7ff) as u16;
+		let fraction = bits & ((1 << 52) - 1);
+
+		let (significand, exponent) = match exponent {
+			0 if fraction == 0 => (0, 0),
+			0 => {
+				let shift = fraction.leading_zeros() as u16;
+				(fraction << shift, 16383 - 1011 - shift)
+			}
+			0x7ff => (Self::INTEGER_BIT | (fraction << 11), 0x7fff),
+			_ => (
+				Self::INTEGER_BIT | (fraction << 11),
+				exponent + (16383 - 1023),
+			),
+		};
+
+		Self {
+			significand,
+			sign_exponent: sign | exponent,
+		}
+	}
+
+	fn exponent(self) -> u16 {
+		self.sign_exponent & !Self::SIGN
+	}
+
+	fn is_sign_negative(self) -> bool {
+		self.sign_exponent & Self::SIGN != 0
+	}
+
+	fn is_nan(self) -> bool {
+		self.exponent() == 0x7fff && self.significand << 1 != 0
+	}
+
+	fn is_zero(self) -> bool {
+		self.exponent() == 0 && self.significand == 0
+	}
+
+	/// Orders numbers, which are not NaN, by their value.
+	fn key(self) -> i128 {
+		let magnitude = (i128::from(self.exponent()) << 64) | i128::from(self.significand);
+		if self.is_sign_negative() {
+			-magnitude
+		} else {
+			magnitude
+		}
+	}
+
+	/// Returns the adjacent number with a larger magnitude.
+	fn step_up(&mut self) {
+		let (significand, overflow) = self.significand.overflowing_add(1);
+		if overflow {
+			self.significand = Self::INTEGER_BIT;
+			self.sign_exponent += 1;
+		} else {
+			self.significand = significand;
+			// A denormal, which carries into the integer bit, becomes normal.
+			if self.exponent() == 0 && significand & Self::INTEGER_BIT != 0 {
+				self.sign_exponent += 1;
+			}
+		}
+	}
+
+	/// Returns the adjacent number with a smaller magnitude.
+	fn step_down(&mut self) {
+		if self.exponent() >= 1 && self.significand == Self::INTEGER_BIT {
+			self.sign_exponent -= 1;
+			self.significand = if self.exponent() == 0 {
+				Self::INTEGER_BIT - 1
+			} else {
+				u64::MAX
+			};
+		} else {
+			self.significand -= 1;
+		}
+	}
+}
+
+/// Replaces `x` by the next number after `x` in the direction of `y`.
+extern "C" fn next_after(x: &mut Extended, y: &Extended) {
+	if x.is_nan() {
+		return;
+	}
+	if y.is_nan() || x.key() == y.key() {
+		*x = *y;
+		return;
+	}
+
+	if x.is_zero() {
+		*x = Extended {
+			significand: 1,
+			sign_exponent: y.sign_exponent & Extended::SIGN,
+		};
+	} else if (y.key() > x.key()) != x.is_sign_negative() {
+		x.step_up();
+	} else {
+		x.step_down();
+	}
+}
+
+extern "C" fn next_toward(x: f64, y: &Extended) -> f64 {
+	if x.is_nan() || y.is_nan() {
+		return f64::NAN;
+	}
+
+	match Extended::from_f64(x).key().cmp(&y.key()) {
+		Ordering::Less => libm::nextafter(x, f64::INFINITY),
+		Ordering::Greater => libm::nextafter(x, -f64::INFINITY),
+		Ordering::Equal => libm::copysign(x, if y.is_sign_negative() { -1.0 } else { 1.0 }),
+	}
+}
+
+extern "C" fn next_toward_f32(x: f32, y: &Extended) -> f32 {
+	if x.is_nan() || y.is_nan() {
+		return f32::NAN;
+	}
+
+	match Extended::from_f64(x.into()).key().cmp(&y.key()) {
+		Ordering::Less => libm::nextafterf(x, f32::INFINITY),
+		Ordering::Greater => libm::nextafterf(x, -f32::INFINITY),
+		Ordering::Equal => libm::copysignf(x, if y.is_sign_negative() { -1.0 } else { 1.0 }),
+	}
+}
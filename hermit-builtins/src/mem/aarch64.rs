@@ -0,0 +1,105 @@
+//! The target requires aligned accesses for general-purpose registers, so
+//! all copies use the byte-element NEON loads and stores, which only require
+//! byte alignment.
+
+use core::arch::aarch64::*;
+
+/// Copies `n <= 32` bytes.
+///
+/// All bytes are loaded before the first store, so the areas may overlap.
+#[inline(always)]
+unsafe fn copy_small(dest: *mut u8, src: *const u8, n: usize) {
+	unsafe {
+		if n >= 16 {
+			let head = vld1q_u8(src);
+			let tail = vld1q_u8(src.add(n - 16));
+			vst1q_u8(dest, head);
+			vst1q_u8(dest.add(n - 16), tail);
+		} else if n >= 8 {
+			let head = vld1_u8(src);
+			let tail = vld1_u8(src.add(n - 8));
+			vst1_u8(dest, head);
+			vst1_u8(dest.add(n - 8), tail);
+		} else if dest.cast_const() < src {
+			for i in 0..n {
+				*dest.add(i) = *src.add(i);
+			}
+		} else {
+			for i in (0..n).rev() {
+				*dest.add(i) = *src.add(i);
+			}
+		}
+	}
+}
+
+pub unsafe fn copy_forward(dest: *mut u8, src: *const u8, n: usize) {
+	if n <= 32 {
+		return unsafe { copy_small(dest, src, n) };
+	}
+
+	// The last 32 bytes are stored after the loops, so that these don't
+	// have to handle a remainder.
+	unsafe {
+		let tail = vld1q_u8_x2(src.add(n - 32));
+		let mut i = 0;
+		while n - i > 64 {
+			let v = vld1q_u8_x4(src.add(i));
+			vst1q_u8_x4(dest.add(i), v);
+			i += 64;
+		}
+		while n - i > 32 {
+			let v = vld1q_u8(src.add(i));
+			vst1q_u8(dest.add(i), v);
+			i += 16;
+		}
+		vst1q_u8_x2(dest.add(n - 32), tail);
+	}
+}
+
+pub unsafe fn copy_backward(dest: *mut u8, src: *const u8, n: usize) {
+	if n <= 32 {
+		return unsafe { copy_small(dest, src, n) };
+	}
+
+	unsafe {
+		let head = vld1q_u8_x2(src);
+		let mut end = n;
+		while end > 64 + 32 {
+			let v = vld1q_u8_x4(src.add(end - 64));
+			vst1q_u8_x4(dest.add(end - 64), v);
+			end -= 64;
+		}
+		while end > 32 {
+			let v = vld1q_u8(src.add(end - 16));
+			vst1q_u8(dest.add(end - 16), v);
+			end -= 16;
+		}
+		vst1q_u8_x2(dest, head);
+	}
+}
+
+pub unsafe fn set(s: *mut u8, c: u8, n: usize) {
+	unsafe {
+		if n >= 16 {
+			let value = vdupq_n_u8(c);
+			let mut i = 0;
+			while n - i > 64 {
+				vst1q_u8_x4(s.add(i), uint8x16x4_t(value, value, value, value));
+				i += 64;
+			}
+			while n - i > 16 {
+				vst1q_u8(s.add(i), value);
+				i += 16;
+			}
+			vst1q_u8(s.add(n - 16), value);
+		} else if n >= 8 {
+			let value = vdup_n_u8(c);
+			vst1_u8(s, value);
+			vst1_u8(s.add(n - 8), value);
+		} else {
+			for i in 0..n {
+				*s.add(i) = c;
+			}
+		}
+	}
+}
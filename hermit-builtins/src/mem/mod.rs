@@ -0,0 +1,145 @@
+//! Memory functions tuned for the processor
+//!
+//! The generic implementations of `compiler_builtins` copy memory in small
+//! units on some targets. These implementations replace them:
+//!
+//! - x86-64 uses `rep movsb`/`rep stosb` for large sizes, if the processor
+//!   supports enhanced `rep movsb`/`stosb` (ERMS), and AVX2 or SSE2 loops
+//!   otherwise. The features are detected on first use.
+//! - AArch64 uses NEON, which is part of the baseline of the target.
+//! - RISC-V uses unrolled loops of doublewords.
+//!
+//! The functions are only exported outside of tests, so that the tests on the
+//! host do not replace the functions of its C library.
+
+#[cfg_attr(target_arch = "aarch64", path = "aarch64.rs")]
+#[cfg_attr(target_arch = "riscv64", path = "riscv64.rs")]
+#[cfg_attr(target_arch = "x86_64", path = "x86_64.rs")]
+mod arch;
+
+/// Copies `n` bytes from `src` to `dest`.
+///
+/// # Safety
+///
+/// `src` must be valid for reads and `dest` valid for writes of `n` bytes.
+/// The areas must not overlap.
+#[cfg_attr(not(test), unsafe(no_mangle))]
+pub unsafe extern "C" fn memcpy(dest: *mut u8, src: *const u8, n: usize) -> *mut u8 {
+	unsafe {
+		arch::copy_forward(dest, src, n);
+	}
+	dest
+}
+
+/// Copies `n` bytes from `src` to `dest`.
+///
+/// # Safety
+///
+/// `src` must be valid for reads and `dest` valid for writes of `n` bytes.
+/// The areas may overlap.
+#[cfg_attr(not(test), unsafe(no_mangle))]
+pub unsafe extern "C" fn memmove(dest: *mut u8, src: *const u8, n: usize) -> *mut u8 {
+	// Copying forwards is only wrong, if `dest` lies within the source area.
+	if (dest as usize).wrapping_sub(src as usize) >= n {
+		unsafe {
+			arch::copy_forward(dest, src, n);
+		}
+	} else {
+		unsafe {
+			arch::copy_backward(dest, src, n);
+		}
+	}
+	dest
+}
+
+/// Sets `n` bytes at `s` to `c`.
+///
+/// # Safety
+///
+/// `s` must be valid for writes of `n` bytes.
+#[cfg_attr(not(test), unsafe(no_mangle))]
+pub unsafe extern "C" fn memset(s: *mut u8, c: i32, n: usize) -> *mut u8 {
+	unsafe {
+		arch::set(s, c as u8, n);
+	}
+	s
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Maximum misalignment of the pointers
+	const ALIGNS: usize = 16;
+
+	/// Returns the sizes around the widths of the unrolled loops and vectors
+	/// and around the threshold of `rep movsb`/`stosb` on x86-64.
+	fn sizes() -> impl Iterator<Item = usize> {
+		(0..=33).chain(60..=68).chain(124..=132).chain(2040..=2056)
+	}
+
+	fn pattern(len: usize) -> Vec<u8> {
+		(0..len).map(|i| (i * 7 + 3) as u8).collect()
+	}
+
+	#[test]
+	fn copy_misaligned() {
+		for n in sizes() {
+			for src_offset in 0..ALIGNS {
+				for dest_offset in 0..ALIGNS {
+					let src = pattern(n + ALIGNS);
+					let mut dest = vec![0xff; n + 2 * ALIGNS];
+					let mut expected = dest.clone();
+					expected[dest_offset..dest_offset + n]
+						.copy_from_slice(&src[src_offset..src_offset + n]);
+
+					let dest_ptr = unsafe { dest.as_mut_ptr().add(dest_offset) };
+					let ret = unsafe { memcpy(dest_ptr, src.as_ptr().add(src_offset), n) };
+					assert_eq!(ret, dest_ptr);
+					assert_eq!(
+						dest, expected,
+						"n = {n}, src = {src_offset}, dest = {dest_offset}"
+					);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn move_overlapping() {
+		for n in sizes() {
+			for offset in 0..ALIGNS {
+				for shift in [1, 3, 8, 15, 16, 17, 32, 64] {
+					// the destination lies below and above the source, respectively
+					for (src, dest) in [(offset + shift, offset), (offset, offset + shift)] {
+						let mut buf = pattern(n + shift + ALIGNS);
+						let mut expected = buf.clone();
+						expected.copy_within(src..src + n, dest);
+
+						let ptr = buf.as_mut_ptr();
+						let ret = unsafe { memmove(ptr.add(dest), ptr.add(src), n) };
+						assert_eq!(ret, unsafe { ptr.add(dest) });
+						assert_eq!(buf, expected, "n = {n}, src = {src}, dest = {dest}");
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn set_misaligned() {
+		for n in sizes() {
+			for offset in 0..ALIGNS {
+				let mut buf = pattern(n + 2 * ALIGNS);
+				let mut expected = buf.clone();
+				expected[offset..offset + n].fill(0xa5);
+
+				let ptr = unsafe { buf.as_mut_ptr().add(offset) };
+				// only the lowest byte of the value is used
+				let ret = unsafe { memset(ptr, 0x1a5, n) };
+				assert_eq!(ret, ptr);
+				assert_eq!(buf, expected, "n = {n}, offset = {offset}");
+			}
+		}
+	}
+}
@@ -0,0 +1,148 @@
+//! Misaligned accesses are not guaranteed to be fast or even supported by
+//! the hardware, so the copies use aligned doublewords, if source and
+//! destination share their alignment, and bytes otherwise.
+
+const WORD: usize = core::mem::size_of::<u64>();
+
+/// Returns whether `a` and `b` can be aligned to doublewords at the same time.
+fn co_aligned(a: *const u8, b: *const u8) -> bool {
+	(a as usize ^ b as usize) % WORD == 0
+}
+
+pub unsafe fn copy_forward(mut dest: *mut u8, mut src: *const u8, mut n: usize) {
+	unsafe {
+		if n >= 2 * WORD && co_aligned(dest, src) {
+			while dest as usize % WORD != 0 {
+				*dest = *src;
+				dest = dest.add(1);
+				src = src.add(1);
+				n -= 1;
+			}
+
+			let mut d = dest.cast::<u64>();
+			let mut s = src.cast::<u64>();
+			while n >= 8 * WORD {
+				let w = [
+					*s,
+					*s.add(1),
+					*s.add(2),
+					*s.add(3),
+					*s.add(4),
+					*s.add(5),
+					*s.add(6),
+					*s.add(7),
+				];
+				for (i, w) in w.into_iter().enumerate() {
+					*d.add(i) = w;
+				}
+				d = d.add(8);
+				s = s.add(8);
+				n -= 8 * WORD;
+			}
+			while n >= WORD {
+				*d = *s;
+				d = d.add(1);
+				s = s.add(1);
+				n -= WORD;
+			}
+			dest = d.cast();
+			src = s.cast();
+		}
+
+		while n >= 4 {
+			let w = [*src, *src.add(1), *src.add(2), *src.add(3)];
+			for (i, w) in w.into_iter().enumerate() {
+				*dest.add(i) = w;
+			}
+			dest = dest.add(4);
+			src = src.add(4);
+			n -= 4;
+		}
+		for i in 0..n {
+			*dest.add(i) = *src.add(i);
+		}
+	}
+}
+
+pub unsafe fn copy_backward(dest: *mut u8, src: *const u8, mut n: usize) {
+	unsafe {
+		if n >= 2 * WORD && co_aligned(dest, src) {
+			while dest.add(n) as usize % WORD != 0 {
+				n -= 1;
+				*dest.add(n) = *src.add(n);
+			}
+
+			while n >= 8 * WORD {
+				let d = dest.add(n - 8 * WORD).cast::<u64>();
+				let s = src.add(n - 8 * WORD).cast::<u64>();
+				let w = [
+					*s.add(7),
+					*s.add(6),
+					*s.add(5),
+					*s.add(4),
+					*s.add(3),
+					*s.add(2),
+					*s.add(1),
+					*s,
+				];
+				for (i, w) in w.into_iter().enumerate() {
+					*d.add(7 - i) = w;
+				}
+				n -= 8 * WORD;
+			}
+			while n >= WORD {
+				n -= WORD;
+				*dest.add(n).cast::<u64>() = *src.add(n).cast::<u64>();
+			}
+		}
+
+		while n >= 4 {
+			n -= 4;
+			let w = [
+				*src.add(n + 3),
+				*src.add(n + 2),
+				*src.add(n + 1),
+				*src.add(n),
+			];
+			for (i, w) in w.into_iter().enumerate() {
+				*dest.add(n + 3 - i) = w;
+			}
+		}
+		while n > 0 {
+			n -= 1;
+			*dest.add(n) = *src.add(n);
+		}
+	}
+}
+
+pub unsafe fn set(mut s: *mut u8, c: u8, mut n: usize) {
+	unsafe {
+		if n >= 2 * WORD {
+			while s as usize % WORD != 0 {
+				*s = c;
+				s = s.add(1);
+				n -= 1;
+			}
+
+			let value = u64::from_ne_bytes([c; WORD]);
+			let mut d = s.cast::<u64>();
+			while n >= 8 * WORD {
+				for i in 0..8 {
+					*d.add(i) = value;
+				}
+				d = d.add(8);
+				n -= 8 * WORD;
+			}
+			while n >= WORD {
+				*d = value;
+				d = d.add(1);
+				n -= WORD;
+			}
+			s = d.cast();
+		}
+
+		for i in 0..n {
+			*s.add(i) = c;
+		}
+	}
+}
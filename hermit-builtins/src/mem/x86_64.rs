@@ -0,0 +1,280 @@
+use core::arch::asm;
+use core::arch::x86_64::*;
+use core::ptr;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Size, from which on `rep movsb`/`rep stosb` outperform the vector loops
+const REP_THRESHOLD: usize = 2048;
+
+const DETECTED: u8 = 1 << 0;
+const ERMS: u8 = 1 << 1;
+const AVX2: u8 = 1 << 2;
+
+static FEATURES: AtomicU8 = AtomicU8::new(0);
+
+fn features() -> u8 {
+	let features = FEATURES.load(Ordering::Relaxed);
+	if features != 0 {
+		return features;
+	}
+
+	let features = detect();
+	FEATURES.store(features, Ordering::Relaxed);
+	features
+}
+
+// `__cpuid` is only safe to call, if SSE is enabled for the target.
+#[allow(unused_unsafe)]
+fn detect() -> u8 {
+	let mut features = DETECTED;
+
+	// SAFETY: CPUID is available on every x86-64 processor
+	let max_leaf = unsafe { __cpuid(0) }.eax;
+	if max_leaf < 7 {
+		return features;
+	}
+	let leaf1 = unsafe { __cpuid(1) };
+	let leaf7 = unsafe { __cpuid_count(7, 0) };
+
+	if leaf7.ebx & (1 << 9) != 0 {
+		features |= ERMS;
+	}
+
+	// AVX2 is only usable, if the kernel saves the SSE and AVX state.
+	let osxsave = leaf1.ecx & (1 << 27) != 0;
+	if leaf7.ebx & (1 << 5) != 0 && osxsave && unsafe { xcr0() } & 0b110 == 0b110 {
+		features |= AVX2;
+	}
+
+	features
+}
+
+#[target_feature(enable = "xsave")]
+unsafe fn xcr0() -> u64 {
+	unsafe { _xgetbv(0) }
+}
+
+/// Copies `n <= 32` bytes.
+///
+/// All bytes are loaded before the first store, so the areas may overlap.
+#[inline(always)]
+unsafe fn copy_small(dest: *mut u8, src: *const u8, n: usize) {
+	unsafe {
+		if n >= 16 {
+			let head = ptr::read_unaligned(src.cast::<u128>());
+			let tail = ptr::read_unaligned(src.add(n - 16).cast::<u128>());
+			ptr::write_unaligned(dest.cast::<u128>(), head);
+			ptr::write_unaligned(dest.add(n - 16).cast::<u128>(), tail);
+		} else if n >= 8 {
+			let head = ptr::read_unaligned(src.cast::<u64>());
+			let tail = ptr::read_unaligned(src.add(n - 8).cast::<u64>());
+			ptr::write_unaligned(dest.cast::<u64>(), head);
+			ptr::write_unaligned(dest.add(n - 8).cast::<u64>(), tail);
+		} else if n >= 4 {
+			let head = ptr::read_unaligned(src.cast::<u32>());
+			let tail = ptr::read_unaligned(src.add(n - 4).cast::<u32>());
+			ptr::write_unaligned(dest.cast::<u32>(), head);
+			ptr::write_unaligned(dest.add(n - 4).cast::<u32>(), tail);
+		} else if n >= 2 {
+			let head = ptr::read_unaligned(src.cast::<u16>());
+			let tail = ptr::read_unaligned(src.add(n - 2).cast::<u16>());
+			ptr::write_unaligned(dest.cast::<u16>(), head);
+			ptr::write_unaligned(dest.add(n - 2).cast::<u16>(), tail);
+		} else if n == 1 {
+			*dest = *src;
+		}
+	}
+}
+
+/// Sets `n <= 32` bytes.
+#[inline(always)]
+unsafe fn set_small(s: *mut u8, c: u8, n: usize) {
+	unsafe {
+		if n >= 16 {
+			let value = u128::from_ne_bytes([c; 16]);
+			ptr::write_unaligned(s.cast::<u128>(), value);
+			ptr::write_unaligned(s.add(n - 16).cast::<u128>(), value);
+		} else if n >= 8 {
+			let value = u64::from_ne_bytes([c; 8]);
+			ptr::write_unaligned(s.cast::<u64>(), value);
+			ptr::write_unaligned(s.add(n - 8).cast::<u64>(), value);
+		} else if n >= 4 {
+			let value = u32::from_ne_bytes([c; 4]);
+			ptr::write_unaligned(s.cast::<u32>(), value);
+			ptr::write_unaligned(s.add(n - 4).cast::<u32>(), value);
+		} else if n >= 2 {
+			let value = u16::from_ne_bytes([c; 2]);
+			ptr::write_unaligned(s.cast::<u16>(), value);
+			ptr::write_unaligned(s.add(n - 2).cast::<u16>(), value);
+		} else if n == 1 {
+			*s = c;
+		}
+	}
+}
+
+/// Generates copy and set loops for `n > 32` bytes with vectors of `$width` bytes.
+///
+/// The forward copy loads the last vector before the loop and stores it
+/// afterwards, the backward copy does the same with the first vector.
+/// Thereby, the loops don't have to handle a remainder and are correct for
+/// overlapping areas in their direction.
+macro_rules! vector_loops {
+	(
+		#[target_feature(enable = $feature:literal)]
+		mod $name:ident {
+			type Vector = $vector:ty;
+			const WIDTH = $width:literal;
+			load = $load:ident, store = $store:ident, splat = $splat:ident;
+		}
+	) => {
+		mod $name {
+			use core::arch::x86_64::*;
+
+			#[target_feature(enable = $feature)]
+			pub unsafe fn copy_forward(dest: *mut u8, src: *const u8, n: usize) {
+				unsafe {
+					let tail = $load(src.add(n - $width).cast::<$vector>());
+					let mut i = 0;
+					while n - i > 4 * $width {
+						let a = $load(src.add(i).cast::<$vector>());
+						let b = $load(src.add(i + $width).cast::<$vector>());
+						let c = $load(src.add(i + 2 * $width).cast::<$vector>());
+						let d = $load(src.add(i + 3 * $width).cast::<$vector>());
+						$store(dest.add(i).cast::<$vector>(), a);
+						$store(dest.add(i + $width).cast::<$vector>(), b);
+						$store(dest.add(i + 2 * $width).cast::<$vector>(), c);
+						$store(dest.add(i + 3 * $width).cast::<$vector>(), d);
+						i += 4 * $width;
+					}
+					while n - i > $width {
+						let a = $load(src.add(i).cast::<$vector>());
+						$store(dest.add(i).cast::<$vector>(), a);
+						i += $width;
+					}
+					$store(dest.add(n - $width).cast::<$vector>(), tail);
+				}
+			}
+
+			#[target_feature(enable = $feature)]
+			pub unsafe fn copy_backward(dest: *mut u8, src: *const u8, n: usize) {
+				unsafe {
+					let head = $load(src.cast::<$vector>());
+					let mut end = n;
+					while end > 4 * $width {
+						let a = $load(src.add(end - $width).cast::<$vector>());
+						let b = $load(src.add(end - 2 * $width).cast::<$vector>());
+						let c = $load(src.add(end - 3 * $width).cast::<$vector>());
+						let d = $load(src.add(end - 4 * $width).cast::<$vector>());
+						$store(dest.add(end - $width).cast::<$vector>(), a);
+						$store(dest.add(end - 2 * $width).cast::<$vector>(), b);
+						$store(dest.add(end - 3 * $width).cast::<$vector>(), c);
+						$store(dest.add(end - 4 * $width).cast::<$vector>(), d);
+						end -= 4 * $width;
+					}
+					while end > $width {
+						let a = $load(src.add(end - $width).cast::<$vector>());
+						$store(dest.add(end - $width).cast::<$vector>(), a);
+						end -= $width;
+					}
+					$store(dest.cast::<$vector>(), head);
+				}
+			}
+
+			#[target_feature(enable = $feature)]
+			pub unsafe fn set(s: *mut u8, c: u8, n: usize) {
+				unsafe {
+					let value = $splat(c as i8);
+					let mut i = 0;
+					while n - i > 4 * $width {
+						$store(s.add(i).cast::<$vector>(), value);
+						$store(s.add(i + $width).cast::<$vector>(), value);
+						$store(s.add(i + 2 * $width).cast::<$vector>(), value);
+						$store(s.add(i + 3 * $width).cast::<$vector>(), value);
+						i += 4 * $width;
+					}
+					while n - i > $width {
+						$store(s.add(i).cast::<$vector>(), value);
+						i += $width;
+					}
+					$store(s.add(n - $width).cast::<$vector>(), value);
+				}
+			}
+		}
+	};
+}
+
+vector_loops! {
+	#[target_feature(enable = "avx2")]
+	mod avx2 {
+		type Vector = __m256i;
+		const WIDTH = 32;
+		load = _mm256_loadu_si256, store = _mm256_storeu_si256, splat = _mm256_set1_epi8;
+	}
+}
+
+vector_loops! {
+	#[target_feature(enable = "sse2")]
+	mod sse2 {
+		type Vector = __m128i;
+		const WIDTH = 16;
+		load = _mm_loadu_si128, store = _mm_storeu_si128, splat = _mm_set1_epi8;
+	}
+}
+
+pub unsafe fn copy_forward(dest: *mut u8, src: *const u8, n: usize) {
+	if n <= 32 {
+		return unsafe { copy_small(dest, src, n) };
+	}
+
+	let features = features();
+	if n >= REP_THRESHOLD && features & ERMS != 0 {
+		unsafe {
+			asm!(
+				"rep movsb",
+				inout("rcx") n => _,
+				inout("rdi") dest => _,
+				inout("rsi") src => _,
+				options(nostack, preserves_flags),
+			);
+		}
+	} else if features & AVX2 != 0 {
+		unsafe { avx2::copy_forward(dest, src, n) }
+	} else {
+		unsafe { sse2::copy_forward(dest, src, n) }
+	}
+}
+
+pub unsafe fn copy_backward(dest: *mut u8, src: *const u8, n: usize) {
+	// `rep movsb` is only fast with a cleared direction flag, so the
+	// backward copy always uses the vector loops.
+	if n <= 32 {
+		unsafe { copy_small(dest, src, n) }
+	} else if features() & AVX2 != 0 {
+		unsafe { avx2::copy_backward(dest, src, n) }
+	} else {
+		unsafe { sse2::copy_backward(dest, src, n) }
+	}
+}
+
+pub unsafe fn set(s: *mut u8, c: u8, n: usize) {
+	if n <= 32 {
+		return unsafe { set_small(s, c, n) };
+	}
+
+	let features = features();
+	if n >= REP_THRESHOLD && features & ERMS != 0 {
+		unsafe {
+			asm!(
+				"rep stosb",
+				inout("rcx") n => _,
+				inout("rdi") s => _,
+				in("al") c,
+				options(nostack, preserves_flags),
+			);
+		}
+	} else if features & AVX2 != 0 {
+		unsafe { avx2::set(s, c, n) }
+	} else {
+		unsafe { sse2::set(s, c, n) }
+	}
+}
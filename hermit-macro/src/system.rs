@@ -1,6 +1,9 @@
 use proc_macro2::{Ident, Span};
 use quote::quote;
-use syn::{Abi, Attribute, FnArg, Item, ItemFn, Pat, Result, Signature, Visibility, parse_quote};
+use syn::{
+	Abi, Attribute, FnArg, GenericArgument, Item, ItemFn, Pat, PathArguments, Result, ReturnType,
+	Signature, Type, Visibility, parse_quote,
+};
 
 fn validate_vis(vis: &Visibility) -> Result<()> {
 	if !matches!(vis, Visibility::Public(_)) {
@@ -12,6 +15,29 @@ fn validate_vis(vis: &Visibility) -> Result<()> {
 
 struct ParsedSig {
 	args: Vec<Ident>,
+	/// Success type, if the function returns a `Result`
+	ok_ty: Option<Type>,
+}
+
+/// Returns the success type `T`, if `output` is `Result<T, E>` or `io::Result<T>`.
+fn result_ok_type(output: &ReturnType) -> Option<Type> {
+	let ReturnType::Type(_, ty) = output else {
+		return None;
+	};
+	let Type::Path(path) = &**ty else {
+		return None;
+	};
+	let segment = path.path.segments.last()?;
+	if segment.ident != "Result" {
+		return None;
+	}
+	let PathArguments::AngleBracketed(args) = &segment.arguments else {
+		return None;
+	};
+	match args.args.first()? {
+		GenericArgument::Type(ty) => Some(ty.clone()),
+		_ => None,
+	}
 }
 
 fn parse_sig(sig: &Signature) -> Result<ParsedSig> {
@@ -55,7 +81,9 @@ fn parse_sig(sig: &Signature) -> Result<ParsedSig> {
 		}
 	}
 
-	Ok(ParsedSig { args })
+	let ok_ty = result_ok_type(&sig.output);
+
+	Ok(ParsedSig { args, ok_ty })
 }
 
 fn validate_attrs(attrs: &[Attribute]) -> Result<()> {
@@ -88,11 +116,27 @@ fn validate_attrs(attrs: &[Attribute]) -> Result<()> {
 	Ok(())
 }
 
-fn emit_func(mut func: ItemFn, sig: &ParsedSig) -> Result<ItemFn> {
-	let args = &sig.args;
+fn emit_func(mut func: ItemFn, parsed_sig: &ParsedSig) -> Result<ItemFn> {
+	let args = &parsed_sig.args;
 	let attrs = func.attrs.clone();
 	let vis = func.vis.clone();
-	let sig = func.sig.clone();
+	let mut sig = func.sig.clone();
+
+	// A `Result` is returned as the value or as the negated error number.
+	let block = func.block.clone();
+	let block = if let Some(ok_ty) = &parsed_sig.ok_ty {
+		let output = func.sig.output.clone();
+		sig.output = parse_quote!(-> #ok_ty);
+		func.sig.output = sig.output.clone();
+		parse_quote! {{
+			match (move || #output #block)() {
+				Ok(ret) => ret,
+				Err(err) => crate::errno::FromErrno::from_errno(i32::from(err)),
+			}
+		}}
+	} else {
+		block
+	};
 
 	let ident = Ident::new(&format!("__{}", func.sig.ident), Span::call_site());
 	func.sig.ident = ident.clone();
@@ -117,17 +161,23 @@ fn emit_func(mut func: ItemFn, sig: &ParsedSig) -> Result<ItemFn> {
 		.collect::<Vec<_>>()
 		.join(", ");
 	let strace_format = format!("{}({input_format}) = ", sig.ident);
+	let name = sig.ident.to_string();
+	let name = name.strip_prefix("sys_").unwrap();
 
-	let block = func.block;
 	func.block = parse_quote! {{
 		#[allow(unreachable_code)]
 		#[allow(clippy::diverging_sub_expression)]
 		{
+			#[cfg(feature = "syscall-audit")]
+			crate::syscalls::audit::enter(#name);
 			#[cfg(feature = "strace")]
 			print!(#strace_format, #(#input_idents),*);
 			let ret = #block;
+			#[cfg(feature = "syscall-audit")]
+			crate::syscalls::audit::exit(#name, &ret);
 			#[cfg(feature = "strace")]
 			println!("{ret:?}");
+			crate::scheduler::group::cancellation_point();
 			ret
 		}
 	}};
@@ -166,8 +216,6 @@ pub fn system_attribute(func: ItemFn) -> Result<Item> {
 
 #[cfg(test)]
 mod tests {
-	use quote::ToTokens;
-
 	use super::*;
 
 	#[test]
@@ -184,7 +232,7 @@ mod tests {
 			}
 		};
 
-		let expected = quote! {
+		let expected: Item = parse_quote! {
 			/// Adds two numbers together.
 			///
 			/// This is very important.
@@ -195,14 +243,77 @@ mod tests {
 					#[allow(unreachable_code)]
 					#[allow(clippy::diverging_sub_expression)]
 					{
+						#[cfg(feature = "syscall-audit")]
+						crate::syscalls::audit::enter("test");
 						#[cfg(feature = "strace")]
 						print!("sys_test(a = {:?}, b = {:?}) = ", a, b);
 						let ret = {
 							let c = i16::from(a) + b;
 							i32::from(c)
 						};
+						#[cfg(feature = "syscall-audit")]
+						crate::syscalls::audit::exit("test", &ret);
+						#[cfg(feature = "strace")]
+						println!("{ret:?}");
+						crate::scheduler::group::cancellation_point();
+						ret
+					}
+				}
+
+				kernel_function!(__sys_test(a, b))
+			}
+		};
+
+		let result = system_attribute(input)?;
+
+		assert_eq!(expected, result);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_result() -> Result<()> {
+		let input = parse_quote! {
+			/// Adds two numbers together.
+			///
+			/// This is very important.
+			#[cfg(target_os = "none")]
+			#[unsafe(no_mangle)]
+			pub extern "C" fn sys_test(a: i8, b: i16) -> io::Result<i32> {
+				let c = i16::from(a).checked_add(b).ok_or(io::Error::EOVERFLOW)?;
+				Ok(i32::from(c))
+			}
+		};
+
+		let expected: Item = parse_quote! {
+			/// Adds two numbers together.
+			///
+			/// This is very important.
+			#[cfg(target_os = "none")]
+			#[unsafe(no_mangle)]
+			pub extern "C" fn sys_test(a: i8, b: i16) -> i32 {
+				extern "C" fn __sys_test(a: i8, b: i16) -> i32 {
+					#[allow(unreachable_code)]
+					#[allow(clippy::diverging_sub_expression)]
+					{
+						#[cfg(feature = "syscall-audit")]
+						crate::syscalls::audit::enter("test");
+						#[cfg(feature = "strace")]
+						print!("sys_test(a = {:?}, b = {:?}) = ", a, b);
+						let ret = {
+							match (move || -> io::Result<i32> {
+								let c = i16::from(a).checked_add(b).ok_or(io::Error::EOVERFLOW)?;
+								Ok(i32::from(c))
+							})() {
+								Ok(ret) => ret,
+								Err(err) => crate::errno::FromErrno::from_errno(i32::from(err)),
+							}
+						};
+						#[cfg(feature = "syscall-audit")]
+						crate::syscalls::audit::exit("test", &ret);
 						#[cfg(feature = "strace")]
 						println!("{ret:?}");
+						crate::scheduler::group::cancellation_point();
 						ret
 					}
 				}
@@ -211,9 +322,9 @@ mod tests {
 			}
 		};
 
-		let result = system_attribute(input)?.into_token_stream();
+		let result = system_attribute(input)?;
 
-		assert_eq!(expected.to_string(), result.to_string());
+		assert_eq!(expected, result);
 
 		Ok(())
 	}
@@ -232,7 +343,7 @@ mod tests {
 			}
 		};
 
-		let expected = quote! {
+		let expected: Item = parse_quote! {
 			/// Adds two numbers together.
 			///
 			/// This is very important.
@@ -243,14 +354,19 @@ mod tests {
 					#[allow(unreachable_code)]
 					#[allow(clippy::diverging_sub_expression)]
 					{
+						#[cfg(feature = "syscall-audit")]
+						crate::syscalls::audit::enter("test");
 						#[cfg(feature = "strace")]
 						print!("sys_test(a = {:?}, b = {:?}) = ", a, b);
 						let ret = {
 							let c = i16::from(a) + b;
 							i32::from(c)
 						};
+						#[cfg(feature = "syscall-audit")]
+						crate::syscalls::audit::exit("test", &ret);
 						#[cfg(feature = "strace")]
 						println!("{ret:?}");
+						crate::scheduler::group::cancellation_point();
 						ret
 					}
 				}
@@ -259,9 +375,9 @@ mod tests {
 			}
 		};
 
-		let result = system_attribute(input)?.into_token_stream();
+		let result = system_attribute(input)?;
 
-		assert_eq!(expected.to_string(), result.to_string());
+		assert_eq!(expected, result);
 
 		Ok(())
 	}
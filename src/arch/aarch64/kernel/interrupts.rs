@@ -1,4 +1,5 @@
 use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::arch::asm;
 use core::ptr;
@@ -9,11 +10,12 @@ use ahash::RandomState;
 use arm_gic::gicv3::{GicV3, IntId, Trigger};
 use hashbrown::HashMap;
 use hermit_dtb::Dtb;
-use hermit_sync::{InterruptSpinMutex, InterruptTicketMutex, OnceCell, SpinMutex};
+use hermit_sync::{InterruptSpinMutex, InterruptTicketMutex, SpinMutex};
 use memory_addresses::arch::aarch64::PhysAddr;
 
 use crate::arch::aarch64::kernel::core_local::increment_irq_counter;
 use crate::arch::aarch64::kernel::scheduler::State;
+use crate::arch::aarch64::kernel::{processor, watchpoint};
 use crate::arch::aarch64::mm::paging::{self, BasePageSize, PageSize, PageTableEntryFlags};
 use crate::arch::aarch64::mm::virtualmem;
 #[cfg(not(feature = "pci"))]
@@ -22,6 +24,7 @@ use crate::drivers::mmio::get_interrupt_handlers;
 use crate::drivers::pci::get_interrupt_handlers;
 use crate::drivers::{InterruptHandlerQueue, InterruptLine};
 use crate::scheduler::{self, CoreId};
+use crate::synch::rcu::Rcu;
 use crate::{core_scheduler, env};
 
 /// The ID of the first Private Peripheral Interrupt.
@@ -35,8 +38,7 @@ pub(crate) const SGI_RESCHED: u8 = 1;
 /// Number of the timer interrupt
 static mut TIMER_INTERRUPT: u32 = 0;
 /// Possible interrupt handlers
-static INTERRUPT_HANDLERS: OnceCell<HashMap<u8, InterruptHandlerQueue, RandomState>> =
-	OnceCell::new();
+static INTERRUPT_HANDLERS: Rcu<HashMap<u8, InterruptHandlerQueue, RandomState>> = Rcu::new();
 /// Driver for the Arm Generic Interrupt Controller version 3 (or 4).
 pub(crate) static GIC: SpinMutex<Option<GicV3>> = SpinMutex::new(None);
 
@@ -67,6 +69,21 @@ pub fn enable_and_wait() {
 	}
 }
 
+/// Determines whether IRQs are enabled
+#[cfg(feature = "sync-stats")]
+#[inline]
+pub fn are_enabled() -> bool {
+	let daif: u64;
+	unsafe {
+		asm!(
+			"mrs {daif}, daif",
+			daif = out(reg) daif,
+			options(nostack, nomem),
+		);
+	}
+	daif & (1 << 7) == 0
+}
+
 /// Disable all interrupts
 #[inline]
 pub fn disable() {
@@ -111,7 +128,7 @@ pub(crate) fn install_handlers() {
 		}
 	}
 
-	INTERRUPT_HANDLERS.set(handlers).unwrap();
+	INTERRUPT_HANDLERS.replace(handlers);
 }
 
 #[unsafe(no_mangle)]
@@ -122,13 +139,13 @@ pub(crate) extern "C" fn do_fiq(_state: &State) -> *mut usize {
 		debug!("Receive fiq {}", vector);
 		increment_irq_counter(vector);
 
-		if let Some(handlers) = INTERRUPT_HANDLERS.get() {
-			if let Some(queue) = handlers.get(&vector) {
+		INTERRUPT_HANDLERS.read(|handlers| {
+			if let Some(queue) = handlers.and_then(|handlers| handlers.get(&vector)) {
 				for handler in queue.iter() {
 					handler();
 				}
 			}
-		}
+		});
 		crate::executor::run();
 		core_scheduler().handle_waiting_tasks();
 
@@ -150,13 +167,13 @@ pub(crate) extern "C" fn do_irq(_state: &State) -> *mut usize {
 		debug!("Receive interrupt {}", vector);
 		increment_irq_counter(vector);
 
-		if let Some(handlers) = INTERRUPT_HANDLERS.get() {
-			if let Some(queue) = handlers.get(&vector) {
+		INTERRUPT_HANDLERS.read(|handlers| {
+			if let Some(queue) = handlers.and_then(|handlers| handlers.get(&vector)) {
 				for handler in queue.iter() {
 					handler();
 				}
 			}
-		}
+		});
 		crate::executor::run();
 		core_scheduler().handle_waiting_tasks();
 
@@ -172,9 +189,15 @@ pub(crate) extern "C" fn do_irq(_state: &State) -> *mut usize {
 
 #[unsafe(no_mangle)]
 pub(crate) extern "C" fn do_sync(state: &State) {
-	let irqid = GicV3::get_and_acknowledge_interrupt().unwrap();
 	let esr = ESR_EL1.get();
 	let ec = esr >> 26;
+
+	if ec == watchpoint::EC_WATCHPOINT_CUR_EL {
+		watchpoint::handle_watchpoint_exception(FAR_EL1.get(), ELR_EL1.get());
+		return;
+	}
+
+	let irqid = GicV3::get_and_acknowledge_interrupt().unwrap();
 	let iss = esr & 0x00ff_ffff;
 	let pc = ELR_EL1.get();
 
@@ -195,6 +218,8 @@ pub(crate) extern "C" fn do_sync(state: &State) {
 			error!("Exception Syndrome Register {:#x}", esr);
 
 			GicV3::end_interrupt(irqid);
+			#[cfg(feature = "coredump")]
+			crate::coredump::write(state.into(), crate::coredump::SIGSEGV);
 			scheduler::abort()
 		} else {
 			error!("Unknown exception");
@@ -228,10 +253,8 @@ pub(crate) fn init() {
 	info!("Initialize generic interrupt controller");
 
 	let dtb = unsafe {
-		Dtb::from_raw(ptr::with_exposed_provenance(
-			env::boot_info().hardware_info.device_tree.unwrap().get() as usize,
-		))
-		.expect(".dtb file has invalid header")
+		Dtb::from_raw(ptr::with_exposed_provenance(env::device_tree().unwrap()))
+			.expect(".dtb file has invalid header")
 	};
 
 	let reg = dtb.get_property("/intc", "reg").unwrap();
@@ -288,11 +311,10 @@ pub(crate) fn init() {
 				let irq_slice = dtb
 					.get_property(parts.first().unwrap(), "interrupts")
 					.unwrap();
-				/* Secure Phys IRQ */
-				let (_irqtype, irq_slice) = irq_slice.split_at(core::mem::size_of::<u32>());
-				let (_irq, irq_slice) = irq_slice.split_at(core::mem::size_of::<u32>());
-				let (_irqflags, irq_slice) = irq_slice.split_at(core::mem::size_of::<u32>());
-				/* Non-secure Phys IRQ */
+				/* Secure Phys IRQ, Non-secure Phys IRQ, Virt IRQ, Hyp Phys IRQ */
+				// At EL2, the physical timer registers address the EL2 physical timer.
+				let index = if processor::current_el() == 2 { 3 } else { 1 };
+				let (_, irq_slice) = irq_slice.split_at(index * 3 * core::mem::size_of::<u32>());
 				let (irqtype, irq_slice) = irq_slice.split_at(core::mem::size_of::<u32>());
 				let (irq, irq_slice) = irq_slice.split_at(core::mem::size_of::<u32>());
 				let (irqflags, _irq_slice) = irq_slice.split_at(core::mem::size_of::<u32>());
@@ -375,6 +397,24 @@ impl IrqStatistics {
 	}
 }
 
+/// Returns the number of received interrupts per core and interrupt.
+pub(crate) fn statistics() -> Vec<(CoreId, String, u64)> {
+	let mut statistics = Vec::new();
+	for (core_id, irg_statistics) in IRQ_COUNTERS.lock().iter() {
+		for (i, counter) in irg_statistics.counters.iter().enumerate() {
+			let counter = counter.load(Ordering::Relaxed);
+			if counter > 0 {
+				let name = match get_irq_name(i.try_into().unwrap()) {
+					Some(name) => name.to_string(),
+					None => i.to_string(),
+				};
+				statistics.push((*core_id, name, counter));
+			}
+		}
+	}
+	statistics
+}
+
 pub(crate) fn print_statistics() {
 	info!("Number of interrupts");
 	for (core_id, irg_statistics) in IRQ_COUNTERS.lock().iter() {
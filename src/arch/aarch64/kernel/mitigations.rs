@@ -0,0 +1,48 @@
+//! Mitigations based on the Speculative Store Bypass Safe (SSBS) extension
+
+use core::arch::asm;
+
+use crate::mitigations::Mitigations;
+
+/// `SCTLR_EL1.DSSBS`, which is the value of `PSTATE.SSBS` on exception entry
+const SCTLR_DSSBS: u64 = 1 << 44;
+
+/// Returns the mitigations, which are supported by the processor.
+pub(crate) fn supported() -> Mitigations {
+	let pfr1: u64;
+	unsafe {
+		asm!("mrs {}, id_aa64pfr1_el1", out(reg) pfr1, options(nomem, nostack));
+	}
+
+	let mut supported = Mitigations::empty();
+	if (pfr1 >> 4) & 0xf != 0 {
+		supported |= Mitigations::SSBS;
+	}
+	supported
+}
+
+/// Enables `mitigations` on the current core.
+pub(crate) fn enable(mitigations: Mitigations) {
+	if !mitigations.contains(Mitigations::SSBS) {
+		return;
+	}
+
+	// Forbid speculative store bypassing in the current context and after exceptions.
+	// New tasks start with a cleared `SPSR.SSBS`.
+	unsafe {
+		asm!(
+			"mrs {tmp}, sctlr_el1",
+			"bic {tmp}, {tmp}, {dssbs}",
+			"msr sctlr_el1, {tmp}",
+			"msr S3_3_C4_C2_6, xzr", // SSBS
+			"isb",
+			dssbs = in(reg) SCTLR_DSSBS,
+			tmp = out(reg) _,
+			options(nostack),
+		);
+	}
+}
+
+/// Applies `mitigations` on a switch to another task.
+#[inline]
+pub(crate) fn task_switch(_mitigations: Mitigations) {}
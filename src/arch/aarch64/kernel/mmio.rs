@@ -1,7 +1,26 @@
+#[cfg(feature = "blk")]
+use alloc::vec::Vec;
+
 use hermit_sync::InterruptTicketMutex;
 
+#[cfg(feature = "blk")]
+use crate::drivers::block::virtio::VirtioBlkDriver;
+#[cfg(feature = "console")]
+use crate::drivers::console::VirtioConsoleDriver;
+#[cfg(any(feature = "tcp", feature = "udp"))]
 use crate::drivers::net::virtio::VirtioNetDriver;
 
+#[cfg(any(feature = "tcp", feature = "udp"))]
 pub(crate) fn get_network_driver() -> Option<&'static InterruptTicketMutex<VirtioNetDriver>> {
 	None
 }
+
+#[cfg(feature = "blk")]
+pub(crate) fn get_block_drivers() -> Vec<&'static InterruptTicketMutex<VirtioBlkDriver>> {
+	Vec::new()
+}
+
+#[cfg(feature = "console")]
+pub(crate) fn get_console_driver() -> Option<&'static InterruptTicketMutex<VirtioConsoleDriver>> {
+	None
+}
@@ -1,6 +1,10 @@
 pub mod core_local;
 pub mod interrupts;
-#[cfg(all(not(feature = "pci"), any(feature = "tcp", feature = "udp")))]
+pub(crate) mod mitigations;
+#[cfg(all(
+	not(feature = "pci"),
+	any(feature = "tcp", feature = "udp", feature = "blk", feature = "console")
+))]
 pub mod mmio;
 #[cfg(feature = "pci")]
 pub mod pci;
@@ -11,6 +15,7 @@ pub mod serial;
 mod start;
 pub mod switch;
 pub mod systemtime;
+pub(crate) mod watchpoint;
 
 use core::arch::global_asm;
 use core::str;
@@ -5,16 +5,15 @@ use arm_gic::gicv3::{IntId, Trigger};
 use bit_field::BitField;
 use hermit_dtb::Dtb;
 use memory_addresses::arch::aarch64::{PhysAddr, VirtAddr};
-use pci_types::{
-	Bar, CommandRegister, ConfigRegionAccess, InterruptLine, InterruptPin, MAX_BARS, PciAddress,
-	PciHeader,
-};
+use pci_types::{ConfigRegionAccess, InterruptLine, InterruptPin, PciAddress, PciHeader};
 
 use crate::arch::aarch64::kernel::interrupts::GIC;
 use crate::arch::aarch64::mm::paging::{self, BasePageSize, PageSize, PageTableEntryFlags};
 use crate::arch::aarch64::mm::virtualmem;
+use crate::drivers::pci::resource::{self, PciWindow, PciWindows};
 use crate::drivers::pci::{PCI_DEVICES, PciDevice};
 use crate::env;
+use crate::mm::physmap::{self, RegionKind};
 
 const PCI_MAX_DEVICE_NUMBER: u8 = 32;
 const PCI_MAX_FUNCTION_NUMBER: u8 = 8;
@@ -58,66 +57,76 @@ impl ConfigRegionAccess for PciConfigRegion {
 	}
 }
 
-/// Try to find regions for the device registers
-#[allow(unused_assignments)]
-fn detect_pci_regions(dtb: &Dtb<'_>, parts: &[&str]) -> (u64, u64, u64) {
-	let mut io_start: u64 = 0;
-	let mut mem32_start: u64 = 0;
-	let mut mem64_start: u64 = 0;
+/// Try to find the windows of the host bridge
+fn detect_pci_regions(dtb: &Dtb<'_>, parts: &[&str]) -> PciWindows {
+	let mut windows = PciWindows::default();
 
 	let mut residual_slice = dtb.get_property(parts.first().unwrap(), "ranges").unwrap();
 	let mut value_slice;
 	while !residual_slice.is_empty() {
 		(value_slice, residual_slice) = residual_slice.split_at(core::mem::size_of::<u32>());
 		let high = u32::from_be_bytes(value_slice.try_into().unwrap());
-		(value_slice, residual_slice) = residual_slice.split_at(core::mem::size_of::<u32>());
-		let _mid = u32::from_be_bytes(value_slice.try_into().unwrap());
-		(value_slice, residual_slice) = residual_slice.split_at(core::mem::size_of::<u32>());
-		let _low = u32::from_be_bytes(value_slice.try_into().unwrap());
+		(value_slice, residual_slice) = residual_slice.split_at(core::mem::size_of::<u64>());
+		let pci_addr = u64::from_be_bytes(value_slice.try_into().unwrap());
+		(value_slice, residual_slice) = residual_slice.split_at(core::mem::size_of::<u64>());
+		let cpu_addr = u64::from_be_bytes(value_slice.try_into().unwrap());
+		(value_slice, residual_slice) = residual_slice.split_at(core::mem::size_of::<u64>());
+		let size = u64::from_be_bytes(value_slice.try_into().unwrap());
 
-		match high.get_bits(24..=25) {
-			0b00 => debug!("Configuration space"),
+		let window = match high.get_bits(24..=25) {
+			0b00 => {
+				debug!("Configuration space");
+				continue;
+			}
 			0b01 => {
-				debug!("IO space");
-				if io_start != 0 {
+				debug!("IO space at {cpu_addr:#x} (size {size:#x})");
+				if windows.io.is_some() {
 					warn!("Found already IO space");
+				} else {
+					// I/O ports are addressed by their PCI address
+					windows.io = Some(pci_addr..pci_addr + size);
 				}
-
-				(value_slice, residual_slice) =
-					residual_slice.split_at(core::mem::size_of::<u64>());
-				io_start = u64::from_be_bytes(value_slice.try_into().unwrap());
+				continue;
 			}
 			0b10 => {
 				let prefetchable = high.get_bit(30);
-				debug!("32 bit memory space: prefetchable {}", prefetchable);
-				if mem32_start != 0 {
-					warn!("Found already 32 bit memory space");
-				}
-
-				(value_slice, residual_slice) =
-					residual_slice.split_at(core::mem::size_of::<u64>());
-				mem32_start = u64::from_be_bytes(value_slice.try_into().unwrap());
+				debug!(
+					"32 bit memory space at {cpu_addr:#x} (PCI {pci_addr:#x}, size {size:#x}): prefetchable {prefetchable}"
+				);
+				&mut windows.mem32
 			}
 			0b11 => {
 				let prefetchable = high.get_bit(30);
-				debug!("64 bit memory space: prefetchable {}", prefetchable);
-				if mem64_start != 0 {
-					warn!("Found already 64 bit memory space");
-				}
-
-				(value_slice, residual_slice) =
-					residual_slice.split_at(core::mem::size_of::<u64>());
-				mem64_start = u64::from_be_bytes(value_slice.try_into().unwrap());
+				debug!(
+					"64 bit memory space at {cpu_addr:#x} (PCI {pci_addr:#x}, size {size:#x}): prefetchable {prefetchable}"
+				);
+				&mut windows.mem64
 			}
-			_ => panic!("Unknown space code"),
+			_ => unreachable!(),
+		};
+
+		if window.is_some() {
+			warn!("Found already space of type {:#b}", high.get_bits(24..=25));
+			continue;
 		}
+		physmap::insert(
+			PhysAddr::new(cpu_addr)..PhysAddr::new(cpu_addr + size),
+			RegionKind::PciWindow,
+			"PCI host bridge",
+		);
+		// the BARs hold PCI addresses, which the drivers translate into CPU addresses
+		*window = Some(PciWindow {
+			pci: pci_addr..pci_addr + size,
+			cpu_start: cpu_addr,
+		});
+	}
 
-		// currently, the size is ignores
-		(value_slice, residual_slice) = residual_slice.split_at(core::mem::size_of::<u64>());
-		//let size = u64::from_be_bytes(value_slice.try_into().unwrap());
+	// keep clear of the legacy ISA ports
+	if let Some(io) = &mut windows.io {
+		io.start = io.start.max(0x1000);
 	}
 
-	(io_start, mem32_start, mem64_start)
+	windows
 }
 
 #[allow(unused_assignments)]
@@ -227,7 +236,7 @@ fn detect_interrupt(
 pub fn init() {
 	let dtb = unsafe {
 		Dtb::from_raw(core::ptr::with_exposed_provenance(
-			env::boot_info().hardware_info.device_tree.unwrap().get() as usize,
+			env::device_tree().unwrap(),
 		))
 		.expect(".dtb file has invalid header")
 	};
@@ -245,6 +254,7 @@ pub fn init() {
 				let addr = PhysAddr::new(u64::from_be_bytes(slice.try_into().unwrap()));
 				let (slice, _residual_slice) = residual_slice.split_at(core::mem::size_of::<u64>());
 				let size = u64::from_be_bytes(slice.try_into().unwrap());
+				physmap::insert(addr..addr + size, RegionKind::Ecam, "PCI host bridge");
 
 				let pci_address =
 					virtualmem::allocate_aligned(size.try_into().unwrap(), 0x1000_0000).unwrap();
@@ -262,14 +272,8 @@ pub fn init() {
 					flags,
 				);
 
-				let (mut io_start, mem32_start, mut mem64_start) = detect_pci_regions(&dtb, &parts);
-
-				debug!("IO address space starts at{:#X}", io_start);
-				debug!("Memory32 address space starts at {:#X}", mem32_start);
-				debug!("Memory64 address space starts {:#X}", mem64_start);
-				assert!(io_start > 0);
-				assert!(mem32_start > 0);
-				assert!(mem64_start > 0);
+				let windows = detect_pci_regions(&dtb, &parts);
+				debug!("PCI windows: {windows:x?}");
 
 				let max_bus_number = size
 					/ (u64::from(PCI_MAX_DEVICE_NUMBER)
@@ -278,6 +282,7 @@ pub fn init() {
 				info!("Scanning PCI Busses 0 to {}", max_bus_number - 1);
 
 				let pci_config = PciConfigRegion::new(pci_address);
+				let mut devices = Vec::new();
 				for bus in 0..max_bus_number {
 					for device in 0..PCI_MAX_DEVICE_NUMBER {
 						let pci_address = PciAddress::new(0, bus.try_into().unwrap(), device, 0);
@@ -287,44 +292,6 @@ pub fn init() {
 						if device_id != u16::MAX && vendor_id != u16::MAX {
 							let dev = PciDevice::new(pci_address, pci_config);
 
-							// Initializes BARs
-							let mut cmd = CommandRegister::empty();
-							for i in 0..MAX_BARS {
-								if let Some(bar) = dev.get_bar(i.try_into().unwrap()) {
-									match bar {
-										Bar::Io { .. } => {
-											dev.set_bar(i.try_into().unwrap(), Bar::Io {
-												port: io_start.try_into().unwrap(),
-											});
-											io_start += 0x20;
-											cmd |= CommandRegister::IO_ENABLE
-												| CommandRegister::BUS_MASTER_ENABLE;
-										}
-										Bar::Memory32 { .. } => {
-											// Currently, we ignore 32 bit memory bars
-											// dev.set_bar(i.try_into().unwrap(), Bar::Memory32 { address: mem32_start.try_into().unwrap(), size,  prefetchable });
-											// mem32_start += u64::from(size);
-											// cmd |= CommandRegister::MEMORY_ENABLE | CommandRegister::BUS_MASTER_ENABLE;
-										}
-										Bar::Memory64 {
-											address: _,
-											size,
-											prefetchable,
-										} => {
-											dev.set_bar(i.try_into().unwrap(), Bar::Memory64 {
-												address: mem64_start,
-												size,
-												prefetchable,
-											});
-											mem64_start += size;
-											cmd |= CommandRegister::MEMORY_ENABLE
-												| CommandRegister::BUS_MASTER_ENABLE;
-										}
-									}
-								}
-							}
-							dev.set_command(cmd);
-
 							if let Some((pin, line)) = detect_interrupt(
 								bus.try_into().unwrap(),
 								device.into(),
@@ -338,11 +305,14 @@ pub fn init() {
 								dev.set_irq(pin, line);
 							}
 
-							PCI_DEVICES.with(|pci_devices| pci_devices.unwrap().push(dev));
+							devices.push(dev);
 						}
 					}
 				}
 
+				resource::assign(&devices, windows);
+				PCI_DEVICES.with(|pci_devices| pci_devices.unwrap().extend(devices));
+
 				return;
 			} else if str::from_utf8(compatible)
 				.unwrap()
@@ -1,11 +1,12 @@
 use core::arch::asm;
 use core::{fmt, str};
 
-use aarch64::regs::{CNTFRQ_EL0, Readable};
+use aarch64::regs::{CNTFRQ_EL0, CurrentEL, Readable};
 use hermit_dtb::Dtb;
-use hermit_sync::{Lazy, without_interrupts};
+use hermit_sync::Lazy;
 
 use crate::env;
+use crate::synch::without_interrupts;
 
 // System counter frequency in Hz
 static CPU_FREQUENCY: Lazy<CpuFrequency> = Lazy::new(|| {
@@ -103,6 +104,14 @@ pub(crate) fn run_on_hypervisor() -> bool {
 	true
 }
 
+/// Returns the exception level, at which the kernel is running.
+///
+/// This is EL2, if the kernel has been started at EL2 on a processor with
+/// the Virtualization Host Extensions, and EL1 otherwise.
+pub(crate) fn current_el() -> u64 {
+	CurrentEL.read(CurrentEL::EL)
+}
+
 /// The halt function stops the processor until the next interrupt arrives
 pub fn halt() {
 	unsafe {
@@ -110,6 +119,54 @@ pub fn halt() {
 	}
 }
 
+/// PSCI function to power off the system
+#[cfg(not(feature = "semihosting"))]
+const PSCI_SYSTEM_OFF: u64 = 0x8400_0008;
+/// PSCI function to reset the system
+const PSCI_SYSTEM_RESET: u64 = 0x8400_0009;
+
+/// Instruction, through which the PSCI firmware is called
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PsciConduit {
+	Hvc,
+	Smc,
+}
+
+/// The conduit is described by the property `method` of the node `/psci` in the device tree.
+/// Without this node, we assume that a hypervisor provides PSCI.
+static PSCI_CONDUIT: Lazy<PsciConduit> = Lazy::new(|| {
+	let dtb = unsafe {
+		Dtb::from_raw(core::ptr::with_exposed_provenance(
+			env::device_tree().unwrap(),
+		))
+		.expect(".dtb file has invalid header")
+	};
+
+	match dtb.get_property("/psci", "method") {
+		Some(method) if method.starts_with(b"smc") => PsciConduit::Smc,
+		_ => PsciConduit::Hvc,
+	}
+});
+
+/// Calls the PSCI `function`, which does not return on success.
+fn psci_call(function: u64) -> ! {
+	debug!(
+		"Calling PSCI function {function:#X} via {:?}",
+		*PSCI_CONDUIT
+	);
+	unsafe {
+		match *PSCI_CONDUIT {
+			PsciConduit::Hvc => asm!("hvc #0", in("x0") function, options(nomem, nostack)),
+			PsciConduit::Smc => asm!("smc #0", in("x0") function, options(nomem, nostack)),
+		}
+
+		// we should never reach this point
+		loop {
+			asm!("wfe", options(nomem, nostack));
+		}
+	}
+}
+
 /// Shutdown the system
 #[allow(unused_variables)]
 pub fn shutdown(error_code: i32) -> ! {
@@ -119,20 +176,18 @@ pub fn shutdown(error_code: i32) -> ! {
 		if #[cfg(feature = "semihosting")] {
 			semihosting::process::exit(error_code)
 		} else {
-			unsafe {
-				const PSCI_SYSTEM_OFF: u64 = 0x8400_0008;
-				// call hypervisor to shut down the system
-				asm!("hvc #0", in("x0") PSCI_SYSTEM_OFF, options(nomem, nostack));
-
-				// we should never reach this point
-				loop {
-					asm!("wfe", options(nomem, nostack));
-				}
-			}
+			psci_call(PSCI_SYSTEM_OFF)
 		}
 	}
 }
 
+/// Reset the system
+pub fn reboot() -> ! {
+	info!("Resetting system");
+
+	psci_call(PSCI_SYSTEM_RESET)
+}
+
 #[inline]
 pub fn get_timer_ticks() -> u64 {
 	// We simulate a timer with a 1 microsecond resolution by taking the CPU timestamp
@@ -224,7 +279,8 @@ pub fn detect_frequency() {
 fn __set_oneshot_timer(wakeup_time: Option<u64>) {
 	if let Some(wt) = wakeup_time {
 		// wt is the absolute wakeup time in microseconds based on processor::get_timer_ticks.
-		let deadline = u128::from(wt) * u128::from(CPU_FREQUENCY.get()) / 1_000_000;
+		// Round up, otherwise the timer fires before get_timer_ticks reaches wt.
+		let deadline = (u128::from(wt) * u128::from(CPU_FREQUENCY.get())).div_ceil(1_000_000);
 		let deadline = u64::try_from(deadline).unwrap();
 
 		unsafe {
@@ -257,7 +313,7 @@ pub fn set_oneshot_timer(wakeup_time: Option<u64>) {
 pub fn print_information() {
 	let dtb = unsafe {
 		Dtb::from_raw(core::ptr::with_exposed_provenance(
-			env::boot_info().hardware_info.device_tree.unwrap().get() as usize,
+			env::device_tree().unwrap(),
 		))
 		.expect(".dtb file has invalid header")
 	};
@@ -269,6 +325,7 @@ pub fn print_information() {
 	infoheader!(" CPU INFORMATION ");
 	infoentry!("Processor compatibility", str::from_utf8(reg).unwrap());
 	infoentry!("Counter frequency", *CPU_FREQUENCY);
+	infoentry!("Exception level", "EL{}", current_el());
 	if run_on_hypervisor() {
 		info!("Run on hypervisor");
 	}
@@ -13,8 +13,8 @@ use core::{mem, ptr};
 use align_address::Align;
 use memory_addresses::arch::aarch64::{PhysAddr, VirtAddr};
 
-use crate::arch::aarch64::kernel::CURRENT_STACK_ADDRESS;
 use crate::arch::aarch64::kernel::core_local::core_scheduler;
+use crate::arch::aarch64::kernel::{CURRENT_STACK_ADDRESS, processor};
 use crate::arch::aarch64::mm::paging::{BasePageSize, PageSize, PageTableEntryFlags};
 #[cfg(not(feature = "common-os"))]
 use crate::env;
@@ -274,7 +274,7 @@ pub struct TaskTLS {
 
 #[cfg(not(feature = "common-os"))]
 impl TaskTLS {
-	fn from_environment() -> Option<Box<Self>> {
+	fn from_environment(canary: u64) -> Option<Box<Self>> {
 		let tls_info = env::boot_info().load_info.tls_info?;
 		assert_ne!(tls_info.memsz, 0);
 
@@ -288,7 +288,8 @@ impl TaskTLS {
 		};
 
 		let off = core::cmp::max(16, usize::try_from(tls_info.align).unwrap()) - 16;
-		let block_len = usize::try_from(tls_info.memsz).unwrap() + off;
+		// The TLS block is followed by a guard value.
+		let block_len = usize::try_from(tls_info.memsz).unwrap() + off + mem::size_of::<u64>();
 		let len = mem::size_of::<Box<[Dtv; 2]>>() + mem::size_of::<usize>() + block_len;
 
 		let layout = Layout::from_size_align(len, 16).unwrap();
@@ -311,6 +312,7 @@ impl TaskTLS {
 		};
 
 		this.block[off..off + tls_init_image.len()].copy_from_slice(tls_init_image);
+		this.block[block_len - mem::size_of::<u64>()..].copy_from_slice(&canary.to_ne_bytes());
 
 		Some(this)
 	}
@@ -318,6 +320,11 @@ impl TaskTLS {
 	fn thread_ptr(&self) -> *const Box<[Dtv; 2]> {
 		self.dtv.as_ptr()
 	}
+
+	/// Returns `true`, if the guard value behind the TLS block has not been overwritten.
+	pub fn is_intact(&self, canary: u64) -> bool {
+		self.block[self.block.len() - mem::size_of::<u64>()..] == canary.to_ne_bytes()
+	}
 }
 
 #[cfg(not(target_os = "none"))]
@@ -358,7 +365,7 @@ impl TaskFrame for Task {
 		// Check if TLS is allocated already and if the task uses thread-local storage.
 		#[cfg(not(feature = "common-os"))]
 		if self.tls.is_none() {
-			self.tls = TaskTLS::from_environment();
+			self.tls = TaskTLS::from_environment(self.canary);
 		}
 
 		unsafe {
@@ -385,8 +392,8 @@ impl TaskFrame for Task {
 			(*state).x1 = arg as u64;
 			(*state).spsel = 1;
 
-			/* Zero the condition flags. */
-			(*state).spsr_el1 = 0x3e5;
+			/* Zero the condition flags and return to the exception level of the kernel (EL1h or EL2h). */
+			(*state).spsr_el1 = 0x3e1 | (processor::current_el() << 2);
 
 			// Set the task's stack pointer entry to the stack we have just crafted.
 			self.last_stack_pointer = stack;
@@ -11,6 +11,11 @@ unsafe extern "C" {
 }
 
 /// Entrypoint - Initialize Stack pointer and Exception Table
+///
+/// If the kernel is started at EL2, it keeps running at EL2 with the
+/// Virtualization Host Extensions (VHE), so that the EL1 system registers
+/// address their EL2 counterparts. Without VHE, it drops to EL1. In both cases,
+/// the translation tables of the loader remain in use.
 #[unsafe(no_mangle)]
 #[naked]
 pub unsafe extern "C" fn _start(boot_info: Option<&'static RawBootInfo>, cpu_id: u32) -> ! {
@@ -29,6 +34,78 @@ pub unsafe extern "C" fn _start(boot_info: Option<&'static RawBootInfo>, cpu_id:
 
 	unsafe {
 		naked_asm!(
+			// Determine the exception level, at which we have been started
+			"mrs x8, CurrentEL",
+			"cmp x8, {current_el2}",
+			"b.ne 4f",
+
+			// We are running at EL2. Check for the Virtualization Host Extensions.
+			"mrs x9, id_aa64mmfr1_el1",
+			"ubfx x9, x9, #8, #4",
+			"cbz x9, 3f",
+
+			// With VHE, the kernel stays at EL2. The EL1 system registers,
+			// which are used by the kernel, are redirected to their EL2 counterparts.
+			"mrs x9, hcr_el2",
+			"tbnz x9, #34, 2f",
+			// E2H is not enabled yet. Switch TCR_EL2 to the layout of TCR_EL1.
+			"mrs x10, tcr_el2",
+			"ubfx x11, x10, #16, #3", // PS
+			"bfi x10, x11, #32, #3", // IPS
+			"orr x10, x10, #(1 << 23)", // EPD1
+			"msr tcr_el2, x10",
+			"2:",
+			"movz x9, #0x8800, lsl #16", // TGE | RW
+			"movk x9, #0x4, lsl #32", // E2H
+			"msr hcr_el2, x9",
+			"isb",
+			"tlbi alle2",
+			"dsb ish",
+			// Do not trap FP/SIMD instructions (CPTR_EL2 in the layout of CPACR_EL1)
+			"mrs x9, cpacr_el1",
+			"orr x9, x9, #(3 << 20)",
+			"msr cpacr_el1, x9",
+			"isb",
+			"b 4f",
+
+			// Without VHE, take over the translation regime of EL2 and drop to EL1.
+			"3:",
+			"mov x9, #(1 << 31)", // RW
+			"msr hcr_el2, x9",
+			// Allow EL1 to access the physical timer and counter
+			"mrs x9, cnthctl_el2",
+			"orr x9, x9, #3",
+			"msr cnthctl_el2, x9",
+			"msr cntvoff_el2, xzr",
+			// Do not trap FP/SIMD instructions
+			"mov x9, #0x33ff",
+			"msr cptr_el2, x9",
+			"mov x9, #(3 << 20)",
+			"msr cpacr_el1, x9",
+			"mrs x9, mair_el2",
+			"msr mair_el1, x9",
+			"mrs x9, ttbr0_el2",
+			"msr ttbr0_el1, x9",
+			"mrs x9, tcr_el2",
+			"ubfx x10, x9, #16, #3", // PS
+			"bfi x9, x10, #32, #3", // IPS
+			"orr x9, x9, #(1 << 23)", // EPD1
+			"msr tcr_el1, x9",
+			"mrs x9, sctlr_el2",
+			"movz x10, #0x0800",
+			"movk x10, #0x30d0, lsl #16", // RES1 bits of SCTLR_EL1
+			"orr x9, x9, x10",
+			"msr sctlr_el1, x9",
+			"mov x9, sp",
+			"msr sp_el1, x9",
+			"mov x9, #0x3c5", // EL1h, all exceptions masked
+			"msr spsr_el2, x9",
+			"adr x9, 4f",
+			"msr elr_el2, x9",
+			"isb",
+			"eret",
+
+			"4:",
 			"msr spsel, {l1}", // we want to use sp_el1
 			"adrp x8, {current_stack_address}",
 			"mov x4, sp",
@@ -41,6 +118,7 @@ pub unsafe extern "C" fn _start(boot_info: Option<&'static RawBootInfo>, cpu_id:
 			// Jump to Rust code
 			"b {pre_init}",
 
+			current_el2 = const 2 << 2,
 			l1 = const 1,
 			stack_top_offset = const KERNEL_STACK_SIZE - TaskStacks::MARKER_SIZE,
 			current_stack_address = sym super::CURRENT_STACK_ADDRESS,
@@ -52,6 +130,12 @@ pub unsafe extern "C" fn _start(boot_info: Option<&'static RawBootInfo>, cpu_id:
 #[inline(never)]
 #[unsafe(no_mangle)]
 unsafe extern "C" fn pre_init(boot_info: Option<&'static RawBootInfo>, cpu_id: u32) -> ! {
+	if cpu_id == 0 {
+		unsafe {
+			crate::arch::relocate::relocate();
+		}
+	}
+
 	// set exception table
 	unsafe {
 		asm!(
@@ -48,7 +48,7 @@ fn rtc_read(off: usize) -> u32 {
 pub fn init() {
 	let dtb = unsafe {
 		Dtb::from_raw(core::ptr::with_exposed_provenance(
-			env::boot_info().hardware_info.device_tree.unwrap().get() as usize,
+			env::device_tree().unwrap(),
 		))
 		.expect(".dtb file has invalid header")
 	};
@@ -0,0 +1,108 @@
+//! Watchpoints based on the debug registers `DBGWVR<n>_EL1` and `DBGWCR<n>_EL1`
+
+use core::arch::asm;
+
+use crate::watchpoint::{MAX_WATCHPOINTS, WatchKind, Watchpoint};
+
+/// Exception class of a watchpoint exception, which was taken without a change in exception level
+pub(crate) const EC_WATCHPOINT_CUR_EL: u64 = 0x35;
+
+/// Monitor debug events
+const MDSCR_MDE: u64 = 1 << 15;
+/// Enable debug exceptions at the current exception level
+const MDSCR_KDE: u64 = 1 << 13;
+
+/// Returns the number of watchpoints, which are supported by the processor.
+pub(crate) fn slots() -> usize {
+	let dfr0: u64;
+	unsafe {
+		asm!("mrs {}, id_aa64dfr0_el1", out(reg) dfr0, options(nomem, nostack));
+	}
+	((dfr0 >> 20) & 0xf) as usize + 1
+}
+
+fn write_registers(slot: usize, value: u64, control: u64) {
+	macro_rules! write {
+		($wvr:literal, $wcr:literal) => {
+			unsafe {
+				asm!(
+					concat!("msr ", $wvr, ", {value}"),
+					concat!("msr ", $wcr, ", {control}"),
+					value = in(reg) value,
+					control = in(reg) control,
+					options(nomem, nostack),
+				)
+			}
+		};
+	}
+
+	match slot {
+		0 => write!("dbgwvr0_el1", "dbgwcr0_el1"),
+		1 => write!("dbgwvr1_el1", "dbgwcr1_el1"),
+		2 => write!("dbgwvr2_el1", "dbgwcr2_el1"),
+		3 => write!("dbgwvr3_el1", "dbgwcr3_el1"),
+		_ => unreachable!(),
+	}
+}
+
+/// Loads `watchpoints` into the debug registers of the current core.
+pub(crate) fn install(watchpoints: &[Option<Watchpoint>; MAX_WATCHPOINTS]) {
+	let slots = slots().min(MAX_WATCHPOINTS);
+
+	for (slot, watchpoint) in watchpoints.iter().take(slots).enumerate() {
+		let Some(watchpoint) = watchpoint else {
+			write_registers(slot, 0, 0);
+			continue;
+		};
+
+		// The value register holds a doubleword-aligned address and the
+		// byte address select field specifies the watched bytes within.
+		let addr = watchpoint.addr.as_u64();
+		let bas = ((1u64 << watchpoint.len) - 1) << (addr & 0x7);
+		let lsc = match watchpoint.kind {
+			WatchKind::Write => 0b10,
+			WatchKind::ReadWrite => 0b11,
+		};
+		// enable the watchpoint for accesses at EL1
+		let control = (bas << 5) | (lsc << 3) | (0b01 << 1) | 1;
+		write_registers(slot, addr & !0x7, control);
+	}
+
+	unsafe {
+		asm!(
+			// unlock the OS lock, which prevents the generation of debug events
+			"msr oslar_el1, xzr",
+			"mrs {tmp}, mdscr_el1",
+			"orr {tmp}, {tmp}, {mask}",
+			"msr mdscr_el1, {tmp}",
+			"isb",
+			// unmask debug exceptions
+			"msr daifclr, #8",
+			tmp = out(reg) _,
+			mask = in(reg) MDSCR_MDE | MDSCR_KDE,
+			options(nostack),
+		);
+	}
+}
+
+/// Handles a watchpoint exception, which was triggered by an access to `far` at `pc`.
+///
+/// Watchpoint exceptions are taken before the access is performed. To be able to
+/// continue, the triggered watchpoint is removed.
+pub(crate) fn handle_watchpoint_exception(far: u64, pc: u64) {
+	let slot = crate::watchpoint::list().iter().position(|watchpoint| {
+		watchpoint.is_some_and(|watchpoint| watchpoint.addr.as_u64() & !0x7 == far & !0x7)
+	});
+
+	match slot {
+		Some(slot) => {
+			crate::watchpoint::report_hit(slot, pc);
+			crate::watchpoint::clear(slot).unwrap();
+		}
+		None => {
+			error!("Unknown watchpoint triggered by an access to {far:#x}, PC = {pc:#x}");
+			// remove all watchpoints to be able to continue
+			install(&[None; MAX_WATCHPOINTS]);
+		}
+	}
+}
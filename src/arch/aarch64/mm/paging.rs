@@ -574,7 +574,15 @@ pub fn virtual_to_physical(virtual_address: VirtAddr) -> Option<PhysAddr> {
 	get_physical_address::<BasePageSize>(virtual_address)
 }
 
-#[cfg(any(feature = "fuse", feature = "vsock", feature = "tcp", feature = "udp"))]
+#[cfg(any(
+	feature = "blk",
+	feature = "console",
+	feature = "fuse",
+	feature = "pmem",
+	feature = "vsock",
+	feature = "tcp",
+	feature = "udp"
+))]
 pub fn virt_to_phys(virtual_address: VirtAddr) -> PhysAddr {
 	virtual_to_physical(virtual_address).unwrap()
 }
@@ -7,6 +7,26 @@ pub mod mm;
 pub(crate) fn memory_barrier() {
 	use core::arch::asm;
 	unsafe {
-		asm!("dmb ish", options(nostack, nomem, preserves_flags),);
+		asm!("dmb ish", options(nostack, preserves_flags),);
+	}
+}
+
+/// Orders the read of a device register before the following memory accesses.
+#[allow(dead_code)]
+#[inline(always)]
+pub(crate) fn io_read_barrier() {
+	use core::arch::asm;
+	unsafe {
+		asm!("dmb oshld", options(nostack, preserves_flags),);
+	}
+}
+
+/// Orders the preceding memory accesses before the write of a device register.
+#[allow(dead_code)]
+#[inline(always)]
+pub(crate) fn io_write_barrier() {
+	use core::arch::asm;
+	unsafe {
+		asm!("dmb oshst", options(nostack, preserves_flags),);
 	}
 }
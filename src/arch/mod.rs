@@ -1,5 +1,8 @@
 //! Architecture-specific architecture abstraction.
 
+#[cfg(target_os = "none")]
+pub(crate) mod relocate;
+
 cfg_if::cfg_if! {
 	if #[cfg(target_arch = "aarch64")] {
 		pub(crate) mod aarch64;
@@ -0,0 +1,135 @@
+//! Self-relocation of the kernel
+//!
+//! The application and the kernel are linked as position-independent
+//! executable (static PIE), so a loader may place the image at any sufficiently
+//! aligned address. Loaders do not have to process the relocations of the
+//! image, because the boot processor applies them itself, before any code
+//! accesses addresses, which have to be relocated. The relative relocations
+//! only depend on the load address, so applying them again is harmless, if the
+//! loader has already relocated the image.
+//!
+//! Until the relocations have been applied, the code must neither access
+//! global data through the GOT nor panic.
+
+use core::arch::asm;
+use core::ptr;
+
+const DT_NULL: i64 = 0;
+const DT_RELA: i64 = 7;
+const DT_RELASZ: i64 = 8;
+const DT_RELAENT: i64 = 9;
+
+#[cfg(target_arch = "x86_64")]
+const R_RELATIVE: u32 = 8;
+#[cfg(target_arch = "aarch64")]
+const R_RELATIVE: u32 = 1027;
+#[cfg(target_arch = "riscv64")]
+const R_RELATIVE: u32 = 3;
+
+/// Entry of the dynamic section (`Elf64_Dyn`)
+#[repr(C)]
+struct Dyn {
+	tag: i64,
+	val: u64,
+}
+
+/// Relocation with addend (`Elf64_Rela`)
+#[repr(C)]
+struct Rela {
+	offset: u64,
+	info: u64,
+	addend: i64,
+}
+
+/// Returns the load address of the image and the address of its dynamic section.
+///
+/// Position-independent executables are linked at address zero, so the load
+/// address is the offset, which has to be added to all link-time addresses.
+#[inline(always)]
+fn image() -> (usize, *const Dyn) {
+	let base: usize;
+	let dynamic: *const Dyn;
+
+	#[cfg(target_arch = "x86_64")]
+	unsafe {
+		asm!(
+			"lea {base}, [rip + __ehdr_start]",
+			"lea {dynamic}, [rip + _DYNAMIC]",
+			base = out(reg) base,
+			dynamic = out(reg) dynamic,
+			options(pure, nomem, nostack),
+		);
+	}
+
+	#[cfg(target_arch = "aarch64")]
+	unsafe {
+		asm!(
+			"adrp {base}, __ehdr_start",
+			"add {base}, {base}, :lo12:__ehdr_start",
+			"adrp {dynamic}, _DYNAMIC",
+			"add {dynamic}, {dynamic}, :lo12:_DYNAMIC",
+			base = out(reg) base,
+			dynamic = out(reg) dynamic,
+			options(pure, nomem, nostack),
+		);
+	}
+
+	#[cfg(target_arch = "riscv64")]
+	unsafe {
+		asm!(
+			"lla {base}, __ehdr_start",
+			"lla {dynamic}, _DYNAMIC",
+			base = out(reg) base,
+			dynamic = out(reg) dynamic,
+			options(pure, nomem, nostack),
+		);
+	}
+
+	(base, dynamic)
+}
+
+/// Applies the relative relocations of the kernel image.
+///
+/// # Safety
+///
+/// This may only be called by the boot processor before any relocated data is
+/// accessed. Other relocations than relative ones are left to the loader.
+#[inline(never)]
+pub(crate) unsafe extern "C" fn relocate() {
+	let (base, mut dynamic) = image();
+
+	let mut rela = 0;
+	let mut relasz = 0;
+	let mut relaent = size_of::<Rela>();
+	loop {
+		let entry = unsafe { dynamic.read() };
+		match entry.tag {
+			DT_NULL => break,
+			DT_RELA => rela = entry.val as usize,
+			DT_RELASZ => relasz = entry.val as usize,
+			DT_RELAENT => relaent = entry.val as usize,
+			_ => {}
+		}
+		dynamic = dynamic.wrapping_add(1);
+	}
+
+	if rela == 0 || relaent == 0 {
+		return;
+	}
+
+	// Use wrapping arithmetic to avoid overflow checks, which could panic.
+	let mut offset = 0;
+	while offset < relasz {
+		let entry =
+			ptr::with_exposed_provenance::<Rela>(base.wrapping_add(rela).wrapping_add(offset));
+		let entry = unsafe { entry.read() };
+		if entry.info as u32 == R_RELATIVE {
+			let target =
+				ptr::with_exposed_provenance_mut::<usize>(base.wrapping_add(entry.offset as usize));
+			unsafe {
+				target.write(base.wrapping_add_signed(entry.addend as isize));
+			}
+		}
+		offset = offset.wrapping_add(relaent);
+	}
+}
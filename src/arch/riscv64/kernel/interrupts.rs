@@ -1,8 +1,9 @@
+use alloc::string::String;
 use alloc::vec::Vec;
 
 use ahash::RandomState;
 use hashbrown::HashMap;
-use hermit_sync::{InterruptTicketMutex, OnceCell, SpinMutex};
+use hermit_sync::{InterruptTicketMutex, SpinMutex};
 use riscv::asm::wfi;
 use riscv::interrupt::{Exception, Interrupt, Trap};
 use riscv::register::{scause, sie, sip, sstatus, stval};
@@ -13,7 +14,8 @@ use crate::drivers::InterruptHandlerQueue;
 use crate::drivers::mmio::get_interrupt_handlers;
 #[cfg(feature = "pci")]
 use crate::drivers::pci::get_interrupt_handlers;
-use crate::scheduler;
+use crate::scheduler::{self, CoreId};
+use crate::synch::rcu::Rcu;
 
 /// base address of the PLIC, only one access at the same time is allowed
 static PLIC_BASE: SpinMutex<usize> = SpinMutex::new(0x0);
@@ -24,8 +26,7 @@ static PLIC_CONTEXT: SpinMutex<u16> = SpinMutex::new(0x0);
 /// PLIC context for new interrupt handlers
 static CURRENT_INTERRUPTS: SpinMutex<Vec<u32>> = SpinMutex::new(Vec::new());
 
-static INTERRUPT_HANDLERS: OnceCell<HashMap<u8, InterruptHandlerQueue, RandomState>> =
-	OnceCell::new();
+static INTERRUPT_HANDLERS: Rcu<HashMap<u8, InterruptHandlerQueue, RandomState>> = Rcu::new();
 
 /// Init Interrupts
 pub(crate) fn install() {
@@ -43,6 +44,13 @@ pub(crate) fn init_plic(base: usize, context: u16) {
 	*PLIC_CONTEXT.lock() = context;
 }
 
+/// Determines whether interrupts are enabled
+#[cfg(feature = "sync-stats")]
+#[inline]
+pub(crate) fn are_enabled() -> bool {
+	sstatus::read().sie()
+}
+
 /// Enable Interrupts
 #[inline]
 pub(crate) fn enable() {
@@ -143,7 +151,7 @@ pub(crate) fn install_handlers() {
 		}
 	}
 
-	INTERRUPT_HANDLERS.set(handlers).unwrap();
+	INTERRUPT_HANDLERS.replace(handlers);
 }
 
 // Derived from rCore: https://github.com/rcore-os/rCore
@@ -207,16 +215,18 @@ fn external_handler() {
 		}
 
 		// Call handler
-		if let Some(handlers) = INTERRUPT_HANDLERS.get() {
-			if let Some(queue) = handlers.get(&u8::try_from(irq).unwrap()) {
+		INTERRUPT_HANDLERS.read(|handlers| {
+			if let Some(queue) =
+				handlers.and_then(|handlers| handlers.get(&u8::try_from(irq).unwrap()))
+			{
 				for handler in queue.iter() {
 					handler();
 				}
 			}
-		}
+		});
 		crate::executor::run();
 
-		core_scheduler().reschedule();
+		core_scheduler().preempt();
 	}
 }
 
@@ -239,4 +249,11 @@ fn external_eoi() {
 	}
 }
 
+/// Returns the number of received interrupts per core and interrupt.
+///
+/// Interrupts are not yet counted on RISC-V.
+pub(crate) fn statistics() -> Vec<(CoreId, String, u64)> {
+	Vec::new()
+}
+
 pub(crate) fn print_statistics() {}
@@ -1,11 +1,24 @@
 use alloc::vec::Vec;
+use core::ptr::NonNull;
 
 use hermit_sync::InterruptSpinMutex;
+#[cfg(any(feature = "blk", feature = "console"))]
+use hermit_sync::InterruptTicketMutex;
+use virtio::mmio::{DeviceRegisters, DeviceRegistersVolatileFieldAccess};
+use volatile::VolatileRef;
 
+#[cfg(feature = "blk")]
+use crate::drivers::block::virtio::VirtioBlkDriver;
+#[cfg(feature = "console")]
+use crate::drivers::console::VirtioConsoleDriver;
+use crate::drivers::error::DriverError;
 #[cfg(feature = "gem-net")]
 use crate::drivers::net::gem::GEMDriver;
 #[cfg(not(feature = "gem-net"))]
 use crate::drivers::net::virtio::VirtioNetDriver;
+use crate::drivers::registry::{DeviceMatch, DriverContext, FdtDriverEntry};
+use crate::drivers::virtio::error::VirtioError;
+use crate::drivers::virtio::transport::mmio::{self as mmio_virtio, VirtioDriver};
 use crate::init_cell::InitCell;
 
 static MMIO_DRIVERS: InitCell<Vec<MmioDriver>> = InitCell::new(Vec::new());
@@ -15,23 +28,123 @@ pub(crate) enum MmioDriver {
 	GEMNet(InterruptSpinMutex<GEMDriver>),
 	#[cfg(not(feature = "gem-net"))]
 	VirtioNet(InterruptSpinMutex<VirtioNetDriver>),
+	#[cfg(feature = "blk")]
+	VirtioBlk(InterruptTicketMutex<VirtioBlkDriver>),
+	#[cfg(feature = "console")]
+	VirtioConsole(InterruptTicketMutex<VirtioConsoleDriver>),
 }
 
 impl MmioDriver {
 	#[cfg(feature = "gem-net")]
 	fn get_network_driver(&self) -> Option<&InterruptSpinMutex<GEMDriver>> {
+		#[allow(unreachable_patterns)]
 		match self {
 			Self::GEMNet(drv) => Some(drv),
+			_ => None,
 		}
 	}
 
 	#[cfg(not(feature = "gem-net"))]
 	fn get_network_driver(&self) -> Option<&InterruptSpinMutex<VirtioNetDriver>> {
+		#[allow(unreachable_patterns)]
 		match self {
 			Self::VirtioNet(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "blk")]
+	fn get_block_driver(&self) -> Option<&InterruptTicketMutex<VirtioBlkDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioBlk(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "console")]
+	fn get_console_driver(&self) -> Option<&InterruptTicketMutex<VirtioConsoleDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioConsole(drv) => Some(drv),
+			_ => None,
 		}
 	}
 }
+/// Driver entry for virtio devices, which are connected through MMIO
+pub(crate) struct VirtioMmioEntry;
+
+impl FdtDriverEntry for VirtioMmioEntry {
+	fn name(&self) -> &'static str {
+		"virtio-mmio"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Compatible("virtio,mmio")];
+		MATCHES
+	}
+
+	fn probe(&self, ctx: &mut DriverContext<'_>) -> bool {
+		let Some(start) = ctx.request_region(0) else {
+			return false;
+		};
+		let ptr = start.as_u64() as *mut DeviceRegisters;
+		let mmio = VolatileRef::new(NonNull::new(ptr).unwrap());
+
+		// Verify the first register value to find out if this is really an MMIO magic-value.
+		const MMIO_MAGIC_VALUE: u32 = 0x7472_6976;
+		if mmio.as_ptr().magic_value().read().to_ne() != MMIO_MAGIC_VALUE {
+			error!("It's not a MMIO-device at {mmio:p}");
+			return false;
+		}
+
+		if mmio.as_ptr().version().read().to_ne() != 2 {
+			warn!("Found a legacy device, which isn't supported");
+			return false;
+		}
+
+		// Verify the device-ID to find a supported device
+		let id = mmio.as_ptr().device_id().read();
+		let is_supported = match id {
+			#[cfg(not(feature = "gem-net"))]
+			virtio::Id::Net => true,
+			#[cfg(feature = "blk")]
+			virtio::Id::Block => true,
+			#[cfg(feature = "console")]
+			virtio::Id::Console => true,
+			_ => false,
+		};
+		if !is_supported {
+			debug!("Device {id:?} of node {} is not supported", ctx.node().name);
+		}
+		is_supported
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<MmioDriver, DriverError> {
+		let start = ctx
+			.request_region(0)
+			.expect("reg property for virtio mmio not found in FDT");
+		let irq = ctx
+			.request_irq("virtio")
+			.expect("interrupts property for virtio mmio not found in FDT");
+		let ptr = start.as_u64() as *mut DeviceRegisters;
+		let mmio = VolatileRef::new(NonNull::new(ptr).unwrap());
+		info!("Found virtio device at {mmio:p}, irq: {irq}");
+
+		match mmio_virtio::init_device(mmio, irq)? {
+			#[cfg(not(feature = "gem-net"))]
+			VirtioDriver::Network(drv) => Ok(MmioDriver::VirtioNet(InterruptSpinMutex::new(drv))),
+			#[cfg(feature = "blk")]
+			VirtioDriver::Block(drv) => Ok(MmioDriver::VirtioBlk(InterruptTicketMutex::new(drv))),
+			#[cfg(feature = "console")]
+			VirtioDriver::Console(drv) => Ok(MmioDriver::VirtioConsole(InterruptTicketMutex::new(drv))),
+			// Devices, which are not supported here, have been rejected by `probe`.
+			#[allow(unreachable_patterns)]
+			_ => Err(VirtioError::DevNotSupported(0).into()),
+		}
+	}
+}
+
 pub(crate) fn register_driver(drv: MmioDriver) {
 	MMIO_DRIVERS.with(|mmio_drivers| mmio_drivers.unwrap().push(drv));
 }
@@ -51,3 +164,21 @@ pub(crate) fn get_network_driver() -> Option<&'static InterruptSpinMutex<VirtioN
 		.iter()
 		.find_map(|drv| drv.get_network_driver())
 }
+
+#[cfg(feature = "blk")]
+pub(crate) fn get_block_drivers() -> Vec<&'static InterruptTicketMutex<VirtioBlkDriver>> {
+	MMIO_DRIVERS.get().map_or_else(Vec::new, |drivers| {
+		drivers
+			.iter()
+			.filter_map(|drv| drv.get_block_driver())
+			.collect()
+	})
+}
+
+#[cfg(feature = "console")]
+pub(crate) fn get_console_driver() -> Option<&'static InterruptTicketMutex<VirtioConsoleDriver>> {
+	MMIO_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_console_driver())
+}
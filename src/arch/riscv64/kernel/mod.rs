@@ -115,7 +115,7 @@ pub fn args() -> Option<&'static str> {
 }
 
 pub fn get_dtb_ptr() -> *const u8 {
-	env::boot_info().hardware_info.device_tree.unwrap().get() as _
+	ptr::with_exposed_provenance(env::device_tree().unwrap())
 }
 
 pub fn get_hart_mask() -> u64 {
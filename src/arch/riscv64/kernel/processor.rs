@@ -234,7 +234,11 @@ pub fn shutdown(error_code: i32) -> ! {
 			semihosting::process::exit(error_code)
 		} else {
 			// use SBI shutdown
-			sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason);
+			if error_code == 0 {
+				sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason);
+			} else {
+				sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::SystemFailure);
+			}
 			loop {
 				core::hint::spin_loop();
 			}
@@ -242,6 +246,16 @@ pub fn shutdown(error_code: i32) -> ! {
 	}
 }
 
+/// Reset the system
+pub fn reboot() -> ! {
+	info!("Resetting system");
+
+	sbi_rt::system_reset(sbi_rt::ColdReboot, sbi_rt::NoReason);
+	loop {
+		core::hint::spin_loop();
+	}
+}
+
 pub fn get_timer_ticks() -> u64 {
 	// We simulate a timer with a 1 microsecond resolution by taking the CPU timestamp
 	// and dividing it by the CPU frequency in MHz.
@@ -267,11 +267,13 @@ pub struct TaskTLS {
 	address: VirtAddr,
 	tp: VirtAddr,
 	layout: Layout,
+	/// Guard value behind the TLS block
+	guard: VirtAddr,
 }
 
 #[cfg(not(feature = "common-os"))]
 impl TaskTLS {
-	pub fn from_environment() -> Option<Box<Self>> {
+	pub fn from_environment(canary: u64) -> Option<Box<Self>> {
 		let tls_info = env::boot_info().load_info.tls_info?;
 		assert_ne!(tls_info.memsz, 0);
 
@@ -282,8 +284,9 @@ impl TaskTLS {
 		// Yes, it does, so we have to allocate TLS memory.
 		// Allocate enough space for the given size and one more variable of type usize, which holds the tls_pointer.
 		let tls_allocation_size = tls_size.align_up(32usize); // + mem::size_of::<usize>();
+		// The TLS block is followed by a guard value.
 		// We allocate in 128 byte granularity (= cache line size) to avoid false sharing
-		let memory_size = tls_allocation_size.align_up(128usize);
+		let memory_size = (tls_allocation_size + mem::size_of::<u64>()).align_up(128usize);
 		let layout =
 			Layout::from_size_align(memory_size, 128).expect("TLS has an invalid size / alignment");
 		let ptr = VirtAddr::new(unsafe { alloc(layout) as u64 });
@@ -302,6 +305,11 @@ impl TaskTLS {
 				tls_size.align_up(32usize) - tdata_size,
 			);
 
+			// Place the guard value behind the TLS block.
+			(ptr + tls_allocation_size)
+				.as_mut_ptr::<u64>()
+				.write(canary);
+
 			// The x86-64 TLS specification also requires that the tls_pointer can be accessed at fs:0.
 			// This allows TLS variable values to be accessed by "mov rax, fs:0" and a later "lea rdx, [rax+VARIABLE_OFFSET]".
 			// See "ELF Handling For Thread-Local Storage", version 0.20 by Ulrich Drepper, page 12 for details.
@@ -319,12 +327,18 @@ impl TaskTLS {
 			address: ptr,
 			tp: tls_pointer,
 			layout,
+			guard: ptr + tls_allocation_size,
 		}))
 	}
 
 	pub fn tp(&self) -> VirtAddr {
 		self.tp
 	}
+
+	/// Returns `true`, if the guard value behind the TLS block has not been overwritten.
+	pub fn is_intact(&self, canary: u64) -> bool {
+		unsafe { self.guard.as_ptr::<u64>().read() == canary }
+	}
 }
 
 #[cfg(not(feature = "common-os"))]
@@ -387,7 +401,7 @@ impl TaskFrame for Task {
 		// check is TLS is already allocated
 		#[cfg(not(feature = "common-os"))]
 		if self.tls.is_none() {
-			self.tls = TaskTLS::from_environment();
+			self.tls = TaskTLS::from_environment(self.canary);
 		}
 
 		unsafe {
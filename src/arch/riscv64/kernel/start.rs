@@ -49,6 +49,13 @@ pub unsafe extern "C" fn _start(hart_id: usize, boot_info: Option<&'static RawBo
 }
 
 unsafe extern "C" fn pre_init(hart_id: usize, boot_info: Option<&'static RawBootInfo>) -> ! {
+	// Only the boot hart receives the boot information.
+	if boot_info.is_some() {
+		unsafe {
+			crate::arch::relocate::relocate();
+		}
+	}
+
 	CURRENT_BOOT_ID.store(hart_id as u32, Ordering::Relaxed);
 
 	if CPU_ONLINE.load(Ordering::Acquire) == 0 {
@@ -8,6 +8,7 @@ use memory_addresses::PhysAddr;
 use crate::arch::riscv64::kernel::{get_limit, get_ram_address};
 use crate::arch::riscv64::mm::paging::{BasePageSize, PageSize};
 use crate::mm;
+use crate::mm::physmap::{self, RegionKind};
 
 static PHYSICAL_FREE_LIST: InterruptSpinMutex<FreeList<16>> =
 	InterruptSpinMutex::new(FreeList::new());
@@ -19,13 +20,21 @@ fn detect_from_limits() -> Result<(), ()> {
 		return Err(());
 	}
 
+	physmap::insert(
+		get_ram_address()..get_ram_address() + limit as u64,
+		RegionKind::Ram,
+		"boot info",
+	);
+
 	let range = PageRange::new(
 		mm::kernel_end_address().as_usize(),
 		get_ram_address().as_usize() + limit,
 	)
 	.unwrap();
 	unsafe {
-		PHYSICAL_FREE_LIST.lock().deallocate(range).unwrap();
+		tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
+			.deallocate(range)
+			.unwrap();
 	}
 	TOTAL_MEMORY.store(limit, Ordering::Relaxed);
 
@@ -40,6 +49,11 @@ pub fn total_memory_size() -> usize {
 	TOTAL_MEMORY.load(Ordering::Relaxed)
 }
 
+/// Returns the size of the physical memory, which is not allocated.
+pub fn free_memory_size() -> usize {
+	PHYSICAL_FREE_LIST.lock().free_space()
+}
+
 pub fn allocate(size: usize) -> Result<PhysAddr, AllocError> {
 	assert!(size > 0);
 	assert_eq!(
@@ -53,8 +67,7 @@ pub fn allocate(size: usize) -> Result<PhysAddr, AllocError> {
 	let layout = PageLayout::from_size(size).unwrap();
 
 	Ok(PhysAddr::new(
-		PHYSICAL_FREE_LIST
-			.lock()
+		tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
 			.allocate(layout)?
 			.start()
 			.try_into()
@@ -81,8 +94,7 @@ pub fn allocate_aligned(size: usize, align: usize) -> Result<PhysAddr, AllocErro
 	let layout = PageLayout::from_size_align(size, align).unwrap();
 
 	Ok(PhysAddr::new(
-		PHYSICAL_FREE_LIST
-			.lock()
+		tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
 			.allocate(layout)?
 			.start()
 			.try_into()
@@ -109,11 +121,36 @@ pub fn deallocate(physical_address: PhysAddr, size: usize) {
 	let range = PageRange::from_start_len(physical_address.as_usize(), size).unwrap();
 
 	unsafe {
-		PHYSICAL_FREE_LIST.lock().deallocate(range).unwrap();
+		tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
+			.deallocate(range)
+			.unwrap();
+	}
+}
+
+/// Removes the free parts of the given range from the list of free physical
+/// memory and returns them.
+///
+/// The other parts are not managed by the list, e.g., because they belong to
+/// the kernel image. Only the returned parts may be deallocated again.
+pub fn reserve_free<const N: usize>(
+	physical_address: PhysAddr,
+	size: usize,
+) -> heapless::Vec<PageRange, N> {
+	let start = physical_address.as_usize();
+	let end = start + size;
+	let mut parts = heapless::Vec::new();
+	let mut free_list = tracked_lock!(PHYSICAL_FREE_LIST, "physical memory");
+	while let Ok(part) = free_list
+		.allocate_with(|entry| PageRange::new(entry.start().max(start), entry.end().min(end)).ok())
+	{
+		parts
+			.push(part)
+			.expect("the range is scattered over too many free regions");
 	}
+	parts
 }
 
 pub fn print_information() {
-	let free_list = PHYSICAL_FREE_LIST.lock();
+	let free_list = tracked_lock!(PHYSICAL_FREE_LIST, "physical memory");
 	info!("Physical memory free list:\n{free_list}");
 }
@@ -26,7 +26,9 @@ pub fn init() {
 	)
 	.unwrap();
 	unsafe {
-		KERNEL_FREE_LIST.lock().deallocate(range).unwrap();
+		tracked_lock!(KERNEL_FREE_LIST, "virtual memory")
+			.deallocate(range)
+			.unwrap();
 	}
 }
 
@@ -43,8 +45,7 @@ pub fn allocate(size: usize) -> Result<VirtAddr, AllocError> {
 	let layout = PageLayout::from_size(size).unwrap();
 
 	Ok(VirtAddr::new(
-		KERNEL_FREE_LIST
-			.lock()
+		tracked_lock!(KERNEL_FREE_LIST, "virtual memory")
 			.allocate(layout)?
 			.start()
 			.try_into()
@@ -71,8 +72,7 @@ pub fn allocate_aligned(size: usize, align: usize) -> Result<VirtAddr, AllocErro
 	let layout = PageLayout::from_size_align(size, align).unwrap();
 
 	Ok(VirtAddr::new(
-		KERNEL_FREE_LIST
-			.lock()
+		tracked_lock!(KERNEL_FREE_LIST, "virtual memory")
 			.allocate(layout)?
 			.start()
 			.try_into()
@@ -107,7 +107,9 @@ pub fn deallocate(virtual_address: VirtAddr, size: usize) {
 	let range = PageRange::from_start_len(virtual_address.as_usize(), size).unwrap();
 
 	unsafe {
-		KERNEL_FREE_LIST.lock().deallocate(range).unwrap();
+		tracked_lock!(KERNEL_FREE_LIST, "virtual memory")
+			.deallocate(range)
+			.unwrap();
 	}
 }
 
@@ -148,6 +150,6 @@ pub fn deallocate(virtual_address: VirtAddr, size: usize) {
 }*/
 
 pub fn print_information() {
-	let free_list = KERNEL_FREE_LIST.lock();
+	let free_list = tracked_lock!(KERNEL_FREE_LIST, "virtual memory");
 	info!("Virtual memory free list:\n{free_list}");
 }
@@ -1,9 +1,36 @@
 pub mod kernel;
 pub mod mm;
 
+/// Force strict CPU ordering, serializes load and store operations
+/// including the accesses to device memory.
 #[allow(dead_code)]
 #[cfg(target_arch = "riscv64")]
 #[inline(always)]
 pub(crate) fn memory_barrier() {
-	riscv::asm::sfence_vma_all();
+	use core::arch::asm;
+	unsafe {
+		asm!("fence iorw, iorw", options(nostack, preserves_flags),);
+	}
+}
+
+/// Orders the read of a device register before the following memory accesses.
+#[allow(dead_code)]
+#[cfg(target_arch = "riscv64")]
+#[inline(always)]
+pub(crate) fn io_read_barrier() {
+	use core::arch::asm;
+	unsafe {
+		asm!("fence i, r", options(nostack, preserves_flags),);
+	}
+}
+
+/// Orders the preceding memory accesses before the write of a device register.
+#[allow(dead_code)]
+#[cfg(target_arch = "riscv64")]
+#[inline(always)]
+pub(crate) fn io_write_barrier() {
+	use core::arch::asm;
+	unsafe {
+		asm!("fence w, o", options(nostack, preserves_flags),);
+	}
 }
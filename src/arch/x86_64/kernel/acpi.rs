@@ -11,6 +11,7 @@ use crate::arch::x86_64::mm::paging::{
 };
 use crate::arch::x86_64::mm::{paging, virtualmem};
 use crate::env;
+use crate::mm::physmap::{self, RegionKind};
 
 /// Memory at this physical address is supposed to contain a pointer to the Extended BIOS Data Area (EBDA).
 const EBDA_PTR_LOCATION: PhysAddr = PhysAddr::new(0x0000_040e);
@@ -40,6 +41,8 @@ const AML_BYTEPREFIX: u8 = 0x0a;
 
 /// Bit to enable an ACPI Sleep State.
 const SLP_EN: u16 = 1 << 13;
+/// FADT flag indicating that the reset register is supported.
+const RESET_REG_SUP: u32 = 1 << 10;
 
 /// The "Multiple APIC Description Table" (MADT) preserved for get_apic_table().
 static MADT: OnceCell<AcpiTable<'_>> = OnceCell::new();
@@ -47,6 +50,20 @@ static MADT: OnceCell<AcpiTable<'_>> = OnceCell::new();
 static PM1A_CNT_BLK: OnceCell<Port<u16>> = OnceCell::new();
 /// The Sleeping State Type code for powering off the computer through ACPI.
 static SLP_TYPA: OnceCell<u8> = OnceCell::new();
+/// The I/O port and the value for resetting the computer through ACPI.
+static RESET_REG: OnceCell<(Port<u8>, u8)> = OnceCell::new();
+
+/// An entry of the "PCI Express Memory-mapped Configuration Space" table (MCFG),
+/// which describes the enhanced configuration space (ECAM) of a PCI segment.
+#[derive(Clone, Copy, Debug)]
+#[repr(C, packed)]
+struct McfgEntry {
+	base_address: u64,
+	segment: u16,
+	start_bus: u8,
+	end_bus: u8,
+	reserved: u32,
+}
 
 /// The "Root System Description Pointer" structure providing pointers to all other ACPI tables.
 #[repr(C, packed)]
@@ -433,6 +450,17 @@ fn parse_fadt(fadt: AcpiTable<'_>) {
 	};
 	PM1A_CNT_BLK.set(Port::new(pm1a_cnt_blk)).unwrap();
 
+	// The reset register has been introduced with ACPI 2.0 and may only be used, if the flags announce it.
+	// We only support reset registers in I/O space, which is used by all common chipsets.
+	let reset_value_field_address = ptr::addr_of!(fadt_table.reset_value) as usize;
+	if reset_value_field_address < fadt.table_end_address()
+		&& fadt_table.flags & RESET_REG_SUP != 0
+		&& fadt_table.reset_reg.address_space == GENERIC_ADDRESS_IO_SPACE
+	{
+		let port = Port::new(fadt_table.reset_reg.address as u16);
+		RESET_REG.set((port, fadt_table.reset_value)).unwrap();
+	}
+
 	// Map the "Differentiated System Description Table" (DSDT).
 	let x_dsdt_field_address = ptr::addr_of!(fadt_table.x_dsdt) as usize;
 	let dsdt_address = if x_dsdt_field_address < fadt.table_end_address() && fadt_table.x_dsdt > 0 {
@@ -470,6 +498,27 @@ fn parse_ssdt(ssdt: AcpiTable<'_>) {
 	search_s5_in_table(ssdt);
 }
 
+fn parse_mcfg(mcfg: AcpiTable<'_>) {
+	// The entries follow 8 reserved bytes.
+	let mut current_address = mcfg.table_start_address() + mem::size_of::<u64>();
+	while current_address + mem::size_of::<McfgEntry>() <= mcfg.table_end_address() {
+		let entry =
+			unsafe { ptr::with_exposed_provenance::<McfgEntry>(current_address).read_unaligned() };
+		current_address += mem::size_of::<McfgEntry>();
+
+		// Each bus has 32 devices with 8 functions and 4 KiB of configuration space.
+		let buses = u64::from(entry.end_bus.saturating_sub(entry.start_bus)) + 1;
+		let start = PhysAddr::new(entry.base_address + (u64::from(entry.start_bus) << 20));
+		debug!(
+			"ECAM of PCI segment {} for the buses {} to {} at {start:p}",
+			{ entry.segment },
+			{ entry.start_bus },
+			{ entry.end_bus }
+		);
+		physmap::insert(start..start + (buses << 20), RegionKind::Ecam, "ACPI MCFG");
+	}
+}
+
 pub fn get_madt() -> Option<&'static AcpiTable<'static>> {
 	MADT.get()
 }
@@ -490,6 +539,20 @@ pub fn poweroff() {
 	}
 }
 
+pub fn reset() {
+	if let Some((mut reset_reg, reset_value)) = RESET_REG.get().cloned() {
+		debug!(
+			"Resetting through ACPI (port {:?}, value {:#X})",
+			reset_reg, reset_value
+		);
+		unsafe {
+			reset_reg.write(reset_value);
+		}
+	} else {
+		warn!("ACPI Reset is not available");
+	}
+}
+
 pub fn init() {
 	// Detect the RSDP and get a pointer to either the XSDT (64-bit) or RSDT (32-bit), whichever is available.
 	// Both are called RSDT in the following.
@@ -552,6 +615,8 @@ pub fn init() {
 				"SSDT at {table_physical_address:p} has invalid checksum"
 			);
 			parse_ssdt(table);
+		} else if table.header.signature() == "MCFG" {
+			parse_mcfg(table);
 		}
 	}
 }
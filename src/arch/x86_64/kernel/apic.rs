@@ -13,7 +13,7 @@ use align_address::Align;
 #[cfg(feature = "smp")]
 use arch::x86_64::kernel::core_local::*;
 use arch::x86_64::kernel::{interrupts, processor};
-use hermit_sync::{OnceCell, SpinMutex, without_interrupts};
+use hermit_sync::{OnceCell, SpinMutex};
 use memory_addresses::{AddrRange, PhysAddr, VirtAddr};
 #[cfg(feature = "smp")]
 use x86_64::registers::control::Cr3;
@@ -30,6 +30,7 @@ use crate::arch::x86_64::mm::{paging, virtualmem};
 use crate::arch::x86_64::swapgs;
 use crate::config::*;
 use crate::scheduler::CoreId;
+use crate::synch::without_interrupts;
 use crate::{arch, env, scheduler};
 
 /// APIC Location and Status (R/W) See Table 35-2. See Section 10.4.4, Local APIC  Status and Location.
@@ -38,6 +39,9 @@ const IA32_APIC_BASE: Msr = Msr::new(0x1b);
 /// TSC Target of Local APIC s TSC Deadline Mode (R/W)  See Table 35-2
 const IA32_TSC_DEADLINE: Msr = Msr::new(0x6e0);
 
+/// x2APIC ID register (R/O)
+const IA32_X2APIC_APICID: u32 = 0x802;
+
 /// x2APIC Task Priority register (R/W)
 const IA32_X2APIC_TPR: u32 = 0x808;
 
@@ -140,7 +144,10 @@ static IOAPIC_ADDRESS: OnceCell<VirtAddr> = OnceCell::new();
 
 /// Stores the Local APIC IDs of all CPUs. The index equals the Core ID.
 /// Both numbers often match, but don't need to (e.g. when a core has been disabled).
-static CPU_LOCAL_APIC_IDS: SpinMutex<Vec<u8>> = SpinMutex::new(Vec::new());
+static CPU_LOCAL_APIC_IDS: SpinMutex<Vec<u32>> = SpinMutex::new(Vec::new());
+
+/// Largest Local APIC ID, which can be addressed in xAPIC mode. 0xFF is the broadcast address.
+const XAPIC_MAX_ID: u32 = 0xfe;
 
 /// After calibration, initialize the APIC Timer with this counter value to let it fire an interrupt
 /// after 1 microsecond.
@@ -229,6 +236,25 @@ impl fmt::Display for ProcessorLocalApicRecord {
 	}
 }
 
+#[cfg(feature = "acpi")]
+#[repr(C, packed)]
+struct ProcessorLocalX2ApicRecord {
+	reserved: u16,
+	x2apic_id: u32,
+	flags: u32,
+	acpi_processor_uid: u32,
+}
+
+#[cfg(feature = "acpi")]
+impl fmt::Display for ProcessorLocalX2ApicRecord {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{{ x2apic_id: {}, ", { self.x2apic_id })?;
+		write!(f, "flags: {}, ", { self.flags })?;
+		write!(f, "acpi_processor_uid: {} }}", { self.acpi_processor_uid })?;
+		Ok(())
+	}
+}
+
 #[cfg(feature = "acpi")]
 const CPU_FLAG_ENABLED: u32 = 1 << 0;
 
@@ -293,14 +319,25 @@ extern "x86-interrupt" fn wakeup_handler(stack_frame: interrupts::ExceptionStack
 	core_scheduler.check_input();
 	eoi();
 	if core_scheduler.is_scheduling() {
-		core_scheduler.reschedule();
+		core_scheduler.preempt();
 	}
 	swapgs(&stack_frame);
 }
 
-#[inline]
-pub fn add_local_apic_id(id: u8) {
-	CPU_LOCAL_APIC_IDS.lock().push(id);
+/// Registers the Local APIC ID of the next core.
+///
+/// Firmware may report a CPU twice, by an xAPIC and an x2APIC record.
+/// Without x2APIC support, CPUs with an ID above [`XAPIC_MAX_ID`] cannot be addressed and are ignored.
+pub fn add_local_apic_id(id: u32) {
+	if id > XAPIC_MAX_ID && !processor::supports_x2apic() {
+		warn!("Ignore CPU with Local APIC ID {id}, which requires x2APIC support");
+		return;
+	}
+
+	let mut apic_ids = CPU_LOCAL_APIC_IDS.lock();
+	if !apic_ids.contains(&id) {
+		apic_ids.push(id);
+	}
 }
 
 #[cfg(feature = "smp")]
@@ -359,7 +396,21 @@ fn detect_from_acpi() -> Result<PhysAddr, ()> {
 				);
 
 				if processor_local_apic_record.flags & CPU_FLAG_ENABLED > 0 {
-					add_local_apic_id(processor_local_apic_record.apic_id);
+					add_local_apic_id(processor_local_apic_record.apic_id.into());
+				}
+			}
+			9 => {
+				// Processor Local x2APIC, used for Local APIC IDs, which do not fit into 8 bits
+				let processor_local_x2apic_record = unsafe {
+					&*(ptr::with_exposed_provenance::<ProcessorLocalX2ApicRecord>(current_address))
+				};
+				debug!(
+					"Found Processor Local x2APIC record: {}",
+					processor_local_x2apic_record
+				);
+
+				if processor_local_x2apic_record.flags & CPU_FLAG_ENABLED > 0 {
+					add_local_apic_id(processor_local_x2apic_record.x2apic_id);
 				}
 			}
 			1 => {
@@ -477,7 +528,7 @@ fn detect_from_mp() -> Result<PhysAddr, ()> {
 					let cpu_entry: &ApicProcessorEntry =
 						unsafe { &*(ptr::with_exposed_provenance(addr)) };
 					if cpu_entry.cpu_flags & 0x01 == 0x01 {
-						add_local_apic_id(cpu_entry.id);
+						add_local_apic_id(cpu_entry.id.into());
 					}
 					addr += mem::size_of::<ApicProcessorEntry>();
 				}
@@ -591,12 +642,19 @@ fn init_ioapic() {
 	let max_entry = ioapic_max_redirection_entry() + 1;
 	info!("IOAPIC v{} has {} entries", ioapic_version(), max_entry);
 
+	// Route the interrupts to the boot processor. Without interrupt remapping,
+	// the IOAPIC only supports 8-bit destinations.
+	let apicid = u8::try_from(local_apic_id()).unwrap_or_else(|_| {
+		warn!("Local APIC ID of the boot processor cannot be addressed by the IOAPIC");
+		0
+	});
+
 	// now lets turn everything else on
 	for i in 0..max_entry {
 		// Turn off the Programmable Interrupt Timer Interrupt (IRQ 0) and
 		// the Real Time Clock (IRQ 2).
 		let enabled = !matches!(i, 0 | 2);
-		ioapic_set_interrupt(i, 0, enabled);
+		ioapic_set_interrupt(i, apicid, enabled);
 	}
 }
 
@@ -922,6 +980,16 @@ fn translate_x2apic_msr_to_xapic_address(x2apic_msr: u32) -> VirtAddr {
 	*LOCAL_APIC_ADDRESS.get().unwrap() + ((u64::from(x2apic_msr) & 0xff) << 4)
 }
 
+/// Returns the Local APIC ID of the current CPU.
+fn local_apic_id() -> u32 {
+	if processor::supports_x2apic() {
+		local_apic_read(IA32_X2APIC_APICID)
+	} else {
+		// In xAPIC mode, the 8-bit ID is stored in the upper bits.
+		local_apic_read(IA32_X2APIC_APICID) >> 24
+	}
+}
+
 fn local_apic_read(x2apic_msr: u32) -> u32 {
 	if processor::supports_x2apic() {
 		// x2APIC is simple, we can just read from the given MSR.
@@ -34,6 +34,11 @@ pub(crate) struct CoreLocal {
 	irq_statistics: &'static IrqStatistics,
 	/// Queue of async tasks
 	async_tasks: RefCell<Vec<AsyncTask>>,
+	/// Generation of the watchpoints, which are loaded into the debug registers
+	pub watchpoint_generation: Cell<u32>,
+	/// ID of the running task, which is returned by the fast path of `gettid`
+	#[cfg(feature = "common-os")]
+	pub current_task_id: Cell<i32>,
 	#[cfg(feature = "smp")]
 	pub hlt: AtomicBool,
 	/// Queues to handle incoming requests from the other cores
@@ -62,6 +67,9 @@ impl CoreLocal {
 			kernel_stack: Cell::new(ptr::null_mut()),
 			irq_statistics,
 			async_tasks: RefCell::new(Vec::new()),
+			watchpoint_generation: Cell::new(0),
+			#[cfg(feature = "common-os")]
+			current_task_id: Cell::new(0),
 			#[cfg(feature = "smp")]
 			hlt: AtomicBool::new(false),
 			#[cfg(feature = "smp")]
@@ -1,10 +1,14 @@
 use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use core::arch::asm;
 use core::sync::atomic::{AtomicU64, Ordering};
 
 use ahash::RandomState;
 use hashbrown::HashMap;
-use hermit_sync::{InterruptSpinMutex, InterruptTicketMutex, OnceCell};
+use hermit_sync::{InterruptSpinMutex, InterruptTicketMutex};
+#[cfg(feature = "sync-stats")]
+pub use x86_64::instructions::interrupts::are_enabled;
 #[cfg(not(feature = "idle-poll"))]
 use x86_64::instructions::interrupts::enable_and_hlt;
 pub use x86_64::instructions::interrupts::{disable, enable};
@@ -22,8 +26,9 @@ use crate::drivers::mmio::get_interrupt_handlers;
 #[cfg(feature = "pci")]
 use crate::drivers::pci::get_interrupt_handlers;
 use crate::scheduler::{self, CoreId};
+use crate::synch::rcu::Rcu;
 
-static IRQ_HANDLERS: OnceCell<HashMap<u8, InterruptHandlerQueue, RandomState>> = OnceCell::new();
+static IRQ_HANDLERS: Rcu<HashMap<u8, InterruptHandlerQueue, RandomState>> = Rcu::new();
 static IRQ_NAMES: InterruptTicketMutex<HashMap<u8, &'static str, RandomState>> =
 	InterruptTicketMutex::new(HashMap::with_hasher(RandomState::with_seeds(0, 0, 0, 0)));
 
@@ -162,7 +167,7 @@ pub(crate) fn install() {
 }
 
 pub(crate) fn install_handlers() {
-	IRQ_HANDLERS.set(get_interrupt_handlers()).unwrap();
+	IRQ_HANDLERS.replace(get_interrupt_handlers());
 }
 
 fn handle_interrupt(stack_frame: ExceptionStackFrame, index: u8, _error_code: Option<u64>) {
@@ -172,20 +177,20 @@ fn handle_interrupt(stack_frame: ExceptionStackFrame, index: u8, _error_code: Op
 	use crate::arch::kernel::core_local::core_scheduler;
 	use crate::scheduler::PerCoreSchedulerExt;
 
-	if let Some(handlers) = IRQ_HANDLERS.get() {
-		if let Some(map) = handlers.get(&(index - 32)) {
+	IRQ_HANDLERS.read(|handlers| {
+		if let Some(map) = handlers.and_then(|handlers| handlers.get(&(index - 32))) {
 			for handler in map.iter() {
 				handler();
 			}
 		}
-	}
+	});
 
 	apic::eoi();
 	increment_irq_counter(index);
 
 	crate::executor::run();
 
-	core_scheduler().reschedule();
+	core_scheduler().preempt();
 	crate::arch::x86_64::swapgs(&stack_frame);
 }
 
@@ -193,6 +198,8 @@ fn abort(stack_frame: ExceptionStackFrame, index: u8, error_code: Option<u64>) {
 	error!("Exception {index}");
 	error!("Error code: {error_code:?}");
 	error!("Stack frame: {stack_frame:#?}");
+	#[cfg(feature = "coredump")]
+	crate::coredump::write((&stack_frame).into(), crate::coredump::SIGSEGV);
 	scheduler::abort();
 }
 
@@ -204,6 +211,10 @@ extern "x86-interrupt" fn divide_error_exception(stack_frame: ExceptionStackFram
 
 extern "x86-interrupt" fn debug_exception(stack_frame: ExceptionStackFrame) {
 	swapgs(&stack_frame);
+	if super::watchpoint::handle_debug_exception(stack_frame.instruction_pointer.as_u64()) {
+		swapgs(&stack_frame);
+		return;
+	}
 	error!("Debug (#DB) Exception: {:#?}", stack_frame);
 	scheduler::abort();
 }
@@ -296,6 +307,8 @@ extern "x86-interrupt" fn general_protection_exception(
 		processor::readfs(),
 		processor::readgs()
 	);
+	#[cfg(feature = "coredump")]
+	crate::coredump::write((&stack_frame).into(), crate::coredump::SIGSEGV);
 	scheduler::abort();
 }
 
@@ -371,6 +384,24 @@ impl IrqStatistics {
 	}
 }
 
+/// Returns the number of received interrupts per core and interrupt.
+pub(crate) fn statistics() -> Vec<(CoreId, String, u64)> {
+	let mut statistics = Vec::new();
+	for (core_id, irg_statistics) in IRQ_COUNTERS.lock().iter() {
+		for (i, counter) in irg_statistics.counters.iter().enumerate() {
+			let counter = counter.load(Ordering::Relaxed);
+			if counter > 0 {
+				let name = match get_irq_name(i.try_into().unwrap()) {
+					Some(name) => name.to_string(),
+					None => i.to_string(),
+				};
+				statistics.push((*core_id, name, counter));
+			}
+		}
+	}
+	statistics
+}
+
 pub(crate) fn print_statistics() {
 	panic_println!("Number of interrupts");
 	for (core_id, irg_statistics) in IRQ_COUNTERS.lock().iter() {
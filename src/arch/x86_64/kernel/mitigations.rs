@@ -0,0 +1,97 @@
+//! Mitigations based on `IA32_SPEC_CTRL` and `IA32_PRED_CMD`
+
+use core::arch::asm;
+use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+use x86_64::registers::model_specific::Msr;
+
+use crate::mitigations::Mitigations;
+
+const IA32_SPEC_CTRL: u32 = 0x48;
+const IA32_PRED_CMD: u32 = 0x49;
+
+const SPEC_CTRL_IBRS: u64 = 1 << 0;
+const SPEC_CTRL_STIBP: u64 = 1 << 1;
+const PRED_CMD_IBPB: u64 = 1 << 0;
+
+/// `CPUID.(EAX=07H,ECX=0):EDX` bit, which enumerates IBRS and IBPB
+const CPUID_SPEC_CTRL: u32 = 1 << 26;
+/// `CPUID.(EAX=07H,ECX=0):EDX` bit, which enumerates STIBP
+const CPUID_STIBP: u32 = 1 << 27;
+
+/// Number of entries, which overwrite the Return Stack Buffer
+const RSB_ENTRIES: usize = 32;
+
+/// Returns the mitigations, which are supported by the processor.
+pub(crate) fn supported() -> Mitigations {
+	let mut supported = Mitigations::RSB;
+
+	if unsafe { __cpuid(0) }.eax >= 7 {
+		let edx = unsafe { __cpuid_count(7, 0) }.edx;
+		if edx & CPUID_SPEC_CTRL != 0 {
+			supported |= Mitigations::IBRS | Mitigations::IBPB;
+		}
+		if edx & CPUID_STIBP != 0 {
+			supported |= Mitigations::STIBP;
+		}
+	}
+
+	supported
+}
+
+/// Enables `mitigations` on the current core.
+pub(crate) fn enable(mitigations: Mitigations) {
+	let mut spec_ctrl = 0;
+	if mitigations.contains(Mitigations::IBRS) {
+		spec_ctrl |= SPEC_CTRL_IBRS;
+	}
+	if mitigations.contains(Mitigations::STIBP) {
+		spec_ctrl |= SPEC_CTRL_STIBP;
+	}
+
+	if spec_ctrl != 0 {
+		let mut msr = Msr::new(IA32_SPEC_CTRL);
+		unsafe {
+			let value = msr.read();
+			msr.write(value | spec_ctrl);
+		}
+	}
+}
+
+/// Applies `mitigations` on a switch to another task.
+#[inline]
+pub(crate) fn task_switch(mitigations: Mitigations) {
+	if mitigations.contains(Mitigations::IBPB) {
+		unsafe {
+			Msr::new(IA32_PRED_CMD).write(PRED_CMD_IBPB);
+		}
+	}
+
+	if mitigations.contains(Mitigations::RSB) {
+		fill_return_stack_buffer();
+	}
+}
+
+/// Overwrites the Return Stack Buffer with entries, which point to a speculation trap.
+#[inline(always)]
+fn fill_return_stack_buffer() {
+	unsafe {
+		asm!(
+			"mov {counter}, {entries}",
+			"2:",
+			"call 4f",
+			"3:",
+			"pause",
+			"lfence",
+			"jmp 3b",
+			"4:",
+			"dec {counter}",
+			"jnz 2b",
+			// Remove the return addresses from the stack
+			"add rsp, {stack_size}",
+			entries = const RSB_ENTRIES,
+			stack_size = const RSB_ENTRIES * 8,
+			counter = out(reg) _,
+		);
+	}
+}
@@ -4,8 +4,8 @@ use core::ptr::NonNull;
 use core::{ptr, str};
 
 use align_address::Align;
-use hermit_sync::{InterruptTicketMutex, without_interrupts};
-use memory_addresses::PhysAddr;
+use hermit_sync::InterruptTicketMutex;
+use memory_addresses::{PhysAddr, VirtAddr};
 use virtio::mmio::{DeviceRegisters, DeviceRegistersVolatileFieldAccess};
 use volatile::VolatileRef;
 
@@ -13,11 +13,17 @@ use crate::arch::x86_64::mm::paging;
 use crate::arch::x86_64::mm::paging::{
 	BasePageSize, PageSize, PageTableEntryFlags, PageTableEntryFlagsExt,
 };
+#[cfg(feature = "blk")]
+use crate::drivers::block::virtio::VirtioBlkDriver;
+#[cfg(feature = "console")]
+use crate::drivers::console::VirtioConsoleDriver;
+#[cfg(any(feature = "tcp", feature = "udp"))]
 use crate::drivers::net::virtio::VirtioNetDriver;
 use crate::drivers::virtio::transport::mmio as mmio_virtio;
 use crate::drivers::virtio::transport::mmio::VirtioDriver;
 use crate::env;
 use crate::init_cell::InitCell;
+use crate::synch::without_interrupts;
 
 pub const MAGIC_VALUE: u32 = 0x7472_6976;
 
@@ -28,17 +34,54 @@ const IRQ_NUMBER: u8 = 44 - 32;
 static MMIO_DRIVERS: InitCell<Vec<MmioDriver>> = InitCell::new(Vec::new());
 
 pub(crate) enum MmioDriver {
+	#[cfg(any(feature = "tcp", feature = "udp"))]
 	VirtioNet(InterruptTicketMutex<VirtioNetDriver>),
+	#[cfg(feature = "blk")]
+	VirtioBlk(InterruptTicketMutex<VirtioBlkDriver>),
+	#[cfg(feature = "console")]
+	VirtioConsole(InterruptTicketMutex<VirtioConsoleDriver>),
 }
 
 impl MmioDriver {
-	#[allow(unreachable_patterns)]
+	#[cfg(any(feature = "tcp", feature = "udp"))]
+	#[allow(unreachable_patterns, clippy::match_wildcard_for_single_variants)]
 	fn get_network_driver(&self) -> Option<&InterruptTicketMutex<VirtioNetDriver>> {
 		match self {
 			Self::VirtioNet(drv) => Some(drv),
 			_ => None,
 		}
 	}
+
+	#[cfg(feature = "blk")]
+	#[allow(unreachable_patterns, clippy::match_wildcard_for_single_variants)]
+	fn get_block_driver(&self) -> Option<&InterruptTicketMutex<VirtioBlkDriver>> {
+		match self {
+			Self::VirtioBlk(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "console")]
+	#[allow(unreachable_patterns, clippy::match_wildcard_for_single_variants)]
+	fn get_console_driver(&self) -> Option<&InterruptTicketMutex<VirtioConsoleDriver>> {
+		match self {
+			Self::VirtioConsole(drv) => Some(drv),
+			_ => None,
+		}
+	}
+}
+
+/// Returns `true`, if a driver for the virtio device `id` is built into the kernel.
+fn is_supported(id: virtio::Id) -> bool {
+	match id {
+		#[cfg(any(feature = "tcp", feature = "udp"))]
+		virtio::Id::Net => true,
+		#[cfg(feature = "blk")]
+		virtio::Id::Block => true,
+		#[cfg(feature = "console")]
+		virtio::Id::Console => true,
+		_ => false,
+	}
 }
 
 unsafe fn check_ptr(ptr: *mut u8) -> Option<VolatileRef<'static, DeviceRegisters>> {
@@ -61,11 +104,11 @@ unsafe fn check_ptr(ptr: *mut u8) -> Option<VolatileRef<'static, DeviceRegisters
 	// We found a MMIO-device (whose 512-bit address in this structure).
 	trace!("Found a MMIO-device at {mmio:p}");
 
-	// Verify the device-ID to find the network card
+	// Verify the device-ID to find a supported device
 	let id = mmio.as_ptr().device_id().read();
 
-	if id != virtio::Id::Net {
-		trace!("It's not a network card at {mmio:p}");
+	if !is_supported(id) {
+		trace!("Device {id:?} at {mmio:p} is not supported");
 		return None;
 	}
 
@@ -74,15 +117,18 @@ unsafe fn check_ptr(ptr: *mut u8) -> Option<VolatileRef<'static, DeviceRegisters
 
 fn check_linux_args(
 	linux_mmio: &'static [String],
-) -> Result<(VolatileRef<'static, DeviceRegisters>, u8), &'static str> {
-	let virtual_address =
-		crate::arch::mm::virtualmem::allocate(BasePageSize::SIZE as usize).unwrap();
+) -> Vec<(VolatileRef<'static, DeviceRegisters>, u8)> {
+	let mut devices = Vec::new();
 
 	for arg in linux_mmio {
 		trace!("check linux parameter: {}", arg);
 
 		match arg.trim().trim_matches(char::from(0)).strip_prefix("4K@") {
 			Some(arg) => {
+				// Each device keeps its own mapping.
+				let virtual_address =
+					crate::arch::mm::virtualmem::allocate(BasePageSize::SIZE as usize).unwrap();
+
 				let v: Vec<&str> = arg.trim().split(':').collect();
 				let without_prefix = v[0].trim_start_matches("0x");
 				let current_address = usize::from_str_radix(without_prefix, 16).unwrap();
@@ -106,6 +152,11 @@ fn check_linux_args(
 					| (current_address & (BasePageSize::SIZE as usize - 1));
 				let ptr = ptr::with_exposed_provenance_mut(addr);
 				let Some(mmio) = (unsafe { check_ptr(ptr) }) else {
+					// frees obsolete virtual memory region for MMIO devices
+					crate::arch::mm::virtualmem::deallocate(
+						virtual_address,
+						BasePageSize::SIZE as usize,
+					);
 					continue;
 				};
 
@@ -114,7 +165,7 @@ fn check_linux_args(
 					BasePageSize::SIZE as usize,
 				);
 
-				return Ok((mmio, irq));
+				devices.push((mmio, irq));
 			}
 			_ => {
 				warn!("Invalid prefix in {}", arg);
@@ -122,17 +173,33 @@ fn check_linux_args(
 		}
 	}
 
-	// frees obsolete virtual memory region for MMIO devices
-	crate::arch::mm::virtualmem::deallocate(virtual_address, BasePageSize::SIZE as usize);
+	devices
+}
 
-	Err("Network card not found!")
+/// Maps the page of the physical address `address` to `virtual_address` and
+/// returns the pointer to `address`.
+fn map_probe(virtual_address: VirtAddr, address: usize) -> *mut u8 {
+	let mut flags = PageTableEntryFlags::empty();
+	flags.normal().writable();
+	paging::map::<BasePageSize>(
+		virtual_address,
+		PhysAddr::from(address.align_down(BasePageSize::SIZE as usize)),
+		1,
+		flags,
+	);
+
+	let addr = virtual_address.as_usize() | (address & (BasePageSize::SIZE as usize - 1));
+	ptr::with_exposed_provenance_mut(addr)
 }
 
+/// Returns the network device within the specified address range or, if
+/// there is none, the first supported device.
 fn guess_device() -> Result<(VolatileRef<'static, DeviceRegisters>, u8), &'static str> {
 	// Trigger page mapping in the first iteration!
 	let mut current_page = 0;
 	let virtual_address =
 		crate::arch::mm::virtualmem::allocate(BasePageSize::SIZE as usize).unwrap();
+	let mut fallback = None;
 
 	// Look for the device-ID in all possible 64-byte aligned addresses within this range.
 	for current_address in (MMIO_START..MMIO_END).step_by(512) {
@@ -141,17 +208,8 @@ fn guess_device() -> Result<(VolatileRef<'static, DeviceRegisters>, u8), &'stati
 			current_address
 		);
 		// Have we crossed a page boundary in the last iteration?
-		// info!("before the {}. paging", current_page);
 		if current_address / BasePageSize::SIZE as usize > current_page {
-			let mut flags = PageTableEntryFlags::empty();
-			flags.normal().writable();
-			paging::map::<BasePageSize>(
-				virtual_address,
-				PhysAddr::from(current_address.align_down(BasePageSize::SIZE as usize)),
-				1,
-				flags,
-			);
-
+			map_probe(virtual_address, current_address);
 			current_page = current_address / BasePageSize::SIZE as usize;
 		}
 
@@ -162,7 +220,27 @@ fn guess_device() -> Result<(VolatileRef<'static, DeviceRegisters>, u8), &'stati
 			continue;
 		};
 
-		info!("Found network card at {mmio:p}");
+		// The guessed interrupt number belongs to the network device, so it is preferred.
+		if mmio.as_ptr().device_id().read() != virtio::Id::Net {
+			fallback.get_or_insert(current_address);
+			continue;
+		}
+
+		info!("Found MMIO device at {mmio:p}");
+
+		crate::arch::mm::physicalmem::reserve(
+			PhysAddr::from(current_address.align_down(BasePageSize::SIZE as usize)),
+			BasePageSize::SIZE as usize,
+		);
+
+		return Ok((mmio, IRQ_NUMBER));
+	}
+
+	if let Some(current_address) = fallback {
+		let ptr = map_probe(virtual_address, current_address);
+		let mmio = unsafe { check_ptr(ptr) }.unwrap();
+
+		info!("Found MMIO device at {mmio:p}");
 
 		crate::arch::mm::physicalmem::reserve(
 			PhysAddr::from(current_address.align_down(BasePageSize::SIZE as usize)),
@@ -175,16 +253,17 @@ fn guess_device() -> Result<(VolatileRef<'static, DeviceRegisters>, u8), &'stati
 	// frees obsolete virtual memory region for MMIO devices
 	crate::arch::mm::virtualmem::deallocate(virtual_address, BasePageSize::SIZE as usize);
 
-	Err("Network card not found!")
+	Err("MMIO device not found!")
 }
 
-/// Tries to find the network device within the specified address range.
-/// Returns a reference to it within the Ok() if successful or an Err() on failure.
-fn detect_network() -> Result<(VolatileRef<'static, DeviceRegisters>, u8), &'static str> {
+/// Finds the supported devices, which are passed on the kernel command line.
+/// Without devices on the command line, the first device within the
+/// specified address range is used.
+fn detect_devices() -> Vec<(VolatileRef<'static, DeviceRegisters>, u8)> {
 	let linux_mmio = env::mmio();
 
 	if linux_mmio.is_empty() {
-		guess_device()
+		guess_device().into_iter().collect()
 	} else {
 		check_linux_args(linux_mmio)
 	}
@@ -194,6 +273,7 @@ pub(crate) fn register_driver(drv: MmioDriver) {
 	MMIO_DRIVERS.with(|mmio_drivers| mmio_drivers.unwrap().push(drv));
 }
 
+#[cfg(any(feature = "tcp", feature = "udp"))]
 pub(crate) fn get_network_driver() -> Option<&'static InterruptTicketMutex<VirtioNetDriver>> {
 	MMIO_DRIVERS
 		.get()?
@@ -201,22 +281,54 @@ pub(crate) fn get_network_driver() -> Option<&'static InterruptTicketMutex<Virti
 		.find_map(|drv| drv.get_network_driver())
 }
 
+#[cfg(feature = "blk")]
+pub(crate) fn get_block_drivers() -> Vec<&'static InterruptTicketMutex<VirtioBlkDriver>> {
+	MMIO_DRIVERS.get().map_or_else(Vec::new, |drivers| {
+		drivers
+			.iter()
+			.filter_map(|drv| drv.get_block_driver())
+			.collect()
+	})
+}
+
+#[cfg(feature = "console")]
+pub(crate) fn get_console_driver() -> Option<&'static InterruptTicketMutex<VirtioConsoleDriver>> {
+	MMIO_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_console_driver())
+}
+
 pub(crate) fn init_drivers() {
 	// virtio: MMIO Device Discovery
 	without_interrupts(|| {
-		if let Ok((mmio, irq)) = detect_network() {
-			warn!(
-				"Found MMIO device, but we guess the interrupt number {}!",
-				irq
-			);
+		let devices = detect_devices();
+		if devices.is_empty() {
+			warn!("Unable to find mmio device");
+		}
+
+		for (mmio, irq) in devices {
+			if env::mmio().is_empty() {
+				warn!(
+					"Found MMIO device, but we guess the interrupt number {}!",
+					irq
+				);
+			}
 			match mmio_virtio::init_device(mmio, irq) {
+				#[cfg(any(feature = "tcp", feature = "udp"))]
 				Ok(VirtioDriver::Network(drv)) => {
 					register_driver(MmioDriver::VirtioNet(InterruptTicketMutex::new(drv)));
 				}
+				#[cfg(feature = "blk")]
+				Ok(VirtioDriver::Block(drv)) => {
+					register_driver(MmioDriver::VirtioBlk(InterruptTicketMutex::new(drv)));
+				}
+				#[cfg(feature = "console")]
+				Ok(VirtioDriver::Console(drv)) => {
+					register_driver(MmioDriver::VirtioConsole(InterruptTicketMutex::new(drv)));
+				}
 				Err(err) => error!("Could not initialize virtio-mmio device: {err}"),
 			}
-		} else {
-			warn!("Unable to find mmio device");
 		}
 
 		MMIO_DRIVERS.finalize();
@@ -1,16 +1,19 @@
 #[cfg(feature = "common-os")]
 use core::arch::asm;
 use core::num::NonZeroU64;
+use core::ops::Range;
 use core::ptr;
 use core::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
 use core::task::Waker;
 
 use hermit_entry::boot_info::{PlatformInfo, RawBootInfo};
 use memory_addresses::{PhysAddr, VirtAddr};
+use multiboot::information::Multiboot;
 use x86_64::registers::control::{Cr0, Cr4};
 
 use self::serial::SerialPort;
 use crate::arch::x86_64::kernel::core_local::*;
+use crate::arch::x86_64::mm::MultibootMemory;
 use crate::env::{self, is_uhyve};
 
 #[cfg(feature = "acpi")]
@@ -19,7 +22,11 @@ pub mod apic;
 pub mod core_local;
 pub mod gdt;
 pub mod interrupts;
-#[cfg(all(not(feature = "pci"), any(feature = "tcp", feature = "udp")))]
+pub(crate) mod mitigations;
+#[cfg(all(
+	not(feature = "pci"),
+	any(feature = "tcp", feature = "udp", feature = "blk", feature = "console")
+))]
 pub mod mmio;
 #[cfg(feature = "pci")]
 pub mod pci;
@@ -36,6 +43,7 @@ mod syscall;
 pub(crate) mod systemtime;
 #[cfg(feature = "vga")]
 mod vga;
+pub(crate) mod watchpoint;
 
 pub(crate) struct Console {
 	serial_port: SerialPort,
@@ -115,6 +123,16 @@ pub fn get_mbinfo() -> Option<NonZeroU64> {
 	}
 }
 
+/// Returns the physical address range of the Multiboot module `index`.
+pub fn get_boot_module(index: usize) -> Option<Range<u64>> {
+	let mb_info = get_mbinfo()?.get();
+
+	let mut mem = MultibootMemory;
+	let mb = unsafe { Multiboot::from_ptr(mb_info, &mut mem)? };
+	let module = mb.modules()?.nth(index)?;
+	Some(module.start..module.end)
+}
+
 #[cfg(feature = "smp")]
 pub fn get_possible_cpus() -> u32 {
 	use core::cmp;
@@ -215,7 +233,7 @@ fn finish_processor_init() {
 		// their APIC IDs in advance.
 		// Therefore, we have to add each booted processor into the CPU_LOCAL_APIC_IDS vector ourselves.
 		// Fortunately, the Local APIC IDs of uhyve are sequential and therefore match the Core IDs.
-		apic::add_local_apic_id(core_id() as u8);
+		apic::add_local_apic_id(core_id());
 
 		// uhyve also boots each processor into _start itself and does not use apic::boot_application_processors.
 		// Therefore, the current processor already needs to prepare the processor variables for a possible next processor.
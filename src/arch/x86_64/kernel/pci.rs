@@ -1,13 +1,24 @@
+use alloc::vec::Vec;
+
 use pci_types::{ConfigRegionAccess, PciAddress, PciHeader};
 use x86_64::instructions::port::Port;
 
+use crate::drivers::pci::resource::{self, PciWindow, PciWindows};
 use crate::drivers::pci::{PCI_DEVICES, PciDevice};
+use crate::env;
 
 const PCI_MAX_BUS_NUMBER: u8 = 32;
 const PCI_MAX_DEVICE_NUMBER: u8 = 32;
 
 const PCI_CONFIG_ADDRESS_ENABLE: u32 = 1 << 31;
 
+/// Lowest start of the memory window for unassigned BARs
+const PCI_MEM32_START: u64 = 0xc000_0000;
+/// End of the memory window for unassigned BARs (start of the I/O APIC)
+const PCI_MEM32_END: u64 = 0xfec0_0000;
+/// I/O ports, which are available for unassigned BARs
+const PCI_IO_WINDOW: core::ops::Range<u64> = 0xc000..0x1_0000;
+
 const CONFIG_ADDRESS: Port<u32> = Port::new(0xcf8);
 const CONFIG_DATA: Port<u32> = Port::new(0xcfc);
 
@@ -56,6 +67,28 @@ impl ConfigRegionAccess for PciConfigRegion {
 	}
 }
 
+/// Returns the windows, from which unassigned BARs are allocated.
+///
+/// Without firmware tables, we use the memory between the end of RAM and
+/// the I/O APIC as well as the upper quarter of the I/O ports.
+fn windows() -> PciWindows {
+	let mem32_start = env::boot_info()
+		.hardware_info
+		.phys_addr_range
+		.end
+		.max(PCI_MEM32_START);
+
+	PciWindows {
+		io: Some(PCI_IO_WINDOW),
+		// PCI bus addresses equal CPU addresses
+		mem32: (mem32_start < PCI_MEM32_END).then_some(PciWindow {
+			pci: mem32_start..PCI_MEM32_END,
+			cpu_start: mem32_start,
+		}),
+		mem64: None,
+	}
+}
+
 pub(crate) fn init() {
 	debug!("Scanning PCI Busses 0 to {}", PCI_MAX_BUS_NUMBER - 1);
 
@@ -63,6 +96,7 @@ pub(crate) fn init() {
 	// Therefore, multifunction devices as well as additional bridges are not scanned.
 	// We also limit scanning to the first 32 buses.
 	let pci_config = PciConfigRegion::new();
+	let mut devices = Vec::new();
 	for bus in 0..PCI_MAX_BUS_NUMBER {
 		for device in 0..PCI_MAX_DEVICE_NUMBER {
 			let pci_address = PciAddress::new(0, bus, device, 0);
@@ -70,9 +104,11 @@ pub(crate) fn init() {
 
 			let (device_id, vendor_id) = header.id(pci_config);
 			if device_id != u16::MAX && vendor_id != u16::MAX {
-				let device = PciDevice::new(pci_address, pci_config);
-				PCI_DEVICES.with(|pci_devices| pci_devices.unwrap().push(device));
+				devices.push(PciDevice::new(pci_address, pci_config));
 			}
 		}
 	}
+
+	resource::assign(&devices, windows());
+	PCI_DEVICES.with(|pci_devices| pci_devices.unwrap().extend(devices));
 }
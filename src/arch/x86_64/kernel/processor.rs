@@ -869,7 +869,7 @@ pub fn configure() {
 			#[cfg(feature = "fsgsbase")]
 			if !supports_fsgs() {
 				error!("FSGSBASE support is enabled, but the processor doesn't support it!");
-				crate::scheduler::shutdown(1);
+				crate::syscalls::shutdown_immediately(1);
 			}
 
 			debug!("Setting CR4 = {:?}", flags);
@@ -1087,6 +1087,21 @@ pub fn shutdown(error_code: i32) -> ! {
 	triple_fault()
 }
 
+/// Reset the system
+pub fn reboot() -> ! {
+	#[cfg(feature = "acpi")]
+	{
+		acpi::reset();
+	}
+
+	// pulse the reset line of the keyboard controller
+	unsafe {
+		Port::<u8>::new(0x64).write(0xfe);
+	}
+
+	triple_fault()
+}
+
 pub fn get_timer_ticks() -> u64 {
 	// We simulate a timer with a 1 microsecond resolution by taking the CPU timestamp
 	// and dividing it by the CPU frequency in MHz.
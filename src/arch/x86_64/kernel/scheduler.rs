@@ -248,6 +248,8 @@ impl Drop for TaskStacks {
 pub struct TaskTLS {
 	_block: Box<[MaybeUninit<u8>]>,
 	thread_ptr: *mut (),
+	/// Guard values in front of the TLS blocks and behind the TCB
+	guards: [*mut u64; 2],
 }
 
 #[cfg(not(feature = "common-os"))]
@@ -256,7 +258,7 @@ impl TaskTLS {
 	//
 	// “ELF Handling For Thread-Local Storage” Section 3.4.6: x86-64 Specific Definitions for Run-Time Handling of TLS
 	// https://akkadia.org/drepper/tls.pdf
-	fn from_environment() -> Option<Box<Self>> {
+	fn from_environment(canary: u64) -> Option<Box<Self>> {
 		let tls_info = env::boot_info().load_info.tls_info?;
 		assert_ne!(tls_info.memsz, 0);
 
@@ -270,9 +272,12 @@ impl TaskTLS {
 		};
 
 		// As described in “ELF Handling For Thread-Local Storage”
-		let tls_offset = usize::try_from(tls_info.memsz)
-			.unwrap()
-			.align_up(usize::try_from(tls_info.align).unwrap());
+		let tls_align = usize::try_from(tls_info.align).unwrap();
+		let tls_offset = usize::try_from(tls_info.memsz).unwrap().align_up(tls_align);
+
+		// The TLS blocks are preceded by a guard value, which keeps the alignment of the blocks.
+		let guard_size = mem::size_of::<u64>();
+		let tls_start = guard_size.align_up(tls_align);
 
 		// Allocate TLS block
 		let mut block = {
@@ -282,24 +287,39 @@ impl TaskTLS {
 			// Since the thread pointer points to the end of the TLS blocks, we need to store it there.
 			let tcb_size = mem::size_of::<*mut ()>();
 
-			vec![MaybeUninit::<u8>::uninit(); tls_offset + tcb_size].into_boxed_slice()
+			vec![MaybeUninit::<u8>::uninit(); tls_start + tls_offset + tcb_size + guard_size]
+				.into_boxed_slice()
 		};
+		let tls_end = tls_start + tls_offset;
 
 		// Initialize beginning of the TLS block with TLS initialization image
-		block[..tls_init_image.len()].copy_from_slice(tls_init_image);
+		block[tls_start..tls_start + tls_init_image.len()].copy_from_slice(tls_init_image);
 
 		// Fill the rest of the block with zeros
-		block[tls_init_image.len()..tls_offset].fill(MaybeUninit::new(0));
+		block[tls_start + tls_init_image.len()..tls_end].fill(MaybeUninit::new(0));
 
-		// thread_ptr = block_ptr + tls_offset
-		let thread_ptr = block[tls_offset..].as_mut_ptr().cast::<()>();
+		// thread_ptr = block_ptr + tls_start + tls_offset
+		let thread_ptr = block[tls_end..].as_mut_ptr().cast::<()>();
 		unsafe {
 			thread_ptr.cast::<*mut ()>().write_unaligned(thread_ptr);
 		}
 
+		let guards = [
+			block[tls_start - guard_size..].as_mut_ptr().cast::<u64>(),
+			block[tls_end + mem::size_of::<*mut ()>()..]
+				.as_mut_ptr()
+				.cast::<u64>(),
+		];
+		for guard in guards {
+			unsafe {
+				guard.write_unaligned(canary);
+			}
+		}
+
 		let this = Self {
 			_block: block,
 			thread_ptr,
+			guards,
 		};
 		Some(Box::new(this))
 	}
@@ -307,6 +327,16 @@ impl TaskTLS {
 	fn thread_ptr(&self) -> *mut () {
 		self.thread_ptr
 	}
+
+	/// Returns `true`, if the guard values and the TCB have not been overwritten.
+	pub fn is_intact(&self, canary: u64) -> bool {
+		let tcb = unsafe { self.thread_ptr.cast::<*mut ()>().read_unaligned() };
+		tcb == self.thread_ptr
+			&& self
+				.guards
+				.iter()
+				.all(|guard| unsafe { guard.read_unaligned() } == canary)
+	}
 }
 
 #[cfg(not(target_os = "none"))]
@@ -332,6 +362,9 @@ extern "C" fn task_start(_f: extern "C" fn(usize), _arg: usize, _user_stack: u64
 }
 
 extern "C" fn task_entry(func: extern "C" fn(usize), arg: usize) -> ! {
+	// The stacks of the previous task are no longer in use.
+	core_scheduler().finish_task_switch();
+
 	// Call the actual entry point of the task.
 	func(arg);
 
@@ -345,7 +378,7 @@ impl TaskFrame for Task {
 		// Check if TLS is allocated already and if the task uses thread-local storage.
 		#[cfg(not(feature = "common-os"))]
 		if self.tls.is_none() {
-			self.tls = TaskTLS::from_environment();
+			self.tls = TaskTLS::from_environment(self.canary);
 		}
 
 		unsafe {
@@ -385,7 +418,7 @@ extern "x86-interrupt" fn timer_handler(_stack_frame: interrupts::ExceptionStack
 	increment_irq_counter(apic::TIMER_INTERRUPT_NUMBER);
 	core_scheduler().handle_waiting_tasks();
 	apic::eoi();
-	core_scheduler().reschedule();
+	core_scheduler().preempt();
 }
 
 pub fn install_timer_handler() {
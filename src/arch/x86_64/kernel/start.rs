@@ -27,6 +27,19 @@ pub unsafe extern "C" fn _start(_boot_info: Option<&'static RawBootInfo>, cpu_id
 
 	unsafe {
 		naked_asm!(
+			// The boot processor relocates the kernel, before the GOT is used.
+			"test esi, esi",
+			"jnz 4f",
+			"mov r12, rdi",
+			"mov r13, rsi",
+			"mov r14, rsp",
+			"and rsp, -16",
+			"call {relocate}",
+			"mov rsp, r14",
+			"mov rsi, r13",
+			"mov rdi, r12",
+			"4:",
+
 			// use core::sync::atomic::{AtomicU32, Ordering};
 			//
 			// pub static CPU_ONLINE: AtomicU32 = AtomicU32::new(0);
@@ -61,6 +74,7 @@ pub unsafe extern "C" fn _start(_boot_info: Option<&'static RawBootInfo>, cpu_id
 			current_stack_address = sym super::CURRENT_STACK_ADDRESS,
 			stack_top_offset = const KERNEL_STACK_SIZE - TaskStacks::MARKER_SIZE,
 			pre_init = sym pre_init,
+			relocate = sym crate::arch::relocate::relocate,
 		)
 	}
 }
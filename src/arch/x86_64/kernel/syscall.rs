@@ -2,13 +2,23 @@ use core::arch::naked_asm;
 use core::mem;
 
 use super::core_local::CoreLocal;
-use crate::syscalls::table::SYSHANDLER_TABLE;
+use crate::syscalls::table::{SYSHANDLER_TABLE, SYSNO_GETPID, SYSNO_GETTID};
 
+/// Entry point of the system calls
+///
+/// `getpid` and `gettid` only return a constant or a field of the core-local
+/// data, so they are answered before the context is saved and the stack is
+/// switched. All other system calls are dispatched through the system call table.
 #[unsafe(no_mangle)]
 #[naked]
 pub(crate) unsafe extern "C" fn syscall_handler() -> ! {
 	unsafe {
 		naked_asm!(
+			// fast path, which preserves all registers except rax
+			"cmp rax, {sysno_getpid}",
+			"je 2f",
+			"cmp rax, {sysno_gettid}",
+			"je 3f",
 			// save context, see x86_64 ABI
 			"push rcx",
 			"push rdx",
@@ -44,8 +54,21 @@ pub(crate) unsafe extern "C" fn syscall_handler() -> ! {
 			"pop rdx",
 			"pop rcx",
 			"sysretq",
+			// getpid
+			"2:",
+			"xor eax, eax",
+			"sysretq",
+			// gettid
+			"3:",
+			"swapgs",
+			"movsxd rax, dword ptr gs:{core_local_current_task_id}",
+			"swapgs",
+			"sysretq",
 			core_local_kernel_stack = const mem::offset_of!(CoreLocal, kernel_stack),
+			core_local_current_task_id = const mem::offset_of!(CoreLocal, current_task_id),
 			table = sym SYSHANDLER_TABLE,
+			sysno_getpid = const SYSNO_GETPID,
+			sysno_gettid = const SYSNO_GETTID,
 		);
 	}
 }
@@ -1,12 +1,13 @@
 use core::hint::spin_loop;
 
 use hermit_entry::boot_info::PlatformInfo;
-use hermit_sync::{OnceCell, without_interrupts};
+use hermit_sync::OnceCell;
 use time::OffsetDateTime;
 use x86_64::instructions::port::Port;
 
 use crate::arch::x86_64::kernel::processor;
 use crate::env;
+use crate::synch::without_interrupts;
 
 const CMOS_COMMAND: Port<u8> = Port::new(0x70);
 const CMOS_DATA: Port<u8> = Port::new(0x71);
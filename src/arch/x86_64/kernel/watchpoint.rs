@@ -0,0 +1,66 @@
+//! Watchpoints based on the debug registers `DR0`-`DR3`
+
+use x86_64::registers::debug::{
+	BreakpointCondition, BreakpointSize, DebugAddressRegister, DebugAddressRegisterNumber, Dr0,
+	Dr1, Dr2, Dr3, Dr6, Dr6Flags, Dr7, Dr7Flags, Dr7Value,
+};
+
+use crate::watchpoint::{MAX_WATCHPOINTS, WatchKind, Watchpoint};
+
+/// Returns the number of watchpoints, which are supported by the processor.
+pub(crate) fn slots() -> usize {
+	4
+}
+
+/// Loads `watchpoints` into the debug registers of the current core.
+pub(crate) fn install(watchpoints: &[Option<Watchpoint>; MAX_WATCHPOINTS]) {
+	let mut dr7 = Dr7Value::from(Dr7Flags::empty());
+
+	for (slot, watchpoint) in watchpoints.iter().enumerate() {
+		let Some(watchpoint) = watchpoint else {
+			continue;
+		};
+		let n = DebugAddressRegisterNumber::new(slot.try_into().unwrap()).unwrap();
+		let addr = watchpoint.addr.as_u64();
+		match n {
+			DebugAddressRegisterNumber::Dr0 => Dr0::write(addr),
+			DebugAddressRegisterNumber::Dr1 => Dr1::write(addr),
+			DebugAddressRegisterNumber::Dr2 => Dr2::write(addr),
+			DebugAddressRegisterNumber::Dr3 => Dr3::write(addr),
+		}
+
+		let condition = match watchpoint.kind {
+			WatchKind::Write => BreakpointCondition::DataWrites,
+			WatchKind::ReadWrite => BreakpointCondition::DataReadsWrites,
+		};
+		dr7.set_condition(n, condition);
+		dr7.set_size(n, BreakpointSize::new(watchpoint.len).unwrap());
+		dr7.insert_flags(Dr7Flags::global_breakpoint_enable(n));
+	}
+
+	Dr7::write(dr7);
+}
+
+/// Handles a debug exception and returns `true` if it was caused by a watchpoint.
+///
+/// Data breakpoints are traps, i.e., the access has already been completed and
+/// the execution can be continued.
+pub(crate) fn handle_debug_exception(rip: u64) -> bool {
+	let dr6 = Dr6::read();
+	let mut handled = false;
+
+	for slot in 0..MAX_WATCHPOINTS {
+		let n = DebugAddressRegisterNumber::new(slot.try_into().unwrap()).unwrap();
+		if dr6.contains(Dr6Flags::trap(n)) {
+			crate::watchpoint::report_hit(slot, rip);
+			handled = true;
+		}
+	}
+
+	// DR6 is never cleared by the processor
+	unsafe {
+		core::arch::asm!("mov dr6, {}", in(reg) 0u64, options(nomem, nostack, preserves_flags));
+	}
+
+	handled
+}
@@ -13,7 +13,7 @@ pub use self::paging::init_page_tables;
 use crate::arch::mm::paging::{PageTableEntryFlags, PageTableEntryFlagsExt};
 
 /// Memory translation, allocation and deallocation for MultibootInformation
-struct MultibootMemory;
+pub(crate) struct MultibootMemory;
 
 impl multiboot::information::MemoryManagement for MultibootMemory {
 	unsafe fn paddr_to_slice(
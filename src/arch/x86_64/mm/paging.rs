@@ -133,7 +133,15 @@ pub fn virtual_to_physical(virtual_address: VirtAddr) -> Option<PhysAddr> {
 	}
 }
 
-#[cfg(any(feature = "fuse", feature = "vsock", feature = "tcp", feature = "udp"))]
+#[cfg(any(
+	feature = "blk",
+	feature = "console",
+	feature = "fuse",
+	feature = "pmem",
+	feature = "vsock",
+	feature = "tcp",
+	feature = "udp"
+))]
 pub fn virt_to_phys(virtual_address: VirtAddr) -> PhysAddr {
 	virtual_to_physical(virtual_address).unwrap()
 }
@@ -181,7 +189,7 @@ pub fn map<S>(
 		M: Mapper<S>,
 		S: PageSize + Debug,
 	{
-		let mut frame_allocator = physicalmem::PHYSICAL_FREE_LIST.lock();
+		let mut frame_allocator = tracked_lock!(physicalmem::PHYSICAL_FREE_LIST, "physical memory");
 		let mut unmapped = false;
 		for (page, frame) in pages.zip(frames) {
 			// TODO: Require explicit unmaps
@@ -246,7 +254,7 @@ where
 		frame.start_address()
 	);
 
-	let mut frame_allocator = physicalmem::PHYSICAL_FREE_LIST.lock();
+	let mut frame_allocator = tracked_lock!(physicalmem::PHYSICAL_FREE_LIST, "physical memory");
 	unsafe {
 		recursive_page_table()
 			.identity_map(
@@ -298,6 +306,8 @@ pub(crate) extern "x86-interrupt" fn page_fault_handler(
 	error!("fs = {:#X}", processor::readfs());
 	error!("gs = {:#X}", processor::readgs());
 	error!("stack_frame = {stack_frame:#?}");
+	#[cfg(feature = "coredump")]
+	crate::coredump::write((&stack_frame).into(), crate::coredump::SIGSEGV);
 	scheduler::abort();
 }
 
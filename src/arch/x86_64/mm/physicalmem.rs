@@ -5,9 +5,10 @@ use hermit_sync::InterruptTicketMutex;
 use memory_addresses::{PhysAddr, VirtAddr};
 use multiboot::information::{MemoryType, Multiboot};
 
-use crate::arch::x86_64::kernel::{get_limit, get_mbinfo};
+use crate::arch::x86_64::kernel::{get_limit, get_mbinfo, get_ram_address};
 use crate::arch::x86_64::mm::MultibootMemory;
 use crate::arch::x86_64::mm::paging::{BasePageSize, PageSize};
+use crate::mm::physmap::{self, RegionKind};
 use crate::{env, mm};
 
 pub static PHYSICAL_FREE_LIST: InterruptTicketMutex<FreeList<16>> =
@@ -18,9 +19,51 @@ const KVM_32BIT_MAX_MEM_SIZE: usize = 1 << 32;
 const KVM_32BIT_GAP_SIZE: usize = 768 << 20;
 const KVM_32BIT_GAP_START: usize = KVM_32BIT_MAX_MEM_SIZE - KVM_32BIT_GAP_SIZE;
 
+/// Enters the region of `size` bytes at `start` into the physical memory map.
+fn insert_region(start: u64, size: u64, kind: RegionKind, owner: &'static str) {
+	physmap::insert(
+		PhysAddr::new(start)..PhysAddr::new(start + size),
+		kind,
+		owner,
+	);
+}
+
 fn detect_from_fdt() -> Result<(), ()> {
 	let fdt = env::fdt().ok_or(())?;
 
+	for m in fdt
+		.find_all_nodes("/memory")
+		.map(|m| m.reg().unwrap().next().unwrap())
+	{
+		insert_region(
+			m.starting_address as u64,
+			m.size.unwrap() as u64,
+			RegionKind::Ram,
+			"devicetree",
+		);
+	}
+	for reservation in fdt.memory_reservations() {
+		insert_region(
+			reservation.address() as u64,
+			reservation.size() as u64,
+			RegionKind::Reserved,
+			"devicetree",
+		);
+	}
+	if let Some(reserved_memory) = fdt.find_node("/reserved-memory") {
+		for m in reserved_memory
+			.children()
+			.filter_map(|node| node.reg()?.next())
+		{
+			insert_region(
+				m.starting_address as u64,
+				m.size.unwrap_or(0) as u64,
+				RegionKind::Reserved,
+				"devicetree",
+			);
+		}
+	}
+
 	let all_regions = fdt
 		.find_all_nodes("/memory")
 		.map(|m| m.reg().unwrap().next().unwrap());
@@ -39,7 +82,9 @@ fn detect_from_fdt() -> Result<(), ()> {
 
 		TOTAL_MEMORY.fetch_add(range.len().get(), Ordering::Relaxed);
 		unsafe {
-			PHYSICAL_FREE_LIST.lock().deallocate(range).unwrap();
+			tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
+				.deallocate(range)
+				.unwrap();
 		}
 	} else {
 		for m in all_regions {
@@ -62,7 +107,9 @@ fn detect_from_fdt() -> Result<(), ()> {
 			let range = PageRange::new(start_address.as_usize(), end_address as usize).unwrap();
 			TOTAL_MEMORY.fetch_add(range.len().get(), Ordering::Relaxed);
 			unsafe {
-				PHYSICAL_FREE_LIST.lock().deallocate(range).unwrap();
+				tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
+					.deallocate(range)
+					.unwrap();
 			}
 		}
 	}
@@ -78,6 +125,15 @@ fn detect_from_multiboot_info() -> Result<(), ()> {
 	let all_regions = mb
 		.memory_regions()
 		.expect("Could not find a memory map in the Multiboot information");
+	for m in mb.memory_regions().unwrap() {
+		let kind = if m.memory_type() == MemoryType::Available {
+			RegionKind::Ram
+		} else {
+			RegionKind::Reserved
+		};
+		insert_region(m.base_address(), m.length(), kind, "multiboot");
+	}
+
 	let ram_regions = all_regions.filter(|m| {
 		m.memory_type() == MemoryType::Available
 			&& m.base_address() + m.length() > mm::kernel_end_address().as_u64()
@@ -100,7 +156,9 @@ fn detect_from_multiboot_info() -> Result<(), ()> {
 		.unwrap();
 		TOTAL_MEMORY.fetch_add(range.len().get(), Ordering::Relaxed);
 		unsafe {
-			PHYSICAL_FREE_LIST.lock().deallocate(range).unwrap();
+			tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
+				.deallocate(range)
+				.unwrap();
 		}
 	}
 
@@ -119,7 +177,39 @@ fn detect_from_uhyve() -> Result<(), ()> {
 
 	let limit = get_limit();
 	assert_ne!(limit, 0);
-	let mut free_list = PHYSICAL_FREE_LIST.lock();
+
+	let ram_start = get_ram_address().as_u64();
+	if limit > KVM_32BIT_GAP_START {
+		insert_region(
+			ram_start,
+			(KVM_32BIT_GAP_START as u64).saturating_sub(ram_start),
+			RegionKind::Ram,
+			"uhyve",
+		);
+		insert_region(
+			KVM_32BIT_GAP_START as u64,
+			KVM_32BIT_GAP_SIZE as u64,
+			RegionKind::Reserved,
+			"uhyve",
+		);
+		if limit > KVM_32BIT_GAP_START + KVM_32BIT_GAP_SIZE {
+			insert_region(
+				(KVM_32BIT_GAP_START + KVM_32BIT_GAP_SIZE) as u64,
+				(limit - KVM_32BIT_GAP_START - KVM_32BIT_GAP_SIZE) as u64,
+				RegionKind::Ram,
+				"uhyve",
+			);
+		}
+	} else {
+		insert_region(
+			ram_start,
+			(limit as u64).saturating_sub(ram_start),
+			RegionKind::Ram,
+			"uhyve",
+		);
+	}
+
+	let mut free_list = tracked_lock!(PHYSICAL_FREE_LIST, "physical memory");
 	let total_memory;
 
 	// add gap for the APIC
@@ -165,6 +255,11 @@ pub fn total_memory_size() -> usize {
 	TOTAL_MEMORY.load(Ordering::Relaxed)
 }
 
+/// Returns the size of the physical memory, which is not allocated.
+pub fn free_memory_size() -> usize {
+	PHYSICAL_FREE_LIST.lock().free_space()
+}
+
 pub fn allocate(size: usize) -> Result<PhysAddr, AllocError> {
 	assert!(size > 0);
 	assert_eq!(
@@ -178,8 +273,7 @@ pub fn allocate(size: usize) -> Result<PhysAddr, AllocError> {
 	let layout = PageLayout::from_size(size).unwrap();
 
 	Ok(PhysAddr::new(
-		PHYSICAL_FREE_LIST
-			.lock()
+		tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
 			.allocate(layout)?
 			.start()
 			.try_into()
@@ -206,8 +300,7 @@ pub fn allocate_aligned(size: usize, align: usize) -> Result<PhysAddr, AllocErro
 	let layout = PageLayout::from_size_align(size, align).unwrap();
 
 	Ok(PhysAddr::new(
-		PHYSICAL_FREE_LIST
-			.lock()
+		tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
 			.allocate(layout)?
 			.start()
 			.try_into()
@@ -230,7 +323,9 @@ pub fn deallocate(physical_address: PhysAddr, size: usize) {
 	let range = PageRange::from_start_len(physical_address.as_u64() as usize, size).unwrap();
 
 	unsafe {
-		PHYSICAL_FREE_LIST.lock().deallocate(range).unwrap();
+		tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
+			.deallocate(range)
+			.unwrap();
 	}
 }
 
@@ -256,10 +351,35 @@ pub fn reserve(physical_address: PhysAddr, size: usize) {
 	let range = PageRange::from_start_len(physical_address.as_usize(), size).unwrap();
 
 	// FIXME: Don't ignore errors anymore
-	PHYSICAL_FREE_LIST.lock().allocate_at(range).ok();
+	tracked_lock!(PHYSICAL_FREE_LIST, "physical memory")
+		.allocate_at(range)
+		.ok();
+}
+
+/// Removes the free parts of the given range from the list of free physical
+/// memory and returns them.
+///
+/// The other parts are not managed by the list, e.g., because they belong to
+/// the kernel image. Only the returned parts may be deallocated again.
+pub fn reserve_free<const N: usize>(
+	physical_address: PhysAddr,
+	size: usize,
+) -> heapless::Vec<PageRange, N> {
+	let start = physical_address.as_usize();
+	let end = start + size;
+	let mut parts = heapless::Vec::new();
+	let mut free_list = tracked_lock!(PHYSICAL_FREE_LIST, "physical memory");
+	while let Ok(part) = free_list
+		.allocate_with(|entry| PageRange::new(entry.start().max(start), entry.end().min(end)).ok())
+	{
+		parts
+			.push(part)
+			.expect("the range is scattered over too many free regions");
+	}
+	parts
 }
 
 pub fn print_information() {
-	let free_list = PHYSICAL_FREE_LIST.lock();
+	let free_list = tracked_lock!(PHYSICAL_FREE_LIST, "physical memory");
 	info!("Physical memory free list:\n{free_list}");
 }
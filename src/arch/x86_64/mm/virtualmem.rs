@@ -33,7 +33,9 @@ pub fn init() {
 	};
 
 	unsafe {
-		KERNEL_FREE_LIST.lock().deallocate(range).unwrap();
+		tracked_lock!(KERNEL_FREE_LIST, "virtual memory")
+			.deallocate(range)
+			.unwrap();
 	}
 }
 
@@ -50,8 +52,7 @@ pub fn allocate(size: usize) -> Result<VirtAddr, AllocError> {
 	let layout = PageLayout::from_size(size).unwrap();
 
 	Ok(VirtAddr::new(
-		KERNEL_FREE_LIST
-			.lock()
+		tracked_lock!(KERNEL_FREE_LIST, "virtual memory")
 			.allocate(layout)?
 			.start()
 			.try_into()
@@ -78,8 +79,7 @@ pub fn allocate_aligned(size: usize, align: usize) -> Result<VirtAddr, AllocErro
 	let layout = PageLayout::from_size_align(size, align).unwrap();
 
 	Ok(VirtAddr::new(
-		KERNEL_FREE_LIST
-			.lock()
+		tracked_lock!(KERNEL_FREE_LIST, "virtual memory")
 			.allocate(layout)?
 			.start()
 			.try_into()
@@ -110,7 +110,9 @@ pub fn deallocate(virtual_address: VirtAddr, size: usize) {
 	let range = PageRange::from_start_len(virtual_address.as_u64() as usize, size).unwrap();
 
 	unsafe {
-		KERNEL_FREE_LIST.lock().deallocate(range).unwrap();
+		tracked_lock!(KERNEL_FREE_LIST, "virtual memory")
+			.deallocate(range)
+			.unwrap();
 	}
 }
 
@@ -153,7 +155,7 @@ pub fn deallocate(virtual_address: VirtAddr, size: usize) {
 }*/
 
 pub fn print_information() {
-	let free_list = KERNEL_FREE_LIST.lock();
+	let free_list = tracked_lock!(KERNEL_FREE_LIST, "virtual memory");
 	info!("Virtual memory free list:\n{free_list}");
 }
 
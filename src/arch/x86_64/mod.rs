@@ -29,6 +29,23 @@ pub(crate) fn swapgs(_stack_frame: &ExceptionStackFrame) {}
 pub(crate) fn memory_barrier() {
 	use core::arch::asm;
 	unsafe {
-		asm!("mfence", options(nostack, nomem, preserves_flags),);
+		asm!("mfence", options(nostack, preserves_flags),);
 	}
 }
+
+/// Orders the read of a device register before the following memory accesses.
+///
+/// Uncached accesses to device memory are not reordered by the CPU,
+/// so that only the compiler has to be prevented from reordering.
+#[allow(dead_code)]
+#[inline(always)]
+pub(crate) fn io_read_barrier() {
+	core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Orders the preceding memory accesses before the write of a device register.
+#[allow(dead_code)]
+#[inline(always)]
+pub(crate) fn io_write_barrier() {
+	core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
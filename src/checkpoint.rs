@@ -0,0 +1,293 @@
+//! Checkpoint and restore of the application state
+//!
+//! The kernel and the application share the same heap, so the heap cannot be
+//! restored wholesale. Instead, the application registers the regions, which
+//! hold its state, with `sys_checkpoint_region`. `sys_checkpoint` freezes the
+//! application, copies all registered regions together with the task states
+//! and the metadata of the open file descriptors into an image and writes the
+//! image to a file descriptor, e.g., a file or a vsock stream.
+//!
+//! If `HERMIT_RESTORE` names an image at boot time, the image is loaded before
+//! the application starts. Afterwards, registering a region, which is part of
+//! the image, fills the region with the saved data.
+//!
+//! The image also contains the values of the clocks, so that `CLOCK_MONOTONIC`
+//! continues at its value of the checkpoint and `CLOCK_BOOTTIME` includes the
+//! time between checkpoint and restore.
+//!
+//! Only a single application task is supported. The task states and the file
+//! descriptor metadata are stored for inspection, but are not restored.
+//! Sockets and other objects without file attributes are not recorded.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::slice;
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::arch::core_local::core_scheduler;
+use crate::executor::block_on;
+use crate::fd::{self, AccessPermission, FileDescriptor, OpenOption};
+use crate::fs::{self, SeekWhence};
+use crate::scheduler::task::TaskHandle;
+use crate::synch::without_interrupts;
+use crate::time::{self, Clocks};
+use crate::{io, scheduler};
+
+const MAGIC: &[u8; 8] = b"HMTCKPT1";
+const VERSION: u32 = 2;
+
+/// Regions of the application state, which are part of a checkpoint
+static REGIONS: InterruptTicketMutex<BTreeMap<u32, (usize, usize)>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Regions of the restored image, which are not yet claimed by the application
+static RESTORED: InterruptTicketMutex<BTreeMap<u32, Vec<u8>>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Metadata of an open file descriptor
+#[derive(Debug)]
+struct FdRecord {
+	fd: FileDescriptor,
+	mode: u32,
+	size: u64,
+	/// Current position or `-1`, if the object is not seekable
+	offset: i64,
+}
+
+fn fd_records() -> Vec<FdRecord> {
+	let object_map = core_scheduler().get_current_task_object_map();
+	let Ok(objects) = block_on(async { Ok(object_map.read().await.clone()) }, None) else {
+		return Vec::new();
+	};
+
+	let mut records: Vec<FdRecord> = objects
+		.into_iter()
+		.filter_map(|(fd, obj)| {
+			let attr = block_on(obj.fstat(), None).ok()?;
+			let offset = block_on(obj.lseek(0, SeekWhence::Cur), None).map_or(-1, |pos| pos as i64);
+			Some(FdRecord {
+				fd,
+				mode: attr.st_mode.bits(),
+				size: attr.st_size,
+				offset,
+			})
+		})
+		.collect();
+	records.sort_by_key(|record| record.fd);
+	records
+}
+
+fn serialize(
+	tasks: &[TaskHandle],
+	fds: &[FdRecord],
+	regions: &BTreeMap<u32, (usize, usize)>,
+) -> Vec<u8> {
+	let mut image = Vec::new();
+	image.extend_from_slice(MAGIC);
+	image.extend_from_slice(&VERSION.to_le_bytes());
+	image.extend_from_slice(&(tasks.len() as u32).to_le_bytes());
+	image.extend_from_slice(&(fds.len() as u32).to_le_bytes());
+	image.extend_from_slice(&(regions.len() as u32).to_le_bytes());
+
+	let clocks = time::clocks();
+	image.extend_from_slice(&clocks.realtime.to_le_bytes());
+	image.extend_from_slice(&clocks.monotonic.to_le_bytes());
+	image.extend_from_slice(&clocks.boottime.to_le_bytes());
+
+	for task in tasks {
+		image.extend_from_slice(&task.get_id().into().to_le_bytes());
+		image.push(task.get_priority().into());
+	}
+
+	for record in fds {
+		image.extend_from_slice(&record.fd.to_le_bytes());
+		image.extend_from_slice(&record.mode.to_le_bytes());
+		image.extend_from_slice(&record.size.to_le_bytes());
+		image.extend_from_slice(&record.offset.to_le_bytes());
+	}
+
+	for (id, &(addr, len)) in regions {
+		image.extend_from_slice(&id.to_le_bytes());
+		image.extend_from_slice(&(len as u64).to_le_bytes());
+		let data = unsafe { slice::from_raw_parts(addr as *const u8, len) };
+		image.extend_from_slice(data);
+	}
+
+	image
+}
+
+/// Writes a checkpoint of the application to `fd`.
+///
+/// Returns `EBUSY`, if more than one application task is alive.
+pub(crate) fn checkpoint(fd: FileDescriptor) -> io::Result<()> {
+	let tasks = scheduler::live_tasks();
+	if tasks.len() > 1 {
+		return Err(io::Error::EBUSY);
+	}
+
+	let fds = fd_records();
+	// freeze the application, while its state is copied
+	let image = without_interrupts(|| serialize(&tasks, &fds, &REGIONS.lock()));
+
+	let mut data = image.as_slice();
+	while !data.is_empty() {
+		match fd::write(fd, data)? {
+			0 => return Err(io::Error::EIO),
+			len => data = &data[len..],
+		}
+	}
+
+	info!(
+		"Wrote checkpoint of {} bytes ({} file descriptors, {} regions)",
+		image.len(),
+		fds.len(),
+		REGIONS.lock().len()
+	);
+	Ok(())
+}
+
+/// Adds `len` bytes at `addr` as region `id` to the checkpoints.
+///
+/// Returns `true`, if the region was filled with the data of the restored image.
+pub(crate) fn register_region(id: u32, addr: usize, len: usize) -> io::Result<bool> {
+	if addr == 0 {
+		return Err(io::Error::EINVAL);
+	}
+
+	REGIONS.lock().insert(id, (addr, len));
+
+	let mut restored = RESTORED.lock();
+	match restored.get(&id) {
+		Some(data) if data.len() == len => {
+			let region = unsafe { slice::from_raw_parts_mut(addr as *mut u8, len) };
+			region.copy_from_slice(data);
+			restored.remove(&id);
+			Ok(true)
+		}
+		Some(data) => {
+			warn!(
+				"Size of checkpoint region {id} differs ({} != {len} bytes), skipping restore",
+				data.len()
+			);
+			Ok(false)
+		}
+		None => Ok(false),
+	}
+}
+
+/// Parser of a checkpoint image
+struct Reader<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+		if self.data.len() < len {
+			return Err(io::Error::EINVAL);
+		}
+		let (bytes, rest) = self.data.split_at(len);
+		self.data = rest;
+		Ok(bytes)
+	}
+
+	fn u8(&mut self) -> io::Result<u8> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn u32(&mut self) -> io::Result<u32> {
+		Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+	}
+
+	fn u64(&mut self) -> io::Result<u64> {
+		Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+	}
+}
+
+/// Content of a checkpoint image, which is restored
+struct Image {
+	clocks: Clocks,
+	regions: BTreeMap<u32, Vec<u8>>,
+}
+
+fn parse(image: &[u8]) -> io::Result<Image> {
+	let mut reader = Reader { data: image };
+	if reader.bytes(MAGIC.len())? != MAGIC || reader.u32()? != VERSION {
+		return Err(io::Error::EINVAL);
+	}
+
+	let tasks = reader.u32()?;
+	let fds = reader.u32()?;
+	let regions = reader.u32()?;
+
+	let clocks = Clocks {
+		realtime: reader.u64()?,
+		monotonic: reader.u64()?,
+		boottime: reader.u64()?,
+	};
+
+	for _ in 0..tasks {
+		let id = reader.u32()?;
+		let prio = reader.u8()?;
+		debug!("Checkpointed task {id} with priority {prio}");
+	}
+
+	for _ in 0..fds {
+		let fd = reader.u32()? as i32;
+		let mode = reader.u32()?;
+		let size = reader.u64()?;
+		let offset = reader.u64()? as i64;
+		debug!("Checkpointed file descriptor {fd}: mode {mode:#o}, size {size}, offset {offset}");
+	}
+
+	let mut restored = BTreeMap::new();
+	for _ in 0..regions {
+		let id = reader.u32()?;
+		let len = usize::try_from(reader.u64()?).map_err(|_| io::Error::EINVAL)?;
+		restored.insert(id, reader.bytes(len)?.to_vec());
+	}
+
+	Ok(Image {
+		clocks,
+		regions: restored,
+	})
+}
+
+fn load(path: &str) -> io::Result<Image> {
+	let fd = fs::open(
+		path,
+		OpenOption::O_RDONLY,
+		AccessPermission::from_bits(0o444).unwrap(),
+	)?;
+
+	let mut image = Vec::new();
+	let mut buf = [0u8; 4096];
+	let result = loop {
+		match fd::read(fd, &mut buf) {
+			Ok(0) => break parse(&image),
+			Ok(len) => image.extend_from_slice(&buf[..len]),
+			Err(err) => break Err(err),
+		}
+	};
+	fd::remove_object(fd)?;
+	result
+}
+
+/// Loads the image, which is specified by `HERMIT_RESTORE`.
+pub(crate) fn init() {
+	let Some(path) = hermit_var!("HERMIT_RESTORE") else {
+		return;
+	};
+
+	match load(&path) {
+		Ok(image) => {
+			info!(
+				"Restore {} checkpoint regions from {path}",
+				image.regions.len()
+			);
+			time::resume(&image.clocks);
+			*RESTORED.lock() = image.regions;
+		}
+		Err(err) => warn!("Unable to restore checkpoint from {path}: {err:?}"),
+	}
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "console")]
+use core::hint;
 use core::task::Waker;
 use core::{fmt, mem};
 
@@ -5,9 +7,16 @@ use heapless::Vec;
 use hermit_sync::{InterruptTicketMutex, Lazy};
 
 use crate::arch;
+#[cfg(feature = "console")]
+use crate::drivers;
 
 const SERIAL_BUFFER_SIZE: usize = 256;
 
+/// Number of attempts to lock the virtio console driver, e.g., while its
+/// interrupt handler runs on another core
+#[cfg(feature = "console")]
+const DRIVER_LOCK_RETRIES: usize = 0x1000;
+
 pub(crate) struct Console {
 	pub inner: arch::kernel::Console,
 	buffer: Vec<u8, SERIAL_BUFFER_SIZE>,
@@ -21,24 +30,42 @@ impl Console {
 		}
 	}
 
+	/// Writes `buf` to the serial device `inner` and, if available, to the virtio console.
+	fn write_devices(inner: &mut arch::kernel::Console, buf: &[u8]) {
+		inner.write(buf);
+
+		// The lock is not awaited, because the driver may log, while it is
+		// locked. In this case, the output is only written to the serial device.
+		#[cfg(feature = "console")]
+		if let Some(driver) = drivers::console::driver() {
+			for _ in 0..DRIVER_LOCK_RETRIES {
+				if let Some(mut driver) = driver.try_lock() {
+					driver.write(buf);
+					break;
+				}
+				hint::spin_loop();
+			}
+		}
+	}
+
 	pub fn write(&mut self, buf: &[u8]) {
 		if SERIAL_BUFFER_SIZE - self.buffer.len() >= buf.len() {
 			// unwrap: we checked that buf fits in self.buffer
 			self.buffer.extend_from_slice(buf).unwrap();
 			if buf.contains(&b'\n') {
-				self.inner.write(&self.buffer);
+				Self::write_devices(&mut self.inner, &self.buffer);
 				self.buffer.clear();
 			}
 		} else {
-			self.inner.write(&self.buffer);
+			Self::write_devices(&mut self.inner, &self.buffer);
 			self.buffer.clear();
 			if buf.len() >= SERIAL_BUFFER_SIZE {
-				self.inner.write(buf);
+				Self::write_devices(&mut self.inner, buf);
 			} else {
 				// unwrap: we checked that buf fits in self.buffer
 				self.buffer.extend_from_slice(buf).unwrap();
 				if buf.contains(&b'\n') {
-					self.inner.write(&self.buffer);
+					Self::write_devices(&mut self.inner, &self.buffer);
 					self.buffer.clear();
 				}
 			}
@@ -46,15 +73,28 @@ impl Console {
 	}
 
 	pub fn read(&mut self) -> Option<u8> {
-		self.inner.read()
+		let byte = self.inner.read();
+
+		#[cfg(feature = "console")]
+		let byte = byte.or_else(drivers::console::read);
+
+		byte
 	}
 
 	pub fn is_empty(&self) -> bool {
+		#[cfg(feature = "console")]
+		if !drivers::console::is_empty() {
+			return false;
+		}
+
 		self.inner.is_empty()
 	}
 
 	pub fn register_waker(&mut self, waker: &Waker) {
 		self.inner.register_waker(waker);
+
+		#[cfg(feature = "console")]
+		drivers::console::register_waker(waker);
 	}
 }
 
@@ -0,0 +1,453 @@
+//! Core dumps for post-mortem debugging
+//!
+//! If the application panics or faults and `HERMIT_COREDUMP` is set, the kernel
+//! writes an ELF core file, which can be loaded into gdb together with the
+//! application binary. On uhyve, `HERMIT_COREDUMP` is a path on the host. With
+//! `HERMIT_COREDUMP=pmem`, the core file overwrites the memory of the
+//! virtio-pmem device, which is backed by a file on the host.
+//!
+//! The core file is written by hypercalls or plain memory copies, because the
+//! file systems, the network stack and the allocator may be locked by the
+//! faulting code. For this reason, paths on a virtio-fs mount and vsock
+//! endpoints are not supported as targets: both require the heap for the
+//! buffers of the virtqueue and the locks of the driver and of the FUSE or vsock
+//! state, so that a dump from a faulting task could deadlock or corrupt them.
+//! On QEMU, a core dump is written to a virtio-pmem device instead, e.g.,
+//! `-object memory-backend-file,id=core,share=on,mem-path=core,size=256M
+//! -device virtio-pmem-pci,memdev=core` with `HERMIT_COREDUMP=pmem`.
+//!
+//! The core file contains the kernel image including the static data, the heap
+//! and the stacks of the current task. On faults, the register state is taken
+//! from the trap frame. On panics, it is restricted to the program counter, the
+//! stack pointer and the frame pointer, which is sufficient to unwind the stack.
+
+use alloc::ffi::CString;
+use core::ops::Range;
+use core::slice;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use hermit_sync::OnceCell;
+use memory_addresses::VirtAddr;
+use uhyve_interface::parameters::{CloseParams, OpenParams, WriteParams};
+use uhyve_interface::{GuestPhysAddr, GuestVirtAddr, Hypercall};
+
+use crate::arch::core_local::core_scheduler;
+use crate::arch::mm::paging;
+#[cfg(feature = "pmem")]
+use crate::drivers::pci::get_pmem_driver;
+#[cfg(feature = "pmem")]
+use crate::drivers::pmem::PmemRegion;
+use crate::fd::OpenOption;
+use crate::syscalls::interfaces::uhyve::uhyve_hypercall;
+use crate::{env, io, mm};
+
+/// Signal, which is reported for a panic
+pub(crate) const SIGABRT: u32 = 6;
+/// Signal, which is reported for a memory fault
+pub(crate) const SIGSEGV: u32 = 11;
+
+#[cfg(target_arch = "x86_64")]
+const EM_MACHINE: u16 = 62;
+#[cfg(target_arch = "aarch64")]
+const EM_MACHINE: u16 = 183;
+#[cfg(target_arch = "riscv64")]
+const EM_MACHINE: u16 = 243;
+
+/// Number of general-purpose registers in `elf_prstatus`
+#[cfg(target_arch = "x86_64")]
+const NREGS: usize = 27;
+#[cfg(target_arch = "aarch64")]
+const NREGS: usize = 34;
+#[cfg(target_arch = "riscv64")]
+const NREGS: usize = 32;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+/// Offset of `pr_reg` in `elf_prstatus`
+const PR_REG_OFFSET: usize = 112;
+const PRSTATUS_SIZE: usize = PR_REG_OFFSET + NREGS * 8 + 8;
+const NOTE_SIZE: usize = 12 + 8 + PRSTATUS_SIZE;
+
+/// Maximum number of memory segments: the kernel image, the heap, the kernel
+/// stack and the user stack
+const MAX_SEGMENTS: usize = 4;
+const HEADERS_SIZE: usize = EHDR_SIZE + (MAX_SEGMENTS + 1) * PHDR_SIZE + NOTE_SIZE;
+
+/// Headers of a core file, which are kept on the stack, because the heap may
+/// be locked by the faulting code
+type Headers = heapless::Vec<u8, HEADERS_SIZE>;
+
+/// Set while a core dump is written to avoid recursion, if writing fails badly
+static WRITING: AtomicBool = AtomicBool::new(false);
+
+/// Register state of the faulting task in the layout of `pr_reg`
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Registers([u64; NREGS]);
+
+impl Registers {
+	/// Captures the program counter, the stack pointer and the frame pointer
+	/// of the caller.
+	#[inline(always)]
+	pub fn current() -> Self {
+		let (pc, sp, fp): (u64, u64, u64);
+		unsafe {
+			#[cfg(target_arch = "x86_64")]
+			core::arch::asm!(
+				"lea {pc}, [rip]",
+				"mov {sp}, rsp",
+				"mov {fp}, rbp",
+				pc = out(reg) pc,
+				sp = out(reg) sp,
+				fp = out(reg) fp,
+				options(nomem, nostack, preserves_flags),
+			);
+			#[cfg(target_arch = "aarch64")]
+			core::arch::asm!(
+				"adr {pc}, .",
+				"mov {sp}, sp",
+				"mov {fp}, x29",
+				pc = out(reg) pc,
+				sp = out(reg) sp,
+				fp = out(reg) fp,
+				options(nomem, nostack, preserves_flags),
+			);
+			#[cfg(target_arch = "riscv64")]
+			core::arch::asm!(
+				"auipc {pc}, 0",
+				"mv {sp}, sp",
+				"mv {fp}, s0",
+				pc = out(reg) pc,
+				sp = out(reg) sp,
+				fp = out(reg) fp,
+				options(nomem, nostack, preserves_flags),
+			);
+		}
+
+		let mut regs = [0; NREGS];
+		#[cfg(target_arch = "x86_64")]
+		{
+			regs[4] = fp;
+			regs[16] = pc;
+			regs[19] = sp;
+		}
+		#[cfg(target_arch = "aarch64")]
+		{
+			regs[29] = fp;
+			regs[31] = sp;
+			regs[32] = pc;
+		}
+		#[cfg(target_arch = "riscv64")]
+		{
+			regs[0] = pc;
+			regs[2] = sp;
+			regs[8] = fp;
+		}
+		Self(regs)
+	}
+}
+
+#[cfg(target_arch = "x86_64")]
+impl From<&crate::arch::x86_64::kernel::interrupts::ExceptionStackFrame> for Registers {
+	/// Takes the registers, which the processor has saved in the exception
+	/// frame. The general-purpose registers are not part of the frame, so
+	/// that gdb unwinds the stack by the call frame information.
+	fn from(stack_frame: &crate::arch::x86_64::kernel::interrupts::ExceptionStackFrame) -> Self {
+		let mut regs = [0; NREGS];
+		regs[16] = stack_frame.instruction_pointer.as_u64();
+		regs[17] = stack_frame.code_segment.0.into();
+		regs[18] = stack_frame.cpu_flags.bits();
+		regs[19] = stack_frame.stack_pointer.as_u64();
+		regs[20] = stack_frame.stack_segment.0.into();
+		Self(regs)
+	}
+}
+
+#[cfg(target_arch = "aarch64")]
+impl From<&crate::arch::aarch64::kernel::scheduler::State> for Registers {
+	fn from(state: &crate::arch::aarch64::kernel::scheduler::State) -> Self {
+		let regs = [
+			state.x0,
+			state.x1,
+			state.x2,
+			state.x3,
+			state.x4,
+			state.x5,
+			state.x6,
+			state.x7,
+			state.x8,
+			state.x9,
+			state.x10,
+			state.x11,
+			state.x12,
+			state.x13,
+			state.x14,
+			state.x15,
+			state.x16,
+			state.x17,
+			state.x18,
+			state.x19,
+			state.x20,
+			state.x21,
+			state.x22,
+			state.x23,
+			state.x24,
+			state.x25,
+			state.x26,
+			state.x27,
+			state.x28,
+			state.x29,
+			state.x30,
+			// the state is saved on the stack of the interrupted code
+			core::ptr::from_ref(state).addr() as u64 + core::mem::size_of_val(state) as u64,
+			state.elr_el1,
+			state.spsr_el1,
+		];
+		Self(regs)
+	}
+}
+
+/// Destination of a core dump, which is prepared at boot time, so that a
+/// fault handler neither allocates memory nor takes locks
+enum Sink {
+	/// File on the host, which is written by hypercalls of uhyve
+	Host(CString),
+	/// Memory of a virtio-pmem device
+	#[cfg(feature = "pmem")]
+	Pmem(PmemRegion),
+}
+
+static SINK: OnceCell<Sink> = OnceCell::new();
+
+/// Writer into a [`Sink`]
+enum Writer {
+	Host(i32),
+	#[cfg(feature = "pmem")]
+	Pmem(&'static mut [u8]),
+}
+
+impl Writer {
+	fn open(sink: &Sink) -> io::Result<Self> {
+		match sink {
+			Sink::Host(path) => {
+				let mut open_params = OpenParams {
+					name: GuestPhysAddr::new(
+						paging::virtual_to_physical(VirtAddr::from_ptr(path.as_ptr()))
+							.unwrap()
+							.as_u64(),
+					),
+					flags: (OpenOption::O_WRONLY | OpenOption::O_CREAT | OpenOption::O_TRUNC)
+						.bits(),
+					mode: 0o600,
+					ret: -1,
+				};
+				uhyve_hypercall(Hypercall::FileOpen(&mut open_params));
+				if open_params.ret <= 0 {
+					return Err(io::Error::EIO);
+				}
+				Ok(Self::Host(open_params.ret))
+			}
+			#[cfg(feature = "pmem")]
+			Sink::Pmem(region) => Ok(Self::Pmem(unsafe {
+				slice::from_raw_parts_mut(region.virt_addr.as_mut_ptr::<u8>(), region.size)
+			})),
+		}
+	}
+
+	fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+		match self {
+			Self::Host(fd) => {
+				let write_params = WriteParams {
+					fd: *fd,
+					buf: GuestVirtAddr::new(data.as_ptr() as u64),
+					len: data.len(),
+				};
+				uhyve_hypercall(Hypercall::FileWrite(&write_params));
+			}
+			#[cfg(feature = "pmem")]
+			Self::Pmem(area) => {
+				if data.len() > area.len() {
+					return Err(io::Error::ENOSPC);
+				}
+				let (dest, rest) = core::mem::take(area).split_at_mut(data.len());
+				dest.copy_from_slice(data);
+				*area = rest;
+			}
+		}
+		Ok(())
+	}
+
+	fn close(self) {
+		match self {
+			Self::Host(fd) => {
+				let mut close_params = CloseParams { fd, ret: -1 };
+				uhyve_hypercall(Hypercall::FileClose(&mut close_params));
+			}
+			#[cfg(feature = "pmem")]
+			Self::Pmem(_) => {}
+		}
+	}
+}
+
+/// Returns the ELF header, the program headers and the notes of a core file.
+fn headers(regs: Registers, signal: u32, segments: &[Range<VirtAddr>]) -> Headers {
+	let phnum = segments.len() + 1;
+	let note_offset = EHDR_SIZE + phnum * PHDR_SIZE;
+
+	let mut buf = Headers::new();
+
+	// ELF header
+	buf.extend(b"\x7fELF");
+	// 64 bit, little endian, current version, System V ABI
+	buf.extend(&[2, 1, 1, 0]);
+	buf.extend(&[0; 8]);
+	// ET_CORE
+	buf.extend(&4u16.to_le_bytes());
+	buf.extend(&EM_MACHINE.to_le_bytes());
+	buf.extend(&1u32.to_le_bytes());
+	// e_entry, e_phoff, e_shoff
+	buf.extend(&0u64.to_le_bytes());
+	buf.extend(&(EHDR_SIZE as u64).to_le_bytes());
+	buf.extend(&0u64.to_le_bytes());
+	// e_flags (RVC and double-float ABI on RISC-V)
+	let flags: u32 = if cfg!(target_arch = "riscv64") {
+		0x5
+	} else {
+		0
+	};
+	buf.extend(&flags.to_le_bytes());
+	buf.extend(&(EHDR_SIZE as u16).to_le_bytes());
+	buf.extend(&(PHDR_SIZE as u16).to_le_bytes());
+	buf.extend(&(phnum as u16).to_le_bytes());
+	// e_shentsize, e_shnum, e_shstrndx
+	buf.extend(&[0; 6]);
+
+	let mut phdr = |p_type: u32, p_flags: u32, offset: usize, vaddr: u64, size: u64| {
+		buf.extend(&p_type.to_le_bytes());
+		buf.extend(&p_flags.to_le_bytes());
+		buf.extend(&(offset as u64).to_le_bytes());
+		buf.extend(&vaddr.to_le_bytes());
+		buf.extend(&0u64.to_le_bytes());
+		buf.extend(&size.to_le_bytes());
+		buf.extend(&size.to_le_bytes());
+		buf.extend(&0u64.to_le_bytes());
+	};
+
+	phdr(PT_NOTE, 0, note_offset, 0, NOTE_SIZE as u64);
+	let mut offset = note_offset + NOTE_SIZE;
+	for segment in segments {
+		let size = segment.end - segment.start;
+		// readable, writable and executable
+		phdr(PT_LOAD, 0x7, offset, segment.start.as_u64(), size);
+		offset += size as usize;
+	}
+
+	// NT_PRSTATUS note
+	buf.extend(&5u32.to_le_bytes());
+	buf.extend(&(PRSTATUS_SIZE as u32).to_le_bytes());
+	buf.extend(&NT_PRSTATUS.to_le_bytes());
+	buf.extend(b"CORE\0\0\0\0");
+
+	let mut prstatus = [0u8; PRSTATUS_SIZE];
+	prstatus[0..4].copy_from_slice(&signal.to_le_bytes());
+	prstatus[12..14].copy_from_slice(&(signal as u16).to_le_bytes());
+	let tid = core_scheduler().get_current_task_id().into();
+	prstatus[32..36].copy_from_slice(&tid.to_le_bytes());
+	for (i, reg) in regs.0.into_iter().enumerate() {
+		let start = PR_REG_OFFSET + i * 8;
+		prstatus[start..start + 8].copy_from_slice(&reg.to_le_bytes());
+	}
+	buf.extend(&prstatus);
+
+	buf
+}
+
+fn write_segments(
+	writer: &mut Writer,
+	regs: Registers,
+	signal: u32,
+	segments: &[Range<VirtAddr>],
+) -> io::Result<()> {
+	writer.write_all(&headers(regs, signal, segments))?;
+	for segment in segments {
+		let data = unsafe {
+			slice::from_raw_parts(
+				segment.start.as_ptr::<u8>(),
+				(segment.end - segment.start) as usize,
+			)
+		};
+		writer.write_all(data)?;
+	}
+	Ok(())
+}
+
+fn dump(sink: &Sink, regs: Registers, signal: u32) -> io::Result<()> {
+	let image = env::get_base_address();
+	let mut segments = heapless::Vec::<Range<VirtAddr>, MAX_SEGMENTS>::new();
+	segments.extend([image..image + env::get_image_size() as u64]);
+	segments.extend(mm::heap_address_range());
+	segments.extend(core_scheduler().get_current_task_stacks());
+
+	let mut writer = Writer::open(sink)?;
+	let result = write_segments(&mut writer, regs, signal, &segments);
+	writer.close();
+	result
+}
+
+/// Writes a core dump to the sink, which has been prepared by [`init`].
+///
+/// The function is called from panic and fault handlers. Hence, it neither
+/// allocates memory nor takes locks, which may be held by the faulting code.
+pub(crate) fn write(regs: Registers, signal: u32) {
+	let Some(sink) = SINK.get() else {
+		return;
+	};
+
+	if WRITING.swap(true, Ordering::AcqRel) {
+		return;
+	}
+
+	match dump(sink, regs, signal) {
+		Ok(()) => error!("Wrote core dump"),
+		Err(err) => error!("Unable to write core dump: {err:?}"),
+	}
+}
+
+/// Prepares the sink, which is specified by `HERMIT_COREDUMP`.
+pub(crate) fn init() {
+	let Some(target) = hermit_var!("HERMIT_COREDUMP") else {
+		return;
+	};
+
+	let sink = if target == "pmem" {
+		#[cfg(feature = "pmem")]
+		{
+			let Some(region) = get_pmem_driver().and_then(|driver| driver.lock().region()) else {
+				warn!("HERMIT_COREDUMP: no virtio-pmem device found");
+				return;
+			};
+			#[cfg(feature = "pstore")]
+			let region = crate::pstore::exclude(region);
+			Sink::Pmem(region)
+		}
+		#[cfg(not(feature = "pmem"))]
+		{
+			warn!("HERMIT_COREDUMP: virtio-pmem is not supported");
+			return;
+		}
+	} else if env::is_uhyve() {
+		let Ok(path) = CString::new(target.as_bytes()) else {
+			warn!("HERMIT_COREDUMP: invalid path {target}");
+			return;
+		};
+		Sink::Host(path)
+	} else {
+		warn!("HERMIT_COREDUMP: host files are only supported on uhyve, use pmem instead");
+		return;
+	};
+
+	info!("Core dumps are written to {target}");
+	let _ = SINK.set(sink);
+}
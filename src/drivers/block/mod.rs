@@ -0,0 +1,111 @@
+//! Block devices
+//!
+//! A block device provides storage, which is read and written in blocks of a
+//! fixed size. Filesystems access the devices through the [`BlockDevice`]
+//! trait, independently of the driver and the transport of the device.
+//! Devices are named in the order of their discovery (`vda`, `vdb`, ...).
+
+pub mod virtio;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use memory_addresses::VirtAddr;
+
+use crate::io;
+
+/// A storage device, which is accessed in blocks of [`BlockDevice::block_size`] bytes
+pub(crate) trait BlockDevice: Send + Sync {
+	/// Returns the size of a block in bytes.
+	fn block_size(&self) -> usize;
+
+	/// Returns the number of blocks of the device.
+	fn num_blocks(&self) -> u64;
+
+	/// Returns `true`, if the device cannot be written.
+	fn is_read_only(&self) -> bool;
+
+	/// Reads the blocks starting at `block` into `buf`, whose length has to be
+	/// a multiple of the block size.
+	fn read_blocks(&self, block: u64, buf: &mut [u8]) -> io::Result<()>;
+
+	/// Writes `buf`, whose length has to be a multiple of the block size, to
+	/// the blocks starting at `block`.
+	fn write_blocks(&self, block: u64, buf: &[u8]) -> io::Result<()>;
+
+	/// Makes all previous writes persistent.
+	fn flush(&self) -> io::Result<()>;
+
+	/// Returns the address, at which the byte `offset` of the device is directly
+	/// accessible (DAX), or `None`, if the device is only accessed by transfers.
+	fn dax_address(&self, _offset: u64) -> Option<VirtAddr> {
+		None
+	}
+}
+
+/// Checks, whether a transfer of `len` bytes to the blocks starting at
+/// `block` lies within `device`.
+pub(crate) fn check_range(device: &dyn BlockDevice, block: u64, len: usize) -> io::Result<u64> {
+	let block_size = device.block_size();
+	if len % block_size != 0 {
+		return Err(io::Error::EINVAL);
+	}
+
+	let count = u64::try_from(len / block_size).map_err(|_| io::Error::EINVAL)?;
+	match block.checked_add(count) {
+		Some(end) if end <= device.num_blocks() => Ok(count),
+		_ => Err(io::Error::EINVAL),
+	}
+}
+
+/// Returns the name of the `index`-th block device.
+fn device_name(index: usize) -> String {
+	let mut suffix = Vec::new();
+	let mut index = index + 1;
+	while index > 0 {
+		index -= 1;
+		suffix.push(char::from(b'a' + (index % 26) as u8));
+		index /= 26;
+	}
+
+	format!("vd{}", suffix.into_iter().rev().collect::<String>())
+}
+
+/// Returns all block devices in the order of their discovery.
+pub(crate) fn devices() -> Vec<&'static dyn BlockDevice> {
+	#[cfg(feature = "pci")]
+	let drivers = crate::drivers::pci::get_block_drivers();
+	#[cfg(not(feature = "pci"))]
+	let drivers = crate::arch::kernel::mmio::get_block_drivers();
+
+	drivers
+		.into_iter()
+		.map(|driver| driver as &'static dyn BlockDevice)
+		.collect()
+}
+
+/// Returns the block device `name`, e.g., `vda`.
+pub(crate) fn get_device(name: &str) -> Option<&'static dyn BlockDevice> {
+	devices()
+		.into_iter()
+		.enumerate()
+		.find_map(|(index, device)| (device_name(index) == name).then_some(device))
+}
+
+/// Prints the block devices.
+pub(crate) fn print_information() {
+	for (index, device) in devices().into_iter().enumerate() {
+		info!(
+			"Block device {}: {} blocks of {} bytes{}",
+			device_name(index),
+			device.num_blocks(),
+			device.block_size(),
+			if device.is_read_only() {
+				" (read-only)"
+			} else {
+				""
+			}
+		);
+	}
+}
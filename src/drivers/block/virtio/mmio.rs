@@ -0,0 +1,67 @@
+use virtio::mmio::{DeviceRegisters, DeviceRegistersVolatileFieldAccess};
+use volatile::VolatileRef;
+
+use crate::drivers::InterruptLine;
+use crate::drivers::block::virtio::{BlkDevCfg, BlkDevCfgRaw, SECTOR_SIZE, VirtioBlkDriver};
+use crate::drivers::virtio::error::VirtioError;
+use crate::drivers::virtio::transport::mmio::{ComCfg, IsrStatus, NotifCfg};
+
+impl VirtioBlkDriver {
+	pub fn new(
+		dev_id: u16,
+		mut registers: VolatileRef<'static, DeviceRegisters>,
+		irq: InterruptLine,
+	) -> Self {
+		let dev_cfg_raw: &'static BlkDevCfgRaw = unsafe {
+			&*registers
+				.borrow_mut()
+				.as_mut_ptr()
+				.config()
+				.as_raw_ptr()
+				.cast::<BlkDevCfgRaw>()
+				.as_ptr()
+		};
+		let dev_cfg_raw = VolatileRef::from_ref(dev_cfg_raw);
+		let dev_cfg = BlkDevCfg {
+			raw: dev_cfg_raw,
+			dev_id,
+			features: virtio::F::empty(),
+		};
+		let isr_stat = IsrStatus::new(registers.borrow_mut());
+		let notif_cfg = NotifCfg::new(registers.borrow_mut());
+
+		VirtioBlkDriver {
+			dev_cfg,
+			com_cfg: ComCfg::new(registers, 1),
+			isr_stat,
+			notif_cfg,
+			irq,
+			request_vq: None,
+			block_size: SECTOR_SIZE,
+			max_request_size: SECTOR_SIZE,
+		}
+	}
+
+	/// Initializes virtio block device
+	pub fn init(
+		dev_id: u16,
+		registers: VolatileRef<'static, DeviceRegisters>,
+		irq: InterruptLine,
+	) -> Result<VirtioBlkDriver, VirtioError> {
+		let mut drv = VirtioBlkDriver::new(dev_id, registers, irq);
+		match drv.init_dev() {
+			Ok(()) => {
+				info!(
+					"Block device with id {:x}, has been initialized by driver!",
+					drv.get_dev_id()
+				);
+				drv.com_cfg.print_information();
+				Ok(drv)
+			}
+			Err(blk_err) => {
+				drv.set_failed();
+				Err(VirtioError::BlkDriver(blk_err))
+			}
+		}
+	}
+}
@@ -0,0 +1,420 @@
+//! A module containing a virtio block driver.
+//!
+//! Requests are processed synchronously on a single request queue. Each
+//! request consists of a header, an optional data buffer and a status byte,
+//! which is written by the device.
+
+cfg_if::cfg_if! {
+	if #[cfg(feature = "pci")] {
+		pub mod pci;
+	} else {
+		pub mod mmio;
+	}
+}
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use hermit_sync::InterruptTicketMutex;
+use virtio::{FeatureBits, le32, le64, le128};
+use volatile::access::ReadOnly;
+use volatile::{VolatileFieldAccess, VolatileRef};
+
+use self::error::VirtioBlkError;
+use crate::config::VIRTIO_MAX_QUEUE_SIZE;
+use crate::drivers::block::{BlockDevice, check_range};
+#[cfg(not(feature = "pci"))]
+use crate::drivers::virtio::transport::mmio::{ComCfg, IsrStatus, NotifCfg};
+#[cfg(feature = "pci")]
+use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
+use crate::drivers::virtio::virtqueue::split::SplitVq;
+use crate::drivers::virtio::virtqueue::{
+	AvailBufferToken, BufferElem, BufferType, Virtq, VqIndex, VqSize,
+};
+use crate::drivers::{Driver, InterruptLine};
+use crate::io;
+use crate::mm::device_alloc::DeviceAlloc;
+
+/// Maximum size of any single segment is in `size_max` (`VIRTIO_BLK_F_SIZE_MAX`)
+///
+/// The feature bits are not defined by `virtio-spec` (Virtio specification v1.2 - 5.2.3).
+const SIZE_MAX: virtio::F = virtio::F::from_bits_retain(le128::from_ne(1 << 1));
+
+/// Device is read-only (`VIRTIO_BLK_F_RO`)
+const RO: virtio::F = virtio::F::from_bits_retain(le128::from_ne(1 << 5));
+
+/// Block size of disk is in `blk_size` (`VIRTIO_BLK_F_BLK_SIZE`)
+const BLK_SIZE: virtio::F = virtio::F::from_bits_retain(le128::from_ne(1 << 6));
+
+/// Cache flush command support (`VIRTIO_BLK_F_FLUSH`)
+const FLUSH: virtio::F = virtio::F::from_bits_retain(le128::from_ne(1 << 9));
+
+/// Request type of a read request (`VIRTIO_BLK_T_IN`)
+const VIRTIO_BLK_T_IN: u32 = 0;
+/// Request type of a write request (`VIRTIO_BLK_T_OUT`)
+const VIRTIO_BLK_T_OUT: u32 = 1;
+/// Request type of a flush request (`VIRTIO_BLK_T_FLUSH`)
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
+
+/// Status of a successful request (`VIRTIO_BLK_S_OK`)
+const VIRTIO_BLK_S_OK: u8 = 0;
+/// Status of a request, which is not supported by the device (`VIRTIO_BLK_S_UNSUPP`)
+const VIRTIO_BLK_S_UNSUPP: u8 = 2;
+
+/// Size of a sector, in which the device is addressed
+const SECTOR_SIZE: usize = 512;
+
+/// Maximum size of the data of a single request
+const MAX_REQUEST_SIZE: usize = 0x0002_0000;
+
+/// Virtio's block device configuration structure.
+/// See specification v1.2. - 5.2.4
+#[derive(VolatileFieldAccess)]
+#[repr(C)]
+pub(crate) struct BlkDevCfgRaw {
+	/// Capacity of the device in 512-byte sectors
+	#[access(ReadOnly)]
+	capacity: le64,
+	#[access(ReadOnly)]
+	size_max: le32,
+	#[access(ReadOnly)]
+	seg_max: le32,
+	#[access(ReadOnly)]
+	cylinders: virtio::le16,
+	#[access(ReadOnly)]
+	heads: u8,
+	#[access(ReadOnly)]
+	sectors: u8,
+	#[access(ReadOnly)]
+	blk_size: le32,
+}
+
+pub(crate) struct BlkDevCfg {
+	pub raw: VolatileRef<'static, BlkDevCfgRaw, ReadOnly>,
+	pub dev_id: u16,
+	pub features: virtio::F,
+}
+
+/// Header of a request
+/// See specification v1.2. - 5.2.6
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+struct BlkReqHdr {
+	req_type: le32,
+	reserved: le32,
+	sector: le64,
+}
+
+impl BlkReqHdr {
+	fn new(req_type: u32, sector: u64) -> Self {
+		Self {
+			req_type: req_type.into(),
+			reserved: 0.into(),
+			sector: sector.into(),
+		}
+	}
+}
+
+pub(crate) struct VirtioBlkDriver {
+	pub(super) dev_cfg: BlkDevCfg,
+	pub(super) com_cfg: ComCfg,
+	pub(super) isr_stat: IsrStatus,
+	pub(super) notif_cfg: NotifCfg,
+	pub(super) irq: InterruptLine,
+	pub(super) request_vq: Option<Box<dyn Virtq>>,
+	/// Size of a block in bytes, which is a multiple of the sector size
+	pub(super) block_size: usize,
+	/// Maximum size of the data of a single request, which is a multiple of the block size
+	pub(super) max_request_size: usize,
+}
+
+impl Driver for VirtioBlkDriver {
+	fn get_interrupt_number(&self) -> InterruptLine {
+		self.irq
+	}
+
+	fn get_name(&self) -> &'static str {
+		"virtio"
+	}
+
+	fn get_queue_count(&self) -> usize {
+		usize::from(self.request_vq.is_some())
+	}
+}
+
+impl VirtioBlkDriver {
+	pub fn get_dev_id(&self) -> u16 {
+		self.dev_cfg.dev_id
+	}
+
+	pub fn set_failed(&mut self) {
+		self.com_cfg.set_failed();
+	}
+
+	pub fn handle_interrupt(&mut self) {
+		self.isr_stat.acknowledge();
+	}
+
+	/// Returns the capacity of the device in sectors.
+	fn capacity(&self) -> u64 {
+		self.dev_cfg.raw.as_ptr().capacity().read().to_ne()
+	}
+
+	fn is_read_only(&self) -> bool {
+		self.dev_cfg.features.contains(RO)
+	}
+
+	/// Negotiates a subset of features, understood and wanted by both the OS
+	/// and the device, and returns this subset.
+	fn negotiate_features(
+		&mut self,
+		driver_features: virtio::F,
+	) -> Result<virtio::F, VirtioBlkError> {
+		let device_features = self.com_cfg.dev_features();
+
+		if device_features.requirements_satisfied() {
+			debug!("Feature set wanted by block driver are in conformance with specification.");
+		} else {
+			return Err(VirtioBlkError::FeatureRequirementsNotMet(device_features));
+		}
+
+		let features = driver_features & device_features;
+		if features.contains(virtio::F::VERSION_1) {
+			self.com_cfg.set_drv_features(features);
+			Ok(features)
+		} else {
+			Err(VirtioBlkError::IncompatibleFeatureSets(
+				driver_features,
+				device_features,
+			))
+		}
+	}
+
+	/// Initializes the device in adherence to specification.
+	///
+	/// See Virtio specification v1.2. - 3.1.1.
+	///                      and v1.2. - 5.2.5
+	pub fn init_dev(&mut self) -> Result<(), VirtioBlkError> {
+		// Reset
+		self.com_cfg.reset_dev();
+
+		// Indicate device, that OS noticed it
+		self.com_cfg.ack_dev();
+
+		// Indicate device, that driver is able to handle it
+		self.com_cfg.set_drv();
+
+		let features =
+			self.negotiate_features(virtio::F::VERSION_1 | SIZE_MAX | RO | BLK_SIZE | FLUSH)?;
+
+		// Indicates the device, that the current feature set is final for the driver
+		// and will not be changed.
+		self.com_cfg.features_ok();
+
+		// Checks if the device has accepted final set. This finishes feature negotiation.
+		if self.com_cfg.check_features() {
+			info!(
+				"Features have been negotiated between virtio block device {:x} and driver.",
+				self.dev_cfg.dev_id
+			);
+			// Set feature set in device config fur future use.
+			self.dev_cfg.features = features;
+		} else {
+			return Err(VirtioBlkError::FailFeatureNeg(self.dev_cfg.dev_id));
+		}
+
+		// The block size is only a hint for the optimal access, the device is
+		// always addressed in sectors.
+		if features.contains(BLK_SIZE) {
+			let blk_size =
+				usize::try_from(self.dev_cfg.raw.as_ptr().blk_size().read().to_ne()).unwrap();
+			if blk_size.is_power_of_two() && blk_size >= SECTOR_SIZE {
+				self.block_size = blk_size;
+			}
+		}
+
+		// Each request uses a single data buffer.
+		self.max_request_size = MAX_REQUEST_SIZE;
+		if features.contains(SIZE_MAX) {
+			let size_max =
+				usize::try_from(self.dev_cfg.raw.as_ptr().size_max().read().to_ne()).unwrap();
+			self.max_request_size = self.max_request_size.min(size_max);
+		}
+		self.max_request_size =
+			(self.max_request_size / self.block_size * self.block_size).max(self.block_size);
+
+		let vq = SplitVq::new(
+			&mut self.com_cfg,
+			&self.notif_cfg,
+			VqSize::from(VIRTIO_MAX_QUEUE_SIZE),
+			VqIndex::from(0u16),
+			self.dev_cfg.features,
+		)
+		.map_err(|_| VirtioBlkError::NoRequestQueue(self.dev_cfg.dev_id))?;
+		self.request_vq = Some(Box::new(vq));
+
+		// At this point the device is "live"
+		self.com_cfg.drv_ok();
+
+		info!(
+			"Block device {:x} has {} sectors, block size {}, max. request size {}",
+			self.dev_cfg.dev_id,
+			self.capacity(),
+			self.block_size,
+			self.max_request_size
+		);
+
+		Ok(())
+	}
+
+	/// Sends a request with the header `header`, the driver-readable data
+	/// `send` and the device-writable data `recv` to the device and waits
+	/// for its completion.
+	///
+	/// Returns the data, which has been written by the device.
+	fn request(
+		&mut self,
+		header: BlkReqHdr,
+		send: Option<Vec<u8, DeviceAlloc>>,
+		recv: Option<Vec<u8, DeviceAlloc>>,
+	) -> Result<Option<Vec<u8, DeviceAlloc>>, VirtioBlkError> {
+		let request_vq = self
+			.request_vq
+			.as_mut()
+			.ok_or(VirtioBlkError::NoRequestQueue(self.dev_cfg.dev_id))?;
+
+		let mut send_buff = Vec::from([BufferElem::Sized(Box::new_in(header, DeviceAlloc))]);
+		send_buff.extend(send.map(BufferElem::Vector));
+		let mut recv_buff = Vec::from_iter(recv.map(BufferElem::Vector));
+		recv_buff.push(BufferElem::Sized(Box::<u8, _>::new_uninit_in(DeviceAlloc)));
+
+		let buffer_tkn = AvailBufferToken::new(send_buff, recv_buff).unwrap();
+		let mut used = request_vq
+			.dispatch_blocking(buffer_tkn, BufferType::Direct)
+			.map_err(|_| VirtioBlkError::RequestFailed)?;
+
+		let data = used.used_recv_buff.pop_front_vec();
+		let status = used
+			.used_recv_buff
+			.pop_front_downcast::<u8>()
+			.ok_or(VirtioBlkError::RequestFailed)?;
+		match *status {
+			VIRTIO_BLK_S_OK => Ok(data),
+			VIRTIO_BLK_S_UNSUPP => Err(VirtioBlkError::Unsupported),
+			_ => Err(VirtioBlkError::RequestFailed),
+		}
+	}
+
+	/// Reads the sectors starting at `sector` into `buf`.
+	pub fn read(&mut self, mut sector: u64, buf: &mut [u8]) -> Result<(), VirtioBlkError> {
+		for chunk in buf.chunks_mut(self.max_request_size) {
+			let data = Vec::with_capacity_in(chunk.len(), DeviceAlloc);
+			let data = self
+				.request(BlkReqHdr::new(VIRTIO_BLK_T_IN, sector), None, Some(data))?
+				.ok_or(VirtioBlkError::RequestFailed)?;
+			if data.len() != chunk.len() {
+				return Err(VirtioBlkError::RequestFailed);
+			}
+			chunk.copy_from_slice(&data);
+			sector += u64::try_from(chunk.len() / SECTOR_SIZE).unwrap();
+		}
+
+		Ok(())
+	}
+
+	/// Writes `buf` to the sectors starting at `sector`.
+	pub fn write(&mut self, mut sector: u64, buf: &[u8]) -> Result<(), VirtioBlkError> {
+		if self.is_read_only() {
+			return Err(VirtioBlkError::ReadOnly);
+		}
+
+		for chunk in buf.chunks(self.max_request_size) {
+			let mut data = Vec::with_capacity_in(chunk.len(), DeviceAlloc);
+			data.extend_from_slice(chunk);
+			self.request(BlkReqHdr::new(VIRTIO_BLK_T_OUT, sector), Some(data), None)?;
+			sector += u64::try_from(chunk.len() / SECTOR_SIZE).unwrap();
+		}
+
+		Ok(())
+	}
+
+	/// Makes all previous writes persistent.
+	///
+	/// Without `VIRTIO_BLK_F_FLUSH`, the device has no write cache and the
+	/// writes are already persistent.
+	pub fn flush(&mut self) -> Result<(), VirtioBlkError> {
+		if !self.dev_cfg.features.contains(FLUSH) {
+			return Ok(());
+		}
+
+		self.request(BlkReqHdr::new(VIRTIO_BLK_T_FLUSH, 0), None, None)?;
+		Ok(())
+	}
+}
+
+impl From<VirtioBlkError> for io::Error {
+	fn from(err: VirtioBlkError) -> Self {
+		match err {
+			VirtioBlkError::ReadOnly => io::Error::EROFS,
+			VirtioBlkError::Unsupported => io::Error::ENOSYS,
+			_ => io::Error::EIO,
+		}
+	}
+}
+
+impl BlockDevice for InterruptTicketMutex<VirtioBlkDriver> {
+	fn block_size(&self) -> usize {
+		self.lock().block_size
+	}
+
+	fn num_blocks(&self) -> u64 {
+		let driver = self.lock();
+		driver.capacity() / u64::try_from(driver.block_size / SECTOR_SIZE).unwrap()
+	}
+
+	fn is_read_only(&self) -> bool {
+		self.lock().is_read_only()
+	}
+
+	fn read_blocks(&self, block: u64, buf: &mut [u8]) -> io::Result<()> {
+		check_range(self, block, buf.len())?;
+		let mut driver = self.lock();
+		let sector = block * u64::try_from(driver.block_size / SECTOR_SIZE).unwrap();
+		Ok(driver.read(sector, buf)?)
+	}
+
+	fn write_blocks(&self, block: u64, buf: &[u8]) -> io::Result<()> {
+		check_range(self, block, buf.len())?;
+		let mut driver = self.lock();
+		let sector = block * u64::try_from(driver.block_size / SECTOR_SIZE).unwrap();
+		Ok(driver.write(sector, buf)?)
+	}
+
+	fn flush(&self) -> io::Result<()> {
+		Ok(self.lock().flush()?)
+	}
+}
+
+/// Error module of virtio's block driver.
+pub mod error {
+	/// Virtio block error enum.
+	#[derive(Debug, Copy, Clone)]
+	pub enum VirtioBlkError {
+		#[cfg(feature = "pci")]
+		NoDevCfg(u16),
+		NoRequestQueue(u16),
+		FailFeatureNeg(u16),
+		/// The first field contains the feature bits wanted by the driver.
+		/// but which are incompatible with the device feature set, second field.
+		IncompatibleFeatureSets(virtio::F, virtio::F),
+		/// Set of features does not adhere to the requirements of features
+		/// indicated by the specification
+		FeatureRequirementsNotMet(virtio::F),
+		/// The device cannot be written
+		ReadOnly,
+		/// The device does not support the request
+		Unsupported,
+		/// The device reported an error on a request
+		RequestFailed,
+	}
+}
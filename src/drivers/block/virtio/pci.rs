@@ -0,0 +1,118 @@
+use hermit_sync::InterruptTicketMutex;
+use pci_types::CommandRegister;
+use volatile::VolatileRef;
+
+use crate::arch::pci::PciConfigRegion;
+use crate::drivers::block::virtio::{BlkDevCfg, BlkDevCfgRaw, SECTOR_SIZE, VirtioBlkDriver, error};
+use crate::drivers::error::DriverError;
+use crate::drivers::pci::{PciDevice, PciDriver};
+use crate::drivers::registry::{DeviceMatch, DriverContext, PciDriverEntry};
+use crate::drivers::virtio::error::VirtioError;
+use crate::drivers::virtio::transport::pci;
+use crate::drivers::virtio::transport::pci::{PciCap, UniCapsColl};
+
+impl VirtioBlkDriver {
+	fn map_cfg(cap: &PciCap) -> Option<BlkDevCfg> {
+		let dev_cfg = pci::map_dev_cfg::<BlkDevCfgRaw>(cap)?;
+
+		let dev_cfg = VolatileRef::from_ref(dev_cfg);
+
+		Some(BlkDevCfg {
+			raw: dev_cfg,
+			dev_id: cap.dev_id(),
+			features: virtio::F::empty(),
+		})
+	}
+
+	/// Instantiates a new VirtioBlkDriver struct, by checking the available
+	/// configuration structures and moving them into the struct.
+	pub fn new(
+		caps_coll: UniCapsColl,
+		device: &PciDevice<PciConfigRegion>,
+	) -> Result<Self, error::VirtioBlkError> {
+		let device_id = device.device_id();
+
+		let UniCapsColl {
+			com_cfg,
+			notif_cfg,
+			isr_cfg,
+			dev_cfg_list,
+			..
+		} = caps_coll;
+
+		let Some(dev_cfg) = dev_cfg_list.iter().find_map(VirtioBlkDriver::map_cfg) else {
+			error!("No dev config. Aborting!");
+			return Err(error::VirtioBlkError::NoDevCfg(device_id));
+		};
+
+		Ok(VirtioBlkDriver {
+			dev_cfg,
+			com_cfg,
+			isr_stat: isr_cfg,
+			notif_cfg,
+			irq: device.get_irq().unwrap(),
+			request_vq: None,
+			block_size: SECTOR_SIZE,
+			max_request_size: SECTOR_SIZE,
+		})
+	}
+
+	/// Initializes virtio block device
+	pub fn init(device: &PciDevice<PciConfigRegion>) -> Result<VirtioBlkDriver, VirtioError> {
+		// enable bus master mode
+		device.set_command(CommandRegister::BUS_MASTER_ENABLE);
+
+		let mut drv = match pci::map_caps(device) {
+			Ok(caps) => match VirtioBlkDriver::new(caps, device) {
+				Ok(driver) => driver,
+				Err(blk_err) => {
+					error!("Initializing new block driver failed. Aborting!");
+					return Err(VirtioError::BlkDriver(blk_err));
+				}
+			},
+			Err(err) => {
+				error!("Mapping capabilities failed. Aborting!");
+				return Err(err);
+			}
+		};
+
+		match drv.init_dev() {
+			Ok(()) => info!(
+				"Block device with id {:x}, has been initialized by driver!",
+				drv.get_dev_id()
+			),
+			Err(blk_err) => {
+				drv.set_failed();
+				return Err(VirtioError::BlkDriver(blk_err));
+			}
+		}
+
+		Ok(drv)
+	}
+}
+
+/// Driver entry of virtio block devices
+pub(crate) struct VirtioBlkEntry;
+
+impl PciDriverEntry for VirtioBlkEntry {
+	fn name(&self) -> &'static str {
+		"virtio-blk"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Virtio(virtio::Id::Block)];
+		MATCHES
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<PciDriver, DriverError> {
+		ctx.request_memory_bars();
+		let drv = VirtioBlkDriver::init(ctx.device())?;
+		info!("Virtio block driver initialized.");
+
+		if let Some(irq) = ctx.request_irq("virtio") {
+			info!("Virtio interrupt handler at line {irq}");
+		}
+
+		Ok(PciDriver::VirtioBlk(InterruptTicketMutex::new(drv)))
+	}
+}
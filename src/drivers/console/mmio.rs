@@ -0,0 +1,66 @@
+use virtio::mmio::{DeviceRegisters, DeviceRegistersVolatileFieldAccess};
+use volatile::VolatileRef;
+
+use crate::drivers::InterruptLine;
+use crate::drivers::console::{ConsoleDevCfg, ConsoleDevCfgRaw, VirtioConsoleDriver};
+use crate::drivers::virtio::error::VirtioError;
+use crate::drivers::virtio::transport::mmio::{ComCfg, IsrStatus, NotifCfg};
+
+impl VirtioConsoleDriver {
+	pub fn new(
+		dev_id: u16,
+		mut registers: VolatileRef<'static, DeviceRegisters>,
+		irq: InterruptLine,
+	) -> Self {
+		let dev_cfg_raw: &'static mut ConsoleDevCfgRaw = unsafe {
+			&mut *registers
+				.borrow_mut()
+				.as_mut_ptr()
+				.config()
+				.as_raw_ptr()
+				.cast::<ConsoleDevCfgRaw>()
+				.as_ptr()
+		};
+		let dev_cfg_raw = VolatileRef::from_mut_ref(dev_cfg_raw);
+		let dev_cfg = ConsoleDevCfg {
+			raw: dev_cfg_raw,
+			dev_id,
+			features: virtio::F::empty(),
+		};
+		let isr_stat = IsrStatus::new(registers.borrow_mut());
+		let notif_cfg = NotifCfg::new(registers.borrow_mut());
+
+		VirtioConsoleDriver {
+			dev_cfg,
+			com_cfg: ComCfg::new(registers, 1),
+			isr_stat,
+			notif_cfg,
+			irq,
+			receive_vq: None,
+			transmit_vq: None,
+		}
+	}
+
+	/// Initializes virtio console device
+	pub fn init(
+		dev_id: u16,
+		registers: VolatileRef<'static, DeviceRegisters>,
+		irq: InterruptLine,
+	) -> Result<VirtioConsoleDriver, VirtioError> {
+		let mut drv = VirtioConsoleDriver::new(dev_id, registers, irq);
+		match drv.init_dev() {
+			Ok(()) => {
+				info!(
+					"Console device with id {:x}, has been initialized by driver!",
+					drv.get_dev_id()
+				);
+				drv.com_cfg.print_information();
+				Ok(drv)
+			}
+			Err(console_err) => {
+				drv.set_failed();
+				Err(VirtioError::ConsoleDriver(console_err))
+			}
+		}
+	}
+}
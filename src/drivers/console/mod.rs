@@ -0,0 +1,361 @@
+//! A module containing a virtio console driver.
+//!
+//! Only the first port of the device is used, so that the multiport feature
+//! is not negotiated and the device has a single receive and a single
+//! transmit queue. Received characters are collected in an input buffer on
+//! interrupts. The input buffer has its own lock, so that the console reads
+//! without locking the driver. Output is copied into buffers of the transmit
+//! queue, which are reclaimed before the next write.
+
+cfg_if::cfg_if! {
+	if #[cfg(feature = "pci")] {
+		pub mod pci;
+	} else {
+		pub mod mmio;
+	}
+}
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::hint;
+use core::task::Waker;
+
+use hermit_sync::InterruptTicketMutex;
+use virtio::{FeatureBits, le16, le32, le128};
+use volatile::access::ReadOnly;
+use volatile::{VolatileFieldAccess, VolatileRef};
+
+use self::error::VirtioConsoleError;
+use crate::config::VIRTIO_MAX_QUEUE_SIZE;
+#[cfg(not(feature = "pci"))]
+use crate::drivers::virtio::transport::mmio::{ComCfg, IsrStatus, NotifCfg};
+#[cfg(feature = "pci")]
+use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
+use crate::drivers::virtio::virtqueue::error::VirtqError;
+use crate::drivers::virtio::virtqueue::split::SplitVq;
+use crate::drivers::virtio::virtqueue::{
+	AvailBufferToken, BufferElem, BufferType, Virtq, VqIndex, VqSize,
+};
+use crate::drivers::{Driver, InterruptLine};
+use crate::executor::WakerRegistration;
+use crate::mm::device_alloc::DeviceAlloc;
+
+/// Configuration `cols` and `rows` are valid (`VIRTIO_CONSOLE_F_SIZE`)
+///
+/// The feature bits are not defined by `virtio-spec` (Virtio specification v1.2 - 5.3.3).
+const SIZE: virtio::F = virtio::F::from_bits_retain(le128::from_ne(1 << 0));
+
+/// Number of buffers, which are provided to the receive queue
+const RX_BUFFERS: u16 = 32;
+
+/// Size of a buffer in the receive queue
+const RX_BUFFER_SIZE: usize = 64;
+
+/// Maximum size of the data of a single buffer in the transmit queue
+const TX_BUFFER_SIZE: usize = 1024;
+
+/// Number of attempts to reclaim a buffer of the transmit queue during a
+/// write, before the remaining output is dropped
+const TX_RETRIES: usize = 0x1000;
+
+/// Characters, which have been received and not yet read
+static INPUT: InterruptTicketMutex<Input> = InterruptTicketMutex::new(Input {
+	chars: VecDeque::new(),
+	waker: WakerRegistration::new(),
+});
+
+struct Input {
+	chars: VecDeque<u8>,
+	waker: WakerRegistration,
+}
+
+/// Virtio's console device configuration structure.
+/// See specification v1.2. - 5.3.4
+#[derive(VolatileFieldAccess)]
+#[repr(C)]
+pub(crate) struct ConsoleDevCfgRaw {
+	#[access(ReadOnly)]
+	cols: le16,
+	#[access(ReadOnly)]
+	rows: le16,
+	#[access(ReadOnly)]
+	max_nr_ports: le32,
+	emerg_wr: le32,
+}
+
+pub(crate) struct ConsoleDevCfg {
+	pub raw: VolatileRef<'static, ConsoleDevCfgRaw>,
+	pub dev_id: u16,
+	pub features: virtio::F,
+}
+
+pub(crate) struct VirtioConsoleDriver {
+	pub(super) dev_cfg: ConsoleDevCfg,
+	pub(super) com_cfg: ComCfg,
+	pub(super) isr_stat: IsrStatus,
+	pub(super) notif_cfg: NotifCfg,
+	pub(super) irq: InterruptLine,
+	pub(super) receive_vq: Option<Box<dyn Virtq>>,
+	pub(super) transmit_vq: Option<Box<dyn Virtq>>,
+}
+
+impl Driver for VirtioConsoleDriver {
+	fn get_interrupt_number(&self) -> InterruptLine {
+		self.irq
+	}
+
+	fn get_name(&self) -> &'static str {
+		"virtio"
+	}
+
+	fn get_queue_count(&self) -> usize {
+		usize::from(self.receive_vq.is_some()) + usize::from(self.transmit_vq.is_some())
+	}
+}
+
+/// Returns the driver of the virtio console, if a device has been found.
+pub(crate) fn driver() -> Option<&'static InterruptTicketMutex<VirtioConsoleDriver>> {
+	#[cfg(feature = "pci")]
+	let driver = crate::drivers::pci::get_console_driver();
+	#[cfg(not(feature = "pci"))]
+	let driver = crate::drivers::mmio::get_console_driver();
+
+	driver
+}
+
+/// Returns the next received character.
+pub(crate) fn read() -> Option<u8> {
+	INPUT.lock().chars.pop_front()
+}
+
+/// Returns `true`, if no received characters are available.
+pub(crate) fn is_empty() -> bool {
+	INPUT.lock().chars.is_empty()
+}
+
+/// Registers `waker` to be woken, when characters are received.
+pub(crate) fn register_waker(waker: &Waker) {
+	INPUT.lock().waker.register(waker);
+}
+
+/// Provides `num` buffers to the receive queue `vq`.
+///
+/// The function is called with the driver locked, so it must not log.
+fn fill_queue(vq: &mut dyn Virtq, num: u16) {
+	for _ in 0..num {
+		let buff_tkn = AvailBufferToken::new(vec![], vec![BufferElem::Vector(
+			Vec::with_capacity_in(RX_BUFFER_SIZE, DeviceAlloc),
+		)])
+		.unwrap();
+
+		if vq.dispatch(buff_tkn, false, BufferType::Direct).is_err() {
+			break;
+		}
+	}
+}
+
+impl VirtioConsoleDriver {
+	pub fn get_dev_id(&self) -> u16 {
+		self.dev_cfg.dev_id
+	}
+
+	pub fn set_failed(&mut self) {
+		self.com_cfg.set_failed();
+	}
+
+	/// Moves the received characters into the input buffer and wakes the reader.
+	///
+	/// The handler must not write to the console, e.g., by logging, because
+	/// the console writes to the device.
+	pub fn handle_interrupt(&mut self) {
+		self.isr_stat.acknowledge();
+
+		let Some(vq) = self.receive_vq.as_mut() else {
+			return;
+		};
+
+		let mut received = false;
+		loop {
+			let mut used = match vq.try_recv() {
+				Ok(used) => used,
+				Err(VirtqError::NoNewUsed) => break,
+				Err(_) => {
+					self.com_cfg.set_failed();
+					return;
+				}
+			};
+
+			if let Some(data) = used.used_recv_buff.pop_front_vec() {
+				INPUT
+					.lock()
+					.chars
+					.extend(data.iter().map(|&c| if c == b'\r' { b'\n' } else { c }));
+				received = true;
+			}
+			fill_queue(vq.as_mut(), 1);
+		}
+
+		if received {
+			INPUT.lock().waker.wake();
+		}
+	}
+
+	/// Sends `buf` to the first port of the device.
+	///
+	/// If the device does not consume its buffers within [`TX_RETRIES`]
+	/// attempts, the remaining output is dropped instead of blocking the
+	/// console.
+	pub fn write(&mut self, buf: &[u8]) {
+		let Some(vq) = self.transmit_vq.as_mut() else {
+			return;
+		};
+
+		let mut retries = TX_RETRIES;
+		for chunk in buf.chunks(TX_BUFFER_SIZE) {
+			// reclaim the buffers of the previous writes
+			while vq.try_recv().is_ok() {}
+
+			loop {
+				let mut data = Vec::with_capacity_in(chunk.len(), DeviceAlloc);
+				data.extend_from_slice(chunk);
+				let buff_tkn =
+					AvailBufferToken::new(vec![BufferElem::Vector(data)], vec![]).unwrap();
+
+				match vq.dispatch(buff_tkn, false, BufferType::Direct) {
+					Ok(()) => break,
+					Err(VirtqError::NoDescrAvail) if retries > 0 => {
+						while vq.try_recv().is_err() && retries > 0 {
+							retries -= 1;
+							hint::spin_loop();
+						}
+					}
+					Err(_) => return,
+				}
+			}
+		}
+	}
+
+	/// Negotiates a subset of features, understood and wanted by both the OS
+	/// and the device, and returns this subset.
+	fn negotiate_features(
+		&mut self,
+		driver_features: virtio::F,
+	) -> Result<virtio::F, VirtioConsoleError> {
+		let device_features = self.com_cfg.dev_features();
+
+		if device_features.requirements_satisfied() {
+			debug!("Feature set wanted by console driver are in conformance with specification.");
+		} else {
+			return Err(VirtioConsoleError::FeatureRequirementsNotMet(
+				device_features,
+			));
+		}
+
+		let features = driver_features & device_features;
+		if features.contains(virtio::F::VERSION_1) {
+			self.com_cfg.set_drv_features(features);
+			Ok(features)
+		} else {
+			Err(VirtioConsoleError::IncompatibleFeatureSets(
+				driver_features,
+				device_features,
+			))
+		}
+	}
+
+	/// Initializes the device in adherence to specification.
+	///
+	/// See Virtio specification v1.2. - 3.1.1.
+	///                      and v1.2. - 5.3.5
+	pub fn init_dev(&mut self) -> Result<(), VirtioConsoleError> {
+		// Reset
+		self.com_cfg.reset_dev();
+
+		// Indicate device, that OS noticed it
+		self.com_cfg.ack_dev();
+
+		// Indicate device, that driver is able to handle it
+		self.com_cfg.set_drv();
+
+		let features = self.negotiate_features(virtio::F::VERSION_1 | SIZE)?;
+
+		// Indicates the device, that the current feature set is final for the driver
+		// and will not be changed.
+		self.com_cfg.features_ok();
+
+		// Checks if the device has accepted final set. This finishes feature negotiation.
+		if self.com_cfg.check_features() {
+			info!(
+				"Features have been negotiated between virtio console device {:x} and driver.",
+				self.dev_cfg.dev_id
+			);
+			// Set feature set in device config fur future use.
+			self.dev_cfg.features = features;
+		} else {
+			return Err(VirtioConsoleError::FailFeatureNeg(self.dev_cfg.dev_id));
+		}
+
+		// receiveq(port0)
+		let mut receive_vq = SplitVq::new(
+			&mut self.com_cfg,
+			&self.notif_cfg,
+			VqSize::from(VIRTIO_MAX_QUEUE_SIZE),
+			VqIndex::from(0u16),
+			self.dev_cfg.features,
+		)
+		.map_err(|_| VirtioConsoleError::NoQueue(self.dev_cfg.dev_id))?;
+		fill_queue(
+			&mut receive_vq,
+			RX_BUFFERS.min(u16::from(receive_vq.size())),
+		);
+		// Interrupt for received characters is wanted
+		receive_vq.enable_notifs();
+		self.receive_vq = Some(Box::new(receive_vq));
+
+		// transmitq(port0)
+		let mut transmit_vq = SplitVq::new(
+			&mut self.com_cfg,
+			&self.notif_cfg,
+			VqSize::from(VIRTIO_MAX_QUEUE_SIZE),
+			VqIndex::from(1u16),
+			self.dev_cfg.features,
+		)
+		.map_err(|_| VirtioConsoleError::NoQueue(self.dev_cfg.dev_id))?;
+		// The transmitted buffers are reclaimed on the next write.
+		transmit_vq.disable_notifs();
+		self.transmit_vq = Some(Box::new(transmit_vq));
+
+		// At this point the device is "live"
+		self.com_cfg.drv_ok();
+
+		if features.contains(SIZE) {
+			info!(
+				"Console device {:x} has {} columns and {} rows",
+				self.dev_cfg.dev_id,
+				self.dev_cfg.raw.as_ptr().cols().read().to_ne(),
+				self.dev_cfg.raw.as_ptr().rows().read().to_ne()
+			);
+		}
+
+		Ok(())
+	}
+}
+
+/// Error module of virtio's console driver.
+pub mod error {
+	/// Virtio console error enum.
+	#[derive(Debug, Copy, Clone)]
+	pub enum VirtioConsoleError {
+		#[cfg(feature = "pci")]
+		NoDevCfg(u16),
+		NoQueue(u16),
+		FailFeatureNeg(u16),
+		/// The first field contains the feature bits wanted by the driver.
+		/// but which are incompatible with the device feature set, second field.
+		IncompatibleFeatureSets(virtio::F, virtio::F),
+		/// Set of features does not adhere to the requirements of features
+		/// indicated by the specification
+		FeatureRequirementsNotMet(virtio::F),
+	}
+}
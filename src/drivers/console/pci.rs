@@ -0,0 +1,117 @@
+use hermit_sync::InterruptTicketMutex;
+use pci_types::CommandRegister;
+use volatile::VolatileRef;
+
+use crate::arch::pci::PciConfigRegion;
+use crate::drivers::console::{ConsoleDevCfg, ConsoleDevCfgRaw, VirtioConsoleDriver, error};
+use crate::drivers::error::DriverError;
+use crate::drivers::pci::{PciDevice, PciDriver};
+use crate::drivers::registry::{DeviceMatch, DriverContext, PciDriverEntry};
+use crate::drivers::virtio::error::VirtioError;
+use crate::drivers::virtio::transport::pci;
+use crate::drivers::virtio::transport::pci::{PciCap, UniCapsColl};
+
+impl VirtioConsoleDriver {
+	fn map_cfg(cap: &PciCap) -> Option<ConsoleDevCfg> {
+		let dev_cfg = pci::map_dev_cfg::<ConsoleDevCfgRaw>(cap)?;
+
+		let dev_cfg = VolatileRef::from_mut_ref(dev_cfg);
+
+		Some(ConsoleDevCfg {
+			raw: dev_cfg,
+			dev_id: cap.dev_id(),
+			features: virtio::F::empty(),
+		})
+	}
+
+	/// Instantiates a new VirtioConsoleDriver struct, by checking the available
+	/// configuration structures and moving them into the struct.
+	pub fn new(
+		caps_coll: UniCapsColl,
+		device: &PciDevice<PciConfigRegion>,
+	) -> Result<Self, error::VirtioConsoleError> {
+		let device_id = device.device_id();
+
+		let UniCapsColl {
+			com_cfg,
+			notif_cfg,
+			isr_cfg,
+			dev_cfg_list,
+			..
+		} = caps_coll;
+
+		let Some(dev_cfg) = dev_cfg_list.iter().find_map(VirtioConsoleDriver::map_cfg) else {
+			error!("No dev config. Aborting!");
+			return Err(error::VirtioConsoleError::NoDevCfg(device_id));
+		};
+
+		Ok(VirtioConsoleDriver {
+			dev_cfg,
+			com_cfg,
+			isr_stat: isr_cfg,
+			notif_cfg,
+			irq: device.get_irq().unwrap(),
+			receive_vq: None,
+			transmit_vq: None,
+		})
+	}
+
+	/// Initializes virtio console device
+	pub fn init(device: &PciDevice<PciConfigRegion>) -> Result<VirtioConsoleDriver, VirtioError> {
+		// enable bus master mode
+		device.set_command(CommandRegister::BUS_MASTER_ENABLE);
+
+		let mut drv = match pci::map_caps(device) {
+			Ok(caps) => match VirtioConsoleDriver::new(caps, device) {
+				Ok(driver) => driver,
+				Err(console_err) => {
+					error!("Initializing new console driver failed. Aborting!");
+					return Err(VirtioError::ConsoleDriver(console_err));
+				}
+			},
+			Err(err) => {
+				error!("Mapping capabilities failed. Aborting!");
+				return Err(err);
+			}
+		};
+
+		match drv.init_dev() {
+			Ok(()) => info!(
+				"Console device with id {:x}, has been initialized by driver!",
+				drv.get_dev_id()
+			),
+			Err(console_err) => {
+				drv.set_failed();
+				return Err(VirtioError::ConsoleDriver(console_err));
+			}
+		}
+
+		Ok(drv)
+	}
+}
+
+/// Driver entry of virtio console devices
+pub(crate) struct VirtioConsoleEntry;
+
+impl PciDriverEntry for VirtioConsoleEntry {
+	fn name(&self) -> &'static str {
+		"virtio-console"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Virtio(virtio::Id::Console)];
+		MATCHES
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<PciDriver, DriverError> {
+		ctx.request_memory_bars();
+		let drv = VirtioConsoleDriver::init(ctx.device())?;
+		info!("Virtio console driver initialized.");
+
+		if let Some(irq) = ctx.request_irq("virtio") {
+			info!("Virtio interrupt handler at line {irq}");
+		}
+
+		Ok(PciDriver::VirtioConsole(InterruptTicketMutex::new(drv)))
+	}
+}
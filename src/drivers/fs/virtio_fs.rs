@@ -1,8 +1,11 @@
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::str;
+use core::task::Waker;
 
+use fuse_abi::linux::fuse_out_header;
 use pci_types::InterruptLine;
 use virtio::FeatureBits;
 use virtio::fs::ConfigVolatileFieldAccess;
@@ -19,11 +22,22 @@ use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
 use crate::drivers::virtio::virtqueue::error::VirtqError;
 use crate::drivers::virtio::virtqueue::split::SplitVq;
 use crate::drivers::virtio::virtqueue::{
-	AvailBufferToken, BufferElem, BufferType, Virtq, VqIndex, VqSize,
+	AvailBufferToken, BufferElem, BufferType, UsedBufferToken, Virtq, VqIndex, VqSize,
 };
-use crate::fs::fuse::{self, FuseInterface, Rsp, RspHeader};
+use crate::fs::fuse::{self, CmdPayload, FuseInterface, Rsp, RspHeader};
 use crate::mm::device_alloc::DeviceAlloc;
 
+/// Maximum number of descriptors, which are used for a borrowed command payload.
+/// Payloads, which are more fragmented in physical memory, are copied.
+const MAX_BORROWED_ELEMS: usize = 32;
+
+/// Number of descriptors of a request besides the borrowed command payload
+/// (command header, response header and response payload)
+const FIXED_DESCRS_PER_REQUEST: usize = 3;
+
+/// Maximum number of descriptors, which are used by a single request.
+const MAX_DESCRS_PER_REQUEST: usize = MAX_BORROWED_ELEMS + FIXED_DESCRS_PER_REQUEST;
+
 /// A wrapper struct for the raw configuration structure.
 /// Handling the right access to fields, as some are read-only
 /// for the driver.
@@ -44,6 +58,15 @@ pub(crate) struct VirtioFsDriver {
 	pub(super) isr_stat: IsrStatus,
 	pub(super) notif_cfg: NotifCfg,
 	pub(super) vqueues: Vec<Box<dyn Virtq>>,
+	/// Number of requests in flight for each queue
+	pub(super) in_flight: Vec<usize>,
+	/// Responses, which were received, but not yet collected by the
+	/// waiting task, indexed by the unique ID of the request
+	pub(super) completed: BTreeMap<u64, UsedBufferToken>,
+	/// Tasks, which wait for the response to a request, indexed by the unique ID of the request
+	pub(super) rsp_wakers: BTreeMap<u64, Waker>,
+	/// Tasks, which wait for a request queue to accept another request
+	pub(super) submit_wakers: Vec<Waker>,
 	pub(super) irq: InterruptLine,
 }
 
@@ -143,36 +166,118 @@ impl VirtioFsDriver {
 			.unwrap();
 			self.vqueues.push(Box::new(vq));
 		}
+		self.in_flight = vec![0; self.vqueues.len()];
 
 		// At this point the device is "live"
 		self.com_cfg.drv_ok();
 
 		Ok(())
 	}
+
+	/// Returns the size of the request queue `queue`.
+	fn queue_size(&self, queue: usize) -> usize {
+		usize::from(u16::from(self.vqueues[queue].size()))
+	}
+
+	/// Returns the number of descriptors, which a borrowed command payload may
+	/// use in the request queue `queue`, so that a request always fits into
+	/// the empty queue.
+	fn max_borrowed_elems(&self, queue: usize) -> usize {
+		MAX_BORROWED_ELEMS.min(
+			self.queue_size(queue)
+				.saturating_sub(FIXED_DESCRS_PER_REQUEST),
+		)
+	}
+
+	/// Returns the request queue with the fewest requests in flight,
+	/// which is able to accept another request.
+	///
+	/// An empty queue accepts a request in any case, because the borrowed
+	/// payload is limited to its size.
+	fn select_request_queue(&self) -> Option<usize> {
+		(1..self.vqueues.len())
+			.filter(|&i| {
+				self.in_flight[i] == 0
+					|| (self.in_flight[i] + 1) * MAX_DESCRS_PER_REQUEST <= self.queue_size(i)
+			})
+			.min_by_key(|&i| self.in_flight[i])
+	}
+
+	/// Moves all responses, which are provided by the device, to `self.completed`
+	/// and wakes the waiting tasks.
+	fn collect_responses(&mut self) {
+		let mut freed = false;
+		for (i, vq) in self.vqueues.iter_mut().enumerate().skip(1) {
+			while let Ok(tkn) = vq.try_recv() {
+				self.in_flight[i] -= 1;
+				freed = true;
+
+				// Every response starts with a `fuse_out_header`.
+				let Some(elem) = tkn.used_recv_buff.front() else {
+					warn!("Drop FUSE response without header");
+					continue;
+				};
+				let out_header = unsafe { elem.addr().cast::<fuse_out_header>().read() };
+				if let Some(waker) = self.rsp_wakers.remove(&out_header.unique) {
+					waker.wake();
+				}
+				self.completed.insert(out_header.unique, tkn);
+			}
+		}
+
+		if freed {
+			for waker in self.submit_wakers.drain(..) {
+				waker.wake();
+			}
+		}
+	}
+
+	pub fn handle_interrupt(&mut self) {
+		self.isr_stat.acknowledge();
+		self.collect_responses();
+	}
 }
 
 impl FuseInterface for VirtioFsDriver {
-	fn send_command<O: fuse::ops::Op + 'static>(
+	fn can_submit(&self) -> bool {
+		self.select_request_queue().is_some()
+	}
+
+	fn submit_command<O: fuse::ops::Op + 'static>(
 		&mut self,
 		cmd: fuse::Cmd<O>,
 		rsp_payload_len: u32,
-	) -> Result<fuse::Rsp<O>, VirtqError>
+	) -> Result<(), VirtqError>
 	where
 		<O as fuse::ops::Op>::InStruct: Send,
 		<O as fuse::ops::Op>::OutStruct: Send,
 	{
+		let queue = self
+			.select_request_queue()
+			.ok_or(VirtqError::NoDescrAvail)?;
+
 		let fuse::Cmd {
 			headers: cmd_headers,
 			payload: cmd_payload_opt,
 		} = cmd;
-		let send = if let Some(cmd_payload) = cmd_payload_opt {
-			vec![
-				BufferElem::Sized(cmd_headers),
-				BufferElem::Vector(cmd_payload),
-			]
-		} else {
-			vec![BufferElem::Sized(cmd_headers)]
-		};
+		let mut send = vec![BufferElem::Sized(cmd_headers)];
+		match cmd_payload_opt {
+			Some(CmdPayload::Owned(cmd_payload)) => send.push(BufferElem::Vector(cmd_payload)),
+			Some(CmdPayload::Borrowed(cmd_payload)) => {
+				// SAFETY: the command is processed synchronously and the caller
+				// guarantees that the payload stays valid until then.
+				let max_elems = self.max_borrowed_elems(queue);
+				if let Some(elems) = unsafe { BufferElem::borrowed(cmd_payload, max_elems) } {
+					send.extend(elems);
+				} else {
+					// The payload is not suitable for zero-copy transfer, so we copy it.
+					trace!("Copy FUSE payload of {} bytes", cmd_payload.len());
+					let cmd_payload = unsafe { &*cmd_payload };
+					send.push(BufferElem::Vector(cmd_payload.to_vec_in(DeviceAlloc)));
+				}
+			}
+			None => {}
+		}
 
 		let rsp_headers = Box::<RspHeader<O>, _>::new_uninit_in(DeviceAlloc);
 		let recv = if rsp_payload_len == 0 {
@@ -186,12 +291,36 @@ impl FuseInterface for VirtioFsDriver {
 		};
 
 		let buffer_tkn = AvailBufferToken::new(send, recv).unwrap();
-		let mut transfer_result =
-			self.vqueues[1].dispatch_blocking(buffer_tkn, BufferType::Direct)?;
+		self.vqueues[queue].dispatch(buffer_tkn, false, BufferType::Direct)?;
+		self.in_flight[queue] += 1;
 
+		Ok(())
+	}
+
+	fn try_complete<O: fuse::ops::Op + 'static>(&mut self, unique: u64) -> Option<Rsp<O>>
+	where
+		<O as fuse::ops::Op>::InStruct: Send,
+		<O as fuse::ops::Op>::OutStruct: Send,
+	{
+		self.collect_responses();
+
+		let mut transfer_result = self.completed.remove(&unique)?;
 		let headers = transfer_result.used_recv_buff.pop_front_downcast().unwrap();
 		let payload = transfer_result.used_recv_buff.pop_front_vec();
-		Ok(Rsp { headers, payload })
+		Some(Rsp { headers, payload })
+	}
+
+	fn register_waker(&mut self, unique: Option<u64>, waker: &Waker) {
+		match unique {
+			Some(unique) => {
+				self.rsp_wakers.insert(unique, waker.clone());
+			}
+			None => {
+				if !self.submit_wakers.iter().any(|w| w.will_wake(waker)) {
+					self.submit_wakers.push(waker.clone());
+				}
+			}
+		}
 	}
 
 	fn get_mount_point(&self) -> String {
@@ -210,6 +339,10 @@ impl Driver for VirtioFsDriver {
 	fn get_name(&self) -> &'static str {
 		"virtio"
 	}
+
+	fn get_queue_count(&self) -> usize {
+		self.vqueues.len()
+	}
 }
 
 /// Error module of virtios filesystem driver.
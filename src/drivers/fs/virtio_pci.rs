@@ -1,10 +1,14 @@
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 
+use hermit_sync::InterruptTicketMutex;
 use volatile::VolatileRef;
 
 use crate::arch::pci::PciConfigRegion;
+use crate::drivers::error::DriverError;
 use crate::drivers::fs::virtio_fs::{FsDevCfg, VirtioFsDriver};
-use crate::drivers::pci::PciDevice;
+use crate::drivers::pci::{PciDevice, PciDriver};
+use crate::drivers::registry::{DeviceMatch, DriverContext, PciDriverEntry};
 use crate::drivers::virtio::error::{self, VirtioError};
 use crate::drivers::virtio::transport::pci;
 use crate::drivers::virtio::transport::pci::{PciCap, UniCapsColl};
@@ -49,6 +53,10 @@ impl VirtioFsDriver {
 			isr_stat: isr_cfg,
 			notif_cfg,
 			vqueues: Vec::new(),
+			in_flight: Vec::new(),
+			completed: BTreeMap::new(),
+			rsp_wakers: BTreeMap::new(),
+			submit_wakers: Vec::new(),
 			irq: device.get_irq().unwrap(),
 		})
 	}
@@ -83,3 +91,29 @@ impl VirtioFsDriver {
 		Ok(drv)
 	}
 }
+
+/// Driver entry of virtio filesystem devices
+pub(crate) struct VirtioFsEntry;
+
+impl PciDriverEntry for VirtioFsEntry {
+	fn name(&self) -> &'static str {
+		"virtio-fs"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Virtio(virtio::Id::Fs)];
+		MATCHES
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<PciDriver, DriverError> {
+		ctx.request_memory_bars();
+		let drv = VirtioFsDriver::init(ctx.device())?;
+		info!("Virtio filesystem driver initialized.");
+
+		if let Some(irq) = ctx.request_irq("virtio") {
+			info!("Virtio interrupt handler at line {irq}");
+		}
+
+		Ok(PciDriver::VirtioFs(InterruptTicketMutex::new(drv)))
+	}
+}
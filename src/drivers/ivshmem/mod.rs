@@ -0,0 +1,143 @@
+//! A driver for the inter-VM shared memory device (ivshmem) of QEMU.
+//!
+//! The device provides a region of host memory (BAR 2), which is shared
+//! between several virtual machines on the same host. With `ivshmem-doorbell`,
+//! the ivshmem server assigns a position to each instance, by which the peers
+//! raise interrupts at each other through the doorbell register (BAR 0).
+//! `ivshmem-plain` provides only the memory, so that waiting for a peer
+//! falls back to polling.
+
+pub mod pci;
+pub mod ring;
+
+use core::ptr;
+use core::task::Waker;
+
+use memory_addresses::VirtAddr;
+use pci_types::InterruptLine;
+
+use crate::drivers::Driver;
+use crate::executor::WakerRegistration;
+
+/// Interrupt mask register
+const INTR_MASK: usize = 0x00;
+/// Interrupt status register, which is cleared by reading it
+const INTR_STATUS: usize = 0x04;
+/// Position of this instance, which is assigned by the ivshmem server
+const IV_POSITION: usize = 0x08;
+/// Writing `peer << 16 | vector` raises the interrupt `vector` at the instance `peer`.
+const DOORBELL: usize = 0x0c;
+
+/// Shared memory region of the device
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ShmRegion {
+	pub virt_addr: VirtAddr,
+	pub size: usize,
+}
+
+pub(crate) struct IvshmemDriver {
+	/// Registers of the device (BAR 0)
+	regs: VirtAddr,
+	region: ShmRegion,
+	irq: InterruptLine,
+	/// Position of this instance, if the device supports the doorbell
+	position: Option<u16>,
+	/// Task, which waits for a message of a peer
+	rx_waker: WakerRegistration,
+	/// Task, which waits for free space in the ring of a peer
+	tx_waker: WakerRegistration,
+}
+
+impl Driver for IvshmemDriver {
+	fn get_interrupt_number(&self) -> InterruptLine {
+		self.irq
+	}
+
+	fn get_name(&self) -> &'static str {
+		"ivshmem"
+	}
+}
+
+impl IvshmemDriver {
+	/// Creates the driver of a device with the registers at `regs` and enables its interrupts.
+	fn new(regs: VirtAddr, region: ShmRegion, irq: InterruptLine) -> Self {
+		let mut drv = Self {
+			regs,
+			region,
+			irq,
+			position: None,
+			rx_waker: WakerRegistration::new(),
+			tx_waker: WakerRegistration::new(),
+		};
+
+		// `ivshmem-plain` reports the position -1
+		drv.position = u16::try_from(drv.read_reg(IV_POSITION)).ok();
+		if drv.position.is_some() {
+			drv.write_reg(INTR_MASK, u32::MAX);
+		}
+
+		drv
+	}
+
+	/// Returns the shared memory region of the device.
+	pub fn region(&self) -> ShmRegion {
+		self.region
+	}
+
+	/// Returns the position of this instance, if the device supports the doorbell.
+	pub fn position(&self) -> Option<u16> {
+		self.position
+	}
+
+	/// Raises the interrupt `vector` at the instance `peer`.
+	pub fn ring_doorbell(&self, peer: u16, vector: u16) {
+		if self.position.is_some() {
+			self.write_reg(DOORBELL, (u32::from(peer) << 16) | u32::from(vector));
+		}
+	}
+
+	/// Registers the task, which waits for a message of a peer.
+	pub fn register_rx_waker(&mut self, waker: &Waker) {
+		self.rx_waker.register(waker);
+	}
+
+	/// Registers the task, which waits for free space in the ring of a peer.
+	pub fn register_tx_waker(&mut self, waker: &Waker) {
+		self.tx_waker.register(waker);
+	}
+
+	/// Acknowledges the interrupt and wakes the waiting tasks.
+	///
+	/// The doorbell does not tell, whether the peer has sent or received a
+	/// message, so that both directions are woken up.
+	pub fn handle_interrupt(&mut self) {
+		if self.position.is_none() {
+			return;
+		}
+
+		if self.read_reg(INTR_STATUS) != 0 {
+			self.rx_waker.wake();
+			self.tx_waker.wake();
+		}
+	}
+
+	fn read_reg(&self, offset: usize) -> u32 {
+		unsafe { ptr::read_volatile((self.regs + offset).as_ptr::<u32>()) }
+	}
+
+	fn write_reg(&self, offset: usize, value: u32) {
+		unsafe { ptr::write_volatile((self.regs + offset).as_mut_ptr::<u32>(), value) }
+	}
+}
+
+/// Error module of the ivshmem driver.
+pub mod error {
+	/// Ivshmem error enum.
+	#[derive(Debug, Copy, Clone)]
+	pub enum IvshmemError {
+		/// BAR 0 does not contain the registers
+		NoRegisters(u16),
+		/// BAR 2 does not contain the shared memory
+		NoSharedMemory(u16),
+	}
+}
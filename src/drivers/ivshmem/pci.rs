@@ -0,0 +1,65 @@
+use hermit_sync::InterruptTicketMutex;
+use memory_addresses::{PhysAddr, VirtAddr};
+use pci_types::{Bar, CommandRegister};
+
+use crate::arch::mm::paging::{BasePageSize, PageSize};
+use crate::drivers::error::DriverError;
+use crate::drivers::ivshmem::error::IvshmemError;
+use crate::drivers::ivshmem::{IvshmemDriver, ShmRegion};
+use crate::drivers::pci::{PciDriver, resource};
+use crate::drivers::registry::{DeviceMatch, DriverContext, PciDriverEntry};
+use crate::env;
+
+/// Driver entry of inter-VM shared memory devices
+pub(crate) struct IvshmemEntry;
+
+impl PciDriverEntry for IvshmemEntry {
+	fn name(&self) -> &'static str {
+		"ivshmem"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Pci {
+			vendor_id: 0x1af4,
+			device_ids: 0x1110..=0x1110,
+		}];
+		MATCHES
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<PciDriver, DriverError> {
+		let device = ctx.device();
+		let device_id = device.device_id();
+
+		// The registers occupy 256 bytes, which are not necessarily page-aligned.
+		let Some(Bar::Memory32 { address, size, .. }) = ctx.request_bar(0) else {
+			return Err(IvshmemError::NoRegisters(device_id).into());
+		};
+		let address = resource::bus_to_cpu(address.into());
+		device.insert_bar_region(0, address, size.try_into().unwrap());
+		let regs = if env::is_uefi() {
+			VirtAddr::new(address)
+		} else {
+			let offset = address % BasePageSize::SIZE;
+			let page = PhysAddr::new(address - offset);
+			crate::mm::map(page, BasePageSize::SIZE as usize, true, true, true) + offset
+		};
+
+		ctx.request_bar(2);
+		let Some((virt_addr, size)) = device.memory_map_bar(2, false) else {
+			return Err(IvshmemError::NoSharedMemory(device_id).into());
+		};
+
+		device.set_command(CommandRegister::MEMORY_ENABLE);
+
+		let irq = ctx.request_irq("ivshmem").unwrap_or_default();
+		let drv = IvshmemDriver::new(regs, ShmRegion { virt_addr, size }, irq);
+		match drv.position() {
+			Some(position) => info!(
+				"Shared memory of {size:#x} bytes at {virt_addr:p}, position {position}, doorbell at line {irq}"
+			),
+			None => info!("Shared memory of {size:#x} bytes at {virt_addr:p} without doorbell"),
+		}
+
+		Ok(PciDriver::Ivshmem(InterruptTicketMutex::new(drv)))
+	}
+}
@@ -0,0 +1,182 @@
+//! Message rings in shared memory
+//!
+//! A ring transfers messages from a single producer to a single consumer,
+//! which may run in different virtual machines. It consists of a header with
+//! the positions of both sides, each in a cache line of its own, followed by
+//! the data area. Each message is stored as its length (`u32`, little endian)
+//! followed by its data, which wraps around at the end of the data area.
+//! The positions increase monotonically and are taken modulo the capacity,
+//! which is a power of two.
+//!
+//! The memory of the rings has to be zeroed, before the instances start.
+
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use memory_addresses::VirtAddr;
+
+use crate::io;
+
+/// Size of the length, which precedes each message
+const LEN_SIZE: u32 = size_of::<u32>() as u32;
+
+/// Smallest data area of a ring
+const MIN_CAPACITY: u32 = 64;
+
+/// Position of one side of the ring in a cache line of its own
+#[repr(C, align(64))]
+struct Position(AtomicU32);
+
+#[repr(C)]
+struct RingHeader {
+	/// Position, up to which the producer has written messages
+	head: Position,
+	/// Position, up to which the consumer has read messages
+	tail: Position,
+}
+
+/// One direction of a message exchange in shared memory
+#[derive(Debug)]
+pub(crate) struct MessageRing {
+	header: VirtAddr,
+	data: VirtAddr,
+	/// Size of the data area, which is a power of two
+	capacity: u32,
+}
+
+impl MessageRing {
+	/// Creates a ring in the shared memory of `size` bytes at `addr`.
+	///
+	/// Returns `None`, if the memory is too small for a ring.
+	///
+	/// # Safety
+	///
+	/// The memory has to be mapped and may only be accessed by this ring
+	/// and its counterpart in the peer.
+	pub unsafe fn new(addr: VirtAddr, size: usize) -> Option<Self> {
+		let data_size = size.checked_sub(size_of::<RingHeader>())?;
+		let data_size = u32::try_from(data_size).unwrap_or(u32::MAX).min(1 << 31);
+		if data_size < MIN_CAPACITY {
+			return None;
+		}
+
+		Some(Self {
+			header: addr,
+			data: addr + size_of::<RingHeader>(),
+			capacity: 1 << data_size.ilog2(),
+		})
+	}
+
+	fn header(&self) -> &RingHeader {
+		unsafe { &*self.header.as_ptr::<RingHeader>() }
+	}
+
+	/// Returns the size of the largest message, which fits into the ring.
+	pub fn max_message_size(&self) -> usize {
+		(self.capacity - LEN_SIZE) as usize
+	}
+
+	/// Returns whether the ring does not contain any message.
+	pub fn is_empty(&self) -> bool {
+		let header = self.header();
+		header.head.0.load(Ordering::Acquire) == header.tail.0.load(Ordering::Acquire)
+	}
+
+	/// Returns whether a message of `len` bytes fits into the free space.
+	pub fn can_send(&self, len: usize) -> bool {
+		let header = self.header();
+		let used = header
+			.head
+			.0
+			.load(Ordering::Acquire)
+			.wrapping_sub(header.tail.0.load(Ordering::Acquire));
+		u32::try_from(len)
+			.ok()
+			.and_then(|len| len.checked_add(LEN_SIZE))
+			.is_some_and(|total| total <= self.capacity - used)
+	}
+
+	/// Appends the message `msg` to the ring.
+	///
+	/// Returns `EAGAIN`, if the free space is not sufficient, and `EMSGSIZE`,
+	/// if the message never fits into the ring.
+	pub fn try_send(&self, msg: &[u8]) -> io::Result<()> {
+		if msg.len() > self.max_message_size() {
+			return Err(io::Error::EMSGSIZE);
+		}
+		let len = msg.len() as u32;
+
+		let header = self.header();
+		// only the producer changes the head
+		let head = header.head.0.load(Ordering::Relaxed);
+		let tail = header.tail.0.load(Ordering::Acquire);
+		if self.capacity - head.wrapping_sub(tail) < len + LEN_SIZE {
+			return Err(io::Error::EAGAIN);
+		}
+
+		self.copy_to(head, &len.to_le_bytes());
+		self.copy_to(head.wrapping_add(LEN_SIZE), msg);
+		header
+			.head
+			.0
+			.store(head.wrapping_add(LEN_SIZE + len), Ordering::Release);
+
+		Ok(())
+	}
+
+	/// Removes the next message from the ring and copies it into `buf`.
+	///
+	/// If `buf` is too small, the rest of the message is discarded. Returns
+	/// the length of the copied data or `EAGAIN`, if the ring is empty.
+	pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let header = self.header();
+		// only the consumer changes the tail
+		let tail = header.tail.0.load(Ordering::Relaxed);
+		let head = header.head.0.load(Ordering::Acquire);
+		let used = head.wrapping_sub(tail);
+		if used == 0 {
+			return Err(io::Error::EAGAIN);
+		}
+
+		let mut len = [0; LEN_SIZE as usize];
+		self.copy_from(tail, &mut len);
+		let len = u32::from_le_bytes(len);
+		if used < LEN_SIZE || len > used - LEN_SIZE {
+			// The peer has corrupted the ring => discard its content.
+			warn!("Discard the corrupted messages of a shared memory ring");
+			header.tail.0.store(head, Ordering::Release);
+			return Err(io::Error::EIO);
+		}
+
+		let n = buf.len().min(len as usize);
+		self.copy_from(tail.wrapping_add(LEN_SIZE), &mut buf[..n]);
+		header
+			.tail
+			.0
+			.store(tail.wrapping_add(LEN_SIZE + len), Ordering::Release);
+
+		Ok(n)
+	}
+
+	/// Copies `src` into the data area at the position `pos`.
+	fn copy_to(&self, pos: u32, src: &[u8]) {
+		let offset = (pos & (self.capacity - 1)) as usize;
+		let first = src.len().min(self.capacity as usize - offset);
+		unsafe {
+			let data = self.data.as_mut_ptr::<u8>();
+			ptr::copy_nonoverlapping(src.as_ptr(), data.add(offset), first);
+			ptr::copy_nonoverlapping(src[first..].as_ptr(), data, src.len() - first);
+		}
+	}
+
+	/// Copies the data area at the position `pos` into `dst`.
+	fn copy_from(&self, pos: u32, dst: &mut [u8]) {
+		let offset = (pos & (self.capacity - 1)) as usize;
+		let first = dst.len().min(self.capacity as usize - offset);
+		unsafe {
+			let data = self.data.as_ptr::<u8>();
+			ptr::copy_nonoverlapping(data.add(offset), dst.as_mut_ptr(), first);
+			ptr::copy_nonoverlapping(data, dst[first..].as_mut_ptr(), dst.len() - first);
+		}
+	}
+}
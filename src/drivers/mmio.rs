@@ -4,9 +4,13 @@ use alloc::collections::VecDeque;
 use ahash::RandomState;
 use hashbrown::HashMap;
 
+#[cfg(feature = "blk")]
+pub(crate) use crate::arch::kernel::mmio::get_block_drivers;
+#[cfg(feature = "console")]
+pub(crate) use crate::arch::kernel::mmio::get_console_driver;
 #[cfg(any(feature = "tcp", feature = "udp"))]
 pub(crate) use crate::arch::kernel::mmio::get_network_driver;
-#[cfg(any(feature = "tcp", feature = "udp"))]
+#[cfg(any(feature = "tcp", feature = "udp", feature = "blk", feature = "console"))]
 use crate::drivers::Driver;
 #[cfg(any(feature = "tcp", feature = "udp"))]
 use crate::drivers::net::NetworkDriver;
@@ -21,6 +25,7 @@ pub(crate) fn get_interrupt_handlers() -> HashMap<InterruptLine, InterruptHandle
 	#[cfg(any(feature = "tcp", feature = "udp"))]
 	if let Some(drv) = get_network_driver() {
 		fn network_handler() {
+			crate::executor::timestamp::interrupt();
 			if let Some(driver) = get_network_driver() {
 				driver.lock().handle_interrupt();
 			}
@@ -37,5 +42,37 @@ pub(crate) fn get_interrupt_handlers() -> HashMap<InterruptLine, InterruptHandle
 		}
 	}
 
+	#[cfg(feature = "blk")]
+	for drv in get_block_drivers() {
+		fn block_handler() {
+			for driver in get_block_drivers() {
+				driver.lock().handle_interrupt();
+			}
+		}
+
+		let irq_number = drv.lock().get_interrupt_number();
+
+		handlers
+			.entry(irq_number)
+			.or_default()
+			.push_back(block_handler);
+	}
+
+	#[cfg(feature = "console")]
+	if let Some(drv) = get_console_driver() {
+		fn console_handler() {
+			if let Some(driver) = get_console_driver() {
+				driver.lock().handle_interrupt();
+			}
+		}
+
+		let irq_number = drv.lock().get_interrupt_number();
+
+		handlers
+			.entry(irq_number)
+			.or_default()
+			.push_back(console_handler);
+	}
+
 	handlers
 }
@@ -1,16 +1,38 @@
 //! A module containing hermit-rs driver, hermit-rs driver trait and driver specific errors.
 
+#[cfg(feature = "blk")]
+pub mod block;
+#[cfg(feature = "console")]
+pub mod console;
 #[cfg(feature = "fuse")]
 pub mod fs;
+#[cfg(feature = "ivshmem")]
+pub mod ivshmem;
 #[cfg(not(feature = "pci"))]
 pub mod mmio;
 #[cfg(any(feature = "tcp", feature = "udp"))]
 pub mod net;
 #[cfg(feature = "pci")]
 pub mod pci;
+#[cfg(feature = "pmem")]
+pub mod pmem;
 #[cfg(any(
 	all(any(feature = "tcp", feature = "udp"), not(feature = "rtl8139")),
+	feature = "blk",
+	feature = "console",
 	feature = "fuse",
+	feature = "pmem",
+	feature = "vsock"
+))]
+pub(crate) mod register;
+#[cfg(any(feature = "pci", all(target_arch = "riscv64", feature = "tcp")))]
+pub(crate) mod registry;
+#[cfg(any(
+	all(any(feature = "tcp", feature = "udp"), not(feature = "rtl8139")),
+	feature = "blk",
+	feature = "console",
+	feature = "fuse",
+	feature = "pmem",
 	feature = "vsock"
 ))]
 pub mod virtio;
@@ -32,13 +54,18 @@ pub(crate) type InterruptHandlerQueue = VecDeque<fn()>;
 pub mod error {
 	use core::fmt;
 
+	#[cfg(feature = "ivshmem")]
+	use crate::drivers::ivshmem::error::IvshmemError;
 	#[cfg(all(target_arch = "riscv64", feature = "gem-net"))]
 	use crate::drivers::net::gem::GEMError;
 	#[cfg(all(target_arch = "x86_64", feature = "rtl8139"))]
 	use crate::drivers::net::rtl8139::RTL8139Error;
 	#[cfg(any(
 		all(any(feature = "tcp", feature = "udp"), not(feature = "rtl8139")),
+		feature = "blk",
+		feature = "console",
 		feature = "fuse",
+		feature = "pmem",
 		feature = "vsock"
 	))]
 	use crate::drivers::virtio::error::VirtioError;
@@ -47,7 +74,10 @@ pub mod error {
 	pub enum DriverError {
 		#[cfg(any(
 			all(any(feature = "tcp", feature = "udp"), not(feature = "rtl8139")),
+			feature = "blk",
+			feature = "console",
 			feature = "fuse",
+			feature = "pmem",
 			feature = "vsock"
 		))]
 		InitVirtioDevFail(VirtioError),
@@ -55,11 +85,16 @@ pub mod error {
 		InitRTL8139DevFail(RTL8139Error),
 		#[cfg(all(target_arch = "riscv64", feature = "gem-net"))]
 		InitGEMDevFail(GEMError),
+		#[cfg(feature = "ivshmem")]
+		InitIvshmemDevFail(IvshmemError),
 	}
 
 	#[cfg(any(
 		all(any(feature = "tcp", feature = "udp"), not(feature = "rtl8139")),
+		feature = "blk",
+		feature = "console",
 		feature = "fuse",
+		feature = "pmem",
 		feature = "vsock"
 	))]
 	impl From<VirtioError> for DriverError {
@@ -82,13 +117,23 @@ pub mod error {
 		}
 	}
 
+	#[cfg(feature = "ivshmem")]
+	impl From<IvshmemError> for DriverError {
+		fn from(err: IvshmemError) -> Self {
+			DriverError::InitIvshmemDevFail(err)
+		}
+	}
+
 	impl fmt::Display for DriverError {
 		#[allow(unused_variables)]
 		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 			match *self {
 				#[cfg(any(
 					all(any(feature = "tcp", feature = "udp"), not(feature = "rtl8139")),
+					feature = "blk",
+					feature = "console",
 					feature = "fuse",
+					feature = "pmem",
 					feature = "vsock"
 				))]
 				DriverError::InitVirtioDevFail(ref err) => {
@@ -102,6 +147,10 @@ pub mod error {
 				DriverError::InitGEMDevFail(ref err) => {
 					write!(f, "GEM driver failed: {err:?}")
 				}
+				#[cfg(feature = "ivshmem")]
+				DriverError::InitIvshmemDevFail(ref err) => {
+					write!(f, "Ivshmem driver failed: {err:?}")
+				}
 			}
 		}
 	}
@@ -115,6 +164,11 @@ pub(crate) trait Driver {
 
 	/// Returns the device driver name
 	fn get_name(&self) -> &'static str;
+
+	/// Returns the number of queues, which the driver uses to communicate with the device
+	fn get_queue_count(&self) -> usize {
+		0
+	}
 }
 
 pub(crate) fn init() {
@@ -124,9 +178,22 @@ pub(crate) fn init() {
 	#[cfg(all(
 		not(feature = "pci"),
 		target_arch = "x86_64",
-		any(feature = "tcp", feature = "udp")
+		any(feature = "tcp", feature = "udp", feature = "blk", feature = "console")
 	))]
 	crate::arch::x86_64::kernel::mmio::init_drivers();
 
 	crate::arch::interrupts::install_handlers();
+
+	#[cfg(feature = "blk")]
+	crate::drivers::block::print_information();
+}
+
+/// Writes the cached data of the devices back on shutdown.
+pub(crate) fn exit() {
+	#[cfg(feature = "blk")]
+	for device in crate::drivers::block::devices() {
+		if let Err(err) = device.flush() {
+			warn!("Unable to flush a block device: {err:?}");
+		}
+	}
 }
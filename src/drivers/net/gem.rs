@@ -20,11 +20,15 @@ use crate::arch::kernel::core_local::core_scheduler;
 use crate::arch::kernel::interrupts::*;
 #[cfg(all(any(feature = "tcp", feature = "udp"), not(feature = "pci")))]
 use crate::arch::kernel::mmio as hardware;
+#[cfg(all(feature = "tcp", not(feature = "pci")))]
+use crate::arch::kernel::mmio::MmioDriver;
 use crate::arch::mm::paging::virt_to_phys;
 use crate::drivers::error::DriverError;
 use crate::drivers::net::NetworkDriver;
 #[cfg(all(any(feature = "tcp", feature = "udp"), feature = "pci"))]
 use crate::drivers::pci as hardware;
+#[cfg(all(feature = "tcp", not(feature = "pci")))]
+use crate::drivers::registry::{DeviceMatch, DriverContext, FdtDriverEntry};
 use crate::drivers::{Driver, InterruptLine};
 use crate::executor::device::{RxToken, TxToken};
 
@@ -466,6 +470,67 @@ impl Drop for GEMDriver {
 	}
 }
 
+/// Driver entry for the GEM, which is found in the device tree
+#[cfg(all(feature = "tcp", not(feature = "pci")))]
+pub(crate) struct GemEntry;
+
+#[cfg(all(feature = "tcp", not(feature = "pci")))]
+impl FdtDriverEntry for GemEntry {
+	fn name(&self) -> &'static str {
+		"gem"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Compatible("sifive,fu540-c000-gem")];
+		MATCHES
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<MmioDriver, DriverError> {
+		let gem_node = ctx.node();
+		let mac = gem_node
+			.property("local-mac-address")
+			.expect("local-mac-address property for GEM not found in FDT")
+			.value;
+		debug!("Local MAC address: {:x?}", mac);
+		let mut phy_addr = u32::MAX;
+
+		let phy_node = gem_node
+			.children()
+			.next()
+			.expect("GEM node has no child node (i. e. ethernet-phy)");
+		if phy_node.name.contains("ethernet-phy") {
+			phy_addr = phy_node
+				.property("reg")
+				.expect("reg property for ethernet-phy not found in FDT")
+				.as_usize()
+				.unwrap() as u32;
+		} else {
+			warn!("Expected ethernet-phy node, found something else");
+		}
+
+		let gem_region_start = ctx
+			.request_region(0)
+			.expect("reg property for GEM not found in FDT");
+		let irq = ctx
+			.request_irq("gem")
+			.expect("interrupts property for GEM not found in FDT");
+		debug!(
+			"Init GEM at {:p}, irq: {}, phy_addr: {}",
+			gem_region_start, irq, phy_addr
+		);
+		let drv = init_device(
+			VirtAddr::new(gem_region_start.as_u64()),
+			irq,
+			phy_addr,
+			<[u8; 6]>::try_from(mac).expect("MAC with invalid length"),
+		)?;
+
+		Ok(MmioDriver::GEMNet(hermit_sync::InterruptSpinMutex::new(
+			drv,
+		)))
+	}
+}
+
 /// Inits the driver. Passing u32::MAX as phy_addr will trigger a search for the actual PHY address
 pub fn init_device(
 	gem_base: VirtAddr,
@@ -11,6 +11,7 @@ use smoltcp::phy::ChecksumCapabilities;
 use crate::arch::kernel::core_local::*;
 use crate::drivers::Driver;
 use crate::executor::device::{RxToken, TxToken};
+use crate::io;
 
 /// A trait for accessing the network interface
 pub(crate) trait NetworkDriver: Driver {
@@ -35,4 +36,25 @@ pub(crate) trait NetworkDriver: Driver {
 	fn set_polling_mode(&mut self, value: bool);
 	/// Handle interrupt and check if a packet is available
 	fn handle_interrupt(&mut self);
+	/// Enables or disables the reception of all packets, regardless of
+	/// their destination address.
+	fn set_promiscuous(&mut self, _on: bool) -> io::Result<()> {
+		Err(io::Error::EOPNOTSUPP)
+	}
+	/// Replaces the additionally received unicast and multicast addresses.
+	fn set_mac_filter(&mut self, _unicast: &[[u8; 6]], _multicast: &[[u8; 6]]) -> io::Result<()> {
+		Err(io::Error::EOPNOTSUPP)
+	}
+	/// Adds the VLAN `vid` to the VLAN filter of the device.
+	fn add_vlan(&mut self, _vid: u16) -> io::Result<()> {
+		Err(io::Error::EOPNOTSUPP)
+	}
+	/// Removes the VLAN `vid` from the VLAN filter of the device.
+	fn remove_vlan(&mut self, _vid: u16) -> io::Result<()> {
+		Err(io::Error::EOPNOTSUPP)
+	}
+	/// Sets the number of queue pairs, which are used to receive and send packets.
+	fn set_queue_pairs(&mut self, _pairs: u16) -> io::Result<()> {
+		Err(io::Error::EOPNOTSUPP)
+	}
 }
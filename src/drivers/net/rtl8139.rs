@@ -5,17 +5,17 @@
 use alloc::boxed::Box;
 use core::mem;
 
+use hermit_sync::InterruptTicketMutex;
 use memory_addresses::VirtAddr;
 use pci_types::{Bar, CommandRegister, InterruptLine, MAX_BARS};
 use x86_64::instructions::port::Port;
 
-use crate::arch::kernel::interrupts::*;
 use crate::arch::mm::paging::virt_to_phys;
-use crate::arch::pci::PciConfigRegion;
 use crate::drivers::Driver;
 use crate::drivers::error::DriverError;
 use crate::drivers::net::NetworkDriver;
-use crate::drivers::pci::PciDevice;
+use crate::drivers::pci::PciDriver;
+use crate::drivers::registry::{DeviceMatch, DriverContext, PciDriverEntry};
 use crate::executor::device::{RxToken, TxToken};
 
 /// size of the receive buffer
@@ -418,14 +418,37 @@ impl Drop for RTL8139Driver {
 	}
 }
 
-pub(crate) fn init_device(
-	device: &PciDevice<PciConfigRegion>,
-) -> Result<RTL8139Driver, DriverError> {
-	let irq = device.get_irq().unwrap();
+/// Driver entry of Realtek RTL8139 network devices
+pub(crate) struct RTL8139Entry;
+
+impl PciDriverEntry for RTL8139Entry {
+	fn name(&self) -> &'static str {
+		"rtl8139"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Pci {
+			vendor_id: 0x10ec,
+			device_ids: 0x8138..=0x8139,
+		}];
+		MATCHES
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<PciDriver, DriverError> {
+		let drv = init_device(ctx)?;
+		Ok(PciDriver::RTL8139Net(InterruptTicketMutex::new(drv)))
+	}
+}
+
+fn init_device(ctx: &mut DriverContext<'_>) -> Result<RTL8139Driver, DriverError> {
+	let device = ctx.device();
+	let irq = ctx
+		.request_irq("rtl8139")
+		.ok_or(DriverError::InitRTL8139DevFail(RTL8139Error::Unknown))?;
 	let mut iobase: Option<u32> = None;
 
 	for i in 0..MAX_BARS {
-		if let Some(Bar::Io { port }) = device.get_bar(i.try_into().unwrap()) {
+		if let Some(Bar::Io { port }) = ctx.request_bar(i.try_into().unwrap()) {
 			iobase = Some(port);
 		}
 	}
@@ -563,7 +586,6 @@ pub(crate) fn init_device(
 	}
 
 	info!("RTL8139 use interrupt line {}", irq);
-	add_irq_name(irq, "rtl8139");
 
 	Ok(RTL8139Driver {
 		iobase,
@@ -59,9 +59,11 @@ impl VirtioNetDriver {
 			recv_vqs,
 			send_vqs,
 			num_vqs: 0,
+			active_pairs: 1,
 			mtu,
 			irq,
 			checksums: ChecksumCapabilities::default(),
+			needs_reset: false,
 		})
 	}
 
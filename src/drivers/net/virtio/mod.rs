@@ -5,30 +5,38 @@
 cfg_if::cfg_if! {
 	if #[cfg(feature = "pci")] {
 		mod pci;
+
+		pub(crate) use self::pci::VirtioNetEntry;
 	} else {
 		mod mmio;
 	}
 }
 
 use alloc::boxed::Box;
+use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 use core::mem::MaybeUninit;
 
 use smoltcp::phy::{Checksum, ChecksumCapabilities};
-use smoltcp::wire::{ETHERNET_HEADER_LEN, EthernetFrame, Ipv4Packet, Ipv6Packet};
-use virtio::net::{ConfigVolatileFieldAccess, Hdr, HdrF};
-use virtio::{DeviceConfigSpace, FeatureBits};
+use smoltcp::wire::{
+	ETHERNET_HEADER_LEN, EthernetFrame, EthernetProtocol, IpAddress, IpProtocol, Ipv4Packet,
+	Ipv6Packet, TcpPacket, UDP_HEADER_LEN, UdpPacket,
+};
+use virtio::net::{ConfigVolatileFieldAccess, HashReport, Hdr, HdrF, HdrGso, HdrHashReport};
+use virtio::{DeviceConfigSpace, FeatureBits, le128};
 use volatile::VolatileRef;
 use volatile::access::ReadOnly;
 
-use self::constants::MAX_NUM_VQ;
+use self::constants::MAX_QUEUE_PAIRS;
 use self::error::VirtioNetError;
+use crate::arch::core_local::core_id;
 use crate::config::VIRTIO_MAX_QUEUE_SIZE;
 use crate::drivers::net::NetworkDriver;
 #[cfg(not(feature = "pci"))]
 use crate::drivers::virtio::transport::mmio::{ComCfg, IsrStatus, NotifCfg};
 #[cfg(feature = "pci")]
 use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
+use crate::drivers::virtio::virtqueue::error::VirtqError;
 use crate::drivers::virtio::virtqueue::packed::PackedVq;
 use crate::drivers::virtio::virtqueue::split::SplitVq;
 use crate::drivers::virtio::virtqueue::{
@@ -36,6 +44,7 @@ use crate::drivers::virtio::virtqueue::{
 };
 use crate::drivers::{Driver, InterruptLine};
 use crate::executor::device::{RxToken, TxToken};
+use crate::io;
 use crate::mm::device_alloc::DeviceAlloc;
 
 /// A wrapper struct for the raw configuration structure.
@@ -47,17 +56,125 @@ pub(crate) struct NetDevCfg {
 	pub features: virtio::net::F,
 }
 
+/// Header of received packets, if VIRTIO_NET_F_HASH_REPORT has been negotiated
+#[derive(Default, Clone, Copy, Debug)]
+#[repr(C)]
+struct HashHdr {
+	hdr: Hdr,
+	hash: HdrHashReport,
+}
+
+/// Driver can receive coalesced UDP packets over IPv4 (`VIRTIO_NET_F_GUEST_USO4`)
+///
+/// The feature bit is not defined by `virtio-spec` (Virtio specification v1.3 - 5.1.3).
+const GUEST_USO4: virtio::net::F = virtio::net::F::from_bits_retain(le128::from_ne(1 << 54));
+
+/// Driver can receive coalesced UDP packets over IPv6 (`VIRTIO_NET_F_GUEST_USO6`)
+const GUEST_USO6: virtio::net::F = virtio::net::F::from_bits_retain(le128::from_ne(1 << 55));
+
+/// Size of a receive buffer for coalesced packets without merged receive buffers
+///
+/// See Virtio specification v1.2 - 5.1.6.3.1
+const MAX_COALESCED_PACKET: u32 = 0x0001_000e;
+
+/// MTU of the network stack, if the device segments large packets
+const GSO_MTU: u16 = u16::MAX;
+
+/// Status of a processed control command (`VIRTIO_NET_OK`)
+const VIRTIO_NET_OK: u8 = 0;
+
+/// Number of attempts to receive the status of a control command, before
+/// the control queue is given up
+const CTRL_RETRIES: usize = 0x0010_0000;
+
+/// Default key of the Toeplitz hash function, which is used by most drivers
+const RSS_KEY: [u8; 40] = [
+	0x6d, 0x5a, 0x56, 0xda, 0x25, 0x5b, 0x0e, 0xc2, 0x41, 0x67, 0x25, 0x3d, 0x43, 0xa3, 0x8f, 0xb0,
+	0xd0, 0xca, 0x2b, 0xcb, 0xae, 0x7b, 0x30, 0xb4, 0x77, 0xcb, 0x2d, 0xa3, 0x80, 0x30, 0xf2, 0x0c,
+	0x6a, 0x42, 0xb7, 0x3b, 0xbe, 0xac, 0x01, 0xfa,
+];
+
+/// Returns the queue pair, which is assigned to the current core.
+///
+/// With `pairs` queue pairs, the pair `i` serves the cores `i`, `i + pairs`,
+/// `i + 2 * pairs`, and so on. As the device steers received packets of a flow
+/// to the receive queue of the pair, which has sent the packets of the flow,
+/// the buffers of a flow are usually used by a single core.
+///
+/// The queue pairs only spread the buffers and the work of the device. All
+/// queues share the interrupt line of the device, and the driver and the
+/// network interface are still accessed under a single lock, so that the
+/// processing of the packets is not parallelized.
+fn queue_pair_of_current_core(pairs: usize) -> usize {
+	usize::try_from(core_id()).unwrap() % pairs.max(1)
+}
+
 pub struct CtrlQueue(Option<Box<dyn Virtq>>);
 
 impl CtrlQueue {
 	pub fn new(vq: Option<Box<dyn Virtq>>) -> Self {
 		CtrlQueue(vq)
 	}
+
+	/// Sends the command `command` of the class `class` with the payload `data`
+	/// to the device and waits until the device has processed it.
+	///
+	/// If the device does not process the command within [`CTRL_RETRIES`]
+	/// attempts, the control queue is given up, because the device may still
+	/// write to the buffers of the command.
+	///
+	/// See Virtio specification v1.2 - 5.1.6.5
+	fn send_command(
+		&mut self,
+		class: virtio::net::Ctrl,
+		command: u8,
+		data: &[u8],
+	) -> Result<(), VirtioNetError> {
+		let vq = self.0.as_mut().ok_or(VirtioNetError::NoCtrlQueue)?;
+
+		let mut payload = Vec::with_capacity_in(data.len(), DeviceAlloc);
+		payload.extend_from_slice(data);
+		let buff_tkn = AvailBufferToken::new(
+			vec![
+				BufferElem::Sized(Box::new_in([u8::from(class), command], DeviceAlloc)),
+				BufferElem::Vector(payload),
+			],
+			vec![BufferElem::Sized(Box::<u8, _>::new_uninit_in(DeviceAlloc))],
+		)
+		.map_err(|_| VirtioNetError::CtrlCommandFailed)?;
+		vq.dispatch(buff_tkn, false, BufferType::Direct)
+			.map_err(|_| VirtioNetError::CtrlCommandFailed)?;
+
+		let mut retries = CTRL_RETRIES;
+		let mut used_tkn = loop {
+			match vq.try_recv() {
+				Ok(used_tkn) => break used_tkn,
+				Err(VirtqError::NoNewUsed) if retries > 0 => {
+					retries -= 1;
+					core::hint::spin_loop();
+				}
+				Err(VirtqError::NoNewUsed) => {
+					warn!("The network device does not process control commands");
+					core::mem::forget(self.0.take());
+					return Err(VirtioNetError::CtrlCommandFailed);
+				}
+				Err(_) => return Err(VirtioNetError::CtrlCommandFailed),
+			}
+		};
+		match used_tkn.used_recv_buff.pop_front_downcast::<u8>() {
+			Some(ack) if *ack == VIRTIO_NET_OK => Ok(()),
+			_ => Err(VirtioNetError::CtrlCommandFailed),
+		}
+	}
 }
 
 pub struct RxQueues {
 	vqs: Vec<Box<dyn Virtq>>,
 	packet_size: u32,
+	/// The header of received packets contains the hash report.
+	hash_report: bool,
+	/// Datagrams of a coalesced UDP packet, which have not been passed to the network stack yet
+	segments: VecDeque<(Vec<u8>, Option<u32>)>,
 }
 
 impl RxQueues {
@@ -66,11 +183,25 @@ impl RxQueues {
 		//
 		let packet_size = if dev_cfg.features.contains(virtio::net::F::MRG_RXBUF) {
 			1514
+		} else if dev_cfg.features.intersects(
+			virtio::net::F::GUEST_TSO4
+				| virtio::net::F::GUEST_TSO6
+				| virtio::net::F::GUEST_UFO
+				| GUEST_USO4 | GUEST_USO6,
+		) {
+			MAX_COALESCED_PACKET
 		} else {
 			dev_cfg.raw.as_ptr().mtu().read().to_ne().into()
 		};
 
-		Self { vqs, packet_size }
+		let hash_report = dev_cfg.features.contains(virtio::net::F::HASH_REPORT);
+
+		Self {
+			vqs,
+			packet_size,
+			hash_report,
+			segments: VecDeque::new(),
+		}
 	}
 
 	/// Takes care of handling packets correctly which need some processing after being received.
@@ -87,12 +218,49 @@ impl RxQueues {
 	fn add(&mut self, mut vq: Box<dyn Virtq>) {
 		const BUFF_PER_PACKET: u16 = 2;
 		let num_packets: u16 = u16::from(vq.size()) / BUFF_PER_PACKET;
-		fill_queue(vq.as_mut(), num_packets, self.packet_size);
+		fill_queue(vq.as_mut(), num_packets, self.packet_size, self.hash_report);
 		self.vqs.push(vq);
 	}
 
-	fn get_next(&mut self) -> Option<UsedBufferToken> {
-		self.vqs[0].try_recv().ok()
+	/// Returns the index of a queue, which contains received buffers.
+	///
+	/// The queue of the current core is preferred, but the other queues are
+	/// processed as well, so that no packet is delayed until its core polls.
+	fn next_queue(&self) -> Option<usize> {
+		let first = queue_pair_of_current_core(self.vqs.len());
+		(0..self.vqs.len())
+			.map(|i| (first + i) % self.vqs.len())
+			.find(|&i| self.vqs[i].has_used_buffers())
+	}
+
+	/// Takes the next received buffer of the queue `index`.
+	///
+	/// If the device has returned a malformed buffer, the device is marked as FAILED.
+	fn get_next(&mut self, index: usize, com_cfg: &mut ComCfg) -> Option<UsedBufferToken> {
+		match self.vqs[index].try_recv() {
+			Ok(buffer_tkn) => Some(buffer_tkn),
+			Err(VirtqError::MalformedUsed) => {
+				com_cfg.set_failed();
+				None
+			}
+			Err(_) => None,
+		}
+	}
+
+	/// Takes the header of a received packet and returns it together with the
+	/// hash of the flow, if the device has reported one.
+	fn pop_header(&self, buffer_tkn: &mut UsedBufferToken) -> Option<(Hdr, Option<u32>)> {
+		if self.hash_report {
+			let header = buffer_tkn.used_recv_buff.pop_front_downcast::<HashHdr>()?;
+			let hash = match HashReport::from(header.hash.hash_report.to_ne()) {
+				HashReport::None => None,
+				_ => Some(header.hash.hash_value.to_ne()),
+			};
+			Some((header.hdr, hash))
+		} else {
+			let header = buffer_tkn.used_recv_buff.pop_front_downcast::<Hdr>()?;
+			Some((*header, None))
+		}
 	}
 
 	fn enable_notifs(&mut self) {
@@ -108,14 +276,157 @@ impl RxQueues {
 	}
 
 	fn has_packet(&self) -> bool {
-		self.vqs.iter().any(|vq| vq.has_used_buffers())
+		!self.segments.is_empty() || self.vqs.iter().any(|vq| vq.has_used_buffers())
+	}
+}
+
+/// Splits the coalesced UDP packet `frame` into Ethernet frames, whose
+/// datagrams carry at most `gso_size` bytes of payload.
+///
+/// Returns `None`, if `frame` is no UDP packet over IPv4 or over IPv6 without
+/// extension headers.
+fn split_udp(frame: &[u8], gso_size: usize) -> Option<Vec<Vec<u8>>> {
+	let ethernet = EthernetFrame::new_checked(frame).ok()?;
+	let ip_header_len = match ethernet.ethertype() {
+		EthernetProtocol::Ipv4 => {
+			let packet = Ipv4Packet::new_checked(ethernet.payload()).ok()?;
+			(packet.next_header() == IpProtocol::Udp).then(|| packet.header_len().into())?
+		}
+		EthernetProtocol::Ipv6 => {
+			let packet = Ipv6Packet::new_checked(ethernet.payload()).ok()?;
+			(packet.next_header() == IpProtocol::Udp).then(|| packet.header_len())?
+		}
+		_ => return None,
+	};
+	let headers_len = ETHERNET_HEADER_LEN + ip_header_len + UDP_HEADER_LEN;
+	let payload = frame.get(headers_len..)?;
+
+	let segments = payload
+		.chunks(gso_size)
+		.enumerate()
+		.map(|(i, chunk)| {
+			let mut segment = Vec::with_capacity(headers_len + chunk.len());
+			segment.extend_from_slice(&frame[..headers_len]);
+			segment.extend_from_slice(chunk);
+
+			let udp_len = u16::try_from(UDP_HEADER_LEN + chunk.len()).unwrap();
+			let mut ethernet = EthernetFrame::new_unchecked(&mut segment[..]);
+			let ethertype = ethernet.ethertype();
+			let (ip_header, datagram) = ethernet.payload_mut().split_at_mut(ip_header_len);
+			let (src_addr, dst_addr) = if ethertype == EthernetProtocol::Ipv4 {
+				let mut packet = Ipv4Packet::new_unchecked(ip_header);
+				packet.set_total_len(u16::try_from(ip_header_len).unwrap() + udp_len);
+				packet.set_ident(packet.ident().wrapping_add(i as u16));
+				packet.fill_checksum();
+				(
+					IpAddress::Ipv4(packet.src_addr()),
+					IpAddress::Ipv4(packet.dst_addr()),
+				)
+			} else {
+				let mut packet = Ipv6Packet::new_unchecked(ip_header);
+				packet.set_payload_len(udp_len);
+				(
+					IpAddress::Ipv6(packet.src_addr()),
+					IpAddress::Ipv6(packet.dst_addr()),
+				)
+			};
+
+			let mut datagram = UdpPacket::new_unchecked(datagram);
+			datagram.set_len(udp_len);
+			datagram.fill_checksum(&src_addr, &dst_addr);
+			segment
+		})
+		.collect();
+
+	Some(segments)
+}
+
+/// Returns the GSO type, the length of all headers and the size of the
+/// segments, if the device has to segment the Ethernet frame `frame`, because
+/// it exceeds the MTU `mtu`.
+///
+/// The device splits TCP packets into segments, whose payload does not exceed
+/// the segment size, and UDP datagrams into IP fragments.
+///
+/// See Virtio specification v1.2 - 5.1.6.2
+fn gso(frame: &[u8], mtu: u16, features: virtio::net::F) -> Option<(HdrGso, u16, u16)> {
+	if frame.len() <= ETHERNET_HEADER_LEN + usize::from(mtu) {
+		return None;
 	}
+
+	let ethernet = EthernetFrame::new_checked(frame).ok()?;
+	let ethertype = ethernet.ethertype();
+	let (ip_header_len, protocol) = match ethertype {
+		EthernetProtocol::Ipv4 => {
+			let packet = Ipv4Packet::new_checked(ethernet.payload()).ok()?;
+			(usize::from(packet.header_len()), packet.next_header())
+		}
+		EthernetProtocol::Ipv6 => {
+			let packet = Ipv6Packet::new_checked(ethernet.payload()).ok()?;
+			(packet.header_len(), packet.next_header())
+		}
+		_ => return None,
+	};
+	let is_ipv6 = ethertype == EthernetProtocol::Ipv6;
+	let mtu = usize::from(mtu);
+
+	let (gso_type, l4_header_len, gso_size) = match protocol {
+		IpProtocol::Tcp => {
+			let (gso_type, feature) = if is_ipv6 {
+				(HdrGso::TCPV6, virtio::net::F::HOST_TSO6)
+			} else {
+				(HdrGso::TCPV4, virtio::net::F::HOST_TSO4)
+			};
+			if !features.contains(feature) {
+				return None;
+			}
+
+			let segment = TcpPacket::new_checked(ethernet.payload().get(ip_header_len..)?).ok()?;
+			// Segments, which reduce the congestion window, can only be split with ECN support.
+			let gso_type = if !segment.cwr() {
+				gso_type
+			} else if features.contains(virtio::net::F::HOST_ECN) {
+				gso_type | HdrGso::ECN
+			} else {
+				return None;
+			};
+			let l4_header_len = usize::from(segment.header_len());
+			(
+				gso_type,
+				l4_header_len,
+				mtu.checked_sub(ip_header_len + l4_header_len)?,
+			)
+		}
+		IpProtocol::Udp => {
+			if !features.contains(virtio::net::F::HOST_UFO) {
+				return None;
+			}
+
+			// IPv6 fragments carry an additional fragment header and the payload
+			// of all fragments except the last one is a multiple of 8 bytes.
+			let fragment_header_len = if is_ipv6 { 8 } else { 0 };
+			let fragment_size = mtu.checked_sub(ip_header_len + fragment_header_len)? & !7;
+			(HdrGso::UDP, UDP_HEADER_LEN, fragment_size)
+		}
+		_ => return None,
+	};
+
+	Some((
+		gso_type,
+		u16::try_from(ETHERNET_HEADER_LEN + ip_header_len + l4_header_len).ok()?,
+		u16::try_from(gso_size).ok()?,
+	))
 }
 
-fn fill_queue(vq: &mut dyn Virtq, num_packets: u16, packet_size: u32) {
+fn fill_queue(vq: &mut dyn Virtq, num_packets: u16, packet_size: u32, hash_report: bool) {
 	for _ in 0..num_packets {
+		let header = if hash_report {
+			BufferElem::Sized(Box::<HashHdr, _>::new_uninit_in(DeviceAlloc))
+		} else {
+			BufferElem::Sized(Box::<Hdr, _>::new_uninit_in(DeviceAlloc))
+		};
 		let buff_tkn = match AvailBufferToken::new(vec![], vec![
-			BufferElem::Sized(Box::<Hdr, _>::new_uninit_in(DeviceAlloc)),
+			header,
 			BufferElem::Vector(Vec::with_capacity_in(
 				packet_size.try_into().unwrap(),
 				DeviceAlloc,
@@ -145,21 +456,31 @@ pub struct TxQueues {
 	/// Indicates, whether the Driver/Device are using multiple
 	/// queues for communication.
 	packet_length: u32,
+	/// Header and packet buffers of finished transmissions, which are reused
+	/// for the next packets.
+	///
+	/// As buffers only return after their transmission, the pool grows at most
+	/// to the number of packets, which are in flight at the same time.
+	pool: Vec<(Box<Hdr, DeviceAlloc>, Vec<u8, DeviceAlloc>)>,
 }
 
 impl TxQueues {
 	pub fn new(vqs: Vec<Box<dyn Virtq>>, dev_cfg: &NetDevCfg) -> Self {
-		let packet_length = if dev_cfg.features.contains(virtio::net::F::GUEST_TSO4)
-			| dev_cfg.features.contains(virtio::net::F::GUEST_TSO6)
-			| dev_cfg.features.contains(virtio::net::F::GUEST_UFO)
-		{
-			0x0001_000e
+		let packet_length = if dev_cfg.features.intersects(
+			virtio::net::F::HOST_TSO4 | virtio::net::F::HOST_TSO6 | virtio::net::F::HOST_UFO,
+		) {
+			MAX_COALESCED_PACKET
 		} else {
 			dev_cfg.raw.as_ptr().mtu().read().to_ne().into()
 		};
 
-		Self { vqs, packet_length }
+		Self {
+			vqs,
+			packet_length,
+			pool: Vec::new(),
+		}
 	}
+
 	#[allow(dead_code)]
 	fn enable_notifs(&mut self) {
 		for vq in &mut self.vqs {
@@ -176,15 +497,37 @@ impl TxQueues {
 
 	fn poll(&mut self) {
 		for vq in &mut self.vqs {
-			// We don't do anything with the buffers but we need to receive them for the
-			// ring slots to be emptied and the memory from the previous transfers to be freed.
-			while vq.try_recv().is_ok() {}
+			// We need to receive the buffers for the ring slots to be emptied.
+			// The buffers of the previous transfers are kept for reuse.
+			while let Ok(buffer_tkn) = vq.try_recv() {
+				let mut elems = buffer_tkn.send_buff.into_iter();
+				if let (Some(BufferElem::Sized(header)), Some(BufferElem::Vector(packet))) =
+					(elems.next(), elems.next())
+				{
+					if let Ok(header) = header.downcast::<Hdr>() {
+						self.pool.push((header, packet));
+					}
+				}
+			}
+		}
+	}
+
+	/// Returns a header and an empty packet buffer with a capacity of at least `len` bytes.
+	fn get_buffer(&mut self, len: usize) -> (Box<Hdr, DeviceAlloc>, Vec<u8, DeviceAlloc>) {
+		if let Some((mut header, mut packet)) = self.pool.pop() {
+			*header = Hdr::default();
+			packet.clear();
+			packet.reserve(len);
+			(header, packet)
+		} else {
+			(
+				Box::new_in(Hdr::default(), DeviceAlloc),
+				Vec::with_capacity_in(len, DeviceAlloc),
+			)
 		}
 	}
 
 	fn add(&mut self, vq: Box<dyn Virtq>) {
-		// Currently we are doing nothing with the additional queues. They are inactive and might be used in the
-		// future
 		self.vqs.push(vq);
 	}
 }
@@ -204,9 +547,13 @@ pub(crate) struct VirtioNetDriver {
 	pub(super) send_vqs: TxQueues,
 
 	pub(super) num_vqs: u16,
+	/// Number of queue pairs, which are used by the device
+	pub(super) active_pairs: u16,
 	pub(super) mtu: u16,
 	pub(super) irq: InterruptLine,
 	pub(super) checksums: ChecksumCapabilities,
+	/// The device has signaled DEVICE_NEEDS_RESET.
+	pub(super) needs_reset: bool,
 }
 
 impl NetworkDriver for VirtioNetDriver {
@@ -222,9 +569,22 @@ impl NetworkDriver for VirtioNetDriver {
 		}
 	}
 
-	/// Returns the current MTU of the device.
+	/// Returns the MTU, which is advertised to the network stack.
+	///
+	/// If the device segments TCP packets and fragments UDP datagrams, the
+	/// network stack passes packets of up to [`GSO_MTU`] bytes, which are
+	/// split by the device at its MTU.
 	fn get_mtu(&self) -> u16 {
-		self.mtu
+		if self.dev_cfg.features.contains(
+			virtio::net::F::CSUM
+				| virtio::net::F::HOST_TSO4
+				| virtio::net::F::HOST_TSO6
+				| virtio::net::F::HOST_UFO,
+		) {
+			GSO_MTU
+		} else {
+			self.mtu
+		}
 	}
 
 	fn get_checksums(&self) -> ChecksumCapabilities {
@@ -247,16 +607,15 @@ impl NetworkDriver for VirtioNetDriver {
 		self.send_vqs.poll();
 
 		assert!(len < usize::try_from(self.send_vqs.packet_length).unwrap());
-		let mut packet = Vec::with_capacity_in(len, DeviceAlloc);
+		let (mut header, mut packet) = self.send_vqs.get_buffer(len);
 		let result = unsafe {
 			let result = f(MaybeUninit::slice_assume_init_mut(
-				packet.spare_capacity_mut(),
+				&mut packet.spare_capacity_mut()[..len],
 			));
 			packet.set_len(len);
 			result
 		};
 
-		let mut header = Box::new_in(<Hdr as Default>::default(), DeviceAlloc);
 		// If a checksum isn't necessary, we have inform the host within the header
 		// see Virtio specification 5.1.6.2
 		if !self.checksums.tcp.tx() || !self.checksums.udp.tx() {
@@ -289,6 +648,15 @@ impl NetworkDriver for VirtioNetDriver {
 				_ => 0,
 			}
 			.into();
+
+			// The device segments frames, which exceed the MTU.
+			if let Some((gso_type, headers_len, gso_size)) =
+				gso(&packet, self.mtu, self.dev_cfg.features)
+			{
+				header.gso_type = gso_type;
+				header.hdr_len = headers_len.into();
+				header.gso_size = gso_size.into();
+			}
 		}
 
 		let buff_tkn = AvailBufferToken::new(
@@ -297,7 +665,8 @@ impl NetworkDriver for VirtioNetDriver {
 		)
 		.unwrap();
 
-		self.send_vqs.vqs[0]
+		let index = queue_pair_of_current_core(self.active_pairs.into());
+		self.send_vqs.vqs[index]
 			.dispatch(buff_tkn, false, BufferType::Direct)
 			.unwrap();
 
@@ -305,11 +674,21 @@ impl NetworkDriver for VirtioNetDriver {
 	}
 
 	fn receive_packet(&mut self) -> Option<(RxToken, TxToken)> {
-		let mut buffer_tkn = self.recv_vqs.get_next()?;
+		if self.needs_reset {
+			self.recover();
+		}
+
+		if let Some((segment, hash)) = self.recv_vqs.segments.pop_front() {
+			return Some((RxToken::new(segment).with_flow_hash(hash), TxToken::new()));
+		}
+
+		// All buffers of a packet are returned by the same queue.
+		let index = self.recv_vqs.next_queue()?;
+		let mut buffer_tkn = self.recv_vqs.get_next(index, &mut self.com_cfg)?;
 		RxQueues::post_processing(&mut buffer_tkn)
 			.inspect_err(|vnet_err| warn!("Post processing failed. Err: {vnet_err:?}"))
 			.ok()?;
-		let first_header = buffer_tkn.used_recv_buff.pop_front_downcast::<Hdr>()?;
+		let (first_header, hash) = self.recv_vqs.pop_header(&mut buffer_tkn)?;
 		let first_packet = buffer_tkn.used_recv_buff.pop_front_vec()?;
 		trace!("Header: {first_header:?}");
 
@@ -325,24 +704,44 @@ impl NetworkDriver for VirtioNetDriver {
 		packets.push(first_packet);
 
 		for _ in 1..num_buffers {
-			let mut buffer_tkn = self.recv_vqs.get_next().unwrap();
+			let Some(mut buffer_tkn) = self.recv_vqs.get_next(index, &mut self.com_cfg) else {
+				error!(
+					"The network device announced {num_buffers} buffers for a packet, but returned less"
+				);
+				self.com_cfg.set_failed();
+				return None;
+			};
 			RxQueues::post_processing(&mut buffer_tkn)
 				.inspect_err(|vnet_err| warn!("Post processing failed. Err: {vnet_err:?}"))
 				.ok()?;
-			let _header = buffer_tkn.used_recv_buff.pop_front_downcast::<Hdr>()?;
+			let _header = self.recv_vqs.pop_header(&mut buffer_tkn)?;
 			let packet = buffer_tkn.used_recv_buff.pop_front_vec()?;
 			packets.push(packet);
 		}
 
 		fill_queue(
-			self.recv_vqs.vqs[0].as_mut(),
+			self.recv_vqs.vqs[index].as_mut(),
 			num_buffers,
 			self.recv_vqs.packet_size,
+			self.recv_vqs.hash_report,
 		);
 
-		let vec_data = packets.into_iter().flatten().collect();
+		let mut vec_data: Vec<u8> = packets.into_iter().flatten().collect();
+
+		// A coalesced UDP packet is passed to the network stack as individual datagrams.
+		let gso_size = first_header.gso_size.to_ne();
+		let segments = (first_header.gso_type.difference(HdrGso::ECN) == HdrGso::UDP_L4
+			&& gso_size > 0)
+			.then(|| split_udp(&vec_data, gso_size.into()))
+			.flatten();
+		if let Some(mut segments) = segments.map(Vec::into_iter) {
+			vec_data = segments.next()?;
+			self.recv_vqs
+				.segments
+				.extend(segments.map(|segment| (segment, hash)));
+		}
 
-		Some((RxToken::new(vec_data), TxToken::new()))
+		Some((RxToken::new(vec_data).with_flow_hash(hash), TxToken::new()))
 	}
 
 	fn set_polling_mode(&mut self, value: bool) {
@@ -357,19 +756,108 @@ impl NetworkDriver for VirtioNetDriver {
 		let status = self.isr_stat.is_queue_interrupt();
 
 		#[cfg(not(feature = "pci"))]
-		if status.contains(virtio::mmio::InterruptStatus::CONFIGURATION_CHANGE_NOTIFICATION) {
-			info!("Configuration changes are not possible! Aborting");
-			todo!("Implement possibility to change config on the fly...")
-		}
-
+		let is_cfg_change =
+			status.contains(virtio::mmio::InterruptStatus::CONFIGURATION_CHANGE_NOTIFICATION);
 		#[cfg(feature = "pci")]
-		if status.contains(virtio::pci::IsrStatus::DEVICE_CONFIGURATION_INTERRUPT) {
-			info!("Configuration changes are not possible! Aborting");
-			todo!("Implement possibility to change config on the fly...")
+		let is_cfg_change = status.contains(virtio::pci::IsrStatus::DEVICE_CONFIGURATION_INTERRUPT);
+
+		// The device is reset outside of the interrupt handler, when the next packet is received.
+		if is_cfg_change {
+			if self.com_cfg.needs_reset() {
+				warn!(
+					"Virtio network device {:x} needs a reset",
+					self.dev_cfg.dev_id
+				);
+				self.needs_reset = true;
+			} else {
+				debug!(
+					"Configuration of virtio network device {:x} has changed",
+					self.dev_cfg.dev_id
+				);
+			}
 		}
 
 		self.isr_stat.acknowledge();
 	}
+
+	/// Enables or disables the reception of all packets, regardless of
+	/// their destination address.
+	fn set_promiscuous(&mut self, on: bool) -> io::Result<()> {
+		self.set_rx_mode(virtio::net::ctrl::Rx::Promisc, on)?;
+		debug!("Promiscuous mode of the network device: {on}");
+		Ok(())
+	}
+
+	/// Replaces the MAC address filter of the device by the addresses
+	/// `unicast` and `multicast`, which are received in addition to the
+	/// address of the device and broadcasts.
+	///
+	/// See Virtio specification v1.2 - 5.1.6.5.2
+	fn set_mac_filter(&mut self, unicast: &[[u8; 6]], multicast: &[[u8; 6]]) -> io::Result<()> {
+		self.check_ctrl_feature(virtio::net::F::CTRL_RX)?;
+
+		// two struct virtio_net_ctrl_mac
+		let mut data = Vec::with_capacity(8 + 6 * (unicast.len() + multicast.len()));
+		for table in [unicast, multicast] {
+			let entries = u32::try_from(table.len()).map_err(|_| io::Error::EINVAL)?;
+			data.extend_from_slice(&entries.to_le_bytes());
+			data.extend(table.iter().flatten());
+		}
+
+		self.ctrl_vq.send_command(
+			virtio::net::Ctrl::Mac,
+			virtio::net::ctrl::Mac::TableSet.into(),
+			&data,
+		)?;
+		Ok(())
+	}
+
+	/// Adds `vid` to the VLAN filter, so that packets of this VLAN are received.
+	///
+	/// See Virtio specification v1.2 - 5.1.6.5.3
+	fn add_vlan(&mut self, vid: u16) -> io::Result<()> {
+		self.check_ctrl_feature(virtio::net::F::CTRL_VLAN)?;
+		self.ctrl_vq.send_command(
+			virtio::net::Ctrl::Vlan,
+			virtio::net::ctrl::Vlan::Add.into(),
+			&vid.to_le_bytes(),
+		)?;
+		Ok(())
+	}
+
+	/// Removes `vid` from the VLAN filter.
+	fn remove_vlan(&mut self, vid: u16) -> io::Result<()> {
+		self.check_ctrl_feature(virtio::net::F::CTRL_VLAN)?;
+		self.ctrl_vq.send_command(
+			virtio::net::Ctrl::Vlan,
+			virtio::net::ctrl::Vlan::Del.into(),
+			&vid.to_le_bytes(),
+		)?;
+		Ok(())
+	}
+
+	/// Sets the number of queue pairs, which are used by the device to
+	/// receive and send packets.
+	///
+	/// `pairs` must not exceed the number of queue pairs of the driver.
+	/// Afterwards, packets are only sent on the first `pairs` send queues.
+	///
+	/// See Virtio specification v1.2 - 5.1.6.5.5
+	fn set_queue_pairs(&mut self, pairs: u16) -> io::Result<()> {
+		self.check_ctrl_feature(virtio::net::F::MQ)?;
+		if pairs == 0 || pairs > self.num_vqs / 2 {
+			return Err(VirtioNetError::InvalidQueuePairs(pairs).into());
+		}
+
+		self.ctrl_vq.send_command(
+			virtio::net::Ctrl::Mq,
+			virtio::net::ctrl::Mq::VqPairsSet.into(),
+			&pairs.to_le_bytes(),
+		)?;
+		self.active_pairs = pairs;
+		info!("Network device uses {pairs} queue pairs");
+		Ok(())
+	}
 }
 
 impl Driver for VirtioNetDriver {
@@ -380,6 +868,10 @@ impl Driver for VirtioNetDriver {
 	fn get_name(&self) -> &'static str {
 		"virtio"
 	}
+
+	fn get_queue_count(&self) -> usize {
+		usize::from(self.num_vqs) + usize::from(self.ctrl_vq.0.is_some())
+	}
 }
 
 // Backend-independent interface for Virtio network driver
@@ -394,6 +886,27 @@ impl VirtioNetDriver {
 		self.com_cfg.set_failed();
 	}
 
+	/// Resets the device after DEVICE_NEEDS_RESET and initializes it again.
+	///
+	/// The queues are replaced by new ones, so that packets in flight are
+	/// lost, while the network stack keeps its connections.
+	fn recover(&mut self) {
+		self.needs_reset = false;
+		match self.init_dev() {
+			Ok(()) => info!(
+				"Virtio network device {:x} has been reset",
+				self.dev_cfg.dev_id
+			),
+			Err(err) => {
+				error!(
+					"Unable to reset virtio network device {:x}: {err:?}",
+					self.dev_cfg.dev_id
+				);
+				self.com_cfg.set_failed();
+			}
+		}
+	}
+
 	/// Returns the current status of the device, if VIRTIO_NET_F_STATUS
 	/// has been negotiated. Otherwise assumes an active device.
 	#[cfg(not(feature = "pci"))]
@@ -440,7 +953,6 @@ impl VirtioNetDriver {
 	/// device and overrides the num_vq field in the common config.
 	///
 	/// Returns 1 (i.e. minimum number of pairs) if VIRTIO_NET_F_MQ is not set.
-	#[allow(dead_code)]
 	pub fn get_max_vq_pairs(&self) -> u16 {
 		if self.dev_cfg.features.contains(virtio::net::F::MQ) {
 			self.dev_cfg
@@ -473,7 +985,9 @@ impl VirtioNetDriver {
 	///                      and v1.1. - 5.1.5
 	pub fn init_dev(&mut self) -> Result<(), VirtioNetError> {
 		// Reset
-		self.com_cfg.reset_dev();
+		if !self.com_cfg.reset_dev() {
+			return Err(VirtioNetError::FailReset(self.dev_cfg.dev_id));
+		}
 
 		// Indicate device, that OS noticed it
 		self.com_cfg.ack_dev();
@@ -501,14 +1015,28 @@ impl VirtioNetDriver {
 			// the link status can be announced
 			| virtio::net::F::STATUS
 			// Multiqueue support
-			| virtio::net::F::MQ;
-
-		// Currently the driver does NOT support the features below.
-		// In order to provide functionality for these, the driver
-		// needs to take care of calculating checksum in
-		// RxQueues.post_processing()
-		// | virtio::net::F::GUEST_TSO4
-		// | virtio::net::F::GUEST_TSO6
+			| virtio::net::F::MQ
+			// Control commands can be sent to the device
+			| virtio::net::F::CTRL_VQ
+			// Receive modes and the MAC address table can be configured
+			| virtio::net::F::CTRL_RX
+			// VLAN filtering can be configured
+			| virtio::net::F::CTRL_VLAN
+			// The device reports the hash of the flow of received packets
+			| virtio::net::F::HASH_REPORT
+			// Driver can receive coalesced TCP packets and large UDP datagrams
+			| virtio::net::F::GUEST_TSO4
+			| virtio::net::F::GUEST_TSO6
+			| virtio::net::F::GUEST_ECN
+			| virtio::net::F::GUEST_UFO
+			// Driver can receive coalesced UDP packets
+			| GUEST_USO4
+			| GUEST_USO6
+			// Device segments large TCP packets and fragments large UDP datagrams
+			| virtio::net::F::HOST_TSO4
+			| virtio::net::F::HOST_TSO6
+			| virtio::net::F::HOST_ECN
+			| virtio::net::F::HOST_UFO;
 
 		// Negotiate features with device. Automatically reduces selected feats in order to meet device capabilities.
 		// Aborts in case incompatible features are selected by the driver or the device does not support min_feat_set.
@@ -575,6 +1103,13 @@ impl VirtioNetDriver {
 						error!("No device config found.");
 						return Err(vnet_err);
 					}
+					VirtioNetError::FailReset(_)
+					| VirtioNetError::NoCtrlQueue
+					| VirtioNetError::CtrlCommandFailed
+					| VirtioNetError::CtrlNotNegotiated(_)
+					| VirtioNetError::InvalidQueuePairs(_) => {
+						return Err(vnet_err);
+					}
 				}
 			}
 		}
@@ -604,6 +1139,29 @@ impl VirtioNetDriver {
 		// At this point the device is "live"
 		self.com_cfg.drv_ok();
 
+		if self.dev_cfg.features.contains(virtio::net::F::HASH_REPORT) {
+			if let Err(err) = self.configure_hash() {
+				warn!("Unable to configure the hash calculation of the network device: {err:?}");
+			}
+		}
+
+		if self.dev_cfg.features.contains(virtio::net::F::MQ) {
+			if let Err(err) = self.set_queue_pairs(self.num_vqs / 2) {
+				warn!("Unable to set the number of queue pairs of the network device: {err:?}");
+			}
+		}
+
+		if self.dev_cfg.features.contains(virtio::net::F::CTRL_RX) {
+			let promiscuous = hermit_var!("HERMIT_NET_PROMISC").is_some_and(|value| value != "0");
+			// Multicast frames are received without a MAC table, e.g., for IPv6 neighbor discovery.
+			if let Err(err) = self
+				.set_rx_mode(virtio::net::ctrl::Rx::Promisc, promiscuous)
+				.and_then(|()| self.set_allmulticast(true))
+			{
+				warn!("Unable to configure the receive mode of the network device: {err:?}");
+			}
+		}
+
 		if self.dev_cfg.features.contains(virtio::net::F::CSUM)
 			&& self.dev_cfg.features.contains(virtio::net::F::GUEST_CSUM)
 		{
@@ -625,6 +1183,65 @@ impl VirtioNetDriver {
 		Ok(())
 	}
 
+	/// Enables the hash calculation for IP, TCP and UDP packets, whose results
+	/// are reported in the header of received packets.
+	///
+	/// See Virtio specification v1.2 - 5.1.6.5.6.4
+	fn configure_hash(&mut self) -> Result<(), VirtioNetError> {
+		let config = self.dev_cfg.raw.as_ptr();
+		let hash_types = config.supported_hash_types().read().to_ne()
+			& (virtio::net::HashType::IPV4
+				| virtio::net::HashType::TCPV4
+				| virtio::net::HashType::UDPV4
+				| virtio::net::HashType::IPV6
+				| virtio::net::HashType::TCPV6
+				| virtio::net::HashType::UDPV6)
+				.bits()
+				.to_ne();
+		let key_len = RSS_KEY.len().min(config.rss_max_key_size().read().into());
+
+		// struct virtio_net_hash_config
+		let mut data = Vec::with_capacity(13 + key_len);
+		data.extend_from_slice(&hash_types.to_le_bytes());
+		data.extend_from_slice(&[0; 8]);
+		data.push(key_len.try_into().unwrap());
+		data.extend_from_slice(&RSS_KEY[..key_len]);
+
+		self.ctrl_vq.send_command(
+			virtio::net::Ctrl::Mq,
+			virtio::net::ctrl::Mq::HashConfig.into(),
+			&data,
+		)?;
+		info!("Enabled hash reporting of the network device (hash types {hash_types:#x})");
+		Ok(())
+	}
+
+	/// Fails with [`VirtioNetError::CtrlNotNegotiated`], if the control
+	/// commands, which depend on `feature`, are not available.
+	fn check_ctrl_feature(&self, feature: virtio::net::F) -> Result<(), VirtioNetError> {
+		if self.dev_cfg.features.contains(feature) {
+			Ok(())
+		} else {
+			Err(VirtioNetError::CtrlNotNegotiated(feature))
+		}
+	}
+
+	/// Switches the receive mode `mode` on or off.
+	///
+	/// See Virtio specification v1.2 - 5.1.6.5.1
+	fn set_rx_mode(&mut self, mode: virtio::net::ctrl::Rx, on: bool) -> Result<(), VirtioNetError> {
+		self.check_ctrl_feature(virtio::net::F::CTRL_RX)?;
+		self.ctrl_vq
+			.send_command(virtio::net::Ctrl::Rx, mode.into(), &[u8::from(on)])
+	}
+
+	/// Enables or disables the reception of all multicast packets.
+	fn set_allmulticast(&mut self, on: bool) -> Result<(), VirtioNetError> {
+		self.set_rx_mode(virtio::net::ctrl::Rx::Allmulti, on)?;
+		debug!("All-multicast mode of the network device: {on}");
+		Ok(())
+	}
+
 	/// Negotiates a subset of features, understood and wanted by both the OS
 	/// and the device.
 	fn negotiate_features(
@@ -658,13 +1275,16 @@ impl VirtioNetDriver {
 
 		// Add a control if feature is negotiated
 		if self.dev_cfg.features.contains(virtio::net::F::CTRL_VQ) {
+			// The control queue follows all queue pairs of the device, even if
+			// not all of them are used (Virtio specification v1.2 - 5.1.2).
+			let ctrl_index = 2 * self.get_max_vq_pairs();
 			if self.dev_cfg.features.contains(virtio::net::F::RING_PACKED) {
 				self.ctrl_vq = CtrlQueue(Some(Box::new(
 					PackedVq::new(
 						&mut self.com_cfg,
 						&self.notif_cfg,
 						VqSize::from(VIRTIO_MAX_QUEUE_SIZE),
-						VqIndex::from(self.num_vqs),
+						VqIndex::from(ctrl_index),
 						self.dev_cfg.features.into(),
 					)
 					.unwrap(),
@@ -675,7 +1295,7 @@ impl VirtioNetDriver {
 						&mut self.com_cfg,
 						&self.notif_cfg,
 						VqSize::from(VIRTIO_MAX_QUEUE_SIZE),
-						VqIndex::from(self.num_vqs),
+						VqIndex::from(ctrl_index),
 						self.dev_cfg.features.into(),
 					)
 					.unwrap(),
@@ -690,6 +1310,12 @@ impl VirtioNetDriver {
 
 	/// Initialize virtqueues via the queue interface and populates receiving queues
 	fn virtqueue_init(&mut self) -> Result<(), VirtioNetError> {
+		// The queues depend on the negotiated features and replace the queues
+		// of a previous initialization.
+		self.recv_vqs = RxQueues::new(Vec::new(), &self.dev_cfg);
+		self.send_vqs = TxQueues::new(Vec::new(), &self.dev_cfg);
+		self.ctrl_vq = CtrlQueue::new(None);
+
 		// We are assuming here, that the device single source of truth is the
 		// device specific configuration. Hence we do NOT check if
 		//
@@ -698,29 +1324,19 @@ impl VirtioNetDriver {
 		// - the plus 1 is due to the possibility of an existing control queue
 		// - the num_queues is found in the ComCfg struct of the device and defines the maximal number
 		// of supported queues.
-		if self.dev_cfg.features.contains(virtio::net::F::MQ) {
-			if self
-				.dev_cfg
-				.raw
-				.as_ptr()
-				.max_virtqueue_pairs()
-				.read()
-				.to_ne() * 2 >= MAX_NUM_VQ
-			{
-				self.num_vqs = MAX_NUM_VQ;
-			} else {
-				self.num_vqs = self
-					.dev_cfg
-					.raw
-					.as_ptr()
-					.max_virtqueue_pairs()
-					.read()
-					.to_ne() * 2;
-			}
+		//
+		// Each core uses one queue pair, so more pairs than cores are not used.
+		// The additional pairs are enabled by `set_queue_pairs`.
+		let cores = u16::try_from(crate::arch::get_processor_count()).unwrap_or(u16::MAX);
+		let pairs = if self.dev_cfg.features.contains(virtio::net::F::MQ) {
+			self.get_max_vq_pairs().min(cores).min(MAX_QUEUE_PAIRS)
 		} else {
 			// Minimal number of virtqueues defined in the standard v1.1. - 5.1.5 Step 1
-			self.num_vqs = 2;
-		}
+			1
+		};
+		self.num_vqs = 2 * pairs;
+		// Without VIRTIO_NET_F_MQ, the device uses only the first queue pair.
+		self.active_pairs = 1;
 
 		// The loop is running from 0 to num_vqs and the indexes are provided to the VqIndex::from function in this way
 		// in order to allow the indexes of the queues to be in a form of:
@@ -795,7 +1411,8 @@ impl VirtioNetDriver {
 
 pub mod constants {
 	// Configuration constants
-	pub const MAX_NUM_VQ: u16 = 2;
+	/// Maximum number of queue pairs, which are used by the driver
+	pub const MAX_QUEUE_PAIRS: u16 = 8;
 }
 
 /// Error module of virtios network driver. Containing the (VirtioNetError)[VirtioNetError]
@@ -807,11 +1424,33 @@ pub mod error {
 		#[cfg(feature = "pci")]
 		NoDevCfg(u16),
 		FailFeatureNeg(u16),
+		/// The device has not completed its reset.
+		FailReset(u16),
 		/// Set of features does not adhere to the requirements of features
 		/// indicated by the specification
 		FeatureRequirementsNotMet(virtio::net::F),
 		/// The first field contains the feature bits wanted by the driver.
 		/// but which are incompatible with the device feature set, second field.
 		IncompatibleFeatureSets(virtio::net::F, virtio::net::F),
+		/// The control queue has not been negotiated.
+		NoCtrlQueue,
+		/// The device has not processed a control command successfully.
+		CtrlCommandFailed,
+		/// The feature, which is required by a control command, has not been negotiated.
+		CtrlNotNegotiated(virtio::net::F),
+		/// The number of queue pairs is zero or exceeds the queue pairs of the driver.
+		InvalidQueuePairs(u16),
+	}
+
+	impl From<VirtioNetError> for crate::io::Error {
+		fn from(err: VirtioNetError) -> Self {
+			match err {
+				VirtioNetError::NoCtrlQueue | VirtioNetError::CtrlNotNegotiated(_) => {
+					Self::EOPNOTSUPP
+				}
+				VirtioNetError::InvalidQueuePairs(_) => Self::EINVAL,
+				_ => Self::EIO,
+			}
+		}
 	}
 }
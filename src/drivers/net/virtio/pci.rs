@@ -5,13 +5,16 @@
 use alloc::vec::Vec;
 use core::str::FromStr;
 
+use hermit_sync::InterruptTicketMutex;
 use pci_types::CommandRegister;
 use smoltcp::phy::ChecksumCapabilities;
 use volatile::VolatileRef;
 
 use crate::arch::pci::PciConfigRegion;
+use crate::drivers::error::DriverError;
 use crate::drivers::net::virtio::{CtrlQueue, NetDevCfg, RxQueues, TxQueues, VirtioNetDriver};
-use crate::drivers::pci::PciDevice;
+use crate::drivers::pci::{PciDevice, PciDriver};
+use crate::drivers::registry::{DeviceMatch, DriverContext, PciDriverEntry};
 use crate::drivers::virtio::error::{self, VirtioError};
 use crate::drivers::virtio::transport::pci;
 use crate::drivers::virtio::transport::pci::{PciCap, UniCapsColl};
@@ -68,9 +71,11 @@ impl VirtioNetDriver {
 			recv_vqs,
 			send_vqs,
 			num_vqs: 0,
+			active_pairs: 1,
 			mtu,
 			irq: device.get_irq().unwrap(),
 			checksums: ChecksumCapabilities::default(),
+			needs_reset: false,
 		})
 	}
 
@@ -122,3 +127,29 @@ impl VirtioNetDriver {
 		Ok(drv)
 	}
 }
+
+/// Driver entry of virtio network devices
+pub(crate) struct VirtioNetEntry;
+
+impl PciDriverEntry for VirtioNetEntry {
+	fn name(&self) -> &'static str {
+		"virtio-net"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Virtio(virtio::Id::Net)];
+		MATCHES
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<PciDriver, DriverError> {
+		ctx.request_memory_bars();
+		let drv = VirtioNetDriver::init(ctx.device())?;
+		info!("Virtio network driver initialized.");
+
+		if let Some(irq) = ctx.request_irq("virtio") {
+			info!("Virtio interrupt handler at line {irq}");
+		}
+
+		Ok(PciDriver::VirtioNet(InterruptTicketMutex::new(drv)))
+	}
+}
@@ -6,9 +6,16 @@ use core::fmt;
 
 use ahash::RandomState;
 use hashbrown::HashMap;
-#[cfg(any(feature = "tcp", feature = "udp", feature = "fuse", feature = "vsock"))]
+#[cfg(any(
+	feature = "tcp",
+	feature = "udp",
+	feature = "blk",
+	feature = "console",
+	feature = "fuse",
+	feature = "pmem",
+	feature = "vsock"
+))]
 use hermit_sync::InterruptTicketMutex;
-use hermit_sync::without_interrupts;
 use memory_addresses::{PhysAddr, VirtAddr};
 use pci_types::capability::CapabilityIterator;
 use pci_types::{
@@ -17,41 +24,39 @@ use pci_types::{
 };
 
 use crate::arch::pci::PciConfigRegion;
+#[cfg(feature = "blk")]
+use crate::drivers::block::virtio::VirtioBlkDriver;
+#[cfg(feature = "console")]
+use crate::drivers::console::VirtioConsoleDriver;
 #[cfg(feature = "fuse")]
 use crate::drivers::fs::virtio_fs::VirtioFsDriver;
+#[cfg(feature = "ivshmem")]
+use crate::drivers::ivshmem::IvshmemDriver;
 #[cfg(any(feature = "tcp", feature = "udp"))]
 use crate::drivers::net::NetworkDriver;
 #[cfg(all(target_arch = "x86_64", feature = "rtl8139"))]
-use crate::drivers::net::rtl8139::{self, RTL8139Driver};
+use crate::drivers::net::rtl8139::RTL8139Driver;
 #[cfg(all(
 	not(all(target_arch = "x86_64", feature = "rtl8139")),
 	any(feature = "tcp", feature = "udp")
 ))]
 use crate::drivers::net::virtio::VirtioNetDriver;
-#[cfg(any(
-	all(
-		any(feature = "tcp", feature = "udp"),
-		not(all(target_arch = "x86_64", feature = "rtl8139"))
-	),
-	feature = "fuse",
-	feature = "vsock"
-))]
-use crate::drivers::virtio::transport::pci as pci_virtio;
-#[cfg(any(
-	all(
-		any(feature = "tcp", feature = "udp"),
-		not(all(target_arch = "x86_64", feature = "rtl8139"))
-	),
-	feature = "fuse",
-	feature = "vsock"
-))]
-use crate::drivers::virtio::transport::pci::VirtioDriver;
+#[cfg(feature = "pmem")]
+use crate::drivers::pmem::VirtioPmemDriver;
+use crate::drivers::registry;
 #[cfg(feature = "vsock")]
 use crate::drivers::vsock::VirtioVsockDriver;
 #[allow(unused_imports)]
 use crate::drivers::{Driver, InterruptHandlerQueue};
 use crate::env;
 use crate::init_cell::InitCell;
+use crate::mm::physmap::{self, RegionKind};
+use crate::synch::without_interrupts;
+
+#[cfg(feature = "pci-ids")]
+pub(crate) mod ids;
+#[cfg_attr(target_arch = "riscv64", allow(dead_code))]
+pub(crate) mod resource;
 
 pub(crate) static PCI_DEVICES: InitCell<Vec<PciDevice<PciConfigRegion>>> =
 	InitCell::new(Vec::new());
@@ -72,11 +77,15 @@ impl<T: ConfigRegionAccess> PciDevice<T> {
 		&self.access
 	}
 
+	pub fn address(&self) -> PciAddress {
+		self.address
+	}
+
 	pub fn header(&self) -> PciHeader {
 		PciHeader::new(self.address)
 	}
 
-	/// Set flag to the command register
+	/// Sets the flags `cmd` in the command register and keeps the other flags
 	pub fn set_command(&self, cmd: CommandRegister) {
 		self.header()
 			.update_command(&self.access, |command| command | cmd);
@@ -92,6 +101,21 @@ impl<T: ConfigRegionAccess> PciDevice<T> {
 		None
 	}
 
+	/// Enters the memory of the BAR `index` at the CPU address `address` into the physical memory map.
+	///
+	/// Stops the kernel, if the BAR overlaps RAM or another device.
+	pub fn insert_bar_region(&self, index: u8, address: u64, size: usize) {
+		let start = PhysAddr::new(address);
+		if let Err(conflict) =
+			physmap::try_insert(start..start + size as u64, RegionKind::Mmio, "PCI BAR")
+		{
+			panic!(
+				"BAR {index} of the PCI device {} is inconsistent with the physical memory map: {conflict}",
+				self.address
+			);
+		}
+	}
+
 	/// Configure the bar at register `slot`
 	pub fn set_bar(&self, slot: u8, bar: Bar) {
 		let value = match bar {
@@ -157,6 +181,7 @@ impl<T: ConfigRegionAccess> PciDevice<T> {
 		if address == 0 {
 			return None;
 		}
+		let address = resource::bus_to_cpu(address);
 
 		debug!(
 			"Mapping bar {} at {:#x} with length {:#x}",
@@ -171,8 +196,10 @@ impl<T: ConfigRegionAccess> PciDevice<T> {
 			warn!("Currently only mapping of prefetchable bars is supported!");
 		}
 
-		// Since the bios/bootloader manages the physical address space, the address got from the bar is unique and not overlapping.
+		// The bios/bootloader manages the physical address space and the BAR has to lie outside of the RAM.
 		// We therefore do not need to reserve any additional memory in our kernel.
+		self.insert_bar_region(index, address, size);
+
 		// Map bar into RW^X virtual memory
 		let physical_address = address;
 		let virtual_address = if env::is_uefi() {
@@ -226,42 +253,39 @@ impl<T: ConfigRegionAccess> fmt::Display for PciDevice<T> {
 		let (_dev_rev, class_id, subclass_id, _interface) = header.revision_and_class(&self.access);
 
 		if let Some(endpoint) = EndpointHeader::from_header(header, &self.access) {
-			#[cfg(feature = "pci-ids")]
-			let (class_name, vendor_name, device_name) = {
-				use pci_ids::{Class, Device, FromId, Subclass};
-
-				let class_name = Class::from_id(class_id).map_or("Unknown Class", |class| {
-					class
-						.subclasses()
-						.find(|s| s.id() == subclass_id)
-						.map(Subclass::name)
-						.unwrap_or_else(|| class.name())
-				});
-
-				let (vendor_name, device_name) = Device::from_vid_pid(vendor_id, device_id)
-					.map(|device| (device.vendor().name(), device.name()))
-					.unwrap_or(("Unknown Vendor", "Unknown Device"));
+			write!(
+				f,
+				"{:02X}:{:02X} ",
+				self.address.bus(),
+				self.address.device()
+			)?;
 
-				(class_name, vendor_name, device_name)
-			};
+			// Without the PCI ID database, only the raw IDs are printed.
+			#[cfg(feature = "pci-ids")]
+			{
+				let (subsystem_id, subsystem_vendor_id) = endpoint.subsystem(&self.access);
+				let class_name = ids::class_name(class_id, subclass_id).unwrap_or("Unknown Class");
+				let vendor_name = ids::vendor_name(vendor_id).unwrap_or("Unknown Vendor");
+				let device_name =
+					ids::device_name(vendor_id, device_id).unwrap_or("Unknown Device");
+
+				// Output detailed readable information about this device.
+				write!(
+					f,
+					"{class_name} [{class_id:02X}{subclass_id:02X}]: {vendor_name} {device_name} [{vendor_id:04X}:{device_id:04X}]"
+				)?;
+
+				if let Some(subsystem_name) =
+					ids::subsystem_name(vendor_id, device_id, subsystem_vendor_id, subsystem_id)
+				{
+					write!(f, " ({subsystem_name})")?;
+				}
+			}
 
 			#[cfg(not(feature = "pci-ids"))]
-			let (class_name, vendor_name, device_name) =
-				("Unknown Class", "Unknown Vendor", "Unknown Device");
-
-			// Output detailed readable information about this device.
 			write!(
 				f,
-				"{:02X}:{:02X} {} [{:02X}{:02X}]: {} {} [{:04X}:{:04X}]",
-				self.address.bus(),
-				self.address.device(),
-				class_name,
-				class_id,
-				subclass_id,
-				vendor_name,
-				device_name,
-				vendor_id,
-				device_id
+				"[{class_id:02X}{subclass_id:02X}]: [{vendor_id:04X}:{device_id:04X}]"
 			)?;
 
 			// If the devices uses an IRQ, output this one as well.
@@ -336,6 +360,14 @@ pub(crate) enum PciDriver {
 	VirtioFs(InterruptTicketMutex<VirtioFsDriver>),
 	#[cfg(feature = "vsock")]
 	VirtioVsock(InterruptTicketMutex<VirtioVsockDriver>),
+	#[cfg(feature = "pmem")]
+	VirtioPmem(InterruptTicketMutex<VirtioPmemDriver>),
+	#[cfg(feature = "blk")]
+	VirtioBlk(InterruptTicketMutex<VirtioBlkDriver>),
+	#[cfg(feature = "console")]
+	VirtioConsole(InterruptTicketMutex<VirtioConsoleDriver>),
+	#[cfg(feature = "ivshmem")]
+	Ivshmem(InterruptTicketMutex<IvshmemDriver>),
 	#[cfg(all(
 		not(all(target_arch = "x86_64", feature = "rtl8139")),
 		any(feature = "tcp", feature = "udp")
@@ -393,6 +425,70 @@ impl PciDriver {
 		}
 	}
 
+	#[cfg(feature = "pmem")]
+	fn get_pmem_driver(&self) -> Option<&InterruptTicketMutex<VirtioPmemDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioPmem(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "blk")]
+	fn get_block_driver(&self) -> Option<&InterruptTicketMutex<VirtioBlkDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioBlk(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "console")]
+	fn get_console_driver(&self) -> Option<&InterruptTicketMutex<VirtioConsoleDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioConsole(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "ivshmem")]
+	fn get_ivshmem_driver(&self) -> Option<&InterruptTicketMutex<IvshmemDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::Ivshmem(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	fn get_queue_count(&self) -> usize {
+		#[allow(unreachable_patterns)]
+		match self {
+			#[cfg(feature = "vsock")]
+			Self::VirtioVsock(drv) => drv.lock().get_queue_count(),
+			#[cfg(all(
+				target_arch = "x86_64",
+				feature = "rtl8139",
+				any(feature = "tcp", feature = "udp")
+			))]
+			Self::RTL8139Net(drv) => drv.lock().get_queue_count(),
+			#[cfg(all(
+				not(all(target_arch = "x86_64", feature = "rtl8139")),
+				any(feature = "tcp", feature = "udp")
+			))]
+			Self::VirtioNet(drv) => drv.lock().get_queue_count(),
+			#[cfg(feature = "fuse")]
+			Self::VirtioFs(drv) => drv.lock().get_queue_count(),
+			#[cfg(feature = "pmem")]
+			Self::VirtioPmem(drv) => drv.lock().get_queue_count(),
+			#[cfg(feature = "blk")]
+			Self::VirtioBlk(drv) => drv.lock().get_queue_count(),
+			#[cfg(feature = "console")]
+			Self::VirtioConsole(drv) => drv.lock().get_queue_count(),
+			_ => 0,
+		}
+	}
+
 	fn get_interrupt_handler(&self) -> (InterruptLine, fn()) {
 		#[allow(unreachable_patterns)]
 		match self {
@@ -415,6 +511,7 @@ impl PciDriver {
 			))]
 			Self::RTL8139Net(drv) => {
 				fn rtl8139_handler() {
+					crate::executor::timestamp::interrupt();
 					if let Some(driver) = get_network_driver() {
 						driver.lock().handle_interrupt();
 					}
@@ -430,6 +527,7 @@ impl PciDriver {
 			))]
 			Self::VirtioNet(drv) => {
 				fn network_handler() {
+					crate::executor::timestamp::interrupt();
 					if let Some(driver) = get_network_driver() {
 						driver.lock().handle_interrupt();
 					}
@@ -441,12 +539,64 @@ impl PciDriver {
 			}
 			#[cfg(feature = "fuse")]
 			Self::VirtioFs(drv) => {
-				fn fuse_handler() {}
+				fn fuse_handler() {
+					if let Some(driver) = get_filesystem_driver() {
+						driver.lock().handle_interrupt();
+					}
+				}
 
 				let irq_number = drv.lock().get_interrupt_number();
 
 				(irq_number, fuse_handler)
 			}
+			#[cfg(feature = "pmem")]
+			Self::VirtioPmem(drv) => {
+				fn pmem_handler() {
+					if let Some(driver) = get_pmem_driver() {
+						driver.lock().handle_interrupt();
+					}
+				}
+
+				let irq_number = drv.lock().get_interrupt_number();
+
+				(irq_number, pmem_handler)
+			}
+			#[cfg(feature = "blk")]
+			Self::VirtioBlk(drv) => {
+				fn blk_handler() {
+					for driver in get_block_drivers() {
+						driver.lock().handle_interrupt();
+					}
+				}
+
+				let irq_number = drv.lock().get_interrupt_number();
+
+				(irq_number, blk_handler)
+			}
+			#[cfg(feature = "console")]
+			Self::VirtioConsole(drv) => {
+				fn console_handler() {
+					if let Some(driver) = get_console_driver() {
+						driver.lock().handle_interrupt();
+					}
+				}
+
+				let irq_number = drv.lock().get_interrupt_number();
+
+				(irq_number, console_handler)
+			}
+			#[cfg(feature = "ivshmem")]
+			Self::Ivshmem(drv) => {
+				fn ivshmem_handler() {
+					if let Some(driver) = get_ivshmem_driver() {
+						driver.lock().handle_interrupt();
+					}
+				}
+
+				let irq_number = drv.lock().get_interrupt_number();
+
+				(irq_number, ivshmem_handler)
+			}
 			_ => todo!(),
 		}
 	}
@@ -515,60 +665,58 @@ pub(crate) fn get_filesystem_driver() -> Option<&'static InterruptTicketMutex<Vi
 		.find_map(|drv| drv.get_filesystem_driver())
 }
 
-pub(crate) fn init() {
-	// virtio: 4.1.2 PCI Device Discovery
-	without_interrupts(|| {
-		for adapter in PCI_DEVICES.finalize().iter().filter(|x| {
-			let (vendor_id, device_id) = x.id();
-			vendor_id == 0x1af4 && (0x1000..=0x107f).contains(&device_id)
-		}) {
-			info!(
-				"Found virtio device with device id {:#x}",
-				adapter.device_id()
-			);
+#[cfg(feature = "pmem")]
+pub(crate) fn get_pmem_driver() -> Option<&'static InterruptTicketMutex<VirtioPmemDriver>> {
+	PCI_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_pmem_driver())
+}
 
-			#[cfg(any(
-				all(
-					any(feature = "tcp", feature = "udp"),
-					not(all(target_arch = "x86_64", feature = "rtl8139"))
-				),
-				feature = "fuse",
-				feature = "vsock"
-			))]
-			match pci_virtio::init_device(adapter) {
-				#[cfg(all(
-					not(all(target_arch = "x86_64", feature = "rtl8139")),
-					any(feature = "tcp", feature = "udp")
-				))]
-				Ok(VirtioDriver::Network(drv)) => {
-					register_driver(PciDriver::VirtioNet(InterruptTicketMutex::new(drv)));
-				}
-				#[cfg(feature = "vsock")]
-				Ok(VirtioDriver::Vsock(drv)) => {
-					register_driver(PciDriver::VirtioVsock(InterruptTicketMutex::new(drv)));
-				}
-				#[cfg(feature = "fuse")]
-				Ok(VirtioDriver::FileSystem(drv)) => {
-					register_driver(PciDriver::VirtioFs(InterruptTicketMutex::new(drv)));
-				}
-				_ => {}
-			}
-		}
+#[cfg(feature = "blk")]
+pub(crate) fn get_block_drivers() -> Vec<&'static InterruptTicketMutex<VirtioBlkDriver>> {
+	PCI_DRIVERS.get().map_or_else(Vec::new, |drivers| {
+		drivers
+			.iter()
+			.filter_map(|drv| drv.get_block_driver())
+			.collect()
+	})
+}
 
-		// Searching for Realtek RTL8139, which is supported by Qemu
-		#[cfg(all(target_arch = "x86_64", feature = "rtl8139"))]
-		for adapter in PCI_DEVICES.finalize().iter().filter(|x| {
-			let (vendor_id, device_id) = x.id();
-			vendor_id == 0x10ec && (0x8138..=0x8139).contains(&device_id)
-		}) {
-			info!(
-				"Found Realtek network device with device id {:#x}",
-				adapter.device_id()
-			);
+#[cfg(feature = "console")]
+pub(crate) fn get_console_driver() -> Option<&'static InterruptTicketMutex<VirtioConsoleDriver>> {
+	PCI_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_console_driver())
+}
 
-			if let Ok(drv) = rtl8139::init_device(adapter) {
-				register_driver(PciDriver::RTL8139Net(InterruptTicketMutex::new(drv)));
-			}
+#[cfg(feature = "ivshmem")]
+pub(crate) fn get_ivshmem_driver() -> Option<&'static InterruptTicketMutex<IvshmemDriver>> {
+	PCI_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_ivshmem_driver())
+}
+
+/// Returns the number of attached drivers.
+pub(crate) fn get_driver_count() -> usize {
+	PCI_DRIVERS.get().map_or(0, Vec::len)
+}
+
+/// Returns the number of queues of the driver, which was attached as `index`-th driver.
+pub(crate) fn get_queue_count(index: usize) -> usize {
+	PCI_DRIVERS
+		.get()
+		.and_then(|drivers| drivers.get(index))
+		.map_or(0, PciDriver::get_queue_count)
+}
+
+pub(crate) fn init() {
+	without_interrupts(|| {
+		let drivers = registry::probe_devices(PCI_DEVICES.finalize());
+		for drv in drivers {
+			register_driver(drv);
 		}
 	});
 }
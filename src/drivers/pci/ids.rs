@@ -0,0 +1,46 @@
+//! Names from the [PCI ID Repository](https://pci-ids.ucw.cz/)
+//!
+//! Vendors are found through a perfect hash map, devices and classes by
+//! scanning the short lists of their vendor or class.
+
+use pci_ids::{Class, Device, FromId, SubSystem, Subclass, Vendor};
+use pci_types::{BaseClass, DeviceId, SubClass, SubsystemId, SubsystemVendorId, VendorId};
+
+fn device(vendor_id: VendorId, device_id: DeviceId) -> Option<&'static Device> {
+	Vendor::from_id(vendor_id)?
+		.devices()
+		.find(|device| device.id() == device_id)
+}
+
+pub(crate) fn vendor_name(vendor_id: VendorId) -> Option<&'static str> {
+	Vendor::from_id(vendor_id).map(Vendor::name)
+}
+
+pub(crate) fn device_name(vendor_id: VendorId, device_id: DeviceId) -> Option<&'static str> {
+	device(vendor_id, device_id).map(Device::name)
+}
+
+/// Returns the name of the subsystem, i.e., the board the device is built into.
+pub(crate) fn subsystem_name(
+	vendor_id: VendorId,
+	device_id: DeviceId,
+	subsystem_vendor_id: SubsystemVendorId,
+	subsystem_id: SubsystemId,
+) -> Option<&'static str> {
+	device(vendor_id, device_id)?
+		.subsystems()
+		.find(|subsystem| {
+			subsystem.subvendor() == subsystem_vendor_id && subsystem.subdevice() == subsystem_id
+		})
+		.map(SubSystem::name)
+}
+
+/// Returns the name of the subclass or, if it is unknown, of the class.
+pub(crate) fn class_name(class_id: BaseClass, subclass_id: SubClass) -> Option<&'static str> {
+	let class = Class::from_id(class_id)?;
+	let name = class
+		.subclasses()
+		.find(|subclass| subclass.id() == subclass_id)
+		.map_or_else(|| class.name(), Subclass::name);
+	Some(name)
+}
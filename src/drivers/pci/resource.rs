@@ -0,0 +1,238 @@
+//! Assignment of PCI resources
+//!
+//! Usually, the firmware assigns addresses to the BARs of all PCI devices.
+//! Some hypervisors and boot paths leave this to the operating system.
+//! Before the drivers probe their devices, unassigned BARs get addresses
+//! from the windows of the host bridge. Addresses, which are already in use
+//! by other BARs, are skipped.
+//!
+//! The BARs hold PCI bus addresses, which differ from the CPU addresses of
+//! the memory on some host bridges. Hence, the memory windows consist of both
+//! and drivers translate a BAR by [`bus_to_cpu`], before they map it.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use align_address::Align;
+use hermit_sync::OnceCell;
+use pci_types::{Bar, CommandRegister, ConfigRegionAccess, MAX_BARS};
+
+use crate::arch::pci::PciConfigRegion;
+use crate::drivers::pci::PciDevice;
+
+/// Memory window of a host bridge
+#[derive(Debug, Clone)]
+pub(crate) struct PciWindow {
+	/// PCI bus addresses of the window
+	pub pci: Range<u64>,
+	/// CPU address, at which the window starts
+	pub cpu_start: u64,
+}
+
+/// Address windows of a host bridge
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PciWindows {
+	/// I/O port space, which is addressed by the PCI addresses
+	pub io: Option<Range<u64>>,
+	/// Memory space below 4 GiB
+	pub mem32: Option<PciWindow>,
+	/// Memory space above 4 GiB
+	pub mem64: Option<PciWindow>,
+}
+
+/// Windows of the host bridge, which are used to translate the BARs
+static WINDOWS: OnceCell<PciWindows> = OnceCell::new();
+
+/// Returns the CPU address of the PCI bus address `address` of a memory BAR.
+///
+/// Addresses outside of the known windows are not translated.
+pub(crate) fn bus_to_cpu(address: u64) -> u64 {
+	WINDOWS
+		.get()
+		.into_iter()
+		.flat_map(|windows| [&windows.mem32, &windows.mem64])
+		.flatten()
+		.find(|window| window.pci.contains(&address))
+		.map_or(address, |window| {
+			window.cpu_start + (address - window.pci.start)
+		})
+}
+
+/// Allocates naturally aligned ranges from a window
+struct WindowAllocator {
+	window: Range<u64>,
+	next: u64,
+	used: Vec<Range<u64>>,
+}
+
+impl WindowAllocator {
+	fn new(window: Option<Range<u64>>) -> Self {
+		let window = window.unwrap_or_default();
+		Self {
+			next: window.start,
+			window,
+			used: Vec::new(),
+		}
+	}
+
+	fn reserve(&mut self, range: Range<u64>) {
+		self.used.push(range);
+	}
+
+	fn allocate(&mut self, size: u64) -> Option<u64> {
+		let mut start = self.next.align_up(size);
+		loop {
+			let end = start.checked_add(size)?;
+			if end > self.window.end {
+				return None;
+			}
+
+			match self
+				.used
+				.iter()
+				.find(|used| used.start < end && start < used.end)
+			{
+				Some(used) => start = used.end.align_up(size),
+				None => {
+					self.used.push(start..end);
+					self.next = end;
+					return Some(start);
+				}
+			}
+		}
+	}
+}
+
+/// Returns the size of the I/O BAR in `slot`.
+fn io_bar_size(device: &PciDevice<PciConfigRegion>, slot: u8) -> u64 {
+	let address = device.header().address();
+	let offset = 0x10 + u16::from(slot) * 4;
+	let access = device.access();
+
+	let readback = unsafe {
+		let value = access.read(address, offset);
+		access.write(address, offset, u32::MAX);
+		let readback = access.read(address, offset);
+		access.write(address, offset, value);
+		readback
+	};
+
+	// the upper 16 bits may be hardwired to zero
+	let mask = readback & 0xfffc;
+	if mask == 0 {
+		0
+	} else {
+		1 << mask.trailing_zeros()
+	}
+}
+
+/// Returns the BARs of `device` together with their slots.
+fn bars(device: &PciDevice<PciConfigRegion>) -> Vec<(u8, Bar)> {
+	let mut bars = Vec::new();
+	let mut slot = 0;
+	while usize::from(slot) < MAX_BARS {
+		let bar = device.get_bar(slot);
+		if let Some(bar) = bar {
+			bars.push((slot, bar));
+		}
+		// a 64 bit BAR occupies two slots
+		slot += if matches!(bar, Some(Bar::Memory64 { .. })) {
+			2
+		} else {
+			1
+		};
+	}
+	bars
+}
+
+/// Assigns addresses from `windows` to all unassigned BARs of `devices`.
+pub(crate) fn assign(devices: &[PciDevice<PciConfigRegion>], windows: PciWindows) {
+	let mut io = WindowAllocator::new(windows.io.clone());
+	let mut mem32 = WindowAllocator::new(windows.mem32.as_ref().map(|window| window.pci.clone()));
+	let mut mem64 = WindowAllocator::new(windows.mem64.as_ref().map(|window| window.pci.clone()));
+	let _ = WINDOWS.set(windows);
+
+	// keep clear of the resources, which are assigned by the firmware
+	for device in devices {
+		for (slot, bar) in bars(device) {
+			match bar {
+				Bar::Io { port } if port != 0 => {
+					let port = u64::from(port);
+					io.reserve(port..port + io_bar_size(device, slot));
+				}
+				Bar::Memory32 { address, size, .. } if address != 0 => {
+					let address = u64::from(address);
+					mem32.reserve(address..address + u64::from(size));
+					mem64.reserve(address..address + u64::from(size));
+				}
+				Bar::Memory64 { address, size, .. } if address != 0 => {
+					mem32.reserve(address..address + size);
+					mem64.reserve(address..address + size);
+				}
+				_ => {}
+			}
+		}
+	}
+
+	for device in devices {
+		let mut cmd = CommandRegister::empty();
+
+		for (slot, bar) in bars(device) {
+			let assigned = match bar {
+				Bar::Io { port: 0 } => {
+					let size = io_bar_size(device, slot);
+					if size == 0 {
+						continue;
+					}
+					io.allocate(size)
+						.and_then(|port| u32::try_from(port).ok())
+						.map(|port| Bar::Io { port })
+				}
+				Bar::Memory32 {
+					address: 0,
+					size,
+					prefetchable,
+				} => mem32
+					.allocate(u64::from(size))
+					.and_then(|address| u32::try_from(address).ok())
+					.map(|address| Bar::Memory32 {
+						address,
+						size,
+						prefetchable,
+					}),
+				Bar::Memory64 {
+					address: 0,
+					size,
+					prefetchable,
+				} => mem64
+					.allocate(size)
+					.or_else(|| mem32.allocate(size))
+					.map(|address| Bar::Memory64 {
+						address,
+						size,
+						prefetchable,
+					}),
+				_ => continue,
+			};
+
+			let (vendor_id, device_id) = device.id();
+			let Some(assigned) = assigned else {
+				warn!("Unable to assign BAR {slot} of PCI device {vendor_id:#x}:{device_id:#x}");
+				continue;
+			};
+
+			debug!(
+				"Assign {assigned:x?} to BAR {slot} of PCI device {vendor_id:#x}:{device_id:#x}"
+			);
+			device.set_bar(slot, assigned);
+			cmd |= match assigned {
+				Bar::Io { .. } => CommandRegister::IO_ENABLE,
+				_ => CommandRegister::MEMORY_ENABLE,
+			} | CommandRegister::BUS_MASTER_ENABLE;
+		}
+
+		if !cmd.is_empty() {
+			device.set_command(cmd);
+		}
+	}
+}
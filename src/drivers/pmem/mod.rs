@@ -0,0 +1,232 @@
+//! A module containing a virtio persistent memory driver.
+//!
+//! A virtio-pmem device provides a range of guest physical memory, which is
+//! backed by a file on the host. The memory is accessed directly (DAX) and
+//! modifications become persistent, once the driver sends a flush request
+//! to the device.
+
+pub mod pci;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use memory_addresses::{PhysAddr, VirtAddr};
+use pci_types::InterruptLine;
+use virtio::{FeatureBits, le32, le64};
+
+use crate::config::VIRTIO_MAX_QUEUE_SIZE;
+use crate::drivers::Driver;
+use crate::drivers::pmem::error::VirtioPmemError;
+use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
+use crate::drivers::virtio::virtqueue::split::SplitVq;
+use crate::drivers::virtio::virtqueue::{
+	AvailBufferToken, BufferElem, BufferType, Virtq, VqIndex, VqSize,
+};
+use crate::mm::device_alloc::DeviceAlloc;
+
+/// Request type of a flush request
+const VIRTIO_PMEM_REQ_TYPE_FLUSH: u32 = 0;
+
+/// Virtio's persistent memory device configuration structure.
+/// See specification v1.2. - 5.19.4
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub(crate) struct PmemDevCfgRaw {
+	/// Guest physical start address of the memory region
+	pub start: le64,
+	/// Size of the memory region in bytes
+	pub size: le64,
+}
+
+pub(crate) struct PmemDevCfg {
+	pub raw: &'static PmemDevCfgRaw,
+	pub dev_id: u16,
+	pub features: virtio::F,
+}
+
+/// Memory region of the device
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct PmemRegion {
+	pub virt_addr: VirtAddr,
+	pub size: usize,
+}
+
+pub(crate) struct VirtioPmemDriver {
+	pub(super) dev_cfg: PmemDevCfg,
+	pub(super) com_cfg: ComCfg,
+	pub(super) isr_stat: IsrStatus,
+	pub(super) notif_cfg: NotifCfg,
+	pub(super) irq: InterruptLine,
+	pub(super) request_vq: Option<Box<dyn Virtq>>,
+	pub(super) region: Option<PmemRegion>,
+}
+
+impl Driver for VirtioPmemDriver {
+	fn get_interrupt_number(&self) -> InterruptLine {
+		self.irq
+	}
+
+	fn get_name(&self) -> &'static str {
+		"virtio"
+	}
+
+	fn get_queue_count(&self) -> usize {
+		usize::from(self.request_vq.is_some())
+	}
+}
+
+impl VirtioPmemDriver {
+	pub fn get_dev_id(&self) -> u16 {
+		self.dev_cfg.dev_id
+	}
+
+	pub fn set_failed(&mut self) {
+		self.com_cfg.set_failed();
+	}
+
+	/// Returns the memory region of the device.
+	pub fn region(&self) -> Option<PmemRegion> {
+		self.region
+	}
+
+	pub fn handle_interrupt(&mut self) {
+		self.isr_stat.acknowledge();
+	}
+
+	/// Negotiates a subset of features, understood and wanted by both the OS
+	/// and the device.
+	fn negotiate_features(&mut self, driver_features: virtio::F) -> Result<(), VirtioPmemError> {
+		let device_features = self.com_cfg.dev_features();
+
+		if device_features.requirements_satisfied() {
+			debug!("Feature set wanted by pmem driver are in conformance with specification.");
+		} else {
+			return Err(VirtioPmemError::FeatureRequirementsNotMet(device_features));
+		}
+
+		if device_features.contains(driver_features) {
+			// If device supports subset of features write feature set to common config
+			self.com_cfg.set_drv_features(driver_features);
+			Ok(())
+		} else {
+			Err(VirtioPmemError::IncompatibleFeatureSets(
+				driver_features,
+				device_features,
+			))
+		}
+	}
+
+	/// Initializes the device in adherence to specification and maps
+	/// the memory region of the device.
+	///
+	/// See Virtio specification v1.2. - 3.1.1.
+	///                      and v1.2. - 5.19.5
+	pub fn init_dev(&mut self) -> Result<(), VirtioPmemError> {
+		// Reset
+		self.com_cfg.reset_dev();
+
+		// Indicate device, that OS noticed it
+		self.com_cfg.ack_dev();
+
+		// Indicate device, that driver is able to handle it
+		self.com_cfg.set_drv();
+
+		let features = virtio::F::VERSION_1;
+		self.negotiate_features(features)?;
+
+		// Indicates the device, that the current feature set is final for the driver
+		// and will not be changed.
+		self.com_cfg.features_ok();
+
+		// Checks if the device has accepted final set. This finishes feature negotiation.
+		if self.com_cfg.check_features() {
+			info!(
+				"Features have been negotiated between virtio pmem device {:x} and driver.",
+				self.dev_cfg.dev_id
+			);
+			// Set feature set in device config fur future use.
+			self.dev_cfg.features = features;
+		} else {
+			return Err(VirtioPmemError::FailFeatureNeg(self.dev_cfg.dev_id));
+		}
+
+		let vq = SplitVq::new(
+			&mut self.com_cfg,
+			&self.notif_cfg,
+			VqSize::from(VIRTIO_MAX_QUEUE_SIZE),
+			VqIndex::from(0u16),
+			self.dev_cfg.features,
+		)
+		.map_err(|_| VirtioPmemError::NoRequestQueue(self.dev_cfg.dev_id))?;
+		self.request_vq = Some(Box::new(vq));
+
+		let start = self.dev_cfg.raw.start.to_ne();
+		let size = usize::try_from(self.dev_cfg.raw.size.to_ne()).unwrap();
+		if size == 0 {
+			return Err(VirtioPmemError::EmptyRegion(self.dev_cfg.dev_id));
+		}
+
+		// The region is ordinary memory, so it is mapped cacheable.
+		let phys_addr = PhysAddr::new(start);
+		let virt_addr = crate::mm::map(phys_addr, size, true, true, false);
+		info!("Map persistent memory {phys_addr:p} ({size:#x} bytes) to {virt_addr:p}");
+		self.region = Some(PmemRegion { virt_addr, size });
+
+		// At this point the device is "live"
+		self.com_cfg.drv_ok();
+
+		Ok(())
+	}
+
+	/// Makes all writes to the memory region persistent.
+	pub fn flush(&mut self) -> Result<(), VirtioPmemError> {
+		let request_vq = self
+			.request_vq
+			.as_mut()
+			.ok_or(VirtioPmemError::NoRequestQueue(self.dev_cfg.dev_id))?;
+
+		// All writes to the region have to be visible before the flush request.
+		crate::arch::memory_barrier();
+
+		let req = Box::new_in(le32::from_ne(VIRTIO_PMEM_REQ_TYPE_FLUSH), DeviceAlloc);
+		let resp = Box::<le32, _>::new_uninit_in(DeviceAlloc);
+		let buffer_tkn = AvailBufferToken::new(
+			Vec::from([BufferElem::Sized(req)]),
+			Vec::from([BufferElem::Sized(resp)]),
+		)
+		.unwrap();
+		let mut used = request_vq
+			.dispatch_blocking(buffer_tkn, BufferType::Direct)
+			.map_err(|_| VirtioPmemError::FlushFailed)?;
+
+		let resp = used
+			.used_recv_buff
+			.pop_front_downcast::<le32>()
+			.ok_or(VirtioPmemError::FlushFailed)?;
+		if resp.to_ne() == 0 {
+			Ok(())
+		} else {
+			Err(VirtioPmemError::FlushFailed)
+		}
+	}
+}
+
+/// Error module of virtio's persistent memory driver.
+pub mod error {
+	/// Virtio persistent memory error enum.
+	#[derive(Debug, Copy, Clone)]
+	pub enum VirtioPmemError {
+		NoDevCfg(u16),
+		NoRequestQueue(u16),
+		EmptyRegion(u16),
+		FailFeatureNeg(u16),
+		/// The first field contains the feature bits wanted by the driver.
+		/// but which are incompatible with the device feature set, second field.
+		IncompatibleFeatureSets(virtio::F, virtio::F),
+		/// Set of features does not adhere to the requirements of features
+		/// indicated by the specification
+		FeatureRequirementsNotMet(virtio::F),
+		/// The device reported an error on a flush request
+		FlushFailed,
+	}
+}
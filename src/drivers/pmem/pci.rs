@@ -0,0 +1,110 @@
+use hermit_sync::InterruptTicketMutex;
+
+use crate::arch::pci::PciConfigRegion;
+use crate::drivers::error::DriverError;
+use crate::drivers::pci::{PciDevice, PciDriver};
+use crate::drivers::pmem::{PmemDevCfg, PmemDevCfgRaw, VirtioPmemDriver, error};
+use crate::drivers::registry::{DeviceMatch, DriverContext, PciDriverEntry};
+use crate::drivers::virtio::error::VirtioError;
+use crate::drivers::virtio::transport::pci;
+use crate::drivers::virtio::transport::pci::{PciCap, UniCapsColl};
+
+impl VirtioPmemDriver {
+	fn map_cfg(cap: &PciCap) -> Option<PmemDevCfg> {
+		let dev_cfg = pci::map_dev_cfg::<PmemDevCfgRaw>(cap)?;
+
+		Some(PmemDevCfg {
+			raw: dev_cfg,
+			dev_id: cap.dev_id(),
+			features: virtio::F::empty(),
+		})
+	}
+
+	/// Instantiates a new VirtioPmemDriver struct, by checking the available
+	/// configuration structures and moving them into the struct.
+	pub fn new(
+		caps_coll: UniCapsColl,
+		device: &PciDevice<PciConfigRegion>,
+	) -> Result<Self, error::VirtioPmemError> {
+		let device_id = device.device_id();
+
+		let UniCapsColl {
+			com_cfg,
+			notif_cfg,
+			isr_cfg,
+			dev_cfg_list,
+			..
+		} = caps_coll;
+
+		let Some(dev_cfg) = dev_cfg_list.iter().find_map(VirtioPmemDriver::map_cfg) else {
+			error!("No dev config. Aborting!");
+			return Err(error::VirtioPmemError::NoDevCfg(device_id));
+		};
+
+		Ok(VirtioPmemDriver {
+			dev_cfg,
+			com_cfg,
+			isr_stat: isr_cfg,
+			notif_cfg,
+			irq: device.get_irq().unwrap(),
+			request_vq: None,
+			region: None,
+		})
+	}
+
+	/// Initializes virtio persistent memory device
+	pub fn init(device: &PciDevice<PciConfigRegion>) -> Result<VirtioPmemDriver, VirtioError> {
+		let mut drv = match pci::map_caps(device) {
+			Ok(caps) => match VirtioPmemDriver::new(caps, device) {
+				Ok(driver) => driver,
+				Err(pmem_err) => {
+					error!("Initializing new pmem driver failed. Aborting!");
+					return Err(VirtioError::PmemDriver(pmem_err));
+				}
+			},
+			Err(err) => {
+				error!("Mapping capabilities failed. Aborting!");
+				return Err(err);
+			}
+		};
+
+		match drv.init_dev() {
+			Ok(()) => info!(
+				"Persistent memory device with id {:x}, has been initialized by driver!",
+				drv.get_dev_id()
+			),
+			Err(pmem_err) => {
+				drv.set_failed();
+				return Err(VirtioError::PmemDriver(pmem_err));
+			}
+		}
+
+		Ok(drv)
+	}
+}
+
+/// Driver entry of virtio persistent memory devices
+pub(crate) struct VirtioPmemEntry;
+
+impl PciDriverEntry for VirtioPmemEntry {
+	fn name(&self) -> &'static str {
+		"virtio-pmem"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Virtio(virtio::Id::Pmem)];
+		MATCHES
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<PciDriver, DriverError> {
+		ctx.request_memory_bars();
+		let drv = VirtioPmemDriver::init(ctx.device())?;
+		info!("Virtio pmem driver initialized.");
+
+		if let Some(irq) = ctx.request_irq("virtio") {
+			info!("Virtio interrupt handler at line {irq}");
+		}
+
+		Ok(PciDriver::VirtioPmem(InterruptTicketMutex::new(drv)))
+	}
+}
@@ -0,0 +1,103 @@
+//! Barrier-correct access to device registers
+//!
+//! A volatile access prevents the compiler from eliding or merging register
+//! accesses, but does not order them with respect to normal memory, which is
+//! shared with the device, e.g., a virtqueue. As `readl` and `writel` on
+//! Linux, the accessors of this module therefore include a barrier:
+//!
+//! - A read is completed before the following memory accesses, so that
+//!   memory, which the device has written before updating the register, is
+//!   read afterwards.
+//! - A write is issued after the preceding memory accesses, so that the
+//!   device sees all updates of the memory, when it is notified.
+
+use virtio::le64;
+use virtio::volatile::{OveralignedField, OveralignedVolatilePtr, WideVolatilePtr};
+use volatile::VolatilePtr;
+use volatile::access::{Readable, Writable};
+
+use crate::arch::{io_read_barrier, io_write_barrier};
+
+/// Ordered reads of a register in device memory
+pub(crate) trait RegisterRead<T> {
+	/// Reads the register before the following memory accesses.
+	fn read_ordered(self) -> T;
+}
+
+/// Ordered writes of a register in device memory
+pub(crate) trait RegisterWrite<T> {
+	/// Writes the register after the preceding memory accesses.
+	fn write_ordered(self, value: T);
+}
+
+/// Ordered read-modify-write of a register in device memory
+pub(crate) trait RegisterUpdate<T> {
+	/// Reads the register, modifies the value with `f` and writes it back.
+	fn update_ordered(self, f: impl FnOnce(T) -> T);
+}
+
+impl<T, R: RegisterRead<T> + RegisterWrite<T> + Copy> RegisterUpdate<T> for R {
+	fn update_ordered(self, f: impl FnOnce(T) -> T) {
+		let value = self.read_ordered();
+		self.write_ordered(f(value));
+	}
+}
+
+impl<T: Copy, A: Readable> RegisterRead<T> for VolatilePtr<'_, T, A> {
+	fn read_ordered(self) -> T {
+		let value = self.read();
+		io_read_barrier();
+		value
+	}
+}
+
+impl<T: Copy, A: Writable> RegisterWrite<T> for VolatilePtr<'_, T, A> {
+	fn write_ordered(self, value: T) {
+		io_write_barrier();
+		self.write(value);
+	}
+}
+
+impl<A: Readable> RegisterRead<le64> for WideVolatilePtr<'_, virtio::le32, A> {
+	fn read_ordered(self) -> le64 {
+		let value = self.read();
+		io_read_barrier();
+		value
+	}
+}
+
+impl<A: Writable> RegisterWrite<le64> for WideVolatilePtr<'_, virtio::le32, A> {
+	fn write_ordered(self, value: le64) {
+		io_write_barrier();
+		self.write(value);
+	}
+}
+
+impl<T: OveralignedField<F>, F: Copy, A: Readable> RegisterRead<T>
+	for OveralignedVolatilePtr<'_, T, F, A>
+{
+	fn read_ordered(self) -> T {
+		let value = self.read();
+		io_read_barrier();
+		value
+	}
+}
+
+impl<T: OveralignedField<F>, F: Copy, A: Writable> RegisterWrite<T>
+	for OveralignedVolatilePtr<'_, T, F, A>
+{
+	fn write_ordered(self, value: T) {
+		io_write_barrier();
+		self.write(value);
+	}
+}
+
+/// Writes `value` to the device register at `ptr` after the preceding memory accesses.
+///
+/// # Safety
+///
+/// `ptr` has to point to a mapped device register.
+pub(crate) unsafe fn write_ordered<T>(ptr: *mut T, value: T) {
+	io_write_barrier();
+	unsafe { ptr.write_volatile(value) };
+}
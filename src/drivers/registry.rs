@@ -0,0 +1,382 @@
+//! Registration of device drivers
+//!
+//! Each driver declares the devices it supports by a list of [`DeviceMatch`] criteria.
+//! While scanning the PCI bus, [`probe_devices`] offers each device to the matching drivers.
+//! Without PCI, [`probe_fdt`] offers the nodes of the device tree to the drivers,
+//! which are compatible with them.
+//! The first driver, which accepts the device in its `probe` method, is attached to it.
+//! During attachment, the driver requests the resources of the device (BARs or memory
+//! regions, interrupt line) through its [`DriverContext`], which keeps track of the
+//! claimed resources.
+
+use alloc::vec::Vec;
+#[cfg(feature = "pci")]
+use core::ops::RangeInclusive;
+
+#[cfg(not(feature = "pci"))]
+use fdt::Fdt;
+#[cfg(not(feature = "pci"))]
+use fdt::node::FdtNode;
+#[cfg(feature = "pci")]
+use hermit_sync::InterruptTicketMutex;
+#[cfg(not(feature = "pci"))]
+use memory_addresses::{AddrRange, PhysAddr};
+#[cfg(feature = "pci")]
+use pci_types::{Bar, DeviceId, MAX_BARS, PciAddress, VendorId};
+
+#[cfg(not(feature = "pci"))]
+use crate::arch::kernel::mmio::{MmioDriver, register_driver};
+#[cfg(not(feature = "pci"))]
+use crate::arch::mm::paging;
+#[cfg(feature = "pci")]
+use crate::arch::pci::PciConfigRegion;
+use crate::drivers::InterruptLine;
+use crate::drivers::error::DriverError;
+#[cfg(feature = "pci")]
+use crate::drivers::pci::{PciDevice, PciDriver};
+
+/// Vendor ID of virtio devices
+#[cfg(feature = "pci")]
+const VIRTIO_VENDOR_ID: VendorId = 0x1af4;
+/// Device ID of the first modern (non-transitional) virtio device
+#[cfg(feature = "pci")]
+const VIRTIO_MODERN_DEVICE_ID: DeviceId = 0x1040;
+
+/// Criterion, which decides whether a driver supports a device
+#[derive(Debug, Clone)]
+// Which variants are constructed depends on the enabled drivers.
+#[allow(dead_code)]
+pub(crate) enum DeviceMatch {
+	/// PCI device of `vendor_id` with a device ID in `device_ids`
+	#[cfg(feature = "pci")]
+	Pci {
+		vendor_id: VendorId,
+		device_ids: RangeInclusive<DeviceId>,
+	},
+	/// Modern virtio-pci device of the given type
+	#[cfg(feature = "pci")]
+	Virtio(virtio::Id),
+	/// Device tree node, whose `compatible` property contains the given string
+	#[cfg(not(feature = "pci"))]
+	Compatible(&'static str),
+}
+
+impl DeviceMatch {
+	#[cfg(feature = "pci")]
+	fn matches(&self, device: &PciDevice<PciConfigRegion>) -> bool {
+		let (vendor_id, device_id) = device.id();
+		match self {
+			Self::Pci {
+				vendor_id: vendor,
+				device_ids,
+			} => vendor_id == *vendor && device_ids.contains(&device_id),
+			Self::Virtio(id) => {
+				vendor_id == VIRTIO_VENDOR_ID
+					&& device_id
+						.checked_sub(VIRTIO_MODERN_DEVICE_ID)
+						.and_then(|id| u8::try_from(id).ok())
+						.is_some_and(|device_type| virtio::Id::from(device_type) == *id)
+			}
+		}
+	}
+
+	#[cfg(not(feature = "pci"))]
+	fn matches_node(&self, node: &FdtNode<'_, '_>) -> bool {
+		match self {
+			Self::Compatible(compatible) => node
+				.compatible()
+				.is_some_and(|compatibles| compatibles.all().any(|c| c == *compatible)),
+		}
+	}
+}
+
+/// Access to a device and its resources during the attachment of a driver
+#[cfg(feature = "pci")]
+pub(crate) struct DriverContext<'a> {
+	device: &'a PciDevice<PciConfigRegion>,
+	/// BARs, which are claimed by the driver
+	bars: Vec<u8>,
+	/// Interrupt line, which is claimed by the driver
+	irq: Option<InterruptLine>,
+}
+
+#[cfg(feature = "pci")]
+impl<'a> DriverContext<'a> {
+	fn new(device: &'a PciDevice<PciConfigRegion>) -> Self {
+		Self {
+			device,
+			bars: Vec::new(),
+			irq: None,
+		}
+	}
+
+	pub fn device(&self) -> &'a PciDevice<PciConfigRegion> {
+		self.device
+	}
+
+	/// Claims the BAR in `slot` and returns its description.
+	pub fn request_bar(&mut self, slot: u8) -> Option<Bar> {
+		let bar = self.device.get_bar(slot)?;
+		if !self.bars.contains(&slot) {
+			self.bars.push(slot);
+		}
+		Some(bar)
+	}
+
+	/// Claims all memory BARs of the device, e.g., because the capabilities
+	/// of a virtio device may refer to any of them.
+	pub fn request_memory_bars(&mut self) {
+		for slot in 0..u8::try_from(MAX_BARS).unwrap() {
+			if let Some(Bar::Memory32 { address: 1.., .. } | Bar::Memory64 { address: 1.., .. }) =
+				self.device.get_bar(slot)
+			{
+				self.request_bar(slot);
+			}
+		}
+	}
+
+	/// Claims the interrupt line of the device and labels it with `name`.
+	pub fn request_irq(&mut self, name: &'static str) -> Option<InterruptLine> {
+		let irq = self.device.get_irq()?;
+		crate::arch::interrupts::add_irq_name(irq, name);
+		self.irq = Some(irq);
+		Some(irq)
+	}
+}
+
+/// Attachment of a driver to a device
+#[cfg(feature = "pci")]
+#[derive(Debug, Clone)]
+pub(crate) struct Binding {
+	pub address: PciAddress,
+	/// Name of the driver
+	pub driver: &'static str,
+	/// Position of the driver in the list of the initialized drivers
+	pub index: usize,
+	/// BARs, which are claimed by the driver
+	pub bars: Vec<u8>,
+	/// Interrupt line, which is claimed by the driver
+	pub irq: Option<InterruptLine>,
+}
+
+/// Attachments of all drivers
+#[cfg(feature = "pci")]
+static BINDINGS: InterruptTicketMutex<Vec<Binding>> = InterruptTicketMutex::new(Vec::new());
+
+/// Returns the attachments of all drivers.
+#[cfg(feature = "pci")]
+pub(crate) fn bindings() -> Vec<Binding> {
+	BINDINGS.lock().clone()
+}
+
+/// A driver for PCI devices
+#[cfg(feature = "pci")]
+pub(crate) trait PciDriverEntry: Sync {
+	/// Name of the driver
+	fn name(&self) -> &'static str;
+
+	/// Devices, which are supported by the driver
+	fn matches(&self) -> &'static [DeviceMatch];
+
+	/// Decides whether the driver takes care of a matching device.
+	fn probe(&self, _device: &PciDevice<PciConfigRegion>) -> bool {
+		true
+	}
+
+	/// Initializes the device and requests its resources through `ctx`.
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<PciDriver, DriverError>;
+}
+
+/// All drivers, which are built into the kernel
+#[cfg(feature = "pci")]
+static DRIVERS: &[&dyn PciDriverEntry] = &[
+	#[cfg(all(
+		not(all(target_arch = "x86_64", feature = "rtl8139")),
+		any(feature = "tcp", feature = "udp")
+	))]
+	&crate::drivers::net::virtio::VirtioNetEntry,
+	#[cfg(feature = "vsock")]
+	&crate::drivers::vsock::pci::VirtioVsockEntry,
+	#[cfg(feature = "fuse")]
+	&crate::drivers::fs::virtio_pci::VirtioFsEntry,
+	#[cfg(feature = "pmem")]
+	&crate::drivers::pmem::pci::VirtioPmemEntry,
+	#[cfg(feature = "blk")]
+	&crate::drivers::block::virtio::pci::VirtioBlkEntry,
+	#[cfg(feature = "console")]
+	&crate::drivers::console::pci::VirtioConsoleEntry,
+	#[cfg(feature = "ivshmem")]
+	&crate::drivers::ivshmem::pci::IvshmemEntry,
+	#[cfg(all(
+		target_arch = "x86_64",
+		feature = "rtl8139",
+		any(feature = "tcp", feature = "udp")
+	))]
+	&crate::drivers::net::rtl8139::RTL8139Entry,
+];
+
+/// Returns the names of the drivers, which are built into the kernel.
+#[cfg(feature = "pci")]
+pub(crate) fn driver_names() -> impl Iterator<Item = &'static str> {
+	DRIVERS.iter().map(|entry| entry.name())
+}
+
+/// Attaches the matching drivers to `devices` and returns the initialized drivers.
+#[cfg(feature = "pci")]
+// Without any built-in driver, `PciDriver` is uninhabited.
+#[allow(unreachable_code, unused_mut)]
+pub(crate) fn probe_devices(devices: &[PciDevice<PciConfigRegion>]) -> Vec<PciDriver> {
+	let mut drivers = Vec::new();
+
+	for device in devices {
+		let (vendor_id, device_id) = device.id();
+		let Some(entry) = DRIVERS
+			.iter()
+			.find(|entry| entry.matches().iter().any(|m| m.matches(device)) && entry.probe(device))
+		else {
+			if vendor_id == VIRTIO_VENDOR_ID {
+				warn!("Virtio device {device_id:#x} is not supported, skipping!");
+			}
+			continue;
+		};
+
+		info!(
+			"Attach driver {} to device {vendor_id:#x}:{device_id:#x}",
+			entry.name()
+		);
+		let mut ctx = DriverContext::new(device);
+		match entry.attach(&mut ctx) {
+			Ok(driver) => {
+				debug!(
+					"Driver {} claimed BARs {:?} and interrupt line {:?}",
+					entry.name(),
+					ctx.bars,
+					ctx.irq
+				);
+				BINDINGS.lock().push(Binding {
+					address: device.address(),
+					driver: entry.name(),
+					index: drivers.len(),
+					bars: ctx.bars,
+					irq: ctx.irq,
+				});
+				drivers.push(driver);
+			}
+			Err(err) => error!(
+				"Driver {} could not be attached to device {vendor_id:#x}:{device_id:#x}: {err:?}",
+				entry.name()
+			),
+		}
+	}
+
+	drivers
+}
+
+/// Access to a device tree node and its resources during the attachment of a driver
+#[cfg(not(feature = "pci"))]
+pub(crate) struct DriverContext<'a> {
+	node: FdtNode<'a, 'a>,
+	/// Memory regions of the `reg` property, which are claimed by the driver
+	regions: Vec<usize>,
+	/// Interrupt line, which is claimed by the driver
+	irq: Option<InterruptLine>,
+}
+
+#[cfg(not(feature = "pci"))]
+impl<'a> DriverContext<'a> {
+	fn new(node: FdtNode<'a, 'a>) -> Self {
+		Self {
+			node,
+			regions: Vec::new(),
+			irq: None,
+		}
+	}
+
+	pub fn node(&self) -> FdtNode<'a, 'a> {
+		self.node
+	}
+
+	/// Claims the memory region `index` of the `reg` property, maps it into
+	/// the address space and returns its start address.
+	pub fn request_region(&mut self, index: usize) -> Option<PhysAddr> {
+		let region = self.node.reg()?.nth(index)?;
+		let start = PhysAddr::new(region.starting_address as u64);
+		if !self.regions.contains(&index) {
+			paging::identity_map::<paging::HugePageSize>(
+				AddrRange::new(start, start + region.size? - 1u64).unwrap(),
+			);
+			self.regions.push(index);
+		}
+		Some(start)
+	}
+
+	/// Claims the first interrupt line of the node and labels it with `name`.
+	pub fn request_irq(&mut self, name: &'static str) -> Option<InterruptLine> {
+		let irq = InterruptLine::try_from(self.node.interrupts()?.next()?).ok()?;
+		crate::arch::interrupts::add_irq_name(irq, name);
+		self.irq = Some(irq);
+		Some(irq)
+	}
+}
+
+/// A driver for devices, which are described by the device tree
+#[cfg(not(feature = "pci"))]
+pub(crate) trait FdtDriverEntry: Sync {
+	/// Name of the driver
+	fn name(&self) -> &'static str;
+
+	/// Devices, which are supported by the driver
+	fn matches(&self) -> &'static [DeviceMatch];
+
+	/// Decides whether the driver takes care of a matching device.
+	fn probe(&self, _ctx: &mut DriverContext<'_>) -> bool {
+		true
+	}
+
+	/// Initializes the device and requests its resources through `ctx`.
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<MmioDriver, DriverError>;
+}
+
+/// All drivers for device tree nodes, which are built into the kernel
+#[cfg(not(feature = "pci"))]
+static FDT_DRIVERS: &[&dyn FdtDriverEntry] = &[
+	#[cfg(feature = "gem-net")]
+	&crate::drivers::net::gem::GemEntry,
+	&crate::arch::kernel::mmio::VirtioMmioEntry,
+];
+
+/// Attaches the matching drivers to the nodes of `fdt` and registers the initialized drivers.
+#[cfg(not(feature = "pci"))]
+pub(crate) fn probe_fdt(fdt: &Fdt<'_>) {
+	for node in fdt.all_nodes() {
+		let Some(entry) = FDT_DRIVERS
+			.iter()
+			.find(|entry| entry.matches().iter().any(|m| m.matches_node(&node)))
+		else {
+			continue;
+		};
+
+		let mut ctx = DriverContext::new(node);
+		if !entry.probe(&mut ctx) {
+			continue;
+		}
+
+		info!("Attach driver {} to device {}", entry.name(), node.name);
+		match entry.attach(&mut ctx) {
+			Ok(driver) => {
+				debug!(
+					"Driver {} claimed regions {:?} and interrupt line {:?}",
+					entry.name(),
+					ctx.regions,
+					ctx.irq
+				);
+				register_driver(driver);
+			}
+			Err(err) => error!(
+				"Driver {} could not be attached to device {}: {err}",
+				entry.name(),
+				node.name
+			),
+		}
+	}
+}
@@ -8,6 +8,10 @@ pub mod virtqueue;
 pub mod error {
 	use core::fmt;
 
+	#[cfg(feature = "blk")]
+	pub use crate::drivers::block::virtio::error::VirtioBlkError;
+	#[cfg(feature = "console")]
+	pub use crate::drivers::console::error::VirtioConsoleError;
 	#[cfg(feature = "fuse")]
 	pub use crate::drivers::fs::virtio_fs::error::VirtioFsError;
 	#[cfg(all(
@@ -17,6 +21,8 @@ pub mod error {
 	pub use crate::drivers::net::virtio::error::VirtioNetError;
 	#[cfg(feature = "pci")]
 	use crate::drivers::pci::error::PciError;
+	#[cfg(feature = "pmem")]
+	pub use crate::drivers::pmem::error::VirtioPmemError;
 	#[cfg(feature = "vsock")]
 	pub use crate::drivers::vsock::error::VirtioVsockError;
 
@@ -41,6 +47,12 @@ pub mod error {
 		FsDriver(VirtioFsError),
 		#[cfg(feature = "vsock")]
 		VsockDriver(VirtioVsockError),
+		#[cfg(feature = "pmem")]
+		PmemDriver(VirtioPmemError),
+		#[cfg(feature = "blk")]
+		BlkDriver(VirtioBlkError),
+		#[cfg(feature = "console")]
+		ConsoleDriver(VirtioConsoleError),
 		#[cfg(not(feature = "pci"))]
 		Unknown,
 	}
@@ -101,6 +113,10 @@ pub mod error {
 						f,
 						"Virtio network driver failed, for device {id:x}, device did not acknowledge negotiated feature set!"
 					),
+					VirtioNetError::FailReset(id) => write!(
+						f,
+						"Virtio network driver failed, for device {id:x}, device did not complete its reset!"
+					),
 					VirtioNetError::FeatureRequirementsNotMet(features) => write!(
 						f,
 						"Virtio network driver tried to set feature bit without setting dependency feature. Feat set: {features:?}"
@@ -111,6 +127,23 @@ pub mod error {
 							"Feature set: {driver_features:?} , is incompatible with the device features: {device_features:?}"
 						)
 					}
+					VirtioNetError::NoCtrlQueue => write!(
+						f,
+						"Virtio network driver tried to send a control command without control queue"
+					),
+					VirtioNetError::CtrlCommandFailed => {
+						write!(
+							f,
+							"Virtio network device failed to process a control command"
+						)
+					}
+					VirtioNetError::CtrlNotNegotiated(feature) => write!(
+						f,
+						"Virtio network driver tried to send a control command, which requires the feature {feature:?}"
+					),
+					VirtioNetError::InvalidQueuePairs(pairs) => {
+						write!(f, "Virtio network driver cannot use {pairs} queue pairs")
+					}
 				},
 				#[cfg(feature = "fuse")]
 				VirtioError::FsDriver(fs_error) => match fs_error {
@@ -164,6 +197,10 @@ pub mod error {
 						f,
 						"Virtio socket device driver failed, for device {id:x}, device did not acknowledge negotiated feature set!"
 					),
+					VirtioVsockError::FailReset(id) => write!(
+						f,
+						"Virtio socket device driver failed, for device {id:x}, device did not complete its reset!"
+					),
 					VirtioVsockError::FeatureRequirementsNotMet(features) => write!(
 						f,
 						"Virtio socket driver tried to set feature bit without setting dependency feature. Feat set: {features:?}"
@@ -175,6 +212,102 @@ pub mod error {
 						)
 					}
 				},
+				#[cfg(feature = "pmem")]
+				VirtioError::PmemDriver(pmem_error) => match pmem_error {
+					VirtioPmemError::NoDevCfg(id) => write!(
+						f,
+						"Virtio pmem driver failed, for device {id:x}, due to a missing or malformed device config!"
+					),
+					VirtioPmemError::NoRequestQueue(id) => write!(
+						f,
+						"Virtio pmem driver failed, for device {id:x}, due to a missing request queue!"
+					),
+					VirtioPmemError::EmptyRegion(id) => write!(
+						f,
+						"Virtio pmem driver failed, for device {id:x}, device does not provide any memory!"
+					),
+					VirtioPmemError::FailFeatureNeg(id) => write!(
+						f,
+						"Virtio pmem driver failed, for device {id:x}, device did not acknowledge negotiated feature set!"
+					),
+					VirtioPmemError::FeatureRequirementsNotMet(features) => write!(
+						f,
+						"Virtio pmem driver tried to set feature bit without setting dependency feature. Feat set: {features:?}"
+					),
+					VirtioPmemError::IncompatibleFeatureSets(driver_features, device_features) => {
+						write!(
+							f,
+							"Feature set: {driver_features:?} , is incompatible with the device features: {device_features:?}"
+						)
+					}
+					VirtioPmemError::FlushFailed => {
+						write!(f, "Virtio pmem driver failed to flush the memory region!")
+					}
+				},
+				#[cfg(feature = "blk")]
+				VirtioError::BlkDriver(blk_error) => match blk_error {
+					#[cfg(feature = "pci")]
+					VirtioBlkError::NoDevCfg(id) => write!(
+						f,
+						"Virtio block driver failed, for device {id:x}, due to a missing or malformed device config!"
+					),
+					VirtioBlkError::NoRequestQueue(id) => write!(
+						f,
+						"Virtio block driver failed, for device {id:x}, due to a missing request queue!"
+					),
+					VirtioBlkError::FailFeatureNeg(id) => write!(
+						f,
+						"Virtio block driver failed, for device {id:x}, device did not acknowledge negotiated feature set!"
+					),
+					VirtioBlkError::FeatureRequirementsNotMet(features) => write!(
+						f,
+						"Virtio block driver tried to set feature bit without setting dependency feature. Feat set: {features:?}"
+					),
+					VirtioBlkError::IncompatibleFeatureSets(driver_features, device_features) => {
+						write!(
+							f,
+							"Feature set: {driver_features:?} , is incompatible with the device features: {device_features:?}"
+						)
+					}
+					VirtioBlkError::ReadOnly => {
+						write!(f, "Virtio block driver tried to write a read-only device!")
+					}
+					VirtioBlkError::Unsupported => {
+						write!(f, "Virtio block device does not support the request!")
+					}
+					VirtioBlkError::RequestFailed => {
+						write!(f, "Virtio block device failed to process a request!")
+					}
+				},
+				#[cfg(feature = "console")]
+				VirtioError::ConsoleDriver(console_error) => match console_error {
+					#[cfg(feature = "pci")]
+					VirtioConsoleError::NoDevCfg(id) => write!(
+						f,
+						"Virtio console driver failed, for device {id:x}, due to a missing or malformed device config!"
+					),
+					VirtioConsoleError::NoQueue(id) => write!(
+						f,
+						"Virtio console driver failed, for device {id:x}, due to a missing receive or transmit queue!"
+					),
+					VirtioConsoleError::FailFeatureNeg(id) => write!(
+						f,
+						"Virtio console driver failed, for device {id:x}, device did not acknowledge negotiated feature set!"
+					),
+					VirtioConsoleError::FeatureRequirementsNotMet(features) => write!(
+						f,
+						"Virtio console driver tried to set feature bit without setting dependency feature. Feat set: {features:?}"
+					),
+					VirtioConsoleError::IncompatibleFeatureSets(
+						driver_features,
+						device_features,
+					) => {
+						write!(
+							f,
+							"Feature set: {driver_features:?} , is incompatible with the device features: {device_features:?}"
+						)
+					}
+				},
 			}
 		}
 	}
@@ -15,10 +15,16 @@ use volatile::access::ReadOnly;
 use volatile::{VolatilePtr, VolatileRef};
 
 use crate::drivers::InterruptLine;
+#[cfg(feature = "blk")]
+use crate::drivers::block::virtio::VirtioBlkDriver;
+#[cfg(feature = "console")]
+use crate::drivers::console::VirtioConsoleDriver;
 use crate::drivers::error::DriverError;
 #[cfg(any(feature = "tcp", feature = "udp"))]
 use crate::drivers::net::virtio::VirtioNetDriver;
+use crate::drivers::register::{self, RegisterRead, RegisterUpdate, RegisterWrite};
 use crate::drivers::virtio::error::VirtioError;
+use crate::drivers::virtio::transport::RESET_RETRIES;
 
 pub struct VqCfgHandler<'a> {
 	vq_index: u16,
@@ -31,7 +37,7 @@ impl VqCfgHandler<'_> {
 		self.raw
 			.as_mut_ptr()
 			.queue_sel()
-			.write(self.vq_index.into());
+			.write_ordered(self.vq_index.into());
 	}
 
 	/// Sets the size of a given virtqueue. In case the provided size exceeds the maximum allowed
@@ -42,9 +48,9 @@ impl VqCfgHandler<'_> {
 		self.select_queue();
 		let ptr = self.raw.as_mut_ptr();
 
-		let num_max = ptr.queue_num_max().read().to_ne();
+		let num_max = ptr.queue_num_max().read_ordered().to_ne();
 		let size = size.min(num_max);
-		ptr.queue_num().write(size.into());
+		ptr.queue_num().write_ordered(size.into());
 		size
 	}
 
@@ -54,7 +60,7 @@ impl VqCfgHandler<'_> {
 		self.raw
 			.as_mut_ptr()
 			.queue_desc()
-			.write(addr.as_u64().into());
+			.write_ordered(addr.as_u64().into());
 	}
 
 	pub fn set_drv_ctrl_addr(&mut self, addr: PhysAddr) {
@@ -63,7 +69,7 @@ impl VqCfgHandler<'_> {
 		self.raw
 			.as_mut_ptr()
 			.queue_driver()
-			.write(addr.as_u64().into());
+			.write_ordered(addr.as_u64().into());
 	}
 
 	pub fn set_dev_ctrl_addr(&mut self, addr: PhysAddr) {
@@ -72,13 +78,13 @@ impl VqCfgHandler<'_> {
 		self.raw
 			.as_mut_ptr()
 			.queue_device()
-			.write(addr.as_u64().into());
+			.write_ordered(addr.as_u64().into());
 	}
 
 	pub fn enable_queue(&mut self) {
 		self.select_queue();
 
-		self.raw.as_mut_ptr().queue_ready().write(true);
+		self.raw.as_mut_ptr().queue_ready().write_ordered(true);
 	}
 }
 
@@ -122,27 +128,45 @@ impl ComCfg {
 
 	pub fn get_max_queue_size(&mut self, sel: u16) -> u16 {
 		let ptr = self.com_cfg.as_mut_ptr();
-		ptr.queue_sel().write(sel.into());
-		ptr.queue_num_max().read().to_ne()
+		ptr.queue_sel().write_ordered(sel.into());
+		ptr.queue_num_max().read_ordered().to_ne()
 	}
 
 	pub fn get_queue_ready(&mut self, sel: u16) -> bool {
 		let ptr = self.com_cfg.as_mut_ptr();
-		ptr.queue_sel().write(sel.into());
-		ptr.queue_ready().read()
+		ptr.queue_sel().write_ordered(sel.into());
+		ptr.queue_ready().read_ordered()
 	}
 
 	/// Returns the device status field.
 	pub fn dev_status(&self) -> u8 {
-		self.com_cfg.as_ptr().status().read().bits()
+		self.com_cfg.as_ptr().status().read_ordered().bits()
 	}
 
 	/// Resets the device status field to zero.
-	pub fn reset_dev(&mut self) {
+	///
+	/// The reset has completed, as soon as the device status reads as zero
+	/// (Virtio specification v1.2 - 2.4.2). Returns `false`, if the device
+	/// does not complete the reset within [`RESET_RETRIES`] reads.
+	pub fn reset_dev(&mut self) -> bool {
 		self.com_cfg
 			.as_mut_ptr()
 			.status()
-			.write(DeviceStatus::empty());
+			.write_ordered(DeviceStatus::empty());
+		for _ in 0..RESET_RETRIES {
+			if self.dev_status() == 0 {
+				return true;
+			}
+			core::hint::spin_loop();
+		}
+
+		warn!("Virtio device does not complete its reset");
+		false
+	}
+
+	/// Returns `true`, if the device has signaled DEVICE_NEEDS_RESET.
+	pub fn needs_reset(&self) -> bool {
+		DeviceStatus::from_bits_retain(self.dev_status()).contains(DeviceStatus::DEVICE_NEEDS_RESET)
 	}
 
 	/// Sets the device status field to FAILED.
@@ -152,7 +176,7 @@ impl ComCfg {
 		self.com_cfg
 			.as_mut_ptr()
 			.status()
-			.write(DeviceStatus::FAILED);
+			.write_ordered(DeviceStatus::FAILED);
 	}
 
 	/// Sets the ACKNOWLEDGE bit in the device status field. This indicates, the
@@ -161,7 +185,7 @@ impl ComCfg {
 		self.com_cfg
 			.as_mut_ptr()
 			.status()
-			.update(|status| status | DeviceStatus::ACKNOWLEDGE);
+			.update_ordered(|status| status | DeviceStatus::ACKNOWLEDGE);
 	}
 
 	/// Sets the DRIVER bit in the device status field. This indicates, the OS
@@ -170,7 +194,7 @@ impl ComCfg {
 		self.com_cfg
 			.as_mut_ptr()
 			.status()
-			.update(|status| status | DeviceStatus::DRIVER);
+			.update_ordered(|status| status | DeviceStatus::DRIVER);
 	}
 
 	/// Sets the FEATURES_OK bit in the device status field.
@@ -180,7 +204,7 @@ impl ComCfg {
 		self.com_cfg
 			.as_mut_ptr()
 			.status()
-			.update(|status| status | DeviceStatus::FEATURES_OK);
+			.update_ordered(|status| status | DeviceStatus::FEATURES_OK);
 	}
 
 	/// In order to correctly check feature negotiaten, this function
@@ -193,7 +217,7 @@ impl ComCfg {
 		self.com_cfg
 			.as_ptr()
 			.status()
-			.read()
+			.read_ordered()
 			.contains(DeviceStatus::FEATURES_OK)
 	}
 
@@ -204,7 +228,7 @@ impl ComCfg {
 		self.com_cfg
 			.as_mut_ptr()
 			.status()
-			.update(|status| status | DeviceStatus::DRIVER_OK);
+			.update_ordered(|status| status | DeviceStatus::DRIVER_OK);
 	}
 
 	/// Returns the features offered by the device.
@@ -213,17 +237,17 @@ impl ComCfg {
 
 		// Indicate device to show high 32 bits in device_feature field.
 		// See Virtio specification v1.1. - 4.1.4.3
-		ptr.device_features_sel().write(1.into());
+		ptr.device_features_sel().write_ordered(1.into());
 
 		// read high 32 bits of device features
-		let mut device_features = u64::from(ptr.device_features().read().to_ne()) << 32;
+		let mut device_features = u64::from(ptr.device_features().read_ordered().to_ne()) << 32;
 
 		// Indicate device to show low 32 bits in device_feature field.
 		// See Virtio specification v1.1. - 4.1.4.3
-		ptr.device_features_sel().write(0.into());
+		ptr.device_features_sel().write_ordered(0.into());
 
 		// read low 32 bits of device features
-		device_features |= u64::from(ptr.device_features().read().to_ne());
+		device_features |= u64::from(ptr.device_features().read_ordered().to_ne());
 
 		virtio::F::from_bits_retain(u128::from(device_features).into())
 	}
@@ -238,17 +262,17 @@ impl ComCfg {
 
 		// Indicate to device that driver_features field shows low 32 bits.
 		// See Virtio specification v1.1. - 4.1.4.3
-		ptr.driver_features_sel().write(0.into());
+		ptr.driver_features_sel().write_ordered(0.into());
 
 		// write low 32 bits of device features
-		ptr.driver_features().write(low.into());
+		ptr.driver_features().write_ordered(low.into());
 
 		// Indicate to device that driver_features field shows high 32 bits.
 		// See Virtio specification v1.1. - 4.1.4.3
-		ptr.driver_features_sel().write(1.into());
+		ptr.driver_features_sel().write_ordered(1.into());
 
 		// write high 32 bits of device features
-		ptr.driver_features().write(high.into());
+		ptr.driver_features().write_ordered(high.into());
 	}
 
 	pub fn print_information(&mut self) {
@@ -256,13 +280,17 @@ impl ComCfg {
 
 		infoheader!(" MMIO REGISTER LAYOUT INFORMATION ");
 
-		infoentry!("Device version", "{:#X}", ptr.version().read());
-		infoentry!("Device ID", "{:?}", ptr.device_id().read());
-		infoentry!("Vendor ID", "{:#X}", ptr.vendor_id().read());
+		infoentry!("Device version", "{:#X}", ptr.version().read_ordered());
+		infoentry!("Device ID", "{:?}", ptr.device_id().read_ordered());
+		infoentry!("Vendor ID", "{:#X}", ptr.vendor_id().read_ordered());
 		infoentry!("Device Features", "{:#X}", self.dev_features());
 		let ptr = self.com_cfg.as_ptr();
-		infoentry!("Interrupt status", "{:#X}", ptr.interrupt_status().read());
-		infoentry!("Device status", "{:#X}", ptr.status().read());
+		infoentry!(
+			"Interrupt status",
+			"{:#X}",
+			ptr.interrupt_status().read_ordered()
+		);
+		infoentry!("Device status", "{:#X}", ptr.status().read_ordered());
 
 		infofooter!();
 	}
@@ -325,7 +353,7 @@ impl NotifCtrl {
 		};
 
 		unsafe {
-			self.notif_addr.write_volatile(notification_data);
+			register::write_ordered(self.notif_addr, notification_data);
 		}
 	}
 }
@@ -349,19 +377,23 @@ impl IsrStatus {
 	}
 
 	pub fn is_queue_interrupt(&self) -> InterruptStatus {
-		self.raw.as_ptr().interrupt_status().read()
+		self.raw.as_ptr().interrupt_status().read_ordered()
 	}
 
 	pub fn acknowledge(&mut self) {
 		let ptr = self.raw.as_mut_ptr();
-		let status = ptr.interrupt_status().read();
-		ptr.interrupt_ack().write(status);
+		let status = ptr.interrupt_status().read_ordered();
+		ptr.interrupt_ack().write_ordered(status);
 	}
 }
 
 pub(crate) enum VirtioDriver {
 	#[cfg(any(feature = "tcp", feature = "udp"))]
 	Network(VirtioNetDriver),
+	#[cfg(feature = "blk")]
+	Block(VirtioBlkDriver),
+	#[cfg(feature = "console")]
+	Console(VirtioConsoleDriver),
 }
 
 #[allow(unused_variables)]
@@ -371,7 +403,7 @@ pub(crate) fn init_device(
 ) -> Result<VirtioDriver, DriverError> {
 	let dev_id: u16 = 0;
 
-	if registers.as_ptr().version().read().to_ne() == 0x1 {
+	if registers.as_ptr().version().read_ordered().to_ne() == 0x1 {
 		error!("Legacy interface isn't supported!");
 		return Err(DriverError::InitVirtioDevFail(
 			VirtioError::DevNotSupported(dev_id),
@@ -379,7 +411,7 @@ pub(crate) fn init_device(
 	}
 
 	// Verify the device-ID to find the network card
-	match registers.as_ptr().device_id().read() {
+	match registers.as_ptr().device_id().read_ordered() {
 		#[cfg(any(feature = "tcp", feature = "udp"))]
 		virtio::Id::Net => match VirtioNetDriver::init(dev_id, registers, irq_no) {
 			Ok(virt_net_drv) => {
@@ -395,6 +427,36 @@ pub(crate) fn init_device(
 				Err(DriverError::InitVirtioDevFail(virtio_error))
 			}
 		},
+		#[cfg(feature = "blk")]
+		virtio::Id::Block => match VirtioBlkDriver::init(dev_id, registers, irq_no) {
+			Ok(virt_blk_drv) => {
+				info!("Virtio block driver initialized.");
+
+				crate::arch::interrupts::add_irq_name(irq_no, "virtio");
+				info!("Virtio interrupt handler at line {}", irq_no);
+
+				Ok(VirtioDriver::Block(virt_blk_drv))
+			}
+			Err(virtio_error) => {
+				error!("Virtio block driver could not be initialized with device");
+				Err(DriverError::InitVirtioDevFail(virtio_error))
+			}
+		},
+		#[cfg(feature = "console")]
+		virtio::Id::Console => match VirtioConsoleDriver::init(dev_id, registers, irq_no) {
+			Ok(virt_console_drv) => {
+				info!("Virtio console driver initialized.");
+
+				crate::arch::interrupts::add_irq_name(irq_no, "virtio");
+				info!("Virtio interrupt handler at line {}", irq_no);
+
+				Ok(VirtioDriver::Console(virt_console_drv))
+			}
+			Err(virtio_error) => {
+				error!("Virtio console driver could not be initialized with device");
+				Err(DriverError::InitVirtioDevFail(virtio_error))
+			}
+		},
 		#[cfg(feature = "vsock")]
 		virtio::Id::Vsock => match VirtioVsockDriver::init(dev_id, registers, irq_no) {
 			Ok(virt_net_drv) => {
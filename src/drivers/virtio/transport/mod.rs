@@ -8,3 +8,7 @@
 pub mod mmio;
 #[cfg(feature = "pci")]
 pub mod pci;
+
+/// Number of reads of the device status, after which a reset is considered
+/// as failed
+const RESET_RETRIES: usize = 0x0010_0000;
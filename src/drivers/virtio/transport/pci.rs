@@ -17,21 +17,12 @@ use virtio::{DeviceStatus, le16, le32};
 use volatile::access::ReadOnly;
 use volatile::{VolatilePtr, VolatileRef};
 
-use crate::arch::memory_barrier;
 use crate::arch::pci::PciConfigRegion;
-use crate::drivers::error::DriverError;
-#[cfg(feature = "fuse")]
-use crate::drivers::fs::virtio_fs::VirtioFsDriver;
-#[cfg(all(
-	not(all(target_arch = "x86_64", feature = "rtl8139")),
-	any(feature = "tcp", feature = "udp")
-))]
-use crate::drivers::net::virtio::VirtioNetDriver;
 use crate::drivers::pci::PciDevice;
 use crate::drivers::pci::error::PciError;
+use crate::drivers::register::{self, RegisterRead, RegisterUpdate, RegisterWrite};
 use crate::drivers::virtio::error::VirtioError;
-#[cfg(feature = "vsock")]
-use crate::drivers::vsock::VirtioVsockDriver;
+use crate::drivers::virtio::transport::RESET_RETRIES;
 
 /// Maps a given device specific pci configuration structure and
 /// returns a static reference to it.
@@ -208,7 +199,7 @@ impl VqCfgHandler<'_> {
 		self.raw
 			.as_mut_ptr()
 			.queue_select()
-			.write(self.vq_index.into());
+			.write_ordered(self.vq_index.into());
 	}
 
 	/// Sets the size of a given virtqueue. In case the provided size exceeds the maximum allowed
@@ -219,11 +210,11 @@ impl VqCfgHandler<'_> {
 		self.select_queue();
 		let queue_size = self.raw.as_mut_ptr().queue_size();
 
-		if queue_size.read().to_ne() >= size {
-			queue_size.write(size.into());
+		if queue_size.read_ordered().to_ne() >= size {
+			queue_size.write_ordered(size.into());
 		}
 
-		queue_size.read().to_ne()
+		queue_size.read_ordered().to_ne()
 	}
 
 	pub fn set_ring_addr(&mut self, addr: PhysAddr) {
@@ -231,7 +222,7 @@ impl VqCfgHandler<'_> {
 		self.raw
 			.as_mut_ptr()
 			.queue_desc()
-			.write(addr.as_u64().into());
+			.write_ordered(addr.as_u64().into());
 	}
 
 	pub fn set_drv_ctrl_addr(&mut self, addr: PhysAddr) {
@@ -239,7 +230,7 @@ impl VqCfgHandler<'_> {
 		self.raw
 			.as_mut_ptr()
 			.queue_driver()
-			.write(addr.as_u64().into());
+			.write_ordered(addr.as_u64().into());
 	}
 
 	pub fn set_dev_ctrl_addr(&mut self, addr: PhysAddr) {
@@ -247,17 +238,21 @@ impl VqCfgHandler<'_> {
 		self.raw
 			.as_mut_ptr()
 			.queue_device()
-			.write(addr.as_u64().into());
+			.write_ordered(addr.as_u64().into());
 	}
 
 	pub fn notif_off(&mut self) -> u16 {
 		self.select_queue();
-		self.raw.as_mut_ptr().queue_notify_off().read().to_ne()
+		self.raw
+			.as_mut_ptr()
+			.queue_notify_off()
+			.read_ordered()
+			.to_ne()
 	}
 
 	pub fn enable_queue(&mut self) {
 		self.select_queue();
-		self.raw.as_mut_ptr().queue_enable().write(1.into());
+		self.raw.as_mut_ptr().queue_enable().write_ordered(1.into());
 	}
 }
 
@@ -268,9 +263,18 @@ impl ComCfg {
 	///
 	/// INFO: The queue size is automatically bounded by constant `src::config:VIRTIO_MAX_QUEUE_SIZE`.
 	pub fn select_vq(&mut self, index: u16) -> Option<VqCfgHandler<'_>> {
-		self.com_cfg.as_mut_ptr().queue_select().write(index.into());
+		self.com_cfg
+			.as_mut_ptr()
+			.queue_select()
+			.write_ordered(index.into());
 
-		if self.com_cfg.as_mut_ptr().queue_size().read().to_ne() == 0 {
+		if self
+			.com_cfg
+			.as_mut_ptr()
+			.queue_size()
+			.read_ordered()
+			.to_ne() == 0
+		{
 			None
 		} else {
 			Some(VqCfgHandler {
@@ -286,58 +290,71 @@ impl ComCfg {
 
 	/// Returns the device status field.
 	pub fn dev_status(&self) -> u8 {
-		self.com_cfg.as_ptr().device_status().read().bits()
+		self.com_cfg.as_ptr().device_status().read_ordered().bits()
 	}
 
 	/// Resets the device status field to zero.
-	pub fn reset_dev(&mut self) {
-		memory_barrier();
+	///
+	/// The reset has completed, as soon as the device status reads as zero
+	/// (Virtio specification v1.2 - 2.4.2). Returns `false`, if the device
+	/// does not complete the reset within [`RESET_RETRIES`] reads.
+	pub fn reset_dev(&mut self) -> bool {
 		self.com_cfg
 			.as_mut_ptr()
 			.device_status()
-			.write(DeviceStatus::empty());
+			.write_ordered(DeviceStatus::empty());
+		for _ in 0..RESET_RETRIES {
+			if self.dev_status() == 0 {
+				return true;
+			}
+			core::hint::spin_loop();
+		}
+
+		warn!("Virtio device does not complete its reset");
+		false
+	}
+
+	/// Returns `true`, if the device has signaled DEVICE_NEEDS_RESET.
+	pub fn needs_reset(&self) -> bool {
+		DeviceStatus::from_bits_retain(self.dev_status()).contains(DeviceStatus::DEVICE_NEEDS_RESET)
 	}
 
 	/// Sets the device status field to FAILED.
 	/// A driver MUST NOT initialize and use the device any further after this.
 	/// A driver MAY use the device again after a proper reset of the device.
 	pub fn set_failed(&mut self) {
-		memory_barrier();
 		self.com_cfg
 			.as_mut_ptr()
 			.device_status()
-			.write(DeviceStatus::FAILED);
+			.write_ordered(DeviceStatus::FAILED);
 	}
 
 	/// Sets the ACKNOWLEDGE bit in the device status field. This indicates, the
 	/// OS has notived the device
 	pub fn ack_dev(&mut self) {
-		memory_barrier();
 		self.com_cfg
 			.as_mut_ptr()
 			.device_status()
-			.update(|s| s | DeviceStatus::ACKNOWLEDGE);
+			.update_ordered(|s| s | DeviceStatus::ACKNOWLEDGE);
 	}
 
 	/// Sets the DRIVER bit in the device status field. This indicates, the OS
 	/// know how to run this device.
 	pub fn set_drv(&mut self) {
-		memory_barrier();
 		self.com_cfg
 			.as_mut_ptr()
 			.device_status()
-			.update(|s| s | DeviceStatus::DRIVER);
+			.update_ordered(|s| s | DeviceStatus::DRIVER);
 	}
 
 	/// Sets the FEATURES_OK bit in the device status field.
 	///
 	/// Drivers MUST NOT accept new features after this step.
 	pub fn features_ok(&mut self) {
-		memory_barrier();
 		self.com_cfg
 			.as_mut_ptr()
 			.device_status()
-			.update(|s| s | DeviceStatus::FEATURES_OK);
+			.update_ordered(|s| s | DeviceStatus::FEATURES_OK);
 	}
 
 	/// In order to correctly check feature negotiaten, this function
@@ -347,11 +364,10 @@ impl ComCfg {
 	/// Re-reads device status to ensure the FEATURES_OK bit is still set:
 	/// otherwise, the device does not support our subset of features and the device is unusable.
 	pub fn check_features(&self) -> bool {
-		memory_barrier();
 		self.com_cfg
 			.as_ptr()
 			.device_status()
-			.read()
+			.read_ordered()
 			.contains(DeviceStatus::FEATURES_OK)
 	}
 
@@ -359,11 +375,10 @@ impl ComCfg {
 	///
 	/// After this call, the device is "live"!
 	pub fn drv_ok(&mut self) {
-		memory_barrier();
 		self.com_cfg
 			.as_mut_ptr()
 			.device_status()
-			.update(|s| s | DeviceStatus::DRIVER_OK);
+			.update_ordered(|s| s | DeviceStatus::DRIVER_OK);
 	}
 
 	/// Returns the features offered by the device.
@@ -374,20 +389,17 @@ impl ComCfg {
 
 		// Indicate device to show high 32 bits in device_feature field.
 		// See Virtio specification v1.1. - 4.1.4.3
-		memory_barrier();
-		device_feature_select.write(1.into());
-		memory_barrier();
+		device_feature_select.write_ordered(1.into());
 
 		// read high 32 bits of device features
-		let mut device_features = u64::from(device_feature.read().to_ne()) << 32;
+		let mut device_features = u64::from(device_feature.read_ordered().to_ne()) << 32;
 
 		// Indicate device to show low 32 bits in device_feature field.
 		// See Virtio specification v1.1. - 4.1.4.3
-		device_feature_select.write(0.into());
-		memory_barrier();
+		device_feature_select.write_ordered(0.into());
 
 		// read low 32 bits of device features
-		device_features |= u64::from(device_feature.read().to_ne());
+		device_features |= u64::from(device_feature.read_ordered().to_ne());
 
 		virtio::F::from_bits_retain(u128::from(device_features).into())
 	}
@@ -404,20 +416,17 @@ impl ComCfg {
 
 		// Indicate to device that driver_features field shows low 32 bits.
 		// See Virtio specification v1.1. - 4.1.4.3
-		memory_barrier();
-		driver_feature_select.write(0.into());
-		memory_barrier();
+		driver_feature_select.write_ordered(0.into());
 
 		// write low 32 bits of device features
-		driver_feature.write(low.into());
+		driver_feature.write_ordered(low.into());
 
 		// Indicate to device that driver_features field shows high 32 bits.
 		// See Virtio specification v1.1. - 4.1.4.3
-		driver_feature_select.write(1.into());
-		memory_barrier();
+		driver_feature_select.write_ordered(1.into());
 
 		// write high 32 bits of device features
-		driver_feature.write(high.into());
+		driver_feature.write_ordered(high.into());
 	}
 }
 
@@ -503,13 +512,11 @@ impl NotifCtrl {
 
 		if self.f_notif_data {
 			unsafe {
-				self.notif_addr.write_volatile(data.into_bits());
+				register::write_ordered(self.notif_addr, data.into_bits());
 			}
 		} else {
 			unsafe {
-				self.notif_addr
-					.cast::<le16>()
-					.write_volatile(data.vqn().into());
+				register::write_ordered(self.notif_addr.cast::<le16>(), data.vqn().into());
 			}
 		}
 	}
@@ -538,7 +545,7 @@ impl IsrStatus {
 	}
 
 	pub fn is_queue_interrupt(&self) -> IsrStatusRaw {
-		self.isr_stat.as_ptr().read()
+		self.isr_stat.as_ptr().read_ordered()
 	}
 
 	pub fn acknowledge(&mut self) {
@@ -792,108 +799,3 @@ pub(crate) fn map_caps(device: &PciDevice<PciConfigRegion>) -> Result<UniCapsCol
 		dev_cfg_list,
 	})
 }
-
-/// Checks existing drivers for support of given device. Upon match, provides
-/// driver with a [`PciDevice<PciConfigRegion>`] reference, allowing access to the capabilities
-/// list of the given device through [map_caps].
-pub(crate) fn init_device(
-	device: &PciDevice<PciConfigRegion>,
-) -> Result<VirtioDriver, DriverError> {
-	let device_id = device.device_id();
-
-	if device_id < 0x1040 {
-		warn!(
-			"Legacy/transitional Virtio device, with id: {:#x} is NOT supported, skipping!",
-			device_id
-		);
-
-		// Return Driver error inidacting device is not supported
-		return Err(DriverError::InitVirtioDevFail(
-			VirtioError::DevNotSupported(device_id),
-		));
-	}
-
-	let id = virtio::Id::from(u8::try_from(device_id - 0x1040).unwrap());
-
-	match id {
-		#[cfg(all(
-			not(all(target_arch = "x86_64", feature = "rtl8139")),
-			any(feature = "tcp", feature = "udp")
-		))]
-		virtio::Id::Net => match VirtioNetDriver::init(device) {
-			Ok(virt_net_drv) => {
-				info!("Virtio network driver initialized.");
-
-				let irq = device.get_irq().unwrap();
-				crate::arch::interrupts::add_irq_name(irq, "virtio");
-				info!("Virtio interrupt handler at line {}", irq);
-
-				Ok(VirtioDriver::Network(virt_net_drv))
-			}
-			Err(virtio_error) => {
-				error!(
-					"Virtio networkd driver could not be initialized with device: {:x}",
-					device_id
-				);
-				Err(DriverError::InitVirtioDevFail(virtio_error))
-			}
-		},
-		#[cfg(feature = "vsock")]
-		virtio::Id::Vsock => match VirtioVsockDriver::init(device) {
-			Ok(virt_sock_drv) => {
-				info!("Virtio sock driver initialized.");
-
-				let irq = device.get_irq().unwrap();
-				crate::arch::interrupts::add_irq_name(irq, "virtio");
-				info!("Virtio interrupt handler at line {}", irq);
-
-				Ok(VirtioDriver::Vsock(virt_sock_drv))
-			}
-			Err(virtio_error) => {
-				error!(
-					"Virtio sock driver could not be initialized with device: {:x}",
-					device_id
-				);
-				Err(DriverError::InitVirtioDevFail(virtio_error))
-			}
-		},
-		#[cfg(feature = "fuse")]
-		virtio::Id::Fs => {
-			// TODO: check subclass
-			// TODO: proper error handling on driver creation fail
-			match VirtioFsDriver::init(device) {
-				Ok(virt_fs_drv) => {
-					info!("Virtio filesystem driver initialized.");
-					Ok(VirtioDriver::FileSystem(virt_fs_drv))
-				}
-				Err(virtio_error) => {
-					error!(
-						"Virtio filesystem driver could not be initialized with device: {:x}",
-						device_id
-					);
-					Err(DriverError::InitVirtioDevFail(virtio_error))
-				}
-			}
-		}
-		id => {
-			warn!("Virtio device {id:?} is not supported, skipping!");
-
-			// Return Driver error inidacting device is not supported
-			Err(DriverError::InitVirtioDevFail(
-				VirtioError::DevNotSupported(device_id),
-			))
-		}
-	}
-}
-
-pub(crate) enum VirtioDriver {
-	#[cfg(all(
-		not(all(target_arch = "x86_64", feature = "rtl8139")),
-		any(feature = "tcp", feature = "udp")
-	))]
-	Network(VirtioNetDriver),
-	#[cfg(feature = "vsock")]
-	Vsock(VirtioVsockDriver),
-	#[cfg(feature = "fuse")]
-	FileSystem(VirtioFsDriver),
-}
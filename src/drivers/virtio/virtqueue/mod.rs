@@ -19,6 +19,7 @@ use core::any::Any;
 use core::mem::MaybeUninit;
 use core::{mem, ptr};
 
+use align_address::Align;
 use memory_addresses::VirtAddr;
 use virtio::{le32, le64, pvirtq, virtq};
 
@@ -27,9 +28,28 @@ use self::error::VirtqError;
 use super::transport::mmio::{ComCfg, NotifCfg};
 #[cfg(feature = "pci")]
 use super::transport::pci::{ComCfg, NotifCfg};
-use crate::arch::mm::paging;
+use crate::arch::mm::paging::{self, BasePageSize, PageSize};
+use crate::metrics::Counter;
 use crate::mm::device_alloc::DeviceAlloc;
 
+/// Number of used buffers, which have been dropped, because the device returned them malformed
+static MALFORMED_USED: Counter = Counter::new(
+	"virtqueue_malformed_used_total",
+	"Used buffers, which have been dropped, because the device returned them malformed",
+);
+
+/// Counts a malformed used buffer and returns the error, which breaks the queue.
+///
+/// After a malformed used buffer, the state of the queue cannot be trusted
+/// anymore, so the queue refuses all further buffers and the driver is
+/// expected to set the device status to FAILED.
+fn malformed_used(reason: &str) -> VirtqError {
+	MALFORMED_USED.inc();
+	let count = MALFORMED_USED.get();
+	error!("Virtqueue: device returned a malformed used buffer ({reason}), {count} dropped so far");
+	VirtqError::MalformedUsed
+}
+
 /// A u16 newtype. If instantiated via ``VqIndex::from(T)``, the newtype is ensured to be
 /// smaller-equal to `min(u16::MAX , T::MAX)`.
 ///
@@ -133,9 +153,16 @@ pub trait Virtq: Send {
 			// we just made available. However, this shouldn't be a problem as the queue this
 			// function is called on makes use of this blocking dispatch function exclusively
 			// and thus dispatches cannot be interleaved.
-			if let Ok(buffer_tkn) = self.try_recv() {
-				result = buffer_tkn;
-				break;
+			match self.try_recv() {
+				Ok(buffer_tkn) => {
+					result = buffer_tkn;
+					break;
+				}
+				Err(VirtqError::NoNewUsed) => {}
+				Err(err) => {
+					self.enable_notifs();
+					return Err(err);
+				}
 			}
 		}
 
@@ -347,6 +374,15 @@ impl<Descriptor> TransferToken<Descriptor> {
 pub enum BufferElem {
 	Sized(Box<dyn Any + Send, DeviceAlloc>),
 	Vector(Vec<u8, DeviceAlloc>),
+	/// Physically contiguous memory, which is not owned by the buffer.
+	///
+	/// The owner has to guarantee that the memory stays valid and unmodified until the
+	/// transfer is finished. This variant is only intended for device-readable buffers,
+	/// e.g., a payload, which is sent without copying it (see [`BufferElem::borrowed`]).
+	Borrowed {
+		addr: VirtAddr,
+		len: u32,
+	},
 }
 
 impl BufferElem {
@@ -360,6 +396,7 @@ impl BufferElem {
 		match self {
 			BufferElem::Sized(sized) => mem::size_of_val(sized.as_ref()),
 			BufferElem::Vector(vec) => vec.len(),
+			BufferElem::Borrowed { len, .. } => *len as usize,
 		}
 		.try_into()
 		.unwrap()
@@ -369,6 +406,7 @@ impl BufferElem {
 		match self {
 			BufferElem::Sized(sized) => mem::size_of_val(sized.as_ref()),
 			BufferElem::Vector(vec) => vec.capacity(),
+			BufferElem::Borrowed { len, .. } => *len as usize,
 		}
 		.try_into()
 		.unwrap()
@@ -378,8 +416,53 @@ impl BufferElem {
 		match self {
 			BufferElem::Sized(sized) => ptr::from_ref(sized.as_ref()).cast::<u8>(),
 			BufferElem::Vector(vec) => vec.as_ptr(),
+			BufferElem::Borrowed { addr, .. } => addr.as_ptr(),
 		}
 	}
+
+	/// Splits `slice` into physically contiguous [`BufferElem::Borrowed`] elements,
+	/// which allows the device to access the memory without copying it.
+	///
+	/// Returns `None` if a part of `slice` is not mapped or if more than
+	/// `max_elems` elements are required.
+	///
+	/// # Safety
+	///
+	/// The memory of `slice` must stay valid and must not be modified
+	/// until the transfer of the returned elements is finished.
+	pub unsafe fn borrowed(slice: *const [u8], max_elems: usize) -> Option<Vec<BufferElem>> {
+		let start = VirtAddr::from_ptr(slice.cast::<u8>());
+		let end = start + slice.len() as u64;
+		let mut elems = Vec::new();
+		let mut next_phys_addr = None;
+		let mut addr = start;
+
+		while addr < end {
+			let chunk_end = end.min((addr + 1u64).align_up(BasePageSize::SIZE));
+			let chunk_len = u32::try_from(chunk_end - addr).ok()?;
+			let phys_addr = paging::virtual_to_physical(addr)?;
+
+			match elems.last_mut() {
+				Some(BufferElem::Borrowed { len, .. }) if next_phys_addr == Some(phys_addr) => {
+					*len = len.checked_add(chunk_len)?;
+				}
+				_ => {
+					if elems.len() == max_elems {
+						return None;
+					}
+					elems.push(BufferElem::Borrowed {
+						addr,
+						len: chunk_len,
+					});
+				}
+			}
+
+			next_phys_addr = Some(phys_addr + u64::from(chunk_len));
+			addr = chunk_end;
+		}
+
+		Some(elems)
+	}
 }
 
 /// The struct represents buffers which are ready to be written or to be send.
@@ -419,6 +502,11 @@ pub(crate) struct UsedDeviceWritableBuffer {
 }
 
 impl UsedDeviceWritableBuffer {
+	/// Returns the first remaining element without removing it.
+	pub fn front(&self) -> Option<&BufferElem> {
+		self.elems.front()
+	}
+
 	pub fn pop_front_downcast<T>(&mut self) -> Option<Box<T, DeviceAlloc>>
 	where
 		T: Any,
@@ -468,14 +556,29 @@ pub(crate) struct UsedBufferToken {
 }
 
 impl UsedBufferToken {
-	fn from_avail_buffer_token(tkn: AvailBufferToken, written_len: u32) -> Self {
-		Self {
+	/// Creates the used buffer of `tkn`, of which the device has written `written_len` bytes.
+	///
+	/// Fails, if the device claims to have written more than the device-writable buffers hold.
+	fn from_avail_buffer_token(
+		tkn: AvailBufferToken,
+		written_len: u32,
+	) -> Result<Self, VirtqError> {
+		let capacity = tkn
+			.recv_buff
+			.iter()
+			.map(|elem| u64::from(elem.capacity()))
+			.sum();
+		if u64::from(written_len) > capacity {
+			return Err(malformed_used("written length exceeds the buffer"));
+		}
+
+		Ok(Self {
 			send_buff: tkn.send_buff,
 			used_recv_buff: UsedDeviceWritableBuffer {
 				elems: tkn.recv_buff.into(),
 				remaining_written_len: written_len,
 			},
-		}
+		})
 	}
 }
 
@@ -602,6 +705,9 @@ pub mod error {
 		AllocationError,
 		IncompleteWrite,
 		NoNewUsed,
+		/// The device returned a used buffer with an invalid id or length.
+		/// The queue does not return any further buffers.
+		MalformedUsed,
 	}
 
 	impl core::fmt::Debug for VirtqError {
@@ -634,6 +740,9 @@ pub mod error {
 				VirtqError::NoNewUsed => {
 					write!(f, "The queue does not contain any new used buffers.")
 				}
+				VirtqError::MalformedUsed => {
+					write!(f, "The device returned a malformed used buffer.")
+				}
 			}
 		}
 	}
@@ -25,7 +25,7 @@ use super::super::transport::pci::{ComCfg, NotifCfg, NotifCtrl};
 use super::error::VirtqError;
 use super::{
 	AvailBufferToken, BufferType, MemDescrId, MemPool, TransferToken, UsedBufferToken, Virtq,
-	VirtqPrivate, VqIndex, VqSize,
+	VirtqPrivate, VqIndex, VqSize, malformed_used,
 };
 use crate::arch::mm::paging;
 use crate::arch::mm::paging::{BasePageSize, PageSize};
@@ -104,6 +104,8 @@ struct DescriptorRing {
 	/// Memory pool controls the amount of "free floating" descriptors
 	/// See [MemPool] docs for detail.
 	mem_pool: MemPool,
+	/// The device has returned a malformed used buffer.
+	is_broken: bool,
 }
 
 impl DescriptorRing {
@@ -125,18 +127,24 @@ impl DescriptorRing {
 			drv_wc: WrapCount::new(),
 			dev_wc: WrapCount::new(),
 			mem_pool: MemPool::new(size),
+			is_broken: false,
 		}
 	}
 
 	/// Polls poll index and sets the state of any finished TransferTokens.
 	fn try_recv(&mut self) -> Result<UsedBufferToken, VirtqError> {
-		let mut ctrl = self.get_read_ctrler();
+		if self.is_broken {
+			return Err(VirtqError::MalformedUsed);
+		}
 
-		ctrl.poll_next()
-			.map(|(tkn, written_len)| {
-				UsedBufferToken::from_avail_buffer_token(tkn.buff_tkn, written_len)
-			})
-			.ok_or(VirtqError::NoNewUsed)
+		let mut ctrl = self.get_read_ctrler();
+		let result = ctrl.poll_next().and_then(|(tkn, written_len)| {
+			UsedBufferToken::from_avail_buffer_token(tkn.buff_tkn, written_len)
+		});
+		if matches!(result, Err(VirtqError::MalformedUsed)) {
+			self.is_broken = true;
+		}
+		result
 	}
 
 	fn push_batch(
@@ -302,16 +310,17 @@ struct ReadCtrl<'a> {
 impl ReadCtrl<'_> {
 	/// Polls the ring for a new finished buffer. If buffer is marked as finished, takes care of
 	/// updating the queue and returns the respective TransferToken.
-	fn poll_next(&mut self) -> Option<(Box<TransferToken<pvirtq::Desc>>, u32)> {
+	fn poll_next(&mut self) -> Result<(Box<TransferToken<pvirtq::Desc>>, u32), VirtqError> {
 		// Check if descriptor has been marked used.
 		let desc = &self.desc_ring.ring[usize::from(self.position)];
 		if self.desc_ring.is_marked_used(desc.flags) {
 			let buff_id = desc.id.to_ne();
-			let tkn = self.desc_ring.tkn_ref_ring[usize::from(buff_id)]
-				.take()
-				.expect(
-					"The buff_id is incorrect or the reference to the TransferToken was misplaced.",
-				);
+			let tkn = self
+				.desc_ring
+				.tkn_ref_ring
+				.get_mut(usize::from(buff_id))
+				.and_then(Option::take)
+				.ok_or_else(|| malformed_used("unknown buffer id"))?;
 
 			// Retrieve if any has been written to the queue. If this is the case, we calculate the overall length
 			// This is necessary in order to provide the drivers with the correct access, to usable data.
@@ -339,9 +348,9 @@ impl ReadCtrl<'_> {
 			}
 			self.desc_ring.mem_pool.ret_id(MemDescrId(buff_id));
 
-			Some((tkn, write_len))
+			Ok((tkn, write_len))
 		} else {
-			None
+			Err(VirtqError::NoNewUsed)
 		}
 	}
 
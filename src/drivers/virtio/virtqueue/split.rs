@@ -21,7 +21,7 @@ use super::super::transport::pci::{ComCfg, NotifCfg, NotifCtrl};
 use super::error::VirtqError;
 use super::{
 	AvailBufferToken, BufferType, MemPool, TransferToken, UsedBufferToken, Virtq, VirtqPrivate,
-	VqIndex, VqSize,
+	VqIndex, VqSize, malformed_used,
 };
 use crate::arch::memory_barrier;
 use crate::arch::mm::paging;
@@ -31,6 +31,8 @@ struct DescrRing {
 	read_idx: u16,
 	token_ring: Box<[Option<Box<TransferToken<virtq::Desc>>>]>,
 	mem_pool: MemPool,
+	/// The device has returned a malformed used buffer.
+	is_broken: bool,
 
 	/// Descriptor Tables
 	///
@@ -100,37 +102,49 @@ impl DescrRing {
 	}
 
 	fn try_recv(&mut self) -> Result<UsedBufferToken, VirtqError> {
+		if self.is_broken {
+			return Err(VirtqError::MalformedUsed);
+		}
 		if self.read_idx == self.used_ring().idx.to_ne() {
 			return Err(VirtqError::NoNewUsed);
 		}
+		let result = self.take_used();
+		if result.is_err() {
+			self.is_broken = true;
+		}
+		result
+	}
+
+	/// Takes the next used buffer, whose id and length have been written by the device.
+	fn take_used(&mut self) -> Result<UsedBufferToken, VirtqError> {
 		let cur_ring_index = self.read_idx as usize % self.token_ring.len();
 		let used_elem = self.used_ring().ring()[cur_ring_index];
 
-		let tkn = self.token_ring[used_elem.id.to_ne() as usize]
-			.take()
-			.expect(
-				"The buff_id is incorrect or the reference to the TransferToken was misplaced.",
-			);
+		let tkn = usize::try_from(used_elem.id.to_ne())
+			.ok()
+			.and_then(|id| self.token_ring.get_mut(id))
+			.and_then(Option::take)
+			.ok_or_else(|| malformed_used("unknown buffer id"))?;
 
 		// We return the indices of the now freed ring slots back to `mem_pool.`
+		// The chain cannot be longer than the descriptor table, unless it has been corrupted.
 		let mut id_ret_idx = u16::try_from(used_elem.id.to_ne()).unwrap();
-		loop {
+		for _ in 0..self.token_ring.len() {
 			self.mem_pool.ret_id(super::MemDescrId(id_ret_idx));
 			let cur_chain_elem =
 				unsafe { self.descr_table_mut()[usize::from(id_ret_idx)].assume_init() };
-			if cur_chain_elem.flags.contains(virtq::DescF::NEXT) {
-				id_ret_idx = cur_chain_elem.next.to_ne();
-			} else {
+			if !cur_chain_elem.flags.contains(virtq::DescF::NEXT) {
 				break;
 			}
+			id_ret_idx = cur_chain_elem.next.to_ne();
+			if usize::from(id_ret_idx) >= self.token_ring.len() {
+				return Err(malformed_used("invalid descriptor chain"));
+			}
 		}
 
 		memory_barrier();
 		self.read_idx = self.read_idx.wrapping_add(1);
-		Ok(UsedBufferToken::from_avail_buffer_token(
-			tkn.buff_tkn,
-			used_elem.len.to_ne(),
-		))
+		UsedBufferToken::from_avail_buffer_token(tkn.buff_tkn, used_elem.len.to_ne())
 	}
 
 	fn drv_enable_notif(&mut self) {
@@ -280,6 +294,7 @@ impl Virtq for SplitVq {
 				.collect::<Vec<_>>()
 				.into_boxed_slice(),
 			mem_pool: MemPool::new(size),
+			is_broken: false,
 
 			descr_table_cell,
 			avail_ring_cell,
@@ -17,6 +17,7 @@ use crate::drivers::Driver;
 use crate::drivers::virtio::error::VirtioVsockError;
 #[cfg(feature = "pci")]
 use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
+use crate::drivers::virtio::virtqueue::error::VirtqError;
 use crate::drivers::virtio::virtqueue::split::SplitVq;
 use crate::drivers::virtio::virtqueue::{
 	AvailBufferToken, BufferElem, BufferType, UsedBufferToken, Virtq, VqIndex, VqSize,
@@ -86,23 +87,32 @@ impl RxQueue {
 		}
 	}
 
-	fn get_next(&mut self) -> Option<UsedBufferToken> {
-		self.vq.as_mut().unwrap().try_recv().ok()
+	fn get_next(&mut self) -> Result<UsedBufferToken, VirtqError> {
+		self.vq.as_mut().unwrap().try_recv()
 	}
 
-	pub fn process_packet<F>(&mut self, mut f: F)
+	/// Passes all received packets to `f`.
+	///
+	/// Fails, if the device has returned a malformed buffer.
+	pub fn process_packet<F>(&mut self, mut f: F) -> Result<(), VirtqError>
 	where
 		F: FnMut(&Hdr, &[u8]),
 	{
-		while let Some(mut buffer_tkn) = self.get_next() {
-			let header = buffer_tkn
-				.used_recv_buff
-				.pop_front_downcast::<Hdr>()
-				.unwrap();
-			let packet = buffer_tkn.used_recv_buff.pop_front_vec().unwrap();
+		loop {
+			let mut buffer_tkn = match self.get_next() {
+				Ok(buffer_tkn) => buffer_tkn,
+				Err(VirtqError::NoNewUsed) => return Ok(()),
+				Err(err) => return Err(err),
+			};
+			let header = buffer_tkn.used_recv_buff.pop_front_downcast::<Hdr>();
+			let packet = buffer_tkn.used_recv_buff.pop_front_vec();
 
 			if let Some(ref mut vq) = self.vq {
-				f(&header, &packet[..]);
+				if let (Some(header), Some(packet)) = (header, packet) {
+					f(&header, &packet[..]);
+				} else {
+					warn!("Drop truncated vsock packet");
+				}
 
 				fill_queue(vq.as_mut(), 1, self.packet_size);
 			} else {
@@ -160,7 +170,7 @@ impl TxQueue {
 		// what we are about to add
 		self.poll();
 		if let Some(ref mut vq) = self.vq {
-			assert!(len < usize::try_from(self.packet_length).unwrap());
+			assert!(len <= usize::try_from(self.packet_length).unwrap());
 			let mut packet = Vec::with_capacity_in(len, DeviceAlloc);
 			let result = unsafe {
 				let result = f(MaybeUninit::slice_assume_init_mut(
@@ -236,6 +246,8 @@ pub(crate) struct VirtioVsockDriver {
 	pub(super) event_vq: EventQueue,
 	pub(super) recv_vq: RxQueue,
 	pub(super) send_vq: TxQueue,
+	/// The device has signaled DEVICE_NEEDS_RESET.
+	pub(super) needs_reset: bool,
 }
 
 impl Driver for VirtioVsockDriver {
@@ -246,6 +258,11 @@ impl Driver for VirtioVsockDriver {
 	fn get_name(&self) -> &'static str {
 		"virtio"
 	}
+
+	fn get_queue_count(&self) -> usize {
+		// event, receive and transmit queue
+		3
+	}
 }
 
 impl VirtioVsockDriver {
@@ -280,20 +297,52 @@ impl VirtioVsockDriver {
 		let status = self.isr_stat.is_queue_interrupt();
 
 		#[cfg(not(feature = "pci"))]
-		if status.contains(virtio::mmio::InterruptStatus::CONFIGURATION_CHANGE_NOTIFICATION) {
-			info!("Configuration changes are not possible! Aborting");
-			todo!("Implement possibility to change config on the fly...")
-		}
-
+		let is_cfg_change =
+			status.contains(virtio::mmio::InterruptStatus::CONFIGURATION_CHANGE_NOTIFICATION);
 		#[cfg(feature = "pci")]
-		if status.contains(virtio::pci::IsrStatus::DEVICE_CONFIGURATION_INTERRUPT) {
-			info!("Configuration changes are not possible! Aborting");
-			todo!("Implement possibility to change config on the fly...")
+		let is_cfg_change = status.contains(virtio::pci::IsrStatus::DEVICE_CONFIGURATION_INTERRUPT);
+
+		// The device is reset outside of the interrupt handler, when the next packets are processed.
+		if is_cfg_change {
+			if self.com_cfg.needs_reset() {
+				warn!(
+					"Virtio socket device {:x} needs a reset",
+					self.dev_cfg.dev_id
+				);
+				self.needs_reset = true;
+			} else {
+				debug!(
+					"Configuration of virtio socket device {:x} has changed",
+					self.dev_cfg.dev_id
+				);
+			}
 		}
 
 		self.isr_stat.acknowledge();
 	}
 
+	/// Resets the device after DEVICE_NEEDS_RESET and initializes it again.
+	///
+	/// The device loses the state of all connections, so that the local
+	/// connections are shut down and pending connection requests are dropped.
+	fn recover(&mut self) {
+		self.needs_reset = false;
+		crate::executor::vsock::VSOCK_MAP.lock().reset();
+		match self.init_dev() {
+			Ok(()) => info!(
+				"Virtio socket device {:x} has been reset",
+				self.dev_cfg.dev_id
+			),
+			Err(err) => {
+				error!(
+					"Unable to reset virtio socket device {:x}: {err:?}",
+					self.dev_cfg.dev_id
+				);
+				self.com_cfg.set_failed();
+			}
+		}
+	}
+
 	/// Negotiates a subset of features, understood and wanted by both the OS
 	/// and the device.
 	fn negotiate_features(
@@ -327,7 +376,9 @@ impl VirtioVsockDriver {
 	///                      and v1.1. - 5.10.6
 	pub fn init_dev(&mut self) -> Result<(), VirtioVsockError> {
 		// Reset
-		self.com_cfg.reset_dev();
+		if !self.com_cfg.reset_dev() {
+			return Err(VirtioVsockError::FailReset(self.dev_cfg.dev_id));
+		}
 
 		// Indicate device, that OS noticed it
 		self.com_cfg.ack_dev();
@@ -406,7 +457,14 @@ impl VirtioVsockDriver {
 	where
 		F: FnMut(&Hdr, &[u8]),
 	{
-		self.recv_vq.process_packet(f);
+		if self.needs_reset {
+			self.recover();
+		}
+
+		if let Err(err) = self.recv_vq.process_packet(f) {
+			error!("Receive queue of the vsock device failed: {err:?}");
+			self.com_cfg.set_failed();
+		}
 	}
 
 	/// Provides a slice to copy the packet and transfer the packet
@@ -431,6 +489,8 @@ pub mod error {
 		NoIsrCfg(u16),
 		NoNotifCfg(u16),
 		FailFeatureNeg(u16),
+		/// The device has not completed its reset.
+		FailReset(u16),
 		/// Set of features does not adhere to the requirements of features
 		/// indicated by the specification
 		FeatureRequirementsNotMet(virtio::vsock::F),
@@ -1,5 +1,9 @@
+use hermit_sync::InterruptTicketMutex;
+
 use crate::arch::pci::PciConfigRegion;
-use crate::drivers::pci::PciDevice;
+use crate::drivers::error::DriverError;
+use crate::drivers::pci::{PciDevice, PciDriver};
+use crate::drivers::registry::{DeviceMatch, DriverContext, PciDriverEntry};
 use crate::drivers::virtio::error::{self, VirtioError};
 use crate::drivers::virtio::transport::pci;
 use crate::drivers::virtio::transport::pci::{PciCap, UniCapsColl};
@@ -57,6 +61,7 @@ impl VirtioVsockDriver {
 			event_vq: EventQueue::new(),
 			recv_vq: RxQueue::new(),
 			send_vq: TxQueue::new(),
+			needs_reset: false,
 		})
 	}
 
@@ -96,3 +101,29 @@ impl VirtioVsockDriver {
 		}
 	}
 }
+
+/// Driver entry of virtio socket devices
+pub(crate) struct VirtioVsockEntry;
+
+impl PciDriverEntry for VirtioVsockEntry {
+	fn name(&self) -> &'static str {
+		"virtio-vsock"
+	}
+
+	fn matches(&self) -> &'static [DeviceMatch] {
+		const MATCHES: &[DeviceMatch] = &[DeviceMatch::Virtio(virtio::Id::Vsock)];
+		MATCHES
+	}
+
+	fn attach(&self, ctx: &mut DriverContext<'_>) -> Result<PciDriver, DriverError> {
+		ctx.request_memory_bars();
+		let drv = VirtioVsockDriver::init(ctx.device())?;
+		info!("Virtio sock driver initialized.");
+
+		if let Some(irq) = ctx.request_irq("virtio") {
+			info!("Virtio interrupt handler at line {irq}");
+		}
+
+		Ok(PciDriver::VirtioVsock(InterruptTicketMutex::new(drv)))
+	}
+}
@@ -2,6 +2,7 @@
 
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::ops::Range;
 use core::{ptr, str};
 
 use ahash::RandomState;
@@ -27,6 +28,7 @@ pub fn set_boot_info(raw_boot_info: RawBootInfo) {
 static CLI: OnceCell<Cli> = OnceCell::new();
 
 pub fn init() {
+	crate::fdt_overlay::apply();
 	CLI.set(Cli::default()).unwrap();
 }
 
@@ -40,6 +42,29 @@ struct Cli {
 	args: Vec<String>,
 	#[allow(dead_code)]
 	mmio: Vec<String>,
+	/// Options of virtio-fs mounts, indexed by tag
+	#[allow(dead_code)]
+	mount_options: Vec<(String, String)>,
+	/// Static neighbors as `<address>,<hardware address>`
+	#[allow(dead_code)]
+	neighbors: Vec<String>,
+	/// Run the diagnostics before starting the application
+	selftest: bool,
+	/// Log the entry and exit of all system calls
+	#[allow(dead_code)]
+	syscall_log: bool,
+	/// System calls, which may be called by the application, if restricted
+	#[allow(dead_code)]
+	syscall_allow: Option<Vec<String>>,
+	/// System calls, which must not be called by the application
+	#[allow(dead_code)]
+	syscall_deny: Vec<String>,
+	/// Options of the mitigations against speculative execution attacks
+	#[allow(dead_code)]
+	mitigations: Vec<String>,
+	/// Whether the network interface is configured by DHCP
+	#[allow(dead_code)]
+	dhcp: Option<bool>,
 }
 
 /// Whether Hermit is running under the "uhyve" hypervisor.
@@ -51,9 +76,21 @@ pub fn is_uefi() -> bool {
 	fdt().is_some_and(|fdt| fdt.root().compatible().first() == "hermit,uefi")
 }
 
+/// Returns the address of the device tree.
+///
+/// After [`init`], this is the device tree, to which the overlay of the boot loader has been applied.
+pub fn device_tree() -> Option<usize> {
+	crate::fdt_overlay::device_tree().or_else(|| {
+		boot_info()
+			.hardware_info
+			.device_tree
+			.map(|fdt| fdt.get().try_into().unwrap())
+	})
+}
+
 pub fn fdt() -> Option<Fdt<'static>> {
-	boot_info().hardware_info.device_tree.map(|fdt| {
-		let ptr = ptr::with_exposed_provenance(fdt.get().try_into().unwrap());
+	device_tree().map(|addr| {
+		let ptr = ptr::with_exposed_provenance(addr);
 		unsafe { Fdt::from_ptr(ptr).unwrap() }
 	})
 }
@@ -70,6 +107,26 @@ pub fn rsdp() -> Option<core::num::NonZero<usize>> {
 	core::num::NonZero::new(rsdp)
 }
 
+/// Returns the physical address range of the initial RAM disk, which is specified
+/// in the `/chosen` node of the device tree.
+pub fn fdt_initrd() -> Option<Range<u64>> {
+	let fdt = fdt()?;
+	let chosen = fdt.find_node("/chosen")?;
+	let start = chosen.property("linux,initrd-start")?.as_usize()?;
+	let end = chosen.property("linux,initrd-end")?.as_usize()?;
+	Some(start as u64..end as u64)
+}
+
+/// Returns the physical address range of the device tree overlay, which is specified
+/// in the `/chosen` node of the device tree.
+pub fn fdt_overlay() -> Option<Range<u64>> {
+	let fdt = fdt()?;
+	let chosen = fdt.find_node("/chosen")?;
+	let start = chosen.property("hermit,overlay-start")?.as_usize()?;
+	let end = chosen.property("hermit,overlay-end")?.as_usize()?;
+	Some(start as u64..end as u64)
+}
+
 pub fn fdt_args() -> Option<&'static str> {
 	fdt().and_then(|fdt| fdt.chosen().bootargs())
 }
@@ -96,12 +153,35 @@ impl Default for Cli {
 
 		let mut args = Vec::new();
 		let mut mmio = Vec::new();
+		let mut mount_options = Vec::new();
+		let mut neighbors = Vec::new();
+		let mut selftest = false;
+		let mut syscall_log = false;
+		let mut syscall_allow: Option<Vec<String>> = None;
+		let mut syscall_deny = Vec::new();
+		let mut mitigations = Vec::new();
+		let mut dhcp = None;
+		let syscall_names = |value: &str| {
+			value
+				.split(',')
+				.map(|name| name.strip_prefix("sys_").unwrap_or(name).to_string())
+				.collect::<Vec<_>>()
+		};
 		while let Some(word) = words.next() {
 			if word.as_str().starts_with("virtio_mmio.device=") {
 				let v: Vec<&str> = word.as_str().split('=').collect();
 				mmio.push(v[1].to_string());
 				continue;
 			}
+			if let Some(option) = word.as_str().strip_prefix("virtiofs.") {
+				match option.split_once('=') {
+					Some((tag, options)) => {
+						mount_options.push((tag.to_string(), options.to_string()));
+					}
+					None => error!("could not parse bootarg: {word}"),
+				}
+				continue;
+			}
 
 			match word.as_str() {
 				#[cfg(not(target_arch = "riscv64"))]
@@ -121,6 +201,15 @@ impl Default for Cli {
 					let gateway = expect_arg(words.next(), word.as_str());
 					env_vars.insert(String::from("HERMIT_GATEWAY"), gateway);
 				}
+				"-hostname" => {
+					let hostname = expect_arg(words.next(), word.as_str());
+					env_vars.insert(String::from("HERMIT_HOSTNAME"), hostname);
+				}
+				"-neigh" => {
+					neighbors.push(expect_arg(words.next(), word.as_str()));
+				}
+				"selftest" => selftest = true,
+				"syscall.log" => syscall_log = true,
 				"-mount" => {
 					let gateway = expect_arg(words.next(), word.as_str());
 					env_vars.insert(String::from("UHYVE_MOUNT"), gateway);
@@ -137,6 +226,20 @@ impl Default for Cli {
 							};
 							env_vars.insert(key.to_string(), value.to_string());
 						}
+						"syscall.allow" => {
+							syscall_allow
+								.get_or_insert_with(Vec::new)
+								.extend(syscall_names(value));
+						}
+						"syscall.deny" => syscall_deny.extend(syscall_names(value)),
+						"mitigations" => {
+							mitigations.extend(value.split(',').map(str::to_string));
+						}
+						"ip" => match value {
+							"dhcp" => dhcp = Some(true),
+							"static" => dhcp = Some(false),
+							_ => error!("could not parse bootarg: {word}"),
+						},
 						_ => error!("could not parse bootarg: {word}"),
 					}
 				}
@@ -153,6 +256,14 @@ impl Default for Cli {
 			args,
 			#[allow(dead_code)]
 			mmio,
+			mount_options,
+			neighbors,
+			selftest,
+			syscall_log,
+			syscall_allow,
+			syscall_deny,
+			mitigations,
+			dhcp,
 		}
 	}
 }
@@ -177,6 +288,64 @@ pub fn args() -> &'static [String] {
 	CLI.get().unwrap().args.as_slice()
 }
 
+/// Returns the options of the virtio-fs mount with the tag `tag`,
+/// which are given by `virtiofs.<tag>=<options>`.
+#[allow(dead_code)]
+pub fn mount_options(tag: &str) -> Option<&'static str> {
+	CLI.get()
+		.unwrap()
+		.mount_options
+		.iter()
+		.rev()
+		.find(|(t, _)| t == tag)
+		.map(|(_, options)| options.as_str())
+}
+
+/// Returns the static neighbors, which are given by `-neigh <address>,<hardware address>`.
+#[allow(dead_code)]
+pub fn neighbors() -> &'static [String] {
+	CLI.get().unwrap().neighbors.as_slice()
+}
+
+/// Whether the network interface is configured by DHCP (`ip=dhcp`) or by
+/// the static configuration (`ip=static`), if given on the command line.
+#[allow(dead_code)]
+pub fn dhcp() -> Option<bool> {
+	CLI.get().unwrap().dhcp
+}
+
+/// Whether the diagnostics are executed before starting the application.
+pub fn selftest() -> bool {
+	CLI.get().unwrap().selftest
+}
+
+/// Whether the entry and exit of all system calls are logged,
+/// which is enabled by `syscall.log`.
+#[allow(dead_code)]
+pub fn syscall_log() -> bool {
+	CLI.get().unwrap().syscall_log
+}
+
+/// Returns the system calls, which are given by `syscall.allow=<name>,...`.
+///
+/// If `None`, all system calls are allowed.
+#[allow(dead_code)]
+pub fn syscall_allow() -> Option<&'static [String]> {
+	CLI.get().unwrap().syscall_allow.as_deref()
+}
+
+/// Returns the system calls, which are given by `syscall.deny=<name>,...`.
+#[allow(dead_code)]
+pub fn syscall_deny() -> &'static [String] {
+	CLI.get().unwrap().syscall_deny.as_slice()
+}
+
+/// Returns the options of the mitigations, which are given by `mitigations=<name>,...`.
+#[allow(dead_code)]
+pub fn mitigations() -> &'static [String] {
+	CLI.get().unwrap().mitigations.as_slice()
+}
+
 /// Returns the configuration of all mmio devices
 #[allow(dead_code)]
 pub fn mmio() -> &'static [String] {
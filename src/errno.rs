@@ -489,6 +489,31 @@ impl ToErrno for isize {
 	}
 }
 
+/// Return values of system calls, which report errors as negated error numbers
+///
+/// `#[system]` functions returning a `Result` are converted with this trait.
+pub(crate) trait FromErrno {
+	fn from_errno(errno: i32) -> Self;
+}
+
+impl FromErrno for i32 {
+	fn from_errno(errno: i32) -> Self {
+		-errno
+	}
+}
+
+impl FromErrno for i64 {
+	fn from_errno(errno: i32) -> Self {
+		-i64::from(errno)
+	}
+}
+
+impl FromErrno for isize {
+	fn from_errno(errno: i32) -> Self {
+		-isize::try_from(errno).unwrap()
+	}
+}
+
 impl ToErrno for u8 {}
 impl ToErrno for u16 {}
 impl ToErrno for u32 {}
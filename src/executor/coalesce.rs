@@ -0,0 +1,193 @@
+//! Coalescing of small writes to TCP sockets
+//!
+//! A corked socket (`TCP_CORK`) holds back partial segments. The written data
+//! is passed to the socket only in full segments, until the socket is uncorked
+//! or the data has been held back for [`CORK_TIMEOUT`].
+//!
+//! In addition, small writes are coalesced automatically (autocorking), as long
+//! as previously written data has not been sent and acknowledged. Thereby,
+//! chatty writers send fewer and larger segments without buffering on the
+//! application level. The held back data is passed to the socket as soon as the
+//! send queue of the socket is empty. Autocorking is disabled by `TCP_NODELAY`.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::socket::tcp;
+use smoltcp::time::{Duration, Instant};
+
+use crate::fd::{gather, total_len};
+
+/// Maximum time, which data is held back by a corked socket
+const CORK_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Size of the IPv4 and TCP headers without options
+const HEADER_SIZE: usize = 40;
+
+#[derive(Debug, Default)]
+struct PendingWrite {
+	/// Set by `TCP_CORK`
+	corked: bool,
+	/// Data, which has not been passed to the socket yet
+	data: Vec<u8>,
+	/// Time, at which the data is passed to the socket at the latest
+	deadline: Option<Instant>,
+}
+
+/// Data, which is held back per TCP socket
+#[derive(Debug, Default)]
+pub(crate) struct PendingWrites {
+	sockets: BTreeMap<SocketHandle, PendingWrite>,
+}
+
+impl PendingWrites {
+	pub const fn new() -> Self {
+		Self {
+			sockets: BTreeMap::new(),
+		}
+	}
+
+	pub fn is_corked(&self, handle: SocketHandle) -> bool {
+		self.sockets
+			.get(&handle)
+			.is_some_and(|pending| pending.corked)
+	}
+
+	/// Corks or uncorks the socket `handle`. Uncorking passes all held back data to the socket.
+	pub fn set_corked(&mut self, sockets: &mut SocketSet<'_>, handle: SocketHandle, corked: bool) {
+		self.sockets.entry(handle).or_default().corked = corked;
+		if !corked {
+			self.flush(sockets, handle);
+		}
+	}
+
+	/// Writes the data of `bufs` after the first `skip` bytes to the socket `handle`
+	/// and returns the number of written bytes.
+	///
+	/// The data is held back, if the socket is corked or, with `autocork`, if
+	/// the data is smaller than a segment and the socket has not sent all data yet.
+	#[allow(clippy::too_many_arguments)]
+	pub fn send(
+		&mut self,
+		sockets: &mut SocketSet<'_>,
+		handle: SocketHandle,
+		bufs: &[&[u8]],
+		skip: usize,
+		segment_size: usize,
+		autocork: bool,
+		timestamp: Instant,
+	) -> Result<usize, tcp::SendError> {
+		let socket = sockets.get_mut::<tcp::Socket<'_>>(handle);
+		let pending = self.sockets.entry(handle).or_default();
+		let segment_size = segment_size.saturating_sub(HEADER_SIZE).max(1);
+		let remaining = total_len(bufs) - skip;
+
+		let hold_back = pending.corked
+			|| autocork && remaining < segment_size && socket.send_queue() > 0
+			|| !pending.data.is_empty();
+		if !hold_back {
+			// copy the data directly into the transmit buffer
+			return socket.send(|tx| {
+				let len = gather(bufs, skip, tx);
+				(len, len)
+			});
+		}
+
+		if !socket.may_send() {
+			return Err(tcp::SendError::InvalidState);
+		}
+
+		// The held back data must fit into the send buffer of the socket.
+		let free = socket
+			.send_capacity()
+			.saturating_sub(socket.send_queue() + pending.data.len());
+		let len = remaining.min(free);
+		let start = pending.data.len();
+		pending.data.resize(start + len, 0);
+		gather(bufs, skip, &mut pending.data[start..]);
+		if pending.deadline.is_none() {
+			pending.deadline = Some(timestamp + CORK_TIMEOUT);
+		}
+
+		if pending.corked {
+			// pass only full segments to the socket
+			let full = pending.data.len() / segment_size * segment_size;
+			Self::pass(socket, pending, full);
+		} else if pending.data.len() >= segment_size || socket.send_queue() == 0 {
+			let all = pending.data.len();
+			Self::pass(socket, pending, all);
+		}
+
+		Ok(len)
+	}
+
+	/// Passes the first `len` bytes of the held back data to the socket.
+	fn pass(socket: &mut tcp::Socket<'_>, pending: &mut PendingWrite, len: usize) {
+		if len == 0 {
+			return;
+		}
+
+		let sent = socket.send_slice(&pending.data[..len]).unwrap_or(0);
+		pending.data.drain(..sent);
+		if pending.data.is_empty() {
+			pending.deadline = None;
+		}
+	}
+
+	/// Passes all held back data of the socket `handle` to the socket.
+	pub fn flush(&mut self, sockets: &mut SocketSet<'_>, handle: SocketHandle) {
+		let Some(pending) = self.sockets.get_mut(&handle) else {
+			return;
+		};
+
+		let socket = sockets.get_mut::<tcp::Socket<'_>>(handle);
+		let all = pending.data.len();
+		Self::pass(socket, pending, all);
+		if !pending.corked && pending.data.is_empty() {
+			self.sockets.remove(&handle);
+		}
+	}
+
+	/// Forgets the socket `handle`, which has been destroyed.
+	pub fn remove(&mut self, handle: SocketHandle) {
+		self.sockets.remove(&handle);
+	}
+
+	/// Passes the held back data to the sockets, whose deadline has expired
+	/// or, if not corked, whose send queue is empty.
+	pub fn poll(&mut self, sockets: &mut SocketSet<'_>, timestamp: Instant) {
+		for (handle, pending) in &mut self.sockets {
+			if pending.data.is_empty() {
+				continue;
+			}
+
+			let socket = sockets.get_mut::<tcp::Socket<'_>>(*handle);
+			if pending
+				.deadline
+				.is_some_and(|deadline| deadline <= timestamp)
+				|| !pending.corked && socket.send_queue() == 0
+			{
+				let all = pending.data.len();
+				Self::pass(socket, pending, all);
+			}
+		}
+		self.sockets
+			.retain(|_, pending| pending.corked || !pending.data.is_empty());
+	}
+
+	/// Returns the time until the next deadline.
+	pub fn delay(&self, timestamp: Instant) -> Option<Duration> {
+		self.sockets
+			.values()
+			.filter_map(|pending| pending.deadline)
+			.min()
+			.map(|deadline| {
+				if deadline > timestamp {
+					deadline - timestamp
+				} else {
+					Duration::ZERO
+				}
+			})
+	}
+}
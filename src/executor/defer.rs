@@ -0,0 +1,125 @@
+//! Deferred acceptance of TCP connections (`TCP_DEFER_ACCEPT`)
+//!
+//! A listening socket with a deferred accept reports its established
+//! connection only after the peer has sent data or closed the connection.
+//! Like on Linux, an idle connection is reported nevertheless after the
+//! timeout of the listener, so that it does not occupy the backlog forever.
+
+use alloc::collections::BTreeMap;
+use core::task::Waker;
+
+use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::socket::tcp;
+use smoltcp::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Deferred {
+	/// Time, for which an idle connection is held back
+	timeout: Duration,
+	/// Time, at which the established connection is reported at the latest
+	deadline: Option<Instant>,
+	/// Task, which waits for the connection
+	waker: Option<Waker>,
+}
+
+/// Listening sockets, which defer the acceptance of their connections
+#[derive(Debug, Default)]
+pub(crate) struct DeferredAccepts {
+	sockets: BTreeMap<SocketHandle, Deferred>,
+}
+
+/// Returns `true`, if the three-way handshake of `socket` has been completed.
+fn is_established(socket: &tcp::Socket<'_>) -> bool {
+	socket.is_active() && socket.state() != tcp::State::SynReceived
+}
+
+impl DeferredAccepts {
+	pub const fn new() -> Self {
+		Self {
+			sockets: BTreeMap::new(),
+		}
+	}
+
+	/// Defers the acceptance of the connection of the listening socket `handle`
+	/// by up to `timeout` or, if `timeout` is `None`, stops deferring it.
+	pub fn set(&mut self, handle: SocketHandle, timeout: Option<Duration>) {
+		if let Some(timeout) = timeout {
+			self.sockets.insert(handle, Deferred {
+				timeout,
+				deadline: None,
+				waker: None,
+			});
+		} else {
+			self.sockets.remove(&handle);
+		}
+	}
+
+	/// Returns the timeout of the listening socket `handle`, if its connection is deferred.
+	pub fn timeout(&self, handle: SocketHandle) -> Option<Duration> {
+		self.sockets.get(&handle).map(|deferred| deferred.timeout)
+	}
+
+	/// Registers `waker` to be woken, when the timeout of the connection of `handle` expires.
+	pub fn register_waker(&mut self, handle: SocketHandle, waker: &Waker) {
+		if let Some(deferred) = self.sockets.get_mut(&handle) {
+			deferred.waker = Some(waker.clone());
+		}
+	}
+
+	/// Returns `true`, if the connection of the listening socket `handle` can be accepted.
+	pub fn is_acceptable(
+		&self,
+		sockets: &SocketSet<'_>,
+		handle: SocketHandle,
+		timestamp: Instant,
+	) -> bool {
+		let socket = sockets.get::<tcp::Socket<'_>>(handle);
+		if !is_established(socket) {
+			return false;
+		}
+
+		self.sockets.get(&handle).is_none_or(|deferred| {
+			socket.can_recv()
+				|| !socket.may_recv()
+				|| deferred
+					.deadline
+					.is_some_and(|deadline| deadline <= timestamp)
+		})
+	}
+
+	/// Starts the timeouts of the established connections and wakes the
+	/// waiting tasks of the connections, whose timeout has expired.
+	pub fn poll(&mut self, sockets: &SocketSet<'_>, timestamp: Instant) {
+		for (handle, deferred) in &mut self.sockets {
+			if !is_established(sockets.get::<tcp::Socket<'_>>(*handle)) {
+				deferred.deadline = None;
+				continue;
+			}
+
+			let deadline = *deferred
+				.deadline
+				.get_or_insert(timestamp + deferred.timeout);
+			if deadline <= timestamp {
+				if let Some(waker) = deferred.waker.take() {
+					waker.wake();
+				}
+			}
+		}
+	}
+
+	/// Returns the time until the next timeout of a connection expires.
+	pub fn delay(&self, timestamp: Instant) -> Option<Duration> {
+		self.sockets
+			.values()
+			.filter(|deferred| deferred.waker.is_some())
+			.filter_map(|deferred| deferred.deadline)
+			.min()
+			.map(|deadline| {
+				if deadline > timestamp {
+					deadline - timestamp
+				} else {
+					Duration::ZERO
+				}
+			})
+	}
+}
@@ -1,22 +1,30 @@
 use alloc::boxed::Box;
+use alloc::collections::VecDeque;
 use alloc::vec::Vec;
-#[cfg(not(feature = "dhcpv4"))]
 use core::str::FromStr;
 
 use smoltcp::iface::{Config, Interface, SocketSet};
 use smoltcp::phy::{self, ChecksumCapabilities, Device, DeviceCapabilities, Medium};
 #[cfg(feature = "dhcpv4")]
 use smoltcp::socket::dhcpv4;
-#[cfg(all(feature = "dns", not(feature = "dhcpv4")))]
+#[cfg(feature = "dns")]
 use smoltcp::socket::dns;
-use smoltcp::time::Instant;
-#[cfg(not(feature = "dhcpv4"))]
-use smoltcp::wire::Ipv4Address;
-use smoltcp::wire::{EthernetAddress, HardwareAddress};
-#[cfg(not(feature = "dhcpv4"))]
-use smoltcp::wire::{IpAddress, IpCidr};
-
-use super::network::{NetworkInterface, NetworkState};
+use smoltcp::time::{Duration, Instant};
+#[cfg(feature = "dhcpv4")]
+use smoltcp::wire::DhcpOption;
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, Ipv4Address};
+
+#[cfg(feature = "tcp")]
+use super::coalesce::PendingWrites;
+#[cfg(feature = "tcp")]
+use super::defer::DeferredAccepts;
+use super::neighbor::NeighborTable;
+#[cfg(feature = "dhcpv4")]
+use super::network::{DHCP_OPT_DOMAIN_NAME, DHCP_OPT_HOST_NAME};
+use super::network::{NetworkInterface, NetworkState, RxBudget};
+use super::route::{DEFAULT_IPV4, RouteProtocol, RouteTable};
+use super::slaac::Slaac;
+use super::timestamp::{RxStamp, RxTimestamps};
 use crate::arch;
 #[cfg(not(feature = "pci"))]
 use crate::arch::kernel::mmio as hardware;
@@ -24,22 +32,158 @@ use crate::drivers::net::NetworkDriver;
 #[cfg(feature = "pci")]
 use crate::drivers::pci as hardware;
 
+/// Options, which are requested from the DHCP server
+#[cfg(feature = "dhcpv4")]
+const DHCP_PARAMETER_REQUEST_LIST: &[u8] = &[
+	1, // subnet mask
+	3, // router
+	6, // domain name server
+	DHCP_OPT_HOST_NAME,
+	DHCP_OPT_DOMAIN_NAME,
+];
+
+/// MTU of the loopback device
+const LOOPBACK_MTU: u16 = u16::MAX;
+
 /// Data type to determine the mac address
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub(crate) struct HermitNet {
 	mtu: u16,
 	checksums: ChecksumCapabilities,
+	/// Frames, which are looped back, if no network driver is available
+	loopback: Option<VecDeque<Vec<u8>>>,
+	pub(super) neighbors: NeighborTable,
+	/// Autoconfiguration of the IPv6 addresses, which is missing on the loopback device
+	pub(super) slaac: Option<Slaac>,
+	/// Stamps the received frames, if a socket has enabled timestamping
+	pub(super) stamping: bool,
+	/// Timestamp of the last received frame
+	pub(super) rx_stamp: Option<RxStamp>,
 }
 
 impl HermitNet {
 	pub(crate) const fn new(mtu: u16, checksums: ChecksumCapabilities) -> Self {
-		Self { mtu, checksums }
+		Self {
+			mtu,
+			checksums,
+			loopback: None,
+			neighbors: NeighborTable::new(),
+			slaac: None,
+			stamping: false,
+			rx_stamp: None,
+		}
+	}
+
+	/// Creates a loopback device, which receives the frames it transmits.
+	///
+	/// The frames are not checksummed, because they never leave the kernel.
+	pub(crate) fn loopback() -> Self {
+		Self {
+			mtu: LOOPBACK_MTU,
+			checksums: ChecksumCapabilities::ignored(),
+			loopback: Some(VecDeque::new()),
+			neighbors: NeighborTable::new(),
+			slaac: None,
+			stamping: false,
+			rx_stamp: None,
+		}
+	}
+
+	/// Returns the delay until the device has to be polled again.
+	///
+	/// Transmitted frames of the loopback device have to be received by the next poll.
+	/// The IPv6 autoconfiguration has to be polled, when its next timer expires.
+	pub(super) fn poll_delay(&self, timestamp: Instant) -> Option<Duration> {
+		let loopback_delay = self
+			.loopback
+			.as_ref()
+			.filter(|queue| !queue.is_empty())
+			.map(|_| Duration::ZERO);
+		let slaac_delay = self.slaac.as_ref().and_then(Slaac::poll_at).map(|at| {
+			if at > timestamp {
+				at - timestamp
+			} else {
+				Duration::ZERO
+			}
+		});
+		match (loopback_delay, slaac_delay) {
+			(Some(delay), Some(slaac_delay)) => Some(delay.min(slaac_delay)),
+			(delay, slaac_delay) => delay.or(slaac_delay),
+		}
+	}
+
+	/// Advances the autoconfiguration of the IPv6 addresses and transmits its
+	/// solicitations.
+	pub(super) fn poll_slaac(&mut self, timestamp: Instant) {
+		let Some(slaac) = &mut self.slaac else {
+			return;
+		};
+
+		slaac.poll(timestamp);
+		while let Some(frame) = slaac.pop_frame() {
+			phy::TxToken::consume(TxToken::new(), frame.len(), |buffer| {
+				buffer.copy_from_slice(&frame);
+			});
+		}
+	}
+
+	fn tx_token(&mut self) -> DeviceTxToken<'_> {
+		match &mut self.loopback {
+			Some(queue) => DeviceTxToken::Loopback(queue),
+			None => DeviceTxToken::Driver(TxToken::new()),
+		}
 	}
 }
 
 impl<'a> NetworkInterface<'a> {
-	#[cfg(feature = "dhcpv4")]
+	/// Creates an interface on top of the loopback device, which is used,
+	/// if no network driver is available.
+	///
+	/// The interface owns the addresses `127.0.0.1/8` and `::1/128`, so that
+	/// sockets can communicate within the application.
+	fn create_loopback() -> NetworkState<'a> {
+		info!("No network device found, use the loopback device");
+
+		let mut device = HermitNet::loopback();
+		let hardware_addr = HardwareAddress::Ethernet(EthernetAddress([0; 6]));
+
+		let mut config = Config::new(hardware_addr);
+		config.random_seed = (arch::kernel::systemtime::now_micros()) / 1_000_000;
+
+		let mut iface = Interface::new(config, &mut device, crate::executor::network::now());
+		iface.update_ip_addrs(|ip_addrs| {
+			ip_addrs
+				.push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
+				.unwrap();
+			ip_addrs
+				.push(IpCidr::new(IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1), 128))
+				.unwrap();
+		});
+
+		NetworkState::Initialized(Box::new(Self {
+			iface,
+			sockets: SocketSet::new(vec![]),
+			device,
+			rx_budget: RxBudget::new(),
+			#[cfg(feature = "tcp")]
+			pending_writes: PendingWrites::new(),
+			#[cfg(feature = "tcp")]
+			deferred_accepts: DeferredAccepts::new(),
+			rx_timestamps: RxTimestamps::new(),
+			routes: RouteTable::new(),
+			#[cfg(feature = "dhcpv4")]
+			dhcp_handle: None,
+			#[cfg(feature = "dns")]
+			dns_handle: None,
+		}))
+	}
+
+	/// Creates the interface of the network device.
+	///
+	/// The interface is configured by DHCPv4, if `use_dhcp` selects it.
+	/// Otherwise, the static configuration of [`Self::configure_static`] is used.
+	/// In both cases, the IPv6 addresses are autoconfigured by [`Slaac`].
 	pub(crate) fn create() -> NetworkState<'a> {
 		let (mtu, mac, checksums) = if let Some(driver) = hardware::get_network_driver() {
 			let guard = driver.lock();
@@ -49,26 +193,19 @@ impl<'a> NetworkInterface<'a> {
 				guard.get_checksums(),
 			)
 		} else {
-			return NetworkState::InitializationFailed;
+			return Self::create_loopback();
 		};
 
 		let mut device = HermitNet::new(mtu, checksums.clone());
 
-		if hermit_var!("HERMIT_IP").is_some() {
-			warn!(
-				"A static IP address is specified with the environment variable HERMIT_IP, but the device is configured to use DHCPv4!"
-			);
-		}
-
 		let ethernet_addr = EthernetAddress([mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]]);
 		let hardware_addr = HardwareAddress::Ethernet(ethernet_addr);
+		device.slaac = Some(Slaac::new(ethernet_addr));
 
 		info!("MAC address {}", hardware_addr);
 		info!("{:?}", checksums);
 		info!("MTU: {} bytes", mtu);
 
-		let dhcp = dhcpv4::Socket::new();
-
 		// use the current time based on the wall-clock time as seed
 		let mut config = Config::new(hardware_addr);
 		config.random_seed = (arch::kernel::systemtime::now_micros()) / 1_000_000;
@@ -76,44 +213,92 @@ impl<'a> NetworkInterface<'a> {
 			config.hardware_addr = hardware_addr;
 		}
 
-		let iface = Interface::new(config, &mut device, crate::executor::network::now());
+		let mut iface = Interface::new(config, &mut device, crate::executor::network::now());
+		#[allow(unused_mut)]
 		let mut sockets = SocketSet::new(vec![]);
-		let dhcp_handle = sockets.add(dhcp);
+
+		#[cfg(feature = "dhcpv4")]
+		if use_dhcp() {
+			let dhcp_handle = sockets.add(Self::dhcp_socket(mtu));
+
+			return NetworkState::Initialized(Box::new(Self {
+				iface,
+				sockets,
+				device,
+				rx_budget: RxBudget::new(),
+				#[cfg(feature = "tcp")]
+				pending_writes: PendingWrites::new(),
+				#[cfg(feature = "tcp")]
+				deferred_accepts: DeferredAccepts::new(),
+				rx_timestamps: RxTimestamps::new(),
+				routes: RouteTable::new(),
+				dhcp_handle: Some(dhcp_handle),
+				#[cfg(feature = "dns")]
+				dns_handle: None,
+			}));
+		}
+
+		let routes = Self::configure_static(&mut iface);
+
+		#[cfg(feature = "dns")]
+		let dns_handle = {
+			// Quad9 DNS server
+			let mydns1 = Ipv4Address::from_str(hermit_var_or!("HERMIT_DNS1", "9.9.9.9")).unwrap();
+			// Cloudflare DNS server
+			let mydns2 = Ipv4Address::from_str(hermit_var_or!("HERMIT_DNS2", "1.1.1.1")).unwrap();
+
+			let servers = &[mydns1.into(), mydns2.into()];
+			let dns_socket = dns::Socket::new(servers, vec![]);
+			sockets.add(dns_socket)
+		};
 
 		NetworkState::Initialized(Box::new(Self {
 			iface,
 			sockets,
 			device,
-			dhcp_handle,
+			rx_budget: RxBudget::new(),
+			#[cfg(feature = "tcp")]
+			pending_writes: PendingWrites::new(),
+			#[cfg(feature = "tcp")]
+			deferred_accepts: DeferredAccepts::new(),
+			rx_timestamps: RxTimestamps::new(),
+			routes,
+			#[cfg(feature = "dhcpv4")]
+			dhcp_handle: None,
 			#[cfg(feature = "dns")]
-			dns_handle: None,
+			dns_handle: Some(dns_handle),
 		}))
 	}
 
-	#[cfg(not(feature = "dhcpv4"))]
-	pub(crate) fn create() -> NetworkState<'a> {
-		let (mtu, mac, checksums) = if let Some(driver) = hardware::get_network_driver() {
-			let guard = driver.lock();
-			(
-				guard.get_mtu(),
-				guard.get_mac_address(),
-				guard.get_checksums(),
-			)
-		} else {
-			return NetworkState::InitializationFailed;
-		};
-
-		let mut device = HermitNet::new(mtu, checksums.clone());
+	/// Creates the DHCPv4 socket, which announces our host name and asks the
+	/// DHCP server for our domain name.
+	///
+	/// The socket lives as long as the interface, so its buffers are leaked.
+	/// The host name is copied once, so the DHCP host name is fixed at boot
+	/// and later changes by `sys_sethostname` are not announced on renewals.
+	#[cfg(feature = "dhcpv4")]
+	fn dhcp_socket(mtu: u16) -> dhcpv4::Socket<'a> {
+		let mut dhcp = dhcpv4::Socket::new();
+		let hostname: &'static [u8] = crate::hostname::hostname().into_bytes().leak();
+		let options: &'static [DhcpOption<'static>] = vec![DhcpOption {
+			kind: DHCP_OPT_HOST_NAME,
+			data: hostname,
+		}]
+		.leak();
+		dhcp.set_outgoing_options(options);
+		dhcp.set_parameter_request_list(DHCP_PARAMETER_REQUEST_LIST);
+		dhcp.set_receive_packet_buffer(vec![0; usize::from(mtu)].leak());
+
+		info!("Configure network interface by DHCPv4");
+		dhcp
+	}
 
+	/// Configures the address and the gateway of `iface`, which are given by
+	/// `HERMIT_IP`, `HERMIT_MASK` and `HERMIT_GATEWAY`, and returns the routes.
+	fn configure_static(iface: &mut Interface) -> RouteTable {
 		let myip = Ipv4Address::from_str(hermit_var_or!("HERMIT_IP", "10.0.5.3")).unwrap();
 		let mygw = Ipv4Address::from_str(hermit_var_or!("HERMIT_GATEWAY", "10.0.5.1")).unwrap();
 		let mymask = Ipv4Address::from_str(hermit_var_or!("HERMIT_MASK", "255.255.255.0")).unwrap();
-		// Quad9 DNS server
-		#[cfg(feature = "dns")]
-		let mydns1 = Ipv4Address::from_str(hermit_var_or!("HERMIT_DNS1", "9.9.9.9")).unwrap();
-		// Cloudflare DNS server
-		#[cfg(feature = "dns")]
-		let mydns2 = Ipv4Address::from_str(hermit_var_or!("HERMIT_DNS2", "1.1.1.1")).unwrap();
 
 		// calculate the netmask length
 		// => count the number of contiguous 1 bits,
@@ -129,9 +314,7 @@ impl<'a> NetworkInterface<'a> {
 			prefix_len += (!mymask.octets()[3]).trailing_zeros();
 		}
 
-		let ethernet_addr = EthernetAddress([mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]]);
-		let hardware_addr = HardwareAddress::Ethernet(ethernet_addr);
-		let ip_addrs = [IpCidr::new(
+		let ip_addr = IpCidr::new(
 			IpAddress::v4(
 				myip.octets()[0],
 				myip.octets()[1],
@@ -139,60 +322,49 @@ impl<'a> NetworkInterface<'a> {
 				myip.octets()[3],
 			),
 			prefix_len.try_into().unwrap(),
-		)];
+		);
 
-		info!("MAC address {}", hardware_addr);
-		info!("Configure network interface with address {}", ip_addrs[0]);
+		info!("Configure network interface with address {}", ip_addr);
 		info!("Configure gateway with address {}", mygw);
-		info!("{:?}", checksums);
-		info!("MTU: {} bytes", mtu);
 
-		// use the current time based on the wall-clock time as seed
-		let mut config = Config::new(hardware_addr);
-		config.random_seed = (arch::kernel::systemtime::now_micros()) / 1_000_000;
-		if device.capabilities().medium == Medium::Ethernet {
-			config.hardware_addr = hardware_addr;
-		}
-
-		let mut iface = Interface::new(config, &mut device, crate::executor::network::now());
 		iface.update_ip_addrs(|ip_addrs| {
-			ip_addrs
-				.push(IpCidr::new(
-					IpAddress::v4(
-						myip.octets()[0],
-						myip.octets()[1],
-						myip.octets()[2],
-						myip.octets()[3],
-					),
-					prefix_len.try_into().unwrap(),
-				))
-				.unwrap();
+			ip_addrs.push(ip_addr).unwrap();
 		});
-		iface.routes_mut().add_default_ipv4_route(mygw).unwrap();
-
-		#[allow(unused_mut)]
-		let mut sockets = SocketSet::new(vec![]);
+		let mut routes = RouteTable::new();
+		routes
+			.add(DEFAULT_IPV4, mygw.into(), 0, RouteProtocol::Boot)
+			.unwrap();
+		routes.apply(iface.routes_mut()).unwrap();
 
-		#[cfg(feature = "dns")]
-		let dns_handle = {
-			let servers = &[mydns1.into(), mydns2.into()];
-			let dns_socket = dns::Socket::new(servers, vec![]);
-			sockets.add(dns_socket)
-		};
+		routes
+	}
+}
 
-		NetworkState::Initialized(Box::new(Self {
-			iface,
-			sockets,
-			device,
-			#[cfg(feature = "dns")]
-			dns_handle: Some(dns_handle),
-		}))
+/// Whether the network interface is configured by DHCPv4.
+///
+/// DHCPv4 is selected by `ip=dhcp` and disabled by `ip=static` on the
+/// kernel command line. By default, it is used, unless a static address
+/// is given by `HERMIT_IP`.
+#[cfg(feature = "dhcpv4")]
+fn use_dhcp() -> bool {
+	let static_ip = hermit_var!("HERMIT_IP").is_some();
+	match crate::env::dhcp() {
+		Some(true) => {
+			if static_ip {
+				warn!(
+					"A static IP address is specified with the environment variable HERMIT_IP, but the device is configured to use DHCPv4!"
+				);
+			}
+			true
+		}
+		Some(false) => false,
+		None => !static_ip,
 	}
 }
 
 impl Device for HermitNet {
 	type RxToken<'a> = RxToken;
-	type TxToken<'a> = TxToken;
+	type TxToken<'a> = DeviceTxToken<'a>;
 
 	fn capabilities(&self) -> DeviceCapabilities {
 		let mut cap = DeviceCapabilities::default();
@@ -202,16 +374,34 @@ impl Device for HermitNet {
 		cap
 	}
 
-	fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
-		if let Some(driver) = hardware::get_network_driver() {
-			driver.lock().receive_packet()
-		} else {
-			None
+	fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+		if let Some(frame) = self.neighbors.pop_injected() {
+			return Some((RxToken::new(frame), self.tx_token()));
 		}
+
+		let (rx_token, tx_token) = match &mut self.loopback {
+			Some(queue) => (
+				RxToken::new(queue.pop_front()?),
+				DeviceTxToken::Loopback(queue),
+			),
+			None => {
+				let (rx_token, tx_token) =
+					hardware::get_network_driver()?.lock().receive_packet()?;
+				(rx_token, DeviceTxToken::Driver(tx_token))
+			}
+		};
+		self.neighbors.snoop(&rx_token.buffer, timestamp);
+		if let Some(slaac) = &mut self.slaac {
+			slaac.snoop(&rx_token.buffer, timestamp);
+		}
+		if self.stamping {
+			self.rx_stamp = RxStamp::new(&rx_token.buffer, rx_token.flow_hash);
+		}
+		Some((rx_token, tx_token))
 	}
 
 	fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
-		Some(TxToken::new())
+		Some(self.tx_token())
 	}
 }
 
@@ -221,11 +411,21 @@ pub(crate) type RxHandle = usize;
 #[doc(hidden)]
 pub(crate) struct RxToken {
 	buffer: Vec<u8>,
+	/// Hash of the flow, if reported by the device
+	flow_hash: Option<u32>,
 }
 
 impl RxToken {
 	pub(crate) fn new(buffer: Vec<u8>) -> Self {
-		Self { buffer }
+		Self {
+			buffer,
+			flow_hash: None,
+		}
+	}
+
+	pub(crate) fn with_flow_hash(mut self, flow_hash: Option<u32>) -> Self {
+		self.flow_hash = flow_hash;
+		self
 	}
 }
 
@@ -258,3 +458,27 @@ impl phy::TxToken for TxToken {
 			.send_packet(len, f)
 	}
 }
+
+/// Token of [`HermitNet`], which passes a frame either to the network driver or to the loopback queue
+#[doc(hidden)]
+pub(crate) enum DeviceTxToken<'a> {
+	Driver(TxToken),
+	Loopback(&'a mut VecDeque<Vec<u8>>),
+}
+
+impl phy::TxToken for DeviceTxToken<'_> {
+	fn consume<R, F>(self, len: usize, f: F) -> R
+	where
+		F: FnOnce(&mut [u8]) -> R,
+	{
+		match self {
+			Self::Driver(token) => phy::TxToken::consume(token, len, f),
+			Self::Loopback(queue) => {
+				let mut buffer = vec![0; len];
+				let result = f(&mut buffer);
+				queue.push_back(buffer);
+				result
+			}
+		}
+	}
+}
@@ -1,10 +1,22 @@
 #![allow(dead_code)]
 
+#[cfg(feature = "tcp")]
+pub(crate) mod coalesce;
+#[cfg(feature = "tcp")]
+pub(crate) mod defer;
 #[cfg(any(feature = "tcp", feature = "udp"))]
 pub(crate) mod device;
 #[cfg(any(feature = "tcp", feature = "udp"))]
+pub(crate) mod neighbor;
+#[cfg(any(feature = "tcp", feature = "udp"))]
 pub(crate) mod network;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+pub(crate) mod route;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+pub(crate) mod slaac;
 pub(crate) mod task;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+pub(crate) mod timestamp;
 #[cfg(feature = "vsock")]
 pub(crate) mod vsock;
 
@@ -17,7 +29,6 @@ use core::task::{Context, Poll, Waker};
 use core::time::Duration;
 
 use crossbeam_utils::Backoff;
-use hermit_sync::without_interrupts;
 #[cfg(any(feature = "tcp", feature = "udp"))]
 use smoltcp::time::Instant;
 
@@ -30,9 +41,11 @@ use crate::drivers::net::NetworkDriver;
 use crate::drivers::pci::get_network_driver;
 use crate::executor::task::AsyncTask;
 use crate::io;
+use crate::metrics::Histogram;
 #[cfg(any(feature = "tcp", feature = "udp"))]
 use crate::scheduler::PerCoreSchedulerExt;
 use crate::synch::futex::*;
+use crate::synch::without_interrupts;
 
 /// WakerRegistration is derived from smoltcp's
 /// implementation.
@@ -129,6 +142,17 @@ pub fn init() {
 	crate::executor::vsock::init();
 }
 
+/// Transmits the pending network data and stops the interrupts of the network device.
+pub fn exit() {
+	#[cfg(any(feature = "tcp", feature = "udp"))]
+	{
+		crate::executor::network::exit();
+		if let Some(driver) = get_network_driver() {
+			driver.lock().set_polling_mode(true);
+		}
+	}
+}
+
 /// Blocks the current thread on `f`, running the executor when idling.
 pub(crate) fn poll_on<F, T>(future: F) -> io::Result<T>
 where
@@ -147,8 +171,33 @@ where
 	}
 }
 
+/// Time, in which tasks are blocked on a future, until the future is ready
+static BLOCK_ON_MICROS: Histogram = Histogram::new(
+	"executor_block_on_microseconds",
+	"Time, in which tasks are blocked on a future, until the future is ready",
+);
+
 /// Blocks the current thread on `f`, running the executor when idling.
 pub(crate) fn block_on<F, T>(future: F, timeout: Option<Duration>) -> io::Result<T>
+where
+	F: Future<Output = io::Result<T>>,
+{
+	wait_on(future, timeout, false)
+}
+
+/// Blocks the current thread on `f` like [`block_on`], but returns `EINTR`,
+/// as soon as the current task has been interrupted or killed.
+///
+/// Only blocking system calls are interruptible. Internal waits rely on the
+/// completion of their futures and use [`block_on`].
+pub(crate) fn block_on_interruptible<F, T>(future: F, timeout: Option<Duration>) -> io::Result<T>
+where
+	F: Future<Output = io::Result<T>>,
+{
+	wait_on(future, timeout, true)
+}
+
+fn wait_on<F, T>(future: F, timeout: Option<Duration>, interruptible: bool) -> io::Result<T>
 where
 	F: Future<Output = io::Result<T>>,
 {
@@ -171,6 +220,8 @@ where
 
 		let now = crate::arch::kernel::systemtime::now_micros();
 		if let Poll::Ready(t) = result {
+			BLOCK_ON_MICROS.observe(now - start);
+
 			// allow network interrupts
 			#[cfg(any(feature = "tcp", feature = "udp"))]
 			{
@@ -192,28 +243,34 @@ where
 			return t;
 		}
 
-		if let Some(duration) = timeout {
-			if Duration::from_micros(now - start) >= duration {
-				// allow network interrupts
-				#[cfg(any(feature = "tcp", feature = "udp"))]
-				{
-					let delay = if let Ok(nic) = crate::executor::network::NIC.lock().as_nic_mut() {
-						nic.poll_delay(Instant::from_micros_const(now.try_into().unwrap()))
-							.map(|d| d.total_micros())
-					} else {
-						None
-					};
-					core_scheduler().add_network_timer(
-						delay.map(|d| crate::arch::processor::get_timer_ticks() + d),
-					);
-
-					if let Some(device) = device {
-						device.lock().set_polling_mode(false);
-					}
-				}
+		// A blocking system call returns early, if the task has been interrupted or killed.
+		let interrupted = interruptible && crate::scheduler::interrupt::take();
+		let timed_out =
+			timeout.is_some_and(|duration| Duration::from_micros(now - start) >= duration);
+		if interrupted || timed_out {
+			// allow network interrupts
+			#[cfg(any(feature = "tcp", feature = "udp"))]
+			{
+				let delay = if let Ok(nic) = crate::executor::network::NIC.lock().as_nic_mut() {
+					nic.poll_delay(Instant::from_micros_const(now.try_into().unwrap()))
+						.map(|d| d.total_micros())
+				} else {
+					None
+				};
+				core_scheduler().add_network_timer(
+					delay.map(|d| crate::arch::processor::get_timer_ticks() + d),
+				);
 
-				return Err(io::Error::ETIME);
+				if let Some(device) = device {
+					device.lock().set_polling_mode(false);
+				}
 			}
+
+			return Err(if interrupted {
+				io::Error::EINTR
+			} else {
+				io::Error::ETIME
+			});
 		}
 
 		#[cfg(any(feature = "tcp", feature = "udp"))]
@@ -229,8 +286,10 @@ where
 				core_scheduler().add_network_timer(
 					delay.map(|d| crate::arch::processor::get_timer_ticks() + d),
 				);
-				let wakeup_time =
-					timeout.map(|duration| start + u64::try_from(duration.as_micros()).unwrap());
+				// the futex expects the remaining time of the timeout
+				let wakeup_time = timeout.map(|duration| {
+					(start + u64::try_from(duration.as_micros()).unwrap()).saturating_sub(now)
+				});
 
 				// allow network interrupts
 				if let Some(device) = device {
@@ -254,8 +313,10 @@ where
 		#[cfg(not(any(feature = "tcp", feature = "udp")))]
 		{
 			if backoff.is_completed() {
-				let wakeup_time =
-					timeout.map(|duration| start + u64::try_from(duration.as_micros()).unwrap());
+				// the futex expects the remaining time of the timeout
+				let wakeup_time = timeout.map(|duration| {
+					(start + u64::try_from(duration.as_micros()).unwrap()).saturating_sub(now)
+				});
 
 				// switch to another task
 				task_notify.wait(wakeup_time);
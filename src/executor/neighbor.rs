@@ -0,0 +1,266 @@
+//! Neighbor table of the network interface
+//!
+//! smoltcp keeps its neighbor cache private. Therefore, the kernel mirrors the
+//! cache by snooping the ARP and NDP packets, from which smoltcp learns its
+//! neighbors, and expires the learned entries after the same lifetime.
+//!
+//! Static entries are added to the cache of smoltcp by injecting ARP replies
+//! or neighbor advertisements into the receive path. Because smoltcp expires
+//! all entries, the injection is repeated before the lifetime elapses. A
+//! removed static entry remains in the cache of smoltcp until it expires.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use smoltcp::phy::{ChecksumCapabilities, Medium};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{
+	ArpOperation, ArpPacket, ArpRepr, ETHERNET_HEADER_LEN, EthernetAddress, EthernetFrame,
+	EthernetProtocol, HardwareAddress, IPV6_HEADER_LEN, Icmpv6Packet, Icmpv6Repr, IpAddress,
+	IpCidr, IpProtocol, Ipv4Address, Ipv6Address, Ipv6Packet, Ipv6Repr, NdiscNeighborFlags,
+	NdiscRepr, RawHardwareAddress,
+};
+
+/// Lifetime of the entries in the neighbor cache of smoltcp
+const ENTRY_LIFETIME: Duration = Duration::from_secs(60);
+/// Interval, in which static entries are injected again
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Neighbor {
+	pub hwaddr: EthernetAddress,
+	/// Expiration time of a learned entry or `None` for a static entry
+	pub expires_at: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct NeighborTable {
+	entries: BTreeMap<IpAddress, Neighbor>,
+	/// Frames, which are injected into the receive path
+	injected: VecDeque<Vec<u8>>,
+	/// Time, at which the static entries are injected again
+	next_refresh: Instant,
+}
+
+impl NeighborTable {
+	pub const fn new() -> Self {
+		Self {
+			entries: BTreeMap::new(),
+			injected: VecDeque::new(),
+			next_refresh: Instant::ZERO,
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&IpAddress, &Neighbor)> {
+		self.entries.iter()
+	}
+
+	/// Adds a static entry, which is injected with the next poll.
+	pub fn insert_static(&mut self, addr: IpAddress, hwaddr: EthernetAddress) {
+		self.entries.insert(addr, Neighbor {
+			hwaddr,
+			expires_at: None,
+		});
+		self.next_refresh = Instant::ZERO;
+	}
+
+	/// Removes an entry and returns `true`, if the entry existed.
+	pub fn remove(&mut self, addr: &IpAddress) -> bool {
+		self.entries.remove(addr).is_some()
+	}
+
+	/// Returns the next frame, which has to be injected into the receive path.
+	pub fn pop_injected(&mut self) -> Option<Vec<u8>> {
+		self.injected.pop_front()
+	}
+
+	/// Learns the neighbor from an ARP or NDP packet in the received `frame`.
+	pub fn snoop(&mut self, frame: &[u8], timestamp: Instant) {
+		let Ok(frame) = EthernetFrame::new_checked(frame) else {
+			return;
+		};
+
+		match frame.ethertype() {
+			EthernetProtocol::Arp => {
+				let Ok(packet) = ArpPacket::new_checked(frame.payload()) else {
+					return;
+				};
+				if let Ok(ArpRepr::EthernetIpv4 {
+					source_hardware_addr,
+					source_protocol_addr,
+					..
+				}) = ArpRepr::parse(&packet)
+				{
+					self.learn(source_protocol_addr.into(), source_hardware_addr, timestamp);
+				}
+			}
+			EthernetProtocol::Ipv6 => {
+				let Ok(packet) = Ipv6Packet::new_checked(frame.payload()) else {
+					return;
+				};
+				if packet.next_header() != IpProtocol::Icmpv6 {
+					return;
+				}
+				let Ok(icmp) = Icmpv6Packet::new_checked(packet.payload()) else {
+					return;
+				};
+				let src_addr = packet.src_addr();
+				let Ok(Icmpv6Repr::Ndisc(
+					NdiscRepr::NeighborAdvert {
+						lladdr: Some(lladdr),
+						..
+					}
+					| NdiscRepr::NeighborSolicit {
+						lladdr: Some(lladdr),
+						..
+					},
+				)) = Icmpv6Repr::parse(
+					&src_addr,
+					&packet.dst_addr(),
+					&icmp,
+					&ChecksumCapabilities::ignored(),
+				)
+				else {
+					return;
+				};
+				if let Ok(HardwareAddress::Ethernet(hwaddr)) = lladdr.parse(Medium::Ethernet) {
+					self.learn(src_addr.into(), hwaddr, timestamp);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	fn learn(&mut self, addr: IpAddress, hwaddr: EthernetAddress, timestamp: Instant) {
+		if !hwaddr.is_unicast() || addr.is_unspecified() {
+			return;
+		}
+
+		let neighbor = self.entries.entry(addr).or_insert(Neighbor {
+			hwaddr,
+			expires_at: Some(timestamp),
+		});
+		// static entries are not overwritten
+		if let Some(expires_at) = &mut neighbor.expires_at {
+			neighbor.hwaddr = hwaddr;
+			*expires_at = timestamp + ENTRY_LIFETIME;
+		}
+	}
+
+	/// Removes the expired entries and prepares the injection of the static entries,
+	/// if the refresh interval has elapsed.
+	///
+	/// `hwaddr` and `addrs` are the addresses of the network interface.
+	pub fn refresh(&mut self, timestamp: Instant, hwaddr: EthernetAddress, addrs: &[IpCidr]) {
+		self.entries.retain(|_, neighbor| {
+			neighbor
+				.expires_at
+				.is_none_or(|expires_at| expires_at > timestamp)
+		});
+
+		if timestamp < self.next_refresh {
+			return;
+		}
+		self.next_refresh = timestamp + REFRESH_INTERVAL;
+
+		for (addr, neighbor) in &self.entries {
+			if neighbor.expires_at.is_some() {
+				continue;
+			}
+
+			// prefer an own address in the same network as the neighbor
+			let own_addr = addrs
+				.iter()
+				.filter(|cidr| cidr.address().version() == addr.version())
+				.max_by_key(|cidr| cidr.contains_addr(addr))
+				.map(|cidr| cidr.address());
+			let frame = match (addr, own_addr) {
+				(IpAddress::Ipv4(addr), Some(IpAddress::Ipv4(own_addr))) => {
+					arp_reply(*addr, neighbor.hwaddr, own_addr, hwaddr)
+				}
+				(IpAddress::Ipv6(addr), Some(IpAddress::Ipv6(own_addr))) => {
+					neighbor_advert(*addr, neighbor.hwaddr, own_addr, hwaddr)
+				}
+				_ => {
+					warn!("No address to add the static neighbor {addr}");
+					continue;
+				}
+			};
+			self.injected.push_back(frame);
+		}
+	}
+}
+
+pub(super) fn ethernet_frame(
+	src_addr: EthernetAddress,
+	dst_addr: EthernetAddress,
+	ethertype: EthernetProtocol,
+	payload_len: usize,
+) -> Vec<u8> {
+	let mut buffer = vec![0; ETHERNET_HEADER_LEN + payload_len];
+	let mut frame = EthernetFrame::new_unchecked(&mut buffer[..]);
+	frame.set_src_addr(src_addr);
+	frame.set_dst_addr(dst_addr);
+	frame.set_ethertype(ethertype);
+	buffer
+}
+
+/// Creates an ARP reply of the neighbor `addr` to the own address `own_addr`.
+fn arp_reply(
+	addr: Ipv4Address,
+	hwaddr: EthernetAddress,
+	own_addr: Ipv4Address,
+	own_hwaddr: EthernetAddress,
+) -> Vec<u8> {
+	let repr = ArpRepr::EthernetIpv4 {
+		operation: ArpOperation::Reply,
+		source_hardware_addr: hwaddr,
+		source_protocol_addr: addr,
+		target_hardware_addr: own_hwaddr,
+		target_protocol_addr: own_addr,
+	};
+
+	let mut buffer = ethernet_frame(hwaddr, own_hwaddr, EthernetProtocol::Arp, repr.buffer_len());
+	repr.emit(&mut ArpPacket::new_unchecked(
+		&mut buffer[ETHERNET_HEADER_LEN..],
+	));
+	buffer
+}
+
+/// Creates a neighbor advertisement of the neighbor `addr` to the own address `own_addr`.
+fn neighbor_advert(
+	addr: Ipv6Address,
+	hwaddr: EthernetAddress,
+	own_addr: Ipv6Address,
+	own_hwaddr: EthernetAddress,
+) -> Vec<u8> {
+	let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+		flags: NdiscNeighborFlags::OVERRIDE,
+		target_addr: addr,
+		lladdr: Some(RawHardwareAddress::from(hwaddr)),
+	});
+	let ip_repr = Ipv6Repr {
+		src_addr: addr,
+		dst_addr: own_addr,
+		next_header: IpProtocol::Icmpv6,
+		payload_len: icmp_repr.buffer_len(),
+		hop_limit: 0xff,
+	};
+
+	let mut buffer = ethernet_frame(
+		hwaddr,
+		own_hwaddr,
+		EthernetProtocol::Ipv6,
+		IPV6_HEADER_LEN + icmp_repr.buffer_len(),
+	);
+	let payload = &mut buffer[ETHERNET_HEADER_LEN..];
+	ip_repr.emit(&mut Ipv6Packet::new_unchecked(&mut payload[..]));
+	icmp_repr.emit(
+		&addr,
+		&own_addr,
+		&mut Icmpv6Packet::new_unchecked(&mut payload[IPV6_HEADER_LEN..]),
+		&ChecksumCapabilities::default(),
+	);
+	buffer
+}
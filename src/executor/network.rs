@@ -1,13 +1,18 @@
 use alloc::boxed::Box;
-#[cfg(feature = "dns")]
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::future;
-use core::sync::atomic::{AtomicU16, Ordering};
+use core::str::FromStr;
+use core::sync::atomic::{AtomicU16, AtomicU64, Ordering};
 use core::task::Poll;
+#[cfg(feature = "tcp")]
+use core::task::Waker;
 
 use hermit_sync::InterruptTicketMutex;
-use smoltcp::iface::{PollResult, SocketHandle, SocketSet};
-use smoltcp::socket::AnySocket;
+use smoltcp::iface::{PollIngressSingleResult, PollResult, SocketHandle, SocketSet};
+#[cfg(feature = "tcp")]
+use smoltcp::phy::Device;
 #[cfg(feature = "dhcpv4")]
 use smoltcp::socket::dhcpv4;
 #[cfg(feature = "dns")]
@@ -16,18 +21,31 @@ use smoltcp::socket::dns::{self, GetQueryResultError, QueryHandle};
 use smoltcp::socket::tcp;
 #[cfg(feature = "udp")]
 use smoltcp::socket::udp;
+use smoltcp::socket::{AnySocket, Socket};
 use smoltcp::time::{Duration, Instant};
 #[cfg(feature = "dns")]
-use smoltcp::wire::{DnsQueryType, IpAddress};
+use smoltcp::wire::DnsQueryType;
 #[cfg(feature = "dhcpv4")]
-use smoltcp::wire::{IpCidr, Ipv4Address, Ipv4Cidr};
-
-use crate::arch;
+use smoltcp::wire::Ipv4Cidr;
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpEndpoint, Ipv4Address};
+
+#[cfg(not(feature = "pci"))]
+use crate::arch::kernel::mmio as hardware;
+use crate::drivers::net::NetworkDriver;
+#[cfg(feature = "pci")]
+use crate::drivers::pci as hardware;
+#[cfg(feature = "tcp")]
+use crate::executor::coalesce::PendingWrites;
+#[cfg(feature = "tcp")]
+use crate::executor::defer::DeferredAccepts;
 use crate::executor::device::HermitNet;
+use crate::executor::route::{DEFAULT_IPV4, DEFAULT_IPV6, RouteProtocol, RouteTable};
+use crate::executor::slaac::{Slaac, SlaacEvent};
 use crate::executor::spawn;
-#[cfg(feature = "dns")]
-use crate::io;
+use crate::executor::timestamp::{self, RxTimestamps};
+use crate::percpu::{PerCpu, alloc_per_cpu};
 use crate::scheduler::PerCoreSchedulerExt;
+use crate::{arch, io};
 
 pub(crate) enum NetworkState<'a> {
 	Missing,
@@ -46,16 +64,125 @@ impl<'a> NetworkState<'a> {
 
 pub(crate) type Handle = SocketHandle;
 
+/// DHCP option, which contains the host name
+#[cfg(feature = "dhcpv4")]
+pub(crate) const DHCP_OPT_HOST_NAME: u8 = 12;
+/// DHCP option, which contains the DNS domain name
+#[cfg(feature = "dhcpv4")]
+pub(crate) const DHCP_OPT_DOMAIN_NAME: u8 = 15;
+
+/// Size of the receive and the transmit buffer of a socket
+pub(crate) const SOCKET_BUFFER_SIZE: usize = 0x10000;
+
 static LOCAL_ENDPOINT: AtomicU16 = AtomicU16::new(0);
 pub(crate) static NIC: InterruptTicketMutex<NetworkState<'_>> =
 	InterruptTicketMutex::new(NetworkState::Missing);
 
+/// Maximum number of received packets, which are processed by a single poll
+const RX_BUDGET: usize = 64;
+/// Maximum number of received packets, which are processed by all cores within [`RX_BUDGET_WINDOW`]
+const RX_BUDGET_GLOBAL: usize = 300;
+/// Interval, in which the global budget is replenished
+const RX_BUDGET_WINDOW: Duration = Duration::from_millis(2);
+
+/// Budget for processing received packets
+///
+/// Under a packet flood, processing all received packets at once starves the
+/// application tasks. Therefore, a single poll processes at most [`RX_BUDGET`]
+/// packets and all polls within [`RX_BUDGET_WINDOW`] at most [`RX_BUDGET_GLOBAL`]
+/// packets. The remaining packets stay in the queue of the device until the
+/// next poll.
+pub(crate) struct RxBudget {
+	/// Start of the current window
+	window_start: Instant,
+	/// Packets, which are processed in the current window
+	used: usize,
+	/// Set, if the last poll left packets in the queue of the device
+	pending: bool,
+	/// Statistics of the polls per core
+	stats: PerCpu<SoftnetStats>,
+}
+
+/// Statistics of the polls of a core
+#[derive(Debug, Default)]
+struct SoftnetStats {
+	/// Number of processed packets
+	processed: AtomicU64,
+	/// Number of polls, which have exhausted their budget
+	squeezed: AtomicU64,
+	/// Number of polls, which have exhausted the global budget
+	throttled: AtomicU64,
+}
+
+impl RxBudget {
+	pub(super) fn new() -> Self {
+		Self {
+			window_start: Instant::ZERO,
+			used: 0,
+			pending: false,
+			stats: alloc_per_cpu(),
+		}
+	}
+
+	/// Returns the number of packets, which the poll at `timestamp` may process.
+	fn acquire(&mut self, timestamp: Instant) -> usize {
+		if timestamp >= self.window_start + RX_BUDGET_WINDOW {
+			self.window_start = timestamp;
+			self.used = 0;
+		}
+
+		RX_BUDGET.min(RX_BUDGET_GLOBAL - self.used)
+	}
+
+	/// Accounts `processed` packets of a poll with the budget `budget`.
+	fn release(&mut self, budget: usize, processed: usize) {
+		let stats = self.stats.get();
+		self.used += processed;
+		stats
+			.processed
+			.fetch_add(u64::try_from(processed).unwrap(), Ordering::Relaxed);
+		self.pending = processed == budget;
+		if self.used == RX_BUDGET_GLOBAL {
+			stats.throttled.fetch_add(1, Ordering::Relaxed);
+		} else if self.pending {
+			stats.squeezed.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	/// Returns the delay until packets, which are left in the queue, can be processed.
+	fn delay(&self, timestamp: Instant) -> Option<Duration> {
+		if !self.pending {
+			None
+		} else if self.used == RX_BUDGET_GLOBAL {
+			let end = self.window_start + RX_BUDGET_WINDOW;
+			Some(if timestamp < end {
+				end - timestamp
+			} else {
+				Duration::ZERO
+			})
+		} else {
+			Some(Duration::ZERO)
+		}
+	}
+}
+
 pub(crate) struct NetworkInterface<'a> {
 	pub(super) iface: smoltcp::iface::Interface,
 	pub(super) sockets: SocketSet<'a>,
 	pub(super) device: HermitNet,
+	pub(super) rx_budget: RxBudget,
+	/// Data, which is held back by corked TCP sockets
+	#[cfg(feature = "tcp")]
+	pub(super) pending_writes: PendingWrites,
+	/// Listening TCP sockets, which defer the acceptance of their connections
+	#[cfg(feature = "tcp")]
+	pub(super) deferred_accepts: DeferredAccepts,
+	/// Receive timestamps of the sockets, which have enabled `SO_TIMESTAMPING`
+	pub(super) rx_timestamps: RxTimestamps,
+	pub(super) routes: RouteTable,
+	/// DHCP socket, which is missing on the loopback device
 	#[cfg(feature = "dhcpv4")]
-	pub(super) dhcp_handle: SocketHandle,
+	pub(super) dhcp_handle: Option<SocketHandle>,
 	#[cfg(feature = "dns")]
 	pub(super) dns_handle: Option<SocketHandle>,
 }
@@ -97,10 +224,16 @@ pub(crate) fn now() -> Instant {
 
 #[cfg(feature = "dhcpv4")]
 async fn dhcpv4_run() {
-	let dhcp_handle = NIC.lock().as_nic_mut().unwrap().dhcp_handle;
+	let Some(dhcp_handle) = tracked_lock!(NIC, "network interface")
+		.as_nic_mut()
+		.unwrap()
+		.dhcp_handle
+	else {
+		return;
+	};
 
 	future::poll_fn(|cx| {
-		let mut guard = NIC.lock();
+		let mut guard = tracked_lock!(NIC, "network interface");
 		let nic = guard.as_nic_mut().unwrap();
 		let socket = nic.sockets.get_mut::<dhcpv4::Socket<'_>>(dhcp_handle);
 
@@ -111,8 +244,26 @@ async fn dhcpv4_run() {
 			Some(dhcpv4::Event::Configured(config)) => {
 				info!("DHCP config acquired!");
 				info!("IP address:      {}", config.address);
+				if let Some(packet) = &config.packet {
+					for option in packet.options() {
+						let Ok(name) = core::str::from_utf8(option.data) else {
+							continue;
+						};
+						let name = name.trim_end_matches('\0');
+						match option.kind {
+							DHCP_OPT_HOST_NAME => crate::hostname::update_hostname_from_dhcp(name),
+							DHCP_OPT_DOMAIN_NAME => {
+								crate::hostname::update_domainname_from_dhcp(name);
+							}
+							_ => {}
+						}
+					}
+				}
 				nic.iface.update_ip_addrs(|addrs| {
-					if let Some(dest) = addrs.iter_mut().next() {
+					if let Some(dest) = addrs
+						.iter_mut()
+						.find(|addr| matches!(addr, IpCidr::Ipv4(_)))
+					{
 						*dest = IpCidr::Ipv4(config.address);
 					} else if addrs.push(IpCidr::Ipv4(config.address)).is_err() {
 						info!("Unable to update IP address");
@@ -120,13 +271,16 @@ async fn dhcpv4_run() {
 				});
 				if let Some(router) = config.router {
 					info!("Default gateway: {}", router);
-					nic.iface
-						.routes_mut()
-						.add_default_ipv4_route(router)
-						.unwrap();
 				} else {
 					info!("Default gateway: None");
-					nic.iface.routes_mut().remove_default_ipv4_route();
+				}
+				nic.routes.replace(
+					RouteProtocol::Dhcp,
+					DEFAULT_IPV4,
+					config.router.map(IpAddress::Ipv4),
+				);
+				if let Err(err) = nic.routes.apply(nic.iface.routes_mut()) {
+					warn!("Unable to install the routes: {err:?}");
 				}
 
 				#[cfg(feature = "dns")]
@@ -147,11 +301,17 @@ async fn dhcpv4_run() {
 				info!("DHCP lost config!");
 				let cidr = Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0);
 				nic.iface.update_ip_addrs(|addrs| {
-					if let Some(dest) = addrs.iter_mut().next() {
+					if let Some(dest) = addrs
+						.iter_mut()
+						.find(|addr| matches!(addr, IpCidr::Ipv4(_)))
+					{
 						*dest = IpCidr::Ipv4(cidr);
 					}
 				});
-				nic.iface.routes_mut().remove_default_ipv4_route();
+				nic.routes.replace(RouteProtocol::Dhcp, DEFAULT_IPV4, None);
+				if let Err(err) = nic.routes.apply(nic.iface.routes_mut()) {
+					warn!("Unable to install the routes: {err:?}");
+				}
 
 				#[cfg(feature = "dns")]
 				{
@@ -190,7 +350,7 @@ async fn network_run() {
 #[cfg(feature = "dns")]
 pub(crate) async fn get_query_result(query: QueryHandle) -> io::Result<Vec<IpAddress>> {
 	future::poll_fn(|cx| {
-		let mut guard = NIC.lock();
+		let mut guard = tracked_lock!(NIC, "network interface");
 		let nic = guard.as_nic_mut().unwrap();
 		let socket = nic.get_mut_dns_socket()?;
 		match socket.get_query_result(query) {
@@ -215,13 +375,204 @@ pub(crate) async fn get_query_result(query: QueryHandle) -> io::Result<Vec<IpAdd
 	.await
 }
 
+/// Returns the addresses of the network interface and the number of its sockets.
+pub(crate) fn report() -> String {
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return String::new();
+	};
+
+	let mut report = format!("hwaddr {}\n", nic.iface.hardware_addr());
+	for addr in nic.iface.ip_addrs() {
+		report += &format!("inet {addr}\n");
+	}
+	report += &format!("sockets {}\n", nic.sockets.iter().count());
+	report
+}
+
+/// Adds a static entry to the neighbor table.
+pub(crate) fn add_neighbor(addr: IpAddress, hwaddr: EthernetAddress) -> io::Result<()> {
+	if !hwaddr.is_unicast() || !addr.is_unicast() {
+		return Err(io::Error::EINVAL);
+	}
+
+	let mut guard = NIC.lock();
+	let nic = guard.as_nic_mut().map_err(|_| io::Error::ENODEV)?;
+	nic.device.neighbors.insert_static(addr, hwaddr);
+	nic.poll_common(now());
+	Ok(())
+}
+
+/// Removes an entry from the neighbor table.
+pub(crate) fn remove_neighbor(addr: IpAddress) -> io::Result<()> {
+	let mut guard = NIC.lock();
+	let nic = guard.as_nic_mut().map_err(|_| io::Error::ENODEV)?;
+	if nic.device.neighbors.remove(&addr) {
+		Ok(())
+	} else {
+		Err(io::Error::ENOENT)
+	}
+}
+
+/// Adds a route to `cidr` via `gateway`.
+pub(crate) fn add_route(cidr: IpCidr, gateway: IpAddress, metric: u32) -> io::Result<()> {
+	let mut guard = NIC.lock();
+	let nic = guard.as_nic_mut().map_err(|_| io::Error::ENODEV)?;
+	nic.routes
+		.add(cidr, gateway, metric, RouteProtocol::Static)?;
+	nic.routes.apply(nic.iface.routes_mut()).inspect_err(|_| {
+		// the route table of smoltcp is full => keep the installed routes
+		nic.routes.remove(cidr, Some(gateway)).unwrap();
+		nic.routes.apply(nic.iface.routes_mut()).unwrap();
+	})
+}
+
+/// Removes the routes to `cidr` via `gateway` or, if `gateway` is `None`, via any gateway.
+pub(crate) fn remove_route(cidr: IpCidr, gateway: Option<IpAddress>) -> io::Result<()> {
+	let mut guard = NIC.lock();
+	let nic = guard.as_nic_mut().map_err(|_| io::Error::ENODEV)?;
+	nic.routes.remove(cidr, gateway)?;
+	nic.routes.apply(nic.iface.routes_mut())
+}
+
+/// Lists the routes of the network interface.
+pub(crate) fn route_report() -> String {
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return String::new();
+	};
+
+	nic.routes.report()
+}
+
+/// Enables or disables the reception of all packets by the network device.
+pub(crate) fn set_promiscuous(on: bool) -> io::Result<()> {
+	let driver = hardware::get_network_driver().ok_or(io::Error::ENODEV)?;
+	driver.lock().set_promiscuous(on)
+}
+
+/// Replaces the unicast and multicast addresses, which are received by the
+/// network device in addition to its own address and broadcasts.
+pub(crate) fn set_mac_filter(
+	unicast: &[EthernetAddress],
+	multicast: &[EthernetAddress],
+) -> io::Result<()> {
+	if unicast.iter().any(|hwaddr| !hwaddr.is_unicast())
+		|| multicast.iter().any(|hwaddr| !hwaddr.is_multicast())
+	{
+		return Err(io::Error::EINVAL);
+	}
+
+	let unicast = unicast.iter().map(|hwaddr| hwaddr.0).collect::<Vec<_>>();
+	let multicast = multicast.iter().map(|hwaddr| hwaddr.0).collect::<Vec<_>>();
+	let driver = hardware::get_network_driver().ok_or(io::Error::ENODEV)?;
+	driver.lock().set_mac_filter(&unicast, &multicast)
+}
+
+/// Largest valid VLAN identifier
+const VLAN_ID_MAX: u16 = 4094;
+
+/// Adds the VLAN `vid` to the VLAN filter of the network device.
+pub(crate) fn add_vlan(vid: u16) -> io::Result<()> {
+	if vid > VLAN_ID_MAX {
+		return Err(io::Error::EINVAL);
+	}
+
+	let driver = hardware::get_network_driver().ok_or(io::Error::ENODEV)?;
+	driver.lock().add_vlan(vid)
+}
+
+/// Removes the VLAN `vid` from the VLAN filter of the network device.
+pub(crate) fn remove_vlan(vid: u16) -> io::Result<()> {
+	if vid > VLAN_ID_MAX {
+		return Err(io::Error::EINVAL);
+	}
+
+	let driver = hardware::get_network_driver().ok_or(io::Error::ENODEV)?;
+	driver.lock().remove_vlan(vid)
+}
+
+/// Sets the number of queue pairs, which are used by the network device.
+pub(crate) fn set_queue_pairs(pairs: u16) -> io::Result<()> {
+	let driver = hardware::get_network_driver().ok_or(io::Error::ENODEV)?;
+	driver.lock().set_queue_pairs(pairs)
+}
+
+/// Lists the entries of the neighbor table.
+pub(crate) fn neighbor_report() -> String {
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return String::new();
+	};
+
+	let mut report = String::from("ADDRESS\tHWADDR\tTYPE\n");
+	for (addr, neighbor) in nic.device.neighbors.iter() {
+		let kind = if neighbor.expires_at.is_some() {
+			"dynamic"
+		} else {
+			"static"
+		};
+		report += &format!("{addr}\t{}\t{kind}\n", neighbor.hwaddr);
+	}
+	report
+}
+
+/// Returns the statistics of the processing of received packets.
+pub(crate) fn softnet_report() -> String {
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return String::new();
+	};
+
+	let mut report = String::from("CPU\tPROCESSED\tSQUEEZED\tTHROTTLED\n");
+	for (core_id, stats) in nic.rx_budget.stats.iter() {
+		report += &format!(
+			"{core_id}\t{}\t{}\t{}\n",
+			stats.processed.load(Ordering::Relaxed),
+			stats.squeezed.load(Ordering::Relaxed),
+			stats.throttled.load(Ordering::Relaxed)
+		);
+	}
+	report
+}
+
+/// Time, in which the pending data of the sockets is transmitted on shutdown
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Transmits the pending data of the sockets on shutdown.
+///
+/// Gives up after [`FLUSH_TIMEOUT`], e.g., if the peers do not acknowledge the data.
+pub(crate) fn exit() {
+	let deadline = now() + FLUSH_TIMEOUT;
+
+	loop {
+		let mut guard = tracked_lock!(NIC, "network interface");
+		let Ok(nic) = guard.as_nic_mut() else {
+			return;
+		};
+
+		let time = now();
+		nic.poll_common(time);
+		if !nic.has_pending_data() {
+			return;
+		}
+		if time >= deadline {
+			warn!("Discard unsent network data on shutdown");
+			return;
+		}
+		drop(guard);
+
+		crate::core_scheduler().reschedule();
+	}
+}
+
 pub(crate) fn init() {
 	info!("Try to initialize network!");
 
 	// initialize variable, which contains the next local endpoint
 	LOCAL_ENDPOINT.store(start_endpoint(), Ordering::Relaxed);
 
-	let mut guard = NIC.lock();
+	let mut guard = tracked_lock!(NIC, "network interface");
 
 	*guard = NetworkInterface::create();
 
@@ -237,15 +588,34 @@ pub(crate) fn init() {
 		#[cfg(feature = "dhcpv4")]
 		spawn(dhcpv4_run());
 	}
+	drop(guard);
+
+	// static neighbors, which are given by `-neigh <address>,<hardware address>`
+	for entry in crate::env::neighbors() {
+		let neighbor = entry.split_once(',').and_then(|(addr, hwaddr)| {
+			Some((
+				IpAddress::from_str(addr).ok()?,
+				EthernetAddress::from_str(hwaddr).ok()?,
+			))
+		});
+		match neighbor {
+			Some((addr, hwaddr)) => {
+				if let Err(err) = add_neighbor(addr, hwaddr) {
+					warn!("Unable to add the static neighbor {addr}: {err:?}");
+				}
+			}
+			None => error!("Invalid static neighbor: {entry}"),
+		}
+	}
 }
 
 impl<'a> NetworkInterface<'a> {
 	#[cfg(feature = "udp")]
 	pub(crate) fn create_udp_handle(&mut self) -> Result<Handle, ()> {
-		let udp_rx_buffer =
-			udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4], vec![0; 0x10000]);
-		let udp_tx_buffer =
-			udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4], vec![0; 0x10000]);
+		let udp_rx_metadata = vec![udp::PacketMetadata::EMPTY; 4];
+		let udp_rx_buffer = udp::PacketBuffer::new(udp_rx_metadata, vec![0; SOCKET_BUFFER_SIZE]);
+		let udp_tx_metadata = vec![udp::PacketMetadata::EMPTY; 4];
+		let udp_tx_buffer = udp::PacketBuffer::new(udp_tx_metadata, vec![0; SOCKET_BUFFER_SIZE]);
 		let udp_socket = udp::Socket::new(udp_rx_buffer, udp_tx_buffer);
 		let udp_handle = self.sockets.add(udp_socket);
 
@@ -254,8 +624,8 @@ impl<'a> NetworkInterface<'a> {
 
 	#[cfg(feature = "tcp")]
 	pub(crate) fn create_tcp_handle(&mut self) -> Result<Handle, ()> {
-		let tcp_rx_buffer = tcp::SocketBuffer::new(vec![0; 0x10000]);
-		let tcp_tx_buffer = tcp::SocketBuffer::new(vec![0; 0x10000]);
+		let tcp_rx_buffer = tcp::SocketBuffer::new(vec![0; SOCKET_BUFFER_SIZE]);
+		let tcp_tx_buffer = tcp::SocketBuffer::new(vec![0; SOCKET_BUFFER_SIZE]);
 		let mut tcp_socket = tcp::Socket::new(tcp_rx_buffer, tcp_tx_buffer);
 		tcp_socket.set_nagle_enabled(true);
 		let tcp_handle = self.sockets.add(tcp_socket);
@@ -263,13 +633,213 @@ impl<'a> NetworkInterface<'a> {
 		Ok(tcp_handle)
 	}
 
+	/// Returns whether a socket has data, which is not sent or acknowledged yet.
+	fn has_pending_data(&self) -> bool {
+		self.sockets.iter().any(|(_, socket)| match socket {
+			#[cfg(feature = "tcp")]
+			Socket::Tcp(socket) => socket.send_queue() > 0,
+			#[cfg(feature = "udp")]
+			Socket::Udp(socket) => socket.send_queue() > 0,
+			#[allow(unreachable_patterns)]
+			_ => false,
+		})
+	}
+
+	/// Applies the changes of the IPv6 autoconfiguration to the interface and the routes.
+	fn poll_slaac(&mut self, timestamp: Instant) {
+		self.device.poll_slaac(timestamp);
+		while let Some(event) = self.device.slaac.as_mut().and_then(Slaac::pop_event) {
+			match event {
+				SlaacEvent::AddressAdded(cidr) => {
+					self.iface.update_ip_addrs(|addrs| {
+						if addrs.push(IpCidr::Ipv6(cidr)).is_err() {
+							warn!("Unable to add the IPv6 address {cidr}");
+						}
+					});
+				}
+				SlaacEvent::AddressRemoved(cidr) => {
+					self.iface
+						.update_ip_addrs(|addrs| addrs.retain(|addr| *addr != IpCidr::Ipv6(cidr)));
+				}
+				SlaacEvent::Router(router) => {
+					self.routes.replace(
+						RouteProtocol::Ra,
+						DEFAULT_IPV6,
+						router.map(IpAddress::Ipv6),
+					);
+					if let Err(err) = self.routes.apply(self.iface.routes_mut()) {
+						warn!("Unable to install the routes: {err:?}");
+					}
+				}
+			}
+		}
+	}
+
+	/// Processes the received packets within the budget and transmits the queued packets.
 	pub(crate) fn poll_common(&mut self, timestamp: Instant) -> PollResult {
-		self.iface
-			.poll(timestamp, &mut self.device, &mut self.sockets)
+		let mut result = PollResult::None;
+
+		let HardwareAddress::Ethernet(hwaddr) = self.iface.hardware_addr();
+		self.device
+			.neighbors
+			.refresh(timestamp, hwaddr, self.iface.ip_addrs());
+		self.poll_slaac(timestamp);
+
+		let budget = self.rx_budget.acquire(timestamp);
+		let mut processed = 0;
+		while processed < budget {
+			match self
+				.iface
+				.poll_ingress_single(timestamp, &mut self.device, &mut self.sockets)
+			{
+				PollIngressSingleResult::None => break,
+				PollIngressSingleResult::PacketProcessed => {}
+				PollIngressSingleResult::SocketStateChanged => {
+					result = PollResult::SocketStateChanged;
+				}
+			}
+			if let Some(stamp) = self.device.rx_stamp.take() {
+				self.rx_timestamps.dispatch(&self.sockets, stamp);
+			}
+			processed += 1;
+		}
+		self.rx_budget.release(budget, processed);
+		timestamp::poll_done();
+
+		#[cfg(feature = "tcp")]
+		{
+			self.pending_writes.poll(&mut self.sockets, timestamp);
+			self.deferred_accepts.poll(&self.sockets, timestamp);
+		}
+
+		if self
+			.iface
+			.poll_egress(timestamp, &mut self.device, &mut self.sockets)
+			== PollResult::SocketStateChanged
+		{
+			result = PollResult::SocketStateChanged;
+		}
+
+		result
 	}
 
 	pub(crate) fn poll_delay(&mut self, timestamp: Instant) -> Option<Duration> {
-		self.iface.poll_delay(timestamp, &self.sockets)
+		let delay = self.iface.poll_delay(timestamp, &self.sockets);
+		#[cfg(feature = "tcp")]
+		let delay = match (delay, self.pending_writes.delay(timestamp)) {
+			(Some(delay), Some(cork_delay)) => Some(delay.min(cork_delay)),
+			(delay, cork_delay) => delay.or(cork_delay),
+		};
+		#[cfg(feature = "tcp")]
+		let delay = match (delay, self.deferred_accepts.delay(timestamp)) {
+			(Some(delay), Some(defer_delay)) => Some(delay.min(defer_delay)),
+			(delay, defer_delay) => delay.or(defer_delay),
+		};
+		let delay = match (delay, self.rx_budget.delay(timestamp)) {
+			(Some(delay), Some(rx_delay)) => Some(delay.min(rx_delay)),
+			(delay, rx_delay) => delay.or(rx_delay),
+		};
+		match (delay, self.device.poll_delay(timestamp)) {
+			(Some(delay), Some(device_delay)) => Some(delay.min(device_delay)),
+			(delay, device_delay) => delay.or(device_delay),
+		}
+	}
+
+	/// Writes the data of `bufs` after the first `skip` bytes to the TCP socket
+	/// `handle`, which may hold back small writes.
+	///
+	/// See [`PendingWrites::send`] for details.
+	#[cfg(feature = "tcp")]
+	pub(crate) fn tcp_send(
+		&mut self,
+		handle: Handle,
+		bufs: &[&[u8]],
+		skip: usize,
+		autocork: bool,
+	) -> Result<usize, tcp::SendError> {
+		let segment_size = self.device.capabilities().max_transmission_unit;
+		self.pending_writes.send(
+			&mut self.sockets,
+			handle,
+			bufs,
+			skip,
+			segment_size,
+			autocork,
+			now(),
+		)
+	}
+
+	/// Enables or disables the receive timestamps of the socket `handle` (`SO_TIMESTAMPING`).
+	pub(crate) fn set_rx_timestamping(&mut self, handle: Handle, enabled: bool) {
+		self.rx_timestamps.set_enabled(handle, enabled);
+		self.device.stamping = self.rx_timestamps.is_active();
+	}
+
+	pub(crate) fn is_rx_timestamping(&self, handle: Handle) -> bool {
+		self.rx_timestamps.is_enabled(handle)
+	}
+
+	/// Returns the receive timestamp of the next datagram of `source` to the socket `handle`.
+	pub(crate) fn take_rx_timestamp(
+		&mut self,
+		handle: Handle,
+		source: IpEndpoint,
+		peek: bool,
+	) -> Option<u64> {
+		self.rx_timestamps.take(handle, source, peek)
+	}
+
+	/// Returns the receive timestamp of the latest segment to the socket `handle`.
+	pub(crate) fn take_latest_rx_timestamp(&mut self, handle: Handle, peek: bool) -> Option<u64> {
+		self.rx_timestamps.take_latest(handle, peek)
+	}
+
+	/// Corks or uncorks the TCP socket `handle` (`TCP_CORK`).
+	#[cfg(feature = "tcp")]
+	pub(crate) fn set_tcp_corked(&mut self, handle: Handle, corked: bool) {
+		self.pending_writes
+			.set_corked(&mut self.sockets, handle, corked);
+	}
+
+	#[cfg(feature = "tcp")]
+	pub(crate) fn is_tcp_corked(&self, handle: Handle) -> bool {
+		self.pending_writes.is_corked(handle)
+	}
+
+	/// Passes the held back data of the TCP socket `handle` to the socket.
+	#[cfg(feature = "tcp")]
+	pub(crate) fn flush_tcp(&mut self, handle: Handle) {
+		self.pending_writes.flush(&mut self.sockets, handle);
+	}
+
+	/// Defers the acceptance of the connection of the listening TCP socket
+	/// `handle` by up to `timeout` (`TCP_DEFER_ACCEPT`) or, if `timeout` is
+	/// `None`, reports the connection as soon as it is established.
+	#[cfg(feature = "tcp")]
+	pub(crate) fn set_defer_accept(&mut self, handle: Handle, timeout: Option<Duration>) {
+		self.deferred_accepts.set(handle, timeout);
+	}
+
+	#[cfg(feature = "tcp")]
+	pub(crate) fn defer_accept(&self, handle: Handle) -> Option<Duration> {
+		self.deferred_accepts.timeout(handle)
+	}
+
+	/// Returns `true`, if the connection of the listening TCP socket `handle` can be accepted.
+	#[cfg(feature = "tcp")]
+	pub(crate) fn is_acceptable(&self, handle: Handle) -> bool {
+		self.deferred_accepts
+			.is_acceptable(&self.sockets, handle, now())
+	}
+
+	/// Registers `waker` to be woken, when the connection of the listening
+	/// TCP socket `handle` may have become acceptable.
+	#[cfg(feature = "tcp")]
+	pub(crate) fn register_accept_waker(&mut self, handle: Handle, waker: &Waker) {
+		self.sockets
+			.get_mut::<tcp::Socket<'_>>(handle)
+			.register_recv_waker(waker);
+		self.deferred_accepts.register_waker(handle, waker);
 	}
 
 	#[allow(dead_code)]
@@ -288,9 +858,37 @@ impl<'a> NetworkInterface<'a> {
 		(self.sockets.get_mut(handle), self.iface.context())
 	}
 
+	/// Returns the first configured address of the interface,
+	/// which belongs to the requested address family.
+	pub(crate) fn local_address(&self, ipv6: bool) -> Option<IpAddress> {
+		self.iface
+			.ip_addrs()
+			.iter()
+			.map(|cidr| cidr.address())
+			.find(|addr| addr.is_unicast() && matches!(addr, IpAddress::Ipv6(_)) == ipv6)
+	}
+
+	/// Returns the gateway of the default IPv4 route with the lowest metric.
+	pub(crate) fn ipv4_gateway(&self) -> Option<Ipv4Address> {
+		self.routes
+			.iter()
+			.filter(|route| route.cidr == DEFAULT_IPV4)
+			.min_by_key(|route| route.metric)
+			.and_then(|route| match route.gateway {
+				IpAddress::Ipv4(gateway) => Some(gateway),
+				IpAddress::Ipv6(_) => None,
+			})
+	}
+
 	pub(crate) fn destroy_socket(&mut self, handle: Handle) {
 		// This deallocates the socket's buffers
 		self.sockets.remove(handle);
+		#[cfg(feature = "tcp")]
+		{
+			self.pending_writes.remove(handle);
+			self.deferred_accepts.set(handle, None);
+		}
+		self.set_rx_timestamping(handle, false);
 	}
 
 	#[cfg(feature = "dns")]
@@ -0,0 +1,185 @@
+//! Route table of the network interface
+//!
+//! smoltcp selects a route only by the longest prefix and does not know metrics.
+//! Therefore, the kernel keeps all routes with their metric and installs for
+//! each destination only the route with the lowest metric into smoltcp. If
+//! this route is removed, the route with the next higher metric takes over.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use smoltcp::iface::{Route, Routes};
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr};
+
+use crate::io;
+
+/// Origin of a route
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum RouteProtocol {
+	/// Configured at boot time
+	Boot,
+	/// Configured by DHCP
+	Dhcp,
+	/// Learned from router advertisements
+	Ra,
+	/// Added at runtime
+	Static,
+}
+
+impl fmt::Display for RouteProtocol {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			RouteProtocol::Boot => f.write_str("boot"),
+			RouteProtocol::Dhcp => f.write_str("dhcp"),
+			RouteProtocol::Ra => f.write_str("ra"),
+			RouteProtocol::Static => f.write_str("static"),
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct RouteEntry {
+	pub cidr: IpCidr,
+	pub gateway: IpAddress,
+	pub metric: u32,
+	pub protocol: RouteProtocol,
+}
+
+/// Destination of the default IPv4 route
+pub(crate) const DEFAULT_IPV4: IpCidr = IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0));
+/// Destination of the default IPv6 route
+pub(crate) const DEFAULT_IPV6: IpCidr = IpCidr::Ipv6(Ipv6Cidr::new(Ipv6Address::UNSPECIFIED, 0));
+
+/// Returns the network of `cidr` without the host bits.
+fn network(cidr: IpCidr) -> IpCidr {
+	match cidr {
+		IpCidr::Ipv4(cidr) => IpCidr::Ipv4(cidr.network()),
+		IpCidr::Ipv6(cidr) => {
+			let mask = u128::MAX
+				.checked_shl(128 - u32::from(cidr.prefix_len()))
+				.unwrap_or(0);
+			let address = Ipv6Address::from_bits(cidr.address().to_bits() & mask);
+			IpCidr::Ipv6(Ipv6Cidr::new(address, cidr.prefix_len()))
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RouteTable(Vec<RouteEntry>);
+
+impl RouteTable {
+	pub const fn new() -> Self {
+		Self(Vec::new())
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &RouteEntry> {
+		self.0.iter()
+	}
+
+	/// Adds a route to `cidr` via `gateway`.
+	pub fn add(
+		&mut self,
+		cidr: IpCidr,
+		gateway: IpAddress,
+		metric: u32,
+		protocol: RouteProtocol,
+	) -> io::Result<()> {
+		let cidr = network(cidr);
+		if cidr.address().version() != gateway.version() || !gateway.is_unicast() {
+			return Err(io::Error::EINVAL);
+		}
+
+		if self
+			.0
+			.iter()
+			.any(|route| route.cidr == cidr && route.gateway == gateway)
+		{
+			return Err(io::Error::EEXIST);
+		}
+
+		self.0.push(RouteEntry {
+			cidr,
+			gateway,
+			metric,
+			protocol,
+		});
+		Ok(())
+	}
+
+	/// Removes the routes to `cidr` via `gateway` or, if `gateway` is `None`,
+	/// via any gateway.
+	pub fn remove(&mut self, cidr: IpCidr, gateway: Option<IpAddress>) -> io::Result<()> {
+		let cidr = network(cidr);
+		let len = self.0.len();
+		self.0.retain(|route| {
+			route.cidr != cidr || gateway.is_some_and(|gateway| route.gateway != gateway)
+		});
+
+		if self.0.len() < len {
+			Ok(())
+		} else {
+			Err(io::Error::ENOENT)
+		}
+	}
+
+	/// Replaces the routes of `protocol` to `cidr` by a route via `gateway`.
+	pub fn replace(&mut self, protocol: RouteProtocol, cidr: IpCidr, gateway: Option<IpAddress>) {
+		self.0
+			.retain(|route| route.protocol != protocol || route.cidr != cidr);
+		if let Some(gateway) = gateway {
+			if let Err(err) = self.add(cidr, gateway, 0, protocol) {
+				warn!("Unable to add the route to {cidr} via {gateway}: {err:?}");
+			}
+		}
+	}
+
+	/// Installs the preferred route of each destination into `routes`.
+	pub fn apply(&self, routes: &mut Routes) -> io::Result<()> {
+		let mut result = Ok(());
+		routes.update(|storage| {
+			storage.clear();
+			for route in &self.0 {
+				let preferred = self
+					.0
+					.iter()
+					.filter(|other| other.cidr == route.cidr)
+					.min_by_key(|other| other.metric)
+					.unwrap();
+				if !core::ptr::eq(preferred, route) {
+					continue;
+				}
+
+				let route = Route {
+					cidr: route.cidr,
+					via_router: route.gateway,
+					preferred_until: None,
+					expires_at: None,
+				};
+				if storage.push(route).is_err() {
+					result = Err(io::Error::ENOSPC);
+				}
+			}
+		});
+		result
+	}
+
+	/// Lists the routes in the format of `ip route`.
+	pub fn report(&self) -> String {
+		let mut report = String::new();
+		for route in &self.0 {
+			if route.cidr.prefix_len() == 0 {
+				report += "default";
+			} else {
+				write!(report, "{}", route.cidr).unwrap();
+			}
+			writeln!(
+				report,
+				" via {} proto {} metric {}",
+				route.gateway, route.protocol, route.metric
+			)
+			.unwrap();
+		}
+		report
+	}
+}
@@ -0,0 +1,397 @@
+//! Stateless address autoconfiguration of IPv6 (SLAAC)
+//!
+//! smoltcp does not configure IPv6 addresses on its own. Therefore, the kernel
+//! derives the link-local address from the hardware address (modified EUI-64),
+//! solicits the routers on the link and forms a global address from each
+//! prefix, which a router advertises for autoconfiguration. Before an address
+//! is assigned, duplicate address detection checks with a neighbor
+//! solicitation, that no other node on the link uses it.
+//!
+//! Like the neighbor table, the router advertisements are snooped in the
+//! receive path of the device. The default router and the addresses expire
+//! after the lifetimes of the advertisements. smoltcp does not know deprecated
+//! addresses, so that the preferred lifetime is ignored.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{
+	ETHERNET_HEADER_LEN, EthernetAddress, EthernetFrame, EthernetProtocol, IPV6_HEADER_LEN,
+	Icmpv6Packet, Icmpv6Repr, IpProtocol, Ipv6Address, Ipv6Cidr, Ipv6Packet, Ipv6Repr,
+	NdiscPrefixInfoFlags, NdiscPrefixInformation, NdiscRepr, RawHardwareAddress,
+};
+
+use super::neighbor::ethernet_frame;
+
+/// Number of router solicitations, which are sent at startup
+const MAX_RTR_SOLICITATIONS: u8 = 3;
+/// Interval between the router solicitations
+const RTR_SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+/// Time, for which duplicate address detection waits for an answer
+const RETRANS_TIMER: Duration = Duration::from_secs(1);
+/// Lower bound of the valid lifetime, which an unauthenticated advertisement may set
+const MIN_VALID_LIFETIME: Duration = Duration::from_secs(2 * 60 * 60);
+/// Valid lifetime of an advertised prefix, which never expires
+const INFINITE_LIFETIME: Duration = Duration::from_secs(0xffff_ffff);
+/// Length of the prefix, to which the interface identifier is appended
+const PREFIX_LEN: u8 = 64;
+
+/// All-routers multicast address `ff02::2`
+const ALL_ROUTERS: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+/// Change of the configuration, which has to be applied to the interface
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum SlaacEvent {
+	/// An address has passed duplicate address detection
+	AddressAdded(Ipv6Cidr),
+	/// The valid lifetime of an address has elapsed
+	AddressRemoved(Ipv6Cidr),
+	/// The default router has changed
+	Router(Option<Ipv6Address>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum AddressState {
+	/// The neighbor solicitation of duplicate address detection is not sent yet
+	Unprobed,
+	/// Duplicate address detection waits for an answer until the given time
+	Tentative(Instant),
+	/// The address is used by the interface
+	Assigned,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct AutoAddress {
+	cidr: Ipv6Cidr,
+	state: AddressState,
+	/// End of the valid lifetime or `None`, if the address never expires
+	expires_at: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Slaac {
+	hwaddr: EthernetAddress,
+	addresses: Vec<AutoAddress>,
+	/// Default router and the end of its lifetime
+	router: Option<(Ipv6Address, Instant)>,
+	/// Number of router solicitations, which are still sent
+	solicitations: u8,
+	/// Time, at which the next router solicitation is sent
+	next_solicitation: Instant,
+	/// Frames, which are transmitted with the next poll
+	outgoing: VecDeque<Vec<u8>>,
+	events: VecDeque<SlaacEvent>,
+}
+
+impl Slaac {
+	/// Starts the autoconfiguration of the interface with the hardware address `hwaddr`.
+	pub fn new(hwaddr: EthernetAddress) -> Self {
+		let mut slaac = Self {
+			hwaddr,
+			addresses: Vec::new(),
+			router: None,
+			solicitations: MAX_RTR_SOLICITATIONS,
+			next_solicitation: Instant::ZERO,
+			outgoing: VecDeque::new(),
+			events: VecDeque::new(),
+		};
+
+		let link_local = slaac.address(Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0));
+		slaac.addresses.push(AutoAddress {
+			cidr: Ipv6Cidr::new(link_local, PREFIX_LEN),
+			state: AddressState::Unprobed,
+			expires_at: None,
+		});
+		slaac
+	}
+
+	/// Returns the address of `prefix` with the interface identifier, which
+	/// is derived from the hardware address (modified EUI-64).
+	fn address(&self, prefix: Ipv6Address) -> Ipv6Address {
+		let mac = self.hwaddr.0;
+		let interface_id = u64::from_be_bytes([
+			mac[0] ^ 0x02,
+			mac[1],
+			mac[2],
+			0xff,
+			0xfe,
+			mac[3],
+			mac[4],
+			mac[5],
+		]);
+		Ipv6Address::from_bits(
+			(prefix.to_bits() & !u128::from(u64::MAX)) | u128::from(interface_id),
+		)
+	}
+
+	/// Returns the assigned link-local address.
+	fn link_local(&self) -> Option<Ipv6Address> {
+		self.addresses
+			.iter()
+			.filter(|addr| addr.state == AddressState::Assigned)
+			.map(|addr| addr.cidr.address())
+			.find(Ipv6Address::is_unicast_link_local)
+	}
+
+	/// Returns the next frame, which has to be transmitted.
+	pub fn pop_frame(&mut self) -> Option<Vec<u8>> {
+		self.outgoing.pop_front()
+	}
+
+	/// Returns the next change of the configuration.
+	pub fn pop_event(&mut self) -> Option<SlaacEvent> {
+		self.events.pop_front()
+	}
+
+	/// Returns the time, at which [`Self::poll`] has to be called again.
+	pub fn poll_at(&self) -> Option<Instant> {
+		let addresses = self.addresses.iter().flat_map(|addr| {
+			let state = match addr.state {
+				AddressState::Unprobed => Some(Instant::ZERO),
+				AddressState::Tentative(until) => Some(until),
+				AddressState::Assigned => None,
+			};
+			state.into_iter().chain(addr.expires_at)
+		});
+		let router = self.router.map(|(_, until)| until);
+		let solicitation = (self.solicitations > 0 && self.link_local().is_some())
+			.then_some(self.next_solicitation);
+
+		addresses.chain(router).chain(solicitation).min()
+	}
+
+	/// Sends the pending probes and solicitations and expires the addresses and the router.
+	pub fn poll(&mut self, timestamp: Instant) {
+		for addr in &mut self.addresses {
+			match addr.state {
+				AddressState::Unprobed => {
+					self.outgoing
+						.push_back(neighbor_solicit(self.hwaddr, addr.cidr.address()));
+					addr.state = AddressState::Tentative(timestamp + RETRANS_TIMER);
+				}
+				AddressState::Tentative(until) if until <= timestamp => {
+					info!("Configure network interface with address {}", addr.cidr);
+					addr.state = AddressState::Assigned;
+					self.events.push_back(SlaacEvent::AddressAdded(addr.cidr));
+				}
+				_ => {}
+			}
+		}
+
+		self.addresses.retain(|addr| {
+			if addr
+				.expires_at
+				.is_none_or(|expires_at| expires_at > timestamp)
+			{
+				return true;
+			}
+			if addr.state == AddressState::Assigned {
+				info!("The address {} has expired", addr.cidr);
+				self.events.push_back(SlaacEvent::AddressRemoved(addr.cidr));
+			}
+			false
+		});
+
+		if self.router.is_some_and(|(_, until)| until <= timestamp) {
+			info!("The default router has expired");
+			self.router = None;
+			self.events.push_back(SlaacEvent::Router(None));
+		}
+
+		if self.solicitations > 0 && timestamp >= self.next_solicitation {
+			if let Some(src_addr) = self.link_local() {
+				self.outgoing
+					.push_back(router_solicit(self.hwaddr, src_addr));
+				self.solicitations -= 1;
+				self.next_solicitation = timestamp + RTR_SOLICITATION_INTERVAL;
+			}
+		}
+	}
+
+	/// Processes a router advertisement or an answer to duplicate address
+	/// detection in the received `frame`.
+	pub fn snoop(&mut self, frame: &[u8], timestamp: Instant) {
+		let Ok(frame) = EthernetFrame::new_checked(frame) else {
+			return;
+		};
+		if frame.ethertype() != EthernetProtocol::Ipv6 {
+			return;
+		}
+		let Ok(packet) = Ipv6Packet::new_checked(frame.payload()) else {
+			return;
+		};
+		// neighbor discovery does not cross routers
+		if packet.next_header() != IpProtocol::Icmpv6 || packet.hop_limit() != 0xff {
+			return;
+		}
+		let Ok(icmp) = Icmpv6Packet::new_checked(packet.payload()) else {
+			return;
+		};
+		let src_addr = packet.src_addr();
+		let Ok(Icmpv6Repr::Ndisc(repr)) = Icmpv6Repr::parse(
+			&src_addr,
+			&packet.dst_addr(),
+			&icmp,
+			&ChecksumCapabilities::default(),
+		) else {
+			return;
+		};
+
+		match repr {
+			NdiscRepr::RouterAdvert {
+				router_lifetime,
+				prefix_info,
+				..
+			} if src_addr.is_unicast_link_local() => {
+				self.solicitations = 0;
+				self.update_router(src_addr, router_lifetime, timestamp);
+				if let Some(prefix_info) = prefix_info {
+					self.update_prefix(&prefix_info, timestamp);
+				}
+			}
+			NdiscRepr::NeighborSolicit { target_addr, .. } if src_addr.is_unspecified() => {
+				self.detect_duplicate(target_addr);
+			}
+			NdiscRepr::NeighborAdvert { target_addr, .. } => {
+				self.detect_duplicate(target_addr);
+			}
+			_ => {}
+		}
+	}
+
+	fn update_router(&mut self, addr: Ipv6Address, lifetime: Duration, timestamp: Instant) {
+		let previous = self.router.map(|(router, _)| router);
+		if lifetime == Duration::ZERO {
+			// the router is no longer a default router
+			if previous == Some(addr) {
+				self.router = None;
+				self.events.push_back(SlaacEvent::Router(None));
+			}
+			return;
+		}
+
+		self.router = Some((addr, timestamp + lifetime));
+		if previous != Some(addr) {
+			info!("Configure IPv6 gateway with address {addr}");
+			self.events.push_back(SlaacEvent::Router(Some(addr)));
+		}
+	}
+
+	fn update_prefix(&mut self, prefix_info: &NdiscPrefixInformation, timestamp: Instant) {
+		if !prefix_info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+			|| prefix_info.prefix_len != PREFIX_LEN
+			|| prefix_info.prefix.is_unicast_link_local()
+			|| prefix_info.preferred_lifetime > prefix_info.valid_lifetime
+		{
+			return;
+		}
+
+		let valid_lifetime = prefix_info.valid_lifetime;
+		let expires_at = (valid_lifetime != INFINITE_LIFETIME).then(|| timestamp + valid_lifetime);
+		let cidr = Ipv6Cidr::new(self.address(prefix_info.prefix), PREFIX_LEN);
+
+		let Some(addr) = self.addresses.iter_mut().find(|addr| addr.cidr == cidr) else {
+			if valid_lifetime > Duration::ZERO {
+				self.addresses.push(AutoAddress {
+					cidr,
+					state: AddressState::Unprobed,
+					expires_at,
+				});
+			}
+			return;
+		};
+
+		// An advertisement may shorten the remaining lifetime at most to two
+		// hours, so that a forged advertisement cannot remove the address (RFC 4862, 5.5.3).
+		let remaining = addr.expires_at.map(|at| {
+			if at > timestamp {
+				at - timestamp
+			} else {
+				Duration::ZERO
+			}
+		});
+		if expires_at.is_none()
+			|| valid_lifetime > MIN_VALID_LIFETIME
+			|| remaining.is_some_and(|remaining| valid_lifetime > remaining)
+		{
+			addr.expires_at = expires_at;
+		} else if remaining.is_none_or(|remaining| remaining > MIN_VALID_LIFETIME) {
+			addr.expires_at = Some(timestamp + MIN_VALID_LIFETIME);
+		}
+	}
+
+	/// Discards the tentative address `target_addr`, because another node uses it.
+	fn detect_duplicate(&mut self, target_addr: Ipv6Address) {
+		self.addresses.retain(|addr| {
+			let duplicate =
+				addr.cidr.address() == target_addr && addr.state != AddressState::Assigned;
+			if duplicate {
+				warn!("The address {target_addr} is already used by another node");
+			}
+			!duplicate
+		});
+	}
+}
+
+/// Returns the solicited-node multicast address of `addr`.
+fn solicited_node(addr: Ipv6Address) -> Ipv6Address {
+	Ipv6Address::from_bits(0xff02_0000_0000_0000_0000_0001_ff00_0000 | (addr.to_bits() & 0xff_ffff))
+}
+
+/// Creates a neighbor discovery message `repr` from `src_addr` to the multicast address `dst_addr`.
+fn ndisc_frame(
+	hwaddr: EthernetAddress,
+	src_addr: Ipv6Address,
+	dst_addr: Ipv6Address,
+	repr: NdiscRepr<'_>,
+) -> Vec<u8> {
+	let icmp_repr = Icmpv6Repr::Ndisc(repr);
+	let ip_repr = Ipv6Repr {
+		src_addr,
+		dst_addr,
+		next_header: IpProtocol::Icmpv6,
+		payload_len: icmp_repr.buffer_len(),
+		hop_limit: 0xff,
+	};
+
+	// multicast addresses are mapped to 33:33 followed by the last 32 bits
+	let group = dst_addr.octets();
+	let dst_hwaddr = EthernetAddress([0x33, 0x33, group[12], group[13], group[14], group[15]]);
+	let mut buffer = ethernet_frame(
+		hwaddr,
+		dst_hwaddr,
+		EthernetProtocol::Ipv6,
+		IPV6_HEADER_LEN + icmp_repr.buffer_len(),
+	);
+	let payload = &mut buffer[ETHERNET_HEADER_LEN..];
+	ip_repr.emit(&mut Ipv6Packet::new_unchecked(&mut payload[..]));
+	icmp_repr.emit(
+		&src_addr,
+		&dst_addr,
+		&mut Icmpv6Packet::new_unchecked(&mut payload[IPV6_HEADER_LEN..]),
+		&ChecksumCapabilities::default(),
+	);
+	buffer
+}
+
+/// Creates the neighbor solicitation of duplicate address detection for `target_addr`.
+fn neighbor_solicit(hwaddr: EthernetAddress, target_addr: Ipv6Address) -> Vec<u8> {
+	ndisc_frame(
+		hwaddr,
+		Ipv6Address::UNSPECIFIED,
+		solicited_node(target_addr),
+		NdiscRepr::NeighborSolicit {
+			target_addr,
+			lladdr: None,
+		},
+	)
+}
+
+/// Creates a router solicitation from the link-local address `src_addr`.
+fn router_solicit(hwaddr: EthernetAddress, src_addr: Ipv6Address) -> Vec<u8> {
+	ndisc_frame(hwaddr, src_addr, ALL_ROUTERS, NdiscRepr::RouterSolicit {
+		lladdr: Some(RawHardwareAddress::from(hwaddr)),
+	})
+}
@@ -0,0 +1,251 @@
+//! Software receive timestamps (`SO_TIMESTAMPING`)
+//!
+//! The interrupt handler of the network device captures the time of the
+//! interrupt. The frames, which are received in response to the interrupt, are
+//! stamped with this time. In polling mode, a frame is stamped with the time, at
+//! which it is taken from the device.
+//!
+//! As long as a socket has enabled timestamping, the TCP and UDP frames are
+//! assigned to their sockets. The timestamps are queued per socket, until the
+//! application receives the data by `recvmsg`. If the device reports the hash
+//! of the flow, the socket of a flow is only searched for its first frame.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use smoltcp::iface::{SocketHandle, SocketSet};
+#[cfg(feature = "tcp")]
+use smoltcp::socket::tcp;
+#[cfg(feature = "udp")]
+use smoltcp::socket::udp;
+use smoltcp::socket::{AnySocket, Socket};
+use smoltcp::wire::{
+	EthernetFrame, EthernetProtocol, IpAddress, IpEndpoint, IpProtocol, Ipv4Packet, Ipv6Packet,
+	TcpPacket, UdpPacket,
+};
+
+use crate::arch;
+
+/// Maximum number of timestamps, which are queued per socket
+const MAX_QUEUED: usize = 64;
+
+/// Maximum number of flows, whose sockets are remembered
+const MAX_FLOWS: usize = 256;
+
+/// Time of the last network interrupt in microseconds since boot, zero if none
+static IRQ_TIME: AtomicU64 = AtomicU64::new(0);
+
+/// Captures the time of a network interrupt.
+pub(crate) fn interrupt() {
+	IRQ_TIME.store(arch::processor::get_timer_ticks(), Ordering::Relaxed);
+}
+
+/// Resets the time of the last interrupt after the received frames have been processed.
+pub(crate) fn poll_done() {
+	IRQ_TIME.store(0, Ordering::Relaxed);
+}
+
+/// Returns the receive time of the frames, which are currently taken from the device.
+fn rx_time() -> u64 {
+	match IRQ_TIME.load(Ordering::Relaxed) {
+		0 => arch::processor::get_timer_ticks(),
+		time => time,
+	}
+}
+
+/// Timestamp of a received TCP or UDP frame
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct RxStamp {
+	protocol: IpProtocol,
+	source: IpEndpoint,
+	port: u16,
+	/// Hash of the flow, if reported by the device
+	flow_hash: Option<u32>,
+	time: u64,
+}
+
+impl RxStamp {
+	/// Stamps the Ethernet frame `frame`, if it contains a TCP segment with
+	/// payload or a UDP datagram.
+	pub fn new(frame: &[u8], flow_hash: Option<u32>) -> Option<Self> {
+		let frame = EthernetFrame::new_checked(frame).ok()?;
+		let (src_addr, protocol, payload) = match frame.ethertype() {
+			EthernetProtocol::Ipv4 => {
+				let packet = Ipv4Packet::new_checked(frame.payload()).ok()?;
+				(
+					IpAddress::Ipv4(packet.src_addr()),
+					packet.next_header(),
+					packet.payload(),
+				)
+			}
+			EthernetProtocol::Ipv6 => {
+				let packet = Ipv6Packet::new_checked(frame.payload()).ok()?;
+				(
+					IpAddress::Ipv6(packet.src_addr()),
+					packet.next_header(),
+					packet.payload(),
+				)
+			}
+			_ => return None,
+		};
+
+		let (src_port, port) = match protocol {
+			IpProtocol::Tcp => {
+				let segment = TcpPacket::new_checked(payload).ok()?;
+				if segment.payload().is_empty() {
+					return None;
+				}
+				(segment.src_port(), segment.dst_port())
+			}
+			IpProtocol::Udp => {
+				let datagram = UdpPacket::new_checked(payload).ok()?;
+				(datagram.src_port(), datagram.dst_port())
+			}
+			_ => return None,
+		};
+
+		Some(Self {
+			protocol,
+			source: IpEndpoint::new(src_addr, src_port),
+			port,
+			flow_hash,
+			time: rx_time(),
+		})
+	}
+}
+
+/// Receive timestamps of the sockets, which have enabled timestamping
+#[derive(Debug, Default)]
+pub(crate) struct RxTimestamps {
+	sockets: BTreeMap<SocketHandle, VecDeque<(IpEndpoint, u64)>>,
+	/// Sockets, which have enabled timestamping, indexed by protocol and hash of their flows
+	flows: BTreeMap<(u8, u32), SocketHandle>,
+}
+
+impl RxTimestamps {
+	pub const fn new() -> Self {
+		Self {
+			sockets: BTreeMap::new(),
+			flows: BTreeMap::new(),
+		}
+	}
+
+	/// Returns `true`, if any socket has enabled timestamping.
+	pub fn is_active(&self) -> bool {
+		!self.sockets.is_empty()
+	}
+
+	pub fn is_enabled(&self, handle: SocketHandle) -> bool {
+		self.sockets.contains_key(&handle)
+	}
+
+	/// Enables or disables timestamping for the socket `handle`.
+	pub fn set_enabled(&mut self, handle: SocketHandle, enabled: bool) {
+		if enabled {
+			self.sockets.entry(handle).or_default();
+		} else {
+			self.sockets.remove(&handle);
+			self.flows.retain(|_, flow_handle| *flow_handle != handle);
+		}
+	}
+
+	/// Assigns `stamp` to the socket, which receives the frame.
+	pub fn dispatch(&mut self, sockets: &SocketSet<'_>, stamp: RxStamp) {
+		let flow = stamp
+			.flow_hash
+			.map(|flow_hash| (u8::from(stamp.protocol), flow_hash));
+		let cached = flow
+			.and_then(|flow| self.flows.get(&flow).copied())
+			.filter(|handle| Self::is_receiver(sockets, *handle, &stamp));
+		let Some(handle) = cached.or_else(|| Self::receiver(sockets, &stamp)) else {
+			return;
+		};
+		let Some(queue) = self.sockets.get_mut(&handle) else {
+			return;
+		};
+
+		if let Some(flow) = flow.filter(|_| cached.is_none()) {
+			if self.flows.len() == MAX_FLOWS {
+				self.flows.clear();
+			}
+			self.flows.insert(flow, handle);
+		}
+
+		if queue.len() == MAX_QUEUED {
+			queue.pop_front();
+		}
+		queue.push_back((stamp.source, stamp.time));
+	}
+
+	/// Finds the socket, which receives the frame of `stamp`.
+	fn receiver(sockets: &SocketSet<'_>, stamp: &RxStamp) -> Option<SocketHandle> {
+		sockets
+			.iter()
+			.find(|(_, socket)| Self::receives(socket, stamp))
+			.map(|(handle, _)| handle)
+	}
+
+	/// Checks whether the socket `handle` of a remembered flow still receives the frame of `stamp`.
+	///
+	/// The flows are indexed by protocol, so the socket has the type of the protocol.
+	fn is_receiver(sockets: &SocketSet<'_>, handle: SocketHandle, stamp: &RxStamp) -> bool {
+		match stamp.protocol {
+			#[cfg(feature = "tcp")]
+			IpProtocol::Tcp => Self::tcp_receives(sockets.get::<tcp::Socket<'_>>(handle), stamp),
+			#[cfg(feature = "udp")]
+			IpProtocol::Udp => Self::udp_receives(sockets.get::<udp::Socket<'_>>(handle), stamp),
+			_ => false,
+		}
+	}
+
+	fn receives(socket: &Socket<'_>, stamp: &RxStamp) -> bool {
+		match stamp.protocol {
+			#[cfg(feature = "tcp")]
+			IpProtocol::Tcp => tcp::Socket::downcast(socket)
+				.is_some_and(|socket| Self::tcp_receives(socket, stamp)),
+			#[cfg(feature = "udp")]
+			IpProtocol::Udp => udp::Socket::downcast(socket)
+				.is_some_and(|socket| Self::udp_receives(socket, stamp)),
+			_ => false,
+		}
+	}
+
+	#[cfg(feature = "tcp")]
+	fn tcp_receives(socket: &tcp::Socket<'_>, stamp: &RxStamp) -> bool {
+		socket
+			.local_endpoint()
+			.is_some_and(|local| local.port == stamp.port)
+			&& socket.remote_endpoint() == Some(stamp.source)
+	}
+
+	#[cfg(feature = "udp")]
+	fn udp_receives(socket: &udp::Socket<'_>, stamp: &RxStamp) -> bool {
+		socket.endpoint().port == stamp.port
+	}
+
+	/// Removes the timestamps of the socket `handle` up to the first frame of
+	/// `source` and returns the timestamp of this frame.
+	///
+	/// With `peek`, the timestamps remain queued.
+	pub fn take(&mut self, handle: SocketHandle, source: IpEndpoint, peek: bool) -> Option<u64> {
+		let queue = self.sockets.get_mut(&handle)?;
+		let index = queue.iter().position(|(from, _)| *from == source)?;
+		let time = queue[index].1;
+		if !peek {
+			queue.drain(..=index);
+		}
+		Some(time)
+	}
+
+	/// Removes all timestamps of the socket `handle` and returns the latest one.
+	///
+	/// With `peek`, the timestamps remain queued.
+	pub fn take_latest(&mut self, handle: SocketHandle, peek: bool) -> Option<u64> {
+		let queue = self.sockets.get_mut(&handle)?;
+		let time = queue.back().map(|(_, time)| *time);
+		if !peek {
+			queue.clear();
+		}
+		time
+	}
+}
@@ -1,4 +1,4 @@
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::vec::Vec;
 use core::future;
 use core::task::Poll;
@@ -20,8 +20,6 @@ pub(crate) static VSOCK_MAP: InterruptTicketMutex<VsockMap> =
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub(crate) enum VsockState {
-	Listen,
-	ReceiveRequest,
 	Connected,
 	Connecting,
 	Shutdown,
@@ -29,6 +27,47 @@ pub(crate) enum VsockState {
 
 pub(crate) const RAW_SOCKET_BUFFER_SIZE: usize = 256 * 1024;
 
+/// Number of connection requests, which are queued per listener, if `listen`
+/// does not specify the backlog
+pub(crate) const DEFAULT_BACKLOG: usize = 128;
+
+/// Local port and address of the peer, which identify a connection
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct ConnectionId {
+	pub port: u32,
+	pub remote_cid: u32,
+	pub remote_port: u32,
+}
+
+/// Connection request of a peer, which has not been accepted yet
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Request {
+	pub remote_cid: u32,
+	pub remote_port: u32,
+	pub peer_buf_alloc: u32,
+	pub peer_fwd_cnt: u32,
+}
+
+/// Socket, which listens for connections on a port
+#[derive(Debug)]
+pub(crate) struct Listener {
+	/// Connection requests in order of their arrival
+	pub backlog: VecDeque<Request>,
+	/// Maximum number of queued connection requests
+	pub max_backlog: usize,
+	pub rx_waker: WakerRegistration,
+}
+
+impl Listener {
+	fn new() -> Self {
+		Self {
+			backlog: VecDeque::new(),
+			max_backlog: DEFAULT_BACKLOG,
+			rx_waker: WakerRegistration::new(),
+		}
+	}
+}
+
 #[derive(Debug)]
 pub(crate) struct RawSocket {
 	pub remote_cid: u32,
@@ -58,6 +97,34 @@ impl RawSocket {
 			buffer: Vec::with_capacity(RAW_SOCKET_BUFFER_SIZE),
 		}
 	}
+
+	/// Returns the number of bytes, which the peer is able to receive.
+	///
+	/// See Virtio specification v1.2 - 5.10.6.3
+	pub fn peer_credit(&self) -> u32 {
+		self.peer_buf_alloc
+			.saturating_sub(self.tx_cnt.wrapping_sub(self.peer_fwd_cnt))
+	}
+
+	/// Creates the socket of the accepted connection `request`.
+	pub fn accepted(request: &Request) -> Self {
+		let mut raw = Self::new(VsockState::Connected);
+		raw.remote_cid = request.remote_cid;
+		raw.remote_port = request.remote_port;
+		raw.peer_buf_alloc = request.peer_buf_alloc;
+		raw.peer_fwd_cnt = request.peer_fwd_cnt;
+		raw
+	}
+
+	/// Takes the credit information from a packet of the peer and wakes up
+	/// the senders, if the peer is able to receive more data.
+	fn update_credit(&mut self, header: &Hdr) {
+		self.peer_buf_alloc = header.buf_alloc.to_ne();
+		self.peer_fwd_cnt = header.fwd_cnt.to_ne();
+		if self.peer_credit() > 0 {
+			self.tx_waker.wake();
+		}
+	}
 }
 
 async fn vsock_run() {
@@ -74,53 +141,57 @@ async fn vsock_run() {
 				let type_ = Type::try_from(header.type_.to_ne()).unwrap();
 				let mut vsock_guard = VSOCK_MAP.lock();
 				let header_cid: u32 = header.src_cid.to_ne().try_into().unwrap();
+				// The connection is identified by the source of the packet, so
+				// that packets of other sources cannot interfere with it.
+				let id = ConnectionId {
+					port,
+					remote_cid: header_cid,
+					remote_port: header.src_port.to_ne(),
+				};
 
-				if let Some(raw) = vsock_guard.get_mut_socket(port) {
-					if op == Op::Request && raw.state == VsockState::Listen && type_ == Type::Stream
-					{
-						raw.state = VsockState::ReceiveRequest;
-						raw.remote_cid = header_cid;
-						raw.remote_port = header.src_port.to_ne();
-						raw.peer_buf_alloc = header.buf_alloc.to_ne();
-						raw.rx_waker.wake();
-					} else if (raw.state == VsockState::Connected
-						|| raw.state == VsockState::Shutdown)
+				if let Some(raw) = vsock_guard.get_mut_socket(id) {
+					if (raw.state == VsockState::Connected || raw.state == VsockState::Shutdown)
 						&& type_ == Type::Stream
 						&& op == Op::Rw
 					{
-						if raw.remote_cid == header_cid {
-							raw.buffer.extend_from_slice(data);
-							raw.fwd_cnt =
-								raw.fwd_cnt.wrapping_add(u32::try_from(data.len()).unwrap());
-							raw.peer_fwd_cnt = header.fwd_cnt.to_ne();
-							raw.tx_waker.wake();
-							raw.rx_waker.wake();
-							hdr = Some(*header);
-							fwd_cnt = raw.fwd_cnt;
-						} else {
-							trace!("Receive message from invalid source {}", header_cid);
-						}
+						raw.buffer.extend_from_slice(data);
+						raw.fwd_cnt = raw.fwd_cnt.wrapping_add(u32::try_from(data.len()).unwrap());
+						raw.update_credit(header);
+						raw.rx_waker.wake();
+						hdr = Some(*header);
+						fwd_cnt = raw.fwd_cnt;
 					} else if op == Op::CreditUpdate {
-						if raw.remote_cid == header_cid {
-							raw.peer_fwd_cnt = header.fwd_cnt.to_ne();
-							raw.tx_waker.wake();
-						} else {
-							trace!("Receive message from invalid source {}", header_cid);
-						}
-					} else if op == Op::Shutdown {
-						if raw.remote_cid == header_cid {
-							raw.state = VsockState::Shutdown;
-						} else {
-							trace!("Receive message from invalid source {}", header_cid);
-						}
+						raw.update_credit(header);
+					} else if op == Op::Shutdown || op == Op::Rst {
+						raw.state = VsockState::Shutdown;
+						raw.rx_waker.wake();
+						raw.tx_waker.wake();
 					} else if op == Op::Response && type_ == Type::Stream {
-						if raw.remote_cid == header_cid && raw.state == VsockState::Connecting {
+						if raw.state == VsockState::Connecting {
 							raw.state = VsockState::Connected;
+							raw.update_credit(header);
+							raw.rx_waker.wake();
 						}
-					} else if raw.remote_cid == header_cid {
+					} else {
 						hdr = Some(*header);
 						fwd_cnt = raw.fwd_cnt;
 					}
+				} else if let Some(listener) = vsock_guard.get_mut_listener(port) {
+					if op == Op::Request
+						&& type_ == Type::Stream
+						&& listener.backlog.len() < listener.max_backlog
+					{
+						listener.backlog.push_back(Request {
+							remote_cid: id.remote_cid,
+							remote_port: id.remote_port,
+							peer_buf_alloc: header.buf_alloc.to_ne(),
+							peer_fwd_cnt: header.fwd_cnt.to_ne(),
+						});
+						listener.rx_waker.wake();
+					} else if op != Op::Rst {
+						// refuse the connection
+						hdr = Some(*header);
+					}
 				}
 			});
 
@@ -156,47 +227,102 @@ async fn vsock_run() {
 }
 
 pub(crate) struct VsockMap {
-	port_map: BTreeMap<u32, RawSocket>,
+	/// Listening sockets, indexed by their port
+	listeners: BTreeMap<u32, Listener>,
+	/// Connections, of which several may share the port of a listener
+	port_map: BTreeMap<ConnectionId, RawSocket>,
 }
 
 impl VsockMap {
 	pub const fn new() -> Self {
 		Self {
+			listeners: BTreeMap::new(),
 			port_map: BTreeMap::new(),
 		}
 	}
 
 	pub fn bind(&mut self, port: u32) -> io::Result<()> {
-		self.port_map
-			.try_insert(port, RawSocket::new(VsockState::Listen))
+		self.listeners
+			.try_insert(port, Listener::new())
 			.map_err(|_| EADDRINUSE)?;
 		Ok(())
 	}
 
-	pub fn connect(&mut self, port: u32, cid: u32) -> io::Result<u32> {
-		for i in u32::MAX / 4..u32::MAX {
-			let mut raw = RawSocket::new(VsockState::Connecting);
-			raw.remote_cid = cid;
-			raw.remote_port = port;
+	/// Returns `true`, if a listener or a connection uses the local port `port`.
+	fn is_used(&self, port: u32) -> bool {
+		let first = ConnectionId {
+			port,
+			remote_cid: 0,
+			remote_port: 0,
+		};
+		let last = ConnectionId {
+			port,
+			remote_cid: u32::MAX,
+			remote_port: u32::MAX,
+		};
+		self.listeners.contains_key(&port) || self.port_map.range(first..=last).next().is_some()
+	}
+
+	pub fn connect(&mut self, port: u32, cid: u32) -> io::Result<ConnectionId> {
+		let local_port = (u32::MAX / 4..u32::MAX)
+			.find(|i| !self.is_used(*i))
+			.ok_or(io::Error::EBADF)?;
+		let id = ConnectionId {
+			port: local_port,
+			remote_cid: cid,
+			remote_port: port,
+		};
 
-			if self.port_map.try_insert(i, raw).is_ok() {
-				return Ok(i);
-			}
-		}
+		let mut raw = RawSocket::new(VsockState::Connecting);
+		raw.remote_cid = cid;
+		raw.remote_port = port;
+		self.port_map.insert(id, raw);
+
+		Ok(id)
+	}
 
-		Err(io::Error::EBADF)
+	/// Adds the accepted connection `request` to the listener `port`.
+	pub fn accept(&mut self, port: u32, request: &Request) -> ConnectionId {
+		let id = ConnectionId {
+			port,
+			remote_cid: request.remote_cid,
+			remote_port: request.remote_port,
+		};
+		self.port_map.insert(id, RawSocket::accepted(request));
+		id
 	}
 
-	pub fn get_socket(&self, port: u32) -> Option<&RawSocket> {
-		self.port_map.get(&port)
+	pub fn get_socket(&self, id: ConnectionId) -> Option<&RawSocket> {
+		self.port_map.get(&id)
 	}
 
-	pub fn get_mut_socket(&mut self, port: u32) -> Option<&mut RawSocket> {
-		self.port_map.get_mut(&port)
+	pub fn get_mut_socket(&mut self, id: ConnectionId) -> Option<&mut RawSocket> {
+		self.port_map.get_mut(&id)
 	}
 
-	pub fn remove_socket(&mut self, port: u32) {
-		let _ = self.port_map.remove(&port);
+	pub fn get_mut_listener(&mut self, port: u32) -> Option<&mut Listener> {
+		self.listeners.get_mut(&port)
+	}
+
+	pub fn remove_socket(&mut self, id: ConnectionId) {
+		let _ = self.port_map.remove(&id);
+	}
+
+	pub fn remove_listener(&mut self, port: u32) {
+		let _ = self.listeners.remove(&port);
+	}
+
+	/// Shuts down all connections and drops the pending connection requests,
+	/// after the device has been reset.
+	pub fn reset(&mut self) {
+		for raw in self.port_map.values_mut() {
+			raw.state = VsockState::Shutdown;
+			raw.rx_waker.wake();
+			raw.tx_waker.wake();
+		}
+		for listener in self.listeners.values_mut() {
+			listener.backlog.clear();
+		}
 	}
 }
 
@@ -1,20 +1,24 @@
 use alloc::boxed::Box;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::ffi::c_ulong;
 use core::future::{self, Future};
+use core::sync::atomic::{AtomicU64, Ordering};
 use core::task::Poll::{Pending, Ready};
 use core::time::Duration;
 
 use async_trait::async_trait;
+use memory_addresses::VirtAddr;
 #[cfg(any(feature = "tcp", feature = "udp"))]
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use crate::arch::kernel::core_local::core_scheduler;
-use crate::executor::block_on;
+use crate::executor::{block_on, block_on_interruptible};
 use crate::fs::{DirectoryEntry, FileAttr, SeekWhence};
 use crate::io;
 
 mod eventfd;
+pub(crate) mod pipe;
 #[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
 pub(crate) mod socket;
 pub(crate) mod stdio;
@@ -42,9 +46,57 @@ pub(crate) enum ListenEndpoint {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub(crate) enum SocketOption {
 	TcpNoDelay,
+	TcpCork,
+	TcpDeferAccept,
+	ReusePort,
+	/// Software receive timestamps (`SO_TIMESTAMPING`)
+	Timestamping,
+	/// Size of the datagrams, into which UDP writes are segmented (`UDP_SEGMENT`)
+	UdpSegment,
+	ReuseAddr,
+	KeepAlive,
+	Broadcast,
+	/// Time in seconds, for which closing waits for unsent data, negative if disabled (`SO_LINGER`)
+	Linger,
+	/// Size hint of the send buffer (`SO_SNDBUF`)
+	SendBuffer,
+	/// Size hint of the receive buffer (`SO_RCVBUF`)
+	RecvBuffer,
+	/// Timeout of blocking sends in milliseconds, zero if disabled (`SO_SNDTIMEO`)
+	SendTimeout,
+	/// Timeout of blocking receives in milliseconds, zero if disabled (`SO_RCVTIMEO`)
+	RecvTimeout,
+	/// Pending error of the socket (`SO_ERROR`)
+	Error,
+}
+
+#[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
+bitflags! {
+	/// Flags of `recv` and `recvfrom`
+	#[derive(Debug, Copy, Clone, Default)]
+	pub struct RecvFlags: i32 {
+		/// Returns the data without removing it from the receive queue.
+		const MSG_PEEK = 0x1;
+		/// Returns `EAGAIN` instead of blocking, independent of the mode of the socket.
+		const MSG_DONTWAIT = 0x40;
+		/// Waits until the whole buffer is filled or the connection is closed.
+		const MSG_WAITALL = 0x100;
+	}
+}
+
+/// Message, which has been received by `recvmsg`
+#[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
+#[derive(Debug)]
+pub(crate) struct RecvMsg {
+	/// Number of received bytes
+	pub len: usize,
+	/// Sender of the message, if known
+	pub endpoint: Option<Endpoint>,
+	/// Receive time in microseconds since boot, if the socket has enabled timestamping
+	pub timestamp: Option<u64>,
 }
 
 #[allow(dead_code)]
@@ -99,6 +151,19 @@ pub struct PollFd {
 	pub revents: PollEvent,
 }
 
+/// Largest number of file descriptors in an [`FdSet`]
+pub const FD_SETSIZE: usize = 1024;
+
+/// Bitmap of file descriptors (`fd_set`) for `select`
+///
+/// C libraries disagree on the size of `fd_set`. Therefore, the kernel only
+/// accesses the words, which cover the file descriptors below `nfds`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FdSet {
+	pub fds_bits: [c_ulong; FD_SETSIZE / c_ulong::BITS as usize],
+}
+
 bitflags! {
 	#[derive(Debug, Default, Copy, Clone)]
 	pub struct EventFlags: i16 {
@@ -142,6 +207,55 @@ impl Default for AccessPermission {
 	}
 }
 
+/// Copies the data of `bufs` after the first `skip` bytes to `dst` and
+/// returns the number of copied bytes (gather).
+pub(crate) fn gather(bufs: &[&[u8]], mut skip: usize, dst: &mut [u8]) -> usize {
+	let mut copied = 0;
+	for buf in bufs {
+		if skip >= buf.len() {
+			skip -= buf.len();
+			continue;
+		}
+
+		let src = &buf[skip..];
+		skip = 0;
+		let len = src.len().min(dst.len() - copied);
+		dst[copied..copied + len].copy_from_slice(&src[..len]);
+		copied += len;
+		if copied == dst.len() {
+			break;
+		}
+	}
+	copied
+}
+
+/// Copies `src` to `bufs` after the first `skip` bytes and returns the
+/// number of copied bytes (scatter).
+pub(crate) fn scatter(src: &[u8], bufs: &mut [&mut [u8]], mut skip: usize) -> usize {
+	let mut copied = 0;
+	for buf in bufs {
+		if skip >= buf.len() {
+			skip -= buf.len();
+			continue;
+		}
+
+		let dst = &mut buf[skip..];
+		skip = 0;
+		let len = dst.len().min(src.len() - copied);
+		dst[..len].copy_from_slice(&src[copied..copied + len]);
+		copied += len;
+		if copied == src.len() {
+			break;
+		}
+	}
+	copied
+}
+
+/// Returns the total length of `bufs`.
+pub(crate) fn total_len(bufs: &[&[u8]]) -> usize {
+	bufs.iter().map(|buf| buf.len()).sum()
+}
+
 #[async_trait]
 pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 	/// check if an IO event is possible
@@ -161,6 +275,46 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 		Err(io::Error::ENOSYS)
 	}
 
+	/// `readv` reads data from the object into the buffers `bufs`
+	///
+	/// The buffers are filled one after another until the object returns less
+	/// data than requested.
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		let mut len = 0;
+		for buf in bufs {
+			if buf.is_empty() {
+				continue;
+			}
+
+			let n = self.read(buf).await?;
+			len += n;
+			if n < buf.len() {
+				break;
+			}
+		}
+		Ok(len)
+	}
+
+	/// `writev` writes the data of the buffers `bufs` to the object
+	///
+	/// The buffers are written one after another until the object accepts
+	/// less data than provided.
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		let mut len = 0;
+		for buf in bufs {
+			if buf.is_empty() {
+				continue;
+			}
+
+			let n = self.write(buf).await?;
+			len += n;
+			if n < buf.len() {
+				break;
+			}
+		}
+		Ok(len)
+	}
+
 	/// `lseek` function repositions the offset of the file descriptor fildes
 	async fn lseek(&self, _offset: isize, _whence: SeekWhence) -> io::Result<isize> {
 		Err(io::Error::EINVAL)
@@ -171,6 +325,17 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 		Err(io::Error::EINVAL)
 	}
 
+	/// `fsync` transfers all modified data of the object to the underlying device
+	async fn fsync(&self) -> io::Result<()> {
+		Err(io::Error::EINVAL)
+	}
+
+	/// `dax_address` returns the address of the directly accessible memory,
+	/// which backs the object at `offset`, and the number of bytes behind it
+	async fn dax_address(&self, _offset: usize) -> io::Result<(VirtAddr, usize)> {
+		Err(io::Error::ENODEV)
+	}
+
 	/// 'readdir' returns a pointer to a dirent structure
 	/// representing the next directory entry in the directory stream
 	/// pointed to by the file descriptor
@@ -204,13 +369,28 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 
 	/// `setsockopt` sets options on sockets
 	#[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
-	async fn setsockopt(&self, _opt: SocketOption, _optval: bool) -> io::Result<()> {
+	async fn setsockopt(&self, _opt: SocketOption, _optval: i32) -> io::Result<()> {
 		Err(io::Error::ENOTSOCK)
 	}
 
 	/// `getsockopt` gets options on sockets
 	#[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
-	async fn getsockopt(&self, _opt: SocketOption) -> io::Result<bool> {
+	async fn getsockopt(&self, _opt: SocketOption) -> io::Result<i32> {
+		Err(io::Error::ENOTSOCK)
+	}
+
+	/// Returns the timeout `opt` (`SO_RCVTIMEO` or `SO_SNDTIMEO`) of a socket.
+	///
+	/// The timeout is queried before every blocking read or write. Hence, it
+	/// is returned without waiting and is `None` for all other objects.
+	fn io_timeout(&self, _opt: SocketOption) -> Option<Duration> {
+		None
+	}
+
+	/// `usage` returns the number of bytes and packets, which have been
+	/// sent and received by the socket
+	#[cfg(any(feature = "tcp", feature = "udp"))]
+	async fn usage(&self) -> io::Result<socket::usage::NetUsage> {
 		Err(io::Error::ENOTSOCK)
 	}
 
@@ -227,12 +407,46 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 		Ok(None)
 	}
 
+	/// receive a message from a connected socket
+	///
+	/// Without flags, this is equivalent to `read`.
+	#[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
+	async fn recv(&self, buffer: &mut [u8], flags: RecvFlags) -> io::Result<usize> {
+		if flags.is_empty() {
+			self.read(buffer).await
+		} else {
+			Err(io::Error::EINVAL)
+		}
+	}
+
 	/// receive a message from a socket
 	#[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
-	async fn recvfrom(&self, _buffer: &mut [u8]) -> io::Result<(usize, Endpoint)> {
+	async fn recvfrom(
+		&self,
+		_buffer: &mut [u8],
+		_flags: RecvFlags,
+	) -> io::Result<(usize, Endpoint)> {
 		Err(io::Error::ENOSYS)
 	}
 
+	/// receive a message from a socket into the buffers `bufs`
+	///
+	/// In addition to `recvfrom`, the receive time of the message is returned.
+	#[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
+	async fn recvmsg(&self, bufs: &mut [&mut [u8]], flags: RecvFlags) -> io::Result<RecvMsg> {
+		let len = match bufs {
+			[buffer] => self.recv(buffer, flags).await?,
+			bufs if flags.is_empty() => self.readv(bufs).await?,
+			_ => return Err(io::Error::EINVAL),
+		};
+
+		Ok(RecvMsg {
+			len,
+			endpoint: None,
+			timestamp: None,
+		})
+	}
+
 	/// send a message from a socket
 	///
 	/// The sendto() function shall send a message.
@@ -245,6 +459,17 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 		Err(io::Error::ENOSYS)
 	}
 
+	/// send a message from the buffers `bufs` to `endpoint`
+	///
+	/// Without a native implementation, the buffers are gathered for `sendto`.
+	#[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
+	async fn sendmsg(&self, bufs: &[&[u8]], endpoint: Endpoint) -> io::Result<usize> {
+		match bufs {
+			[buffer] => self.sendto(buffer, endpoint).await,
+			bufs => self.sendto(&bufs.concat(), endpoint).await,
+		}
+	}
+
 	/// shut down part of a full-duplex connection
 	#[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
 	async fn shutdown(&self, _how: i32) -> io::Result<()> {
@@ -256,6 +481,58 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 	async fn ioctl(&self, _cmd: IoCtl, _value: bool) -> io::Result<()> {
 		Err(io::Error::ENOSYS)
 	}
+
+	/// Returns the capacity of a pipe (`F_GETPIPE_SZ`)
+	async fn pipe_size(&self) -> io::Result<usize> {
+		Err(io::Error::EBADF)
+	}
+
+	/// Changes the capacity of a pipe (`F_SETPIPE_SZ`) and returns the new capacity
+	async fn set_pipe_size(&self, _size: usize) -> io::Result<usize> {
+		Err(io::Error::EBADF)
+	}
+
+	/// Moves up to `len` bytes from the read end of a pipe to `out` (`splice`)
+	async fn splice_to(
+		&self,
+		_out: &dyn ObjectInterface,
+		_len: usize,
+		_nonblocking: bool,
+	) -> io::Result<usize> {
+		Err(io::Error::EINVAL)
+	}
+
+	/// Moves up to `len` bytes from `input` to the write end of a pipe (`splice`)
+	async fn splice_from(
+		&self,
+		_input: &dyn ObjectInterface,
+		_len: usize,
+		_nonblocking: bool,
+	) -> io::Result<usize> {
+		Err(io::Error::EINVAL)
+	}
+}
+
+/// Blocks on the I/O operation `future` of `obj` for at most the timeout `opt`
+/// of the socket (`SO_RCVTIMEO` or `SO_SNDTIMEO`).
+///
+/// As on Linux, an elapsed timeout is reported as `EAGAIN`.
+pub(crate) fn block_on_io<F, T>(
+	obj: &dyn ObjectInterface,
+	opt: SocketOption,
+	future: F,
+) -> io::Result<T>
+where
+	F: Future<Output = io::Result<T>>,
+{
+	let timeout = obj.io_timeout(opt);
+	block_on_interruptible(future, timeout).map_err(|err| {
+		if timeout.is_some() && err == io::Error::ETIME {
+			io::Error::EAGAIN
+		} else {
+			err
+		}
+	})
 }
 
 pub(crate) fn read(fd: FileDescriptor, buf: &mut [u8]) -> io::Result<usize> {
@@ -265,7 +542,7 @@ pub(crate) fn read(fd: FileDescriptor, buf: &mut [u8]) -> io::Result<usize> {
 		return Ok(0);
 	}
 
-	block_on(obj.read(buf), None)
+	block_on_io(&*obj, SocketOption::RecvTimeout, obj.read(buf))
 }
 
 pub(crate) fn lseek(fd: FileDescriptor, offset: isize, whence: SeekWhence) -> io::Result<isize> {
@@ -281,7 +558,17 @@ pub(crate) fn write(fd: FileDescriptor, buf: &[u8]) -> io::Result<usize> {
 		return Ok(0);
 	}
 
-	block_on(obj.write(buf), None)
+	block_on_io(&*obj, SocketOption::SendTimeout, obj.write(buf))
+}
+
+pub(crate) fn readv(fd: FileDescriptor, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+	let obj = get_object(fd)?;
+	block_on_io(&*obj, SocketOption::RecvTimeout, obj.readv(bufs))
+}
+
+pub(crate) fn writev(fd: FileDescriptor, bufs: &[&[u8]]) -> io::Result<usize> {
+	let obj = get_object(fd)?;
+	block_on_io(&*obj, SocketOption::SendTimeout, obj.writev(bufs))
 }
 
 async fn poll_fds(fds: &mut [PollFd]) -> io::Result<u64> {
@@ -319,7 +606,7 @@ async fn poll_fds(fds: &mut [PollFd]) -> io::Result<u64> {
 /// monitored is specified in the `fds` argument, which is an array
 /// of structs of `PollFd`.
 pub fn poll(fds: &mut [PollFd], timeout: Option<Duration>) -> io::Result<u64> {
-	let result = block_on(poll_fds(fds), timeout);
+	let result = block_on_interruptible(poll_fds(fds), timeout);
 	if let Err(ref e) = result {
 		if timeout.is_some() {
 			// A return value of zero indicates that the system call timed out
@@ -337,6 +624,16 @@ pub fn fstat(fd: FileDescriptor) -> io::Result<FileAttr> {
 	block_on(obj.fstat(), None)
 }
 
+pub(crate) fn fsync(fd: FileDescriptor) -> io::Result<()> {
+	let obj = get_object(fd)?;
+	block_on(obj.fsync(), None)
+}
+
+pub(crate) fn dax_address(fd: FileDescriptor, offset: usize) -> io::Result<(VirtAddr, usize)> {
+	let obj = get_object(fd)?;
+	block_on(obj.dax_address(offset), None)
+}
+
 /// Wait for some event on a file descriptor.
 ///
 /// `eventfd` creates an linux-like "eventfd object" that can be used
@@ -363,6 +660,48 @@ pub fn eventfd(initval: u64, flags: EventFlags) -> io::Result<FileDescriptor> {
 	Ok(fd)
 }
 
+/// Creates a pipe and returns the file descriptors of its read and write end.
+pub(crate) fn pipe(nonblocking: bool) -> io::Result<(FileDescriptor, FileDescriptor)> {
+	let (reader, writer) = self::pipe::pipe(nonblocking);
+
+	let read_fd = insert_object(Arc::new(reader))?;
+	let write_fd = match insert_object(Arc::new(writer)) {
+		Ok(fd) => fd,
+		Err(err) => {
+			let _ = remove_object(read_fd);
+			return Err(err);
+		}
+	};
+
+	Ok((read_fd, write_fd))
+}
+
+/// Moves up to `len` bytes between `fd_in` and `fd_out`, of which at least one is a pipe.
+pub(crate) fn splice(
+	fd_in: FileDescriptor,
+	fd_out: FileDescriptor,
+	len: usize,
+	nonblocking: bool,
+) -> io::Result<usize> {
+	let obj_in = get_object(fd_in)?;
+	let obj_out = get_object(fd_out)?;
+
+	if len == 0 {
+		return Ok(0);
+	}
+
+	block_on_interruptible(
+		async {
+			if obj_in.pipe_size().await.is_ok() {
+				obj_in.splice_to(&*obj_out, len, nonblocking).await
+			} else {
+				obj_out.splice_from(&*obj_in, len, nonblocking).await
+			}
+		},
+		None,
+	)
+}
+
 pub(crate) fn get_object(fd: FileDescriptor) -> io::Result<Arc<dyn ObjectInterface>> {
 	block_on(core_scheduler().get_object(fd), None)
 }
@@ -382,3 +721,60 @@ pub(crate) fn dup_object(fd: FileDescriptor) -> io::Result<FileDescriptor> {
 pub(crate) fn remove_object(fd: FileDescriptor) -> io::Result<Arc<dyn ObjectInterface>> {
 	block_on(core_scheduler().remove_object(fd), None)
 }
+
+/// Time, in which the objects are synchronized and closed on shutdown
+const CLOSE_ALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Point in time in microseconds, after which closing an object does not
+/// wait anymore, or zero, if the system is not shutting down
+static CLOSE_DEADLINE: AtomicU64 = AtomicU64::new(0);
+
+/// Limits the time `timeout`, for which closing an object may wait, to the
+/// deadline of the shutdown.
+pub(crate) fn close_timeout(timeout: Option<Duration>) -> Option<Duration> {
+	let deadline = CLOSE_DEADLINE.load(Ordering::Relaxed);
+	if deadline == 0 {
+		return timeout;
+	}
+
+	let now = crate::arch::kernel::systemtime::now_micros();
+	let remaining = Duration::from_micros(deadline.saturating_sub(now));
+	Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)))
+}
+
+/// Closes the file descriptors of the application on shutdown.
+///
+/// All objects are synchronized before, so that written data reaches the
+/// file systems. Sockets are closed according to `SO_LINGER`. The standard
+/// descriptors remain open for the last messages of the application.
+/// Objects, which are not closed within [`CLOSE_ALL_TIMEOUT`], e.g.,
+/// because a peer does not close its side of a connection, are aborted.
+pub(crate) fn close_all() {
+	let now = crate::arch::kernel::systemtime::now_micros();
+	CLOSE_DEADLINE.store(
+		now + u64::try_from(CLOSE_ALL_TIMEOUT.as_micros()).unwrap(),
+		Ordering::Relaxed,
+	);
+
+	for fd in STDIN_FILENO..=STDERR_FILENO {
+		let _ = fsync(fd);
+	}
+
+	let objects = match block_on(
+		core_scheduler().remove_objects(STDERR_FILENO + 1),
+		close_timeout(None),
+	) {
+		Ok(objects) => objects,
+		Err(err) => {
+			warn!("Unable to close the file descriptors: {err:?}");
+			return;
+		}
+	};
+
+	debug!("Close {} file descriptors", objects.len());
+	for obj in objects {
+		// objects without backing storage do not support synchronization
+		let _ = block_on(obj.fsync(), close_timeout(None));
+		drop(obj);
+	}
+}
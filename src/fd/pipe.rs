@@ -0,0 +1,543 @@
+//! Anonymous pipes
+//!
+//! A pipe consists of a read end and a write end, which share a ring buffer.
+//! The capacity of the buffer can be queried and changed with
+//! `F_GETPIPE_SZ` and `F_SETPIPE_SZ`. Writes of at most [`PIPE_BUF`] bytes
+//! are atomic, i.e., they are never interleaved with other writes.
+//!
+//! `splice` moves data between a pipe and another object, e.g., a socket,
+//! within the kernel. Concurrent readers and concurrent writers of a pipe are
+//! serialized, so that a splice does not interleave with other reads or writes.
+//! A splice passes the ring buffer itself to the other object, so that the
+//! data is not copied to an intermediate buffer.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::{future, ptr, slice};
+
+use async_trait::async_trait;
+use hermit_sync::InterruptTicketMutex;
+
+use crate::fd::{IoCtl, ObjectInterface, PollEvent, gather, scatter, total_len};
+use crate::io;
+
+const PAGE_SIZE: usize = 4096;
+
+/// Maximum size of a write, which is not interleaved with other writes
+pub(crate) const PIPE_BUF: usize = PAGE_SIZE;
+
+/// Capacity of a new pipe
+const DEFAULT_CAPACITY: usize = 16 * PAGE_SIZE;
+
+/// Maximum capacity, which can be set by `F_SETPIPE_SZ`
+const MAX_CAPACITY: usize = 256 * PAGE_SIZE;
+
+/// Ring buffer of a pipe
+///
+/// The storage is only accessed through raw pointers, so that a splice can
+/// pass the data to another object without holding the lock of the pipe.
+/// The filled part is only removed by the reader, which holds the read lock,
+/// and the free part is only filled by the writer, which holds the write lock.
+/// The storage is only replaced by [`RingBuffer::resize`], while no splice
+/// borrows it.
+#[derive(Debug)]
+struct RingBuffer {
+	data: NonNull<u8>,
+	capacity: usize,
+	head: usize,
+	len: usize,
+}
+
+// SAFETY: The ring buffer owns its storage.
+unsafe impl Send for RingBuffer {}
+
+impl RingBuffer {
+	fn new(capacity: usize) -> Self {
+		let data = Box::into_raw(vec![0u8; capacity].into_boxed_slice());
+		Self {
+			data: NonNull::new(data.cast()).unwrap(),
+			capacity,
+			head: 0,
+			len: 0,
+		}
+	}
+
+	fn len(&self) -> usize {
+		self.len
+	}
+
+	fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	fn free(&self) -> usize {
+		self.capacity - self.len
+	}
+
+	/// Returns the (up to two) parts of the storage, which start at `start`
+	/// and have a total length of `len`.
+	fn parts(&self, start: usize, len: usize) -> [Range<usize>; 2] {
+		let end = start + len;
+		if end <= self.capacity {
+			[start..end, 0..0]
+		} else {
+			[start..self.capacity, 0..end - self.capacity]
+		}
+	}
+
+	/// Returns the parts of the storage, which contain the first `max` bytes
+	/// of the data.
+	fn filled(&self, max: usize) -> [Range<usize>; 2] {
+		self.parts(self.head, self.len.min(max))
+	}
+
+	/// Returns the parts of the storage, which provide room for `max` bytes.
+	fn vacant(&self, max: usize) -> [Range<usize>; 2] {
+		self.parts((self.head + self.len) % self.capacity, self.free().min(max))
+	}
+
+	/// Returns the first `max` bytes of the data.
+	///
+	/// # Safety
+	///
+	/// The data must not be removed and the storage must not be replaced,
+	/// while the slices are alive.
+	unsafe fn readable<'a>(&self, max: usize) -> [&'a [u8]; 2] {
+		self.filled(max).map(|range| unsafe {
+			slice::from_raw_parts(self.data.as_ptr().add(range.start), range.len())
+		})
+	}
+
+	/// Returns room for `max` bytes at the back.
+	///
+	/// # Safety
+	///
+	/// The room must not be filled otherwise and the storage must not be
+	/// replaced, while the slices are alive.
+	unsafe fn writable<'a>(&self, max: usize) -> [&'a mut [u8]; 2] {
+		self.vacant(max).map(|range| unsafe {
+			slice::from_raw_parts_mut(self.data.as_ptr().add(range.start), range.len())
+		})
+	}
+
+	/// Removes `len` bytes from the front.
+	fn consume(&mut self, len: usize) {
+		assert!(len <= self.len);
+		self.head = (self.head + len) % self.capacity;
+		self.len -= len;
+	}
+
+	/// Adds `len` bytes, which have been written to the free part, to the back.
+	fn produce(&mut self, len: usize) {
+		assert!(len <= self.free());
+		self.len += len;
+	}
+
+	/// Moves the data to a new storage of `capacity` bytes.
+	fn resize(&mut self, capacity: usize) {
+		let mut buffer = Self::new(capacity);
+		// SAFETY: The new storage is not shared yet.
+		let [front, back] = unsafe { buffer.writable(self.len) };
+		// SAFETY: `&mut self` prevents concurrent changes.
+		let [src_front, src_back] = unsafe { self.readable(self.len) };
+		let mut dst = [front, back];
+		let len = scatter(src_front, &mut dst, 0);
+		scatter(src_back, &mut dst, len);
+		buffer.len = self.len;
+		*self = buffer;
+	}
+}
+
+impl Drop for RingBuffer {
+	fn drop(&mut self) {
+		let data = ptr::slice_from_raw_parts_mut(self.data.as_ptr(), self.capacity);
+		// SAFETY: The storage has been allocated by `RingBuffer::new`.
+		drop(unsafe { Box::from_raw(data) });
+	}
+}
+
+#[derive(Debug)]
+struct PipeState {
+	buffer: RingBuffer,
+	/// Set, as soon as the read end has been closed
+	reader_closed: bool,
+	/// Set, as soon as the write end has been closed
+	writer_closed: bool,
+	read_wakers: Vec<Waker>,
+	write_wakers: Vec<Waker>,
+}
+
+/// Adds `waker` to `wakers`, if it does not contain a waker, which wakes the same task.
+fn register(wakers: &mut Vec<Waker>, waker: &Waker) {
+	if !wakers.iter().any(|w| w.will_wake(waker)) {
+		wakers.push(waker.clone());
+	}
+}
+
+impl PipeState {
+	fn wake_readers(&mut self) {
+		for waker in self.read_wakers.drain(..) {
+			waker.wake();
+		}
+	}
+
+	fn wake_writers(&mut self) {
+		for waker in self.write_wakers.drain(..) {
+			waker.wake();
+		}
+	}
+}
+
+#[derive(Debug)]
+struct Pipe {
+	state: InterruptTicketMutex<PipeState>,
+	/// Serializes the readers
+	read_lock: async_lock::Mutex<()>,
+	/// Serializes the writers
+	write_lock: async_lock::Mutex<()>,
+	/// Prevents the replacement of the storage, while a splice borrows it
+	storage_lock: async_lock::RwLock<()>,
+}
+
+impl Pipe {
+	fn new() -> Self {
+		Self {
+			state: InterruptTicketMutex::new(PipeState {
+				buffer: RingBuffer::new(DEFAULT_CAPACITY),
+				reader_closed: false,
+				writer_closed: false,
+				read_wakers: Vec::new(),
+				write_wakers: Vec::new(),
+			}),
+			read_lock: async_lock::Mutex::new(()),
+			write_lock: async_lock::Mutex::new(()),
+			storage_lock: async_lock::RwLock::new(()),
+		}
+	}
+
+	fn size(&self) -> usize {
+		self.state.lock().buffer.capacity()
+	}
+
+	/// Changes the capacity to `size` rounded up to a power of two pages.
+	async fn set_size(&self, size: usize) -> io::Result<usize> {
+		if size > MAX_CAPACITY {
+			return Err(io::Error::EPERM);
+		}
+
+		let capacity = size.max(PAGE_SIZE).next_power_of_two();
+		let _guard = self.storage_lock.write().await;
+		let mut state = self.state.lock();
+		if state.buffer.len() > capacity {
+			return Err(io::Error::EBUSY);
+		}
+
+		state.buffer.resize(capacity);
+		state.wake_writers();
+		Ok(capacity)
+	}
+
+	/// Waits until the pipe contains data and returns the number of available
+	/// bytes, or zero if the write end has been closed.
+	fn poll_readable(&self, cx: &Context<'_>, nonblocking: bool) -> Poll<io::Result<usize>> {
+		let mut state = self.state.lock();
+		if !state.buffer.is_empty() {
+			Poll::Ready(Ok(state.buffer.len()))
+		} else if state.writer_closed {
+			Poll::Ready(Ok(0))
+		} else if nonblocking {
+			Poll::Ready(Err(io::Error::EAGAIN))
+		} else {
+			register(&mut state.read_wakers, cx.waker());
+			Poll::Pending
+		}
+	}
+
+	/// Waits until the pipe has room for `min` bytes and returns the free space.
+	fn poll_writable(
+		&self,
+		cx: &Context<'_>,
+		min: usize,
+		nonblocking: bool,
+	) -> Poll<io::Result<usize>> {
+		let mut state = self.state.lock();
+		let free = state.buffer.free();
+		if state.reader_closed {
+			Poll::Ready(Err(io::Error::EPIPE))
+		} else if free >= min {
+			Poll::Ready(Ok(free))
+		} else if nonblocking {
+			Poll::Ready(Err(io::Error::EAGAIN))
+		} else {
+			register(&mut state.write_wakers, cx.waker());
+			Poll::Pending
+		}
+	}
+
+	/// Removes `len` bytes from the buffer.
+	fn consume(&self, len: usize) {
+		let mut state = self.state.lock();
+		state.buffer.consume(len);
+		state.wake_writers();
+	}
+
+	/// Appends `len` bytes, which have been written to the free part, to the buffer.
+	fn produce(&self, len: usize) {
+		let mut state = self.state.lock();
+		state.buffer.produce(len);
+		state.wake_readers();
+	}
+}
+
+/// Read end of a pipe
+#[derive(Debug)]
+pub(crate) struct PipeReader {
+	pipe: Arc<Pipe>,
+	nonblocking: AtomicBool,
+}
+
+/// Write end of a pipe
+#[derive(Debug)]
+pub(crate) struct PipeWriter {
+	pipe: Arc<Pipe>,
+	nonblocking: AtomicBool,
+}
+
+/// Creates a pipe and returns its read and write end.
+pub(crate) fn pipe(nonblocking: bool) -> (PipeReader, PipeWriter) {
+	let pipe = Arc::new(Pipe::new());
+	let reader = PipeReader {
+		pipe: pipe.clone(),
+		nonblocking: AtomicBool::new(nonblocking),
+	};
+	let writer = PipeWriter {
+		pipe,
+		nonblocking: AtomicBool::new(nonblocking),
+	};
+	(reader, writer)
+}
+
+impl PipeReader {
+	fn is_nonblocking(&self) -> bool {
+		self.nonblocking.load(Ordering::Relaxed)
+	}
+}
+
+impl PipeWriter {
+	fn is_nonblocking(&self) -> bool {
+		self.nonblocking.load(Ordering::Relaxed)
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for PipeReader {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		future::poll_fn(|cx| {
+			let mut state = self.pipe.state.lock();
+			let mut available = PollEvent::empty();
+			if !state.buffer.is_empty() {
+				available.insert(PollEvent::POLLIN | PollEvent::POLLRDNORM);
+			}
+			if state.writer_closed {
+				available.insert(PollEvent::POLLHUP);
+			}
+
+			let ret = event & available;
+			if ret.is_empty() && !state.writer_closed {
+				register(&mut state.read_wakers, cx.waker());
+				Poll::Pending
+			} else {
+				Poll::Ready(Ok(ret | (available & PollEvent::POLLHUP)))
+			}
+		})
+		.await
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		self.readv(&mut [buf]).await
+	}
+
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		let _guard = self.pipe.read_lock.lock().await;
+		let nonblocking = self.is_nonblocking();
+		future::poll_fn(|cx| self.pipe.poll_readable(cx, nonblocking)).await?;
+
+		let mut state = self.pipe.state.lock();
+		// SAFETY: The slices do not outlive the lock.
+		let [front, back] = unsafe { state.buffer.readable(usize::MAX) };
+		let mut len = scatter(front, bufs, 0);
+		if len == front.len() {
+			len += scatter(back, bufs, len);
+		}
+		state.buffer.consume(len);
+		state.wake_writers();
+		Ok(len)
+	}
+
+	async fn ioctl(&self, cmd: IoCtl, value: bool) -> io::Result<()> {
+		if cmd == IoCtl::NonBlocking {
+			self.nonblocking.store(value, Ordering::Relaxed);
+			Ok(())
+		} else {
+			Err(io::Error::EINVAL)
+		}
+	}
+
+	async fn pipe_size(&self) -> io::Result<usize> {
+		Ok(self.pipe.size())
+	}
+
+	async fn set_pipe_size(&self, size: usize) -> io::Result<usize> {
+		self.pipe.set_size(size).await
+	}
+
+	async fn splice_to(
+		&self,
+		out: &dyn ObjectInterface,
+		len: usize,
+		nonblocking: bool,
+	) -> io::Result<usize> {
+		let _guard = self.pipe.read_lock.lock().await;
+		let nonblocking = nonblocking || self.is_nonblocking();
+		let available = future::poll_fn(|cx| self.pipe.poll_readable(cx, nonblocking)).await?;
+		if available == 0 {
+			return Ok(0);
+		}
+
+		// The data remains in the pipe, until `out` has accepted it.
+		let storage_guard = self.pipe.storage_lock.read().await;
+		// SAFETY: Only the reader, which holds the lock, removes data.
+		let bufs = unsafe { self.pipe.state.lock().buffer.readable(len) };
+		let written = out.writev(&bufs).await?;
+		drop(storage_guard);
+		self.pipe.consume(written);
+		Ok(written)
+	}
+}
+
+impl Drop for PipeReader {
+	fn drop(&mut self) {
+		let mut state = self.pipe.state.lock();
+		state.reader_closed = true;
+		state.wake_writers();
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for PipeWriter {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		future::poll_fn(|cx| {
+			let mut state = self.pipe.state.lock();
+			let mut available = PollEvent::empty();
+			if state.buffer.free() >= PIPE_BUF.min(state.buffer.capacity()) {
+				available.insert(PollEvent::POLLOUT | PollEvent::POLLWRNORM);
+			}
+			if state.reader_closed {
+				available.insert(PollEvent::POLLERR);
+			}
+
+			let ret = event & available;
+			if ret.is_empty() && !state.reader_closed {
+				register(&mut state.write_wakers, cx.waker());
+				Poll::Pending
+			} else {
+				Poll::Ready(Ok(ret | (available & PollEvent::POLLERR)))
+			}
+		})
+		.await
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		self.writev(&[buf]).await
+	}
+
+	/// Writes the data of `bufs` to the pipe.
+	///
+	/// Up to [`PIPE_BUF`] bytes are written at once. Larger writes block,
+	/// until all data has been written, unless the pipe is non-blocking.
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		let _guard = self.pipe.write_lock.lock().await;
+		let nonblocking = self.is_nonblocking();
+		let len = total_len(bufs);
+		let mut written = 0;
+
+		while written < len {
+			let min = if len <= PIPE_BUF {
+				len.min(self.pipe.size())
+			} else {
+				1
+			};
+			let free =
+				match future::poll_fn(|cx| self.pipe.poll_writable(cx, min, nonblocking)).await {
+					Ok(free) => free,
+					Err(io::Error::EAGAIN) if written > 0 => break,
+					Err(err) => return Err(err),
+				};
+
+			let mut state = self.pipe.state.lock();
+			// SAFETY: The slices do not outlive the lock.
+			let [front, back] = unsafe { state.buffer.writable(free.min(len - written)) };
+			let mut copied = gather(bufs, written, front);
+			copied += gather(bufs, written + copied, back);
+			state.buffer.produce(copied);
+			state.wake_readers();
+			written += copied;
+		}
+
+		Ok(written)
+	}
+
+	async fn ioctl(&self, cmd: IoCtl, value: bool) -> io::Result<()> {
+		if cmd == IoCtl::NonBlocking {
+			self.nonblocking.store(value, Ordering::Relaxed);
+			Ok(())
+		} else {
+			Err(io::Error::EINVAL)
+		}
+	}
+
+	async fn pipe_size(&self) -> io::Result<usize> {
+		Ok(self.pipe.size())
+	}
+
+	async fn set_pipe_size(&self, size: usize) -> io::Result<usize> {
+		self.pipe.set_size(size).await
+	}
+
+	async fn splice_from(
+		&self,
+		input: &dyn ObjectInterface,
+		len: usize,
+		nonblocking: bool,
+	) -> io::Result<usize> {
+		let _guard = self.pipe.write_lock.lock().await;
+		let nonblocking = nonblocking || self.is_nonblocking();
+		let free = future::poll_fn(|cx| self.pipe.poll_writable(cx, 1, nonblocking)).await?;
+
+		let storage_guard = self.pipe.storage_lock.read().await;
+		// SAFETY: Only the writer, which holds the lock, adds data.
+		let mut bufs = unsafe { self.pipe.state.lock().buffer.writable(len.min(free)) };
+		let read = input.readv(&mut bufs).await?;
+		drop(storage_guard);
+		self.pipe.produce(read);
+		Ok(read)
+	}
+}
+
+impl Drop for PipeWriter {
+	fn drop(&mut self) {
+		let mut state = self.pipe.state.lock();
+		state.writer_closed = true;
+		state.wake_readers();
+	}
+}
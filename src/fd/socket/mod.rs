@@ -1,6 +1,180 @@
+use alloc::vec::Vec;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use core::sync::atomic::AtomicU16;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
+use core::time::Duration;
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::fd::SocketOption;
+use crate::io;
+
 #[cfg(feature = "tcp")]
 pub(crate) mod tcp;
 #[cfg(feature = "udp")]
 pub(crate) mod udp;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+pub(crate) mod usage;
 #[cfg(feature = "vsock")]
 pub(crate) mod vsock;
+
+/// Returns the next local port for a connection or a socket, which is not bound explicitly.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+pub(crate) fn get_ephemeral_port() -> u16 {
+	static LOCAL_ENDPOINT: AtomicU16 = AtomicU16::new(49152);
+
+	LOCAL_ENDPOINT.fetch_add(1, Ordering::SeqCst)
+}
+
+/// further receives will be disallowed
+pub const SHUT_RD: i32 = 0;
+/// further sends will be disallowed
+pub const SHUT_WR: i32 = 1;
+/// further sends and receives will be disallowed
+pub const SHUT_RDWR: i32 = 2;
+
+/// Directions of a connection, which have been shut down by `shutdown`
+///
+/// The directions are shut down through a shared reference, so that
+/// `shutdown` does not wait for the tasks, which are blocked on the socket.
+/// Instead, these tasks are woken up and observe the new state.
+#[derive(Debug, Default)]
+pub(crate) struct Shutdown {
+	/// Receiving returns the end of the stream and discards incoming data.
+	read: AtomicBool,
+	/// Sending fails with `EPIPE`.
+	write: AtomicBool,
+	/// Tasks, which wait for receiving or sending on the socket
+	wakers: InterruptTicketMutex<Vec<Waker>>,
+}
+
+impl Shutdown {
+	pub fn read(&self) -> bool {
+		self.read.load(Ordering::Acquire)
+	}
+
+	pub fn write(&self) -> bool {
+		self.write.load(Ordering::Acquire)
+	}
+
+	/// Registers a task, which is woken up, when a direction is shut down.
+	pub fn register_waker(&self, waker: &Waker) {
+		let mut wakers = self.wakers.lock();
+		if !wakers.iter().any(|w| w.will_wake(waker)) {
+			wakers.push(waker.clone());
+		}
+	}
+
+	/// Adds the directions given by `how` (`SHUT_RD`, `SHUT_WR` or `SHUT_RDWR`)
+	/// and wakes up the waiting tasks.
+	///
+	/// Returns the directions `(read, write)`, which have not been shut down before.
+	pub fn insert(&self, how: i32) -> io::Result<(bool, bool)> {
+		let (read, write) = match how {
+			SHUT_RD => (true, false),
+			SHUT_WR => (false, true),
+			SHUT_RDWR => (true, true),
+			_ => return Err(io::Error::EINVAL),
+		};
+
+		let added = (
+			read && !self.read.swap(true, Ordering::AcqRel),
+			write && !self.write.swap(true, Ordering::AcqRel),
+		);
+		if added.0 || added.1 {
+			let wakers = core::mem::take(&mut *self.wakers.lock());
+			for waker in wakers {
+				waker.wake();
+			}
+		}
+		Ok(added)
+	}
+}
+
+/// Options of the socket level (`SOL_SOCKET`), which are common to all sockets
+///
+/// The buffers of the sockets have a fixed size. Thus, `SO_SNDBUF` and
+/// `SO_RCVBUF` are only hints, which are reported back by `getsockopt`.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct SocketOptions {
+	reuse_addr: bool,
+	keep_alive: bool,
+	broadcast: bool,
+	/// Linger time in seconds, negative if disabled
+	linger: i32,
+	send_buffer: i32,
+	recv_buffer: i32,
+	/// Send timeout in milliseconds, zero if disabled
+	send_timeout: i32,
+	/// Receive timeout in milliseconds, zero if disabled
+	recv_timeout: i32,
+}
+
+impl SocketOptions {
+	/// Creates the default options of a socket with buffers of `buffer_size` bytes.
+	pub const fn new(buffer_size: usize) -> Self {
+		Self {
+			reuse_addr: false,
+			keep_alive: false,
+			broadcast: false,
+			linger: -1,
+			send_buffer: buffer_size as i32,
+			recv_buffer: buffer_size as i32,
+			send_timeout: 0,
+			recv_timeout: 0,
+		}
+	}
+
+	/// Returns the time, for which closing waits for unsent data, if `SO_LINGER` is enabled.
+	#[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+	pub fn linger(&self) -> Option<Duration> {
+		u64::try_from(self.linger).ok().map(Duration::from_secs)
+	}
+
+	/// Returns the timeout `opt` (`SO_RCVTIMEO` or `SO_SNDTIMEO`), if it is enabled.
+	pub fn timeout(&self, opt: SocketOption) -> Option<Duration> {
+		let millis = match opt {
+			SocketOption::SendTimeout => self.send_timeout,
+			SocketOption::RecvTimeout => self.recv_timeout,
+			_ => return None,
+		};
+		u64::try_from(millis)
+			.ok()
+			.filter(|millis| *millis > 0)
+			.map(Duration::from_millis)
+	}
+
+	pub fn set(&mut self, opt: SocketOption, optval: i32) -> io::Result<()> {
+		match opt {
+			SocketOption::ReuseAddr => self.reuse_addr = optval != 0,
+			SocketOption::KeepAlive => self.keep_alive = optval != 0,
+			SocketOption::Broadcast => self.broadcast = optval != 0,
+			SocketOption::Linger => self.linger = optval.max(-1),
+			SocketOption::SendBuffer | SocketOption::RecvBuffer if optval <= 0 => {
+				return Err(io::Error::EINVAL);
+			}
+			SocketOption::SendBuffer => self.send_buffer = optval,
+			SocketOption::RecvBuffer => self.recv_buffer = optval,
+			SocketOption::SendTimeout => self.send_timeout = optval.max(0),
+			SocketOption::RecvTimeout => self.recv_timeout = optval.max(0),
+			_ => return Err(io::Error::EINVAL),
+		}
+
+		Ok(())
+	}
+
+	pub fn get(&self, opt: SocketOption) -> io::Result<i32> {
+		match opt {
+			SocketOption::ReuseAddr => Ok(self.reuse_addr.into()),
+			SocketOption::KeepAlive => Ok(self.keep_alive.into()),
+			SocketOption::Broadcast => Ok(self.broadcast.into()),
+			SocketOption::Linger => Ok(self.linger),
+			SocketOption::SendBuffer => Ok(self.send_buffer),
+			SocketOption::RecvBuffer => Ok(self.recv_buffer),
+			SocketOption::SendTimeout => Ok(self.send_timeout),
+			SocketOption::RecvTimeout => Ok(self.recv_timeout),
+			_ => Err(io::Error::EINVAL),
+		}
+	}
+}
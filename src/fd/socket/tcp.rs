@@ -1,33 +1,128 @@
 use alloc::boxed::Box;
-use alloc::collections::BTreeSet;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
 use alloc::sync::Arc;
 use core::future;
-use core::sync::atomic::{AtomicU16, Ordering};
-use core::task::Poll;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Poll, Waker};
 
 use async_trait::async_trait;
+use hermit_sync::InterruptTicketMutex;
 use smoltcp::iface;
 use smoltcp::socket::tcp;
 use smoltcp::time::Duration;
 
 use crate::executor::block_on;
-use crate::executor::network::{Handle, NIC};
-use crate::fd::{Endpoint, IoCtl, ListenEndpoint, ObjectInterface, PollEvent, SocketOption};
+use crate::executor::network::{Handle, NIC, NetworkInterface, SOCKET_BUFFER_SIZE, now};
+use crate::fd::socket::usage::{NetUsage, Usage};
+use crate::fd::socket::{Shutdown, SocketOptions, get_ephemeral_port};
+use crate::fd::{
+	Endpoint, IoCtl, ListenEndpoint, ObjectInterface, PollEvent, RecvFlags, RecvMsg, SocketOption,
+	scatter, total_len,
+};
 use crate::{DEFAULT_KEEP_ALIVE_INTERVAL, io};
 
-/// further receives will be disallowed
-pub const SHUT_RD: i32 = 0;
-/// further sends will be disallowed
-pub const SHUT_WR: i32 = 1;
-/// further sends and receives will be disallowed
-pub const SHUT_RDWR: i32 = 2;
 /// The default queue size for incoming connections
 pub const DEFAULT_BACKLOG: i32 = 128;
 
-fn get_ephemeral_port() -> u16 {
-	static LOCAL_ENDPOINT: AtomicU16 = AtomicU16::new(49152);
+/// Listener, which shares its port with other listeners (`SO_REUSEPORT`)
+#[derive(Debug, Default)]
+struct Member {
+	/// Established connections, which are assigned to the listener
+	assigned: VecDeque<Handle>,
+	/// Task, which waits for a connection
+	waker: Option<Waker>,
+}
+
+/// Listeners on the same port, which receive the connections in turn
+///
+/// The listening sockets belong to the group and not to a single listener.
+/// Whenever a connection is established, it is assigned to the next listener
+/// in a round-robin fashion and the listening socket is replaced.
+#[derive(Debug)]
+struct ReusePortGroup {
+	/// Sockets, which listen for connections
+	pool: BTreeSet<Handle>,
+	members: BTreeMap<u64, Member>,
+	/// Number of assigned connections, which selects the next listener
+	turn: usize,
+	nagle_enabled: bool,
+	defer_accept: Option<Duration>,
+}
+
+impl ReusePortGroup {
+	/// Assigns the established connections to the listeners.
+	fn distribute(&mut self, nic: &mut NetworkInterface<'_>, port: u16) -> io::Result<()> {
+		let established: alloc::vec::Vec<Handle> = self
+			.pool
+			.iter()
+			.copied()
+			.filter(|handle| nic.is_acceptable(*handle))
+			.collect();
+
+		for handle in established {
+			self.pool.remove(&handle);
+			nic.set_defer_accept(handle, None);
+			let new_handle = nic.create_tcp_handle().unwrap();
+			nic.set_defer_accept(new_handle, self.defer_accept);
+			let socket = nic.get_mut_socket::<tcp::Socket<'_>>(new_handle);
+			socket.set_nagle_enabled(self.nagle_enabled);
+			socket.listen(port).map_err(|_| io::Error::EIO)?;
+			self.pool.insert(new_handle);
+
+			self.assign(handle);
+		}
+
+		Ok(())
+	}
+
+	/// Assigns the connection `handle` to the next listener.
+	fn assign(&mut self, handle: Handle) {
+		let index = self.turn % self.members.len();
+		self.turn = self.turn.wrapping_add(1);
+		let member = self.members.values_mut().nth(index).unwrap();
+		member.assigned.push_back(handle);
+		if let Some(waker) = member.waker.take() {
+			waker.wake();
+		}
+	}
+
+	/// Registers the listener `id` for a wakeup on new connections.
+	fn register_waker(&mut self, id: u64, waker: &Waker, nic: &mut NetworkInterface<'_>) {
+		self.members.get_mut(&id).unwrap().waker = Some(waker.clone());
+		// Any waiting listener distributes the connections.
+		for handle in &self.pool {
+			nic.register_accept_waker(*handle, waker);
+		}
+	}
+}
+
+/// Moves the received data of `socket` into `bufs`.
+fn dequeue(socket: &mut tcp::Socket<'_>, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+	let mut pos = 0;
+	// The data may wrap around the end of the receive buffer.
+	while socket.can_recv() {
+		let len = socket
+			.recv(|data| {
+				let len = scatter(data, bufs, pos);
+				(len, len)
+			})
+			.map_err(|_| io::Error::EIO)?;
+		if len == 0 {
+			break;
+		}
+		pos += len;
+	}
+	Ok(pos)
+}
 
-	LOCAL_ENDPOINT.fetch_add(1, Ordering::SeqCst)
+/// Groups of listeners by their port
+static REUSE_PORT_GROUPS: InterruptTicketMutex<BTreeMap<u16, ReusePortGroup>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+fn get_listener_id() -> u64 {
+	static LISTENER_ID: AtomicU64 = AtomicU64::new(0);
+
+	LISTENER_ID.fetch_add(1, Ordering::Relaxed)
 }
 
 #[derive(Debug)]
@@ -36,6 +131,20 @@ pub struct Socket {
 	port: u16,
 	is_nonblocking: bool,
 	is_listen: bool,
+	/// Shares the port with other listeners
+	reuse_port: bool,
+	/// Identifies the listener in its [`ReusePortGroup`]
+	listener_id: Option<u64>,
+	/// Time, for which the acceptance of a connection is deferred until data arrives
+	defer_accept: Option<Duration>,
+	/// Directions, which have been shut down
+	shutdown: Shutdown,
+	/// Set while the connection is established, until its abort has been
+	/// reported by `SO_ERROR`
+	established: AtomicBool,
+	usage: Usage,
+	/// Options of the socket level (`SOL_SOCKET`)
+	options: SocketOptions,
 }
 
 impl Socket {
@@ -48,6 +157,13 @@ impl Socket {
 			port: 0,
 			is_nonblocking: false,
 			is_listen: false,
+			reuse_port: false,
+			listener_id: None,
+			defer_accept: None,
+			shutdown: Shutdown::default(),
+			established: AtomicBool::new(false),
+			usage: Usage::new(),
+			options: SocketOptions::new(SOCKET_BUFFER_SIZE),
 		}
 	}
 
@@ -64,7 +180,16 @@ impl Socket {
 		f(s, cx)
 	}
 
+	fn with_nic<R>(&self, f: impl FnOnce(&mut NetworkInterface<'_>, Handle) -> R) -> R {
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		f(nic, *self.handle.first().unwrap())
+	}
+
 	async fn close(&self) -> io::Result<()> {
+		// pass the held back data to the socket before sending the FIN
+		self.with_nic(|nic, handle| nic.flush_tcp(handle));
+
 		future::poll_fn(|_cx| {
 			self.with(|socket| {
 				if socket.is_active() {
@@ -104,7 +229,27 @@ impl Socket {
 	}
 
 	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		if let Some(id) = self.listener_id {
+			return self.poll_shared(id, event).await;
+		}
+
 		future::poll_fn(|cx| {
+			// Shut down directions do not block.
+			let mut shutdown = PollEvent::empty();
+			if self.shutdown.read() {
+				shutdown.insert(PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND);
+			}
+			if self.shutdown.write() {
+				shutdown.insert(PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND);
+			}
+
+			// A listener is readable, as soon as a connection can be accepted.
+			let acceptable = self.is_listen
+				&& self.with_nic(|nic, handle| {
+					nic.register_accept_waker(handle, cx.waker());
+					nic.is_acceptable(handle)
+				});
+
 			self.with(|socket| match socket.state() {
 				tcp::State::Closed | tcp::State::Closing | tcp::State::CloseWait => {
 					let available = PollEvent::POLLOUT
@@ -122,18 +267,17 @@ impl Socket {
 						Poll::Ready(Ok(ret))
 					}
 				}
-				tcp::State::FinWait1 | tcp::State::FinWait2 | tcp::State::TimeWait => {
-					Poll::Ready(Ok(PollEvent::POLLHUP))
-				}
+				tcp::State::TimeWait => Poll::Ready(Ok(PollEvent::POLLHUP)),
 				tcp::State::Listen => {
 					socket.register_recv_waker(cx.waker());
 					socket.register_send_waker(cx.waker());
 					Poll::Pending
 				}
 				_ => {
-					let mut available = PollEvent::empty();
+					// After a half-close (`SHUT_WR`), data can still be received.
+					let mut available = shutdown;
 
-					if socket.can_recv() || socket.may_recv() && self.is_listen {
+					if socket.can_recv() || acceptable {
 						// In case, we just establish a fresh connection in non-blocking mode, we try to read data.
 						available.insert(
 							PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND,
@@ -149,6 +293,7 @@ impl Socket {
 					let ret = event & available;
 
 					if ret.is_empty() {
+						self.shutdown.register_waker(cx.waker());
 						if event.intersects(
 							PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND,
 						) {
@@ -171,34 +316,159 @@ impl Socket {
 		.await
 	}
 
+	/// Waits for a connection, which is assigned to the shared listener `id`.
+	async fn poll_shared(&self, id: u64, event: PollEvent) -> io::Result<PollEvent> {
+		let readable = PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND;
+		if !event.intersects(readable) {
+			return Ok(PollEvent::empty());
+		}
+
+		future::poll_fn(|cx| {
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().unwrap();
+			let mut groups = REUSE_PORT_GROUPS.lock();
+			let group = groups.get_mut(&self.port).unwrap();
+			group.distribute(nic, self.port)?;
+
+			if group.members[&id].assigned.is_empty() {
+				group.register_waker(id, cx.waker(), nic);
+				Poll::Pending
+			} else {
+				Poll::Ready(Ok(event & readable))
+			}
+		})
+		.await
+	}
+
 	async fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
+		self.readv(&mut [buffer]).await
+	}
+
+	/// Copies the received data from the receive buffer into `bufs` without intermediate buffers.
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		self.receive_vectored(bufs, self.is_nonblocking).await
+	}
+
+	/// Receives data into `bufs` like `readv`, but only blocks if `nonblocking` is not set.
+	async fn receive_vectored(
+		&self,
+		bufs: &mut [&mut [u8]],
+		nonblocking: bool,
+	) -> io::Result<usize> {
+		let len = self
+			.receive(1, nonblocking, |socket| dequeue(socket, bufs))
+			.await?;
+		self.account_received(len);
+		Ok(len)
+	}
+
+	async fn recv(&self, buffer: &mut [u8], flags: RecvFlags) -> io::Result<usize> {
+		let nonblocking = self.is_nonblocking || flags.contains(RecvFlags::MSG_DONTWAIT);
+		let peek = flags.contains(RecvFlags::MSG_PEEK);
+		if peek {
+			let min = if flags.contains(RecvFlags::MSG_WAITALL) && !nonblocking {
+				buffer.len()
+			} else {
+				1
+			};
+			return self
+				.receive(min, nonblocking, |socket| {
+					socket.peek_slice(buffer).map_err(|_| io::Error::EIO)
+				})
+				.await;
+		}
+
+		if !flags.contains(RecvFlags::MSG_WAITALL) || nonblocking {
+			return self.receive_vectored(&mut [buffer], nonblocking).await;
+		}
+
+		let mut pos: usize = 0;
+		while pos < buffer.len() {
+			let remaining = &mut buffer[pos..];
+			let min = remaining.len();
+			match self
+				.receive(min, false, |socket| dequeue(socket, &mut [&mut *remaining]))
+				.await
+			{
+				Ok(0) => break,
+				Ok(len) => pos += len,
+				Err(_) if pos > 0 => break,
+				Err(err) => return Err(err),
+			}
+		}
+
+		self.account_received(pos);
+		Ok(pos)
+	}
+
+	/// Receives data like `recv` together with the receive time of the latest segment.
+	async fn recvmsg(&self, bufs: &mut [&mut [u8]], flags: RecvFlags) -> io::Result<RecvMsg> {
+		let len = match bufs {
+			[buffer] => self.recv(buffer, flags).await?,
+			bufs if flags.difference(RecvFlags::MSG_DONTWAIT).is_empty() => {
+				let nonblocking = self.is_nonblocking || flags.contains(RecvFlags::MSG_DONTWAIT);
+				self.receive_vectored(bufs, nonblocking).await?
+			}
+			_ => return Err(io::Error::EINVAL),
+		};
+
+		let timestamp = if len > 0 {
+			let peek = flags.contains(RecvFlags::MSG_PEEK);
+			self.with_nic(|nic, handle| nic.take_latest_rx_timestamp(handle, peek))
+		} else {
+			None
+		};
+
+		Ok(RecvMsg {
+			len,
+			endpoint: None,
+			timestamp,
+		})
+	}
+
+	/// Accounts `len` received bytes, if the connection has delivered any data.
+	fn account_received(&self, len: usize) {
+		if len > 0 {
+			self.usage.received(len);
+		}
+	}
+
+	/// Receives data by `f`, as soon as at least `min` bytes are available.
+	///
+	/// If the peer has closed the connection, the remaining data is returned
+	/// immediately. `min` is limited by the capacity of the receive buffer.
+	/// If `nonblocking` is set, `EAGAIN` is returned instead of waiting.
+	async fn receive(
+		&self,
+		min: usize,
+		nonblocking: bool,
+		mut f: impl FnMut(&mut tcp::Socket<'_>) -> io::Result<usize>,
+	) -> io::Result<usize> {
 		future::poll_fn(|cx| {
 			self.with(|socket| {
-				let state = socket.state();
-				match state {
+				if self.shutdown.read() {
+					// discard the incoming data
+					while socket.can_recv() {
+						let _ = socket.recv(|data| (data.len(), ()));
+					}
+					return Poll::Ready(Ok(0));
+				}
+
+				match socket.state() {
 					tcp::State::Closed => Poll::Ready(Ok(0)),
-					tcp::State::FinWait1
-					| tcp::State::FinWait2
-					| tcp::State::Listen
-					| tcp::State::TimeWait => Poll::Ready(Err(io::Error::EIO)),
+					tcp::State::Listen => Poll::Ready(Err(io::Error::EIO)),
 					_ => {
-						if socket.can_recv() {
-							Poll::Ready(
-								socket
-									.recv(|data| {
-										let len = core::cmp::min(buffer.len(), data.len());
-										buffer[..len].copy_from_slice(&data[..len]);
-										(len, len)
-									})
-									.map_err(|_| io::Error::EIO),
-							)
-						} else if state == tcp::State::CloseWait {
+						let min = min.clamp(1, socket.recv_capacity());
+						if socket.recv_queue() >= min || socket.can_recv() && !socket.may_recv() {
+							Poll::Ready(f(socket))
+						} else if !socket.may_recv() {
 							// The local end-point has received a connection termination request
 							// and not data are in the receive buffer => return 0 to close the connection
 							Poll::Ready(Ok(0))
-						} else if self.is_nonblocking {
+						} else if nonblocking {
 							Poll::Ready(Err(io::Error::EAGAIN))
 						} else {
+							self.shutdown.register_waker(cx.waker());
 							socket.register_recv_waker(cx.waker());
 							Poll::Pending
 						}
@@ -210,37 +480,55 @@ impl Socket {
 	}
 
 	async fn write(&self, buffer: &[u8]) -> io::Result<usize> {
+		self.writev(&[buffer]).await
+	}
+
+	/// Copies the data of `bufs` into the transmit buffer without intermediate buffers.
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		let len = total_len(bufs);
 		let mut pos: usize = 0;
 
-		while pos < buffer.len() {
+		while pos < len {
 			let n = future::poll_fn(|cx| {
-				self.with(|socket| {
+				if self.shutdown.write() {
+					return Poll::Ready(Err(io::Error::EPIPE));
+				}
+
+				self.with_nic(|nic, handle| {
+					let socket = nic.get_mut_socket::<tcp::Socket<'_>>(handle);
 					match socket.state() {
 						tcp::State::Closed | tcp::State::Closing | tcp::State::CloseWait => {
-							Poll::Ready(Ok(0))
+							return Poll::Ready(Ok(0));
 						}
 						tcp::State::FinWait1
 						| tcp::State::FinWait2
 						| tcp::State::Listen
-						| tcp::State::TimeWait => Poll::Ready(Err(io::Error::EIO)),
-						_ => {
-							if socket.can_send() {
-								Poll::Ready(
-									socket
-										.send_slice(&buffer[pos..])
-										.map_err(|_| io::Error::EIO),
-								)
-							} else if pos > 0 {
-								// we already send some data => return 0 as signal to stop the
-								// async write
-								Poll::Ready(Ok(0))
-							} else if self.is_nonblocking {
-								Poll::Ready(Err(io::Error::EAGAIN))
-							} else {
-								socket.register_send_waker(cx.waker());
-								Poll::Pending
-							}
-						}
+						| tcp::State::TimeWait => return Poll::Ready(Err(io::Error::EIO)),
+						_ => {}
+					}
+
+					// small writes are coalesced, as long as Nagle's algorithm is enabled
+					let autocork = socket.nagle_enabled();
+					let n = if socket.can_send() {
+						nic.tcp_send(handle, bufs, pos, autocork)
+							.map_err(|_| io::Error::EIO)?
+					} else {
+						0
+					};
+
+					if n > 0 {
+						Poll::Ready(Ok(n))
+					} else if pos > 0 {
+						// we already send some data => return 0 as signal to stop the
+						// async write
+						Poll::Ready(Ok(0))
+					} else if self.is_nonblocking {
+						Poll::Ready(Err(io::Error::EAGAIN))
+					} else {
+						self.shutdown.register_waker(cx.waker());
+						nic.get_mut_socket::<tcp::Socket<'_>>(handle)
+							.register_send_waker(cx.waker());
+						Poll::Pending
 					}
 				})
 			})
@@ -253,6 +541,9 @@ impl Socket {
 			pos += n;
 		}
 
+		if pos > 0 {
+			self.usage.sent(pos);
+		}
 		Ok(pos)
 	}
 
@@ -285,7 +576,10 @@ impl Socket {
 					_ => Poll::Ready(Ok(())),
 				})
 			})
-			.await
+			.await?;
+
+			self.established.store(true, Ordering::Relaxed);
+			Ok(())
 		} else {
 			Err(io::Error::EIO)
 		}
@@ -296,29 +590,29 @@ impl Socket {
 			self.listen(DEFAULT_BACKLOG).await?;
 		}
 
+		if let Some(id) = self.listener_id {
+			let connection_handle = self.accept_shared(id).await?;
+			return self.accepted(connection_handle);
+		}
+
 		let connection_handle = future::poll_fn(|cx| {
 			let mut guard = NIC.lock();
 			let nic = guard.as_nic_mut().unwrap();
-			let mut socket_handle = None;
-
-			for handle in self.handle.iter() {
-				let s = nic.get_mut_socket::<tcp::Socket<'_>>(*handle);
-
-				if s.is_active() {
-					socket_handle = Some(*handle);
-					break;
-				}
-			}
+			let socket_handle = self
+				.handle
+				.iter()
+				.copied()
+				.find(|handle| nic.is_acceptable(*handle));
 
 			if let Some(handle) = socket_handle {
 				self.handle.remove(&handle);
+				nic.set_defer_accept(handle, None);
 				Poll::Ready(Ok(handle))
 			} else if self.is_nonblocking {
 				Poll::Ready(Err(io::Error::EAGAIN))
 			} else {
 				for handle in self.handle.iter() {
-					let s = nic.get_mut_socket::<tcp::Socket<'_>>(*handle);
-					s.register_recv_waker(cx.waker());
+					nic.register_accept_waker(*handle, cx.waker());
 				}
 
 				Poll::Pending
@@ -326,19 +620,55 @@ impl Socket {
 		})
 		.await?;
 
+		{
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().map_err(|_| io::Error::EIO)?;
+			let nagle_enabled = nic
+				.get_mut_socket::<tcp::Socket<'_>>(connection_handle)
+				.nagle_enabled();
+			let timestamping = nic.is_rx_timestamping(connection_handle);
+
+			// fill up queue for pending connections
+			let new_handle = nic.create_tcp_handle().unwrap();
+			self.handle.insert(new_handle);
+			nic.set_rx_timestamping(new_handle, timestamping);
+			nic.set_defer_accept(new_handle, self.defer_accept);
+			let socket = nic.get_mut_socket::<tcp::Socket<'_>>(new_handle);
+			socket.set_nagle_enabled(nagle_enabled);
+			socket.listen(self.port).map_err(|_| io::Error::EIO)?;
+		}
+
+		self.accepted(connection_handle)
+	}
+
+	/// Waits for a connection, which is assigned to the shared listener `id`.
+	async fn accept_shared(&self, id: u64) -> io::Result<Handle> {
+		future::poll_fn(|cx| {
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().unwrap();
+			let mut groups = REUSE_PORT_GROUPS.lock();
+			let group = groups.get_mut(&self.port).unwrap();
+			group.distribute(nic, self.port)?;
+
+			if let Some(handle) = group.members.get_mut(&id).unwrap().assigned.pop_front() {
+				Poll::Ready(Ok(handle))
+			} else if self.is_nonblocking {
+				Poll::Ready(Err(io::Error::EAGAIN))
+			} else {
+				group.register_waker(id, cx.waker(), nic);
+				Poll::Pending
+			}
+		})
+		.await
+	}
+
+	/// Creates the socket of the accepted connection `connection_handle`.
+	fn accepted(&self, connection_handle: Handle) -> io::Result<(Socket, Endpoint)> {
 		let mut guard = NIC.lock();
 		let nic = guard.as_nic_mut().map_err(|_| io::Error::EIO)?;
 		let socket = nic.get_mut_socket::<tcp::Socket<'_>>(connection_handle);
 		socket.set_keep_alive(Some(Duration::from_millis(DEFAULT_KEEP_ALIVE_INTERVAL)));
 		let endpoint = Endpoint::Ip(socket.remote_endpoint().unwrap());
-		let nagle_enabled = socket.nagle_enabled();
-
-		// fill up queue for pending connections
-		let new_handle = nic.create_tcp_handle().unwrap();
-		self.handle.insert(new_handle);
-		let socket = nic.get_mut_socket::<tcp::Socket<'_>>(new_handle);
-		socket.set_nagle_enabled(nagle_enabled);
-		socket.listen(self.port).map_err(|_| io::Error::EIO)?;
 
 		let mut handle = BTreeSet::new();
 		handle.insert(connection_handle);
@@ -348,6 +678,14 @@ impl Socket {
 			port: self.port,
 			is_nonblocking: self.is_nonblocking,
 			is_listen: false,
+			reuse_port: false,
+			listener_id: None,
+			defer_accept: None,
+			shutdown: Shutdown::default(),
+			established: AtomicBool::new(true),
+			usage: Usage::new(),
+			// accepted sockets inherit the options of the listener
+			options: self.options,
 		};
 
 		Ok((socket, endpoint))
@@ -380,12 +718,18 @@ impl Socket {
 			return Err(io::Error::EINVAL);
 		}
 
+		if self.reuse_port {
+			return self.listen_shared(nic, backlog, nagle_enabled);
+		}
+
 		socket.listen(self.port).map_err(|_| io::Error::EIO)?;
+		nic.set_defer_accept(*self.handle.first().unwrap(), self.defer_accept);
 
 		self.is_listen = true;
 
 		for _ in 1..backlog {
 			let handle = nic.create_tcp_handle().unwrap();
+			nic.set_defer_accept(handle, self.defer_accept);
 
 			let s = nic.get_mut_socket::<tcp::Socket<'_>>(handle);
 			s.set_nagle_enabled(nagle_enabled);
@@ -397,41 +741,230 @@ impl Socket {
 		Ok(())
 	}
 
-	async fn setsockopt(&self, opt: SocketOption, optval: bool) -> io::Result<()> {
-		if opt == SocketOption::TcpNoDelay {
+	/// Joins the group of listeners on the port and adds `backlog` listening sockets to it.
+	///
+	/// The own socket of the listener does not listen, because connections
+	/// are only received through the group.
+	fn listen_shared(
+		&mut self,
+		nic: &mut NetworkInterface<'_>,
+		backlog: i32,
+		nagle_enabled: bool,
+	) -> io::Result<()> {
+		let mut groups = REUSE_PORT_GROUPS.lock();
+		let group = groups.entry(self.port).or_insert_with(|| ReusePortGroup {
+			pool: BTreeSet::new(),
+			members: BTreeMap::new(),
+			turn: 0,
+			nagle_enabled,
+			defer_accept: self.defer_accept,
+		});
+
+		for _ in 0..backlog {
+			let handle = nic.create_tcp_handle().unwrap();
+			nic.set_defer_accept(handle, group.defer_accept);
+			let socket = nic.get_mut_socket::<tcp::Socket<'_>>(handle);
+			socket.set_nagle_enabled(nagle_enabled);
+			socket.listen(self.port).map_err(|_| io::Error::EIO)?;
+			group.pool.insert(handle);
+		}
+
+		let id = get_listener_id();
+		group.members.insert(id, Member::default());
+		self.listener_id = Some(id);
+		self.is_listen = true;
+
+		Ok(())
+	}
+
+	/// Leaves the group of listeners.
+	///
+	/// The connections, which are not yet accepted, are passed on to the remaining
+	/// listeners. The last listener closes the listening sockets of the group.
+	fn leave_group(&mut self, id: u64) {
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		let mut groups = REUSE_PORT_GROUPS.lock();
+		let Some(group) = groups.get_mut(&self.port) else {
+			return;
+		};
+
+		let member = group.members.remove(&id).unwrap();
+		if group.members.is_empty() {
+			let group = groups.remove(&self.port).unwrap();
+			for handle in group.pool.into_iter().chain(member.assigned) {
+				let socket = nic.get_mut_socket::<tcp::Socket<'_>>(handle);
+				if socket.is_active() {
+					socket.abort();
+				}
+				nic.destroy_socket(handle);
+			}
+		} else {
+			for handle in member.assigned {
+				group.assign(handle);
+			}
+			// a remaining listener has to take over the wakeups of the listening sockets
+			for member in group.members.values_mut() {
+				if let Some(waker) = member.waker.take() {
+					waker.wake();
+				}
+			}
+		}
+	}
+
+	async fn setsockopt(&mut self, opt: SocketOption, optval: i32) -> io::Result<()> {
+		let enabled = optval != 0;
+		if opt == SocketOption::ReusePort {
+			if self.is_listen {
+				return Err(io::Error::EINVAL);
+			}
+
+			self.reuse_port = enabled;
+			Ok(())
+		} else if opt == SocketOption::TcpDeferAccept {
+			// Like on Linux, the value is the timeout in seconds.
+			let timeout = u64::try_from(optval).unwrap_or(0);
+			self.defer_accept = (timeout > 0).then(|| Duration::from_secs(timeout));
+			self.set_defer_accept();
+			Ok(())
+		} else if opt == SocketOption::TcpNoDelay {
 			let mut guard = NIC.lock();
 			let nic = guard.as_nic_mut().unwrap();
 
 			for i in self.handle.iter() {
 				let socket = nic.get_mut_socket::<tcp::Socket<'_>>(*i);
-				socket.set_nagle_enabled(optval);
+				socket.set_nagle_enabled(!enabled);
+				if enabled {
+					// data, which has been held back by autocorking, is sent immediately
+					nic.flush_tcp(*i);
+				}
+			}
+
+			Ok(())
+		} else if opt == SocketOption::TcpCork {
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().unwrap();
+
+			for i in self.handle.iter() {
+				nic.set_tcp_corked(*i, enabled);
+			}
+
+			Ok(())
+		} else if opt == SocketOption::Timestamping {
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().unwrap();
+
+			for i in self.handle.iter() {
+				nic.set_rx_timestamping(*i, enabled);
+			}
+
+			Ok(())
+		} else if opt == SocketOption::KeepAlive {
+			let interval = Duration::from_millis(DEFAULT_KEEP_ALIVE_INTERVAL);
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().unwrap();
+
+			for i in self.handle.iter() {
+				let socket = nic.get_mut_socket::<tcp::Socket<'_>>(*i);
+				socket.set_keep_alive(enabled.then_some(interval));
 			}
 
 			Ok(())
 		} else {
-			Err(io::Error::EINVAL)
+			self.options.set(opt, optval)
+		}
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<i32> {
+		match opt {
+			SocketOption::TcpDeferAccept => Ok(self
+				.defer_accept
+				.map_or(0, |timeout| timeout.secs().try_into().unwrap_or(i32::MAX))),
+			SocketOption::ReusePort
+			| SocketOption::TcpNoDelay
+			| SocketOption::TcpCork
+			| SocketOption::Timestamping
+			| SocketOption::KeepAlive => self.getsockopt_bool(opt).map(i32::from),
+			SocketOption::Error => Ok(self.take_error()),
+			_ => self.options.get(opt),
+		}
+	}
+
+	/// Applies `TCP_DEFER_ACCEPT` to the listening sockets, which wait for a connection.
+	fn set_defer_accept(&self) {
+		if !self.is_listen {
+			return;
+		}
+
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		if self.listener_id.is_some() {
+			let mut groups = REUSE_PORT_GROUPS.lock();
+			let group = groups.get_mut(&self.port).unwrap();
+			group.defer_accept = self.defer_accept;
+			for handle in &group.pool {
+				nic.set_defer_accept(*handle, self.defer_accept);
+			}
+		} else {
+			for handle in &self.handle {
+				nic.set_defer_accept(*handle, self.defer_accept);
+			}
 		}
 	}
 
-	async fn getsockopt(&self, opt: SocketOption) -> io::Result<bool> {
-		if opt == SocketOption::TcpNoDelay {
+	/// Returns the pending error (`SO_ERROR`) and clears it.
+	///
+	/// An established connection, which has been closed without shutting
+	/// down the local side, has been reset by the peer or has timed out.
+	fn take_error(&self) -> i32 {
+		let aborted =
+			!self.shutdown.write() && self.with(|socket| socket.state() == tcp::State::Closed);
+		if aborted && self.established.swap(false, Ordering::Relaxed) {
+			crate::errno::ECONNRESET
+		} else {
+			0
+		}
+	}
+
+	fn getsockopt_bool(&self, opt: SocketOption) -> io::Result<bool> {
+		if opt == SocketOption::ReusePort {
+			Ok(self.reuse_port)
+		} else if opt == SocketOption::TcpNoDelay {
 			let mut guard = NIC.lock();
 			let nic = guard.as_nic_mut().unwrap();
 			let socket = nic.get_mut_socket::<tcp::Socket<'_>>(*self.handle.first().unwrap());
 
-			Ok(socket.nagle_enabled())
+			Ok(!socket.nagle_enabled())
+		} else if opt == SocketOption::TcpCork {
+			Ok(self.with_nic(|nic, handle| nic.is_tcp_corked(handle)))
+		} else if opt == SocketOption::Timestamping {
+			Ok(self.with_nic(|nic, handle| nic.is_rx_timestamping(handle)))
+		} else if opt == SocketOption::KeepAlive {
+			Ok(self.with(|socket| socket.keep_alive().is_some()))
 		} else {
 			Err(io::Error::EINVAL)
 		}
 	}
 
+	/// Shuts down the directions given by `how`.
+	///
+	/// `SHUT_WR` sends a FIN after the pending data, while data can still be
+	/// received (half-close). After `SHUT_RD`, received data is discarded and
+	/// receiving returns the end of the stream.
 	async fn shutdown(&self, how: i32) -> io::Result<()> {
-		match how {
-			SHUT_RD /* Read  */ |
-			SHUT_WR /* Write */ |
-			SHUT_RDWR /* Both */ => Ok(()),
-			_ => Err(io::Error::EINVAL),
+		if !self.is_listen && !self.with(|socket| socket.is_active()) {
+			return Err(io::Error::ENOTCONN);
+		}
+
+		let (_, added_write) = self.shutdown.insert(how)?;
+		if added_write && !self.is_listen {
+			self.with_nic(|nic, handle| {
+				nic.flush_tcp(handle);
+				nic.get_mut_socket::<tcp::Socket<'_>>(handle).close();
+			});
 		}
+
+		Ok(())
 	}
 
 	async fn ioctl(&mut self, cmd: IoCtl, value: bool) -> io::Result<()> {
@@ -453,11 +986,33 @@ impl Socket {
 
 impl Drop for Socket {
 	fn drop(&mut self) {
-		let _ = block_on(self.close(), None);
+		if let Some(id) = self.listener_id {
+			self.leave_group(id);
+		}
+
+		// With `SO_LINGER` or on shutdown, the connection is aborted, if the
+		// unsent data cannot be delivered within the linger time.
+		let linger = self.options.linger();
+		let timeout = crate::fd::close_timeout(linger);
+		if linger != Some(core::time::Duration::ZERO) {
+			let _ = block_on(self.close(), timeout);
+		}
 
 		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		if timeout.is_some() {
+			for h in self.handle.iter() {
+				let socket = nic.get_mut_socket::<tcp::Socket<'_>>(*h);
+				if socket.is_active() {
+					socket.abort();
+				}
+			}
+			// send the resets before the sockets are destroyed
+			nic.poll_common(now());
+		}
+
 		for h in self.handle.iter() {
-			guard.as_nic_mut().unwrap().destroy_socket(*h);
+			nic.destroy_socket(*h);
 		}
 	}
 }
@@ -472,10 +1027,22 @@ impl ObjectInterface for async_lock::RwLock<Socket> {
 		self.read().await.read(buffer).await
 	}
 
+	async fn recv(&self, buffer: &mut [u8], flags: RecvFlags) -> io::Result<usize> {
+		self.read().await.recv(buffer, flags).await
+	}
+
 	async fn write(&self, buffer: &[u8]) -> io::Result<usize> {
 		self.read().await.write(buffer).await
 	}
 
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		self.read().await.readv(bufs).await
+	}
+
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		self.read().await.writev(bufs).await
+	}
+
 	async fn bind(&self, endpoint: ListenEndpoint) -> io::Result<()> {
 		self.write().await.bind(endpoint).await
 	}
@@ -501,14 +1068,27 @@ impl ObjectInterface for async_lock::RwLock<Socket> {
 		self.write().await.listen(backlog).await
 	}
 
-	async fn setsockopt(&self, opt: SocketOption, optval: bool) -> io::Result<()> {
-		self.read().await.setsockopt(opt, optval).await
+	async fn setsockopt(&self, opt: SocketOption, optval: i32) -> io::Result<()> {
+		self.write().await.setsockopt(opt, optval).await
 	}
 
-	async fn getsockopt(&self, opt: SocketOption) -> io::Result<bool> {
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<i32> {
 		self.read().await.getsockopt(opt).await
 	}
 
+	fn io_timeout(&self, opt: SocketOption) -> Option<core::time::Duration> {
+		// no timeout is applied, while the socket is locked for writing
+		self.try_read()?.options.timeout(opt)
+	}
+
+	async fn recvmsg(&self, bufs: &mut [&mut [u8]], flags: RecvFlags) -> io::Result<RecvMsg> {
+		self.read().await.recvmsg(bufs, flags).await
+	}
+
+	async fn usage(&self) -> io::Result<NetUsage> {
+		Ok(self.read().await.usage.get())
+	}
+
 	async fn shutdown(&self, how: i32) -> io::Result<()> {
 		self.read().await.shutdown(how).await
 	}
@@ -5,18 +5,33 @@ use core::task::Poll;
 use async_trait::async_trait;
 use smoltcp::socket::udp;
 use smoltcp::socket::udp::UdpMetadata;
-use smoltcp::wire::IpEndpoint;
+use smoltcp::wire::{IpAddress, IpEndpoint};
 
 use crate::executor::block_on;
-use crate::executor::network::{Handle, NIC};
-use crate::fd::{Endpoint, IoCtl, ListenEndpoint, ObjectInterface, PollEvent};
+use crate::executor::network::{Handle, NIC, SOCKET_BUFFER_SIZE};
+use crate::fd::socket::usage::{NetUsage, Usage};
+use crate::fd::socket::{Shutdown, SocketOptions};
+use crate::fd::{
+	Endpoint, IoCtl, ListenEndpoint, ObjectInterface, PollEvent, RecvFlags, RecvMsg, SocketOption,
+	gather, scatter, total_len,
+};
 use crate::io;
 
+/// Maximum number of datagrams, into which a write is segmented (`UDP_SEGMENT`)
+const UDP_MAX_SEGMENTS: usize = 64;
+
 #[derive(Debug)]
 pub struct Socket {
 	handle: Handle,
 	nonblocking: bool,
 	endpoint: Option<IpEndpoint>,
+	/// Directions, which have been shut down
+	shutdown: Shutdown,
+	usage: Usage,
+	/// Size of the datagrams, into which writes are segmented, zero if disabled (`UDP_SEGMENT`)
+	segment_size: u16,
+	/// Options of the socket level (`SOL_SOCKET`)
+	options: SocketOptions,
 }
 
 impl Socket {
@@ -25,6 +40,10 @@ impl Socket {
 			handle,
 			nonblocking: false,
 			endpoint: None,
+			shutdown: Shutdown::default(),
+			usage: Usage::new(),
+			segment_size: 0,
+			options: SocketOptions::new(SOCKET_BUFFER_SIZE),
 		}
 	}
 
@@ -44,27 +63,56 @@ impl Socket {
 		.await
 	}
 
-	async fn write_with_meta(&self, buffer: &[u8], meta: &UdpMetadata) -> io::Result<usize> {
-		future::poll_fn(|cx| {
-			self.with(|socket| {
-				if socket.is_open() {
-					if socket.can_send() {
-						Poll::Ready(
-							socket
-								.send_slice(buffer, *meta)
-								.map(|()| buffer.len())
-								.map_err(|_| io::Error::EIO),
-						)
+	/// Sends the data of `bufs` as a single datagram, which is gathered
+	/// directly in the transmit buffer.
+	///
+	/// With `UDP_SEGMENT`, the data is sent as datagrams of the segment size
+	/// instead, of which only the last one may be smaller.
+	async fn write_with_meta(&self, bufs: &[&[u8]], meta: &UdpMetadata) -> io::Result<usize> {
+		let len = total_len(bufs);
+		let segment_size = match self.segment_size {
+			0 => len,
+			size => usize::from(size),
+		};
+		if segment_size > 0 && len.div_ceil(segment_size) > UDP_MAX_SEGMENTS {
+			return Err(io::Error::EINVAL);
+		}
+
+		let mut sent = 0;
+		loop {
+			let segment = segment_size.min(len - sent);
+			future::poll_fn(|cx| {
+				if self.shutdown.write() {
+					return Poll::Ready(Err(io::Error::EPIPE));
+				}
+
+				self.with(|socket| {
+					if socket.is_open() {
+						if socket.can_send() {
+							Poll::Ready(
+								socket
+									.send(segment, *meta)
+									.map(|payload| gather(bufs, sent, payload))
+									.map_err(|_| io::Error::EIO),
+							)
+						} else {
+							self.shutdown.register_waker(cx.waker());
+							socket.register_recv_waker(cx.waker());
+							Poll::Pending
+						}
 					} else {
-						socket.register_recv_waker(cx.waker());
-						Poll::Pending
+						Poll::Ready(Err(io::Error::EIO))
 					}
-				} else {
-					Poll::Ready(Err(io::Error::EIO))
-				}
+				})
 			})
-		})
-		.await
+			.await?;
+
+			self.usage.sent(segment);
+			sent += segment;
+			if sent == len {
+				return Ok(len);
+			}
+		}
 	}
 
 	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
@@ -73,6 +121,18 @@ impl Socket {
 				let ret = if socket.is_open() {
 					let mut avail = PollEvent::empty();
 
+					// Shut down directions do not block.
+					if self.shutdown.read() {
+						avail.insert(
+							PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND,
+						);
+					}
+					if self.shutdown.write() {
+						avail.insert(
+							PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND,
+						);
+					}
+
 					if socket.can_send() {
 						avail.insert(
 							PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND,
@@ -91,6 +151,7 @@ impl Socket {
 				};
 
 				if ret.is_empty() {
+					self.shutdown.register_waker(cx.waker());
 					if event.intersects(
 						PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND,
 					) {
@@ -132,89 +193,176 @@ impl Socket {
 	}
 
 	async fn sendto(&self, buf: &[u8], endpoint: Endpoint) -> io::Result<usize> {
+		self.sendmsg(&[buf], endpoint).await
+	}
+
+	/// Sends the data of `bufs` as a datagram to `endpoint` without intermediate buffers.
+	async fn sendmsg(&self, bufs: &[&[u8]], endpoint: Endpoint) -> io::Result<usize> {
 		#[allow(irrefutable_let_patterns)]
 		if let Endpoint::Ip(endpoint) = endpoint {
 			let meta = UdpMetadata::from(endpoint);
-			self.write_with_meta(buf, &meta).await
+			self.write_with_meta(bufs, &meta).await
 		} else {
 			Err(io::Error::EIO)
 		}
 	}
 
-	async fn recvfrom(&self, buffer: &mut [u8]) -> io::Result<(usize, Endpoint)> {
-		future::poll_fn(|cx| {
+	/// Receives the next datagram of the connected peer or, if the socket
+	/// is not connected, of any peer.
+	///
+	/// With `MSG_PEEK`, the datagram remains in the receive queue. Datagrams
+	/// are always received as a whole, so `MSG_WAITALL` has no effect. With
+	/// `MSG_DONTWAIT` or in nonblocking mode, `EAGAIN` is returned instead of waiting.
+	async fn recvfrom(&self, buffer: &mut [u8], flags: RecvFlags) -> io::Result<(usize, Endpoint)> {
+		self.recvfrom_vectored(&mut [buffer], flags).await
+	}
+
+	/// Receives the next datagram like `recvfrom` and scatters it directly
+	/// from the receive buffer into `bufs`.
+	async fn recvfrom_vectored(
+		&self,
+		bufs: &mut [&mut [u8]],
+		flags: RecvFlags,
+	) -> io::Result<(usize, Endpoint)> {
+		let (len, endpoint) = future::poll_fn(|cx| {
 			self.with(|socket| {
-				if socket.is_open() {
-					if socket.can_recv() {
-						match socket.recv_slice(buffer) {
-							Ok((len, meta)) => match self.endpoint {
-								Some(ep) => {
-									if meta.endpoint == ep {
-										Poll::Ready(Ok((len, meta.endpoint)))
-									} else {
-										buffer[..len].iter_mut().for_each(|x| *x = 0);
-										socket.register_recv_waker(cx.waker());
-										Poll::Pending
-									}
-								}
-								None => Poll::Ready(Ok((len, meta.endpoint))),
-							},
-							_ => Poll::Ready(Err(io::Error::EIO)),
-						}
+				if !socket.is_open() {
+					return Poll::Ready(Err(io::Error::EIO));
+				}
+
+				if self.shutdown.read() {
+					// discard the incoming datagrams
+					while socket.recv().is_ok() {}
+					let endpoint = self
+						.endpoint
+						.unwrap_or_else(|| IpEndpoint::new(IpAddress::v4(0, 0, 0, 0), 0));
+					return Poll::Ready(Ok((0, endpoint)));
+				}
+
+				while socket.can_recv() {
+					let result = if flags.contains(RecvFlags::MSG_PEEK) {
+						socket.peek().map(|(data, meta)| (data, meta.endpoint))
 					} else {
-						socket.register_recv_waker(cx.waker());
-						Poll::Pending
+						socket.recv().map(|(data, meta)| (data, meta.endpoint))
+					};
+
+					match result {
+						Ok((data, endpoint)) if self.endpoint.is_none_or(|ep| ep == endpoint) => {
+							return Poll::Ready(Ok((scatter(data, bufs, 0), endpoint)));
+						}
+						Ok(_) => {
+							// discard the datagram of a foreign peer
+							if flags.contains(RecvFlags::MSG_PEEK) {
+								let _ = socket.recv();
+							}
+						}
+						Err(_) => return Poll::Ready(Err(io::Error::EIO)),
 					}
-				} else {
-					Poll::Ready(Err(io::Error::EIO))
 				}
+
+				if self.nonblocking || flags.contains(RecvFlags::MSG_DONTWAIT) {
+					return Poll::Ready(Err(io::Error::EAGAIN));
+				}
+
+				self.shutdown.register_waker(cx.waker());
+				socket.register_recv_waker(cx.waker());
+				Poll::Pending
 			})
 		})
-		.await
-		.map(|(len, endpoint)| (len, Endpoint::Ip(endpoint)))
+		.await?;
+
+		if !flags.contains(RecvFlags::MSG_PEEK) && !self.shutdown.read() {
+			self.usage.received(len);
+		}
+		Ok((len, Endpoint::Ip(endpoint)))
 	}
 
 	async fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
-		future::poll_fn(|cx| {
-			self.with(|socket| {
-				if socket.is_open() {
-					if socket.can_recv() {
-						match socket.recv_slice(buffer) {
-							Ok((len, meta)) => match self.endpoint {
-								Some(ep) => {
-									if meta.endpoint == ep {
-										Poll::Ready(Ok(len))
-									} else {
-										buffer[..len].iter_mut().for_each(|x| *x = 0);
-										socket.register_recv_waker(cx.waker());
-										Poll::Pending
-									}
-								}
-								None => Poll::Ready(Ok(len)),
-							},
-							_ => Poll::Ready(Err(io::Error::EIO)),
-						}
-					} else {
-						socket.register_recv_waker(cx.waker());
-						Poll::Pending
-					}
-				} else {
-					Poll::Ready(Err(io::Error::EIO))
-				}
-			})
-		})
-		.await
+		self.readv(&mut [buffer]).await
+	}
+
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		self.recvfrom_vectored(bufs, RecvFlags::empty())
+			.await
+			.map(|(len, _)| len)
 	}
 
 	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		self.writev(&[buf]).await
+	}
+
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
 		if let Some(endpoint) = self.endpoint {
 			let meta = UdpMetadata::from(endpoint);
-			self.write_with_meta(buf, &meta).await
+			self.write_with_meta(bufs, &meta).await
 		} else {
 			Err(io::Error::EINVAL)
 		}
 	}
 
+	/// Shuts down the directions given by `how` of a connected socket.
+	///
+	/// After `SHUT_RD`, received datagrams are discarded and receiving returns
+	/// zero bytes. After `SHUT_WR`, sending fails with `EPIPE`.
+	async fn shutdown(&self, how: i32) -> io::Result<()> {
+		if self.endpoint.is_none() {
+			return Err(io::Error::ENOTCONN);
+		}
+
+		self.shutdown.insert(how)?;
+		Ok(())
+	}
+
+	/// Receives the next datagram like `recvfrom` together with its receive time.
+	async fn recvmsg(&self, bufs: &mut [&mut [u8]], flags: RecvFlags) -> io::Result<RecvMsg> {
+		let (len, endpoint) = self.recvfrom_vectored(bufs, flags).await?;
+
+		let timestamp = match endpoint {
+			Endpoint::Ip(source) if !self.shutdown.read() => {
+				let peek = flags.contains(RecvFlags::MSG_PEEK);
+				let mut guard = NIC.lock();
+				let nic = guard.as_nic_mut().unwrap();
+				nic.take_rx_timestamp(self.handle, source, peek)
+			}
+			_ => None,
+		};
+
+		Ok(RecvMsg {
+			len,
+			endpoint: Some(endpoint),
+			timestamp,
+		})
+	}
+
+	async fn setsockopt(&mut self, opt: SocketOption, optval: i32) -> io::Result<()> {
+		if opt == SocketOption::Timestamping {
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().unwrap();
+			nic.set_rx_timestamping(self.handle, optval != 0);
+			Ok(())
+		} else if opt == SocketOption::UdpSegment {
+			self.segment_size = u16::try_from(optval).map_err(|_| io::Error::EINVAL)?;
+			Ok(())
+		} else {
+			self.options.set(opt, optval)
+		}
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<i32> {
+		if opt == SocketOption::Timestamping {
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().unwrap();
+			Ok(nic.is_rx_timestamping(self.handle).into())
+		} else if opt == SocketOption::UdpSegment {
+			Ok(self.segment_size.into())
+		} else if opt == SocketOption::Error {
+			// smoltcp does not report ICMP errors, so that no error can be pending
+			Ok(0)
+		} else {
+			self.options.get(opt)
+		}
+	}
+
 	async fn ioctl(&mut self, cmd: IoCtl, value: bool) -> io::Result<()> {
 		if cmd == IoCtl::NonBlocking {
 			if value {
@@ -257,8 +405,20 @@ impl ObjectInterface for async_lock::RwLock<Socket> {
 		self.read().await.sendto(buffer, endpoint).await
 	}
 
-	async fn recvfrom(&self, buffer: &mut [u8]) -> io::Result<(usize, Endpoint)> {
-		self.read().await.recvfrom(buffer).await
+	async fn sendmsg(&self, bufs: &[&[u8]], endpoint: Endpoint) -> io::Result<usize> {
+		self.read().await.sendmsg(bufs, endpoint).await
+	}
+
+	async fn recv(&self, buffer: &mut [u8], flags: RecvFlags) -> io::Result<usize> {
+		self.read()
+			.await
+			.recvfrom(buffer, flags)
+			.await
+			.map(|(len, _)| len)
+	}
+
+	async fn recvfrom(&self, buffer: &mut [u8], flags: RecvFlags) -> io::Result<(usize, Endpoint)> {
+		self.read().await.recvfrom(buffer, flags).await
 	}
 
 	async fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
@@ -269,7 +429,40 @@ impl ObjectInterface for async_lock::RwLock<Socket> {
 		self.read().await.write(buf).await
 	}
 
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		self.read().await.readv(bufs).await
+	}
+
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		self.read().await.writev(bufs).await
+	}
+
+	async fn shutdown(&self, how: i32) -> io::Result<()> {
+		self.read().await.shutdown(how).await
+	}
+
 	async fn ioctl(&self, cmd: IoCtl, value: bool) -> io::Result<()> {
 		self.write().await.ioctl(cmd, value).await
 	}
+
+	async fn recvmsg(&self, bufs: &mut [&mut [u8]], flags: RecvFlags) -> io::Result<RecvMsg> {
+		self.read().await.recvmsg(bufs, flags).await
+	}
+
+	async fn setsockopt(&self, opt: SocketOption, optval: i32) -> io::Result<()> {
+		self.write().await.setsockopt(opt, optval).await
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<i32> {
+		self.read().await.getsockopt(opt).await
+	}
+
+	fn io_timeout(&self, opt: SocketOption) -> Option<core::time::Duration> {
+		// no timeout is applied, while the socket is locked for writing
+		self.try_read()?.options.timeout(opt)
+	}
+
+	async fn usage(&self) -> io::Result<NetUsage> {
+		Ok(self.read().await.usage.get())
+	}
 }
@@ -0,0 +1,196 @@
+//! Accounting of the network usage
+//!
+//! Every socket counts the bytes and packets, which it has sent and received.
+//! For TCP sockets, a packet is a successful send or receive operation, because
+//! the segmentation is hidden by the network stack. The counters of a socket are
+//! atomics, so that sending and receiving does not take a global lock.
+//!
+//! In addition, the usage is aggregated per task, which has created the
+//! sockets, together with the number of its open sockets. The usage of the
+//! open sockets is summed up, when the usage is reported. The usage of closed
+//! sockets is kept by their task.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::arch::kernel::core_local::core_scheduler;
+use crate::scheduler::task::TaskId;
+
+/// Snapshot of the network usage
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct NetUsage {
+	pub rx_bytes: u64,
+	pub rx_packets: u64,
+	pub tx_bytes: u64,
+	pub tx_packets: u64,
+}
+
+impl NetUsage {
+	fn add(&mut self, other: &Self) {
+		self.rx_bytes += other.rx_bytes;
+		self.rx_packets += other.rx_packets;
+		self.tx_bytes += other.tx_bytes;
+		self.tx_packets += other.tx_packets;
+	}
+}
+
+/// Counters of a socket
+#[derive(Debug, Default)]
+struct Counters {
+	rx_bytes: AtomicU64,
+	rx_packets: AtomicU64,
+	tx_bytes: AtomicU64,
+	tx_packets: AtomicU64,
+}
+
+impl Counters {
+	fn get(&self) -> NetUsage {
+		NetUsage {
+			rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+			rx_packets: self.rx_packets.load(Ordering::Relaxed),
+			tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+			tx_packets: self.tx_packets.load(Ordering::Relaxed),
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+struct TaskUsage {
+	/// Counters of the open sockets, which have been created by the task
+	sockets: Vec<Arc<Counters>>,
+	/// Usage of the closed sockets of the task
+	closed: NetUsage,
+	/// Set, after the task has exited
+	exited: bool,
+}
+
+impl TaskUsage {
+	fn get(&self) -> NetUsage {
+		let mut usage = self.closed;
+		for counters in &self.sockets {
+			usage.add(&counters.get());
+		}
+		usage
+	}
+}
+
+/// Network usage of the tasks, which own sockets or whose sockets have been closed
+static TASKS: InterruptTicketMutex<BTreeMap<TaskId, TaskUsage>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Network usage of the tasks, which have exited
+static EXITED: InterruptTicketMutex<NetUsage> = InterruptTicketMutex::new(NetUsage {
+	rx_bytes: 0,
+	rx_packets: 0,
+	tx_bytes: 0,
+	tx_packets: 0,
+});
+
+/// Network usage of a socket
+#[derive(Debug)]
+pub(crate) struct Usage {
+	/// Task, which has created the socket
+	owner: TaskId,
+	counters: Arc<Counters>,
+}
+
+impl Usage {
+	/// Creates the accounting of a new socket, which is owned by the current task.
+	pub fn new() -> Self {
+		let owner = core_scheduler().get_current_task_id();
+		let counters = Arc::new(Counters::default());
+		TASKS
+			.lock()
+			.entry(owner)
+			.or_default()
+			.sockets
+			.push(counters.clone());
+
+		Self { owner, counters }
+	}
+
+	/// Accounts a received packet with `len` bytes.
+	pub fn received(&self, len: usize) {
+		let len = u64::try_from(len).unwrap();
+		self.counters.rx_bytes.fetch_add(len, Ordering::Relaxed);
+		self.counters.rx_packets.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Accounts a sent packet with `len` bytes.
+	pub fn sent(&self, len: usize) {
+		let len = u64::try_from(len).unwrap();
+		self.counters.tx_bytes.fetch_add(len, Ordering::Relaxed);
+		self.counters.tx_packets.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Returns the current usage of the socket.
+	pub fn get(&self) -> NetUsage {
+		self.counters.get()
+	}
+}
+
+impl Drop for Usage {
+	fn drop(&mut self) {
+		let mut tasks = TASKS.lock();
+		let Some(task) = tasks.get_mut(&self.owner) else {
+			return;
+		};
+
+		task.sockets
+			.retain(|counters| !Arc::ptr_eq(counters, &self.counters));
+		if task.exited {
+			// The entries of exited tasks are only kept for their open sockets.
+			EXITED.lock().add(&self.counters.get());
+			if task.sockets.is_empty() {
+				tasks.remove(&self.owner);
+			}
+		} else {
+			task.closed.add(&self.counters.get());
+		}
+	}
+}
+
+/// Adds the usage of the closed sockets of the exited task `id` to the usage
+/// of all exited tasks.
+///
+/// The sockets, which are still open, remain accounted to the task, until they are closed.
+pub(crate) fn task_exited(id: TaskId) {
+	let mut tasks = TASKS.lock();
+	let Some(task) = tasks.get_mut(&id) else {
+		return;
+	};
+
+	EXITED.lock().add(&task.closed);
+	if task.sockets.is_empty() {
+		tasks.remove(&id);
+	} else {
+		task.closed = NetUsage::default();
+		task.exited = true;
+	}
+}
+
+/// Returns the network usage per task.
+pub(crate) fn report() -> String {
+	let mut report = String::from("TASK\tSOCKETS\tRX_BYTES\tRX_PACKETS\tTX_BYTES\tTX_PACKETS\n");
+	let mut entry = |task: &dyn core::fmt::Display, sockets: usize, usage: &NetUsage| {
+		writeln!(
+			report,
+			"{task}\t{sockets}\t{}\t{}\t{}\t{}",
+			usage.rx_bytes, usage.rx_packets, usage.tx_bytes, usage.tx_packets
+		)
+		.unwrap();
+	};
+
+	for (id, task) in TASKS.lock().iter() {
+		entry(id, task.sockets.len(), &task.get());
+	}
+	entry(&"exited", 0, &EXITED.lock());
+
+	report
+}
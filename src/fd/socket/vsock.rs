@@ -1,19 +1,25 @@
 use alloc::boxed::Box;
 use alloc::sync::Arc;
-use alloc::vec::Vec;
 use core::future;
 use core::task::Poll;
 
 use async_trait::async_trait;
-use virtio::vsock::{Hdr, Op, Type};
+use virtio::vsock::{Hdr, Op, ShutdownF, Type};
 use virtio::{le16, le32, le64};
 
 #[cfg(not(feature = "pci"))]
 use crate::arch::kernel::mmio as hardware;
+use crate::config::VSOCK_PACKET_SIZE;
 #[cfg(feature = "pci")]
 use crate::drivers::pci as hardware;
-use crate::executor::vsock::{VSOCK_MAP, VsockState};
-use crate::fd::{Endpoint, IoCtl, ListenEndpoint, ObjectInterface, PollEvent};
+use crate::executor::vsock::{
+	ConnectionId, DEFAULT_BACKLOG, RAW_SOCKET_BUFFER_SIZE, VSOCK_MAP, VsockState,
+};
+use crate::fd::socket::{Shutdown, SocketOptions};
+use crate::fd::{
+	Endpoint, IoCtl, ListenEndpoint, ObjectInterface, PollEvent, SocketOption, gather, scatter,
+	total_len,
+};
 use crate::io::{self, Error};
 
 #[derive(Debug)]
@@ -40,23 +46,19 @@ impl VsockEndpoint {
 	}
 }
 
-#[derive(Debug)]
-pub struct NullSocket;
-
-impl NullSocket {
-	pub const fn new() -> Self {
-		Self {}
-	}
-}
-
-#[async_trait]
-impl ObjectInterface for async_lock::RwLock<NullSocket> {}
-
 #[derive(Debug)]
 pub struct Socket {
 	port: u32,
 	cid: u32,
+	/// The socket listens on `port`.
+	is_listen: bool,
+	/// Connection of the socket, if it has been connected or accepted
+	connection: Option<ConnectionId>,
 	is_nonblocking: bool,
+	/// Directions, which have been shut down
+	shutdown: Shutdown,
+	/// Options of the socket level (`SOL_SOCKET`)
+	options: SocketOptions,
 }
 
 impl Socket {
@@ -64,17 +66,42 @@ impl Socket {
 		Self {
 			port: 0,
 			cid: u32::MAX,
+			is_listen: false,
+			connection: None,
 			is_nonblocking: false,
+			shutdown: Shutdown::default(),
+			options: SocketOptions::new(RAW_SOCKET_BUFFER_SIZE),
 		}
 	}
 
+	fn connection(&self) -> io::Result<ConnectionId> {
+		self.connection.ok_or(Error::ENOTCONN)
+	}
+
 	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
 		future::poll_fn(|cx| {
 			let mut guard = VSOCK_MAP.lock();
-			let raw = guard.get_mut_socket(self.port).ok_or(Error::EINVAL)?;
 
+			// A listening socket is readable, as long as connection requests are pending.
+			let Some(id) = self.connection else {
+				let listener = guard.get_mut_listener(self.port).ok_or(Error::EINVAL)?;
+				let ret = if listener.backlog.is_empty() {
+					PollEvent::empty()
+				} else {
+					event & (PollEvent::POLLIN | PollEvent::POLLRDNORM)
+				};
+
+				return if ret.is_empty() {
+					listener.rx_waker.register(cx.waker());
+					Poll::Pending
+				} else {
+					Poll::Ready(Ok(ret))
+				};
+			};
+
+			let raw = guard.get_mut_socket(id).ok_or(Error::EINVAL)?;
 			match raw.state {
-				VsockState::Shutdown | VsockState::ReceiveRequest => {
+				VsockState::Shutdown => {
 					let available = PollEvent::POLLOUT
 						| PollEvent::POLLWRNORM
 						| PollEvent::POLLWRBAND
@@ -90,7 +117,7 @@ impl Socket {
 						Poll::Ready(Ok(ret))
 					}
 				}
-				VsockState::Listen | VsockState::Connecting => {
+				VsockState::Connecting => {
 					raw.rx_waker.register(cx.waker());
 					raw.tx_waker.register(cx.waker());
 					Poll::Pending
@@ -98,6 +125,18 @@ impl Socket {
 				VsockState::Connected => {
 					let mut available = PollEvent::empty();
 
+					// Shut down directions do not block.
+					if self.shutdown.read() {
+						available.insert(
+							PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND,
+						);
+					}
+					if self.shutdown.write() {
+						available.insert(
+							PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND,
+						);
+					}
+
 					if !raw.buffer.is_empty() {
 						// In case, we just establish a fresh connection in non-blocking mode, we try to read data.
 						available.insert(
@@ -105,8 +144,8 @@ impl Socket {
 						);
 					}
 
-					let diff = raw.tx_cnt.abs_diff(raw.peer_fwd_cnt);
-					if diff < raw.peer_buf_alloc {
+					// The peer has announced free space in its receive buffer.
+					if raw.peer_credit() > 0 {
 						available.insert(
 							PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND,
 						);
@@ -140,13 +179,15 @@ impl Socket {
 	async fn bind(&mut self, endpoint: ListenEndpoint) -> io::Result<()> {
 		match endpoint {
 			ListenEndpoint::Vsock(ep) => {
+				VSOCK_MAP.lock().bind(ep.port)?;
 				self.port = ep.port;
 				if let Some(cid) = ep.cid {
 					self.cid = cid;
 				} else {
 					self.cid = u32::MAX;
 				}
-				VSOCK_MAP.lock().bind(ep.port)
+				self.is_listen = true;
+				Ok(())
 			}
 			#[cfg(any(feature = "tcp", feature = "udp"))]
 			_ => Err(io::Error::EINVAL),
@@ -157,9 +198,9 @@ impl Socket {
 		match endpoint {
 			Endpoint::Vsock(ep) => {
 				const HEADER_SIZE: usize = core::mem::size_of::<Hdr>();
-				let port = VSOCK_MAP.lock().connect(ep.port, ep.cid)?;
-				self.port = port;
-				self.port = ep.cid;
+				let id = VSOCK_MAP.lock().connect(ep.port, ep.cid)?;
+				self.port = id.port;
+				self.connection = Some(id);
 
 				future::poll_fn(|_cx| {
 					if let Some(mut driver_guard) = hardware::get_vsock_driver().unwrap().try_lock()
@@ -171,7 +212,7 @@ impl Socket {
 
 							response.src_cid = le64::from_ne(local_cid);
 							response.dst_cid = le64::from_ne(ep.cid.into());
-							response.src_port = le32::from_ne(port);
+							response.src_port = le32::from_ne(id.port);
 							response.dst_port = le32::from_ne(ep.port);
 							response.len = le32::from_ne(0);
 							response.type_ = le16::from_ne(Type::Stream.into());
@@ -192,7 +233,7 @@ impl Socket {
 
 				future::poll_fn(|cx| {
 					let mut guard = VSOCK_MAP.lock();
-					let raw = guard.get_mut_socket(port).ok_or(Error::EINVAL)?;
+					let raw = guard.get_mut_socket(id).ok_or(Error::EINVAL)?;
 
 					match raw.state {
 						VsockState::Connected => Poll::Ready(Ok(())),
@@ -200,7 +241,7 @@ impl Socket {
 							raw.rx_waker.register(cx.waker());
 							Poll::Pending
 						}
-						_ => Poll::Ready(Err(io::Error::EBADF)),
+						VsockState::Shutdown => Poll::Ready(Err(io::Error::EBADF)),
 					}
 				})
 				.await
@@ -211,12 +252,11 @@ impl Socket {
 	}
 
 	async fn getpeername(&self) -> io::Result<Option<Endpoint>> {
-		let guard = VSOCK_MAP.lock();
-		let raw = guard.get_socket(self.port).ok_or(Error::EINVAL)?;
+		let id = self.connection()?;
 
 		Ok(Some(Endpoint::Vsock(VsockEndpoint::new(
-			raw.remote_port,
-			raw.remote_cid,
+			id.remote_port,
+			id.remote_cid,
 		))))
 	}
 
@@ -229,71 +269,155 @@ impl Socket {
 		))))
 	}
 
-	async fn listen(&self, _backlog: i32) -> io::Result<()> {
+	/// Sets the number of connection requests, which are queued until they are accepted.
+	async fn listen(&self, backlog: i32) -> io::Result<()> {
+		if !self.is_listen {
+			return Err(Error::EINVAL);
+		}
+
+		let mut guard = VSOCK_MAP.lock();
+		let listener = guard.get_mut_listener(self.port).ok_or(Error::EINVAL)?;
+		listener.max_backlog = usize::try_from(backlog).map_or(DEFAULT_BACKLOG, |n| n.max(1));
 		Ok(())
 	}
 
-	async fn accept(&mut self) -> io::Result<(NullSocket, Endpoint)> {
+	/// Accepts the oldest pending connection request.
+	///
+	/// Requests, which are not addressed to the CID of the listener, are refused.
+	async fn accept(&mut self) -> io::Result<(Socket, Endpoint)> {
+		const HEADER_SIZE: usize = core::mem::size_of::<Hdr>();
+
+		if !self.is_listen {
+			return Err(Error::EINVAL);
+		}
+
 		let port = self.port;
 		let cid = self.cid;
 
-		let endpoint = future::poll_fn(|cx| {
-			let mut guard = VSOCK_MAP.lock();
-			let raw = guard.get_mut_socket(port).ok_or(Error::EINVAL)?;
+		loop {
+			let request = future::poll_fn(|cx| {
+				let mut guard = VSOCK_MAP.lock();
+				let listener = guard.get_mut_listener(port).ok_or(Error::EINVAL)?;
 
-			match raw.state {
-				VsockState::Listen => {
-					if self.is_nonblocking {
-						Poll::Ready(Err(io::Error::EAGAIN))
-					} else {
-						raw.rx_waker.register(cx.waker());
-						Poll::Pending
-					}
+				if let Some(request) = listener.backlog.pop_front() {
+					Poll::Ready(Ok(request))
+				} else if self.is_nonblocking {
+					Poll::Ready(Err(io::Error::EAGAIN))
+				} else {
+					listener.rx_waker.register(cx.waker());
+					Poll::Pending
 				}
-				VsockState::ReceiveRequest => {
-					let result = {
-						const HEADER_SIZE: usize = core::mem::size_of::<Hdr>();
-						let mut driver_guard = hardware::get_vsock_driver().unwrap().lock();
-						let local_cid = driver_guard.get_cid();
+			})
+			.await?;
+
+			let mut driver_guard = hardware::get_vsock_driver().unwrap().lock();
+			let local_cid = driver_guard.get_cid();
+			let is_refused = local_cid != cid.into() && cid != u32::MAX;
+
+			// The connection is registered before the response, so that the
+			// first packets of the peer find it.
+			let id = (!is_refused).then(|| VSOCK_MAP.lock().accept(port, &request));
+
+			driver_guard.send_packet(HEADER_SIZE, |buffer| {
+				let response = unsafe { &mut *buffer.as_mut_ptr().cast::<Hdr>() };
+
+				response.src_cid = le64::from_ne(local_cid);
+				response.dst_cid = le64::from_ne(request.remote_cid.into());
+				response.src_port = le32::from_ne(port);
+				response.dst_port = le32::from_ne(request.remote_port);
+				response.len = le32::from_ne(0);
+				response.type_ = le16::from_ne(Type::Stream.into());
+				if is_refused {
+					response.op = le16::from_ne(Op::Rst.into());
+				} else {
+					response.op = le16::from_ne(Op::Response.into());
+				}
+				response.flags = le32::from_ne(0);
+				response.buf_alloc =
+					le32::from_ne(crate::executor::vsock::RAW_SOCKET_BUFFER_SIZE as u32);
+				response.fwd_cnt = le32::from_ne(0);
+			});
+
+			if let Some(id) = id {
+				let socket = Socket {
+					port,
+					cid,
+					is_listen: false,
+					connection: Some(id),
+					is_nonblocking: self.is_nonblocking,
+					shutdown: Shutdown::default(),
+					options: self.options,
+				};
+				let endpoint = VsockEndpoint::new(request.remote_port, request.remote_cid);
+
+				return Ok((socket, Endpoint::Vsock(endpoint)));
+			}
+		}
+	}
 
-						driver_guard.send_packet(HEADER_SIZE, |buffer| {
-							let response = unsafe { &mut *buffer.as_mut_ptr().cast::<Hdr>() };
+	/// Shuts down the directions given by `how` and informs the peer about it.
+	///
+	/// After `SHUT_RD`, received data is discarded and receiving returns the
+	/// end of the stream. After `SHUT_WR`, sending fails with `EPIPE`.
+	async fn shutdown(&self, how: i32) -> io::Result<()> {
+		const HEADER_SIZE: usize = core::mem::size_of::<Hdr>();
 
-							response.src_cid = le64::from_ne(local_cid);
-							response.dst_cid = le64::from_ne(raw.remote_cid.into());
-							response.src_port = le32::from_ne(port);
-							response.dst_port = le32::from_ne(raw.remote_port);
-							response.len = le32::from_ne(0);
-							response.type_ = le16::from_ne(Type::Stream.into());
-							if local_cid != cid.into() && cid != u32::MAX {
-								response.op = le16::from_ne(Op::Rst.into());
-							} else {
-								response.op = le16::from_ne(Op::Response.into());
-							}
-							response.flags = le32::from_ne(0);
-							response.buf_alloc = le32::from_ne(
-								crate::executor::vsock::RAW_SOCKET_BUFFER_SIZE as u32,
-							);
-							response.fwd_cnt = le32::from_ne(raw.fwd_cnt);
-						});
+		let id = self.connection()?;
+		let mut guard = VSOCK_MAP.lock();
+		let raw = guard.get_mut_socket(id).ok_or(Error::EINVAL)?;
+		if raw.state != VsockState::Connected && raw.state != VsockState::Shutdown {
+			return Err(Error::ENOTCONN);
+		}
 
-						raw.state = VsockState::Connected;
+		self.shutdown.insert(how)?;
+		if self.shutdown.read() {
+			raw.buffer.clear();
+		}
+		// the blocked readers and writers observe the shut down directions
+		raw.rx_waker.wake();
+		raw.tx_waker.wake();
 
-						Ok(VsockEndpoint::new(raw.remote_port, raw.remote_cid))
-					};
+		let mut flags = ShutdownF::empty();
+		if self.shutdown.read() {
+			flags |= ShutdownF::RECEIVE;
+		}
+		if self.shutdown.write() {
+			flags |= ShutdownF::SEND;
+		}
 
-					Poll::Ready(result)
-				}
-				_ => Poll::Ready(Err(Error::EBADF)),
-			}
-		})
-		.await?;
+		let mut driver_guard = hardware::get_vsock_driver().unwrap().lock();
+		let local_cid = driver_guard.get_cid();
+		driver_guard.send_packet(HEADER_SIZE, |buffer| {
+			let response = unsafe { &mut *buffer.as_mut_ptr().cast::<Hdr>() };
+
+			response.src_cid = le64::from_ne(local_cid);
+			response.dst_cid = le64::from_ne(raw.remote_cid.into());
+			response.src_port = le32::from_ne(id.port);
+			response.dst_port = le32::from_ne(raw.remote_port);
+			response.len = le32::from_ne(0);
+			response.type_ = le16::from_ne(Type::Stream.into());
+			response.op = le16::from_ne(Op::Shutdown.into());
+			response.flags = flags.bits();
+			response.buf_alloc =
+				le32::from_ne(crate::executor::vsock::RAW_SOCKET_BUFFER_SIZE as u32);
+			response.fwd_cnt = le32::from_ne(raw.fwd_cnt);
+		});
 
-		Ok((NullSocket::new(), Endpoint::Vsock(endpoint)))
+		Ok(())
 	}
 
-	async fn shutdown(&self, _how: i32) -> io::Result<()> {
-		Ok(())
+	async fn setsockopt(&mut self, opt: SocketOption, optval: i32) -> io::Result<()> {
+		self.options.set(opt, optval)
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<i32> {
+		if opt == SocketOption::Error {
+			// Connecting blocks until the peer has answered and a reset of the
+			// connection is reported by reading and writing. Hence, no error is pending.
+			Ok(0)
+		} else {
+			self.options.get(opt)
+		}
 	}
 
 	async fn ioctl(&mut self, cmd: IoCtl, value: bool) -> io::Result<()> {
@@ -312,15 +436,22 @@ impl Socket {
 		}
 	}
 
-	async fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
-		let port = self.port;
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		let capacity = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+		let id = self.connection()?;
 		future::poll_fn(|cx| {
 			let mut guard = VSOCK_MAP.lock();
-			let raw = guard.get_mut_socket(port).ok_or(Error::EINVAL)?;
+			let raw = guard.get_mut_socket(id).ok_or(Error::EINVAL)?;
+
+			if self.shutdown.read() {
+				// discard the incoming data
+				raw.buffer.clear();
+				return Poll::Ready(Ok(0));
+			}
 
 			match raw.state {
 				VsockState::Connected => {
-					let len = core::cmp::min(buffer.len(), raw.buffer.len());
+					let len = core::cmp::min(capacity, raw.buffer.len());
 
 					if len == 0 {
 						if self.is_nonblocking {
@@ -330,41 +461,76 @@ impl Socket {
 							Poll::Pending
 						}
 					} else {
-						let tmp: Vec<_> = raw.buffer.drain(..len).collect();
-						buffer[..len].copy_from_slice(tmp.as_slice());
+						scatter(&raw.buffer[..len], bufs, 0);
+						raw.buffer.drain(..len);
 
 						Poll::Ready(Ok(len))
 					}
 				}
 				VsockState::Shutdown => {
-					let len = core::cmp::min(buffer.len(), raw.buffer.len());
+					let len = core::cmp::min(capacity, raw.buffer.len());
 
-					if len == 0 {
-						Poll::Ready(Ok(0))
-					} else {
-						let tmp: Vec<_> = raw.buffer.drain(..len).collect();
-						buffer[..len].copy_from_slice(tmp.as_slice());
-
-						Poll::Ready(Ok(len))
+					if len > 0 {
+						scatter(&raw.buffer[..len], bufs, 0);
+						raw.buffer.drain(..len);
 					}
+
+					Poll::Ready(Ok(len))
 				}
-				_ => Poll::Ready(Err(Error::EIO)),
+				VsockState::Connecting => Poll::Ready(Err(Error::EIO)),
 			}
 		})
 		.await
 	}
 
-	async fn write(&self, buffer: &[u8]) -> io::Result<usize> {
-		let port = self.port;
+	/// Sends the data of `bufs` as far as the peer grants credit.
+	///
+	/// If the receive buffer of the peer is full, a blocking socket waits for
+	/// a credit update, while a non-blocking socket returns `EAGAIN`. Once a
+	/// part of the data has been sent, the length of this part is returned
+	/// instead of waiting.
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		let id = self.connection()?;
+		let len = total_len(bufs);
+		let mut pos = 0;
+		while pos < len {
+			let n = self.write_packet(id, bufs, pos, len - pos).await?;
+			if n == 0 {
+				break;
+			}
+			pos += n;
+		}
+
+		Ok(pos)
+	}
+
+	/// Sends a single packet with the data of `bufs` after the first `skip`
+	/// bytes, of which `remaining` bytes are left.
+	///
+	/// Returns zero instead of waiting for credit, if data has already been sent.
+	async fn write_packet(
+		&self,
+		id: ConnectionId,
+		bufs: &[&[u8]],
+		skip: usize,
+		remaining: usize,
+	) -> io::Result<usize> {
+		let partial = skip > 0;
 		future::poll_fn(|cx| {
+			if self.shutdown.write() {
+				return Poll::Ready(Err(Error::EPIPE));
+			}
+
 			let mut guard = VSOCK_MAP.lock();
-			let raw = guard.get_mut_socket(port).ok_or(Error::EINVAL)?;
-			let diff = raw.tx_cnt.abs_diff(raw.peer_fwd_cnt);
+			let raw = guard.get_mut_socket(id).ok_or(Error::EINVAL)?;
+			let credit = raw.peer_credit();
 
 			match raw.state {
 				VsockState::Connected => {
-					if diff >= raw.peer_buf_alloc {
-						if self.is_nonblocking {
+					if credit == 0 {
+						if partial {
+							Poll::Ready(Ok(0))
+						} else if self.is_nonblocking {
 							Poll::Ready(Err(io::Error::EAGAIN))
 						} else {
 							raw.tx_waker.register(cx.waker());
@@ -374,10 +540,9 @@ impl Socket {
 						const HEADER_SIZE: usize = core::mem::size_of::<Hdr>();
 						let mut driver_guard = hardware::get_vsock_driver().unwrap().lock();
 						let local_cid = driver_guard.get_cid();
-						let len = core::cmp::min(
-							buffer.len(),
-							usize::try_from(raw.peer_buf_alloc - diff).unwrap(),
-						);
+						let len = remaining
+							.min(usize::try_from(credit).unwrap())
+							.min(usize::try_from(VSOCK_PACKET_SIZE).unwrap());
 
 						driver_guard.send_packet(HEADER_SIZE + len, |virtio_buffer| {
 							let response =
@@ -386,7 +551,7 @@ impl Socket {
 							raw.tx_cnt = raw.tx_cnt.wrapping_add(len.try_into().unwrap());
 							response.src_cid = le64::from_ne(local_cid);
 							response.dst_cid = le64::from_ne(raw.remote_cid.into());
-							response.src_port = le32::from_ne(port);
+							response.src_port = le32::from_ne(id.port);
 							response.dst_port = le32::from_ne(raw.remote_port);
 							response.len = le32::from_ne(len.try_into().unwrap());
 							response.type_ = le16::from_ne(Type::Stream.into());
@@ -397,14 +562,18 @@ impl Socket {
 							);
 							response.fwd_cnt = le32::from_ne(raw.fwd_cnt);
 
-							virtio_buffer[HEADER_SIZE..HEADER_SIZE + len]
-								.copy_from_slice(&buffer[..len]);
+							gather(
+								bufs,
+								skip,
+								&mut virtio_buffer[HEADER_SIZE..HEADER_SIZE + len],
+							);
 						});
 
 						Poll::Ready(Ok(len))
 					}
 				}
-				_ => Poll::Ready(Err(Error::EIO)),
+				VsockState::Shutdown => Poll::Ready(Err(Error::EPIPE)),
+				VsockState::Connecting => Poll::Ready(Err(Error::EIO)),
 			}
 		})
 		.await
@@ -414,7 +583,11 @@ impl Socket {
 impl Drop for Socket {
 	fn drop(&mut self) {
 		let mut guard = VSOCK_MAP.lock();
-		guard.remove_socket(self.port);
+		if let Some(id) = self.connection {
+			guard.remove_socket(id);
+		} else if self.is_listen {
+			guard.remove_listener(self.port);
+		}
 	}
 }
 
@@ -425,11 +598,19 @@ impl ObjectInterface for async_lock::RwLock<Socket> {
 	}
 
 	async fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
-		self.read().await.read(buffer).await
+		self.read().await.readv(&mut [buffer]).await
 	}
 
 	async fn write(&self, buffer: &[u8]) -> io::Result<usize> {
-		self.read().await.write(buffer).await
+		self.read().await.writev(&[buffer]).await
+	}
+
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		self.read().await.readv(bufs).await
+	}
+
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		self.read().await.writev(bufs).await
 	}
 
 	async fn bind(&self, endpoint: ListenEndpoint) -> io::Result<()> {
@@ -457,6 +638,19 @@ impl ObjectInterface for async_lock::RwLock<Socket> {
 		self.write().await.listen(backlog).await
 	}
 
+	async fn setsockopt(&self, opt: SocketOption, optval: i32) -> io::Result<()> {
+		self.write().await.setsockopt(opt, optval).await
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<i32> {
+		self.read().await.getsockopt(opt).await
+	}
+
+	fn io_timeout(&self, opt: SocketOption) -> Option<core::time::Duration> {
+		// no timeout is applied, while the socket is locked for writing
+		self.try_read()?.options.timeout(opt)
+	}
+
 	async fn shutdown(&self, how: i32) -> io::Result<()> {
 		self.read().await.shutdown(how).await
 	}
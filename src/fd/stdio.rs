@@ -8,7 +8,7 @@ use uhyve_interface::{GuestVirtAddr, Hypercall};
 use zerocopy::IntoBytes;
 
 use crate::console::CONSOLE;
-use crate::fd::{ObjectInterface, PollEvent, STDERR_FILENO, STDOUT_FILENO};
+use crate::fd::{ObjectInterface, PollEvent, STDERR_FILENO, STDOUT_FILENO, total_len};
 use crate::io;
 use crate::syscalls::interfaces::uhyve_hypercall;
 
@@ -75,6 +75,14 @@ impl ObjectInterface for GenericStdout {
 		CONSOLE.lock().write(buf);
 		Ok(buf.len())
 	}
+
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		let mut console = CONSOLE.lock();
+		for buf in bufs {
+			console.write(buf);
+		}
+		Ok(total_len(bufs))
+	}
 }
 
 impl GenericStdout {
@@ -97,6 +105,14 @@ impl ObjectInterface for GenericStderr {
 		CONSOLE.lock().write(buf);
 		Ok(buf.len())
 	}
+
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		let mut console = CONSOLE.lock();
+		for buf in bufs {
+			console.write(buf);
+		}
+		Ok(total_len(bufs))
+	}
 }
 
 impl GenericStderr {
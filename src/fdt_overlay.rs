@@ -0,0 +1,575 @@
+//! Device tree overlays
+//!
+//! The boot loader or the VMM may pass a device tree overlay (`.dtbo`) via the
+//! `hermit,overlay-start` and `hermit,overlay-end` properties of the `/chosen`
+//! device tree node. Before the devices are discovered, the overlay is applied
+//! to the device tree of the boot loader and the kernel continues with the
+//! resulting device tree.
+//!
+//! An overlay consists of fragments, which are applied to the node given by
+//! `target-path` or by the phandle `target`. The properties of the `__overlay__`
+//! node of a fragment replace or extend the properties of the target node and
+//! its subnodes are merged into the subnodes of the target node. The phandles
+//! of the overlay are renumbered according to `__local_fixups__` and references
+//! to labels of the base device tree (`__fixups__`) are resolved by its
+//! `__symbols__` node.
+//!
+//! The memory nodes are evaluated before the overlay is applied, so an overlay
+//! cannot add memory.
+
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::{ptr, slice, str};
+
+use align_address::Align;
+use free_list::PageRange;
+use hermit_sync::OnceCell;
+use memory_addresses::PhysAddr;
+
+use crate::arch::mm::paging::{BasePageSize, PageSize};
+use crate::arch::mm::physicalmem;
+use crate::{env, mm};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// Size of the header of a flattened device tree (version 17)
+const HEADER_SIZE: usize = 40;
+/// Version of the created device tree
+const VERSION: u32 = 17;
+/// Lowest version, which is compatible with the created device tree
+const LAST_COMP_VERSION: u32 = 16;
+
+#[derive(Debug, Copy, Clone)]
+enum Error {
+	/// The blob is not a flattened device tree.
+	InvalidHeader,
+	/// The structure block of the blob is malformed.
+	InvalidStructure,
+	/// The target of a fragment does not exist.
+	TargetNotFound,
+	/// A label of `__fixups__` is not defined in `__symbols__`.
+	SymbolNotFound,
+	/// An entry of `__fixups__` or `__local_fixups__` is malformed.
+	InvalidFixup,
+}
+
+#[derive(Debug)]
+struct Property {
+	name: String,
+	value: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+	name: String,
+	properties: Vec<Property>,
+	children: Vec<Node>,
+}
+
+impl Node {
+	fn property(&self, name: &str) -> Option<&[u8]> {
+		self.properties
+			.iter()
+			.find(|property| property.name == name)
+			.map(|property| property.value.as_slice())
+	}
+
+	fn property_mut(&mut self, name: &str) -> Option<&mut Vec<u8>> {
+		self.properties
+			.iter_mut()
+			.find(|property| property.name == name)
+			.map(|property| &mut property.value)
+	}
+
+	fn set_property(&mut self, name: &str, value: Vec<u8>) {
+		match self.property_mut(name) {
+			Some(old) => *old = value,
+			None => self.properties.push(Property {
+				name: name.to_owned(),
+				value,
+			}),
+		}
+	}
+
+	fn phandle(&self) -> Option<u32> {
+		self.property("phandle")
+			.or_else(|| self.property("linux,phandle"))
+			.and_then(read_u32)
+	}
+
+	/// Returns the subnode `name`. Without unit address, `name` matches any unit address.
+	fn child_mut(&mut self, name: &str) -> Option<&mut Node> {
+		self.children.iter_mut().find(|child| {
+			child.name == name || !name.contains('@') && child.name.split('@').next() == Some(name)
+		})
+	}
+
+	fn take_child(&mut self, name: &str) -> Option<Node> {
+		let index = self.children.iter().position(|child| child.name == name)?;
+		Some(self.children.remove(index))
+	}
+
+	/// Returns the node with the absolute `path`.
+	fn find_mut(&mut self, path: &str) -> Option<&mut Node> {
+		path.split('/')
+			.filter(|name| !name.is_empty())
+			.try_fold(self, |node, name| node.child_mut(name))
+	}
+
+	fn find_by_phandle_mut(&mut self, phandle: u32) -> Option<&mut Node> {
+		if self.phandle() == Some(phandle) {
+			return Some(self);
+		}
+		self.children
+			.iter_mut()
+			.find_map(|child| child.find_by_phandle_mut(phandle))
+	}
+
+	fn max_phandle(&self) -> u32 {
+		self.children
+			.iter()
+			.map(Node::max_phandle)
+			.fold(self.phandle().unwrap_or(0), u32::max)
+	}
+
+	/// Adds `delta` to the phandles of this node and its subnodes.
+	fn renumber_phandles(&mut self, delta: u32) {
+		for property in &mut self.properties {
+			if property.name == "phandle" || property.name == "linux,phandle" {
+				if let Some(phandle) = read_u32(&property.value) {
+					property.value = (phandle + delta).to_be_bytes().to_vec();
+				}
+			}
+		}
+		for child in &mut self.children {
+			child.renumber_phandles(delta);
+		}
+	}
+
+	/// Merges the properties and subnodes of `other` into this node.
+	fn merge(&mut self, other: Node) {
+		for property in other.properties {
+			self.set_property(&property.name, property.value);
+		}
+		for child in other.children {
+			match self
+				.children
+				.iter_mut()
+				.find(|node| node.name == child.name)
+			{
+				Some(node) => node.merge(child),
+				None => self.children.push(child),
+			}
+		}
+	}
+}
+
+fn read_u32(data: &[u8]) -> Option<u32> {
+	Some(u32::from_be_bytes(data.get(..4)?.try_into().unwrap()))
+}
+
+fn read_u32_at(data: &[u8], offset: usize) -> Result<u32, Error> {
+	data.get(offset..)
+		.and_then(read_u32)
+		.ok_or(Error::InvalidStructure)
+}
+
+/// Returns the NUL-terminated string at the beginning of `data`.
+fn read_str(data: &[u8]) -> Result<&str, Error> {
+	let len = data
+		.iter()
+		.position(|&byte| byte == 0)
+		.ok_or(Error::InvalidStructure)?;
+	str::from_utf8(&data[..len]).map_err(|_| Error::InvalidStructure)
+}
+
+/// Adds `delta` to the big-endian cell at `offset` of `value`.
+fn add_to_cell(value: &mut [u8], offset: usize, delta: u32) -> Result<(), Error> {
+	let cell = value
+		.get_mut(offset..offset + 4)
+		.ok_or(Error::InvalidFixup)?;
+	let phandle = u32::from_be_bytes(cell.try_into().unwrap()) + delta;
+	cell.copy_from_slice(&phandle.to_be_bytes());
+	Ok(())
+}
+
+/// Flattened device tree, which has been parsed into a tree of nodes
+struct DeviceTree {
+	/// Memory reservation block including its terminating entry
+	reservations: Vec<u8>,
+	boot_cpuid_phys: u32,
+	root: Node,
+}
+
+impl DeviceTree {
+	fn parse(blob: &[u8]) -> Result<Self, Error> {
+		let header = |index: usize| read_u32_at(blob, 4 * index).map_err(|_| Error::InvalidHeader);
+		if header(0)? != FDT_MAGIC || header(6)? > LAST_COMP_VERSION || header(5)? < VERSION {
+			return Err(Error::InvalidHeader);
+		}
+
+		let field = |index| header(index).map(|value| value as usize);
+		let blob = blob.get(..field(1)?).ok_or(Error::InvalidHeader)?;
+		let structure = blob
+			.get(field(2)?..field(2)? + field(9)?)
+			.ok_or(Error::InvalidHeader)?;
+		let strings = blob
+			.get(field(3)?..field(3)? + field(8)?)
+			.ok_or(Error::InvalidHeader)?;
+
+		let start = field(4)?;
+		let mut end = start;
+		loop {
+			let entry = blob.get(end..end + 16).ok_or(Error::InvalidHeader)?;
+			end += 16;
+			if entry.iter().all(|&byte| byte == 0) {
+				break;
+			}
+		}
+
+		let mut pos = 0;
+		while read_u32_at(structure, pos)? == FDT_NOP {
+			pos += 4;
+		}
+		if read_u32_at(structure, pos)? != FDT_BEGIN_NODE {
+			return Err(Error::InvalidStructure);
+		}
+		pos += 4;
+		let root = Self::parse_node(structure, strings, &mut pos)?;
+
+		Ok(Self {
+			reservations: blob[start..end].to_vec(),
+			boot_cpuid_phys: header(7)?,
+			root,
+		})
+	}
+
+	/// Parses the node at `pos` after its `FDT_BEGIN_NODE` token.
+	fn parse_node(structure: &[u8], strings: &[u8], pos: &mut usize) -> Result<Node, Error> {
+		let name = read_str(structure.get(*pos..).ok_or(Error::InvalidStructure)?)?;
+		*pos = (*pos + name.len() + 1).align_up(4);
+		let mut node = Node {
+			name: name.to_owned(),
+			..Default::default()
+		};
+
+		loop {
+			let token = read_u32_at(structure, *pos)?;
+			*pos += 4;
+			match token {
+				FDT_PROP => {
+					let len = read_u32_at(structure, *pos)? as usize;
+					let name_offset = read_u32_at(structure, *pos + 4)? as usize;
+					*pos += 8;
+					let value = structure
+						.get(*pos..*pos + len)
+						.ok_or(Error::InvalidStructure)?;
+					*pos = (*pos + len).align_up(4);
+					let name =
+						read_str(strings.get(name_offset..).ok_or(Error::InvalidStructure)?)?;
+					node.properties.push(Property {
+						name: name.to_owned(),
+						value: value.to_vec(),
+					});
+				}
+				FDT_BEGIN_NODE => {
+					let child = Self::parse_node(structure, strings, pos)?;
+					node.children.push(child);
+				}
+				FDT_END_NODE => return Ok(node),
+				FDT_NOP => {}
+				_ => return Err(Error::InvalidStructure),
+			}
+		}
+	}
+
+	/// Creates the flattened device tree.
+	fn to_blob(&self) -> Vec<u8> {
+		let mut structure = Vec::new();
+		let mut strings = Vec::new();
+		let mut offsets = BTreeMap::new();
+		Self::write_node(&self.root, &mut structure, &mut strings, &mut offsets);
+		structure.extend_from_slice(&FDT_END.to_be_bytes());
+
+		let off_mem_rsvmap = HEADER_SIZE.align_up(8);
+		let off_dt_struct = off_mem_rsvmap + self.reservations.len();
+		let off_dt_strings = off_dt_struct + structure.len();
+		let totalsize = off_dt_strings + strings.len();
+
+		let header = [
+			FDT_MAGIC as usize,
+			totalsize,
+			off_dt_struct,
+			off_dt_strings,
+			off_mem_rsvmap,
+			VERSION as usize,
+			LAST_COMP_VERSION as usize,
+			self.boot_cpuid_phys as usize,
+			strings.len(),
+			structure.len(),
+		];
+
+		let mut blob = Vec::with_capacity(totalsize);
+		for field in header {
+			blob.extend_from_slice(&u32::try_from(field).unwrap().to_be_bytes());
+		}
+		blob.resize(off_mem_rsvmap, 0);
+		blob.extend_from_slice(&self.reservations);
+		blob.extend_from_slice(&structure);
+		blob.extend_from_slice(&strings);
+		blob
+	}
+
+	fn write_node(
+		node: &Node,
+		structure: &mut Vec<u8>,
+		strings: &mut Vec<u8>,
+		offsets: &mut BTreeMap<String, u32>,
+	) {
+		structure.extend_from_slice(&FDT_BEGIN_NODE.to_be_bytes());
+		structure.extend_from_slice(node.name.as_bytes());
+		structure.push(0);
+		structure.resize(structure.len().align_up(4), 0);
+
+		for property in &node.properties {
+			let name_offset = *offsets.entry(property.name.clone()).or_insert_with(|| {
+				let offset = u32::try_from(strings.len()).unwrap();
+				strings.extend_from_slice(property.name.as_bytes());
+				strings.push(0);
+				offset
+			});
+			let len = u32::try_from(property.value.len()).unwrap();
+			structure.extend_from_slice(&FDT_PROP.to_be_bytes());
+			structure.extend_from_slice(&len.to_be_bytes());
+			structure.extend_from_slice(&name_offset.to_be_bytes());
+			structure.extend_from_slice(&property.value);
+			structure.resize(structure.len().align_up(4), 0);
+		}
+
+		for child in &node.children {
+			Self::write_node(child, structure, strings, offsets);
+		}
+
+		structure.extend_from_slice(&FDT_END_NODE.to_be_bytes());
+	}
+
+	/// Applies the overlay `overlay` to this device tree.
+	fn apply(&mut self, mut overlay: Node) -> Result<(), Error> {
+		let delta = self.root.max_phandle();
+		overlay.renumber_phandles(delta);
+		if let Some(local_fixups) = overlay.take_child("__local_fixups__") {
+			Self::apply_local_fixups(&mut overlay, &local_fixups, delta)?;
+		}
+		if let Some(fixups) = overlay.take_child("__fixups__") {
+			self.apply_fixups(&mut overlay, &fixups)?;
+		}
+		overlay.take_child("__symbols__");
+
+		for mut fragment in overlay.children {
+			let Some(content) = fragment.take_child("__overlay__") else {
+				continue;
+			};
+
+			let target = if let Some(path) = fragment.property("target-path") {
+				let path = read_str(path)?;
+				self.root.find_mut(path)
+			} else {
+				let phandle = fragment
+					.property("target")
+					.and_then(read_u32)
+					.ok_or(Error::TargetNotFound)?;
+				self.root.find_by_phandle_mut(phandle)
+			};
+
+			let target = target.ok_or(Error::TargetNotFound)?;
+			debug!(
+				"Apply device tree overlay {} to {}",
+				fragment.name, target.name
+			);
+			target.merge(content);
+		}
+
+		Ok(())
+	}
+
+	/// Adds `delta` to the references of `node` to phandles of the overlay.
+	///
+	/// Each property of `fixups` lists the offsets of the references within the
+	/// property of `node` with the same name.
+	fn apply_local_fixups(node: &mut Node, fixups: &Node, delta: u32) -> Result<(), Error> {
+		for fixup in &fixups.properties {
+			let value = node.property_mut(&fixup.name).ok_or(Error::InvalidFixup)?;
+			for offset in fixup.value.chunks_exact(4) {
+				let offset = u32::from_be_bytes(offset.try_into().unwrap()) as usize;
+				add_to_cell(value, offset, delta)?;
+			}
+		}
+
+		for fixups in &fixups.children {
+			let child = node
+				.children
+				.iter_mut()
+				.find(|child| child.name == fixups.name)
+				.ok_or(Error::InvalidFixup)?;
+			Self::apply_local_fixups(child, fixups, delta)?;
+		}
+
+		Ok(())
+	}
+
+	/// Resolves the references of the overlay to labels of this device tree.
+	///
+	/// Each property of `fixups` is a label, whose value lists the locations
+	/// of the references as `<path>:<property>:<offset>`.
+	fn apply_fixups(&mut self, overlay: &mut Node, fixups: &Node) -> Result<(), Error> {
+		for fixup in &fixups.properties {
+			let path = self
+				.root
+				.find_mut("/__symbols__")
+				.and_then(|symbols| symbols.property(&fixup.name))
+				.ok_or(Error::SymbolNotFound)?;
+			let path = read_str(path)?.to_owned();
+			let phandle = self
+				.root
+				.find_mut(&path)
+				.and_then(|node| node.phandle())
+				.ok_or(Error::SymbolNotFound)?;
+
+			for location in fixup.value.split(|&byte| byte == 0) {
+				if location.is_empty() {
+					continue;
+				}
+
+				let location = str::from_utf8(location).map_err(|_| Error::InvalidFixup)?;
+				let mut parts = location.rsplitn(3, ':');
+				let (Some(offset), Some(property), Some(path)) =
+					(parts.next(), parts.next(), parts.next())
+				else {
+					return Err(Error::InvalidFixup);
+				};
+				let offset = offset.parse::<usize>().map_err(|_| Error::InvalidFixup)?;
+				let value = overlay
+					.find_mut(path)
+					.and_then(|node| node.property_mut(property))
+					.ok_or(Error::InvalidFixup)?;
+				let cell = value
+					.get_mut(offset..offset + 4)
+					.ok_or(Error::InvalidFixup)?;
+				cell.copy_from_slice(&phandle.to_be_bytes());
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+struct Overlay {
+	/// Page-aligned physical memory, which contains the overlay
+	start: PhysAddr,
+	size: usize,
+	/// Position of the overlay within the memory
+	offset: usize,
+	len: usize,
+	/// Pages, which have been taken from the list of free memory and have to
+	/// be released after use
+	reserved: heapless::Vec<PageRange, 4>,
+}
+
+static OVERLAY: OnceCell<Overlay> = OnceCell::new();
+
+/// Device tree, to which the overlay has been applied
+static DEVICE_TREE: OnceCell<&'static [u64]> = OnceCell::new();
+
+/// Protects the memory of the overlay from being allocated.
+///
+/// This has to be called before the frame allocator hands out memory, e.g.,
+/// for the page tables.
+pub(crate) fn reserve() {
+	let Some(range) = env::fdt_overlay().filter(|range| !range.is_empty()) else {
+		return;
+	};
+
+	let Range { start, end } = range;
+	let page = PhysAddr::new(start).align_down(BasePageSize::SIZE);
+	let size = (PhysAddr::new(end).align_up(BasePageSize::SIZE) - page) as usize;
+	let reserved = physicalmem::reserve_free(page, size);
+
+	info!("Found device tree overlay at {start:#x}..{end:#x}");
+	OVERLAY
+		.set(Overlay {
+			start: page,
+			size,
+			offset: (start - page.as_u64()) as usize,
+			len: (end - start) as usize,
+			reserved,
+		})
+		.unwrap();
+}
+
+/// Applies the overlay to the device tree of the boot loader and releases the memory of the overlay.
+pub(crate) fn apply() {
+	let Some(overlay) = OVERLAY.get() else {
+		return;
+	};
+	let Some(base) = env::boot_info().hardware_info.device_tree else {
+		warn!("Unable to apply the device tree overlay without a device tree");
+		return;
+	};
+
+	let virt_addr = mm::map(overlay.start, overlay.size, false, true, false);
+	let blob =
+		unsafe { slice::from_raw_parts(virt_addr.as_ptr::<u8>().add(overlay.offset), overlay.len) };
+	let result = DeviceTree::parse(blob);
+	mm::unmap(virt_addr, overlay.size);
+	for part in &overlay.reserved {
+		physicalmem::deallocate(
+			PhysAddr::new(part.start().try_into().unwrap()),
+			part.len().get(),
+		);
+	}
+
+	let base = {
+		let ptr = ptr::with_exposed_provenance::<u8>(base.get().try_into().unwrap());
+		let len = u32::from_be(unsafe { ptr.add(4).cast::<u32>().read_unaligned() });
+		unsafe { slice::from_raw_parts(ptr, len as usize) }
+	};
+	let result = result.and_then(|overlay| {
+		let mut device_tree = DeviceTree::parse(base)?;
+		device_tree.apply(overlay.root)?;
+		Ok(device_tree.to_blob())
+	});
+
+	match result {
+		Ok(blob) => {
+			// The device tree has to be aligned to 8 bytes.
+			let mut words = vec![0u64; blob.len().div_ceil(8)];
+			unsafe {
+				ptr::copy_nonoverlapping(
+					blob.as_ptr(),
+					words.as_mut_ptr().cast::<u8>(),
+					blob.len(),
+				);
+			}
+			DEVICE_TREE.set(words.leak()).unwrap();
+			info!("Applied device tree overlay ({} bytes)", overlay.len);
+		}
+		Err(err) => error!("Unable to apply device tree overlay: {err:?}"),
+	}
+}
+
+/// Returns the address of the device tree, to which the overlay has been applied.
+pub(crate) fn device_tree() -> Option<usize> {
+	DEVICE_TREE
+		.get()
+		.map(|words| words.as_ptr().expose_provenance())
+}
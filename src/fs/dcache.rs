@@ -0,0 +1,171 @@
+//! Cache of directory entries and their attributes.
+//!
+//! Resolving a path on a file system, which is provided by the host, requires
+//! a round trip to the host. The cache remembers the results of previous lookups,
+//! identified by the parent node and the name of the entry. Entries expire after
+//! the period, which is provided by the file system, and they are invalidated if the
+//! entry is removed or renamed. The number of entries is bounded; if the cache is
+//! full, the oldest entry is evicted.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use core::mem;
+
+use crate::arch::kernel::processor::get_timer_ticks;
+use crate::fs::FileAttr;
+
+/// Maximum number of cached entries
+const DCACHE_CAPACITY: usize = 1024;
+
+type Key = (u64, String);
+
+#[derive(Debug)]
+struct Dentry {
+	ino: u64,
+	attr: FileAttr,
+	/// Point in time (in microseconds since boot), until which the entry is valid
+	entry_valid_until: u64,
+	/// Point in time (in microseconds since boot), until which the attributes are valid
+	attr_valid_until: u64,
+	/// Insertion sequence number, which identifies the entry in the eviction queue
+	seq: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct DentryCache {
+	entries: BTreeMap<Key, Dentry>,
+	/// Keys in order of insertion
+	order: VecDeque<(u64, Key)>,
+	seq: u64,
+}
+
+impl DentryCache {
+	pub const fn new() -> Self {
+		Self {
+			entries: BTreeMap::new(),
+			order: VecDeque::new(),
+			seq: 0,
+		}
+	}
+
+	/// Returns the node id of the entry `name` in the directory `parent`.
+	pub fn lookup(&mut self, parent: u64, name: &str) -> Option<u64> {
+		let now = get_timer_ticks();
+		let key = (parent, name.to_string());
+		match self.entries.get(&key) {
+			Some(dentry) if dentry.entry_valid_until > now => Some(dentry.ino),
+			Some(_) => {
+				self.entries.remove(&key);
+				None
+			}
+			None => None,
+		}
+	}
+
+	/// Returns the node id and the attributes of the entry `name` in the directory `parent`.
+	pub fn getattr(&mut self, parent: u64, name: &str) -> Option<(u64, FileAttr)> {
+		let ino = self.lookup(parent, name)?;
+		let dentry = self.entries.get(&(parent, name.to_string()))?;
+		(dentry.attr_valid_until > get_timer_ticks()).then_some((ino, dentry.attr))
+	}
+
+	/// Inserts the entry `name` in the directory `parent`.
+	///
+	/// `entry_valid` and `attr_valid` specify the period in microseconds,
+	/// for which the entry and its attributes may be cached.
+	pub fn insert(
+		&mut self,
+		parent: u64,
+		name: &str,
+		ino: u64,
+		attr: FileAttr,
+		entry_valid: u64,
+		attr_valid: u64,
+	) {
+		if entry_valid == 0 {
+			return;
+		}
+
+		let now = get_timer_ticks();
+		let key = (parent, name.to_string());
+		self.seq += 1;
+		self.order.push_back((self.seq, key.clone()));
+		self.entries.insert(key, Dentry {
+			ino,
+			attr,
+			entry_valid_until: now.saturating_add(entry_valid),
+			attr_valid_until: now.saturating_add(attr_valid),
+			seq: self.seq,
+		});
+
+		while self.entries.len() > DCACHE_CAPACITY {
+			let Some((seq, key)) = self.order.pop_front() else {
+				break;
+			};
+			if self
+				.entries
+				.get(&key)
+				.is_some_and(|dentry| dentry.seq == seq)
+			{
+				self.entries.remove(&key);
+			}
+		}
+
+		// drop keys of removed or replaced entries
+		if self.order.len() > 2 * DCACHE_CAPACITY {
+			let entries = &self.entries;
+			self.order
+				.retain(|(seq, key)| entries.get(key).is_some_and(|dentry| dentry.seq == *seq));
+		}
+	}
+
+	/// Removes the entry `name` in the directory `parent`.
+	///
+	/// If names are paths relative to `parent`, the entries below `name` are removed as well.
+	/// This has to be called if the entry is unlinked or renamed.
+	pub fn invalidate(&mut self, parent: u64, name: &str) {
+		let name = name.strip_suffix('/').unwrap_or(name);
+		self.entries.retain(|(p, n), _| {
+			*p != parent
+				|| n.strip_prefix(name)
+					.is_none_or(|rest| !rest.is_empty() && !rest.starts_with('/'))
+		});
+	}
+
+	/// Returns the approximate number of bytes, which are occupied by the cached entries.
+	pub fn size(&self) -> usize {
+		self.entries
+			.keys()
+			.map(|(_, name)| mem::size_of::<(Key, Dentry)>() + name.capacity())
+			.sum()
+	}
+
+	/// Evicts the oldest entries until at least `target` bytes are released.
+	///
+	/// Returns the number of released bytes.
+	pub fn shrink(&mut self, target: usize) -> usize {
+		let mut released = 0;
+		while released < target {
+			let Some((seq, key)) = self.order.pop_front() else {
+				break;
+			};
+			if self
+				.entries
+				.get(&key)
+				.is_some_and(|dentry| dentry.seq == seq)
+			{
+				released += mem::size_of::<(Key, Dentry)>() + key.1.capacity();
+				self.entries.remove(&key);
+			}
+		}
+		released
+	}
+
+	/// Marks the cached attributes of the node `ino` as outdated,
+	/// which is the case after the node has been modified.
+	pub fn invalidate_attr(&mut self, ino: u64) {
+		for dentry in self.entries.values_mut().filter(|dentry| dentry.ino == ino) {
+			dentry.attr_valid_until = 0;
+		}
+	}
+}
@@ -0,0 +1,345 @@
+//! Device nodes
+//!
+//! Like devtmpfs, `/dev` is a file system of its own, which provides the
+//! standard character devices:
+//!
+//! - `null` discards all writes and returns end-of-file on reads.
+//! - `zero` discards all writes and returns zeros on reads.
+//! - `random` and `urandom` return data of the entropy pool.
+//! - `console` reads from and writes to the console of the kernel.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use async_trait::async_trait;
+
+use crate::fd::stdio::{GenericStdin, GenericStdout, UhyveStdin, UhyveStdout};
+use crate::fd::{AccessPermission, ObjectInterface, OpenOption, PollEvent};
+use crate::fs::mem::MemDirectory;
+use crate::fs::{DirectoryEntry, FileAttr, NodeKind, SeekWhence, VfsNode};
+use crate::{entropy, env, io};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Device {
+	Null,
+	Zero,
+	Random,
+	Console,
+}
+
+impl Device {
+	fn open(self) -> Arc<dyn ObjectInterface> {
+		match self {
+			Device::Null => Arc::new(NullDevice),
+			Device::Zero => Arc::new(ZeroDevice),
+			Device::Random => Arc::new(RandomDevice),
+			Device::Console if env::is_uhyve() => Arc::new(ConsoleDevice {
+				input: Arc::new(UhyveStdin::new()),
+				output: Arc::new(UhyveStdout::new()),
+			}),
+			Device::Console => Arc::new(ConsoleDevice {
+				input: Arc::new(GenericStdin::new()),
+				output: Arc::new(GenericStdout::new()),
+			}),
+		}
+	}
+}
+
+/// Character device in the VFS
+#[derive(Debug)]
+struct CharDevice {
+	device: Device,
+	attr: FileAttr,
+}
+
+impl CharDevice {
+	fn new(device: Device, mode: u32, (major, minor): (u64, u64)) -> Self {
+		Self {
+			device,
+			attr: FileAttr {
+				st_mode: AccessPermission::S_IFCHR | AccessPermission::from_bits(mode).unwrap(),
+				st_rdev: (major << 8) | minor,
+				st_nlink: 1,
+				..Default::default()
+			},
+		}
+	}
+}
+
+impl VfsNode for CharDevice {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::File
+	}
+
+	fn get_object(&self) -> io::Result<Arc<dyn ObjectInterface>> {
+		Ok(Arc::new(DeviceInterface {
+			inner: self.device.open(),
+			attr: self.attr,
+		}))
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		Ok(self.attr)
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(io::Error::EBADF)
+		}
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(io::Error::EBADF)
+		}
+	}
+}
+
+/// Opened device, which adds the attributes of the device node
+#[derive(Debug)]
+struct DeviceInterface {
+	inner: Arc<dyn ObjectInterface>,
+	attr: FileAttr,
+}
+
+#[async_trait]
+impl ObjectInterface for DeviceInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		self.inner.poll(event).await
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		self.inner.read(buf).await
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		self.inner.write(buf).await
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		self.inner.lseek(offset, whence).await
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		Ok(self.attr)
+	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+const READABLE: PollEvent = PollEvent::POLLIN.union(PollEvent::POLLRDNORM);
+const WRITABLE: PollEvent = PollEvent::POLLOUT.union(PollEvent::POLLWRNORM);
+
+#[derive(Debug)]
+struct NullDevice;
+
+#[async_trait]
+impl ObjectInterface for NullDevice {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event & (READABLE | WRITABLE))
+	}
+
+	/// Like on Linux, the device has no position, so that seeking always succeeds.
+	async fn lseek(&self, _offset: isize, _whence: SeekWhence) -> io::Result<isize> {
+		Ok(0)
+	}
+
+	async fn read(&self, _buf: &mut [u8]) -> io::Result<usize> {
+		Ok(0)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		Ok(buf.len())
+	}
+}
+
+#[derive(Debug)]
+struct ZeroDevice;
+
+#[async_trait]
+impl ObjectInterface for ZeroDevice {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event & (READABLE | WRITABLE))
+	}
+
+	async fn lseek(&self, _offset: isize, _whence: SeekWhence) -> io::Result<isize> {
+		Ok(0)
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		buf.fill(0);
+		Ok(buf.len())
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		Ok(buf.len())
+	}
+}
+
+#[derive(Debug)]
+struct RandomDevice;
+
+#[async_trait]
+impl ObjectInterface for RandomDevice {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event & (READABLE | WRITABLE))
+	}
+
+	async fn lseek(&self, _offset: isize, _whence: SeekWhence) -> io::Result<isize> {
+		Ok(0)
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let ret = entropy::read(buf, entropy::Flags::empty());
+		usize::try_from(ret).map_err(|_| io::Error::ENOSYS)
+	}
+
+	/// Like on Linux, written data is accepted, but not mixed into the pool.
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		Ok(buf.len())
+	}
+}
+
+#[derive(Debug)]
+struct ConsoleDevice {
+	input: Arc<dyn ObjectInterface>,
+	output: Arc<dyn ObjectInterface>,
+}
+
+#[async_trait]
+impl ObjectInterface for ConsoleDevice {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		let input = self
+			.input
+			.poll(event & READABLE)
+			.await
+			.unwrap_or(PollEvent::empty());
+		let output = self.output.poll(event & WRITABLE).await?;
+		Ok(input | output)
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		self.input.read(buf).await
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		self.output.write(buf).await
+	}
+}
+
+/// Root directory of the device file system
+///
+/// Unlike in the RAM file system, opening an existing device with `O_CREAT`
+/// opens the device, e.g., for the redirection of output to `/dev/null`.
+#[derive(Debug)]
+pub(crate) struct DevDirectory(MemDirectory);
+
+impl VfsNode for DevDirectory {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::Directory
+	}
+
+	fn get_object(&self) -> io::Result<Arc<dyn ObjectInterface>> {
+		self.0.get_object()
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		self.0.get_file_attributes()
+	}
+
+	fn traverse_mkdir(&self, components: &mut Vec<&str>, mode: AccessPermission) -> io::Result<()> {
+		self.0.traverse_mkdir(components, mode)
+	}
+
+	fn traverse_rmdir(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		self.0.traverse_rmdir(components)
+	}
+
+	fn traverse_unlink(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		self.0.traverse_unlink(components)
+	}
+
+	fn traverse_rename(
+		&self,
+		components: &mut Vec<&str>,
+		new_components: &mut Vec<&str>,
+	) -> io::Result<()> {
+		self.0.traverse_rename(components, new_components)
+	}
+
+	fn traverse_readdir(&self, components: &mut Vec<&str>) -> io::Result<Vec<DirectoryEntry>> {
+		self.0.traverse_readdir(components)
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.0.traverse_lstat(components)
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.0.traverse_stat(components)
+	}
+
+	fn traverse_mount(
+		&self,
+		components: &mut Vec<&str>,
+		obj: Box<dyn VfsNode + core::marker::Send + core::marker::Sync>,
+	) -> io::Result<()> {
+		self.0.traverse_mount(components, obj)
+	}
+
+	fn traverse_umount(
+		&self,
+		components: &mut Vec<&str>,
+	) -> io::Result<Box<dyn VfsNode + core::marker::Send + core::marker::Sync>> {
+		self.0.traverse_umount(components)
+	}
+
+	fn traverse_open(
+		&self,
+		components: &mut Vec<&str>,
+		mut option: OpenOption,
+		mode: AccessPermission,
+	) -> io::Result<Arc<dyn ObjectInterface>> {
+		if option.contains(OpenOption::O_CREAT)
+			&& components.len() == 1
+			&& self.0.traverse_lstat(&mut components.clone()).is_ok()
+		{
+			if option.contains(OpenOption::O_EXCL) {
+				return Err(io::Error::EEXIST);
+			}
+			option.remove(OpenOption::O_CREAT);
+		}
+
+		self.0.traverse_open(components, option, mode)
+	}
+}
+
+/// Creates the root directory of the device file system.
+pub(crate) fn devtmpfs() -> DevDirectory {
+	let root = MemDirectory::new(AccessPermission::from_bits(0o755).unwrap());
+	// name, device, permissions and the device number on Linux
+	let devices = [
+		("null", Device::Null, 0o666, (1, 3)),
+		("zero", Device::Zero, 0o666, (1, 5)),
+		("random", Device::Random, 0o666, (1, 8)),
+		("urandom", Device::Random, 0o666, (1, 9)),
+		("console", Device::Console, 0o620, (5, 1)),
+	];
+	for (name, device, mode, number) in devices {
+		root.traverse_mount(
+			&mut vec![name],
+			Box::new(CharDevice::new(device, mode, number)),
+		)
+		.unwrap();
+	}
+
+	DevDirectory(root)
+}
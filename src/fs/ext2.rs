@@ -0,0 +1,1751 @@
+//! Second extended file system (ext2)
+//!
+//! A block device, which contains an ext2 file system, is mounted by
+//! `mount("vda", "/mnt", "ext2", flags)`. The file system of ext3 is accepted as
+//! well, as long as its journal does not have to be recovered. The journal is
+//! ignored. Files are mapped by direct and indirect blocks, extents (ext4) are
+//! not supported. If the file system uses unknown read-only compatible features,
+//! it is mounted read-only.
+//!
+//! On a device with direct access, e.g., the persistent memory `pmem0`, the
+//! blocks of a file are mapped into memory by `sys_dax_map` instead of being
+//! copied. Holes are filled, when they are mapped on a writable file system.
+//!
+//! All modifications are written through to the device. Only the primary
+//! superblock and group descriptors are updated and access times are not
+//! maintained. If the last link of a file is removed, while the file is still
+//! open, its blocks are released, when the file is closed for the last time.
+//! Until then, the inode is kept in the orphan list of the superblock, which is
+//! processed at the next mount, if the file system has not been unmounted cleanly.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::collections::btree_map::Entry;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use async_lock::Mutex;
+use async_trait::async_trait;
+use memory_addresses::VirtAddr;
+
+use crate::drivers::block::{self, BlockDevice};
+use crate::executor::block_on;
+use crate::fd::{AccessPermission, ObjectInterface, OpenOption, PollEvent};
+use crate::fs::{DirectoryEntry, FileAttr, NodeKind, SeekWhence, VfsNode};
+use crate::io;
+use crate::time::{realtime_micros, timespec};
+
+/// Position of the superblock on the device
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const MAGIC: u16 = 0xef53;
+const ROOT_INO: u32 = 2;
+/// Largest supported block size (64 KiB)
+const MAX_LOG_BLOCK_SIZE: u32 = 6;
+/// Number of direct blocks of an inode
+const DIRECT_BLOCKS: usize = 12;
+const GROUP_DESC_SIZE: usize = 32;
+/// Inode size and first non-reserved inode of revision 0
+const GOOD_OLD_INODE_SIZE: usize = 128;
+const GOOD_OLD_FIRST_INO: u32 = 11;
+const MAX_NAME_LEN: usize = 255;
+/// Size of the header of a directory entry
+const DIR_ENTRY_HEADER: usize = 8;
+/// Offset of the head of the orphan list in the superblock
+const LAST_ORPHAN: usize = 232;
+
+const FEATURE_INCOMPAT_FILETYPE: u32 = 0x0002;
+const FEATURE_RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+const FEATURE_RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+/// The directory is indexed by a hash tree
+const INDEX_FL: u32 = 0x1000;
+
+const S_IFMT: u16 = 0o170_000;
+const S_IFDIR: u16 = 0o040_000;
+const S_IFREG: u16 = 0o100_000;
+
+/// File types of directory entries
+const FT_REG_FILE: u8 = 1;
+const FT_DIR: u8 = 2;
+
+fn get16(buf: &[u8], offset: usize) -> u16 {
+	u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn get32(buf: &[u8], offset: usize) -> u32 {
+	u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn put16(buf: &mut [u8], offset: usize, value: u16) {
+	buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put32(buf: &mut [u8], offset: usize, value: u32) {
+	buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Returns the current time in seconds since the epoch.
+fn now() -> u32 {
+	(realtime_micros() / 1_000_000) as u32
+}
+
+/// Returns the size of a directory entry with a name of `name_len` bytes.
+fn entry_len(name_len: usize) -> usize {
+	(DIR_ENTRY_HEADER + name_len).next_multiple_of(4)
+}
+
+/// Reads `buf.len()` bytes at the byte `offset` of `device`.
+fn read_bytes(device: &dyn BlockDevice, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+	let block_size = device.block_size() as u64;
+	let start = offset / block_size * block_size;
+	let end = (offset + buf.len() as u64).next_multiple_of(block_size);
+	if start == offset && end == offset + buf.len() as u64 {
+		return device.read_blocks(start / block_size, buf);
+	}
+
+	let mut tmp = vec![0u8; (end - start) as usize];
+	device.read_blocks(start / block_size, &mut tmp)?;
+	let skip = (offset - start) as usize;
+	buf.copy_from_slice(&tmp[skip..skip + buf.len()]);
+	Ok(())
+}
+
+/// Writes `data` to the byte `offset` of `device`.
+fn write_bytes(device: &dyn BlockDevice, offset: u64, data: &[u8]) -> io::Result<()> {
+	let block_size = device.block_size() as u64;
+	let start = offset / block_size * block_size;
+	let end = (offset + data.len() as u64).next_multiple_of(block_size);
+	if start == offset && end == offset + data.len() as u64 {
+		return device.write_blocks(start / block_size, data);
+	}
+
+	let mut tmp = vec![0u8; (end - start) as usize];
+	device.read_blocks(start / block_size, &mut tmp)?;
+	let skip = (offset - start) as usize;
+	tmp[skip..skip + data.len()].copy_from_slice(data);
+	device.write_blocks(start / block_size, &tmp)
+}
+
+/// On-disk inode, whose fields are accessed in place
+#[derive(Debug, Clone)]
+struct Inode(Vec<u8>);
+
+impl Inode {
+	fn mode(&self) -> u16 {
+		get16(&self.0, 0)
+	}
+
+	fn is_dir(&self) -> bool {
+		self.mode() & S_IFMT == S_IFDIR
+	}
+
+	fn is_regular(&self) -> bool {
+		self.mode() & S_IFMT == S_IFREG
+	}
+
+	fn size(&self) -> u64 {
+		let low = u64::from(get32(&self.0, 4));
+		if self.is_regular() {
+			low | (u64::from(get32(&self.0, 108)) << 32)
+		} else {
+			low
+		}
+	}
+
+	fn set_size(&mut self, size: u64) {
+		put32(&mut self.0, 4, size as u32);
+		if self.is_regular() {
+			put32(&mut self.0, 108, (size >> 32) as u32);
+		}
+	}
+
+	fn set_times(&mut self, modified: bool) {
+		let now = now();
+		put32(&mut self.0, 12, now);
+		if modified {
+			put32(&mut self.0, 16, now);
+		}
+	}
+
+	fn links(&self) -> u16 {
+		get16(&self.0, 26)
+	}
+
+	fn set_links(&mut self, links: u16) {
+		put16(&mut self.0, 26, links);
+	}
+
+	/// Returns the number of 512-byte sectors, which are allocated for the inode.
+	fn sectors(&self) -> u32 {
+		get32(&self.0, 28)
+	}
+
+	fn set_sectors(&mut self, sectors: u32) {
+		put32(&mut self.0, 28, sectors);
+	}
+
+	fn flags(&self) -> u32 {
+		get32(&self.0, 32)
+	}
+
+	fn set_flags(&mut self, flags: u32) {
+		put32(&mut self.0, 32, flags);
+	}
+
+	fn block(&self, slot: usize) -> u32 {
+		get32(&self.0, 40 + 4 * slot)
+	}
+
+	fn set_block(&mut self, slot: usize, block: u32) {
+		put32(&mut self.0, 40 + 4 * slot, block);
+	}
+
+	fn attributes(&self, ino: u32, block_size: usize) -> FileAttr {
+		let time = |offset| timespec {
+			tv_sec: i64::from(get32(&self.0, offset)),
+			tv_nsec: 0,
+		};
+
+		FileAttr {
+			st_ino: u64::from(ino),
+			st_nlink: u64::from(self.links()),
+			st_mode: AccessPermission::from_bits_retain(u32::from(self.mode())),
+			st_uid: u32::from(get16(&self.0, 2)) | (u32::from(get16(&self.0, 120)) << 16),
+			st_gid: u32::from(get16(&self.0, 24)) | (u32::from(get16(&self.0, 122)) << 16),
+			st_size: self.size(),
+			st_blksize: block_size as i64,
+			st_blocks: i64::from(self.sectors()),
+			st_atim: time(8),
+			st_ctim: time(12),
+			st_mtim: time(16),
+			..Default::default()
+		}
+	}
+}
+
+/// Mounted ext2 file system
+struct Ext2 {
+	device: &'static dyn BlockDevice,
+	read_only: bool,
+	superblock: Vec<u8>,
+	/// Group descriptor table
+	groups: Vec<u8>,
+	block_size: usize,
+	inode_size: usize,
+	first_ino: u32,
+	inodes_count: u32,
+	inodes_per_group: u32,
+	blocks_count: u32,
+	blocks_per_group: u32,
+	first_data_block: u32,
+	/// Directory entries contain the file type
+	filetype: bool,
+	/// Number of open handles of each inode
+	handles: BTreeMap<u32, usize>,
+}
+
+impl fmt::Debug for Ext2 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Ext2")
+			.field("read_only", &self.read_only)
+			.field("block_size", &self.block_size)
+			.field("blocks_count", &self.blocks_count)
+			.field("inodes_count", &self.inodes_count)
+			.finish_non_exhaustive()
+	}
+}
+
+impl Ext2 {
+	fn new(device: &'static dyn BlockDevice, read_only: bool) -> io::Result<Self> {
+		let mut superblock = vec![0u8; SUPERBLOCK_SIZE];
+		read_bytes(device, SUPERBLOCK_OFFSET, &mut superblock)?;
+		if get16(&superblock, 56) != MAGIC {
+			return Err(io::Error::EINVAL);
+		}
+
+		let log_block_size = get32(&superblock, 24);
+		if log_block_size > MAX_LOG_BLOCK_SIZE {
+			error!("ext2: unsupported block size");
+			return Err(io::Error::EINVAL);
+		}
+
+		let revision = get32(&superblock, 76);
+		let (inode_size, first_ino, incompat, ro_compat) = if revision == 0 {
+			(GOOD_OLD_INODE_SIZE, GOOD_OLD_FIRST_INO, 0, 0)
+		} else {
+			(
+				usize::from(get16(&superblock, 88)),
+				get32(&superblock, 84),
+				get32(&superblock, 96),
+				get32(&superblock, 100),
+			)
+		};
+
+		let unsupported = incompat & !FEATURE_INCOMPAT_FILETYPE;
+		if unsupported != 0 {
+			error!("ext2: unsupported incompatible features {unsupported:#x}");
+			return Err(io::Error::EINVAL);
+		}
+
+		let mut read_only = read_only;
+		let unsupported =
+			ro_compat & !(FEATURE_RO_COMPAT_SPARSE_SUPER | FEATURE_RO_COMPAT_LARGE_FILE);
+		if unsupported != 0 && !read_only {
+			warn!("ext2: unsupported read-only features {unsupported:#x}, mounting read-only");
+			read_only = true;
+		}
+
+		let block_size = 1024usize << log_block_size;
+		let blocks_per_group = get32(&superblock, 32);
+		let inodes_per_group = get32(&superblock, 40);
+		let first_data_block = get32(&superblock, 20);
+		let blocks_count = get32(&superblock, 4);
+		let inodes_count = get32(&superblock, 0);
+		// the bitmaps of a group occupy a single block
+		let bits_per_block = 8 * block_size as u32;
+		if inode_size < GOOD_OLD_INODE_SIZE
+			|| inode_size > block_size
+			|| !inode_size.is_power_of_two()
+			|| blocks_per_group == 0
+			|| blocks_per_group > bits_per_block
+			|| inodes_per_group == 0
+			|| inodes_per_group > bits_per_block
+			|| blocks_count <= first_data_block
+			|| inodes_count < ROOT_INO
+			|| first_ino > inodes_count
+			|| u64::from(blocks_count) * block_size as u64
+				> device.num_blocks() * device.block_size() as u64
+		{
+			error!("ext2: invalid superblock");
+			return Err(io::Error::EINVAL);
+		}
+
+		let group_count = (blocks_count - first_data_block).div_ceil(blocks_per_group);
+		if u64::from(inodes_count) > u64::from(inodes_per_group) * u64::from(group_count) {
+			error!("ext2: the groups do not cover {inodes_count} inodes");
+			return Err(io::Error::EINVAL);
+		}
+
+		let mut groups = vec![0u8; group_count as usize * GROUP_DESC_SIZE];
+		read_bytes(
+			device,
+			u64::from(first_data_block + 1) * block_size as u64,
+			&mut groups,
+		)?;
+
+		let table_blocks = (inodes_per_group as usize * inode_size).div_ceil(block_size) as u64;
+		let valid = |block: u32, len: u64| {
+			block >= first_data_block && u64::from(block) + len <= u64::from(blocks_count)
+		};
+		for desc in groups.chunks_exact(GROUP_DESC_SIZE) {
+			if !valid(get32(desc, 0), 1)
+				|| !valid(get32(desc, 4), 1)
+				|| !valid(get32(desc, 8), table_blocks)
+			{
+				error!("ext2: invalid group descriptor");
+				return Err(io::Error::EINVAL);
+			}
+		}
+
+		let mut fs = Self {
+			device,
+			read_only,
+			inodes_count,
+			superblock,
+			groups,
+			block_size,
+			inode_size,
+			first_ino,
+			inodes_per_group,
+			blocks_count,
+			blocks_per_group,
+			first_data_block,
+			filetype: incompat & FEATURE_INCOMPAT_FILETYPE != 0,
+			handles: BTreeMap::new(),
+		};
+		if !fs.read_only {
+			fs.release_orphans()?;
+		}
+
+		Ok(fs)
+	}
+
+	fn read_block(&self, block: u32, buf: &mut [u8]) -> io::Result<()> {
+		read_bytes(self.device, u64::from(block) * self.block_size as u64, buf)
+	}
+
+	fn write(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+		if self.read_only {
+			return Err(io::Error::EROFS);
+		}
+
+		write_bytes(self.device, offset, data)
+	}
+
+	fn write_block(&self, block: u32, data: &[u8]) -> io::Result<()> {
+		self.write(u64::from(block) * self.block_size as u64, data)
+	}
+
+	fn write_superblock(&self) -> io::Result<()> {
+		self.write(SUPERBLOCK_OFFSET, &self.superblock)
+	}
+
+	fn group_count(&self) -> usize {
+		self.groups.len() / GROUP_DESC_SIZE
+	}
+
+	fn group(&self, group: usize) -> &[u8] {
+		&self.groups[group * GROUP_DESC_SIZE..(group + 1) * GROUP_DESC_SIZE]
+	}
+
+	/// Adds `delta` to the 16-bit counter at `offset` of the descriptor of `group`
+	/// and writes the descriptor to the device.
+	fn adjust_group(&mut self, group: usize, offset: usize, delta: i16) -> io::Result<()> {
+		let offset = group * GROUP_DESC_SIZE + offset;
+		let value = get16(&self.groups, offset).wrapping_add_signed(delta);
+		put16(&mut self.groups, offset, value);
+
+		let table = u64::from(self.first_data_block + 1) * self.block_size as u64;
+		self.write(table + (group * GROUP_DESC_SIZE) as u64, self.group(group))
+	}
+
+	/// Adds `delta` to the 32-bit counter at `offset` of the superblock and writes
+	/// the superblock to the device.
+	fn adjust_superblock(&mut self, offset: usize, delta: i32) -> io::Result<()> {
+		let value = get32(&self.superblock, offset).wrapping_add_signed(delta);
+		put32(&mut self.superblock, offset, value);
+		self.write_superblock()
+	}
+
+	/// Returns the block group of the inode `ino`.
+	fn group_of(&self, ino: u32) -> usize {
+		((ino - 1) / self.inodes_per_group) as usize
+	}
+
+	fn inode_offset(&self, ino: u32) -> io::Result<u64> {
+		if ino == 0 || ino > self.inodes_count {
+			error!("ext2: invalid inode {ino}");
+			return Err(io::Error::EIO);
+		}
+
+		let table = get32(self.group(self.group_of(ino)), 8);
+		let index = (ino - 1) % self.inodes_per_group;
+		Ok(u64::from(table) * self.block_size as u64 + u64::from(index) * self.inode_size as u64)
+	}
+
+	fn read_inode(&self, ino: u32) -> io::Result<Inode> {
+		let mut raw = vec![0u8; self.inode_size];
+		read_bytes(self.device, self.inode_offset(ino)?, &mut raw)?;
+		Ok(Inode(raw))
+	}
+
+	fn write_inode(&self, ino: u32, inode: &Inode) -> io::Result<()> {
+		self.write(self.inode_offset(ino)?, &inode.0)
+	}
+
+	/// Marks the first free entry of the bitmap at `bitmap`, which covers
+	/// `count` entries, as used and returns its index.
+	fn alloc_bit(&self, bitmap: u32, count: u32) -> io::Result<Option<u32>> {
+		let mut buf = vec![0u8; self.block_size];
+		self.read_block(bitmap, &mut buf)?;
+
+		let Some(byte) = buf.iter().position(|byte| *byte != 0xff) else {
+			return Ok(None);
+		};
+		let bit = buf[byte].trailing_ones();
+		let index = byte as u32 * 8 + bit;
+		if index >= count {
+			return Ok(None);
+		}
+
+		buf[byte] |= 1 << bit;
+		self.write_block(bitmap, &buf)?;
+		Ok(Some(index))
+	}
+
+	/// Marks the entry `index` of the bitmap at `bitmap` as free.
+	fn free_bit(&self, bitmap: u32, index: u32) -> io::Result<()> {
+		let mut buf = vec![0u8; self.block_size];
+		self.read_block(bitmap, &mut buf)?;
+
+		let byte = (index / 8) as usize;
+		let mask = 1u8 << (index % 8);
+		if buf[byte] & mask == 0 {
+			warn!("ext2: freeing unused entry {index} of bitmap {bitmap}");
+		}
+		buf[byte] &= !mask;
+		self.write_block(bitmap, &buf)
+	}
+
+	/// Allocates a zeroed block, preferably in the block group `goal`.
+	fn alloc_block(&mut self, goal: usize) -> io::Result<u32> {
+		let group_count = self.group_count();
+		for group in (0..group_count).map(|i| (goal + i) % group_count) {
+			if get16(self.group(group), 12) == 0 {
+				continue;
+			}
+
+			let first = self.first_data_block + group as u32 * self.blocks_per_group;
+			let count = self.blocks_per_group.min(self.blocks_count - first);
+			let Some(index) = self.alloc_bit(get32(self.group(group), 0), count)? else {
+				continue;
+			};
+
+			self.adjust_group(group, 12, -1)?;
+			self.adjust_superblock(12, -1)?;
+
+			let block = first + index;
+			self.write_block(block, &vec![0u8; self.block_size])?;
+			return Ok(block);
+		}
+
+		Err(io::Error::ENOSPC)
+	}
+
+	fn free_block(&mut self, block: u32) -> io::Result<()> {
+		if block < self.first_data_block || block >= self.blocks_count {
+			error!("ext2: invalid block {block}");
+			return Err(io::Error::EIO);
+		}
+
+		let group = ((block - self.first_data_block) / self.blocks_per_group) as usize;
+		let index = (block - self.first_data_block) % self.blocks_per_group;
+		self.free_bit(get32(self.group(group), 0), index)?;
+		self.adjust_group(group, 12, 1)?;
+		self.adjust_superblock(12, 1)
+	}
+
+	/// Allocates an inode, preferably in the block group `goal`.
+	fn alloc_inode(&mut self, goal: usize, dir: bool) -> io::Result<u32> {
+		let group_count = self.group_count();
+		for group in (0..group_count).map(|i| (goal + i) % group_count) {
+			if get16(self.group(group), 14) == 0 {
+				continue;
+			}
+
+			let Some(index) = self.alloc_bit(get32(self.group(group), 4), self.inodes_per_group)?
+			else {
+				continue;
+			};
+
+			let ino = group as u32 * self.inodes_per_group + index + 1;
+			if ino < self.first_ino || ino > self.inodes_count {
+				error!("ext2: bitmap of group {group} marks reserved inode {ino} as free");
+				return Err(io::Error::EIO);
+			}
+
+			self.adjust_group(group, 14, -1)?;
+			if dir {
+				self.adjust_group(group, 16, 1)?;
+			}
+			self.adjust_superblock(16, -1)?;
+			return Ok(ino);
+		}
+
+		Err(io::Error::ENOSPC)
+	}
+
+	fn free_inode(&mut self, ino: u32, dir: bool) -> io::Result<()> {
+		let group = self.group_of(ino);
+		self.free_bit(
+			get32(self.group(group), 4),
+			(ino - 1) % self.inodes_per_group,
+		)?;
+		self.adjust_group(group, 14, 1)?;
+		if dir {
+			self.adjust_group(group, 16, -1)?;
+		}
+		self.adjust_superblock(16, 1)
+	}
+
+	fn sectors_per_block(&self) -> u32 {
+		(self.block_size / 512) as u32
+	}
+
+	fn ptrs_per_block(&self) -> u64 {
+		(self.block_size / 4) as u64
+	}
+
+	/// Returns the block, which stores the `index`-th block of the inode `ino`.
+	///
+	/// Holes are filled, if `allocate` is set. Afterwards, the inode has to be written.
+	fn map_block(
+		&mut self,
+		ino: u32,
+		inode: &mut Inode,
+		index: u64,
+		allocate: bool,
+	) -> io::Result<Option<u32>> {
+		let per = self.ptrs_per_block();
+		let (slot, path) = if index < DIRECT_BLOCKS as u64 {
+			(index as usize, Vec::new())
+		} else if index - (DIRECT_BLOCKS as u64) < per {
+			(DIRECT_BLOCKS, vec![index - DIRECT_BLOCKS as u64])
+		} else if index - (DIRECT_BLOCKS as u64) - per < per * per {
+			let rel = index - DIRECT_BLOCKS as u64 - per;
+			(DIRECT_BLOCKS + 1, vec![rel / per, rel % per])
+		} else if index - (DIRECT_BLOCKS as u64) - per - per * per < per * per * per {
+			let rel = index - DIRECT_BLOCKS as u64 - per - per * per;
+			(DIRECT_BLOCKS + 2, vec![
+				rel / (per * per),
+				rel / per % per,
+				rel % per,
+			])
+		} else {
+			return Err(io::Error::EFBIG);
+		};
+
+		let goal = self.group_of(ino);
+		let mut block = inode.block(slot);
+		if block == 0 {
+			if !allocate {
+				return Ok(None);
+			}
+			block = self.alloc_block(goal)?;
+			inode.set_block(slot, block);
+			inode.set_sectors(inode.sectors() + self.sectors_per_block());
+		}
+
+		for entry in path {
+			let offset = u64::from(block) * self.block_size as u64 + entry * 4;
+			let mut buf = [0u8; 4];
+			read_bytes(self.device, offset, &mut buf)?;
+			let mut next = u32::from_le_bytes(buf);
+			if next == 0 {
+				if !allocate {
+					return Ok(None);
+				}
+				next = self.alloc_block(goal)?;
+				inode.set_sectors(inode.sectors() + self.sectors_per_block());
+				self.write(offset, &next.to_le_bytes())?;
+			}
+			block = next;
+		}
+
+		Ok(Some(block))
+	}
+
+	/// Releases the data block `block` of `inode`.
+	fn release_block(&mut self, inode: &mut Inode, block: u32) -> io::Result<()> {
+		self.free_block(block)?;
+		inode.set_sectors(inode.sectors().saturating_sub(self.sectors_per_block()));
+		Ok(())
+	}
+
+	/// Releases all blocks below the indirect block `block` with `depth` levels,
+	/// which map a block index of at least `first` relative to `block`.
+	///
+	/// Returns `true`, if `block` itself has been released.
+	fn release_branch(
+		&mut self,
+		inode: &mut Inode,
+		block: u32,
+		depth: u32,
+		first: u64,
+	) -> io::Result<bool> {
+		let mut buf = vec![0u8; self.block_size];
+		self.read_block(block, &mut buf)?;
+
+		let span = self.ptrs_per_block().pow(depth - 1);
+		let mut modified = false;
+		for (i, entry) in buf.chunks_exact_mut(4).enumerate() {
+			let ptr = get32(entry, 0);
+			let start = i as u64 * span;
+			if ptr == 0 || start + span <= first {
+				continue;
+			}
+
+			let released = if depth == 1 {
+				self.release_block(inode, ptr)?;
+				true
+			} else {
+				self.release_branch(inode, ptr, depth - 1, first.saturating_sub(start))?
+			};
+			if released {
+				put32(entry, 0, 0);
+				modified = true;
+			}
+		}
+
+		if first == 0 {
+			self.release_block(inode, block)?;
+			return Ok(true);
+		}
+
+		if modified {
+			self.write_block(block, &buf)?;
+		}
+		Ok(false)
+	}
+
+	/// Releases the blocks of `inode` starting with the block index `keep`.
+	fn truncate_blocks(&mut self, inode: &mut Inode, keep: u64) -> io::Result<()> {
+		for slot in (keep.min(DIRECT_BLOCKS as u64) as usize)..DIRECT_BLOCKS {
+			let block = inode.block(slot);
+			if block != 0 {
+				self.release_block(inode, block)?;
+				inode.set_block(slot, 0);
+			}
+		}
+
+		let mut start = DIRECT_BLOCKS as u64;
+		for depth in 1..=3 {
+			let slot = DIRECT_BLOCKS + depth as usize - 1;
+			let span = self.ptrs_per_block().pow(depth);
+			let block = inode.block(slot);
+			let first = keep.saturating_sub(start);
+			if block != 0 && first < span && self.release_branch(inode, block, depth, first)? {
+				inode.set_block(slot, 0);
+			}
+			start += span;
+		}
+
+		Ok(())
+	}
+
+	/// Reads the content of the inode `ino` at `pos` into `buf`.
+	fn read_data(
+		&mut self,
+		ino: u32,
+		inode: &mut Inode,
+		pos: u64,
+		buf: &mut [u8],
+	) -> io::Result<usize> {
+		let size = inode.size();
+		if pos >= size {
+			return Ok(0);
+		}
+
+		let block_size = self.block_size as u64;
+		let len = (size - pos).min(buf.len() as u64) as usize;
+		let mut done = 0;
+		while done < len {
+			let offset = pos + done as u64;
+			let within = offset % block_size;
+			let n = ((block_size - within) as usize).min(len - done);
+			match self.map_block(ino, inode, offset / block_size, false)? {
+				Some(block) => read_bytes(
+					self.device,
+					u64::from(block) * block_size + within,
+					&mut buf[done..done + n],
+				)?,
+				None => buf[done..done + n].fill(0),
+			}
+			done += n;
+		}
+
+		Ok(len)
+	}
+
+	/// Writes `data` to the inode `ino` at `pos` and writes the inode.
+	fn write_data(
+		&mut self,
+		ino: u32,
+		inode: &mut Inode,
+		pos: u64,
+		data: &[u8],
+	) -> io::Result<usize> {
+		let block_size = self.block_size as u64;
+		let mut done = 0;
+		let mut result = Ok(());
+		while done < data.len() {
+			let offset = pos + done as u64;
+			let within = offset % block_size;
+			let n = ((block_size - within) as usize).min(data.len() - done);
+			result = self
+				.map_block(ino, inode, offset / block_size, true)
+				.and_then(|block| {
+					self.write(
+						u64::from(block.unwrap()) * block_size + within,
+						&data[done..done + n],
+					)
+				});
+			if result.is_err() {
+				break;
+			}
+			done += n;
+		}
+
+		let end = pos + done as u64;
+		if end > inode.size() {
+			inode.set_size(end);
+			if end > i32::MAX as u64
+				&& get32(&self.superblock, 100) & FEATURE_RO_COMPAT_LARGE_FILE == 0
+			{
+				let features = get32(&self.superblock, 100) | FEATURE_RO_COMPAT_LARGE_FILE;
+				put32(&mut self.superblock, 100, features);
+				self.write_superblock()?;
+			}
+		}
+		inode.set_times(true);
+		self.write_inode(ino, inode)?;
+
+		match result {
+			Err(err) if done == 0 => Err(err),
+			_ => Ok(done),
+		}
+	}
+
+	/// Returns the address, at which the content of the inode `ino` at `pos` is
+	/// directly accessible, and the number of bytes, which are stored
+	/// contiguously behind it.
+	fn dax_address(&mut self, ino: u32, pos: u64) -> io::Result<(VirtAddr, usize)> {
+		if self.device.dax_address(0).is_none() {
+			return Err(io::Error::ENODEV);
+		}
+
+		let mut inode = self.read_inode(ino)?;
+		let size = inode.size();
+		if pos >= size {
+			return Err(io::Error::EINVAL);
+		}
+
+		let block_size = self.block_size as u64;
+		let index = pos / block_size;
+		let sectors = inode.sectors();
+		let first = self.map_block(ino, &mut inode, index, !self.read_only)?;
+		if inode.sectors() != sectors {
+			self.write_inode(ino, &inode)?;
+		}
+		let first = first.ok_or(io::Error::EINVAL)?;
+		let addr = self
+			.device
+			.dax_address(u64::from(first) * block_size + pos % block_size)
+			.ok_or(io::Error::EINVAL)?;
+
+		// extend the range over the following blocks, as long as they are adjacent
+		let blocks = size.div_ceil(block_size);
+		let mut end = index + 1;
+		while end < blocks
+			&& self.map_block(ino, &mut inode, end, false)?.map(u64::from)
+				== Some(u64::from(first) + end - index)
+		{
+			end += 1;
+		}
+
+		let len = (end * block_size).min(size) - pos;
+		Ok((addr, usize::try_from(len).unwrap()))
+	}
+
+	/// Returns the entries of the directory `ino` including `.` and `..`.
+	fn read_dir(&mut self, ino: u32) -> io::Result<Vec<(String, u32)>> {
+		let mut inode = self.read_inode(ino)?;
+		if !inode.is_dir() {
+			return Err(io::Error::ENOTDIR);
+		}
+
+		let mut entries = Vec::new();
+		let mut buf = vec![0u8; self.block_size];
+		for index in 0..inode.size() / self.block_size as u64 {
+			let Some(block) = self.map_block(ino, &mut inode, index, false)? else {
+				continue;
+			};
+			self.read_block(block, &mut buf)?;
+
+			let mut offset = 0;
+			while offset < self.block_size {
+				let (entry_ino, rec_len, name) = self.parse_entry(&buf, offset)?;
+				if entry_ino != 0 {
+					entries.push((String::from_utf8_lossy(name).into_owned(), entry_ino));
+				}
+				offset += rec_len;
+			}
+		}
+
+		Ok(entries)
+	}
+
+	/// Returns the inode, the record length and the name of the directory entry at `offset`.
+	fn parse_entry<'a>(&self, buf: &'a [u8], offset: usize) -> io::Result<(u32, usize, &'a [u8])> {
+		let rec_len = usize::from(get16(buf, offset + 4));
+		let name_len = if self.filetype {
+			usize::from(buf[offset + 6])
+		} else {
+			usize::from(get16(buf, offset + 6))
+		};
+		if rec_len < DIR_ENTRY_HEADER
+			|| offset + rec_len > buf.len()
+			|| DIR_ENTRY_HEADER + name_len > rec_len
+		{
+			error!("ext2: corrupted directory entry");
+			return Err(io::Error::EIO);
+		}
+
+		let name = &buf[offset + DIR_ENTRY_HEADER..offset + DIR_ENTRY_HEADER + name_len];
+		Ok((get32(buf, offset), rec_len, name))
+	}
+
+	fn write_entry(
+		&self,
+		buf: &mut [u8],
+		offset: usize,
+		ino: u32,
+		rec_len: usize,
+		name: &str,
+		file_type: u8,
+	) {
+		put32(buf, offset, ino);
+		put16(buf, offset + 4, rec_len as u16);
+		buf[offset + 6] = name.len() as u8;
+		buf[offset + 7] = if self.filetype { file_type } else { 0 };
+		buf[offset + DIR_ENTRY_HEADER..offset + DIR_ENTRY_HEADER + name.len()]
+			.copy_from_slice(name.as_bytes());
+	}
+
+	fn lookup(&mut self, dir: u32, name: &str) -> io::Result<Option<u32>> {
+		Ok(self
+			.read_dir(dir)?
+			.into_iter()
+			.find_map(|(entry, ino)| (entry == name).then_some(ino)))
+	}
+
+	/// Adds the entry `name` for the inode `ino` to the directory `dir_ino`.
+	///
+	/// Afterwards, the directory inode has to be written.
+	fn add_entry(
+		&mut self,
+		dir_ino: u32,
+		dir: &mut Inode,
+		name: &str,
+		ino: u32,
+		file_type: u8,
+	) -> io::Result<()> {
+		// the hash tree is not maintained
+		dir.set_flags(dir.flags() & !INDEX_FL);
+
+		let needed = entry_len(name.len());
+		let mut buf = vec![0u8; self.block_size];
+		let blocks = dir.size() / self.block_size as u64;
+		for index in 0..blocks {
+			let Some(block) = self.map_block(dir_ino, dir, index, false)? else {
+				continue;
+			};
+			self.read_block(block, &mut buf)?;
+
+			let mut offset = 0;
+			while offset < self.block_size {
+				let (entry_ino, rec_len, entry_name) = self.parse_entry(&buf, offset)?;
+				let used = if entry_ino == 0 {
+					0
+				} else {
+					entry_len(entry_name.len())
+				};
+				if rec_len - used >= needed {
+					if used != 0 {
+						put16(&mut buf, offset + 4, used as u16);
+					}
+					self.write_entry(
+						&mut buf,
+						offset + used,
+						ino,
+						rec_len - used,
+						name,
+						file_type,
+					);
+					return self.write_block(block, &buf);
+				}
+				offset += rec_len;
+			}
+		}
+
+		let block = self.map_block(dir_ino, dir, blocks, true)?.unwrap();
+		buf.fill(0);
+		self.write_entry(&mut buf, 0, ino, self.block_size, name, file_type);
+		self.write_block(block, &buf)?;
+		dir.set_size(dir.size() + self.block_size as u64);
+		Ok(())
+	}
+
+	/// Removes the entry `name` from the directory `dir_ino` and returns its inode.
+	///
+	/// Afterwards, the directory inode has to be written.
+	fn remove_entry(&mut self, dir_ino: u32, dir: &mut Inode, name: &str) -> io::Result<u32> {
+		dir.set_flags(dir.flags() & !INDEX_FL);
+
+		let mut buf = vec![0u8; self.block_size];
+		for index in 0..dir.size() / self.block_size as u64 {
+			let Some(block) = self.map_block(dir_ino, dir, index, false)? else {
+				continue;
+			};
+			self.read_block(block, &mut buf)?;
+
+			let mut previous = None;
+			let mut offset = 0;
+			while offset < self.block_size {
+				let (entry_ino, rec_len, entry_name) = self.parse_entry(&buf, offset)?;
+				if entry_ino != 0 && entry_name == name.as_bytes() {
+					match previous {
+						// merge the entry into the previous one
+						Some(previous) => {
+							let previous_len = get16(&buf, previous + 4);
+							put16(&mut buf, previous + 4, previous_len + rec_len as u16);
+						}
+						None => put32(&mut buf, offset, 0),
+					}
+					self.write_block(block, &buf)?;
+					return Ok(entry_ino);
+				}
+				previous = Some(offset);
+				offset += rec_len;
+			}
+		}
+
+		Err(io::Error::ENOENT)
+	}
+
+	/// Returns the inode of the path `components`, which are given in reversed order.
+	fn resolve(&mut self, components: &[&str]) -> io::Result<u32> {
+		let mut ino = ROOT_INO;
+		for name in components
+			.iter()
+			.rev()
+			.filter(|name| !name.is_empty() && **name != ".")
+		{
+			ino = self.lookup(ino, name)?.ok_or(io::Error::ENOENT)?;
+		}
+
+		Ok(ino)
+	}
+
+	/// Returns the directory, which contains the last component of the path
+	/// `components`, and the name of the last component.
+	fn resolve_parent<'a>(&mut self, components: &[&'a str]) -> io::Result<(u32, &'a str)> {
+		let Some(index) = components
+			.iter()
+			.position(|name| !name.is_empty() && *name != ".")
+		else {
+			return Err(io::Error::EINVAL);
+		};
+
+		let name = components[index];
+		if name == ".." {
+			return Err(io::Error::EINVAL);
+		}
+		if name.len() > MAX_NAME_LEN {
+			return Err(io::Error::ENAMETOOLONG);
+		}
+
+		Ok((self.resolve(&components[index + 1..])?, name))
+	}
+
+	/// Creates a new file or directory `name` in the directory `dir_ino`.
+	fn create(&mut self, dir_ino: u32, name: &str, mode: u16) -> io::Result<u32> {
+		if self.read_only {
+			return Err(io::Error::EROFS);
+		}
+		if self.lookup(dir_ino, name)?.is_some() {
+			return Err(io::Error::EEXIST);
+		}
+
+		let is_dir = mode & S_IFMT == S_IFDIR;
+		let mut dir = self.read_inode(dir_ino)?;
+		let ino = self.alloc_inode(self.group_of(dir_ino), is_dir)?;
+
+		let mut inode = Inode(vec![0u8; self.inode_size]);
+		put16(&mut inode.0, 0, mode);
+		put32(&mut inode.0, 8, now());
+		inode.set_times(true);
+		inode.set_links(1);
+
+		let result = if is_dir {
+			self.init_dir(ino, &mut inode, dir_ino)
+				.and_then(|()| self.add_entry(dir_ino, &mut dir, name, ino, FT_DIR))
+		} else {
+			self.write_inode(ino, &inode)
+				.and_then(|()| self.add_entry(dir_ino, &mut dir, name, ino, FT_REG_FILE))
+		};
+		if let Err(err) = result {
+			let _ = self.truncate_blocks(&mut inode, 0);
+			let _ = self.free_inode(ino, is_dir);
+			return Err(err);
+		}
+
+		if is_dir {
+			dir.set_links(dir.links() + 1);
+		}
+		dir.set_times(true);
+		self.write_inode(dir_ino, &dir)?;
+
+		Ok(ino)
+	}
+
+	/// Creates the entries `.` and `..` of the new directory `ino` and writes its inode.
+	fn init_dir(&mut self, ino: u32, inode: &mut Inode, parent: u32) -> io::Result<()> {
+		let block = self.map_block(ino, inode, 0, true)?.unwrap();
+		let mut buf = vec![0u8; self.block_size];
+		let dot_len = entry_len(1);
+		self.write_entry(&mut buf, 0, ino, dot_len, ".", FT_DIR);
+		self.write_entry(
+			&mut buf,
+			dot_len,
+			parent,
+			self.block_size - dot_len,
+			"..",
+			FT_DIR,
+		);
+		self.write_block(block, &buf)?;
+
+		inode.set_size(self.block_size as u64);
+		inode.set_links(2);
+		self.write_inode(ino, inode)
+	}
+
+	/// Releases the blocks and the inode `ino`, whose last link has been removed.
+	fn release_inode(&mut self, ino: u32, inode: &mut Inode) -> io::Result<()> {
+		let is_dir = inode.is_dir();
+		self.truncate_blocks(inode, 0)?;
+		inode.set_size(0);
+		inode.set_links(0);
+		put32(&mut inode.0, 20, now());
+		self.write_inode(ino, inode)?;
+		self.free_inode(ino, is_dir)
+	}
+
+	/// Removes the last link of the inode `ino`.
+	///
+	/// If the inode is still open, it is added to the orphan list and released,
+	/// when its last handle is closed.
+	fn remove_last_link(&mut self, ino: u32, inode: &mut Inode) -> io::Result<()> {
+		if !self.handles.contains_key(&ino) {
+			return self.release_inode(ino, inode);
+		}
+
+		inode.set_links(0);
+		inode.set_times(false);
+		put32(&mut inode.0, 20, get32(&self.superblock, LAST_ORPHAN));
+		self.write_inode(ino, inode)?;
+		put32(&mut self.superblock, LAST_ORPHAN, ino);
+		self.write_superblock()
+	}
+
+	/// Removes the inode `ino` from the orphan list, which is chained through
+	/// the deletion times of the inodes.
+	fn remove_orphan(&mut self, ino: u32) -> io::Result<()> {
+		let next = get32(&self.read_inode(ino)?.0, 20);
+		let mut current = get32(&self.superblock, LAST_ORPHAN);
+		if current == ino {
+			put32(&mut self.superblock, LAST_ORPHAN, next);
+			return self.write_superblock();
+		}
+
+		for _ in 0..self.inodes_count {
+			if current == 0 {
+				break;
+			}
+
+			let mut inode = self.read_inode(current)?;
+			let following = get32(&inode.0, 20);
+			if following == ino {
+				put32(&mut inode.0, 20, next);
+				return self.write_inode(current, &inode);
+			}
+			current = following;
+		}
+
+		error!("ext2: inode {ino} is missing in the orphan list");
+		Err(io::Error::EIO)
+	}
+
+	/// Releases the inodes of the orphan list, which have still been open,
+	/// when the file system was unmounted.
+	fn release_orphans(&mut self) -> io::Result<()> {
+		for _ in 0..self.inodes_count {
+			let ino = get32(&self.superblock, LAST_ORPHAN);
+			if ino == 0 {
+				return Ok(());
+			}
+
+			let mut inode = self.read_inode(ino)?;
+			put32(&mut self.superblock, LAST_ORPHAN, get32(&inode.0, 20));
+			self.write_superblock()?;
+			if inode.links() == 0 {
+				self.release_inode(ino, &mut inode)?;
+			}
+		}
+
+		error!("ext2: the orphan list contains a cycle");
+		Err(io::Error::EIO)
+	}
+
+	/// Registers a new handle of the inode `ino`.
+	fn open_handle(&mut self, ino: u32) {
+		*self.handles.entry(ino).or_default() += 1;
+	}
+
+	/// Unregisters a handle of the inode `ino` and releases the inode, if the
+	/// handle has been the last one and the last link has already been removed.
+	fn close_handle(&mut self, ino: u32) -> io::Result<()> {
+		let Entry::Occupied(mut entry) = self.handles.entry(ino) else {
+			return Ok(());
+		};
+		*entry.get_mut() -= 1;
+		if *entry.get() > 0 {
+			return Ok(());
+		}
+		entry.remove();
+
+		let mut inode = self.read_inode(ino)?;
+		if inode.links() == 0 && !self.read_only {
+			self.remove_orphan(ino)?;
+			self.release_inode(ino, &mut inode)?;
+		}
+		Ok(())
+	}
+
+	fn unlink(&mut self, components: &[&str]) -> io::Result<()> {
+		if self.read_only {
+			return Err(io::Error::EROFS);
+		}
+
+		let (dir_ino, name) = self.resolve_parent(components)?;
+		let ino = self.lookup(dir_ino, name)?.ok_or(io::Error::ENOENT)?;
+		let mut inode = self.read_inode(ino)?;
+		if inode.is_dir() {
+			return Err(io::Error::EISDIR);
+		}
+
+		let mut dir = self.read_inode(dir_ino)?;
+		self.remove_entry(dir_ino, &mut dir, name)?;
+		dir.set_times(true);
+		self.write_inode(dir_ino, &dir)?;
+
+		let links = inode.links().saturating_sub(1);
+		if links == 0 {
+			self.remove_last_link(ino, &mut inode)
+		} else {
+			inode.set_links(links);
+			inode.set_times(false);
+			self.write_inode(ino, &inode)
+		}
+	}
+
+	fn rmdir(&mut self, components: &[&str]) -> io::Result<()> {
+		if self.read_only {
+			return Err(io::Error::EROFS);
+		}
+
+		let (dir_ino, name) = self.resolve_parent(components)?;
+		let ino = self.lookup(dir_ino, name)?.ok_or(io::Error::ENOENT)?;
+		let mut inode = self.read_inode(ino)?;
+		if !inode.is_dir() {
+			return Err(io::Error::ENOTDIR);
+		}
+		if self
+			.read_dir(ino)?
+			.iter()
+			.any(|(name, _)| name != "." && name != "..")
+		{
+			return Err(io::Error::ENOTEMPTY);
+		}
+
+		let mut dir = self.read_inode(dir_ino)?;
+		self.remove_entry(dir_ino, &mut dir, name)?;
+		dir.set_links(dir.links().saturating_sub(1));
+		dir.set_times(true);
+		self.write_inode(dir_ino, &dir)?;
+
+		self.remove_last_link(ino, &mut inode)
+	}
+
+	fn mkdir(&mut self, components: &[&str], mode: AccessPermission) -> io::Result<()> {
+		let (dir_ino, name) = self.resolve_parent(components)?;
+		self.create(dir_ino, name, S_IFDIR | (mode.bits() & 0o7777) as u16)?;
+		Ok(())
+	}
+
+	/// Opens the file `components` and returns its inode.
+	fn open(
+		&mut self,
+		components: &[&str],
+		opt: OpenOption,
+		mode: AccessPermission,
+	) -> io::Result<u32> {
+		let writable = opt.intersects(OpenOption::O_WRONLY | OpenOption::O_RDWR);
+		if self.read_only
+			&& (writable
+				|| opt.intersects(OpenOption::O_CREAT | OpenOption::O_TRUNC | OpenOption::O_APPEND))
+		{
+			return Err(io::Error::EROFS);
+		}
+		if opt.contains(OpenOption::O_DIRECTORY | OpenOption::O_CREAT) {
+			return Err(io::Error::EINVAL);
+		}
+
+		let ino = match self.resolve(components) {
+			Ok(ino) => {
+				if opt.contains(OpenOption::O_CREAT | OpenOption::O_EXCL) {
+					return Err(io::Error::EEXIST);
+				}
+				ino
+			}
+			Err(io::Error::ENOENT) if opt.contains(OpenOption::O_CREAT) => {
+				let (dir_ino, name) = self.resolve_parent(components)?;
+				return self.create(dir_ino, name, S_IFREG | (mode.bits() & 0o7777) as u16);
+			}
+			Err(err) => return Err(err),
+		};
+
+		let mut inode = self.read_inode(ino)?;
+		if inode.is_dir() {
+			if writable {
+				return Err(io::Error::EISDIR);
+			}
+		} else if opt.contains(OpenOption::O_DIRECTORY) {
+			return Err(io::Error::ENOTDIR);
+		} else if opt.contains(OpenOption::O_TRUNC) && inode.is_regular() && writable {
+			self.truncate_blocks(&mut inode, 0)?;
+			inode.set_size(0);
+			inode.set_times(true);
+			self.write_inode(ino, &inode)?;
+		}
+
+		Ok(ino)
+	}
+
+	fn attributes(&self, ino: u32) -> io::Result<FileAttr> {
+		Ok(self.read_inode(ino)?.attributes(ino, self.block_size))
+	}
+}
+
+/// Unregisters a handle of the inode `ino`, e.g., if a file is closed.
+fn close_handle(fs: &Mutex<Ext2>, ino: u32) {
+	if let Err(err) = block_on(async { fs.lock().await.close_handle(ino) }, None) {
+		error!("ext2: unable to release inode {ino}: {err:?}");
+	}
+}
+
+/// Open file of an ext2 file system
+#[derive(Debug)]
+struct Ext2File {
+	fs: Arc<Mutex<Ext2>>,
+	ino: u32,
+	/// Position within the file
+	pos: Mutex<u64>,
+	append: bool,
+}
+
+#[async_trait]
+impl ObjectInterface for Ext2File {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event.intersection(
+			PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLOUT | PollEvent::POLLWRNORM,
+		))
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let mut pos_guard = self.pos.lock().await;
+		let mut fs = self.fs.lock().await;
+		let mut inode = fs.read_inode(self.ino)?;
+		let len = fs.read_data(self.ino, &mut inode, *pos_guard, buf)?;
+		*pos_guard += len as u64;
+
+		Ok(len)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let mut pos_guard = self.pos.lock().await;
+		let mut fs = self.fs.lock().await;
+		let mut inode = fs.read_inode(self.ino)?;
+		if self.append {
+			*pos_guard = inode.size();
+		}
+		let len = fs.write_data(self.ino, &mut inode, *pos_guard, buf)?;
+		*pos_guard += len as u64;
+
+		Ok(len)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		let mut pos_guard = self.pos.lock().await;
+
+		let new_pos = match whence {
+			SeekWhence::Set => offset,
+			SeekWhence::Cur => *pos_guard as isize + offset,
+			SeekWhence::End => {
+				let size = self.fs.lock().await.read_inode(self.ino)?.size();
+				size as isize + offset
+			}
+			_ => return Err(io::Error::EINVAL),
+		};
+
+		if new_pos < 0 {
+			return Err(io::Error::EINVAL);
+		}
+
+		*pos_guard = new_pos as u64;
+		Ok(new_pos)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		self.fs.lock().await.attributes(self.ino)
+	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		self.fs.lock().await.device.flush()
+	}
+
+	async fn dax_address(&self, offset: usize) -> io::Result<(VirtAddr, usize)> {
+		self.fs.lock().await.dax_address(self.ino, offset as u64)
+	}
+}
+
+impl Drop for Ext2File {
+	fn drop(&mut self) {
+		close_handle(&self.fs, self.ino);
+	}
+}
+
+/// Open directory of an ext2 file system
+#[derive(Debug)]
+struct Ext2DirectoryHandle {
+	fs: Arc<Mutex<Ext2>>,
+	ino: u32,
+}
+
+#[async_trait]
+impl ObjectInterface for Ext2DirectoryHandle {
+	async fn readdir(&self) -> io::Result<Vec<DirectoryEntry>> {
+		Ok(self
+			.fs
+			.lock()
+			.await
+			.read_dir(self.ino)?
+			.into_iter()
+			.filter(|(name, _)| name != "." && name != "..")
+			.map(|(name, _)| DirectoryEntry::new(name))
+			.collect())
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		self.fs.lock().await.attributes(self.ino)
+	}
+}
+
+impl Drop for Ext2DirectoryHandle {
+	fn drop(&mut self) {
+		close_handle(&self.fs, self.ino);
+	}
+}
+
+/// Root directory of a mounted ext2 file system
+#[derive(Debug)]
+struct Ext2Directory {
+	fs: Arc<Mutex<Ext2>>,
+}
+
+impl Ext2Directory {
+	fn with_fs<T>(&self, f: impl FnOnce(&mut Ext2) -> io::Result<T>) -> io::Result<T> {
+		block_on(async { f(&mut *self.fs.lock().await) }, None)
+	}
+}
+
+impl VfsNode for Ext2Directory {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::Directory
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		self.with_fs(|fs| fs.attributes(ROOT_INO))
+	}
+
+	fn get_object(&self) -> io::Result<Arc<dyn ObjectInterface>> {
+		self.with_fs(|fs| {
+			fs.open_handle(ROOT_INO);
+			Ok(())
+		})?;
+		Ok(Arc::new(Ext2DirectoryHandle {
+			fs: self.fs.clone(),
+			ino: ROOT_INO,
+		}))
+	}
+
+	fn traverse_mkdir(&self, components: &mut Vec<&str>, mode: AccessPermission) -> io::Result<()> {
+		self.with_fs(|fs| fs.mkdir(components, mode))
+	}
+
+	fn traverse_rmdir(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		self.with_fs(|fs| fs.rmdir(components))
+	}
+
+	fn traverse_unlink(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		self.with_fs(|fs| fs.unlink(components))
+	}
+
+	fn traverse_readdir(&self, components: &mut Vec<&str>) -> io::Result<Vec<DirectoryEntry>> {
+		self.with_fs(|fs| {
+			let ino = fs.resolve(components)?;
+			Ok(fs
+				.read_dir(ino)?
+				.into_iter()
+				.filter(|(name, _)| name != "." && name != "..")
+				.map(|(name, _)| DirectoryEntry::new(name))
+				.collect())
+		})
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.with_fs(|fs| {
+			let ino = fs.resolve(components)?;
+			fs.attributes(ino)
+		})
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.traverse_lstat(components)
+	}
+
+	fn traverse_open(
+		&self,
+		components: &mut Vec<&str>,
+		opt: OpenOption,
+		mode: AccessPermission,
+	) -> io::Result<Arc<dyn ObjectInterface>> {
+		let (ino, is_dir) = self.with_fs(|fs| {
+			let ino = fs.open(components, opt, mode)?;
+			let is_dir = fs.read_inode(ino)?.is_dir();
+			fs.open_handle(ino);
+			Ok((ino, is_dir))
+		})?;
+
+		if is_dir {
+			Ok(Arc::new(Ext2DirectoryHandle {
+				fs: self.fs.clone(),
+				ino,
+			}))
+		} else {
+			Ok(Arc::new(Ext2File {
+				fs: self.fs.clone(),
+				ino,
+				pos: Mutex::new(0),
+				append: opt.contains(OpenOption::O_APPEND),
+			}))
+		}
+	}
+}
+
+/// Creates the root directory of a new mount of the ext2 file system on the
+/// block device `source`, e.g., `vda` or `/dev/vda`.
+pub(crate) fn new_mount(
+	source: &str,
+	read_only: bool,
+) -> io::Result<Box<dyn VfsNode + Send + Sync>> {
+	let name = source.strip_prefix("/dev/").unwrap_or(source);
+	let device = match name {
+		#[cfg(feature = "pmem")]
+		"pmem0" => super::pmem::block_device(),
+		_ => block::get_device(name),
+	}
+	.ok_or(io::Error::ENODEV)?;
+	let fs = Ext2::new(device, read_only || device.is_read_only())?;
+	info!(
+		"ext2: mounted {name} with {} blocks of {} bytes{}",
+		fs.blocks_count,
+		fs.block_size,
+		if fs.read_only { " (read-only)" } else { "" }
+	);
+
+	Ok(Box::new(Ext2Directory {
+		fs: Arc::new(Mutex::new(fs)),
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use hermit_sync::InterruptTicketMutex;
+
+	use super::*;
+
+	const BLOCK_SIZE: usize = 1024;
+	const BLOCKS: u32 = 128;
+	const INODES: u32 = 32;
+	const ROOT_BLOCK: u32 = 9;
+
+	/// Block device in memory
+	struct RamDisk(InterruptTicketMutex<Vec<u8>>);
+
+	impl BlockDevice for RamDisk {
+		fn block_size(&self) -> usize {
+			512
+		}
+
+		fn num_blocks(&self) -> u64 {
+			(self.0.lock().len() / 512) as u64
+		}
+
+		fn is_read_only(&self) -> bool {
+			false
+		}
+
+		fn read_blocks(&self, block: u64, buf: &mut [u8]) -> io::Result<()> {
+			let start = block as usize * 512;
+			buf.copy_from_slice(&self.0.lock()[start..start + buf.len()]);
+			Ok(())
+		}
+
+		fn write_blocks(&self, block: u64, buf: &[u8]) -> io::Result<()> {
+			let start = block as usize * 512;
+			self.0.lock()[start..start + buf.len()].copy_from_slice(buf);
+			Ok(())
+		}
+
+		fn flush(&self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	/// Creates an image with a single group, which contains an empty root
+	/// directory.
+	///
+	/// Block 1 contains the superblock, block 2 the group descriptors, blocks
+	/// 3 and 4 the bitmaps, blocks 5 to 8 the inode table and block 9 the
+	/// root directory.
+	fn image() -> Vec<u8> {
+		let mut image = vec![0u8; BLOCKS as usize * BLOCK_SIZE];
+
+		let sb = &mut image[SUPERBLOCK_OFFSET as usize..][..SUPERBLOCK_SIZE];
+		put32(sb, 0, INODES);
+		put32(sb, 4, BLOCKS);
+		put32(sb, 12, BLOCKS - 1 - ROOT_BLOCK);
+		put32(sb, 16, INODES - 10);
+		put32(sb, 20, 1);
+		put32(sb, 32, 8 * BLOCK_SIZE as u32);
+		put32(sb, 40, INODES);
+		put16(sb, 56, MAGIC);
+		put32(sb, 76, 1);
+		put32(sb, 84, GOOD_OLD_FIRST_INO);
+		put16(sb, 88, GOOD_OLD_INODE_SIZE as u16);
+		put32(sb, 96, FEATURE_INCOMPAT_FILETYPE);
+
+		let desc = &mut image[2 * BLOCK_SIZE..][..GROUP_DESC_SIZE];
+		put32(desc, 0, 3);
+		put32(desc, 4, 4);
+		put32(desc, 8, 5);
+		put16(desc, 12, (BLOCKS - 1 - ROOT_BLOCK) as u16);
+		put16(desc, 14, (INODES - 10) as u16);
+		put16(desc, 16, 1);
+
+		// blocks 1 to 9 and the reserved inodes 1 to 10 are used
+		image[3 * BLOCK_SIZE] = 0xff;
+		image[3 * BLOCK_SIZE + 1] = 0x01;
+		image[4 * BLOCK_SIZE] = 0xff;
+		image[4 * BLOCK_SIZE + 1] = 0x03;
+
+		let root = &mut image[5 * BLOCK_SIZE + GOOD_OLD_INODE_SIZE..][..GOOD_OLD_INODE_SIZE];
+		put16(root, 0, S_IFDIR | 0o755);
+		put32(root, 4, BLOCK_SIZE as u32);
+		put16(root, 26, 2);
+		put32(root, 28, 2);
+		put32(root, 40, ROOT_BLOCK);
+
+		let dir = &mut image[ROOT_BLOCK as usize * BLOCK_SIZE..][..BLOCK_SIZE];
+		put32(dir, 0, ROOT_INO);
+		put16(dir, 4, 12);
+		dir[6] = 1;
+		dir[7] = FT_DIR;
+		dir[8] = b'.';
+		put32(dir, 12, ROOT_INO);
+		put16(dir, 16, (BLOCK_SIZE - 12) as u16);
+		dir[18] = 2;
+		dir[19] = FT_DIR;
+		dir[20..22].copy_from_slice(b"..");
+
+		image
+	}
+
+	fn mount(image: Vec<u8>) -> io::Result<Ext2> {
+		let device = Box::leak(Box::new(RamDisk(InterruptTicketMutex::new(image))));
+		Ext2::new(device, false)
+	}
+
+	fn free_blocks(fs: &Ext2) -> u32 {
+		get32(&fs.superblock, 12)
+	}
+
+	fn write_file(fs: &mut Ext2, name: &str, data: &[u8]) -> u32 {
+		let ino = fs
+			.open(
+				&[name],
+				OpenOption::O_CREAT | OpenOption::O_RDWR,
+				AccessPermission::from_bits(0o644).unwrap(),
+			)
+			.unwrap();
+		let mut inode = fs.read_inode(ino).unwrap();
+		assert_eq!(fs.write_data(ino, &mut inode, 0, data).unwrap(), data.len());
+		ino
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_write_read() {
+		let mut fs = mount(image()).unwrap();
+		let data: Vec<u8> = (0..20 * BLOCK_SIZE).map(|i| i as u8).collect();
+		let ino = write_file(&mut fs, "file", &data);
+
+		let mut inode = fs.read_inode(ino).unwrap();
+		let mut buf = vec![0u8; data.len()];
+		assert_eq!(
+			fs.read_data(ino, &mut inode, 0, &mut buf).unwrap(),
+			data.len()
+		);
+		assert_eq!(buf, data);
+		assert_eq!(fs.resolve(&["file"]).unwrap(), ino);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_unlink_open_file() {
+		let mut fs = mount(image()).unwrap();
+		let free = free_blocks(&fs);
+		let ino = write_file(&mut fs, "file", &[1; 4 * BLOCK_SIZE]);
+		fs.open_handle(ino);
+
+		fs.unlink(&["file"]).unwrap();
+		assert_eq!(fs.resolve(&["file"]), Err(io::Error::ENOENT));
+		assert_eq!(get32(&fs.superblock, LAST_ORPHAN), ino);
+		assert!(free_blocks(&fs) < free);
+
+		// the data remains accessible through the handle
+		let mut inode = fs.read_inode(ino).unwrap();
+		let mut buf = [0u8; 4];
+		fs.read_data(ino, &mut inode, 0, &mut buf).unwrap();
+		assert_eq!(buf, [1; 4]);
+
+		fs.close_handle(ino).unwrap();
+		assert_eq!(get32(&fs.superblock, LAST_ORPHAN), 0);
+		assert_eq!(free_blocks(&fs), free);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_release_orphans() {
+		let mut fs = mount(image()).unwrap();
+		let free = free_blocks(&fs);
+		let ino = write_file(&mut fs, "file", &[1; 4 * BLOCK_SIZE]);
+		fs.open_handle(ino);
+		fs.unlink(&["file"]).unwrap();
+
+		// mount the image, while the file is still open
+		let mut image = vec![0u8; BLOCKS as usize * BLOCK_SIZE];
+		fs.device.read_blocks(0, &mut image).unwrap();
+		let fs = mount(image).unwrap();
+		assert_eq!(get32(&fs.superblock, LAST_ORPHAN), 0);
+		assert_eq!(free_blocks(&fs), free);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_invalid_superblock() {
+		let mut image = image();
+		// more inodes than the single group covers
+		put32(&mut image[SUPERBLOCK_OFFSET as usize..], 0, 2 * INODES);
+		assert_eq!(mount(image).unwrap_err(), io::Error::EINVAL);
+
+		let mut image = self::image();
+		// inode table beyond the end of the file system
+		put32(&mut image[2 * BLOCK_SIZE..], 8, BLOCKS - 1);
+		assert_eq!(mount(image).unwrap_err(), io::Error::EINVAL);
+	}
+}
@@ -5,12 +5,13 @@ use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::sync::atomic::{AtomicU64, Ordering};
-use core::task::Poll;
-use core::{future, mem};
+use core::task::{Poll, Waker};
+use core::{future, mem, ptr};
 
 use async_lock::Mutex;
 use async_trait::async_trait;
 use fuse_abi::linux::*;
+use hermit_sync::InterruptTicketMutex;
 use num_traits::FromPrimitive;
 use zerocopy::FromBytes;
 
@@ -22,13 +23,15 @@ use crate::drivers::pci::get_filesystem_driver;
 use crate::drivers::virtio::virtqueue::error::VirtqError;
 use crate::executor::block_on;
 use crate::fd::PollEvent;
+use crate::fs::dcache::DentryCache;
 use crate::fs::{
 	self, AccessPermission, DirectoryEntry, FileAttr, NodeKind, ObjectInterface, OpenOption,
 	SeekWhence, VfsNode,
 };
 use crate::mm::device_alloc::DeviceAlloc;
-use crate::time::{time_t, timespec};
-use crate::{arch, io};
+use crate::mm::pressure::Shrinker;
+use crate::time::{realtime_micros, time_t, timespec};
+use crate::{env, io};
 
 // response out layout eg @ https://github.com/zargony/fuse-rs/blob/bf6d1cf03f3277e35b580f3c7b9999255d72ecf3/src/ll/request.rs#L44
 // op in/out sizes/layout: https://github.com/hanwen/go-fuse/blob/204b45dba899dfa147235c255908236d5fde2d32/fuse/opcode.go#L439
@@ -36,28 +39,158 @@ use crate::{arch, io};
 
 const MAX_READ_LEN: usize = 1024 * 64;
 const MAX_WRITE_LEN: usize = 1024 * 64;
+/// Writes of at least this size pass the buffer of the caller to the device instead of copying it.
+const MIN_ZERO_COPY_WRITE_LEN: usize = 1024 * 4;
 
 const U64_SIZE: usize = mem::size_of::<u64>();
 
+/// Linux flag to avoid updates of the access time
+const O_NOATIME: u32 = 0o1_000_000;
+
+/// Options of a FUSE mount
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct MountOptions {
+	/// Size of the read-ahead window in bytes (`0` disables read-ahead)
+	pub readahead: usize,
+	/// Size of the write-behind buffer in bytes (`0` disables write-behind)
+	///
+	/// Buffered data is sent to the host on `fsync`, `lseek`, `fstat` and `close`.
+	pub write_behind: usize,
+	/// Rejects all modifications with `EROFS`
+	pub read_only: bool,
+	/// Asks the host to not update the access time of opened files
+	pub noatime: bool,
+	/// Mapping of a guest user ID to a host user ID
+	pub uid_map: Option<(u32, u32)>,
+	/// Mapping of a guest group ID to a host group ID
+	pub gid_map: Option<(u32, u32)>,
+}
+
+impl Default for MountOptions {
+	fn default() -> Self {
+		Self {
+			readahead: MAX_READ_LEN,
+			write_behind: 0,
+			read_only: false,
+			noatime: false,
+			uid_map: None,
+			gid_map: None,
+		}
+	}
+}
+
+fn parse_id_map(map: &str) -> Option<(u32, u32)> {
+	let (guest, host) = map.split_once(':')?;
+	Some((guest.parse().ok()?, host.parse().ok()?))
+}
+
+impl MountOptions {
+	/// Parses the options of a mount of the device with the tag `tag`.
+	///
+	/// `options` is a comma-separated list of `ro`, `rw`, `noatime`,
+	/// `uid=<guest>:<host>`, `gid=<guest>:<host>`, `readahead=<bytes>` and
+	/// `write_behind=<bytes>`. They are given by the kernel argument
+	/// `virtiofs.<tag>=<options>` or by the data of `sys_mount`.
+	fn new(tag: &str, options: &str) -> Self {
+		let mut mount_options = Self::default();
+		for option in options.split(',') {
+			match option.split_once('=') {
+				None if option.is_empty() => {}
+				None if option == "ro" => mount_options.read_only = true,
+				None if option == "rw" => mount_options.read_only = false,
+				None if option == "noatime" => mount_options.noatime = true,
+				Some(("uid", map)) if parse_id_map(map).is_some() => {
+					mount_options.uid_map = parse_id_map(map);
+				}
+				Some(("gid", map)) if parse_id_map(map).is_some() => {
+					mount_options.gid_map = parse_id_map(map);
+				}
+				Some(("readahead", size)) if size.parse::<usize>().is_ok() => {
+					mount_options.readahead = size.parse::<usize>().unwrap().min(MAX_READ_LEN);
+				}
+				Some(("write_behind", size)) if size.parse::<usize>().is_ok() => {
+					mount_options.write_behind = size.parse::<usize>().unwrap().min(MAX_WRITE_LEN);
+				}
+				_ => warn!("Ignore invalid option {option} of virtio-fs tag {tag}"),
+			}
+		}
+		mount_options
+	}
+
+	/// Returns `EROFS`, if the mount is read-only.
+	fn check_writable(&self) -> io::Result<()> {
+		if self.read_only {
+			Err(io::Error::EROFS)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Translates the owner of a file on the host to the guest.
+	fn map_attr(&self, mut attr: FileAttr) -> FileAttr {
+		match self.uid_map {
+			Some((guest, host)) if attr.st_uid == host => attr.st_uid = guest,
+			_ => {}
+		}
+		match self.gid_map {
+			Some((guest, host)) if attr.st_gid == host => attr.st_gid = guest,
+			_ => {}
+		}
+		attr
+	}
+
+	/// Sets the credentials of a command, which creates a file on the host.
+	fn set_owner(&self, in_header: &mut fuse_in_header) {
+		if let Some((_, host)) = self.uid_map {
+			in_header.uid = host;
+		}
+		if let Some((_, host)) = self.gid_map {
+			in_header.gid = host;
+		}
+	}
+
+	/// Adds the flags of the mount to the flags of `FUSE_OPEN`.
+	fn open_flags(&self, flags: u32) -> u32 {
+		if self.noatime {
+			flags | O_NOATIME
+		} else {
+			flags
+		}
+	}
+}
+
 const S_IFLNK: u32 = 0o120_000;
 const S_IFMT: u32 = 0o170_000;
 
 pub(crate) trait FuseInterface {
-	fn send_command<O: ops::Op + 'static>(
+	/// Returns `true` if the device is able to accept another command.
+	fn can_submit(&self) -> bool;
+
+	/// Passes `cmd` to the device without waiting for the response.
+	fn submit_command<O: ops::Op + 'static>(
 		&mut self,
 		cmd: Cmd<O>,
 		rsp_payload_len: u32,
-	) -> Result<Rsp<O>, VirtqError>
+	) -> Result<(), VirtqError>
 	where
 		<O as ops::Op>::InStruct: Send,
 		<O as ops::Op>::OutStruct: Send;
 
+	/// Returns the response to the command with the ID `unique`, if it is available.
+	fn try_complete<O: ops::Op + 'static>(&mut self, unique: u64) -> Option<Rsp<O>>
+	where
+		<O as ops::Op>::InStruct: Send,
+		<O as ops::Op>::OutStruct: Send;
+
+	/// Registers `waker` to be woken, when the response to the command with the ID
+	/// `unique` is available or, without ID, when another command can be submitted.
+	fn register_waker(&mut self, unique: Option<u64>, waker: &Waker);
+
 	fn get_mount_point(&self) -> String;
 }
 
 pub(crate) mod ops {
 	#![allow(clippy::type_complexity)]
-	use alloc::boxed::Box;
 	use alloc::ffi::CString;
 
 	use fuse_abi::linux::*;
@@ -164,8 +297,8 @@ pub(crate) mod ops {
 	}
 
 	impl Write {
-		pub(crate) fn create(nid: u64, fh: u64, buf: Box<[u8]>, offset: u64) -> (Cmd<Self>, u32) {
-			let cmd = Cmd::with_boxed_slice(
+		pub(crate) fn create(nid: u64, fh: u64, buf: &[u8], offset: u64) -> (Cmd<Self>, u32) {
+			let cmd = Cmd::with_slice(
 				nid,
 				fuse_write_in {
 					fh,
@@ -177,6 +310,32 @@ pub(crate) mod ops {
 			);
 			(cmd, 0)
 		}
+
+		/// Creates a write command, which refers to `buf` instead of copying it.
+		///
+		/// # Safety
+		///
+		/// `buf` must stay valid and must not be modified until the command is completed.
+		pub(crate) unsafe fn create_borrowed(
+			nid: u64,
+			fh: u64,
+			buf: &[u8],
+			offset: u64,
+		) -> (Cmd<Self>, u32) {
+			let cmd = unsafe {
+				Cmd::with_borrowed_slice(
+					nid,
+					fuse_write_in {
+						fh,
+						offset,
+						size: buf.len().try_into().unwrap(),
+						..Default::default()
+					},
+					buf,
+				)
+			};
+			(cmd, 0)
+		}
 	}
 
 	#[derive(Debug)]
@@ -270,6 +429,27 @@ pub(crate) mod ops {
 		}
 	}
 
+	#[derive(Debug)]
+	pub(crate) struct Fsync;
+
+	impl Op for Fsync {
+		const OP_CODE: fuse_opcode = fuse_opcode::FUSE_FSYNC;
+		type InStruct = fuse_fsync_in;
+		type InPayload = ();
+		type OutStruct = ();
+		type OutPayload = ();
+	}
+
+	impl Fsync {
+		pub(crate) fn create(nid: u64, fh: u64) -> (Cmd<Self>, u32) {
+			let cmd = Cmd::new(nid, fuse_fsync_in {
+				fh,
+				..Default::default()
+			});
+			(cmd, 0)
+		}
+	}
+
 	#[derive(Debug)]
 	pub(crate) struct Release;
 
@@ -375,6 +555,32 @@ pub(crate) mod ops {
 		}
 	}
 
+	#[derive(Debug)]
+	pub(crate) struct Rename;
+
+	impl Op for Rename {
+		const OP_CODE: fuse_opcode = fuse_opcode::FUSE_RENAME;
+		type InStruct = fuse_rename_in;
+		type InPayload = [u8];
+		type OutStruct = ();
+		type OutPayload = ();
+	}
+
+	impl Rename {
+		pub(crate) fn create(old: CString, new: CString) -> (Cmd<Self>, u32) {
+			let mut names = old.into_bytes_with_nul();
+			names.extend_from_slice(new.as_bytes_with_nul());
+			let cmd = Cmd::with_slice(
+				FUSE_ROOT_ID,
+				fuse_rename_in {
+					newdir: FUSE_ROOT_ID,
+				},
+				&names,
+			);
+			(cmd, 0)
+		}
+	}
+
 	#[derive(Debug)]
 	pub(crate) struct Lookup;
 
@@ -425,6 +631,33 @@ impl From<fuse_attr> for FileAttr {
 	}
 }
 
+/// Results of previous lookups, indexed by the path relative to the root node
+static DENTRY_CACHE: InterruptTicketMutex<DentryCache> =
+	InterruptTicketMutex::new(DentryCache::new());
+
+/// Releases entries of the dentry cache under memory pressure
+pub(crate) struct DentryCacheShrinker;
+
+impl Shrinker for DentryCacheShrinker {
+	fn name(&self) -> &'static str {
+		"fuse dentry cache"
+	}
+
+	fn cached_bytes(&self) -> usize {
+		DENTRY_CACHE.try_lock().map_or(0, |cache| cache.size())
+	}
+
+	fn shrink(&self, target: usize) -> usize {
+		// the allocation may have failed while the cache is locked
+		DENTRY_CACHE
+			.try_lock()
+			.map_or(0, |mut cache| cache.shrink(target))
+	}
+}
+
+/// Source of the IDs, which allow to match responses to their commands
+static NEXT_UNIQUE: AtomicU64 = AtomicU64::new(1);
+
 #[repr(C)]
 #[derive(Debug)]
 pub(crate) struct CmdHeader<O: ops::Op> {
@@ -453,7 +686,7 @@ impl<O: ops::Op> CmdHeader<O> {
 					.expect("The command is too large"),
 				opcode: O::OP_CODE.into(),
 				nodeid,
-				unique: 1,
+				unique: NEXT_UNIQUE.fetch_add(1, Ordering::Relaxed),
 				..Default::default()
 			},
 			op_header,
@@ -461,9 +694,19 @@ impl<O: ops::Op> CmdHeader<O> {
 	}
 }
 
+pub(crate) enum CmdPayload {
+	/// Payload, which is stored in memory that is accessible by the device
+	Owned(Vec<u8, DeviceAlloc>),
+	/// Payload, which remains in the memory of the caller
+	///
+	/// The driver may pass the memory directly to the device or copy it,
+	/// if the device is not able to access it.
+	Borrowed(*const [u8]),
+}
+
 pub(crate) struct Cmd<O: ops::Op> {
 	pub headers: Box<CmdHeader<O>, DeviceAlloc>,
-	pub payload: Option<Vec<u8, DeviceAlloc>>,
+	pub payload: Option<CmdPayload>,
 }
 
 impl<O: ops::Op> Cmd<O>
@@ -489,7 +732,7 @@ where
 				CmdHeader::with_payload_size(nodeid, op_header, cstring_bytes.len()),
 				DeviceAlloc,
 			),
-			payload: Some(cstring_bytes),
+			payload: Some(CmdPayload::Owned(cstring_bytes)),
 		}
 	}
 }
@@ -498,15 +741,27 @@ impl<O: ops::Op> Cmd<O>
 where
 	O: ops::Op<InPayload = [u8]>,
 {
-	fn with_boxed_slice(nodeid: u64, op_header: O::InStruct, slice: Box<[u8]>) -> Self {
-		let mut device_slice = Vec::with_capacity_in(slice.len(), DeviceAlloc);
-		device_slice.extend_from_slice(&slice);
+	fn with_slice(nodeid: u64, op_header: O::InStruct, slice: &[u8]) -> Self {
+		let device_slice = slice.to_vec_in(DeviceAlloc);
+		Self {
+			headers: Box::new_in(
+				CmdHeader::with_payload_size(nodeid, op_header, slice.len()),
+				DeviceAlloc,
+			),
+			payload: Some(CmdPayload::Owned(device_slice)),
+		}
+	}
+
+	/// # Safety
+	///
+	/// `slice` must stay valid and must not be modified until the command is completed.
+	unsafe fn with_borrowed_slice(nodeid: u64, op_header: O::InStruct, slice: &[u8]) -> Self {
 		Self {
 			headers: Box::new_in(
 				CmdHeader::with_payload_size(nodeid, op_header, slice.len()),
 				DeviceAlloc,
 			),
-			payload: Some(device_slice),
+			payload: Some(CmdPayload::Borrowed(ptr::from_ref(slice))),
 		}
 	}
 }
@@ -524,28 +779,106 @@ pub(crate) struct Rsp<O: ops::Op> {
 	pub payload: Option<Vec<u8, DeviceAlloc>>,
 }
 
+/// Sends `cmd` to the file system device and waits for the response.
+///
+/// The driver is only locked while the command is submitted or a response is collected.
+/// Consequently, commands of different tasks are processed by the device in parallel.
+///
+/// Returns the error of the device, if the response reports one.
+fn send_command<O: ops::Op + 'static>(cmd: Cmd<O>, rsp_payload_len: u32) -> io::Result<Rsp<O>>
+where
+	<O as ops::Op>::InStruct: Send,
+	<O as ops::Op>::OutStruct: Send,
+{
+	let driver = get_filesystem_driver().ok_or(io::Error::ENOSYS)?;
+	let unique = cmd.headers.in_header.unique;
+	let mut cmd = Some(cmd);
+
+	let rsp = block_on(
+		future::poll_fn(|cx| {
+			let mut guard = driver.lock();
+			if cmd.is_some() {
+				if !guard.can_submit() {
+					// the responses to other commands will free some descriptors
+					guard.register_waker(None, cx.waker());
+					return Poll::Pending;
+				}
+				guard.submit_command(cmd.take().unwrap(), rsp_payload_len)?;
+			}
+
+			match guard.try_complete(unique) {
+				Some(rsp) => Poll::Ready(Ok(rsp)),
+				None => {
+					guard.register_waker(Some(unique), cx.waker());
+					Poll::Pending
+				}
+			}
+		}),
+		None,
+	)?;
+
+	// The length includes the header and is limited by the provided buffers.
+	let len = rsp.headers.out_header.len as usize;
+	let max_len = mem::size_of::<RspHeader<O>>() + rsp_payload_len as usize;
+	if len < mem::size_of::<fuse_out_header>() || len > max_len {
+		warn!("FUSE response {unique} has an invalid length of {len} bytes");
+		return Err(io::Error::EIO);
+	}
+
+	match rsp.headers.out_header.error {
+		0 => Ok(rsp),
+		error => Err(io::Error::from_i32(error.saturating_neg()).unwrap_or(io::Error::EIO)),
+	}
+}
+
+/// Converts a validity period of FUSE into microseconds.
+fn valid_usecs(secs: u64, nsecs: u32) -> u64 {
+	secs.saturating_mul(1_000_000)
+		.saturating_add(u64::from(nsecs / 1000))
+}
+
+/// Returns the node id and the attributes of `path`.
+fn lookup_entry(path: CString) -> io::Result<(u64, FileAttr)> {
+	let name = path.to_str().unwrap().to_owned();
+	if let Some(entry) = DENTRY_CACHE.lock().getattr(FUSE_ROOT_ID, &name) {
+		return Ok(entry);
+	}
+
+	let (cmd, rsp_payload_len) = ops::Lookup::create(path);
+	let rsp = send_command(cmd, rsp_payload_len)?;
+	if rsp.headers.out_header.error != 0 {
+		return Err(io::Error::from_i32(-rsp.headers.out_header.error).unwrap());
+	}
+
+	let entry_out = fuse_entry_out::ref_from_bytes(rsp.payload.as_ref().unwrap()).unwrap();
+	let attr = FileAttr::from(entry_out.attr);
+	DENTRY_CACHE.lock().insert(
+		FUSE_ROOT_ID,
+		&name,
+		entry_out.nodeid,
+		attr,
+		valid_usecs(entry_out.entry_valid, entry_out.entry_valid_nsec),
+		valid_usecs(entry_out.attr_valid, entry_out.attr_valid_nsec),
+	);
+
+	Ok((entry_out.nodeid, attr))
+}
+
 fn lookup(name: CString) -> Option<u64> {
-	let (cmd, rsp_payload_len) = ops::Lookup::create(name);
-	let rsp = get_filesystem_driver()
-		.unwrap()
+	if let Some(nid) = DENTRY_CACHE
 		.lock()
-		.send_command(cmd, rsp_payload_len)
-		.ok()?;
-	if rsp.headers.out_header.error == 0 {
-		let entry_out = fuse_entry_out::ref_from_bytes(rsp.payload.as_ref().unwrap()).unwrap();
-		Some(entry_out.nodeid)
-	} else {
-		None
+		.lookup(FUSE_ROOT_ID, name.to_str().unwrap())
+	{
+		return Some(nid);
 	}
+
+	lookup_entry(name).ok().map(|(nid, _)| nid)
 }
 
 fn readlink(nid: u64) -> io::Result<String> {
 	let len = MAX_READ_LEN as u32;
 	let (cmd, rsp_payload_len) = ops::Readlink::create(nid, len);
-	let rsp = get_filesystem_driver()
-		.unwrap()
-		.lock()
-		.send_command(cmd, rsp_payload_len)?;
+	let rsp = send_command(cmd, rsp_payload_len)?;
 	let len: usize = if rsp.headers.out_header.len as usize - mem::size_of::<fuse_out_header>()
 		>= len.try_into().unwrap()
 	{
@@ -562,14 +895,25 @@ struct FuseFileHandleInner {
 	fuse_nid: Option<u64>,
 	fuse_fh: Option<u64>,
 	offset: usize,
+	options: MountOptions,
+	/// Data, which was read ahead, starting at the file offset `read_buf_offset`
+	read_buf: Vec<u8>,
+	read_buf_offset: usize,
+	/// Data, which was written, but not yet sent to the host.
+	/// The data ends at the current file offset.
+	write_buf: Vec<u8>,
 }
 
 impl FuseFileHandleInner {
-	pub fn new() -> Self {
+	pub fn new(options: MountOptions) -> Self {
 		Self {
 			fuse_nid: None,
 			fuse_fh: None,
 			offset: 0,
+			options,
+			read_buf: Vec::new(),
+			read_buf_offset: 0,
+			write_buf: Vec::new(),
 		}
 	}
 
@@ -580,10 +924,7 @@ impl FuseFileHandleInner {
 		future::poll_fn(|cx| {
 			if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
 				let (cmd, rsp_payload_len) = ops::Poll::create(nid, fh, kh, events);
-				let rsp = get_filesystem_driver()
-					.ok_or(io::Error::ENOSYS)?
-					.lock()
-					.send_command(cmd, rsp_payload_len)?;
+				let rsp = send_command(cmd, rsp_payload_len)?;
 
 				if rsp.headers.out_header.error < 0 {
 					Poll::Ready(Err(io::Error::EIO))
@@ -608,7 +949,8 @@ impl FuseFileHandleInner {
 		.await
 	}
 
-	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+	/// Reads from the host into `buf` at `offset`, without using the read-ahead buffer.
+	fn read_at(&self, buf: &mut [u8], offset: usize) -> io::Result<usize> {
 		let mut len = buf.len();
 		if len > MAX_READ_LEN {
 			debug!("Reading longer than max_read_len: {}", len);
@@ -616,11 +958,8 @@ impl FuseFileHandleInner {
 		}
 		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
 			let (cmd, rsp_payload_len) =
-				ops::Read::create(nid, fh, len.try_into().unwrap(), self.offset as u64);
-			let rsp = get_filesystem_driver()
-				.ok_or(io::Error::ENOSYS)?
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
+				ops::Read::create(nid, fh, len.try_into().unwrap(), offset as u64);
+			let rsp = send_command(cmd, rsp_payload_len)?;
 			let len: usize =
 				if (rsp.headers.out_header.len as usize) - mem::size_of::<fuse_out_header>() >= len
 				{
@@ -628,7 +967,6 @@ impl FuseFileHandleInner {
 				} else {
 					(rsp.headers.out_header.len as usize) - mem::size_of::<fuse_out_header>()
 				};
-			self.offset += len;
 
 			buf[..len].copy_from_slice(&rsp.payload.unwrap()[..len]);
 
@@ -639,7 +977,8 @@ impl FuseFileHandleInner {
 		}
 	}
 
-	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+	/// Writes `buf` to the host at `offset`, without using the write-behind buffer.
+	fn write_at(&self, buf: &[u8], offset: usize) -> io::Result<usize> {
 		debug!("FUSE write!");
 		let mut truncated_len = buf.len();
 		if truncated_len > MAX_WRITE_LEN {
@@ -651,25 +990,28 @@ impl FuseFileHandleInner {
 			truncated_len = MAX_WRITE_LEN;
 		}
 		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
-			let truncated_buf = Box::<[u8]>::from(&buf[..truncated_len]);
-			let (cmd, rsp_payload_len) =
-				ops::Write::create(nid, fh, truncated_buf, self.offset as u64);
-			let rsp = get_filesystem_driver()
-				.ok_or(io::Error::ENOSYS)?
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
+			let truncated_buf = &buf[..truncated_len];
+			let (cmd, rsp_payload_len) = if truncated_len >= MIN_ZERO_COPY_WRITE_LEN {
+				// SAFETY: `send_command` blocks until the device has processed the command
+				// and `truncated_buf` is borrowed for the whole call.
+				unsafe { ops::Write::create_borrowed(nid, fh, truncated_buf, offset as u64) }
+			} else {
+				ops::Write::create(nid, fh, truncated_buf, offset as u64)
+			};
+			let rsp = send_command(cmd, rsp_payload_len)?;
 
 			if rsp.headers.out_header.error < 0 {
 				return Err(io::Error::EIO);
 			}
 
+			DENTRY_CACHE.lock().invalidate_attr(nid);
+
 			let rsp_size = rsp.headers.op_header.size;
 			let rsp_len: usize = if rsp_size > truncated_len.try_into().unwrap() {
 				truncated_len
 			} else {
 				rsp_size.try_into().unwrap()
 			};
-			self.offset += rsp_len;
 			Ok(rsp_len)
 		} else {
 			warn!("File not open, cannot read!");
@@ -677,15 +1019,94 @@ impl FuseFileHandleInner {
 		}
 	}
 
+	/// Sends the content of the write-behind buffer to the host.
+	fn flush(&mut self) -> io::Result<()> {
+		let mut offset = self.offset - self.write_buf.len();
+		let mut written = 0;
+		while written < self.write_buf.len() {
+			let len = self.write_at(&self.write_buf[written..], offset)?;
+			if len == 0 {
+				return Err(io::Error::EIO);
+			}
+			written += len;
+			offset += len;
+		}
+		self.write_buf.clear();
+
+		Ok(())
+	}
+
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.flush()?;
+
+		let buffered = self.read_buf_offset..self.read_buf_offset + self.read_buf.len();
+		if !buffered.contains(&self.offset) {
+			self.read_buf.clear();
+
+			if buf.len() >= self.options.readahead {
+				let len = self.read_at(buf, self.offset)?;
+				self.offset += len;
+				return Ok(len);
+			}
+
+			let mut read_buf = mem::take(&mut self.read_buf);
+			read_buf.resize(self.options.readahead, 0);
+			let len = self.read_at(&mut read_buf, self.offset)?;
+			read_buf.truncate(len);
+			self.read_buf = read_buf;
+			self.read_buf_offset = self.offset;
+		}
+
+		let start = self.offset - self.read_buf_offset;
+		let len = buf.len().min(self.read_buf.len() - start);
+		buf[..len].copy_from_slice(&self.read_buf[start..start + len]);
+		self.offset += len;
+
+		Ok(len)
+	}
+
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.read_buf.clear();
+
+		if self.write_buf.len() + buf.len() > self.options.write_behind {
+			self.flush()?;
+		}
+
+		if buf.len() >= self.options.write_behind {
+			let len = self.write_at(buf, self.offset)?;
+			self.offset += len;
+			return Ok(len);
+		}
+
+		self.write_buf.extend_from_slice(buf);
+		self.offset += buf.len();
+
+		Ok(buf.len())
+	}
+
+	fn fsync(&mut self) -> io::Result<()> {
+		self.flush()?;
+
+		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
+			let (cmd, rsp_payload_len) = ops::Fsync::create(nid, fh);
+			let rsp = send_command(cmd, rsp_payload_len)?;
+			if rsp.headers.out_header.error < 0 {
+				return Err(io::Error::EIO);
+			}
+			Ok(())
+		} else {
+			Err(io::Error::EIO)
+		}
+	}
+
 	fn lseek(&mut self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
 		debug!("FUSE lseek");
+		self.flush()?;
+		self.read_buf.clear();
 
 		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
 			let (cmd, rsp_payload_len) = ops::Lseek::create(nid, fh, offset, whence);
-			let rsp = get_filesystem_driver()
-				.ok_or(io::Error::ENOSYS)?
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
+			let rsp = send_command(cmd, rsp_payload_len)?;
 
 			if rsp.headers.out_header.error < 0 {
 				return Err(io::Error::EIO);
@@ -701,16 +1122,14 @@ impl FuseFileHandleInner {
 
 	fn fstat(&mut self) -> io::Result<FileAttr> {
 		debug!("FUSE getattr");
+		self.flush()?;
 		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
 			let (cmd, rsp_payload_len) = ops::Getattr::create(nid, fh, FUSE_GETATTR_FH);
-			let rsp = get_filesystem_driver()
-				.ok_or(io::Error::ENOSYS)?
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
+			let rsp = send_command(cmd, rsp_payload_len)?;
 			if rsp.headers.out_header.error < 0 {
 				return Err(io::Error::EIO);
 			}
-			Ok(rsp.headers.op_header.attr.into())
+			Ok(self.options.map_attr(rsp.headers.op_header.attr.into()))
 		} else {
 			Err(io::Error::EIO)
 		}
@@ -719,14 +1138,14 @@ impl FuseFileHandleInner {
 
 impl Drop for FuseFileHandleInner {
 	fn drop(&mut self) {
+		if let Err(err) = self.flush() {
+			error!("Unable to write buffered data to the host: {err:?}");
+		}
+
 		if self.fuse_nid.is_some() && self.fuse_fh.is_some() {
 			let (cmd, rsp_payload_len) =
 				ops::Release::create(self.fuse_nid.unwrap(), self.fuse_fh.unwrap());
-			get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)
-				.unwrap();
+			send_command(cmd, rsp_payload_len).unwrap();
 		}
 	}
 }
@@ -735,8 +1154,8 @@ impl Drop for FuseFileHandleInner {
 struct FuseFileHandle(pub Arc<Mutex<FuseFileHandleInner>>);
 
 impl FuseFileHandle {
-	pub fn new() -> Self {
-		Self(Arc::new(Mutex::new(FuseFileHandleInner::new())))
+	pub fn new(options: MountOptions) -> Self {
+		Self(Arc::new(Mutex::new(FuseFileHandleInner::new(options))))
 	}
 }
 
@@ -761,6 +1180,10 @@ impl ObjectInterface for FuseFileHandle {
 	async fn fstat(&self) -> io::Result<FileAttr> {
 		self.0.lock().await.fstat()
 	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		self.0.lock().await.fsync()
+	}
 }
 
 impl Clone for FuseFileHandle {
@@ -798,10 +1221,7 @@ impl ObjectInterface for FuseDirectoryHandle {
 		// Flag 0x10000 for O_DIRECTORY might not be necessary
 		let (mut cmd, rsp_payload_len) = ops::Open::create(fuse_nid, 0x10000);
 		cmd.headers.in_header.opcode = fuse_opcode::FUSE_OPENDIR as u32;
-		let rsp = get_filesystem_driver()
-			.ok_or(io::Error::ENOSYS)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		let fuse_fh = rsp.headers.op_header.fh;
 
 		debug!("FUSE readdir: {path:#?}");
@@ -813,10 +1233,7 @@ impl ObjectInterface for FuseDirectoryHandle {
 		// read content of the directory
 		let (mut cmd, rsp_payload_len) = ops::Read::create(fuse_nid, fuse_fh, len, 0);
 		cmd.headers.in_header.opcode = fuse_opcode::FUSE_READDIR as u32;
-		let rsp = get_filesystem_driver()
-			.ok_or(io::Error::ENOSYS)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 
 		let len: usize = if rsp.headers.out_header.len as usize - mem::size_of::<fuse_out_header>()
 			>= len.try_into().unwrap()
@@ -859,10 +1276,7 @@ impl ObjectInterface for FuseDirectoryHandle {
 		}
 
 		let (cmd, rsp_payload_len) = ops::Release::create(fuse_nid, fuse_fh);
-		get_filesystem_driver()
-			.unwrap()
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		send_command(cmd, rsp_payload_len)?;
 
 		Ok(entries)
 	}
@@ -872,22 +1286,25 @@ impl ObjectInterface for FuseDirectoryHandle {
 pub(crate) struct FuseDirectory {
 	prefix: Option<String>,
 	attr: FileAttr,
+	options: MountOptions,
 }
 
 impl FuseDirectory {
-	pub fn new(prefix: Option<String>) -> Self {
-		let microseconds = arch::kernel::systemtime::now_micros();
+	pub fn new(prefix: Option<String>, options: MountOptions) -> Self {
+		let microseconds = realtime_micros();
 		let t = timespec::from_usec(microseconds as i64);
 
 		FuseDirectory {
 			prefix,
 			attr: FileAttr {
-				st_mode: AccessPermission::from_bits(0o777).unwrap() | AccessPermission::S_IFDIR,
+				st_mode: AccessPermission::from_bits(if options.read_only { 0o555 } else { 0o777 })
+					.unwrap() | AccessPermission::S_IFDIR,
 				st_atim: t,
 				st_mtim: t,
 				st_ctim: t,
 				..Default::default()
 			},
+			options,
 		}
 	}
 
@@ -930,10 +1347,7 @@ impl VfsNode for FuseDirectory {
 		// Flag 0x10000 for O_DIRECTORY might not be necessary
 		let (mut cmd, rsp_payload_len) = ops::Open::create(fuse_nid, 0x10000);
 		cmd.headers.in_header.opcode = fuse_opcode::FUSE_OPENDIR as u32;
-		let rsp = get_filesystem_driver()
-			.ok_or(io::Error::ENOSYS)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		let fuse_fh = rsp.headers.op_header.fh;
 
 		debug!("FUSE readdir: {path:#?}");
@@ -945,10 +1359,7 @@ impl VfsNode for FuseDirectory {
 		// read content of the directory
 		let (mut cmd, rsp_payload_len) = ops::Read::create(fuse_nid, fuse_fh, len, 0);
 		cmd.headers.in_header.opcode = fuse_opcode::FUSE_READDIR as u32;
-		let rsp = get_filesystem_driver()
-			.ok_or(io::Error::ENOSYS)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 
 		let len: usize = if rsp.headers.out_header.len as usize - mem::size_of::<fuse_out_header>()
 			>= len.try_into().unwrap()
@@ -991,10 +1402,7 @@ impl VfsNode for FuseDirectory {
 		}
 
 		let (cmd, rsp_payload_len) = ops::Release::create(fuse_nid, fuse_fh);
-		get_filesystem_driver()
-			.unwrap()
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		send_command(cmd, rsp_payload_len)?;
 
 		Ok(entries)
 	}
@@ -1004,25 +1412,13 @@ impl VfsNode for FuseDirectory {
 
 		debug!("FUSE stat: {path:#?}");
 
-		// Is there a better way to implement this?
-		let (cmd, rsp_payload_len) = ops::Lookup::create(path);
-		let rsp = get_filesystem_driver()
-			.unwrap()
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
-
-		if rsp.headers.out_header.error != 0 {
-			return Err(io::Error::from_i32(-rsp.headers.out_header.error).unwrap());
-		}
-
-		let entry_out = fuse_entry_out::ref_from_bytes(rsp.payload.as_ref().unwrap()).unwrap();
-		let attr = entry_out.attr;
+		let (nid, attr) = lookup_entry(path)?;
 
-		if attr.mode & S_IFMT != S_IFLNK {
-			return Ok(FileAttr::from(attr));
+		if attr.st_mode.bits() & S_IFMT != S_IFLNK {
+			return Ok(self.options.map_attr(attr));
 		}
 
-		let path = readlink(entry_out.nodeid)?;
+		let path = readlink(nid)?;
 		let mut components: Vec<&str> = path.split('/').collect();
 		self.traverse_stat(&mut components)
 	}
@@ -1032,18 +1428,7 @@ impl VfsNode for FuseDirectory {
 
 		debug!("FUSE lstat: {path:#?}");
 
-		let (cmd, rsp_payload_len) = ops::Lookup::create(path);
-		let rsp = get_filesystem_driver()
-			.unwrap()
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
-
-		if rsp.headers.out_header.error != 0 {
-			Err(io::Error::from_i32(-rsp.headers.out_header.error).unwrap())
-		} else {
-			let entry_out = fuse_entry_out::ref_from_bytes(rsp.payload.as_ref().unwrap()).unwrap();
-			Ok(FileAttr::from(entry_out.attr))
-		}
+		lookup_entry(path).map(|(_, attr)| self.options.map_attr(attr))
 	}
 
 	fn traverse_open(
@@ -1056,6 +1441,16 @@ impl VfsNode for FuseDirectory {
 
 		debug!("FUSE open: {path:#?}, {opt:?} {mode:?}");
 
+		if opt.intersects(
+			OpenOption::O_WRONLY
+				| OpenOption::O_RDWR
+				| OpenOption::O_CREAT
+				| OpenOption::O_TRUNC
+				| OpenOption::O_APPEND,
+		) {
+			self.options.check_writable()?;
+		}
+
 		if opt.contains(OpenOption::O_DIRECTORY) {
 			if opt.contains(OpenOption::O_CREAT) {
 				// See https://lwn.net/Articles/926782/
@@ -1063,28 +1458,16 @@ impl VfsNode for FuseDirectory {
 				return Err(io::Error::EINVAL);
 			}
 
-			let (cmd, rsp_payload_len) = ops::Lookup::create(path.clone());
-			let rsp = get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
-
-			if rsp.headers.out_header.error == 0 {
-				let entry_out =
-					fuse_entry_out::ref_from_bytes(rsp.payload.as_ref().unwrap()).unwrap();
-				let attr = FileAttr::from(entry_out.attr);
-				if attr.st_mode.contains(AccessPermission::S_IFDIR) {
-					let mut path = path.into_string().unwrap();
-					path.remove(0);
-					Ok(Arc::new(FuseDirectoryHandle::new(Some(path))))
-				} else {
-					Err(io::Error::ENOTDIR)
-				}
+			let (_, attr) = lookup_entry(path.clone())?;
+			if attr.st_mode.contains(AccessPermission::S_IFDIR) {
+				let mut path = path.into_string().unwrap();
+				path.remove(0);
+				Ok(Arc::new(FuseDirectoryHandle::new(Some(path))))
 			} else {
-				Err(io::Error::from_i32(-rsp.headers.out_header.error).unwrap())
+				Err(io::Error::ENOTDIR)
 			}
 		} else {
-			let file = FuseFileHandle::new();
+			let file = FuseFileHandle::new(self.options);
 
 			// 1.FUSE_INIT to create session
 			// Already done
@@ -1093,12 +1476,16 @@ impl VfsNode for FuseDirectory {
 			// Differentiate between opening and creating new file, since fuse does not support O_CREAT on open.
 			if opt.contains(OpenOption::O_CREAT) {
 				// Create file (opens implicitly, returns results from both lookup and open calls)
-				let (cmd, rsp_payload_len) =
-					ops::Create::create(path, opt.bits().try_into().unwrap(), mode.bits());
-				let rsp = get_filesystem_driver()
-					.ok_or(io::Error::ENOSYS)?
+				DENTRY_CACHE
 					.lock()
-					.send_command(cmd, rsp_payload_len)?;
+					.invalidate(FUSE_ROOT_ID, path.to_str().unwrap());
+				let (mut cmd, rsp_payload_len) = ops::Create::create(
+					path,
+					self.options.open_flags(opt.bits().try_into().unwrap()),
+					mode.bits(),
+				);
+				self.options.set_owner(&mut cmd.headers.in_header);
+				let rsp = send_command(cmd, rsp_payload_len)?;
 
 				let inner = rsp.headers.op_header;
 				file_guard.fuse_nid = Some(inner.entry.nodeid);
@@ -1113,13 +1500,18 @@ impl VfsNode for FuseDirectory {
 				}
 
 				// 3.FUSE_OPEN(nodeid, O_RDONLY) -> fh
-				let (cmd, rsp_payload_len) =
-					ops::Open::create(file_guard.fuse_nid.unwrap(), opt.bits().try_into().unwrap());
-				let rsp = get_filesystem_driver()
-					.ok_or(io::Error::ENOSYS)?
-					.lock()
-					.send_command(cmd, rsp_payload_len)?;
+				let (cmd, rsp_payload_len) = ops::Open::create(
+					file_guard.fuse_nid.unwrap(),
+					self.options.open_flags(opt.bits().try_into().unwrap()),
+				);
+				let rsp = send_command(cmd, rsp_payload_len)?;
 				file_guard.fuse_fh = Some(rsp.headers.op_header.fh);
+
+				if opt.contains(OpenOption::O_TRUNC) {
+					DENTRY_CACHE
+						.lock()
+						.invalidate_attr(file_guard.fuse_nid.unwrap());
+				}
 			}
 
 			drop(file_guard);
@@ -1129,39 +1521,64 @@ impl VfsNode for FuseDirectory {
 	}
 
 	fn traverse_unlink(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		self.options.check_writable()?;
 		let path = self.traversal_path(components);
+		DENTRY_CACHE
+			.lock()
+			.invalidate(FUSE_ROOT_ID, path.to_str().unwrap());
 
 		let (cmd, rsp_payload_len) = ops::Unlink::create(path);
-		let rsp = get_filesystem_driver()
-			.ok_or(io::Error::ENOSYS)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		trace!("unlink answer {:?}", rsp);
 
 		Ok(())
 	}
 
 	fn traverse_rmdir(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		self.options.check_writable()?;
 		let path = self.traversal_path(components);
+		DENTRY_CACHE
+			.lock()
+			.invalidate(FUSE_ROOT_ID, path.to_str().unwrap());
 
 		let (cmd, rsp_payload_len) = ops::Rmdir::create(path);
-		let rsp = get_filesystem_driver()
-			.ok_or(io::Error::ENOSYS)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		trace!("rmdir answer {:?}", rsp);
 
 		Ok(())
 	}
 
+	fn traverse_rename(
+		&self,
+		components: &mut Vec<&str>,
+		new_components: &mut Vec<&str>,
+	) -> io::Result<()> {
+		self.options.check_writable()?;
+		let path = self.traversal_path(components);
+		let new_path = self.traversal_path(new_components);
+		// The cached entries below both paths refer to the old names.
+		{
+			let mut dcache = DENTRY_CACHE.lock();
+			dcache.invalidate(FUSE_ROOT_ID, path.to_str().unwrap());
+			dcache.invalidate(FUSE_ROOT_ID, new_path.to_str().unwrap());
+		}
+
+		let (cmd, rsp_payload_len) = ops::Rename::create(path, new_path);
+		let rsp = send_command(cmd, rsp_payload_len)?;
+		if rsp.headers.out_header.error == 0 {
+			Ok(())
+		} else {
+			Err(num::FromPrimitive::from_i32(-rsp.headers.out_header.error).unwrap())
+		}
+	}
+
 	fn traverse_mkdir(&self, components: &mut Vec<&str>, mode: AccessPermission) -> io::Result<()> {
+		self.options.check_writable()?;
 		let path = self.traversal_path(components);
-		let (cmd, rsp_payload_len) = ops::Mkdir::create(path, mode.bits());
+		let (mut cmd, rsp_payload_len) = ops::Mkdir::create(path, mode.bits());
+		self.options.set_owner(&mut cmd.headers.in_header);
 
-		let rsp = get_filesystem_driver()
-			.ok_or(io::Error::ENOSYS)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		if rsp.headers.out_header.error == 0 {
 			Ok(())
 		} else {
@@ -1170,26 +1587,42 @@ impl VfsNode for FuseDirectory {
 	}
 }
 
+/// Creates the root directory of a new mount of the device with the tag `tag`.
+///
+/// `options` are the options of the mount, see [`MountOptions::new`].
+pub(crate) fn new_mount(
+	tag: &str,
+	read_only: bool,
+	options: &str,
+) -> io::Result<Box<dyn VfsNode + Send + Sync>> {
+	let driver = get_filesystem_driver().ok_or(io::Error::ENODEV)?;
+	if driver.lock().get_mount_point() != tag {
+		return Err(io::Error::ENODEV);
+	}
+
+	let mut options = MountOptions::new(tag, options);
+	options.read_only |= read_only;
+	Ok(Box::new(FuseDirectory::new(None, options)))
+}
+
 pub(crate) fn init() {
 	debug!("Try to initialize fuse filesystem");
 
 	if let Some(driver) = get_filesystem_driver() {
 		let (cmd, rsp_payload_len) = ops::Init::create();
-		let rsp = driver.lock().send_command(cmd, rsp_payload_len).unwrap();
+		let rsp = send_command(cmd, rsp_payload_len).unwrap();
 		trace!("fuse init answer: {:?}", rsp);
 
 		let mount_point = driver.lock().get_mount_point();
+		let tag = mount_point.clone();
+		let options = MountOptions::new(&tag, env::mount_options(&tag).unwrap_or_default());
 		if mount_point == "/" {
 			let fuse_nid = lookup(c"/".to_owned()).unwrap();
 			// Opendir
 			// Flag 0x10000 for O_DIRECTORY might not be necessary
 			let (mut cmd, rsp_payload_len) = ops::Open::create(fuse_nid, 0x10000);
 			cmd.headers.in_header.opcode = fuse_opcode::FUSE_OPENDIR as u32;
-			let rsp = get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)
-				.unwrap();
+			let rsp = send_command(cmd, rsp_payload_len).unwrap();
 			let fuse_fh = rsp.headers.op_header.fh;
 
 			// Linux seems to allocate a single page to store the dirfile
@@ -1199,11 +1632,7 @@ pub(crate) fn init() {
 			// read content of the directory
 			let (mut cmd, rsp_payload_len) = ops::Read::create(fuse_nid, fuse_fh, len, 0);
 			cmd.headers.in_header.opcode = fuse_opcode::FUSE_READDIR as u32;
-			let rsp = get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)
-				.unwrap();
+			let rsp = send_command(cmd, rsp_payload_len).unwrap();
 
 			let len: usize = if rsp.headers.out_header.len as usize
 				- mem::size_of::<fuse_out_header>()
@@ -1247,29 +1676,23 @@ pub(crate) fn init() {
 			}
 
 			let (cmd, rsp_payload_len) = ops::Release::create(fuse_nid, fuse_fh);
-			get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)
-				.unwrap();
+			send_command(cmd, rsp_payload_len).unwrap();
 
 			// remove predefined directories
 			entries.retain(|x| x != ".");
 			entries.retain(|x| x != "..");
 			entries.retain(|x| x != "tmp");
 			entries.retain(|x| x != "proc");
+			entries.retain(|x| x != "dev");
+			entries.retain(|x| x != "sys");
 			warn!(
-				"Fuse don't mount the host directories 'tmp' and 'proc' into the guest file system!"
+				"Fuse don't mount the host directories 'tmp', 'proc', 'dev' and 'sys' into the guest file system!"
 			);
 
 			for i in entries {
 				let i_cstr = CString::new(i.clone()).unwrap();
 				let (cmd, rsp_payload_len) = ops::Lookup::create(i_cstr);
-				let rsp = get_filesystem_driver()
-					.unwrap()
-					.lock()
-					.send_command(cmd, rsp_payload_len)
-					.unwrap();
+				let rsp = send_command(cmd, rsp_payload_len).unwrap();
 
 				assert_eq!(rsp.headers.out_header.error, 0);
 				let entry_out =
@@ -1284,7 +1707,9 @@ pub(crate) fn init() {
 						.unwrap()
 						.mount(
 							&("/".to_owned() + i.as_str()),
-							Box::new(FuseDirectory::new(Some(i))),
+							"virtiofs",
+							&tag,
+							Box::new(FuseDirectory::new(Some(i), options)),
 						)
 						.expect("Mount failed. Invalid mount_point?");
 				} else {
@@ -1302,7 +1727,12 @@ pub(crate) fn init() {
 			fs::FILESYSTEM
 				.get()
 				.unwrap()
-				.mount(mount_point.as_str(), Box::new(FuseDirectory::new(None)))
+				.mount(
+					mount_point.as_str(),
+					"virtiofs",
+					&tag,
+					Box::new(FuseDirectory::new(None, options)),
+				)
 				.expect("Mount failed. Invalid mount_point?");
 		}
 	}
@@ -0,0 +1,482 @@
+//! Initial RAM disk
+//!
+//! The boot loader may pass a cpio (`newc` format) or tar (`ustar` format) archive
+//! to the kernel, either as Multiboot module or via the `linux,initrd-start` and
+//! `linux,initrd-end` properties of the `/chosen` device tree node. Before the
+//! application starts, the kernel unpacks the archive into the RAM file system.
+//! Afterwards, the memory of the archive is released.
+//!
+//! With the features `initrd-gzip` and `initrd-zstd`, the archive may be
+//! compressed by gzip and zstd, respectively, which reduces the size of the
+//! images, which have to be transferred to the machine. Likewise, an
+//! executable in the archive, e.g., the application ELF, may be compressed.
+//! It is stored as `<name>.gz` or `<name>.zst` and decompressed to `<name>`.
+
+use alloc::borrow::Cow;
+use alloc::format;
+use core::ops::Range;
+use core::slice;
+
+use align_address::Align;
+use free_list::PageRange;
+use hermit_sync::OnceCell;
+use memory_addresses::PhysAddr;
+
+use crate::arch::mm::paging::{BasePageSize, PageSize};
+use crate::arch::mm::physicalmem;
+use crate::fd::{self, AccessPermission, OpenOption};
+use crate::{env, fs, io, mm};
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFREG: u32 = 0o100_000;
+
+/// Size of a tar block
+const TAR_BLOCK_SIZE: usize = 512;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Maximum number of memory regions, which may contain the archive
+const MAX_CANDIDATES: usize = 4;
+
+/// Memory region, which may contain the archive
+#[derive(Debug)]
+struct Candidate {
+	range: Range<u64>,
+	/// Pages, which have been taken from the list of free memory and have to
+	/// be released after use
+	reserved: heapless::Vec<PageRange, 4>,
+}
+
+impl Candidate {
+	/// Returns the page-aligned physical memory, which contains the region.
+	fn pages(&self) -> (PhysAddr, usize) {
+		let start = PhysAddr::new(self.range.start).align_down(BasePageSize::SIZE);
+		let end = PhysAddr::new(self.range.end).align_up(BasePageSize::SIZE);
+		(start, (end - start) as usize)
+	}
+
+	/// Returns the offset of the region within its pages.
+	fn offset(&self) -> usize {
+		(self.range.start % BasePageSize::SIZE) as usize
+	}
+
+	/// Checks whether the region contains an archive.
+	fn contains_archive(&self) -> bool {
+		let page = PhysAddr::new(self.range.start).align_down(BasePageSize::SIZE);
+		let len = usize::try_from(self.range.end - self.range.start)
+			.unwrap()
+			.min(BasePageSize::SIZE as usize - self.offset());
+
+		let virt_addr = mm::map(page, BasePageSize::SIZE as usize, false, true, false);
+		let header =
+			unsafe { slice::from_raw_parts(virt_addr.as_ptr::<u8>().add(self.offset()), len) };
+		let result = is_archive(header);
+		mm::unmap(virt_addr, BasePageSize::SIZE as usize);
+
+		result
+	}
+
+	/// Returns the reserved pages to the list of free memory.
+	fn release(&self) {
+		for part in &self.reserved {
+			physicalmem::deallocate(
+				PhysAddr::new(part.start().try_into().unwrap()),
+				part.len().get(),
+			);
+		}
+	}
+}
+
+static CANDIDATES: OnceCell<heapless::Vec<Candidate, MAX_CANDIDATES>> = OnceCell::new();
+
+fn is_archive(header: &[u8]) -> bool {
+	header.starts_with(b"070701")
+		|| header.starts_with(b"070702")
+		|| header.get(257..262) == Some(b"ustar")
+		|| header.starts_with(GZIP_MAGIC)
+		|| header.starts_with(ZSTD_MAGIC)
+}
+
+/// Protects the memory regions, which may contain the initial RAM disk, from
+/// being allocated.
+///
+/// This has to be called before the frame allocator hands out memory. As the
+/// regions cannot be inspected before the page tables are initialized, all
+/// candidates are reserved. [`unpack`] releases them.
+pub(crate) fn reserve() {
+	let mut candidates = heapless::Vec::new();
+	let mut add = |range: Range<u64>| {
+		if range.is_empty() {
+			return;
+		}
+
+		let mut candidate = Candidate {
+			range,
+			reserved: heapless::Vec::new(),
+		};
+		let (start, size) = candidate.pages();
+		candidate.reserved = physicalmem::reserve_free(start, size);
+		if let Err(candidate) = candidates.push(candidate) {
+			warn!(
+				"Ignoring boot module at {:#x}..{:#x}",
+				candidate.range.start, candidate.range.end
+			);
+			candidate.release();
+		}
+	};
+
+	if let Some(range) = env::fdt_initrd() {
+		add(range);
+	}
+
+	// The first Multiboot module is typically the application itself.
+	#[cfg(target_arch = "x86_64")]
+	for range in (0..).map_while(crate::arch::kernel::get_boot_module) {
+		add(range);
+	}
+
+	CANDIDATES.set(candidates).unwrap();
+}
+
+/// Strips leading `/` and `./` from `name`.
+fn normalize(mut name: &str) -> &str {
+	loop {
+		if let Some(rest) = name.strip_prefix("./") {
+			name = rest;
+		} else if let Some(rest) = name.strip_prefix('/') {
+			name = rest;
+		} else {
+			break name.trim_end_matches('/');
+		}
+	}
+}
+
+fn create_parents(path: &str) {
+	let mut end = 0;
+	while let Some(pos) = path[end + 1..].find('/') {
+		end += pos + 1;
+		// The directory may already exist.
+		let _ = fs::create_dir(&path[..end], AccessPermission::from_bits(0o755).unwrap());
+	}
+}
+
+fn create_entry(name: &str, mode: u32, data: &[u8]) -> io::Result<()> {
+	let name = normalize(name);
+	if name.is_empty() || name == "." {
+		return Ok(());
+	}
+
+	let path = format!("/{name}");
+	let permissions = AccessPermission::from_bits_truncate(mode & 0o777);
+	create_parents(&path);
+
+	match mode & S_IFMT {
+		S_IFDIR => {
+			debug!("Create directory {path}");
+			// The directory may already exist.
+			let _ = fs::create_dir(&path, permissions);
+			Ok(())
+		}
+		S_IFREG => {
+			let (path, data) = decompress_executable(&path, mode, data)?;
+			debug!("Create file {path} ({} bytes)", data.len());
+			let fd = fs::open(
+				path,
+				OpenOption::O_CREAT | OpenOption::O_TRUNC | OpenOption::O_WRONLY,
+				permissions,
+			)?;
+			let mut written = 0;
+			let result = loop {
+				if written == data.len() {
+					break Ok(());
+				}
+				match fd::write(fd, &data[written..]) {
+					Ok(0) => break Err(io::Error::EIO),
+					Ok(len) => written += len,
+					Err(err) => break Err(err),
+				}
+			};
+			fd::remove_object(fd)?;
+			result
+		}
+		_ => {
+			warn!("Skip {path}, which is neither a regular file nor a directory");
+			Ok(())
+		}
+	}
+}
+
+fn unpack_cpio(mut archive: &[u8]) -> io::Result<()> {
+	const HEADER_LEN: usize = 110;
+
+	loop {
+		let header = archive.get(..HEADER_LEN).ok_or(io::Error::EINVAL)?;
+		if !header.starts_with(b"070701") && !header.starts_with(b"070702") {
+			return Err(io::Error::EINVAL);
+		}
+
+		// all fields consist of 8 hexadecimal digits and follow the magic number
+		let field = |index: usize| {
+			let start = 6 + 8 * index;
+			core::str::from_utf8(&header[start..start + 8])
+				.ok()
+				.and_then(|digits| usize::from_str_radix(digits, 16).ok())
+				.ok_or(io::Error::EINVAL)
+		};
+		let mode = field(1)?;
+		let filesize = field(6)?;
+		let namesize = field(11)?;
+
+		// `namesize` includes the terminating null byte
+		let name_end = HEADER_LEN + namesize;
+		let name = archive
+			.get(HEADER_LEN..name_end.saturating_sub(1))
+			.and_then(|name| core::str::from_utf8(name).ok())
+			.ok_or(io::Error::EINVAL)?;
+		if name == "TRAILER!!!" {
+			return Ok(());
+		}
+
+		let data_start = name_end.align_up(4);
+		let data_end = data_start + filesize;
+		let data = archive.get(data_start..data_end).ok_or(io::Error::EINVAL)?;
+		create_entry(name, mode.try_into().unwrap(), data)?;
+
+		archive = archive.get(data_end.align_up(4)..).unwrap_or_default();
+	}
+}
+
+fn unpack_tar(mut archive: &[u8]) -> io::Result<()> {
+	fn string(field: &[u8]) -> io::Result<&str> {
+		let len = field.iter().position(|&c| c == 0).unwrap_or(field.len());
+		core::str::from_utf8(&field[..len]).map_err(|_| io::Error::EINVAL)
+	}
+
+	fn octal(field: &[u8]) -> io::Result<usize> {
+		let digits = string(field)?.trim_matches(' ');
+		if digits.is_empty() {
+			return Ok(0);
+		}
+		usize::from_str_radix(digits, 8).map_err(|_| io::Error::EINVAL)
+	}
+
+	while let Some(header) = archive.get(..TAR_BLOCK_SIZE) {
+		// the archive ends with blocks of zeros
+		if header.iter().all(|&c| c == 0) {
+			break;
+		}
+		if &header[257..262] != b"ustar" {
+			return Err(io::Error::EINVAL);
+		}
+
+		let name = string(&header[..100])?;
+		let prefix = string(&header[345..500])?;
+		let mode = u32::try_from(octal(&header[100..108])?).map_err(|_| io::Error::EINVAL)?;
+		let size = octal(&header[124..136])?;
+		let data = archive
+			.get(TAR_BLOCK_SIZE..TAR_BLOCK_SIZE + size)
+			.ok_or(io::Error::EINVAL)?;
+
+		let mode = match header[156] {
+			b'0' | b'\0' => S_IFREG | (mode & !S_IFMT),
+			b'5' => S_IFDIR | (mode & !S_IFMT),
+			_ => mode & !S_IFMT,
+		};
+		if prefix.is_empty() {
+			create_entry(name, mode, data)?;
+		} else {
+			create_entry(&format!("{prefix}/{name}"), mode, data)?;
+		}
+
+		archive = archive
+			.get(TAR_BLOCK_SIZE + size.align_up(TAR_BLOCK_SIZE)..)
+			.unwrap_or_default();
+	}
+
+	Ok(())
+}
+
+/// Decompresses the gzip member `data` (RFC 1952).
+#[cfg(feature = "initrd-gzip")]
+fn gunzip(data: &[u8]) -> io::Result<alloc::vec::Vec<u8>> {
+	const FHCRC: u8 = 1 << 1;
+	const FEXTRA: u8 = 1 << 2;
+	const FNAME: u8 = 1 << 3;
+	const FCOMMENT: u8 = 1 << 4;
+	const HEADER_LEN: usize = 10;
+	const TRAILER_LEN: usize = 8;
+
+	fn skip_string(data: &[u8], pos: usize) -> io::Result<usize> {
+		let len = data
+			.get(pos..)
+			.and_then(|rest| rest.iter().position(|&c| c == 0))
+			.ok_or(io::Error::EINVAL)?;
+		Ok(pos + len + 1)
+	}
+
+	let header = data.get(..HEADER_LEN).ok_or(io::Error::EINVAL)?;
+	// only deflate is defined as compression method
+	if !header.starts_with(GZIP_MAGIC) || header[2] != 8 {
+		return Err(io::Error::EINVAL);
+	}
+
+	let flags = header[3];
+	let mut pos = HEADER_LEN;
+	if flags & FEXTRA != 0 {
+		let len = data.get(pos..pos + 2).ok_or(io::Error::EINVAL)?;
+		pos += 2 + usize::from(u16::from_le_bytes([len[0], len[1]]));
+	}
+	if flags & FNAME != 0 {
+		pos = skip_string(data, pos)?;
+	}
+	if flags & FCOMMENT != 0 {
+		pos = skip_string(data, pos)?;
+	}
+	if flags & FHCRC != 0 {
+		pos += 2;
+	}
+
+	let stream = data
+		.get(pos..data.len().saturating_sub(TRAILER_LEN))
+		.ok_or(io::Error::EINVAL)?;
+	let trailer = &data[data.len() - TRAILER_LEN..];
+	let isize = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+
+	let decompressed =
+		miniz_oxide::inflate::decompress_to_vec(stream).map_err(|_| io::Error::EINVAL)?;
+	// `ISIZE` is the size of the uncompressed data modulo 2^32
+	if decompressed.len() as u32 != isize {
+		return Err(io::Error::EINVAL);
+	}
+
+	Ok(decompressed)
+}
+
+/// Decompresses the zstd frames `data` (RFC 8878).
+#[cfg(feature = "initrd-zstd")]
+fn unzstd(data: &[u8]) -> io::Result<alloc::vec::Vec<u8>> {
+	use ruzstd::decoding::StreamingDecoder;
+	use ruzstd::io::Read;
+
+	let mut decompressed = alloc::vec::Vec::new();
+	let mut buf = [0; 4096];
+	let mut data = data;
+	while !data.is_empty() {
+		let mut decoder = StreamingDecoder::new(&mut data).map_err(|_| io::Error::EINVAL)?;
+		loop {
+			match decoder.read(&mut buf) {
+				Ok(0) => break,
+				Ok(len) => decompressed.extend_from_slice(&buf[..len]),
+				Err(_) => return Err(io::Error::EINVAL),
+			}
+		}
+	}
+
+	Ok(decompressed)
+}
+
+/// Returns the uncompressed contents of `data`, which may be compressed by gzip or zstd.
+fn decompress(data: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+	if data.starts_with(GZIP_MAGIC) {
+		#[cfg(feature = "initrd-gzip")]
+		{
+			let decompressed = gunzip(data)?;
+			debug!(
+				"Decompressed gzip data ({} to {} bytes)",
+				data.len(),
+				decompressed.len()
+			);
+			return Ok(Cow::Owned(decompressed));
+		}
+
+		#[cfg(not(feature = "initrd-gzip"))]
+		{
+			error!("Data is compressed by gzip, which requires the feature initrd-gzip");
+			return Err(io::Error::EINVAL);
+		}
+	}
+
+	if data.starts_with(ZSTD_MAGIC) {
+		#[cfg(feature = "initrd-zstd")]
+		{
+			let decompressed = unzstd(data)?;
+			debug!(
+				"Decompressed zstd data ({} to {} bytes)",
+				data.len(),
+				decompressed.len()
+			);
+			return Ok(Cow::Owned(decompressed));
+		}
+
+		#[cfg(not(feature = "initrd-zstd"))]
+		{
+			error!("Data is compressed by zstd, which requires the feature initrd-zstd");
+			return Err(io::Error::EINVAL);
+		}
+	}
+
+	Ok(Cow::Borrowed(data))
+}
+
+/// Returns the path and the contents of the regular file `path`.
+///
+/// An executable, e.g., the application ELF, which is stored as `<path>.gz`
+/// or `<path>.zst`, is decompressed to `<path>`.
+fn decompress_executable<'a>(
+	path: &'a str,
+	mode: u32,
+	data: &'a [u8],
+) -> io::Result<(&'a str, Cow<'a, [u8]>)> {
+	let stripped = path
+		.strip_suffix(".gz")
+		.filter(|_| data.starts_with(GZIP_MAGIC))
+		.or_else(|| {
+			path.strip_suffix(".zst")
+				.filter(|_| data.starts_with(ZSTD_MAGIC))
+		});
+	match stripped {
+		Some(stripped) if mode & 0o111 != 0 => Ok((stripped, decompress(data)?)),
+		_ => Ok((path, Cow::Borrowed(data))),
+	}
+}
+
+/// Unpacks the initial RAM disk into the RAM file system and releases its memory.
+pub(crate) fn unpack() {
+	let Some(candidates) = CANDIDATES.get() else {
+		return;
+	};
+
+	if let Some(initrd) = candidates
+		.iter()
+		.find(|candidate| candidate.contains_archive())
+	{
+		let Range { start, end } = initrd.range;
+		info!("Found initial RAM disk at {start:#x}..{end:#x}");
+
+		let (pages, size) = initrd.pages();
+		let len = (end - start) as usize;
+		let virt_addr = mm::map(pages, size, false, true, false);
+		let archive =
+			unsafe { slice::from_raw_parts(virt_addr.as_ptr::<u8>().add(initrd.offset()), len) };
+
+		let result = decompress(archive).and_then(|archive| {
+			if archive.starts_with(b"07070") {
+				unpack_cpio(&archive)
+			} else {
+				unpack_tar(&archive)
+			}
+		});
+		match result {
+			Ok(()) => info!("Unpacked initial RAM disk ({len} bytes)"),
+			Err(err) => error!("Unable to unpack initial RAM disk: {err:?}"),
+		}
+
+		mm::unmap(virt_addr, size);
+	}
+
+	for candidate in candidates {
+		candidate.release();
+	}
+}
@@ -0,0 +1,249 @@
+//! Message channel over inter-VM shared memory
+//!
+//! The shared memory of an ivshmem device is provided as character device
+//! `/dev/ivshmem0`, which exchanges messages with a peer instance. The memory
+//! is split into two halves with a [`MessageRing`] for each direction. The
+//! instance with an even position sends through the first half, its peer
+//! with the next odd position through the second half. Each write sends one
+//! message, each read receives one message and truncates it to the buffer.
+//!
+//! With `ivshmem-doorbell`, the position is assigned by the ivshmem server
+//! and the instances notify each other through the doorbell. `ivshmem-plain`
+//! has no position, so that it is given by `HERMIT_IVSHMEM_POSITION` and
+//! waiting for the peer is done by polling.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future;
+use core::task::{Context, Poll};
+
+use async_lock::Mutex;
+use async_trait::async_trait;
+
+use crate::drivers::ivshmem::ring::MessageRing;
+use crate::drivers::pci::get_ivshmem_driver;
+use crate::fd::{AccessPermission, ObjectInterface, PollEvent};
+use crate::fs::{FileAttr, NodeKind, VfsNode};
+use crate::io;
+
+const READABLE: PollEvent = PollEvent::POLLIN.union(PollEvent::POLLRDNORM);
+const WRITABLE: PollEvent = PollEvent::POLLOUT.union(PollEvent::POLLWRNORM);
+
+/// Interrupt vector, which is raised at the peer
+const DOORBELL_VECTOR: u16 = 0;
+
+/// Both directions of the message exchange with the peer
+#[derive(Debug)]
+struct Channel {
+	tx: MessageRing,
+	rx: MessageRing,
+	/// Serializes the writers, because a ring has a single producer
+	tx_lock: Mutex<()>,
+	/// Serializes the readers, because a ring has a single consumer
+	rx_lock: Mutex<()>,
+	/// Position of the peer
+	peer: u16,
+	attr: FileAttr,
+}
+
+impl Channel {
+	/// Raises an interrupt at the peer, after a message has been sent or received.
+	fn notify_peer(&self) {
+		if let Some(driver) = get_ivshmem_driver() {
+			driver.lock().ring_doorbell(self.peer, DOORBELL_VECTOR);
+		}
+	}
+
+	/// Polls `f` until it is ready, registering the waker of the direction `rx`.
+	///
+	/// Without doorbell, the task is woken again immediately.
+	fn poll_with<T>(
+		&self,
+		cx: &mut Context<'_>,
+		rx: bool,
+		mut f: impl FnMut() -> Poll<T>,
+	) -> Poll<T> {
+		if let Poll::Ready(t) = f() {
+			return Poll::Ready(t);
+		}
+
+		let Some(driver) = get_ivshmem_driver() else {
+			return Poll::Pending;
+		};
+		let mut guard = driver.lock();
+		if guard.position().is_none() {
+			cx.waker().wake_by_ref();
+			return Poll::Pending;
+		}
+		if rx {
+			guard.register_rx_waker(cx.waker());
+		} else {
+			guard.register_tx_waker(cx.waker());
+		}
+		drop(guard);
+
+		// check again, because the peer may have rung before the registration
+		f()
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for Channel {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		let available = || {
+			let mut available = PollEvent::empty();
+			if !self.rx.is_empty() {
+				available.insert(READABLE);
+			}
+			if self.tx.can_send(0) {
+				available.insert(WRITABLE);
+			}
+			available
+		};
+
+		future::poll_fn(|cx| {
+			let rx = event.intersects(READABLE);
+			self.poll_with(cx, rx, || {
+				let ret = event & available();
+				if ret.is_empty() {
+					Poll::Pending
+				} else {
+					Poll::Ready(Ok(ret))
+				}
+			})
+		})
+		.await
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let _guard = self.rx_lock.lock().await;
+		let len = future::poll_fn(|cx| {
+			self.poll_with(cx, true, || match self.rx.try_recv(buf) {
+				Err(io::Error::EAGAIN) => Poll::Pending,
+				result => Poll::Ready(result),
+			})
+		})
+		.await?;
+
+		self.notify_peer();
+		Ok(len)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let _guard = self.tx_lock.lock().await;
+		future::poll_fn(|cx| {
+			self.poll_with(cx, false, || match self.tx.try_send(buf) {
+				Err(io::Error::EAGAIN) => Poll::Pending,
+				result => Poll::Ready(result),
+			})
+		})
+		.await?;
+
+		self.notify_peer();
+		Ok(buf.len())
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		Ok(self.attr)
+	}
+}
+
+/// Character device, which provides the channel to the peer
+#[derive(Debug)]
+struct IvshmemDevice {
+	channel: Arc<Channel>,
+}
+
+impl VfsNode for IvshmemDevice {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::File
+	}
+
+	fn get_object(&self) -> io::Result<Arc<dyn ObjectInterface>> {
+		Ok(self.channel.clone())
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		Ok(self.channel.attr)
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(io::Error::EBADF)
+		}
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(io::Error::EBADF)
+		}
+	}
+}
+
+pub(crate) fn init() {
+	let Some(driver) = get_ivshmem_driver() else {
+		return;
+	};
+	let guard = driver.lock();
+	let region = guard.region();
+	let position = guard.position().or_else(|| {
+		hermit_var!("HERMIT_IVSHMEM_POSITION").and_then(|position| position.parse().ok())
+	});
+	drop(guard);
+
+	let Some(position) = position else {
+		warn!("The position of the shared memory instance is unknown, set HERMIT_IVSHMEM_POSITION");
+		return;
+	};
+
+	let half = region.size / 2;
+	let first = region.virt_addr;
+	let second = region.virt_addr + half;
+	let (tx, rx) = if position % 2 == 0 {
+		(first, second)
+	} else {
+		(second, first)
+	};
+	// The rings are the only users of the shared memory.
+	let rings = unsafe { (MessageRing::new(tx, half), MessageRing::new(rx, half)) };
+	let (Some(tx), Some(rx)) = rings else {
+		warn!("The shared memory of {} bytes is too small", region.size);
+		return;
+	};
+
+	let channel = Channel {
+		tx,
+		rx,
+		tx_lock: Mutex::new(()),
+		rx_lock: Mutex::new(()),
+		peer: position ^ 1,
+		attr: FileAttr {
+			st_mode: AccessPermission::S_IFCHR | AccessPermission::from_bits(0o660).unwrap(),
+			st_nlink: 1,
+			..Default::default()
+		},
+	};
+	let max_message_size = channel.tx.max_message_size();
+
+	super::FILESYSTEM
+		.get()
+		.unwrap()
+		.mount(
+			"/dev/ivshmem0",
+			"ivshmem",
+			"ivshmem0",
+			Box::new(IvshmemDevice {
+				channel: Arc::new(channel),
+			}),
+		)
+		.expect("Unable to mount /dev/ivshmem0");
+	info!(
+		"Shared memory channel to instance {} with messages up to {max_message_size} bytes is available at /dev/ivshmem0",
+		position ^ 1
+	);
+}
@@ -14,15 +14,18 @@ use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::fmt;
 
 use async_lock::{Mutex, RwLock};
 use async_trait::async_trait;
 
 use crate::executor::block_on;
-use crate::fd::{AccessPermission, ObjectInterface, OpenOption, PollEvent};
+use crate::fd::{
+	AccessPermission, ObjectInterface, OpenOption, PollEvent, gather, scatter, total_len,
+};
 use crate::fs::{DirectoryEntry, FileAttr, NodeKind, SeekWhence, VfsNode};
-use crate::time::timespec;
-use crate::{arch, io};
+use crate::io;
+use crate::time::{realtime_micros, timespec};
 
 #[derive(Debug)]
 pub(crate) struct RomFileInner {
@@ -59,9 +62,17 @@ impl ObjectInterface for RomFileInterface {
 		Ok(ret)
 	}
 
+	async fn fsync(&self) -> io::Result<()> {
+		Ok(())
+	}
+
 	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		self.readv(&mut [buf]).await
+	}
+
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
 		{
-			let microseconds = arch::kernel::systemtime::now_micros();
+			let microseconds = realtime_micros();
 			let t = timespec::from_usec(microseconds as i64);
 			let mut guard = self.inner.write().await;
 			guard.attr.st_atim = t;
@@ -75,13 +86,7 @@ impl ObjectInterface for RomFileInterface {
 			return Ok(0);
 		}
 
-		let len = if vec.len() - pos < buf.len() {
-			vec.len() - pos
-		} else {
-			buf.len()
-		};
-
-		buf[0..len].clone_from_slice(&vec[pos..pos + len]);
+		let len = scatter(&vec[pos..], bufs, 0);
 		*pos_guard = pos + len;
 
 		Ok(len)
@@ -165,43 +170,46 @@ impl ObjectInterface for RamFileInterface {
 		Ok(event & available)
 	}
 
+	async fn fsync(&self) -> io::Result<()> {
+		Ok(())
+	}
+
 	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
-		{
-			let microseconds = arch::kernel::systemtime::now_micros();
-			let t = timespec::from_usec(microseconds as i64);
-			let mut guard = self.inner.write().await;
-			guard.attr.st_atim = t;
-		}
+		self.readv(&mut [buf]).await
+	}
 
-		let guard = self.inner.read().await;
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		let microseconds = realtime_micros();
+		let t = timespec::from_usec(microseconds as i64);
+		let mut guard = self.inner.write().await;
 		let mut pos_guard = self.pos.lock().await;
 		let pos = *pos_guard;
 
+		guard.attr.st_atim = t;
 		if pos >= guard.data.len() {
 			return Ok(0);
 		}
 
-		let len = if guard.data.len() - pos < buf.len() {
-			guard.data.len() - pos
-		} else {
-			buf.len()
-		};
-
-		buf[0..len].clone_from_slice(&guard.data[pos..pos + len]);
+		let len = scatter(&guard.data[pos..], bufs, 0);
 		*pos_guard = pos + len;
 
 		Ok(len)
 	}
 
 	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
-		let microseconds = arch::kernel::systemtime::now_micros();
+		self.writev(&[buf]).await
+	}
+
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		let microseconds = realtime_micros();
 		let t = timespec::from_usec(microseconds as i64);
 		let mut guard = self.inner.write().await;
 		let mut pos_guard = self.pos.lock().await;
 		let pos = *pos_guard;
+		let len = total_len(bufs);
 
-		if pos + buf.len() > guard.data.len() {
-			guard.data.resize(pos + buf.len(), 0);
+		if pos + len > guard.data.len() {
+			guard.data.resize(pos + len, 0);
 			guard.attr.st_size = guard.data.len().try_into().unwrap();
 		}
 
@@ -209,10 +217,10 @@ impl ObjectInterface for RamFileInterface {
 		guard.attr.st_mtim = t;
 		guard.attr.st_ctim = t;
 
-		guard.data[pos..pos + buf.len()].clone_from_slice(buf);
-		*pos_guard = pos + buf.len();
+		gather(bufs, 0, &mut guard.data[pos..pos + len]);
+		*pos_guard = pos + len;
 
-		Ok(buf.len())
+		Ok(len)
 	}
 
 	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
@@ -293,7 +301,7 @@ impl VfsNode for RomFile {
 
 impl RomFile {
 	pub fn new(data: &'static [u8], mode: AccessPermission) -> Self {
-		let microseconds = arch::kernel::systemtime::now_micros();
+		let microseconds = realtime_micros();
 		let t = timespec::from_usec(microseconds as i64);
 		let attr = FileAttr {
 			st_size: data.len() as u64,
@@ -310,6 +318,127 @@ impl RomFile {
 	}
 }
 
+#[derive(Debug)]
+struct GenFileInterface {
+	/// Position within the file
+	pos: Mutex<usize>,
+	/// Snapshot of the file content, which was generated when the file was opened
+	data: Vec<u8>,
+}
+
+#[async_trait]
+impl ObjectInterface for GenFileInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		let ret = if *self.pos.lock().await < self.data.len() {
+			event.intersection(PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND)
+		} else {
+			PollEvent::empty()
+		};
+
+		Ok(ret)
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		self.readv(&mut [buf]).await
+	}
+
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		let mut pos_guard = self.pos.lock().await;
+		let pos = (*pos_guard).min(self.data.len());
+		let len = scatter(&self.data[pos..], bufs, 0);
+		*pos_guard = pos + len;
+
+		Ok(len)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		let mut pos_guard = self.pos.lock().await;
+
+		let new_pos = match whence {
+			SeekWhence::Set => offset,
+			SeekWhence::Cur => *pos_guard as isize + offset,
+			SeekWhence::End => self.data.len() as isize + offset,
+			_ => return Err(io::Error::EINVAL),
+		};
+
+		if new_pos < 0 {
+			return Err(io::Error::EINVAL);
+		}
+
+		*pos_guard = new_pos.try_into().unwrap();
+		Ok(new_pos)
+	}
+}
+
+/// Read-only file, whose content is generated whenever the file is opened
+pub(crate) struct GenFile {
+	generate: Box<dyn Fn() -> String + Send + Sync>,
+	attr: FileAttr,
+}
+
+impl fmt::Debug for GenFile {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("GenFile")
+			.field("attr", &self.attr)
+			.finish_non_exhaustive()
+	}
+}
+
+impl VfsNode for GenFile {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::File
+	}
+
+	fn get_object(&self) -> io::Result<Arc<dyn ObjectInterface>> {
+		Ok(Arc::new(GenFileInterface {
+			pos: Mutex::new(0),
+			data: (self.generate)().into_bytes(),
+		}))
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		Ok(self.attr)
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(io::Error::EBADF)
+		}
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(io::Error::EBADF)
+		}
+	}
+}
+
+impl GenFile {
+	pub fn new(
+		generate: impl Fn() -> String + Send + Sync + 'static,
+		mode: AccessPermission,
+	) -> Self {
+		let microseconds = realtime_micros();
+		let t = timespec::from_usec(microseconds as i64);
+		let attr = FileAttr {
+			st_mode: mode | AccessPermission::S_IFREG,
+			st_atim: t,
+			st_mtim: t,
+			st_ctim: t,
+			..Default::default()
+		};
+
+		Self {
+			generate: Box::new(generate),
+			attr,
+		}
+	}
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct RamFile {
 	data: Arc<RwLock<RamFileInner>>,
@@ -347,7 +476,7 @@ impl VfsNode for RamFile {
 
 impl RamFile {
 	pub fn new(mode: AccessPermission) -> Self {
-		let microseconds = arch::kernel::systemtime::now_micros();
+		let microseconds = realtime_micros();
 		let t = timespec::from_usec(microseconds as i64);
 		let attr = FileAttr {
 			st_mode: mode | AccessPermission::S_IFREG,
@@ -401,7 +530,7 @@ pub(crate) struct MemDirectory {
 
 impl MemDirectory {
 	pub fn new(mode: AccessPermission) -> Self {
-		let microseconds = arch::kernel::systemtime::now_micros();
+		let microseconds = realtime_micros();
 		let t = timespec::from_usec(microseconds as i64);
 
 		Self {
@@ -553,6 +682,63 @@ impl VfsNode for MemDirectory {
 		)
 	}
 
+	/// Renames an entry of a directory.
+	///
+	/// Entries can only be renamed within their directory, because the
+	/// entries cannot be moved between the directories of the RAM file system.
+	fn traverse_rename(
+		&self,
+		components: &mut Vec<&str>,
+		new_components: &mut Vec<&str>,
+	) -> io::Result<()> {
+		block_on(
+			async {
+				let (Some(component), Some(new_component)) =
+					(components.pop(), new_components.pop())
+				else {
+					return Err(io::Error::EBADF);
+				};
+
+				if components.is_empty() && new_components.is_empty() {
+					let mut guard = self.inner.write().await;
+
+					let kind = guard.get(component).ok_or(io::Error::ENOENT)?.get_kind();
+					if let Some(target) = guard.get(new_component) {
+						match (kind, target.get_kind()) {
+							(NodeKind::File, NodeKind::Directory) => return Err(io::Error::EISDIR),
+							(NodeKind::Directory, NodeKind::File) => {
+								return Err(io::Error::ENOTDIR);
+							}
+							(NodeKind::Directory, NodeKind::Directory)
+								if !target.traverse_readdir(&mut Vec::new())?.is_empty() =>
+							{
+								return Err(io::Error::ENOTEMPTY);
+							}
+							_ => {}
+						}
+					}
+
+					let obj = guard.remove(component).unwrap();
+					guard.insert(String::from(new_component), obj);
+					return Ok(());
+				}
+
+				if component == new_component
+					&& !components.is_empty()
+					&& !new_components.is_empty()
+				{
+					if let Some(directory) = self.inner.read().await.get(component) {
+						return directory.traverse_rename(components, new_components);
+					}
+					return Err(io::Error::ENOENT);
+				}
+
+				Err(io::Error::EXDEV)
+			},
+			None,
+		)
+	}
+
 	fn traverse_readdir(&self, components: &mut Vec<&str>) -> io::Result<Vec<DirectoryEntry>> {
 		block_on(
 			async {
@@ -653,6 +839,33 @@ impl VfsNode for MemDirectory {
 		)
 	}
 
+	fn traverse_umount(
+		&self,
+		components: &mut Vec<&str>,
+	) -> io::Result<Box<dyn VfsNode + core::marker::Send + core::marker::Sync>> {
+		block_on(
+			async {
+				if let Some(component) = components.pop() {
+					let node_name = String::from(component);
+
+					if components.is_empty() {
+						return self
+							.inner
+							.write()
+							.await
+							.remove(&node_name)
+							.ok_or(io::Error::ENOENT);
+					} else if let Some(directory) = self.inner.read().await.get(&node_name) {
+						return directory.traverse_umount(components);
+					}
+				}
+
+				Err(io::Error::EBADF)
+			},
+			None,
+		)
+	}
+
 	fn traverse_open(
 		&self,
 		components: &mut Vec<&str>,
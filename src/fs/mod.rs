@@ -1,20 +1,35 @@
 #[cfg(all(feature = "fuse", feature = "pci"))]
+mod dcache;
+mod dev;
+#[cfg(feature = "blk")]
+mod ext2;
+#[cfg(all(feature = "fuse", feature = "pci"))]
 pub(crate) mod fuse;
+pub(crate) mod initrd;
+#[cfg(feature = "ivshmem")]
+mod ivshmem;
 mod mem;
+mod mount;
+#[cfg(feature = "pmem")]
+mod pmem;
+mod proc;
+mod sys;
 mod uhyve;
 
 use alloc::boxed::Box;
 use alloc::string::{String, ToString};
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 
+use async_lock::Mutex;
 use async_trait::async_trait;
 use hermit_sync::OnceCell;
 use mem::MemDirectory;
+use mount::{MountPoint, MountTable};
 
+use crate::executor::block_on;
 use crate::fd::{AccessPermission, ObjectInterface, OpenOption, insert_object, remove_object};
 use crate::io;
-use crate::io::Write;
 use crate::time::{SystemTime, timespec};
 
 static FILESYSTEM: OnceCell<Filesystem> = OnceCell::new();
@@ -73,6 +88,15 @@ pub(crate) trait VfsNode: core::fmt::Debug {
 		Err(io::Error::ENOSYS)
 	}
 
+	/// Helper function to rename a file or directory
+	fn traverse_rename(
+		&self,
+		_components: &mut Vec<&str>,
+		_new_components: &mut Vec<&str>,
+	) -> io::Result<()> {
+		Err(io::Error::ENOSYS)
+	}
+
 	/// Helper function to open a directory
 	fn traverse_readdir(&self, _components: &mut Vec<&str>) -> io::Result<Vec<DirectoryEntry>> {
 		Err(io::Error::ENOSYS)
@@ -97,6 +121,14 @@ pub(crate) trait VfsNode: core::fmt::Debug {
 		Err(io::Error::ENOSYS)
 	}
 
+	/// Helper function to unmount a file system
+	fn traverse_umount(
+		&self,
+		_components: &mut Vec<&str>,
+	) -> io::Result<Box<dyn VfsNode + core::marker::Send + core::marker::Sync>> {
+		Err(io::Error::ENOSYS)
+	}
+
 	/// Helper function to open a file
 	fn traverse_open(
 		&self,
@@ -137,12 +169,24 @@ impl ObjectInterface for DirectoryReader {
 #[derive(Debug)]
 pub(crate) struct Filesystem {
 	root: MemDirectory,
+	mounts: Mutex<MountTable>,
 }
 
 impl Filesystem {
 	pub fn new() -> Self {
+		// The root file system is always mounted.
+		let mut mounts = MountTable::new();
+		mounts.insert(
+			"/".to_string(),
+			"ramfs",
+			"rootfs".to_string(),
+			None,
+			Weak::new(),
+		);
+
 		Self {
 			root: MemDirectory::new(AccessPermission::from_bits(0o777).unwrap()),
+			mounts: Mutex::new(mounts),
 		}
 	}
 
@@ -176,6 +220,10 @@ impl Filesystem {
 	/// Remove directory given by path
 	pub fn rmdir(&self, path: &str) -> io::Result<()> {
 		debug!("Removing directory {}", path);
+		if self.is_mount_point(path) {
+			return Err(io::Error::EBUSY);
+		}
+
 		let mut components: Vec<&str> = path.split('/').collect();
 
 		components.reverse();
@@ -184,6 +232,25 @@ impl Filesystem {
 		self.root.traverse_rmdir(&mut components)
 	}
 
+	/// Renames the file or directory `path` to `new_path`
+	pub fn rename(&self, path: &str, new_path: &str) -> io::Result<()> {
+		debug!("Renaming {path} to {new_path}");
+		if self.is_mount_point(path) || self.is_mount_point(new_path) {
+			return Err(io::Error::EBUSY);
+		}
+
+		let mut components: Vec<&str> = path.split('/').collect();
+		let mut new_components: Vec<&str> = new_path.split('/').collect();
+
+		components.reverse();
+		components.pop();
+		new_components.reverse();
+		new_components.pop();
+
+		self.root
+			.traverse_rename(&mut components, &mut new_components)
+	}
+
 	/// Create directory given by path
 	pub fn mkdir(&self, path: &str, mode: AccessPermission) -> io::Result<()> {
 		debug!("Create directory {}", path);
@@ -238,19 +305,130 @@ impl Filesystem {
 	}
 
 	/// Create new backing-fs at mountpoint mntpath
+	///
+	/// An existing empty directory at `path` is covered by the file system and
+	/// restored, when the file system is unmounted.
 	pub fn mount(
 		&self,
 		path: &str,
+		fstype: &'static str,
+		source: &str,
 		obj: Box<dyn VfsNode + core::marker::Send + core::marker::Sync>,
 	) -> io::Result<()> {
-		debug!("Mounting {}", path);
-
-		let mut components: Vec<&str> = path.split('/').collect();
-
-		components.reverse();
-		components.pop();
+		debug!("Mounting {} ({}) at {}", source, fstype, path);
+
+		let path = mount_path(path)?;
+		block_on(
+			async {
+				let mut mounts = self.mounts.lock().await;
+				if mounts.get(&path).is_some() {
+					return Err(io::Error::EBUSY);
+				}
+
+				let mut components: Vec<&str> = path.split('/').collect();
+
+				components.reverse();
+				components.pop();
+
+				let covered = match self.readdir(&path) {
+					Ok(entries) if entries.is_empty() => {
+						let mode = self.stat(&path)?.st_mode & !AccessPermission::S_IFMT;
+						self.root.traverse_rmdir(&mut components.clone())?;
+						Some(mode)
+					}
+					Ok(_) => return Err(io::Error::EBUSY),
+					Err(_) => None,
+				};
+
+				let mount_point = MountPoint::new(obj);
+				let users = mount_point.users();
+				if let Err(err) = self
+					.root
+					.traverse_mount(&mut components, Box::new(mount_point))
+				{
+					if let Some(mode) = covered {
+						let _ = self.mkdir(&path, mode);
+					}
+					return Err(err);
+				}
+				mounts.insert(path.clone(), fstype, source.to_string(), covered, users);
+
+				Ok(())
+			},
+			None,
+		)
+	}
+
+	/// Removes the file system, which is mounted at `path`.
+	///
+	/// If objects of the file system are still in use or other file systems are
+	/// mounted below `path`, the call fails with `EBUSY`. A lazy unmount detaches the
+	/// file system (including all nested mounts) nevertheless. Open objects remain
+	/// usable until they are closed. The root file system cannot be unmounted.
+	pub fn umount(&self, path: &str, lazy: bool) -> io::Result<()> {
+		let path = mount_path(path)?;
+		if path == "/" {
+			return Err(io::Error::EBUSY);
+		}
 
-		self.root.traverse_mount(&mut components, obj)
+		block_on(
+			async {
+				let mut mounts = self.mounts.lock().await;
+				let info = mounts.get(&path).ok_or(io::Error::EINVAL)?;
+				let nested = mounts.nested(&path);
+				if !lazy && (info.is_busy() || !nested.is_empty()) {
+					return Err(io::Error::EBUSY);
+				}
+
+				let mut components: Vec<&str> = path.split('/').collect();
+
+				components.reverse();
+				components.pop();
+
+				self.root.traverse_umount(&mut components)?;
+				for nested in nested {
+					mounts.remove(&nested);
+				}
+				let info = mounts.remove(&path).unwrap();
+				info!("Unmounted {} ({}) from {}", info.source, info.fstype, path);
+				if let Some(mode) = info.covered {
+					self.mkdir(&path, mode)?;
+				}
+
+				Ok(())
+			},
+			None,
+		)
+	}
+
+	/// Returns the mount points with the type and the source of their file systems.
+	pub fn mounts(&self) -> Vec<(String, &'static str, String)> {
+		block_on(
+			async {
+				Ok(self
+					.mounts
+					.lock()
+					.await
+					.iter()
+					.map(|(path, info)| (path.clone(), info.fstype, info.source.clone()))
+					.collect())
+			},
+			None,
+		)
+		.unwrap_or_default()
+	}
+
+	/// Returns `true`, if a file system is mounted at `path`.
+	fn is_mount_point(&self, path: &str) -> bool {
+		let Ok(path) = mount_path(path) else {
+			return false;
+		};
+
+		block_on(
+			async { Ok(self.mounts.lock().await.get(&path).is_some()) },
+			None,
+		)
+		.unwrap_or(false)
 	}
 
 	/// Create read-only file
@@ -271,6 +449,24 @@ impl Filesystem {
 	}
 }
 
+/// Returns the normalized path of a mount point.
+fn mount_path(path: &str) -> io::Result<String> {
+	if !path.starts_with('/') {
+		return Err(io::Error::EINVAL);
+	}
+
+	let path = path.trim_end_matches('/');
+	if path.is_empty() {
+		// the root file system
+		return Ok("/".to_string());
+	}
+	if path.split('/').skip(1).any(str::is_empty) {
+		return Err(io::Error::EINVAL);
+	}
+
+	Ok(path.to_string())
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct FileAttr {
@@ -322,9 +518,6 @@ pub enum SeekWhence {
 }
 
 pub(crate) fn init() {
-	const VERSION: &str = env!("CARGO_PKG_VERSION");
-	const UTC_BUILT_TIME: &str = build_time::build_time_utc!();
-
 	FILESYSTEM.set(Filesystem::new()).unwrap();
 	FILESYSTEM
 		.get()
@@ -334,16 +527,25 @@ pub(crate) fn init() {
 	FILESYSTEM
 		.get()
 		.unwrap()
-		.mkdir("/proc", AccessPermission::from_bits(0o777).unwrap())
-		.expect("Unable to create /proc");
+		.mount("/proc", "proc", "proc", Box::new(proc::procfs()))
+		.expect("Unable to mount /proc");
+	FILESYSTEM
+		.get()
+		.unwrap()
+		.mount("/dev", "devtmpfs", "devtmpfs", Box::new(dev::devtmpfs()))
+		.expect("Unable to mount /dev");
+	FILESYSTEM
+		.get()
+		.unwrap()
+		.mount("/sys", "sysfs", "sysfs", Box::new(sys::sysfs()))
+		.expect("Unable to mount /sys");
 
-	if let Ok(mut file) = File::create("/proc/version") {
-		if write!(file, "HermitOS version {VERSION} # UTC {UTC_BUILT_TIME}").is_err() {
-			error!("Unable to write in /proc/version");
-		}
-	} else {
-		error!("Unable to create /proc/version");
-	}
+	initrd::unpack();
+
+	#[cfg(feature = "pmem")]
+	pmem::init();
+	#[cfg(feature = "ivshmem")]
+	ivshmem::init();
 
 	#[cfg(all(feature = "fuse", feature = "pci"))]
 	fuse::init();
@@ -357,6 +559,48 @@ pub fn create_file(name: &str, data: &'static [u8], mode: AccessPermission) -> i
 		.create_file(name, data, mode)
 }
 
+/// Mounts a new file system of type `fstype` at `path`.
+///
+/// Supported are an empty `ramfs` (alias `tmpfs`), `virtiofs`, whose source is
+/// the tag of the device, and `ext2`, whose source is a block device (e.g., `vda`
+/// or the persistent memory `pmem0`).
+/// `options` are the file-system-specific options of the mount.
+pub(crate) fn mount(
+	source: &str,
+	path: &str,
+	fstype: &str,
+	read_only: bool,
+	#[allow(unused_variables)] options: &str,
+) -> io::Result<()> {
+	let (fstype, node): (&'static str, Box<dyn VfsNode + Send + Sync>) = match fstype {
+		"ramfs" | "tmpfs" if !read_only => (
+			"ramfs",
+			Box::new(MemDirectory::new(
+				AccessPermission::from_bits(0o777).unwrap(),
+			)),
+		),
+		"ramfs" | "tmpfs" => return Err(io::Error::EINVAL),
+		#[cfg(all(feature = "fuse", feature = "pci"))]
+		"virtiofs" => ("virtiofs", fuse::new_mount(source, read_only, options)?),
+		#[cfg(feature = "blk")]
+		"ext2" => ("ext2", ext2::new_mount(source, read_only)?),
+		_ => return Err(io::Error::ENODEV),
+	};
+
+	FILESYSTEM
+		.get()
+		.ok_or(io::Error::EINVAL)?
+		.mount(path, fstype, source, node)
+}
+
+/// Unmounts the file system at `path`, see [`Filesystem::umount`].
+pub(crate) fn umount(path: &str, lazy: bool) -> io::Result<()> {
+	FILESYSTEM
+		.get()
+		.ok_or(io::Error::EINVAL)?
+		.umount(path, lazy)
+}
+
 /// Removes an empty directory.
 pub fn remove_dir(path: &str) -> io::Result<()> {
 	FILESYSTEM.get().ok_or(io::Error::EINVAL)?.rmdir(path)
@@ -366,6 +610,14 @@ pub fn unlink(path: &str) -> io::Result<()> {
 	FILESYSTEM.get().ok_or(io::Error::EINVAL)?.unlink(path)
 }
 
+/// Renames a file or directory.
+pub fn rename(path: &str, new_path: &str) -> io::Result<()> {
+	FILESYSTEM
+		.get()
+		.ok_or(io::Error::EINVAL)?
+		.rename(path, new_path)
+}
+
 /// Creates a new, empty directory at the provided path
 pub fn create_dir(path: &str, mode: AccessPermission) -> io::Result<()> {
 	FILESYSTEM.get().ok_or(io::Error::EINVAL)?.mkdir(path, mode)
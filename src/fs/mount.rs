@@ -0,0 +1,254 @@
+//! Mount table of the virtual file system
+//!
+//! Each file system, which is mounted into the VFS, is wrapped by a [`MountPoint`].
+//! The mount point hands out a reference to all objects, which are opened below
+//! it. As long as such an object exists, the file system is busy and can only be
+//! unmounted lazily. A lazy unmount detaches the file system from the VFS, while
+//! the objects remain usable until they are closed.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+use async_trait::async_trait;
+use memory_addresses::VirtAddr;
+
+use crate::fd::{AccessPermission, IoCtl, ObjectInterface, OpenOption, PollEvent};
+use crate::fs::{DirectoryEntry, FileAttr, NodeKind, SeekWhence, VfsNode};
+use crate::io;
+
+/// Entry of the mount table
+#[derive(Debug)]
+pub(crate) struct MountInfo {
+	/// Type of the file system
+	pub fstype: &'static str,
+	/// Device or tag, which backs the file system
+	pub source: String,
+	/// Permissions of the empty directory, which is covered by the file system
+	pub covered: Option<AccessPermission>,
+	/// Reference, which is shared by all objects of the file system
+	users: Weak<()>,
+}
+
+impl MountInfo {
+	/// Returns `true`, if objects of the file system are in use.
+	pub fn is_busy(&self) -> bool {
+		// the mount point holds the first reference
+		self.users.strong_count() > 1
+	}
+}
+
+/// Mounted file systems, indexed by the path of the mount point
+#[derive(Debug, Default)]
+pub(crate) struct MountTable(BTreeMap<String, MountInfo>);
+
+impl MountTable {
+	pub const fn new() -> Self {
+		Self(BTreeMap::new())
+	}
+
+	pub fn insert(
+		&mut self,
+		path: String,
+		fstype: &'static str,
+		source: String,
+		covered: Option<AccessPermission>,
+		users: Weak<()>,
+	) {
+		self.0.insert(path, MountInfo {
+			fstype,
+			source,
+			covered,
+			users,
+		});
+	}
+
+	pub fn get(&self, path: &str) -> Option<&MountInfo> {
+		self.0.get(path)
+	}
+
+	/// Returns the mount points below `path`.
+	pub fn nested(&self, path: &str) -> Vec<String> {
+		self.0
+			.keys()
+			.filter(|mount| {
+				mount
+					.strip_prefix(path)
+					.is_some_and(|rest| rest.starts_with('/'))
+			})
+			.cloned()
+			.collect()
+	}
+
+	pub fn remove(&mut self, path: &str) -> Option<MountInfo> {
+		self.0.remove(path)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&String, &MountInfo)> {
+		self.0.iter()
+	}
+}
+
+/// Root node of a mounted file system
+#[derive(Debug)]
+pub(crate) struct MountPoint {
+	node: Box<dyn VfsNode + Send + Sync>,
+	users: Arc<()>,
+}
+
+impl MountPoint {
+	pub fn new(node: Box<dyn VfsNode + Send + Sync>) -> Self {
+		Self {
+			node,
+			users: Arc::new(()),
+		}
+	}
+
+	pub fn users(&self) -> Weak<()> {
+		Arc::downgrade(&self.users)
+	}
+
+	fn wrap(&self, obj: Arc<dyn ObjectInterface>) -> Arc<dyn ObjectInterface> {
+		Arc::new(MountedObject {
+			obj,
+			_users: self.users.clone(),
+		})
+	}
+}
+
+impl VfsNode for MountPoint {
+	fn get_kind(&self) -> NodeKind {
+		self.node.get_kind()
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		self.node.get_file_attributes()
+	}
+
+	fn get_object(&self) -> io::Result<Arc<dyn ObjectInterface>> {
+		self.node.get_object().map(|obj| self.wrap(obj))
+	}
+
+	fn traverse_mkdir(&self, components: &mut Vec<&str>, mode: AccessPermission) -> io::Result<()> {
+		self.node.traverse_mkdir(components, mode)
+	}
+
+	fn traverse_rmdir(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		self.node.traverse_rmdir(components)
+	}
+
+	fn traverse_unlink(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		self.node.traverse_unlink(components)
+	}
+
+	fn traverse_rename(
+		&self,
+		components: &mut Vec<&str>,
+		new_components: &mut Vec<&str>,
+	) -> io::Result<()> {
+		self.node.traverse_rename(components, new_components)
+	}
+
+	fn traverse_readdir(&self, components: &mut Vec<&str>) -> io::Result<Vec<DirectoryEntry>> {
+		self.node.traverse_readdir(components)
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.node.traverse_lstat(components)
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.node.traverse_stat(components)
+	}
+
+	fn traverse_mount(
+		&self,
+		components: &mut Vec<&str>,
+		obj: Box<dyn VfsNode + Send + Sync>,
+	) -> io::Result<()> {
+		self.node.traverse_mount(components, obj)
+	}
+
+	fn traverse_umount(
+		&self,
+		components: &mut Vec<&str>,
+	) -> io::Result<Box<dyn VfsNode + Send + Sync>> {
+		self.node.traverse_umount(components)
+	}
+
+	fn traverse_open(
+		&self,
+		components: &mut Vec<&str>,
+		option: OpenOption,
+		mode: AccessPermission,
+	) -> io::Result<Arc<dyn ObjectInterface>> {
+		self.node
+			.traverse_open(components, option, mode)
+			.map(|obj| self.wrap(obj))
+	}
+
+	fn traverse_create_file(
+		&self,
+		components: &mut Vec<&str>,
+		data: &'static [u8],
+		mode: AccessPermission,
+	) -> io::Result<()> {
+		self.node.traverse_create_file(components, data, mode)
+	}
+}
+
+/// Object of a mounted file system, which keeps the file system busy
+#[derive(Debug)]
+struct MountedObject {
+	obj: Arc<dyn ObjectInterface>,
+	_users: Arc<()>,
+}
+
+#[async_trait]
+impl ObjectInterface for MountedObject {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		self.obj.poll(event).await
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		self.obj.read(buf).await
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		self.obj.write(buf).await
+	}
+
+	async fn readv(&self, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+		self.obj.readv(bufs).await
+	}
+
+	async fn writev(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		self.obj.writev(bufs).await
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		self.obj.lseek(offset, whence).await
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		self.obj.fstat().await
+	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		self.obj.fsync().await
+	}
+
+	async fn dax_address(&self, offset: usize) -> io::Result<(VirtAddr, usize)> {
+		self.obj.dax_address(offset).await
+	}
+
+	async fn readdir(&self) -> io::Result<Vec<DirectoryEntry>> {
+		self.obj.readdir().await
+	}
+
+	async fn ioctl(&self, cmd: IoCtl, value: bool) -> io::Result<()> {
+		self.obj.ioctl(cmd, value).await
+	}
+}
@@ -0,0 +1,248 @@
+//! Access to persistent memory devices
+//!
+//! The memory of a virtio-pmem device is provided as block device `/dev/pmem0`.
+//! Besides reading and writing, the memory is directly accessible (DAX) via
+//! `sys_dax_map`. `fsync` makes all modifications persistent.
+//!
+//! With `blk`, the device may also contain an ext2 file system, which is
+//! mounted by `mount("pmem0", "/mnt", "ext2", flags)`. The blocks of its files
+//! are directly accessible as well.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::slice;
+
+use async_lock::Mutex;
+use async_trait::async_trait;
+#[cfg(feature = "blk")]
+use hermit_sync::OnceCell;
+use memory_addresses::VirtAddr;
+
+#[cfg(feature = "blk")]
+use crate::drivers::block::{self, BlockDevice};
+use crate::drivers::pci::get_pmem_driver;
+use crate::drivers::pmem::PmemRegion;
+use crate::fd::{AccessPermission, ObjectInterface, PollEvent};
+use crate::fs::{FileAttr, NodeKind, SeekWhence, VfsNode};
+use crate::io;
+
+/// Size of a block of the device
+const BLOCK_SIZE: usize = 4096;
+
+/// Memory region, which is provided as block device `pmem0`
+#[cfg(feature = "blk")]
+static DEVICE: OnceCell<PmemRegion> = OnceCell::new();
+
+/// Makes all modifications of the memory persistent.
+fn flush() -> io::Result<()> {
+	let driver = get_pmem_driver().ok_or(io::Error::ENODEV)?;
+	driver.lock().flush().map_err(|err| {
+		error!("Unable to flush persistent memory: {err:?}");
+		io::Error::EIO
+	})
+}
+
+#[derive(Debug)]
+struct PmemInterface {
+	region: PmemRegion,
+	/// Position within the device
+	pos: Mutex<usize>,
+}
+
+#[async_trait]
+impl ObjectInterface for PmemInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event.intersection(
+			PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLOUT | PollEvent::POLLWRNORM,
+		))
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let mut pos_guard = self.pos.lock().await;
+		let pos = (*pos_guard).min(self.region.size);
+		let len = buf.len().min(self.region.size - pos);
+
+		let memory = unsafe {
+			slice::from_raw_parts(self.region.virt_addr.as_ptr::<u8>(), self.region.size)
+		};
+		buf[..len].copy_from_slice(&memory[pos..pos + len]);
+		*pos_guard = pos + len;
+
+		Ok(len)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let mut pos_guard = self.pos.lock().await;
+		let pos = (*pos_guard).min(self.region.size);
+		let len = buf.len().min(self.region.size - pos);
+		if len == 0 && !buf.is_empty() {
+			return Err(io::Error::ENOSPC);
+		}
+
+		let memory = unsafe {
+			slice::from_raw_parts_mut(self.region.virt_addr.as_mut_ptr::<u8>(), self.region.size)
+		};
+		memory[pos..pos + len].copy_from_slice(&buf[..len]);
+		*pos_guard = pos + len;
+
+		Ok(len)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		let mut pos_guard = self.pos.lock().await;
+
+		let new_pos = match whence {
+			SeekWhence::Set => offset,
+			SeekWhence::Cur => *pos_guard as isize + offset,
+			SeekWhence::End => self.region.size as isize + offset,
+			_ => return Err(io::Error::EINVAL),
+		};
+
+		if new_pos < 0 {
+			return Err(io::Error::EINVAL);
+		}
+
+		*pos_guard = new_pos.try_into().unwrap();
+		Ok(new_pos)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		Ok(attributes(&self.region))
+	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		flush()
+	}
+
+	async fn dax_address(&self, offset: usize) -> io::Result<(VirtAddr, usize)> {
+		if offset >= self.region.size {
+			return Err(io::Error::EINVAL);
+		}
+
+		Ok((self.region.virt_addr + offset, self.region.size - offset))
+	}
+}
+
+fn attributes(region: &PmemRegion) -> FileAttr {
+	FileAttr {
+		st_size: region.size as u64,
+		st_blksize: BLOCK_SIZE as i64,
+		st_blocks: region.size.div_ceil(512) as i64,
+		st_mode: AccessPermission::S_IFBLK | AccessPermission::from_bits(0o660).unwrap(),
+		..Default::default()
+	}
+}
+
+/// Block device, which provides the memory of a persistent memory device
+#[derive(Debug)]
+struct PmemDevice {
+	region: PmemRegion,
+}
+
+impl VfsNode for PmemDevice {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::File
+	}
+
+	fn get_object(&self) -> io::Result<Arc<dyn ObjectInterface>> {
+		Ok(Arc::new(PmemInterface {
+			region: self.region,
+			pos: Mutex::new(0),
+		}))
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		Ok(attributes(&self.region))
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(io::Error::EBADF)
+		}
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(io::Error::EBADF)
+		}
+	}
+}
+
+#[cfg(feature = "blk")]
+impl BlockDevice for PmemRegion {
+	fn block_size(&self) -> usize {
+		BLOCK_SIZE
+	}
+
+	fn num_blocks(&self) -> u64 {
+		(self.size / BLOCK_SIZE) as u64
+	}
+
+	fn is_read_only(&self) -> bool {
+		false
+	}
+
+	fn read_blocks(&self, block: u64, buf: &mut [u8]) -> io::Result<()> {
+		block::check_range(self, block, buf.len())?;
+		let start = block as usize * BLOCK_SIZE;
+		let memory = unsafe { slice::from_raw_parts(self.virt_addr.as_ptr::<u8>(), self.size) };
+		buf.copy_from_slice(&memory[start..start + buf.len()]);
+		Ok(())
+	}
+
+	fn write_blocks(&self, block: u64, buf: &[u8]) -> io::Result<()> {
+		block::check_range(self, block, buf.len())?;
+		let start = block as usize * BLOCK_SIZE;
+		let memory =
+			unsafe { slice::from_raw_parts_mut(self.virt_addr.as_mut_ptr::<u8>(), self.size) };
+		memory[start..start + buf.len()].copy_from_slice(buf);
+		Ok(())
+	}
+
+	fn flush(&self) -> io::Result<()> {
+		flush()
+	}
+
+	fn dax_address(&self, offset: u64) -> Option<VirtAddr> {
+		let offset = usize::try_from(offset).ok()?;
+		(offset < self.size).then(|| self.virt_addr + offset)
+	}
+}
+
+/// Returns the block device `pmem0`, if a persistent memory device is available.
+#[cfg(feature = "blk")]
+pub(crate) fn block_device() -> Option<&'static dyn BlockDevice> {
+	DEVICE
+		.get()
+		.map(|region| region as &'static dyn BlockDevice)
+}
+
+pub(crate) fn init() {
+	let Some(region) = get_pmem_driver().and_then(|driver| driver.lock().region()) else {
+		return;
+	};
+	#[cfg(feature = "pstore")]
+	let region = crate::pstore::exclude(region);
+	#[cfg(feature = "blk")]
+	DEVICE.set(region).unwrap();
+
+	super::FILESYSTEM
+		.get()
+		.unwrap()
+		.mount(
+			"/dev/pmem0",
+			"pmem",
+			"pmem0",
+			Box::new(PmemDevice { region }),
+		)
+		.expect("Unable to mount /dev/pmem0");
+	info!(
+		"Persistent memory of {} bytes is available at /dev/pmem0",
+		region.size
+	);
+}
@@ -0,0 +1,193 @@
+//! Introspection of the kernel state
+//!
+//! `/proc` is a file system of its own, whose files are generated from the
+//! current state of the kernel, whenever they are opened:
+//!
+//! - `version` describes the kernel.
+//! - `sbom` lists the features, drivers and libraries of the kernel as SPDX document.
+//! - `uptime` contains the time since boot in seconds.
+//! - `loadavg` contains the 1, 5 and 15 minute load averages.
+//! - `meminfo` summarizes the usage of the physical memory and the heap.
+//! - `mounts` lists the mounted file systems.
+//! - `tasks` lists the tasks, which are not finished, with their priority and core.
+//! - `interrupts` contains the number of received interrupts per core.
+//! - `mitigations` lists the mitigations against speculative execution attacks and their state.
+//! - `net/interfaces` lists the addresses of the network interface.
+//! - `net/neighbors` lists the neighbors of the network interface.
+//! - `net/route` lists the routes of the network interface.
+//! - `net/usage` lists the bytes and packets, which have been received and sent by the sockets of each task.
+//! - `net/softnet` counts the received packets and the polls, which have exhausted their budget, per core.
+//! - `pstore` contains the kernel log saved before the last reboot (with the feature `pstore`).
+//! - `metrics` contains the lock statistics (with the feature `sync-stats`).
+//! - `prometheus` contains the metrics of the kernel in the text format of Prometheus.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::{format, vec};
+use core::fmt::Write;
+
+use crate::fd::AccessPermission;
+use crate::fs::VfsNode;
+use crate::fs::mem::{GenFile, MemDirectory};
+use crate::{arch, mm, scheduler, time};
+
+/// Function, which generates the content of a file
+type Generator = fn() -> String;
+
+fn version() -> String {
+	const VERSION: &str = env!("CARGO_PKG_VERSION");
+	const UTC_BUILT_TIME: &str = build_time::build_time_utc!();
+
+	format!("HermitOS version {VERSION} # UTC {UTC_BUILT_TIME}\n")
+}
+
+fn uptime() -> String {
+	let micros = time::boottime_micros();
+	format!(
+		"{}.{:02}\n",
+		micros / 1_000_000,
+		micros % 1_000_000 / 10_000
+	)
+}
+
+fn loadavg() -> String {
+	let [one, five, fifteen] = scheduler::loadavg::averages().map(|load| {
+		let load = (load * 100) >> scheduler::loadavg::FSHIFT;
+		format!("{}.{:02}", load / 100, load % 100)
+	});
+	format!("{one} {five} {fifteen}\n")
+}
+
+fn meminfo() -> String {
+	let mut report = String::new();
+	let mut entry = |name: &str, value: usize, unit: &str| {
+		writeln!(report, "{:<16}{value:>12}{unit}", format!("{name}:")).unwrap();
+	};
+
+	entry(
+		"MemTotal",
+		arch::mm::physicalmem::total_memory_size() >> 10,
+		" kB",
+	);
+	#[cfg(target_os = "none")]
+	{
+		let heap = mm::ALLOCATOR.heap_state();
+		entry("HeapTotal", heap.claimed_bytes >> 10, " kB");
+		entry("HeapUsed", heap.allocated_bytes >> 10, " kB");
+		entry("HeapFree", heap.available_bytes >> 10, " kB");
+		entry("HeapAllocations", heap.allocation_count, "");
+		entry("HeapFragments", heap.fragment_count, "");
+	}
+	entry("Cached", mm::pressure::cached_bytes() >> 10, " kB");
+	entry(
+		"StackPending",
+		scheduler::pending_stack_bytes() >> 10,
+		" kB",
+	);
+	report
+}
+
+/// Lists the mount points in the format of `/proc/mounts` on Linux.
+fn mounts() -> String {
+	let Some(filesystem) = super::FILESYSTEM.get() else {
+		return String::new();
+	};
+
+	let mut report = String::new();
+	for (path, fstype, source) in filesystem.mounts() {
+		writeln!(report, "{source} {path} {fstype} defaults 0 0").unwrap();
+	}
+	report
+}
+
+fn tasks() -> String {
+	let mut report = String::from("ID\tPRIO\tCORE\n");
+	for task in scheduler::live_tasks() {
+		#[cfg(feature = "smp")]
+		let core_id = task.get_core_id();
+		#[cfg(not(feature = "smp"))]
+		let core_id = 0;
+		writeln!(
+			report,
+			"{}\t{}\t{core_id}",
+			task.get_id(),
+			task.get_priority()
+		)
+		.unwrap();
+	}
+	report
+}
+
+fn interrupts() -> String {
+	let mut report = String::new();
+	for (core_id, name, counter) in arch::interrupts::statistics() {
+		writeln!(report, "{core_id}\t{name}\t{counter}").unwrap();
+	}
+	report
+}
+
+/// Creates the root directory of the proc file system.
+pub(crate) fn procfs() -> MemDirectory {
+	let root = MemDirectory::new(AccessPermission::from_bits(0o555).unwrap());
+	let mode = AccessPermission::from_bits(0o444).unwrap();
+	let files: &[(&str, Generator)] = &[
+		("version", version),
+		("sbom", crate::sbom::spdx),
+		("uptime", uptime),
+		("loadavg", loadavg),
+		("meminfo", meminfo),
+		("mounts", mounts),
+		("tasks", tasks),
+		("interrupts", interrupts),
+		#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+		("mitigations", crate::mitigations::report),
+		#[cfg(feature = "pstore")]
+		("pstore", crate::pstore::previous),
+		#[cfg(feature = "sync-stats")]
+		("metrics", crate::synch::stats::report),
+		("prometheus", crate::metrics::report),
+	];
+	for (name, generate) in files {
+		root.traverse_mount(&mut vec![*name], Box::new(GenFile::new(*generate, mode)))
+			.unwrap();
+	}
+
+	#[cfg(any(feature = "tcp", feature = "udp"))]
+	{
+		root.traverse_mkdir(
+			&mut vec!["net"],
+			AccessPermission::from_bits(0o555).unwrap(),
+		)
+		.unwrap();
+		root.traverse_mount(
+			&mut vec!["interfaces", "net"],
+			Box::new(GenFile::new(crate::executor::network::report, mode)),
+		)
+		.unwrap();
+		root.traverse_mount(
+			&mut vec!["neighbors", "net"],
+			Box::new(GenFile::new(
+				crate::executor::network::neighbor_report,
+				mode,
+			)),
+		)
+		.unwrap();
+		root.traverse_mount(
+			&mut vec!["route", "net"],
+			Box::new(GenFile::new(crate::executor::network::route_report, mode)),
+		)
+		.unwrap();
+		root.traverse_mount(
+			&mut vec!["usage", "net"],
+			Box::new(GenFile::new(crate::fd::socket::usage::report, mode)),
+		)
+		.unwrap();
+		root.traverse_mount(
+			&mut vec!["softnet", "net"],
+			Box::new(GenFile::new(crate::executor::network::softnet_report, mode)),
+		)
+		.unwrap();
+	}
+
+	root
+}
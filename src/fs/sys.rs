@@ -0,0 +1,131 @@
+//! Description of the devices
+//!
+//! `/sys` is a file system of its own, which is generated from the driver
+//! registration after the devices are probed. Like on Linux, each PCI device is
+//! represented by the directory `/sys/bus/pci/devices/<segment>:<bus>:<device>.<function>`,
+//! which contains the files:
+//!
+//! - `vendor` and `device` with the identity of the device,
+//! - `irq` with the interrupt line of the device.
+//!
+//! If a driver is attached to the device, the directory contains additionally:
+//!
+//! - `driver` with the name of the driver,
+//! - `queues` with the number of queues, which the driver uses,
+//! - `resources` with the BARs, which are claimed by the driver.
+
+#[cfg(feature = "pci")]
+use alloc::boxed::Box;
+#[cfg(feature = "pci")]
+use alloc::string::String;
+#[cfg(feature = "pci")]
+use alloc::vec::Vec;
+
+use crate::fd::AccessPermission;
+#[cfg(feature = "pci")]
+use crate::fs::VfsNode;
+#[cfg(feature = "pci")]
+use crate::fs::mem::GenFile;
+use crate::fs::mem::MemDirectory;
+
+#[cfg(feature = "pci")]
+fn mkdir(root: &MemDirectory, path: &str) {
+	let mut components: Vec<&str> = path.rsplit('/').collect();
+	root.traverse_mkdir(&mut components, AccessPermission::from_bits(0o555).unwrap())
+		.unwrap();
+}
+
+#[cfg(feature = "pci")]
+fn add_file(
+	root: &MemDirectory,
+	path: &str,
+	generate: impl Fn() -> String + Send + Sync + 'static,
+) {
+	let mut components: Vec<&str> = path.rsplit('/').collect();
+	root.traverse_mount(
+		&mut components,
+		Box::new(GenFile::new(
+			generate,
+			AccessPermission::from_bits(0o444).unwrap(),
+		)),
+	)
+	.unwrap();
+}
+
+#[cfg(feature = "pci")]
+fn add_pci_devices(root: &MemDirectory) {
+	use alloc::format;
+	use core::fmt::Write;
+
+	use pci_types::Bar;
+
+	use crate::drivers::pci::{self, PCI_DEVICES};
+	use crate::drivers::registry;
+
+	mkdir(root, "bus");
+	mkdir(root, "bus/pci");
+	mkdir(root, "bus/pci/devices");
+
+	let bindings = registry::bindings();
+	for device in PCI_DEVICES.finalize() {
+		let address = device.address();
+		let path = format!(
+			"bus/pci/devices/{:04x}:{:02x}:{:02x}.{:x}",
+			address.segment(),
+			address.bus(),
+			address.device(),
+			address.function()
+		);
+		mkdir(root, &path);
+
+		let (vendor_id, device_id) = device.id();
+		let vendor = format!("{vendor_id:#06x}\n");
+		let device_id = format!("{device_id:#06x}\n");
+		let binding = bindings.iter().find(|binding| binding.address == address);
+		let irq = match binding.and_then(|binding| binding.irq).or(device.get_irq()) {
+			Some(irq) => format!("{irq}\n"),
+			None => String::from("0\n"),
+		};
+		add_file(root, &format!("{path}/vendor"), move || vendor.clone());
+		add_file(root, &format!("{path}/device"), move || device_id.clone());
+		add_file(root, &format!("{path}/irq"), move || irq.clone());
+
+		let Some(binding) = binding else {
+			continue;
+		};
+
+		let driver = format!("{}\n", binding.driver);
+		let index = binding.index;
+		let mut resources = String::new();
+		for slot in &binding.bars {
+			match device.get_bar(*slot) {
+				Some(Bar::Io { port }) => writeln!(resources, "{slot} io {port:#x}"),
+				Some(Bar::Memory32 { address, size, .. }) => {
+					writeln!(resources, "{slot} mem {address:#x} {size:#x}")
+				}
+				Some(Bar::Memory64 { address, size, .. }) => {
+					writeln!(resources, "{slot} mem {address:#x} {size:#x}")
+				}
+				None => Ok(()),
+			}
+			.unwrap();
+		}
+		add_file(root, &format!("{path}/driver"), move || driver.clone());
+		add_file(root, &format!("{path}/queues"), move || {
+			format!("{}\n", pci::get_queue_count(index))
+		});
+		add_file(root, &format!("{path}/resources"), move || {
+			resources.clone()
+		});
+	}
+}
+
+/// Creates the root directory of the sys file system.
+pub(crate) fn sysfs() -> MemDirectory {
+	let root = MemDirectory::new(AccessPermission::from_bits(0o555).unwrap());
+
+	#[cfg(feature = "pci")]
+	add_pci_devices(&root);
+
+	root
+}
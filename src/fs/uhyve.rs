@@ -105,6 +105,11 @@ impl ObjectInterface for UhyveFileHandle {
 	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
 		self.0.lock().await.lseek(offset, whence)
 	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		// uhyve performs all writes synchronously
+		Ok(())
+	}
 }
 
 impl Clone for UhyveFileHandle {
@@ -113,29 +118,102 @@ impl Clone for UhyveFileHandle {
 	}
 }
 
+/// Resolves the components `.` and `..` of a path, whose components are
+/// given in order.
+///
+/// Returns `None`, if the path leaves its root by `..`.
+fn canonicalize<'a>(components: impl Iterator<Item = &'a str>) -> Option<Vec<&'a str>> {
+	let mut canonical = Vec::new();
+	for component in components {
+		match component {
+			"" | "." => {}
+			".." => {
+				canonical.pop()?;
+			}
+			component => canonical.push(component),
+		}
+	}
+
+	Some(canonical)
+}
+
+/// Joins canonical components to an absolute path.
+fn absolute_path(components: &[&str]) -> String {
+	if components.is_empty() {
+		"/".to_string()
+	} else {
+		components
+			.iter()
+			.flat_map(|component| ["/", component])
+			.collect()
+	}
+}
+
+/// Returns `true`, if the canonical path `path` is the directory `dir` or lies below it.
+fn is_below(path: &str, dir: &str) -> bool {
+	dir == "/"
+		|| path
+			.strip_prefix(dir)
+			.is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Parses the host directories, which are accessible to the guest, from a
+/// colon-separated list of absolute paths.
+fn parse_allowlist(list: &str) -> Vec<String> {
+	list.split(':')
+		.filter(|dir| !dir.is_empty())
+		.filter_map(|dir| {
+			let canonical = dir
+				.starts_with('/')
+				.then(|| canonicalize(dir.split('/')))
+				.flatten();
+			if canonical.is_none() {
+				warn!("Ignoring invalid entry {dir} of the uhyve allowlist");
+			}
+			canonical.map(|components| absolute_path(&components))
+		})
+		.collect()
+}
+
 #[derive(Debug)]
 pub(crate) struct UhyveDirectory {
 	prefix: Option<String>,
+	/// Canonical host directories, to which the accesses are restricted
+	///
+	/// Without an explicit allowlist, only the paths below `prefix` are accessible.
+	allowlist: Vec<String>,
 }
 
 impl UhyveDirectory {
-	pub const fn new(prefix: Option<String>) -> Self {
-		UhyveDirectory { prefix }
+	pub fn new(prefix: Option<String>, allowlist: Option<Vec<String>>) -> Self {
+		let allowlist = allowlist.unwrap_or_else(|| {
+			let prefix = prefix.as_deref().unwrap_or_default();
+			parse_allowlist(if prefix.is_empty() { "/" } else { prefix })
+		});
+		UhyveDirectory { prefix, allowlist }
 	}
 
-	fn traversal_path(&self, components: &[&str]) -> CString {
-		let prefix_deref = self.prefix.as_deref();
-		let components_with_prefix = prefix_deref.iter().chain(components.iter().rev());
-		// Unlike src/fs/fuse.rs, we skip the first element here so as to not prepend / before /root
-		let path: String = components_with_prefix
-			.flat_map(|component| ["/", component])
-			.skip(1)
-			.collect();
-		if path.is_empty() {
-			CString::new("/").unwrap()
-		} else {
-			CString::new(path).unwrap()
+	/// Returns the canonical path on the host, which is passed to uhyve.
+	///
+	/// uhyve opens the path as it is on the host, so that the prefix and the
+	/// components are joined first and the resulting host path is canonicalized
+	/// as a whole. Paths, which leave the root of the host or lie outside the
+	/// allowlist, are rejected with `EACCES`, so that a buggy guest cannot
+	/// access arbitrary files of the host.
+	fn traversal_path(&self, components: &[&str]) -> io::Result<CString> {
+		let host_path = self
+			.prefix
+			.iter()
+			.flat_map(|prefix| prefix.split('/'))
+			.chain(components.iter().rev().copied());
+		let path = absolute_path(&canonicalize(host_path).ok_or(io::Error::EACCES)?);
+
+		if !self.allowlist.iter().any(|dir| is_below(&path, dir)) {
+			debug!("Access to {path} is not allowed by the uhyve allowlist");
+			return Err(io::Error::EACCES);
 		}
+
+		CString::new(path).map_err(|_| io::Error::EINVAL)
 	}
 }
 
@@ -159,7 +237,7 @@ impl VfsNode for UhyveDirectory {
 		opt: OpenOption,
 		mode: AccessPermission,
 	) -> io::Result<Arc<dyn ObjectInterface>> {
-		let path = self.traversal_path(components);
+		let path = self.traversal_path(components)?;
 
 		let mut open_params = OpenParams {
 			name: GuestPhysAddr::new(
@@ -181,7 +259,7 @@ impl VfsNode for UhyveDirectory {
 	}
 
 	fn traverse_unlink(&self, components: &mut Vec<&str>) -> io::Result<()> {
-		let path = self.traversal_path(components);
+		let path = self.traversal_path(components)?;
 
 		let mut unlink_params = UnlinkParams {
 			name: GuestPhysAddr::new(
@@ -217,13 +295,19 @@ pub(crate) fn init() {
 	info!("Try to initialize uhyve filesystem");
 	if is_uhyve() {
 		let mount_point = hermit_var_or!("UHYVE_MOUNT", "/root").to_string();
+		let allowlist = hermit_var!("HERMIT_UHYVE_ALLOWLIST").map(|list| parse_allowlist(&list));
 		info!("Mounting uhyve filesystem at {}", mount_point);
+		if let Some(allowlist) = &allowlist {
+			info!("Restricting uhyve filesystem to {allowlist:?}");
+		}
 		fs::FILESYSTEM
 			.get()
 			.unwrap()
 			.mount(
 				&mount_point,
-				Box::new(UhyveDirectory::new(Some(mount_point.to_owned()))),
+				"uhyve",
+				"uhyve",
+				Box::new(UhyveDirectory::new(Some(mount_point.to_owned()), allowlist)),
 			)
 			.expect("Mount failed. Duplicate mount_point?");
 	}
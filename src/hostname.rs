@@ -0,0 +1,148 @@
+//! Network identity of this Hermit instance.
+//!
+//! The host name and the DNS domain name are taken from the kernel command line
+//! (`-hostname <name>`, `env=HERMIT_HOSTNAME=<name>` and `env=HERMIT_DOMAINNAME=<name>`).
+//! If they are not configured explicitly, they may be provided by a DHCP server.
+//! The application is able to change both at runtime.
+//! The host name announced to the DHCP server is the one at boot, though.
+
+use alloc::string::{String, ToString};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::io;
+
+/// Maximum length of a host name (without the terminating null byte)
+pub(crate) const HOST_NAME_MAX: usize = 64;
+
+/// Host name, which is used if nothing else is configured
+const DEFAULT_HOSTNAME: &str = "hermit";
+
+struct Identity {
+	hostname: String,
+	domainname: String,
+	/// The host name was configured explicitly and must not be
+	/// overwritten by the DHCP server.
+	hostname_fixed: bool,
+	/// The domain name was configured explicitly and must not be
+	/// overwritten by the DHCP server.
+	domainname_fixed: bool,
+}
+
+static IDENTITY: InterruptTicketMutex<Identity> = InterruptTicketMutex::new(Identity {
+	hostname: String::new(),
+	domainname: String::new(),
+	hostname_fixed: false,
+	domainname_fixed: false,
+});
+
+fn validate(name: &str) -> io::Result<()> {
+	if name.len() > HOST_NAME_MAX {
+		return Err(io::Error::ENAMETOOLONG);
+	}
+	if name.contains('\0') {
+		return Err(io::Error::EINVAL);
+	}
+
+	Ok(())
+}
+
+pub(crate) fn init() {
+	let mut identity = IDENTITY.lock();
+
+	identity.hostname = DEFAULT_HOSTNAME.to_string();
+	if let Some(hostname) = hermit_var!("HERMIT_HOSTNAME") {
+		if validate(&hostname).is_ok() {
+			identity.hostname = hostname.to_string();
+			identity.hostname_fixed = true;
+		} else {
+			warn!("Ignore invalid host name {hostname}");
+		}
+	}
+
+	if let Some(domainname) = hermit_var!("HERMIT_DOMAINNAME") {
+		if validate(&domainname).is_ok() {
+			identity.domainname = domainname.to_string();
+			identity.domainname_fixed = true;
+		} else {
+			warn!("Ignore invalid domain name {domainname}");
+		}
+	}
+
+	info!("Host name: {}", identity.hostname);
+}
+
+/// Returns the host name of this instance.
+pub fn hostname() -> String {
+	IDENTITY.lock().hostname.clone()
+}
+
+/// Returns the DNS domain name of this instance.
+pub fn domainname() -> String {
+	IDENTITY.lock().domainname.clone()
+}
+
+/// Sets the host name of this instance.
+pub fn set_hostname(name: &str) -> io::Result<()> {
+	validate(name)?;
+
+	let mut identity = IDENTITY.lock();
+	identity.hostname = name.to_string();
+	identity.hostname_fixed = true;
+
+	Ok(())
+}
+
+/// Sets the DNS domain name of this instance.
+pub fn set_domainname(name: &str) -> io::Result<()> {
+	validate(name)?;
+
+	let mut identity = IDENTITY.lock();
+	identity.domainname = name.to_string();
+	identity.domainname_fixed = true;
+
+	Ok(())
+}
+
+/// Updates the host name with the value provided by a DHCP server,
+/// if it wasn't configured explicitly.
+#[cfg(feature = "dhcpv4")]
+pub(crate) fn update_hostname_from_dhcp(name: &str) {
+	let mut identity = IDENTITY.lock();
+	if !identity.hostname_fixed && validate(name).is_ok() {
+		info!("Host name:       {name}");
+		identity.hostname = name.to_string();
+	}
+}
+
+/// Updates the domain name with the value provided by a DHCP server,
+/// if it wasn't configured explicitly.
+#[cfg(feature = "dhcpv4")]
+pub(crate) fn update_domainname_from_dhcp(name: &str) {
+	let mut identity = IDENTITY.lock();
+	if !identity.domainname_fixed && validate(name).is_ok() {
+		info!("Domain name:     {name}");
+		identity.domainname = name.to_string();
+	}
+}
+
+/// Determines whether `name` refers to this instance, which is the case for
+/// `localhost`, the host name and the fully qualified domain name.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+pub(crate) fn is_local_name(name: &str) -> bool {
+	let name = name.strip_suffix('.').unwrap_or(name);
+	if name.eq_ignore_ascii_case("localhost") {
+		return true;
+	}
+
+	let identity = IDENTITY.lock();
+	if name.eq_ignore_ascii_case(&identity.hostname) {
+		return true;
+	}
+
+	!identity.domainname.is_empty()
+		&& name.split_once('.').is_some_and(|(host, domain)| {
+			host.eq_ignore_ascii_case(&identity.hostname)
+				&& domain.eq_ignore_ascii_case(&identity.domainname)
+		})
+}
@@ -2,6 +2,8 @@
 	all(
 		not(feature = "pci"),
 		not(all(target_arch = "x86_64", feature = "tcp")),
+		not(all(target_arch = "x86_64", feature = "blk")),
+		not(all(target_arch = "x86_64", feature = "console")),
 		not(all(target_arch = "riscv64", feature = "tcp")),
 	),
 	expect(dead_code)
@@ -30,7 +32,6 @@ impl<T> InitCell<T> {
 		f((*guard).as_mut());
 	}
 
-	#[cfg_attr(all(feature = "pci", not(feature = "tcp")), expect(dead_code))]
 	pub fn get(&self) -> Option<&T> {
 		self.once.get()
 	}
@@ -0,0 +1,236 @@
+//! Initialization of the kernel subsystems
+//!
+//! After the cores have been started, the subsystems are initialized by
+//! `initd` in the order of their [`Level`]. Within a level, a subsystem is
+//! initialized after all subsystems it depends on. A dependency has to be
+//! initialized at the same or an earlier level. Dependencies on subsystems,
+//! which are disabled by the configuration, are ignored.
+//!
+//! New subsystems are registered in [`INITCALLS`]. On shutdown, the
+//! initialized subsystems are torn down in the reverse order.
+
+use alloc::vec::Vec;
+
+use hermit_sync::InterruptTicketMutex;
+
+/// Initialization phase of a subsystem
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+	/// Subsystems without any requirements
+	Early,
+	/// Device drivers and the services built on top of them
+	Driver,
+	/// File systems
+	Fs,
+	/// Subsystems, which require a complete kernel
+	Late,
+}
+
+/// A statically registered subsystem
+pub(crate) struct Initcall {
+	pub name: &'static str,
+	pub level: Level,
+	/// Names of the subsystems, which have to be initialized before
+	pub depends_on: &'static [&'static str],
+	pub init: fn(),
+	/// Teardown on shutdown
+	pub exit: Option<fn()>,
+}
+
+static INITCALLS: &[Initcall] = &[
+	Initcall {
+		name: "metrics",
+		level: Level::Early,
+		depends_on: &[],
+		init: crate::metrics::init,
+		exit: None,
+	},
+	Initcall {
+		name: "allocator-caches",
+		level: Level::Early,
+		depends_on: &[],
+		init: crate::mm::init_caches,
+		exit: None,
+	},
+	Initcall {
+		name: "hostname",
+		level: Level::Early,
+		depends_on: &[],
+		init: crate::hostname::init,
+		exit: None,
+	},
+	Initcall {
+		name: "drivers",
+		level: Level::Driver,
+		depends_on: &[],
+		init: crate::drivers::init,
+		exit: Some(crate::drivers::exit),
+	},
+	Initcall {
+		name: "executor",
+		level: Level::Driver,
+		depends_on: &["drivers"],
+		init: crate::executor::init,
+		exit: Some(crate::executor::exit),
+	},
+	// The MMIO drivers of the device tree are initialized after the executor.
+	#[cfg(target_arch = "riscv64")]
+	Initcall {
+		name: "devicetree",
+		level: Level::Driver,
+		depends_on: &["executor"],
+		init: crate::arch::riscv64::kernel::init_drivers,
+		exit: None,
+	},
+	Initcall {
+		name: "syscalls",
+		level: Level::Driver,
+		depends_on: &["drivers"],
+		init: crate::syscalls::init,
+		exit: None,
+	},
+	Initcall {
+		name: "fs",
+		level: Level::Fs,
+		depends_on: &[],
+		init: crate::fs::init,
+		exit: None,
+	},
+	Initcall {
+		name: "checkpoint",
+		level: Level::Fs,
+		depends_on: &["fs"],
+		init: crate::checkpoint::init,
+		exit: None,
+	},
+	#[cfg(feature = "pstore")]
+	Initcall {
+		name: "pstore",
+		level: Level::Fs,
+		depends_on: &["drivers"],
+		init: crate::pstore::init,
+		exit: None,
+	},
+	#[cfg(feature = "coredump")]
+	Initcall {
+		name: "coredump",
+		level: Level::Late,
+		depends_on: &["drivers"],
+		init: crate::coredump::init,
+		exit: None,
+	},
+	#[cfg(all(feature = "shell", target_arch = "x86_64"))]
+	Initcall {
+		name: "shell",
+		level: Level::Late,
+		depends_on: &["fs", "executor"],
+		init: crate::shell::init,
+		exit: None,
+	},
+	#[cfg(any(feature = "udp", feature = "vsock"))]
+	Initcall {
+		name: "metrics-push",
+		level: Level::Late,
+		depends_on: &["executor"],
+		init: crate::metrics::push::init,
+		exit: None,
+	},
+	Initcall {
+		name: "files",
+		level: Level::Late,
+		depends_on: &["fs", "executor"],
+		init: || {},
+		exit: Some(crate::fd::close_all),
+	},
+	Initcall {
+		name: "selftest",
+		level: Level::Late,
+		depends_on: &["drivers", "executor"],
+		init: crate::selftest::init,
+		exit: None,
+	},
+	#[cfg(all(
+		target_os = "none",
+		feature = "heap-profile",
+		not(feature = "common-os")
+	))]
+	Initcall {
+		name: "heap-profile",
+		level: Level::Late,
+		depends_on: &["fs"],
+		init: || {},
+		exit: Some(crate::mm::profile::dump_on_exit),
+	},
+];
+
+/// Initialized subsystems in the order of their initialization
+static INITIALIZED: InterruptTicketMutex<Vec<&'static Initcall>> =
+	InterruptTicketMutex::new(Vec::new());
+
+fn find(name: &str) -> Option<&'static Initcall> {
+	INITCALLS.iter().find(|initcall| initcall.name == name)
+}
+
+/// Returns the subsystems in the order of their initialization.
+///
+/// Panics, if the dependencies cannot be satisfied.
+fn order() -> Vec<&'static Initcall> {
+	let mut ordered: Vec<&'static Initcall> = Vec::with_capacity(INITCALLS.len());
+	let mut pending: Vec<&'static Initcall> = INITCALLS.iter().collect();
+	pending.sort_by_key(|initcall| initcall.level);
+
+	for initcall in &pending {
+		for dependency in initcall.depends_on.iter().filter_map(|name| find(name)) {
+			assert!(
+				dependency.level <= initcall.level,
+				"Subsystem {} depends on {} at the later level {:?}",
+				initcall.name,
+				dependency.name,
+				dependency.level
+			);
+		}
+	}
+
+	while !pending.is_empty() {
+		// initialize the first subsystem, whose dependencies are initialized
+		let index = pending
+			.iter()
+			.position(|initcall| {
+				initcall.depends_on.iter().all(|name| {
+					find(name).is_none_or(|dependency| {
+						ordered.iter().any(|done| core::ptr::eq(*done, dependency))
+					})
+				})
+			})
+			.unwrap_or_else(|| {
+				let names: Vec<_> = pending.iter().map(|initcall| initcall.name).collect();
+				panic!("Cyclic dependencies between the subsystems {names:?}")
+			});
+		ordered.push(pending.remove(index));
+	}
+
+	ordered
+}
+
+/// Initializes all registered subsystems.
+pub(crate) fn run() {
+	for initcall in order() {
+		debug!(
+			"Initialize subsystem {} ({:?})",
+			initcall.name, initcall.level
+		);
+		(initcall.init)();
+		INITIALIZED.lock().push(initcall);
+	}
+}
+
+/// Tears down the initialized subsystems in the reverse order.
+pub(crate) fn teardown() {
+	let initialized = core::mem::take(&mut *INITIALIZED.lock());
+	for initcall in initialized.into_iter().rev() {
+		if let Some(exit) = initcall.exit {
+			debug!("Tear down subsystem {}", initcall.name);
+			exit();
+		}
+	}
+}
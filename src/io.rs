@@ -6,6 +6,7 @@ use core::{fmt, result};
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, FromPrimitive, ToPrimitive)]
 pub enum Error {
+	EPERM = crate::errno::EPERM as isize,
 	ENOENT = crate::errno::ENOENT as isize,
 	ENOSYS = crate::errno::ENOSYS as isize,
 	EIO = crate::errno::EIO as isize,
@@ -17,16 +18,38 @@ pub enum Error {
 	EFAULT = crate::errno::EFAULT as isize,
 	ENOBUFS = crate::errno::ENOBUFS as isize,
 	ENOTCONN = crate::errno::ENOTCONN as isize,
+	EPIPE = crate::errno::EPIPE as isize,
 	ENOTDIR = crate::errno::ENOTDIR as isize,
 	EMFILE = crate::errno::EMFILE as isize,
 	EEXIST = crate::errno::EEXIST as isize,
 	EADDRINUSE = crate::errno::EADDRINUSE as isize,
 	EOVERFLOW = crate::errno::EOVERFLOW as isize,
 	ENOTSOCK = crate::errno::ENOTSOCK as isize,
+	ENAMETOOLONG = crate::errno::ENAMETOOLONG as isize,
+	EBUSY = crate::errno::EBUSY as isize,
+	ENODEV = crate::errno::ENODEV as isize,
+	ENOSPC = crate::errno::ENOSPC as isize,
+	ESRCH = crate::errno::ESRCH as isize,
+	ETIMEDOUT = crate::errno::ETIMEDOUT as isize,
+	EROFS = crate::errno::EROFS as isize,
+	EACCES = crate::errno::EACCES as isize,
+	ENOTEMPTY = crate::errno::ENOTEMPTY as isize,
+	EFBIG = crate::errno::EFBIG as isize,
+	EINTR = crate::errno::EINTR as isize,
+	EMSGSIZE = crate::errno::EMSGSIZE as isize,
+	EOPNOTSUPP = crate::errno::EOPNOTSUPP as isize,
+	EXDEV = crate::errno::EXDEV as isize,
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+impl From<Error> for i32 {
+	/// Returns the error number of `err`.
+	fn from(err: Error) -> Self {
+		num::ToPrimitive::to_i32(&err).unwrap()
+	}
+}
+
 /// The Read trait allows for reading bytes from a source.
 ///
 /// The Read trait is derived from Rust's std library.
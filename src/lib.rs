@@ -66,24 +66,41 @@ mod macros;
 mod logging;
 
 pub mod arch;
+mod checkpoint;
 mod config;
 pub mod console;
+#[cfg(feature = "coredump")]
+mod coredump;
 mod drivers;
 mod entropy;
 mod env;
 pub mod errno;
 mod executor;
 pub mod fd;
+mod fdt_overlay;
 pub mod fs;
+pub mod hostname;
 mod init_cell;
+mod initcall;
 pub mod io;
+mod metrics;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+mod mitigations;
 mod mm;
+mod percpu;
+#[cfg(feature = "pstore")]
+mod pstore;
+pub mod rlimit;
+mod sbom;
 pub mod scheduler;
+mod selftest;
 #[cfg(all(feature = "shell", target_arch = "x86_64"))]
 mod shell;
 mod synch;
 pub mod syscalls;
 pub mod time;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub mod watchpoint;
 
 hermit_entry::define_abi_tag!();
 
@@ -132,18 +149,8 @@ extern "C" fn initd(_arg: usize) {
 		info!("Hermit is running on common system!");
 	}
 
-	// Initialize Drivers
-	drivers::init();
-	crate::executor::init();
-
-	// Initialize MMIO Drivers if on riscv64
-	#[cfg(target_arch = "riscv64")]
-	riscv64::kernel::init_drivers();
-
-	syscalls::init();
-	fs::init();
-	#[cfg(all(feature = "shell", target_arch = "x86_64"))]
-	shell::init();
+	// Initialize the subsystems, e.g., drivers and file systems
+	initcall::run();
 
 	// Get the application arguments and environment variables.
 	#[cfg(not(test))]
@@ -200,6 +207,8 @@ fn boot_processor_main() -> ! {
 	info!("BSS starts at {bss_ptr:p}");
 	info!("tls_info = {:#x?}", env::boot_info().load_info.tls_info);
 	arch::boot_processor_init();
+	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+	mitigations::init();
 
 	#[cfg(not(target_arch = "riscv64"))]
 	scheduler::add_current_core();
@@ -232,6 +241,7 @@ fn boot_processor_main() -> ! {
 			scheduler::task::NORMAL_PRIO,
 			0,
 			USER_STACK_SIZE,
+			scheduler::group::NO_GROUP,
 		)
 	};
 
@@ -243,6 +253,8 @@ fn boot_processor_main() -> ! {
 #[cfg(all(target_os = "none", feature = "smp"))]
 fn application_processor_main() -> ! {
 	arch::application_processor_init();
+	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+	mitigations::init();
 	#[cfg(not(target_arch = "riscv64"))]
 	scheduler::add_current_core();
 	interrupts::enable();
@@ -263,5 +275,12 @@ fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
 	let core_id = crate::arch::core_local::core_id();
 	panic_println!("[{core_id}][PANIC] {info}\n");
 
-	crate::scheduler::shutdown(1);
+	#[cfg(feature = "pstore")]
+	pstore::save(info);
+
+	#[cfg(feature = "coredump")]
+	coredump::write(coredump::Registers::current(), coredump::SIGABRT);
+
+	// The subsystems are not torn down, because the panicking code may hold their locks.
+	crate::syscalls::shutdown_immediately(1);
 }
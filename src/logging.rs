@@ -23,6 +23,13 @@ impl log::Log for KernelLogger {
 				ColorLevel(record.level()),
 				record.args()
 			);
+			#[cfg(feature = "pstore")]
+			crate::pstore::record(format_args!(
+				"[{}][{}] {}\n",
+				crate::arch::core_local::core_id(),
+				record.level(),
+				record.args()
+			));
 		}
 	}
 }
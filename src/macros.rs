@@ -248,3 +248,22 @@ macro_rules! hermit_var_or {
 		hermit_var!($name).as_deref().unwrap_or($default)
 	};
 }
+
+/// Locks the specified mutex.
+///
+/// With the feature `sync-stats`, contention of the lock is accounted under the specified name.
+#[allow(unused_macros)]
+macro_rules! tracked_lock {
+	($mutex:expr, $name:literal) => {{
+		#[cfg(feature = "sync-stats")]
+		{
+			static STATS: $crate::synch::stats::LockStats =
+				$crate::synch::stats::LockStats::new($name);
+			STATS.lock(&$mutex)
+		}
+		#[cfg(not(feature = "sync-stats"))]
+		{
+			$mutex.lock()
+		}
+	}};
+}
@@ -0,0 +1,310 @@
+//! Registry of kernel metrics
+//!
+//! Subsystems declare their metrics as statics of the type [`Counter`],
+//! [`Gauge`] or [`Histogram`]. A metric is registered in a lock-free list,
+//! when it is updated for the first time, so metrics, which have never been
+//! updated, are not exported.
+//!
+//! All metrics are updated without locking on a per-core instance, which is
+//! allocated on the first update after all cores have been started. Before,
+//! the updates are accounted on a shared instance. A gauge accumulates the
+//! changes per core, so that its value is the sum of all instances.
+//!
+//! The metrics are readable from `/proc/prometheus` in the text exposition format
+//! of Prometheus. In addition, they can be pushed periodically to a collector
+//! (see [`push`]).
+
+#[cfg(any(feature = "udp", feature = "vsock"))]
+pub(crate) mod push;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicI64, AtomicPtr, AtomicU64, Ordering};
+
+use hermit_sync::OnceCell;
+
+use crate::percpu::{PerCpu, alloc_per_cpu};
+
+/// Number of buckets of a histogram; bucket `i` counts values of less than
+/// `2^i` and the last bucket counts all larger values.
+pub(crate) const BUCKETS: usize = 32;
+
+/// Head of the list of all metrics, which have been updated at least once
+static METRICS: AtomicPtr<Metric> = AtomicPtr::new(ptr::null_mut());
+
+/// Set, as soon as per-core instances can be allocated
+static PER_CPU_READY: AtomicBool = AtomicBool::new(false);
+
+/// Allows the allocation of per-core instances, after all cores have been started.
+pub(crate) fn init() {
+	PER_CPU_READY.store(true, Ordering::Release);
+}
+
+/// Value, which is updated on a per-core instance
+struct PerCore<T: Default> {
+	/// Instance, which is used until the cores have been started
+	shared: T,
+	per_cpu: OnceCell<PerCpu<T>>,
+}
+
+impl<T: Default> PerCore<T> {
+	const fn new(shared: T) -> Self {
+		Self {
+			shared,
+			per_cpu: OnceCell::new(),
+		}
+	}
+
+	/// Returns the instance of the current core.
+	fn get(&self) -> &T {
+		if PER_CPU_READY.load(Ordering::Acquire) {
+			self.per_cpu.get_or_init(alloc_per_cpu).get()
+		} else {
+			&self.shared
+		}
+	}
+
+	/// Returns all instances.
+	fn iter(&self) -> impl Iterator<Item = &T> {
+		let per_cpu = self.per_cpu.get().into_iter().flat_map(PerCpu::iter);
+		core::iter::once(&self.shared).chain(per_cpu.map(|(_, value)| value))
+	}
+}
+
+#[derive(Default)]
+struct HistogramCell {
+	buckets: [AtomicU64; BUCKETS],
+	sum: AtomicU64,
+}
+
+impl HistogramCell {
+	const fn new() -> Self {
+		Self {
+			buckets: [const { AtomicU64::new(0) }; BUCKETS],
+			sum: AtomicU64::new(0),
+		}
+	}
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Value {
+	Counter(PerCore<AtomicU64>),
+	Gauge(PerCore<AtomicI64>),
+	Histogram(PerCore<HistogramCell>),
+}
+
+/// Current value of a metric
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub(crate) enum Sample {
+	Counter(u64),
+	Gauge(i64),
+	Histogram {
+		/// Number of values per bucket (not cumulative)
+		buckets: [u64; BUCKETS],
+		sum: u64,
+	},
+}
+
+/// Name, description and value of a metric
+struct Metric {
+	name: &'static str,
+	help: &'static str,
+	value: Value,
+	registered: AtomicBool,
+	next: AtomicPtr<Metric>,
+}
+
+impl Metric {
+	const fn new(name: &'static str, help: &'static str, value: Value) -> Self {
+		Self {
+			name,
+			help,
+			value,
+			registered: AtomicBool::new(false),
+			next: AtomicPtr::new(ptr::null_mut()),
+		}
+	}
+
+	fn register(&'static self) {
+		if self.registered.load(Ordering::Relaxed) || self.registered.swap(true, Ordering::AcqRel) {
+			return;
+		}
+
+		let this = ptr::from_ref(self).cast_mut();
+		let mut head = METRICS.load(Ordering::Acquire);
+		loop {
+			self.next.store(head, Ordering::Relaxed);
+			match METRICS.compare_exchange_weak(head, this, Ordering::AcqRel, Ordering::Acquire) {
+				Ok(_) => break,
+				Err(current) => head = current,
+			}
+		}
+	}
+
+	fn sample(&self) -> Sample {
+		match &self.value {
+			Value::Counter(counter) => Sample::Counter(
+				counter
+					.iter()
+					.map(|count| count.load(Ordering::Relaxed))
+					.sum(),
+			),
+			Value::Gauge(gauge) => Sample::Gauge(
+				gauge
+					.iter()
+					.map(|value| value.load(Ordering::Relaxed))
+					.sum(),
+			),
+			Value::Histogram(histogram) => {
+				let mut buckets = [0; BUCKETS];
+				let mut sum = 0;
+				for cell in histogram.iter() {
+					for (total, bucket) in buckets.iter_mut().zip(&cell.buckets) {
+						*total += bucket.load(Ordering::Relaxed);
+					}
+					sum += cell.sum.load(Ordering::Relaxed);
+				}
+				Sample::Histogram { buckets, sum }
+			}
+		}
+	}
+}
+
+/// Monotonically increasing count of events
+pub(crate) struct Counter(Metric);
+
+impl Counter {
+	pub const fn new(name: &'static str, help: &'static str) -> Self {
+		Self(Metric::new(
+			name,
+			help,
+			Value::Counter(PerCore::new(AtomicU64::new(0))),
+		))
+	}
+
+	pub fn add(&'static self, count: u64) {
+		self.0.register();
+		let Value::Counter(counter) = &self.0.value else {
+			unreachable!()
+		};
+		counter.get().fetch_add(count, Ordering::Relaxed);
+	}
+
+	pub fn inc(&'static self) {
+		self.add(1);
+	}
+
+	/// Returns the sum of all per-core counts.
+	pub fn get(&self) -> u64 {
+		match self.0.sample() {
+			Sample::Counter(count) => count,
+			Sample::Gauge(_) | Sample::Histogram { .. } => unreachable!(),
+		}
+	}
+}
+
+/// Value, which may go up and down
+pub(crate) struct Gauge(Metric);
+
+impl Gauge {
+	pub const fn new(name: &'static str, help: &'static str) -> Self {
+		Self(Metric::new(
+			name,
+			help,
+			Value::Gauge(PerCore::new(AtomicI64::new(0))),
+		))
+	}
+
+	pub fn add(&'static self, delta: i64) {
+		self.0.register();
+		let Value::Gauge(gauge) = &self.0.value else {
+			unreachable!()
+		};
+		gauge.get().fetch_add(delta, Ordering::Relaxed);
+	}
+
+	/// Returns the sum of all per-core changes.
+	pub fn get(&self) -> i64 {
+		match self.0.sample() {
+			Sample::Gauge(value) => value,
+			Sample::Counter(_) | Sample::Histogram { .. } => unreachable!(),
+		}
+	}
+}
+
+/// Distribution of values in buckets of powers of two, e.g., of latencies or sizes
+pub(crate) struct Histogram(Metric);
+
+impl Histogram {
+	pub const fn new(name: &'static str, help: &'static str) -> Self {
+		Self(Metric::new(
+			name,
+			help,
+			Value::Histogram(PerCore::new(HistogramCell::new())),
+		))
+	}
+
+	pub fn observe(&'static self, value: u64) {
+		self.0.register();
+		let Value::Histogram(histogram) = &self.0.value else {
+			unreachable!()
+		};
+		let cell = histogram.get();
+		let bucket = (u64::BITS - value.leading_zeros()) as usize;
+		cell.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+		cell.sum.fetch_add(value, Ordering::Relaxed);
+	}
+}
+
+/// Returns the name, description and current value of all registered metrics, sorted by name.
+pub(crate) fn samples() -> Vec<(&'static str, &'static str, Sample)> {
+	let mut samples = Vec::new();
+	let mut current = METRICS.load(Ordering::Acquire);
+	while let Some(metric) = unsafe { current.as_ref() } {
+		samples.push((metric.name, metric.help, metric.sample()));
+		current = metric.next.load(Ordering::Acquire);
+	}
+	samples.sort_unstable_by_key(|(name, ..)| *name);
+	samples
+}
+
+/// Returns the metrics in the text exposition format of Prometheus.
+pub(crate) fn report() -> String {
+	let mut out = String::new();
+	// writing to a `String` does not fail
+	format_report(&mut out).unwrap();
+	out
+}
+
+fn format_report(out: &mut String) -> core::fmt::Result {
+	for (name, help, sample) in samples() {
+		writeln!(out, "# HELP {name} {help}")?;
+		match sample {
+			Sample::Counter(count) => {
+				writeln!(out, "# TYPE {name} counter")?;
+				writeln!(out, "{name} {count}")?;
+			}
+			Sample::Gauge(value) => {
+				writeln!(out, "# TYPE {name} gauge")?;
+				writeln!(out, "{name} {value}")?;
+			}
+			Sample::Histogram { buckets, sum } => {
+				writeln!(out, "# TYPE {name} histogram")?;
+				let mut count = 0;
+				for (i, bucket) in buckets[..BUCKETS - 1].iter().enumerate() {
+					count += bucket;
+					writeln!(out, "{name}_bucket{{le=\"{}\"}} {count}", (1u64 << i) - 1)?;
+				}
+				count += buckets[BUCKETS - 1];
+				writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}")?;
+				writeln!(out, "{name}_sum {sum}")?;
+				writeln!(out, "{name}_count {count}")?;
+			}
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,204 @@
+//! Periodic push of the metrics to a collector
+//!
+//! If `HERMIT_METRICS_PUSH` is set, the metrics are sent every
+//! `HERMIT_METRICS_INTERVAL` seconds (default 10) to a collector:
+//!
+//! - `udp:<address>:<port>` sends the metrics as statsd datagrams. Counters
+//!   are sent as the increments since the last push, histograms as the
+//!   increments of their count and sum.
+//! - `vsock:<cid>:<port>` writes the metrics in the text exposition format of
+//!   Prometheus to a stream connection, as they are readable from `/proc/prometheus`.
+
+#[cfg(feature = "udp")]
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+#[cfg(feature = "udp")]
+use alloc::vec::Vec;
+#[cfg(feature = "udp")]
+use core::fmt::Write;
+use core::future;
+use core::task::Poll;
+
+#[cfg(feature = "udp")]
+use super::Sample;
+use crate::arch::kernel::processor;
+use crate::fd::ObjectInterface;
+use crate::{executor, io};
+
+/// Default interval between two pushes in seconds
+const DEFAULT_INTERVAL: u64 = 10;
+
+/// Maximum size of a statsd datagram, which is not fragmented on common links
+#[cfg(feature = "udp")]
+const MAX_DATAGRAM: usize = 1400;
+
+/// Format, in which the metrics are pushed
+#[derive(Debug, Copy, Clone)]
+enum Format {
+	#[cfg(feature = "udp")]
+	Statsd,
+	#[cfg(feature = "vsock")]
+	Prometheus,
+}
+
+/// Starts pushing the metrics, if a collector is configured.
+pub(crate) fn init() {
+	let Some(target) = hermit_var!("HERMIT_METRICS_PUSH") else {
+		return;
+	};
+	let interval = match hermit_var!("HERMIT_METRICS_INTERVAL") {
+		Some(interval) => match interval.parse::<u64>() {
+			Ok(interval) if interval > 0 => interval,
+			_ => {
+				warn!("Invalid metrics interval {interval}");
+				return;
+			}
+		},
+		None => DEFAULT_INTERVAL,
+	};
+
+	let target = String::from(target);
+	info!("Push metrics to {target} every {interval} s");
+	executor::spawn(async move {
+		if let Err(err) = run(&target, interval * 1_000_000).await {
+			error!("Unable to push metrics to {target}: {err:?}");
+		}
+	});
+}
+
+async fn connect(target: &str) -> io::Result<(Arc<dyn ObjectInterface>, Format)> {
+	#[cfg(feature = "udp")]
+	if let Some(endpoint) = target.strip_prefix("udp:") {
+		use core::str::FromStr;
+
+		use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
+
+		use crate::executor::network::NIC;
+		use crate::fd::socket::{get_ephemeral_port, udp};
+		use crate::fd::{Endpoint, ListenEndpoint};
+
+		let endpoint = IpEndpoint::from_str(endpoint).map_err(|()| io::Error::EINVAL)?;
+		let handle = NIC
+			.lock()
+			.as_nic_mut()
+			.map_err(|_| io::Error::ENODEV)?
+			.create_udp_handle()
+			.map_err(|()| io::Error::ENOBUFS)?;
+		let socket: Arc<dyn ObjectInterface> =
+			Arc::new(async_lock::RwLock::new(udp::Socket::new(handle)));
+		socket
+			.bind(ListenEndpoint::Ip(IpListenEndpoint {
+				addr: None,
+				port: get_ephemeral_port(),
+			}))
+			.await?;
+		socket.connect(Endpoint::Ip(endpoint)).await?;
+		return Ok((socket, Format::Statsd));
+	}
+
+	#[cfg(feature = "vsock")]
+	if let Some(endpoint) = target.strip_prefix("vsock:") {
+		use crate::fd::Endpoint;
+		use crate::fd::socket::vsock::{Socket, VsockEndpoint};
+
+		let (cid, port) = endpoint.split_once(':').ok_or(io::Error::EINVAL)?;
+		let cid = cid.parse().map_err(|_| io::Error::EINVAL)?;
+		let port = port.parse().map_err(|_| io::Error::EINVAL)?;
+		let socket: Arc<dyn ObjectInterface> = Arc::new(async_lock::RwLock::new(Socket::new()));
+		socket
+			.connect(Endpoint::Vsock(VsockEndpoint::new(port, cid)))
+			.await?;
+		return Ok((socket, Format::Prometheus));
+	}
+
+	Err(io::Error::EINVAL)
+}
+
+/// Waits until the timer reaches `deadline` (in microseconds since boot).
+///
+/// The executor polls its tasks regularly, so the task does not have to be woken.
+async fn sleep_until(deadline: u64) {
+	future::poll_fn(|_cx| {
+		if processor::get_timer_ticks() >= deadline {
+			Poll::Ready(())
+		} else {
+			Poll::Pending
+		}
+	})
+	.await;
+}
+
+async fn run(target: &str, interval: u64) -> io::Result<()> {
+	let (socket, format) = connect(target).await?;
+	// values of the counters at the last push
+	#[cfg(feature = "udp")]
+	let mut previous = BTreeMap::new();
+	let mut deadline = processor::get_timer_ticks();
+
+	loop {
+		deadline += interval;
+		sleep_until(deadline).await;
+
+		match format {
+			#[cfg(feature = "udp")]
+			Format::Statsd => {
+				for datagram in statsd(&mut previous) {
+					socket.write(datagram.as_bytes()).await?;
+				}
+			}
+			#[cfg(feature = "vsock")]
+			Format::Prometheus => {
+				let report = super::report();
+				let mut data = report.as_bytes();
+				while !data.is_empty() {
+					match socket.write(data).await? {
+						0 => return Err(io::Error::EPIPE),
+						len => data = &data[len..],
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Returns the metrics as statsd datagrams.
+///
+/// `previous` contains the values of the counters at the last push, from which
+/// the increments are computed.
+#[cfg(feature = "udp")]
+fn statsd(previous: &mut BTreeMap<(&'static str, &'static str), u64>) -> Vec<String> {
+	let mut datagrams = Vec::new();
+	let mut datagram = String::new();
+	let mut push_line = |line: String| {
+		if !datagram.is_empty() && datagram.len() + line.len() > MAX_DATAGRAM {
+			datagrams.push(core::mem::take(&mut datagram));
+		}
+		datagram.push_str(&line);
+	};
+	let mut increment = |name: &'static str, suffix: &'static str, value: u64| {
+		let last = previous.insert((name, suffix), value).unwrap_or(0);
+		value.wrapping_sub(last)
+	};
+
+	for (name, _, sample) in super::samples() {
+		let mut line = String::new();
+		match sample {
+			Sample::Counter(count) => {
+				writeln!(line, "{name}:{}|c", increment(name, "", count)).unwrap();
+			}
+			Sample::Gauge(value) => writeln!(line, "{name}:{value}|g").unwrap(),
+			Sample::Histogram { buckets, sum } => {
+				let count = buckets.iter().sum();
+				writeln!(line, "{name}.count:{}|c", increment(name, "count", count)).unwrap();
+				writeln!(line, "{name}.sum:{}|c", increment(name, "sum", sum)).unwrap();
+			}
+		}
+		push_line(line);
+	}
+
+	if !datagram.is_empty() {
+		datagrams.push(datagram);
+	}
+	datagrams
+}
@@ -0,0 +1,108 @@
+//! Mitigations against speculative execution attacks
+//!
+//! The application and the kernel share a single address space and privilege
+//! level. Nevertheless, an application, which runs untrusted code, may want to
+//! prevent tasks from influencing the speculative execution of other tasks.
+//! All mitigations are disabled by default and are enabled by the kernel
+//! argument `mitigations=<name>,...`:
+//!
+//! - `auto` enables all mitigations, which are supported by the processor.
+//! - `off` disables all mitigations, which have been enabled before.
+//! - `<name>` enables and `no<name>` disables a single mitigation.
+//!
+//! The active mitigations are reported by `/proc/mitigations`.
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use hermit_sync::Lazy;
+
+use crate::arch::kernel::mitigations as arch_mitigations;
+use crate::env;
+
+bitflags! {
+	/// Set of mitigations
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub(crate) struct Mitigations: u32 {
+		/// Indirect Branch Restricted Speculation (x86_64)
+		const IBRS = 1 << 0;
+		/// Indirect Branch Prediction Barrier on every task switch (x86_64)
+		const IBPB = 1 << 1;
+		/// Single Thread Indirect Branch Predictors (x86_64)
+		const STIBP = 1 << 2;
+		/// Return Stack Buffer stuffing on every task switch (x86_64)
+		const RSB = 1 << 3;
+		/// Speculative Store Bypass Safe (AArch64)
+		const SSBS = 1 << 4;
+	}
+}
+
+/// Mitigations, which are requested and supported by the processor
+static ACTIVE: Lazy<Mitigations> = Lazy::new(|| {
+	let requested = requested();
+	let supported = arch_mitigations::supported();
+	let unsupported = requested.difference(supported);
+	if !unsupported.is_empty() {
+		warn!("Mitigations are not supported by the processor: {unsupported:?}");
+	}
+	requested.intersection(supported)
+});
+
+/// Returns the mitigations, which are requested by `mitigations=<name>,...`.
+fn requested() -> Mitigations {
+	let mut requested = Mitigations::empty();
+	for option in env::mitigations() {
+		match option.as_str() {
+			"auto" => requested = Mitigations::all(),
+			"off" => requested = Mitigations::empty(),
+			option => {
+				let (enable, name) = match option.strip_prefix("no") {
+					Some(name) => (false, name),
+					None => (true, option),
+				};
+				let Some(mitigation) = Mitigations::from_name(&name.to_ascii_uppercase()) else {
+					error!("Unknown mitigation: {option}");
+					continue;
+				};
+				requested.set(mitigation, enable);
+			}
+		}
+	}
+	requested
+}
+
+/// Returns the active mitigations.
+pub(crate) fn active() -> Mitigations {
+	*ACTIVE
+}
+
+/// Enables the active mitigations on the current core.
+pub(crate) fn init() {
+	let active = active();
+	arch_mitigations::enable(active);
+	debug!("Enabled mitigations: {active:?}");
+}
+
+/// Applies the mitigations, which are required on a switch to another task.
+#[inline]
+pub(crate) fn task_switch() {
+	arch_mitigations::task_switch(active());
+}
+
+/// Returns the state of all mitigations.
+pub(crate) fn report() -> String {
+	let active = active();
+	let supported = arch_mitigations::supported();
+	let mut report = String::new();
+	for (name, mitigation) in Mitigations::all().iter_names() {
+		let state = if active.contains(mitigation) {
+			"active"
+		} else if supported.contains(mitigation) {
+			"inactive"
+		} else {
+			"unsupported"
+		};
+		writeln!(report, "{}\t{state}", name.to_ascii_lowercase()).unwrap();
+	}
+	report
+}
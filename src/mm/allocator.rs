@@ -1,30 +1,148 @@
 //! Implementation of the Hermit Allocator for dynamically allocating heap memory
 //! in the kernel.
+//!
+//! Small blocks of up to [`CACHE_CLASSES`] cache lines are freed into a
+//! per-core cache, from which the same core allocates them again without
+//! taking the lock of the heap. The caches are enabled by [`LockedAllocator::init_caches`],
+//! after all cores have been started, and are released to the heap under
+//! memory pressure.
 
 use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
 
-use hermit_sync::RawInterruptTicketMutex;
+use hermit_sync::{InterruptTicketMutex, OnceCell, RawInterruptTicketMutex};
 use talc::{ErrOnOom, Span, Talc, Talck};
 
-pub struct LockedAllocator(Talck<RawInterruptTicketMutex, ErrOnOom>);
+use crate::mm::oom::HeapState;
+use crate::mm::pressure::Shrinker;
+use crate::percpu::{PerCpu, alloc_per_cpu};
+
+/// Minimal alignment of all allocations, which avoids false sharing
+const CACHE_LINE: usize = core::mem::align_of::<crossbeam_utils::CachePadded<u8>>();
+/// Number of size classes of the per-core caches; class `i` holds blocks of `i + 1` cache lines.
+const CACHE_CLASSES: usize = 8;
+/// Maximum number of free blocks per size class and core
+const CACHE_DEPTH: usize = 32;
+
+/// Free blocks of a size class
+struct SizeClass {
+	blocks: [*mut u8; CACHE_DEPTH],
+	len: usize,
+}
+
+impl Default for SizeClass {
+	fn default() -> Self {
+		Self {
+			blocks: [ptr::null_mut(); CACHE_DEPTH],
+			len: 0,
+		}
+	}
+}
+
+// The free blocks are owned by the cache and can be handed to any core.
+unsafe impl Send for SizeClass {}
+
+/// Free blocks of a core
+#[derive(Default)]
+struct CoreCache {
+	classes: [SizeClass; CACHE_CLASSES],
+}
+
+/// Returns the size class of the aligned `layout`, if its blocks are cached.
+fn size_class(layout: Layout) -> Option<usize> {
+	(layout.align() == CACHE_LINE
+		&& layout.size() > 0
+		&& layout.size() <= CACHE_CLASSES * CACHE_LINE)
+		.then(|| layout.size().div_ceil(CACHE_LINE) - 1)
+}
+
+/// Returns the layout of the blocks of the size class `class`.
+fn class_layout(class: usize) -> Layout {
+	Layout::from_size_align((class + 1) * CACHE_LINE, CACHE_LINE).unwrap()
+}
+
+pub struct LockedAllocator {
+	heap: Talck<RawInterruptTicketMutex, ErrOnOom>,
+	caches: OnceCell<PerCpu<InterruptTicketMutex<CoreCache>>>,
+}
 
 impl LockedAllocator {
 	pub const fn new() -> Self {
-		Self(Talc::new(ErrOnOom).lock())
+		Self {
+			heap: Talc::new(ErrOnOom).lock(),
+			caches: OnceCell::new(),
+		}
 	}
 
 	#[inline]
 	fn align_layout(layout: Layout) -> Layout {
-		let align = layout
-			.align()
-			.max(core::mem::align_of::<crossbeam_utils::CachePadded<u8>>());
+		let align = layout.align().max(CACHE_LINE);
 		Layout::from_size_align(layout.size(), align).unwrap()
 	}
 
+	/// Retries a failed allocation after clean caches have been dropped.
+	///
+	/// If the allocation fails nevertheless, the failure is reported.
+	#[inline]
+	fn retry_on_oom(&self, layout: Layout, mut alloc: impl FnMut() -> *mut u8) -> *mut u8 {
+		let mut ptr = alloc();
+		if ptr.is_null() && crate::mm::pressure::relieve(layout.size()) > 0 {
+			ptr = alloc();
+		}
+		if ptr.is_null() {
+			crate::mm::oom::out_of_memory(layout, self.heap_state());
+		}
+		ptr
+	}
+
+	/// Returns a cached block of the size class `class` of the current core.
+	fn pop_cached(&self, class: usize) -> Option<*mut u8> {
+		let mut cache = self.caches.get()?.get().lock();
+		let class = &mut cache.classes[class];
+		class.len = class.len.checked_sub(1)?;
+		Some(class.blocks[class.len])
+	}
+
+	/// Puts the free block `ptr` of the size class `class` into the cache of
+	/// the current core and returns `false`, if the cache is full.
+	fn push_cached(&self, class: usize, ptr: *mut u8) -> bool {
+		let Some(caches) = self.caches.get() else {
+			return false;
+		};
+		let mut cache = caches.get().lock();
+		let class = &mut cache.classes[class];
+		if class.len == CACHE_DEPTH {
+			return false;
+		}
+		class.blocks[class.len] = ptr;
+		class.len += 1;
+		true
+	}
+
+	/// Enables the per-core caches.
+	///
+	/// The caches have to be enabled after all cores have been started.
+	pub fn init_caches(&self) {
+		self.caches.get_or_init(alloc_per_cpu);
+	}
+
+	/// Returns the current usage of the heap.
+	pub fn heap_state(&self) -> HeapState {
+		let talc = self.heap.lock();
+		let counters = talc.get_counters();
+		HeapState {
+			allocated_bytes: counters.allocated_bytes,
+			allocation_count: counters.allocation_count,
+			available_bytes: counters.available_bytes,
+			fragment_count: counters.fragment_count,
+			claimed_bytes: counters.claimed_bytes,
+		}
+	}
+
 	pub unsafe fn init(&self, heap_bottom: *mut u8, heap_size: usize) {
 		let arena = Span::from_base_size(heap_bottom, heap_size);
 		unsafe {
-			self.0.lock().claim(arena).unwrap();
+			self.heap.lock().claim(arena).unwrap();
 		}
 	}
 }
@@ -34,22 +152,108 @@ impl LockedAllocator {
 unsafe impl GlobalAlloc for LockedAllocator {
 	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
 		let layout = Self::align_layout(layout);
-		unsafe { self.0.alloc(layout) }
+		if let Some(class) = size_class(layout) {
+			if let Some(ptr) = self.pop_cached(class) {
+				return ptr;
+			}
+			let layout = class_layout(class);
+			return self.retry_on_oom(layout, || unsafe { self.heap.alloc(layout) });
+		}
+
+		self.retry_on_oom(layout, || unsafe { self.heap.alloc(layout) })
 	}
 
 	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
 		let layout = Self::align_layout(layout);
-		unsafe { self.0.dealloc(ptr, layout) }
+		if let Some(class) = size_class(layout) {
+			if !self.push_cached(class, ptr) {
+				unsafe { self.heap.dealloc(ptr, class_layout(class)) }
+			}
+			return;
+		}
+
+		unsafe { self.heap.dealloc(ptr, layout) }
 	}
 
 	unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
 		let layout = Self::align_layout(layout);
-		unsafe { self.0.alloc_zeroed(layout) }
+		if let Some(class) = size_class(layout) {
+			if let Some(ptr) = self.pop_cached(class) {
+				unsafe {
+					ptr.write_bytes(0, layout.size());
+				}
+				return ptr;
+			}
+			let layout = class_layout(class);
+			return self.retry_on_oom(layout, || unsafe { self.heap.alloc_zeroed(layout) });
+		}
+
+		self.retry_on_oom(layout, || unsafe { self.heap.alloc_zeroed(layout) })
 	}
 
 	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
 		let layout = Self::align_layout(layout);
-		unsafe { self.0.realloc(ptr, layout, new_size) }
+		let new_layout = Layout::from_size_align(new_size, layout.align()).unwrap();
+
+		// Blocks of the size classes are larger than requested, so that they
+		// are moved instead of being resized by the heap.
+		if size_class(layout).is_some() || size_class(new_layout).is_some() {
+			let new_ptr = unsafe { self.alloc(new_layout) };
+			if !new_ptr.is_null() {
+				unsafe {
+					ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+					self.dealloc(ptr, layout);
+				}
+			}
+			return new_ptr;
+		}
+
+		self.retry_on_oom(new_layout, || unsafe {
+			self.heap.realloc(ptr, layout, new_size)
+		})
+	}
+}
+
+impl Shrinker for LockedAllocator {
+	fn name(&self) -> &'static str {
+		"allocator caches"
+	}
+
+	fn cached_bytes(&self) -> usize {
+		let Some(caches) = self.caches.get() else {
+			return 0;
+		};
+		caches
+			.iter()
+			.map(|(_, cache)| {
+				let cache = cache.lock();
+				(0..CACHE_CLASSES)
+					.map(|class| cache.classes[class].len * class_layout(class).size())
+					.sum::<usize>()
+			})
+			.sum()
+	}
+
+	fn shrink(&self, target: usize) -> usize {
+		let Some(caches) = self.caches.get() else {
+			return 0;
+		};
+
+		let mut released = 0;
+		for (_, cache) in caches.iter() {
+			let mut cache = cache.lock();
+			for (class, blocks) in cache.classes.iter_mut().enumerate() {
+				let layout = class_layout(class);
+				while released < target && blocks.len > 0 {
+					blocks.len -= 1;
+					unsafe {
+						self.heap.dealloc(blocks.blocks[blocks.len], layout);
+					}
+					released += layout.size();
+				}
+			}
+		}
+		released
 	}
 }
 
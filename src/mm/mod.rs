@@ -1,14 +1,23 @@
 pub mod allocator;
 pub mod device_alloc;
+pub(crate) mod oom;
+pub(crate) mod physmap;
+pub(crate) mod pressure;
+#[cfg(all(
+	target_os = "none",
+	feature = "heap-profile",
+	not(feature = "common-os")
+))]
+pub(crate) mod profile;
 
 use core::mem;
 use core::ops::Range;
 
 use align_address::Align;
 use hermit_sync::Lazy;
-#[cfg(feature = "pci")]
-use memory_addresses::PhysAddr;
-use memory_addresses::VirtAddr;
+#[cfg(feature = "coredump")]
+use hermit_sync::OnceCell;
+use memory_addresses::{PhysAddr, VirtAddr};
 
 use self::allocator::LockedAllocator;
 #[cfg(any(target_arch = "x86_64", target_arch = "riscv64"))]
@@ -42,6 +51,15 @@ pub(crate) fn kernel_end_address() -> VirtAddr {
 	KERNEL_ADDR_RANGE.end
 }
 
+/// Virtual address range of the heap
+#[cfg(feature = "coredump")]
+static HEAP_ADDR_RANGE: OnceCell<Range<VirtAddr>> = OnceCell::new();
+
+#[cfg(feature = "coredump")]
+pub(crate) fn heap_address_range() -> Option<Range<VirtAddr>> {
+	HEAP_ADDR_RANGE.get().cloned()
+}
+
 #[cfg(target_os = "none")]
 pub(crate) fn init() {
 	use crate::arch::mm::paging;
@@ -49,6 +67,10 @@ pub(crate) fn init() {
 	Lazy::force(&KERNEL_ADDR_RANGE);
 
 	arch::mm::init();
+	// The memory of the boot modules must be reserved, before the frame
+	// allocator hands out memory, e.g., for page tables.
+	crate::fs::initrd::reserve();
+	crate::fdt_overlay::reserve();
 	arch::mm::init_page_tables();
 
 	let total_mem = physicalmem::total_memory_size();
@@ -238,13 +260,23 @@ pub(crate) fn init() {
 			(heap_end_addr - heap_start_addr) as usize,
 		);
 	}
+	#[cfg(feature = "coredump")]
+	HEAP_ADDR_RANGE.set(heap_start_addr..heap_end_addr).unwrap();
 
 	info!("Heap is located at {heap_start_addr:p}..{heap_end_addr:p} ({map_size} Bytes unmapped)");
 }
 
+/// Enables the per-core caches of the allocator, after all cores have been started.
+pub(crate) fn init_caches() {
+	#[cfg(target_os = "none")]
+	ALLOCATOR.init_caches();
+}
+
 pub(crate) fn print_information() {
 	arch::mm::physicalmem::print_information();
 	arch::mm::virtualmem::print_information();
+	physmap::print_information();
+	pressure::print_information();
 }
 
 /// Soft-deprecated in favor of `DeviceAlloc`
@@ -284,7 +316,6 @@ pub(crate) fn deallocate(virtual_address: VirtAddr, size: usize) {
 }
 
 /// Maps a given physical address and size in virtual space and returns address.
-#[cfg(feature = "pci")]
 pub(crate) fn map(
 	physical_address: PhysAddr,
 	size: usize,
@@ -313,7 +344,6 @@ pub(crate) fn map(
 	virtual_address
 }
 
-#[allow(dead_code)]
 /// unmaps virtual address, without 'freeing' physical memory it is mapped to!
 pub(crate) fn unmap(virtual_address: VirtAddr, size: usize) {
 	let size = size.align_up(BasePageSize::SIZE as usize);
@@ -0,0 +1,173 @@
+//! Handling of allocation failures
+//!
+//! If the allocator cannot satisfy a request even after the reclaimable caches
+//! are dropped, the kernel logs a report of the allocator state and notifies
+//! the application. The application receives these notifications by a
+//! descriptor of `sys_oom_eventfd`, which becomes readable after an allocation
+//! failure. Like an eventfd, a read returns the number of failures since the
+//! last read as a 64-bit integer.
+//!
+//! The failing allocation itself is not retried. Because the kernel and the
+//! application share the heap, there is no task group, which could be killed
+//! to release memory.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Poll, Waker};
+use core::{future, mem};
+
+use async_trait::async_trait;
+use hermit_sync::InterruptTicketMutex;
+
+use crate::fd::{self, FileDescriptor, ObjectInterface, PollEvent};
+use crate::{executor, io};
+
+/// Number of allocation failures since boot
+static EVENTS: AtomicU64 = AtomicU64::new(0);
+/// Set, if the waiting tasks have not yet been notified about a failure
+static PENDING: AtomicBool = AtomicBool::new(false);
+/// Set, while a failure is handled to avoid recursion
+static HANDLING: AtomicBool = AtomicBool::new(false);
+/// Set, if the notifier has been spawned on the executor
+static NOTIFIER: AtomicBool = AtomicBool::new(false);
+/// Tasks, which wait for an allocation failure
+static WAITERS: InterruptTicketMutex<Vec<Waker>> = InterruptTicketMutex::new(Vec::new());
+
+/// Usage of the heap
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct HeapState {
+	pub allocated_bytes: usize,
+	pub allocation_count: usize,
+	pub available_bytes: usize,
+	pub fragment_count: usize,
+	pub claimed_bytes: usize,
+}
+
+/// Handles a failed allocation of `layout`.
+///
+/// This function is called by the allocator and therefore must not allocate memory.
+/// Waking up the waiting tasks is left to the executor.
+pub(crate) fn out_of_memory(layout: Layout, heap: HeapState) {
+	if HANDLING.swap(true, Ordering::Acquire) {
+		return;
+	}
+
+	error!(
+		"Out of memory: unable to allocate {} bytes (alignment {})",
+		layout.size(),
+		layout.align()
+	);
+	error!(
+		"Heap: {} KiB allocated in {} allocations, {} KiB available in {} fragments, {} KiB claimed",
+		heap.allocated_bytes >> 10,
+		heap.allocation_count,
+		heap.available_bytes >> 10,
+		heap.fragment_count,
+		heap.claimed_bytes >> 10
+	);
+	error!(
+		"Reclaimable caches: {} KiB",
+		crate::mm::pressure::cached_bytes() >> 10
+	);
+
+	EVENTS.fetch_add(1, Ordering::Release);
+	PENDING.store(true, Ordering::Release);
+	HANDLING.store(false, Ordering::Release);
+}
+
+/// Returns the number of allocation failures since boot.
+pub(crate) fn events() -> u64 {
+	EVENTS.load(Ordering::Acquire)
+}
+
+/// Wakes up the waiting tasks after an allocation failure.
+async fn notifier() {
+	future::poll_fn(|_cx| {
+		if PENDING.swap(false, Ordering::AcqRel) {
+			for waker in mem::take(&mut *WAITERS.lock()) {
+				waker.wake();
+			}
+		}
+		Poll::<()>::Pending
+	})
+	.await;
+}
+
+#[derive(Debug)]
+struct OomEventFd {
+	/// Number of failures, which are already reported by this descriptor
+	seen: AtomicU64,
+}
+
+impl OomEventFd {
+	/// Returns the number of failures, which are not yet reported, and registers
+	/// the task for a wakeup, if there is none.
+	fn poll_unseen(&self, waker: &Waker) -> u64 {
+		let unseen = events() - self.seen.load(Ordering::Relaxed);
+		if unseen > 0 {
+			return unseen;
+		}
+
+		let mut waiters = WAITERS.lock();
+		// a task, which polls repeatedly, is registered only once
+		if !waiters.iter().any(|w| w.will_wake(waker)) {
+			waiters.push(waker.clone());
+		}
+		drop(waiters);
+		// check again to avoid a lost wakeup
+		events() - self.seen.load(Ordering::Relaxed)
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for OomEventFd {
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let len = mem::size_of::<u64>();
+		if buf.len() < len {
+			return Err(io::Error::EINVAL);
+		}
+
+		future::poll_fn(|cx| {
+			let unseen = self.poll_unseen(cx.waker());
+			if unseen > 0 {
+				self.seen.fetch_add(unseen, Ordering::Relaxed);
+				buf[..len].copy_from_slice(&unseen.to_ne_bytes());
+				Poll::Ready(Ok(len))
+			} else {
+				Poll::Pending
+			}
+		})
+		.await
+	}
+
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		let readable = PollEvent::POLLIN | PollEvent::POLLRDNORM;
+		if !event.intersects(readable) {
+			return Ok(PollEvent::empty());
+		}
+
+		future::poll_fn(|cx| {
+			if self.poll_unseen(cx.waker()) > 0 {
+				Poll::Ready(Ok(event & readable))
+			} else {
+				Poll::Pending
+			}
+		})
+		.await
+	}
+}
+
+/// Creates a descriptor, which becomes readable after an allocation failure.
+pub(crate) fn eventfd() -> io::Result<FileDescriptor> {
+	if !NOTIFIER.swap(true, Ordering::AcqRel) {
+		executor::spawn(notifier());
+	}
+
+	let obj: Arc<dyn ObjectInterface> = Arc::new(OomEventFd {
+		seen: AtomicU64::new(events()),
+	});
+	fd::insert_object(obj)
+}
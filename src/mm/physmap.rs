@@ -0,0 +1,226 @@
+//! Map of the physical address space
+//!
+//! The memory detection and the PCI subsystem enter the regions, which they
+//! find, into a single map. RAM, which overlaps a device, would be handed out
+//! by the page allocator and corrupt the device or the allocated memory in a
+//! way, which is hard to debug. Therefore, each region is validated against
+//! the known regions and the kernel stops with a description of both regions,
+//! if the map is inconsistent.
+//!
+//! Firmware maps often contain RAM, which overlaps reserved regions. In this
+//! case, the reserved region wins and only the remaining parts are kept as RAM.
+//!
+//! The map lives in static memory, because the memory detection runs before
+//! the heap is available.
+
+use core::fmt;
+use core::ops::Range;
+
+use hermit_sync::InterruptTicketMutex;
+use memory_addresses::PhysAddr;
+
+/// Maximum number of regions in the map
+const MAX_REGIONS: usize = 64;
+
+/// Type of a region in the physical address space
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum RegionKind {
+	/// Memory, which may be used by the page allocator
+	Ram,
+	/// Memory or a hole, which is reserved by the firmware
+	Reserved,
+	/// Enhanced configuration space of a PCI host bridge (ECAM)
+	Ecam,
+	/// Address window of a PCI host bridge, which contains the BARs of its devices
+	PciWindow,
+	/// Registers or memory of a device
+	Mmio,
+}
+
+impl RegionKind {
+	/// Returns whether a region of this kind may overlap a region of kind `other`.
+	fn may_overlap(self, other: Self) -> bool {
+		match (self, other) {
+			(Self::Reserved, _) | (_, Self::Reserved) | (Self::Ram, Self::Ram) => true,
+			(Self::Ram, _) | (_, Self::Ram) => false,
+			(Self::PciWindow, Self::Mmio) | (Self::Mmio, Self::PciWindow) => true,
+			_ => false,
+		}
+	}
+}
+
+impl fmt::Display for RegionKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Ram => f.write_str("RAM"),
+			Self::Reserved => f.write_str("reserved"),
+			Self::Ecam => f.write_str("ECAM"),
+			Self::PciWindow => f.write_str("PCI window"),
+			Self::Mmio => f.write_str("MMIO"),
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Region {
+	pub start: PhysAddr,
+	pub end: PhysAddr,
+	pub kind: RegionKind,
+	/// Origin of the region, e.g., the boot information or the device
+	pub owner: &'static str,
+}
+
+impl Region {
+	fn overlaps(&self, other: &Self) -> bool {
+		self.start < other.end && other.start < self.end
+	}
+
+	/// Returns the parts of the region, which are not covered by `hole`.
+	fn without(self, hole: &Self) -> impl Iterator<Item = Self> {
+		let (front, back) = if self.overlaps(hole) {
+			(
+				(self.start < hole.start).then_some(Self {
+					end: hole.start,
+					..self
+				}),
+				(hole.end < self.end).then_some(Self {
+					start: hole.end,
+					..self
+				}),
+			)
+		} else {
+			(Some(self), None)
+		};
+		front.into_iter().chain(back)
+	}
+}
+
+impl fmt::Display for Region {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} {:p}..{:p} ({})",
+			self.kind, self.start, self.end, self.owner
+		)
+	}
+}
+
+/// Overlap of a new region with a known region
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Conflict {
+	pub new: Region,
+	pub existing: Region,
+}
+
+impl fmt::Display for Conflict {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} overlaps {}", self.new, self.existing)
+	}
+}
+
+/// Known regions, which are sorted by their start address
+static MAP: InterruptTicketMutex<heapless::Vec<Region, MAX_REGIONS>> =
+	InterruptTicketMutex::new(heapless::Vec::new());
+
+type Map = heapless::Vec<Region, MAX_REGIONS>;
+
+/// Enters `region` into `map` and keeps the map sorted.
+fn insert_sorted(map: &mut Map, region: Region) {
+	let index = map.partition_point(|known| known.start <= region.start);
+	if map.insert(index, region).is_err() {
+		warn!("The physical memory map is full, {region} is not validated");
+	}
+}
+
+/// Enters `range` as region of type `kind` into the map.
+///
+/// Returns the conflict, if the region overlaps a known region, which it must
+/// not overlap. A region, which is already known, is entered only once.
+/// Reserved regions are removed from RAM regardless of the order, in which
+/// they are entered.
+pub(crate) fn try_insert(
+	range: Range<PhysAddr>,
+	kind: RegionKind,
+	owner: &'static str,
+) -> Result<(), Conflict> {
+	if range.is_empty() {
+		return Ok(());
+	}
+
+	let new = Region {
+		start: range.start,
+		end: range.end,
+		kind,
+		owner,
+	};
+	let mut map = MAP.lock();
+	if map
+		.iter()
+		.any(|region| region.start == new.start && region.end == new.end && region.kind == kind)
+	{
+		return Ok(());
+	}
+
+	let mut parts = Map::new();
+	parts.push(new).unwrap();
+	if kind == RegionKind::Ram {
+		for reserved in map
+			.iter()
+			.filter(|region| region.kind == RegionKind::Reserved)
+		{
+			if reserved.overlaps(&new) {
+				debug!("Remove {reserved} from {new}");
+			}
+			parts = parts
+				.into_iter()
+				.flat_map(|part| part.without(reserved))
+				.collect();
+		}
+	}
+
+	for part in &parts {
+		if let Some(existing) = map
+			.iter()
+			.find(|region| region.overlaps(part) && !region.kind.may_overlap(kind))
+		{
+			return Err(Conflict {
+				new,
+				existing: *existing,
+			});
+		}
+	}
+
+	if kind == RegionKind::Reserved {
+		while let Some(index) = map
+			.iter()
+			.position(|region| region.kind == RegionKind::Ram && region.overlaps(&new))
+		{
+			let ram = map.remove(index);
+			debug!("Remove {new} from {ram}");
+			for part in ram.without(&new) {
+				insert_sorted(&mut map, part);
+			}
+		}
+	}
+
+	for part in parts {
+		insert_sorted(&mut map, part);
+	}
+	Ok(())
+}
+
+/// Enters `range` as region of type `kind` into the map and stops the kernel,
+/// if the region conflicts with a known region.
+pub(crate) fn insert(range: Range<PhysAddr>, kind: RegionKind, owner: &'static str) {
+	if let Err(conflict) = try_insert(range, kind, owner) {
+		panic!("Inconsistent physical memory map: {conflict}");
+	}
+}
+
+pub(crate) fn print_information() {
+	let map = MAP.lock();
+	info!("Physical memory map:");
+	for region in map.iter() {
+		info!("  {region}");
+	}
+}
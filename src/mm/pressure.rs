@@ -0,0 +1,60 @@
+//! Accounting of reclaimable caches and relief of memory pressure
+//!
+//! Caches, e.g., of file systems, hold memory, which can be released at any time,
+//! because the content can be fetched again. Each cache implements [`Shrinker`].
+//! Before an allocation fails, the allocator asks the caches to drop clean
+//! entries by [`relieve`]. A memory balloon should consult [`cached_bytes`] and
+//! [`relieve`] in the same way, before it returns memory to the host.
+
+/// A cache, whose memory can be released under memory pressure
+pub(crate) trait Shrinker: Sync {
+	/// Name of the cache
+	fn name(&self) -> &'static str;
+
+	/// Returns the number of bytes, which are occupied by clean entries.
+	fn cached_bytes(&self) -> usize;
+
+	/// Drops clean entries until at least `target` bytes are released.
+	///
+	/// Returns the number of released bytes. The shrinker is called by the
+	/// allocator and must not block on locks, which may be held during an allocation.
+	fn shrink(&self, target: usize) -> usize;
+}
+
+/// All caches, which are built into the kernel
+static SHRINKERS: &[&dyn Shrinker] = &[
+	#[cfg(all(feature = "fuse", feature = "pci"))]
+	&crate::fs::fuse::DentryCacheShrinker,
+	#[cfg(target_os = "none")]
+	&crate::mm::ALLOCATOR,
+];
+
+/// Returns the number of bytes, which are occupied by clean entries of all caches.
+pub(crate) fn cached_bytes() -> usize {
+	SHRINKERS
+		.iter()
+		.map(|shrinker| shrinker.cached_bytes())
+		.sum()
+}
+
+/// Drops clean cache entries until at least `target` bytes are released.
+///
+/// Returns the number of released bytes.
+pub(crate) fn relieve(target: usize) -> usize {
+	let mut released = 0;
+	for shrinker in SHRINKERS {
+		if released >= target {
+			break;
+		}
+		let bytes = shrinker.shrink(target - released);
+		if bytes > 0 {
+			debug!("Released {bytes} bytes of the {}", shrinker.name());
+		}
+		released += bytes;
+	}
+	released
+}
+
+pub(crate) fn print_information() {
+	info!("Reclaimable caches: {} KiB", cached_bytes() >> 10);
+}
@@ -0,0 +1,251 @@
+//! Heap profiling of the application.
+//!
+//! If the kernel is built with the feature `heap-profile`, all allocations of the
+//! application (`sys_alloc`, `sys_realloc`, `sys_dealloc`, ...) are accounted per
+//! size class. In addition, each live allocation remembers the return addresses
+//! of its innermost stack frames. These are only meaningful if the kernel and the
+//! application are built with frame pointers (`-C force-frame-pointers=yes`).
+//!
+//! The profile is written to a file by `sys_heap_profile_dump` or, if
+//! `HERMIT_HEAP_PROFILE` specifies a path, when the application exits.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::fmt::Write;
+
+use hermit_sync::InterruptTicketMutex;
+use memory_addresses::VirtAddr;
+
+use crate::arch::mm::paging::virtual_to_physical;
+use crate::fd::{self, AccessPermission, OpenOption};
+use crate::{fs, io};
+
+/// Number of size classes; class `i` contains allocations up to `16 << i` bytes
+/// and the last class contains all larger allocations.
+const SIZE_CLASSES: usize = 20;
+
+/// Number of return addresses, which are recorded per allocation
+const MAX_FRAMES: usize = 8;
+
+type Backtrace = [usize; MAX_FRAMES];
+
+#[derive(Debug, Default, Copy, Clone)]
+struct SizeClass {
+	allocs: u64,
+	frees: u64,
+	live_objects: u64,
+	live_bytes: u64,
+}
+
+#[derive(Debug)]
+struct Allocation {
+	size: usize,
+	backtrace: Backtrace,
+}
+
+struct Profile {
+	classes: [SizeClass; SIZE_CLASSES],
+	live: BTreeMap<usize, Allocation>,
+}
+
+static PROFILE: InterruptTicketMutex<Profile> = InterruptTicketMutex::new(Profile {
+	classes: [SizeClass {
+		allocs: 0,
+		frees: 0,
+		live_objects: 0,
+		live_bytes: 0,
+	}; SIZE_CLASSES],
+	live: BTreeMap::new(),
+});
+
+fn size_class(size: usize) -> usize {
+	let class = size.max(16).next_power_of_two().trailing_zeros() - 4;
+	usize::min(class as usize, SIZE_CLASSES - 1)
+}
+
+#[inline(always)]
+fn frame_pointer() -> usize {
+	let fp: usize;
+	unsafe {
+		#[cfg(target_arch = "x86_64")]
+		core::arch::asm!("mov {}, rbp", out(reg) fp, options(nomem, nostack));
+		#[cfg(target_arch = "aarch64")]
+		core::arch::asm!("mov {}, x29", out(reg) fp, options(nomem, nostack));
+		#[cfg(target_arch = "riscv64")]
+		core::arch::asm!("mv {}, s0", out(reg) fp, options(nomem, nostack));
+	}
+	fp
+}
+
+/// Reads a word of a stack frame, if it is mapped.
+fn read_frame(addr: usize) -> Option<usize> {
+	if addr == 0 || addr % core::mem::align_of::<usize>() != 0 {
+		return None;
+	}
+	virtual_to_physical(VirtAddr::new(addr as u64))?;
+	Some(unsafe { *(addr as *const usize) })
+}
+
+/// Collects the return addresses by following the chain of frame pointers.
+///
+/// Without frame pointers, the chain is usually broken after a few frames.
+/// Each frame is checked to be mapped, so that a broken chain does not fault.
+#[inline(always)]
+fn backtrace() -> Backtrace {
+	let mut backtrace = [0; MAX_FRAMES];
+	let mut fp = frame_pointer();
+
+	for ret in backtrace.iter_mut() {
+		// On RISC-V, the frame pointer points above the saved registers.
+		#[cfg(target_arch = "riscv64")]
+		let (next, addr) = (
+			read_frame(fp.wrapping_sub(16)),
+			read_frame(fp.wrapping_sub(8)),
+		);
+		#[cfg(not(target_arch = "riscv64"))]
+		let (next, addr) = (read_frame(fp), read_frame(fp.wrapping_add(8)));
+
+		match (next, addr) {
+			(Some(next), Some(addr)) if addr != 0 => {
+				*ret = addr;
+				fp = next;
+			}
+			_ => break,
+		}
+	}
+
+	backtrace
+}
+
+/// Accounts the allocation of `size` bytes at `ptr`.
+pub(crate) fn record_alloc(ptr: *mut u8, size: usize) {
+	if ptr.is_null() {
+		return;
+	}
+
+	let backtrace = backtrace();
+	let mut profile = PROFILE.lock();
+	let class = &mut profile.classes[size_class(size)];
+	class.allocs += 1;
+	class.live_objects += 1;
+	class.live_bytes += size as u64;
+	profile
+		.live
+		.insert(ptr.addr(), Allocation { size, backtrace });
+}
+
+/// Accounts the release of `size` bytes at `ptr`.
+pub(crate) fn record_dealloc(ptr: *mut u8, size: usize) {
+	let mut profile = PROFILE.lock();
+	let size = profile
+		.live
+		.remove(&ptr.addr())
+		.map_or(size, |allocation| allocation.size);
+	let class = &mut profile.classes[size_class(size)];
+	class.frees += 1;
+	class.live_objects = class.live_objects.saturating_sub(1);
+	class.live_bytes = class.live_bytes.saturating_sub(size as u64);
+}
+
+/// Writes the profile in a human-readable form.
+fn format_profile(out: &mut String) -> core::fmt::Result {
+	let profile = PROFILE.lock();
+
+	let total = profile
+		.classes
+		.iter()
+		.fold(SizeClass::default(), |total, class| SizeClass {
+			allocs: total.allocs + class.allocs,
+			frees: total.frees + class.frees,
+			live_objects: total.live_objects + class.live_objects,
+			live_bytes: total.live_bytes + class.live_bytes,
+		});
+	writeln!(
+		out,
+		"heap profile: {} live objects, {} live bytes, {} allocations, {} frees",
+		total.live_objects, total.live_bytes, total.allocs, total.frees
+	)?;
+	writeln!(out)?;
+	writeln!(
+		out,
+		"{:>12} {:>12} {:>14} {:>12} {:>12}",
+		"size class", "live objects", "live bytes", "allocations", "frees"
+	)?;
+	for (i, class) in profile.classes.iter().enumerate() {
+		if class.allocs == 0 {
+			continue;
+		}
+		let limit = if i == SIZE_CLASSES - 1 {
+			String::from("larger")
+		} else {
+			alloc::format!("<= {}", 16usize << i)
+		};
+		writeln!(
+			out,
+			"{:>12} {:>12} {:>14} {:>12} {:>12}",
+			limit, class.live_objects, class.live_bytes, class.allocs, class.frees
+		)?;
+	}
+
+	// aggregate the live allocations by call site
+	let mut sites = BTreeMap::<Backtrace, (u64, u64)>::new();
+	for allocation in profile.live.values() {
+		let site = sites.entry(allocation.backtrace).or_default();
+		site.0 += 1;
+		site.1 += allocation.size as u64;
+	}
+	drop(profile);
+
+	let mut sites = sites.into_iter().collect::<alloc::vec::Vec<_>>();
+	sites.sort_unstable_by(|a, b| b.1.1.cmp(&a.1.1));
+
+	writeln!(out)?;
+	writeln!(out, "live objects by caller:")?;
+	for (backtrace, (objects, bytes)) in sites {
+		write!(out, "{bytes} bytes in {objects} objects at")?;
+		for addr in backtrace.iter().take_while(|addr| **addr != 0) {
+			write!(out, " {addr:#x}")?;
+		}
+		writeln!(out)?;
+	}
+
+	Ok(())
+}
+
+/// Writes the current heap profile to the file `path`.
+pub(crate) fn dump(path: &str) -> io::Result<()> {
+	let mut buf = String::new();
+	// writing to a `String` does not fail
+	format_profile(&mut buf).unwrap();
+
+	let fd = fs::open(
+		path,
+		OpenOption::O_CREAT | OpenOption::O_TRUNC | OpenOption::O_WRONLY,
+		AccessPermission::from_bits(0o644).unwrap(),
+	)?;
+	let mut data = buf.as_bytes();
+	let result = loop {
+		if data.is_empty() {
+			break Ok(());
+		}
+		match fd::write(fd, data) {
+			Ok(0) => break Err(io::Error::EIO),
+			Ok(len) => data = &data[len..],
+			Err(err) => break Err(err),
+		}
+	};
+	fd::remove_object(fd)?;
+	result
+}
+
+/// Writes the heap profile to the file, which is specified by `HERMIT_HEAP_PROFILE`.
+pub(crate) fn dump_on_exit() {
+	let Some(path) = hermit_var!("HERMIT_HEAP_PROFILE") else {
+		return;
+	};
+
+	match dump(&path) {
+		Ok(()) => info!("Wrote heap profile to {path}"),
+		Err(err) => warn!("Unable to write heap profile to {path}: {err:?}"),
+	}
+}
@@ -0,0 +1,74 @@
+//! Dynamically allocated per-CPU variables
+//!
+//! In contrast to the fields of `CoreLocal`, which are part of the
+//! architecture-specific core setup, a per-CPU variable can be allocated by
+//! any subsystem with [`alloc_per_cpu`]. Every core has its own cache-padded
+//! instance, which is accessed without locking through [`PerCpu::get`].
+//! The instances of the other cores can be accessed by indexing the handle
+//! with their core ID, e.g., to sum up per-core counters.
+//!
+//! The number of instances is determined on allocation. Therefore, per-CPU
+//! variables have to be allocated after all cores have been started.
+
+use alloc::boxed::Box;
+use core::ops::Index;
+
+use crossbeam_utils::CachePadded;
+
+use crate::arch::core_local::core_id;
+use crate::scheduler::CoreId;
+
+/// Returns the number of cores, which will be started.
+fn possible_cores() -> usize {
+	#[cfg(feature = "smp")]
+	let count = crate::arch::kernel::get_possible_cpus();
+	#[cfg(not(feature = "smp"))]
+	let count = 1;
+
+	count.try_into().unwrap()
+}
+
+/// Handle of a per-CPU variable
+pub(crate) struct PerCpu<T> {
+	values: Box<[CachePadded<T>]>,
+}
+
+impl<T> PerCpu<T> {
+	/// Allocates a per-CPU variable, whose instances are created by `init`.
+	pub fn new_with(mut init: impl FnMut() -> T) -> Self {
+		let values = (0..possible_cores())
+			.map(|_| CachePadded::new(init()))
+			.collect();
+		Self { values }
+	}
+
+	/// Returns the instance of the current core.
+	pub fn get(&self) -> &T {
+		&self[core_id()]
+	}
+
+	/// Returns the instances of all cores together with their core IDs.
+	pub fn iter(&self) -> impl Iterator<Item = (CoreId, &T)> {
+		self.values
+			.iter()
+			.enumerate()
+			.map(|(core_id, value)| (core_id.try_into().unwrap(), &**value))
+	}
+}
+
+impl<T> Index<CoreId> for PerCpu<T> {
+	type Output = T;
+
+	fn index(&self, core_id: CoreId) -> &T {
+		self.values
+			.get(usize::try_from(core_id).unwrap())
+			.unwrap_or_else(|| {
+				panic!("Per-CPU variable has been allocated before core {core_id} was started")
+			})
+	}
+}
+
+/// Allocates a per-CPU variable, whose instances are initialized with their default value.
+pub(crate) fn alloc_per_cpu<T: Default>() -> PerCpu<T> {
+	PerCpu::new_with(T::default)
+}
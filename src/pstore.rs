@@ -0,0 +1,220 @@
+//! Persistent storage of the kernel log
+//!
+//! All log messages are copied into a ring buffer, which holds the last
+//! [`LOG_SIZE`] bytes of the kernel log. On panic, the ring buffer is saved,
+//! so that the crash context survives the virtual machine:
+//!
+//! - If a virtio-pmem device is available, the log is written to the last
+//!   bytes of its memory region, which are not provided by `/dev/pmem0`.
+//!   After the next boot, the saved log is available at `/proc/pstore`.
+//! - On uhyve, the log is written to the host file given by `HERMIT_PSTORE`.
+
+use alloc::ffi::CString;
+use alloc::string::String;
+use core::fmt::{self, Write};
+use core::slice;
+
+use hermit_sync::{InterruptTicketMutex, OnceCell};
+use memory_addresses::VirtAddr;
+use uhyve_interface::parameters::{CloseParams, OpenParams, WriteParams};
+use uhyve_interface::{GuestPhysAddr, GuestVirtAddr, Hypercall};
+
+use crate::arch::mm::paging;
+use crate::drivers::pci::get_pmem_driver;
+use crate::drivers::pmem::PmemRegion;
+use crate::env;
+use crate::fd::OpenOption;
+use crate::syscalls::interfaces::uhyve::uhyve_hypercall;
+
+/// Size of the saved kernel log in bytes
+pub(crate) const LOG_SIZE: usize = 16 * 1024;
+/// Magic number at the start of a saved log
+const MAGIC: [u8; 8] = *b"HPSTORE1";
+/// Size of the header, which consists of the magic number and the length of the log
+const HEADER_SIZE: usize = 16;
+/// Size of the area, which is reserved at the end of the memory of a virtio-pmem device
+pub(crate) const AREA_SIZE: usize = HEADER_SIZE + LOG_SIZE;
+
+struct LogRing {
+	buffer: [u8; LOG_SIZE],
+	/// Number of bytes, which have been written in total
+	written: usize,
+}
+
+impl LogRing {
+	/// Returns the contents from the oldest to the newest byte.
+	fn contents(&self) -> (&[u8], &[u8]) {
+		if self.written < LOG_SIZE {
+			(&self.buffer[..self.written], &[])
+		} else {
+			let (newer, older) = self.buffer.split_at(self.written % LOG_SIZE);
+			(older, newer)
+		}
+	}
+}
+
+impl Write for LogRing {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		// only the last bytes of long messages fit into the buffer
+		let bytes = &s.as_bytes()[s.len().saturating_sub(LOG_SIZE)..];
+		self.written += s.len() - bytes.len();
+
+		let pos = self.written % LOG_SIZE;
+		let (first, second) = bytes.split_at(bytes.len().min(LOG_SIZE - pos));
+		self.buffer[pos..pos + first.len()].copy_from_slice(first);
+		self.buffer[..second.len()].copy_from_slice(second);
+		self.written += bytes.len();
+		Ok(())
+	}
+}
+
+static LOG: InterruptTicketMutex<LogRing> = InterruptTicketMutex::new(LogRing {
+	buffer: [0; LOG_SIZE],
+	written: 0,
+});
+
+/// Log, which has been saved before the last reboot
+static PREVIOUS: OnceCell<String> = OnceCell::new();
+
+/// Appends a message to the ring buffer.
+pub(crate) fn record(args: fmt::Arguments<'_>) {
+	// a panic within the logger must not deadlock
+	if let Some(mut log) = LOG.try_lock() {
+		let _ = log.write_fmt(args);
+	}
+}
+
+/// Returns `true`, if the memory region is large enough to reserve an area for the log.
+fn has_area(region: &PmemRegion) -> bool {
+	region.size >= 2 * AREA_SIZE
+}
+
+/// Returns the region of a virtio-pmem device without the reserved area.
+pub(crate) fn exclude(region: PmemRegion) -> PmemRegion {
+	if !has_area(&region) {
+		return region;
+	}
+
+	PmemRegion {
+		size: region.size - AREA_SIZE,
+		..region
+	}
+}
+
+/// Returns the reserved area of the virtio-pmem device.
+fn area() -> Option<&'static mut [u8]> {
+	let region = get_pmem_driver()?.try_lock()?.region()?;
+	if !has_area(&region) {
+		return None;
+	}
+
+	let start = region.virt_addr + (region.size - AREA_SIZE);
+	Some(unsafe { slice::from_raw_parts_mut(start.as_mut_ptr::<u8>(), AREA_SIZE) })
+}
+
+fn save_to_pmem(older: &[u8], newer: &[u8]) {
+	let Some(area) = area() else {
+		return;
+	};
+
+	let (header, data) = area.split_at_mut(HEADER_SIZE);
+	let len = older.len() + newer.len();
+	data[..older.len()].copy_from_slice(older);
+	data[older.len()..len].copy_from_slice(newer);
+	// the log has to be complete, before the header marks it as valid
+	crate::arch::memory_barrier();
+	header[8..].copy_from_slice(&(len as u64).to_le_bytes());
+	header[..8].copy_from_slice(&MAGIC);
+
+	let Some(mut driver) = get_pmem_driver().and_then(|driver| driver.try_lock()) else {
+		return;
+	};
+	match driver.flush() {
+		Ok(()) => error!("Saved kernel log to persistent memory"),
+		Err(err) => error!("Unable to flush the kernel log: {err:?}"),
+	}
+}
+
+fn save_to_host(path: &str, older: &[u8], newer: &[u8]) {
+	let Ok(path) = CString::new(path) else {
+		return;
+	};
+
+	let mut open_params = OpenParams {
+		name: GuestPhysAddr::new(
+			paging::virtual_to_physical(VirtAddr::from_ptr(path.as_ptr()))
+				.unwrap()
+				.as_u64(),
+		),
+		flags: (OpenOption::O_WRONLY | OpenOption::O_CREAT | OpenOption::O_TRUNC).bits(),
+		mode: 0o644,
+		ret: -1,
+	};
+	uhyve_hypercall(Hypercall::FileOpen(&mut open_params));
+	if open_params.ret <= 0 {
+		error!("Unable to save the kernel log to {path:?}");
+		return;
+	}
+
+	for data in [older, newer] {
+		let write_params = WriteParams {
+			fd: open_params.ret,
+			buf: GuestVirtAddr::new(data.as_ptr() as u64),
+			len: data.len(),
+		};
+		uhyve_hypercall(Hypercall::FileWrite(&write_params));
+	}
+
+	let mut close_params = CloseParams {
+		fd: open_params.ret,
+		ret: -1,
+	};
+	uhyve_hypercall(Hypercall::FileClose(&mut close_params));
+	error!("Saved kernel log to {path:?}");
+}
+
+/// Saves the ring buffer together with the panic message.
+pub(crate) fn save(info: &core::panic::PanicInfo<'_>) {
+	record(format_args!("[PANIC] {info}\n"));
+
+	let Some(log) = LOG.try_lock() else {
+		return;
+	};
+	let (older, newer) = log.contents();
+
+	save_to_pmem(older, newer);
+	if env::is_uhyve() {
+		if let Some(path) = hermit_var!("HERMIT_PSTORE") {
+			save_to_host(&path, older, newer);
+		}
+	}
+}
+
+/// Returns the log, which has been saved before the last reboot.
+pub(crate) fn previous() -> String {
+	PREVIOUS.get().cloned().unwrap_or_default()
+}
+
+/// Takes over the log, which has been saved before the last reboot.
+pub(crate) fn init() {
+	let Some(area) = area() else {
+		return;
+	};
+
+	let (header, data) = area.split_at_mut(HEADER_SIZE);
+	if header[..8] != MAGIC {
+		return;
+	}
+	let len = u64::from_le_bytes(header[8..].try_into().unwrap());
+	let len = usize::try_from(len).unwrap_or(usize::MAX).min(LOG_SIZE);
+
+	let log = String::from_utf8_lossy(&data[..len]).into_owned();
+	info!("Found kernel log of {len} bytes from the last boot at /proc/pstore");
+	PREVIOUS.set(log).unwrap();
+
+	// the log is not reported again after the next reboot
+	header.fill(0);
+	if let Some(driver) = get_pmem_driver() {
+		let _ = driver.lock().flush();
+	}
+}
@@ -0,0 +1,106 @@
+//! Process-wide resource limits.
+//!
+//! Hermit runs a single process, so the limits apply to all tasks. They are
+//! enforced when file descriptors are allocated (`RLIMIT_NOFILE`) and when new
+//! tasks are spawned (`RLIMIT_STACK`).
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::config::DEFAULT_STACK_SIZE;
+use crate::fd::FileDescriptor;
+use crate::io;
+
+/// Maximum size of the stack of a task
+pub const RLIMIT_STACK: i32 = 3;
+/// Maximum number of open file descriptors
+pub const RLIMIT_NOFILE: i32 = 7;
+/// Value, which represents "no limit"
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// Default soft limit of open file descriptors
+const DEFAULT_NOFILE: u64 = 1024;
+/// Hard limit of open file descriptors, which is bounded by the range of file descriptors
+const MAX_NOFILE: u64 = FileDescriptor::MAX as u64 + 1;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct rlimit {
+	/// Soft limit
+	pub rlim_cur: u64,
+	/// Hard limit (ceiling for `rlim_cur`)
+	pub rlim_max: u64,
+}
+
+struct Limits {
+	stack: rlimit,
+	nofile: rlimit,
+}
+
+impl Limits {
+	fn get_mut(&mut self, resource: i32) -> io::Result<&mut rlimit> {
+		match resource {
+			RLIMIT_STACK => Ok(&mut self.stack),
+			RLIMIT_NOFILE => Ok(&mut self.nofile),
+			_ => Err(io::Error::EINVAL),
+		}
+	}
+}
+
+static LIMITS: InterruptTicketMutex<Limits> = InterruptTicketMutex::new(Limits {
+	stack: rlimit {
+		rlim_cur: RLIM_INFINITY,
+		rlim_max: RLIM_INFINITY,
+	},
+	nofile: rlimit {
+		rlim_cur: DEFAULT_NOFILE,
+		rlim_max: MAX_NOFILE,
+	},
+});
+
+/// Returns the soft and hard limit of `resource`.
+pub fn getrlimit(resource: i32) -> io::Result<rlimit> {
+	LIMITS.lock().get_mut(resource).copied()
+}
+
+/// Sets the soft and hard limit of `resource`.
+///
+/// The hard limit can only be lowered and the soft limit must not exceed the hard limit.
+/// The soft limit of the stack size must not be below [`DEFAULT_STACK_SIZE`].
+pub fn setrlimit(resource: i32, new: rlimit) -> io::Result<()> {
+	if new.rlim_cur > new.rlim_max {
+		return Err(io::Error::EINVAL);
+	}
+
+	if resource == RLIMIT_STACK && new.rlim_cur < DEFAULT_STACK_SIZE as u64 {
+		return Err(io::Error::EINVAL);
+	}
+
+	let mut limits = LIMITS.lock();
+	let limit = limits.get_mut(resource)?;
+	if new.rlim_max > limit.rlim_max {
+		return Err(io::Error::EPERM);
+	}
+
+	*limit = new;
+
+	Ok(())
+}
+
+/// Returns the smallest file descriptor, which exceeds `RLIMIT_NOFILE`.
+pub(crate) fn fd_limit() -> FileDescriptor {
+	let limit = LIMITS.lock().nofile.rlim_cur;
+	FileDescriptor::try_from(limit).unwrap_or(FileDescriptor::MAX)
+}
+
+/// Clamps a requested stack size to `RLIMIT_STACK`.
+pub(crate) fn clamp_stack_size(stack_size: usize) -> usize {
+	let limit = LIMITS.lock().stack.rlim_cur;
+	let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+	if stack_size > limit {
+		debug!("Clamp stack size {stack_size:#x} to RLIMIT_STACK {limit:#x}");
+		limit
+	} else {
+		stack_size
+	}
+}
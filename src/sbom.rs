@@ -0,0 +1,191 @@
+//! Software bill of materials of the kernel
+//!
+//! The enabled features, the compiled-in drivers, and the libraries with
+//! their versions are determined at build time, so that the capabilities of
+//! a kernel image can be verified at runtime. They are reported by
+//! `sys_kernel_features` and in the SPDX tag-value format by `/proc/sbom`.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+mod generated {
+	include!(concat!(env!("OUT_DIR"), "/sbom.rs"));
+}
+
+const NAME: &str = env!("CARGO_PKG_NAME");
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const LICENSE: &str = env!("CARGO_PKG_LICENSE");
+
+/// Kind of a component of the kernel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub(crate) enum Kind {
+	/// Cargo feature
+	Feature = 0,
+	/// Device driver
+	Driver = 1,
+	/// Library, which is linked into the kernel
+	Library = 2,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Component {
+	pub kind: Kind,
+	pub name: &'static str,
+	/// Version, empty for features
+	pub version: &'static str,
+}
+
+/// Device drivers, which are not attached through the PCI driver registry
+static MMIO_DRIVERS: &[&str] = &[
+	#[cfg(not(feature = "pci"))]
+	"virtio-mmio",
+	#[cfg(all(
+		not(feature = "pci"),
+		any(feature = "tcp", feature = "udp"),
+		not(feature = "gem-net")
+	))]
+	"virtio-net",
+	#[cfg(all(target_arch = "riscv64", feature = "gem-net"))]
+	"gem",
+	#[cfg(all(not(feature = "pci"), feature = "blk"))]
+	"virtio-blk",
+	#[cfg(all(not(feature = "pci"), feature = "console"))]
+	"virtio-console",
+];
+
+/// Returns the compiled-in device drivers.
+fn drivers() -> Vec<&'static str> {
+	let mut drivers = MMIO_DRIVERS.to_vec();
+	#[cfg(feature = "pci")]
+	{
+		drivers.push("virtio-pci");
+		drivers.extend(crate::drivers::registry::driver_names());
+	}
+	drivers
+}
+
+/// Libraries, which implement notable parts of the kernel
+static LIBRARIES: &[&str] = &[
+	"hermit-entry",
+	"talc",
+	"virtio-spec",
+	#[cfg(feature = "smoltcp")]
+	"smoltcp",
+	#[cfg(feature = "fuse")]
+	"fuse-abi",
+	#[cfg(feature = "pci-ids")]
+	"pci-ids",
+];
+
+/// Returns the version of a dependency as given by `Cargo.lock`.
+fn dependency_version(name: &str) -> &'static str {
+	generated::DEPENDENCIES
+		.iter()
+		.find(|(dependency, _)| *dependency == name)
+		.map_or("", |(_, version)| version)
+}
+
+/// Returns all components of the kernel.
+pub(crate) fn components() -> Vec<Component> {
+	let features = generated::FEATURES.iter().map(|name| Component {
+		kind: Kind::Feature,
+		name,
+		version: "",
+	});
+	let drivers = drivers().into_iter().map(|name| Component {
+		kind: Kind::Driver,
+		name,
+		version: VERSION,
+	});
+	let libraries = LIBRARIES.iter().map(|name| Component {
+		kind: Kind::Library,
+		name,
+		version: dependency_version(name),
+	});
+
+	features.chain(drivers).chain(libraries).collect()
+}
+
+/// Returns an SPDX identifier, which may only contain letters, numbers, `.` and `-`.
+fn spdx_id(name: &str) -> String {
+	let name: String = name
+		.chars()
+		.map(|c| {
+			if c.is_ascii_alphanumeric() || c == '.' {
+				c
+			} else {
+				'-'
+			}
+		})
+		.collect();
+	format!("SPDXRef-Package-{name}")
+}
+
+/// Returns the bill of materials as SPDX 2.3 document in the tag-value format.
+pub(crate) fn spdx() -> String {
+	const CREATED: &str = build_time::build_time_utc!("%Y-%m-%dT%H:%M:%SZ");
+
+	let kernel_id = spdx_id(NAME);
+	let mut report = String::new();
+	writeln!(report, "SPDXVersion: SPDX-2.3").unwrap();
+	writeln!(report, "DataLicense: CC0-1.0").unwrap();
+	writeln!(report, "SPDXID: SPDXRef-DOCUMENT").unwrap();
+	writeln!(report, "DocumentName: {NAME}-{VERSION}").unwrap();
+	writeln!(
+		report,
+		"DocumentNamespace: https://hermit-os.org/spdx/{NAME}-{VERSION}-{CREATED}"
+	)
+	.unwrap();
+	writeln!(report, "Creator: Tool: {NAME}-{VERSION}").unwrap();
+	writeln!(report, "Created: {CREATED}").unwrap();
+
+	let features: Vec<_> = components()
+		.into_iter()
+		.filter(|component| component.kind == Kind::Feature)
+		.map(|component| component.name)
+		.collect();
+	writeln!(report).unwrap();
+	writeln!(report, "PackageName: {NAME}").unwrap();
+	writeln!(report, "SPDXID: {kernel_id}").unwrap();
+	writeln!(report, "PackageVersion: {VERSION}").unwrap();
+	writeln!(report, "PackageDownloadLocation: NOASSERTION").unwrap();
+	writeln!(report, "FilesAnalyzed: false").unwrap();
+	writeln!(report, "PackageLicenseDeclared: {LICENSE}").unwrap();
+	writeln!(report, "PackageLicenseConcluded: {LICENSE}").unwrap();
+	writeln!(report, "PackageCopyrightText: NOASSERTION").unwrap();
+	writeln!(
+		report,
+		"PackageComment: <text>Features: {}\nDrivers: {}</text>",
+		features.join(", "),
+		drivers().join(", ")
+	)
+	.unwrap();
+	writeln!(
+		report,
+		"Relationship: SPDXRef-DOCUMENT DESCRIBES {kernel_id}"
+	)
+	.unwrap();
+
+	for name in LIBRARIES {
+		let id = spdx_id(name);
+		let version = match dependency_version(name) {
+			"" => "NOASSERTION",
+			version => version,
+		};
+		writeln!(report).unwrap();
+		writeln!(report, "PackageName: {name}").unwrap();
+		writeln!(report, "SPDXID: {id}").unwrap();
+		writeln!(report, "PackageVersion: {version}").unwrap();
+		writeln!(report, "PackageDownloadLocation: NOASSERTION").unwrap();
+		writeln!(report, "FilesAnalyzed: false").unwrap();
+		writeln!(report, "PackageLicenseConcluded: NOASSERTION").unwrap();
+		writeln!(report, "PackageLicenseDeclared: NOASSERTION").unwrap();
+		writeln!(report, "PackageCopyrightText: NOASSERTION").unwrap();
+		writeln!(report, "Relationship: {kernel_id} CONTAINS {id}").unwrap();
+	}
+
+	report
+}
@@ -0,0 +1,89 @@
+//! Stack canaries
+//!
+//! Each task gets a random canary, which is placed at the bottom of its kernel
+//! and user stack and around its TLS block. An overflow of a stack or of the
+//! TLS block overwrites the canary, before it corrupts the neighbouring memory.
+//! The canaries are verified, when the task exits, and, if the kernel is started
+//! with `HERMIT_STACK_CHECK=switch`, whenever the task is switched out.
+//! A violation stops the kernel with a report naming the task.
+
+use hermit_sync::Lazy;
+use memory_addresses::VirtAddr;
+
+use crate::arch;
+use crate::entropy::{self, Flags};
+use crate::scheduler::task::Task;
+
+/// Verify the canaries on every context switch
+static CHECK_ON_SWITCH: Lazy<bool> =
+	Lazy::new(|| hermit_var!("HERMIT_STACK_CHECK").is_some_and(|value| value == "switch"));
+
+/// Returns a new random canary.
+///
+/// The lowest byte is always zero, which stops overflows by string functions.
+pub(crate) fn generate() -> u64 {
+	let mut buf = [0u8; 8];
+	if entropy::read(&mut buf, Flags::empty()) < 0 {
+		buf = arch::processor::get_timer_ticks()
+			.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+			.to_ne_bytes();
+	}
+
+	match u64::from_ne_bytes(buf) & !0xff {
+		0 => 0xdead_beef_0000_0000,
+		canary => canary,
+	}
+}
+
+/// Returns the canaries at the bottom of the stacks of `task`.
+fn stack_canaries(task: &Task) -> [(&'static str, VirtAddr); 2] {
+	[
+		("kernel stack", task.stacks.get_kernel_stack()),
+		("user stack", task.stacks.get_user_stack()),
+	]
+}
+
+/// Places the canary of `task` at the bottom of its stacks.
+pub(crate) fn install(task: &Task) {
+	if task.canary == 0 {
+		return;
+	}
+
+	for (_, addr) in stack_canaries(task) {
+		unsafe {
+			addr.as_mut_ptr::<u64>().write_volatile(task.canary);
+		}
+	}
+}
+
+/// Verifies the canaries of `task` and stops the kernel, if one of them has been overwritten.
+pub(crate) fn check(task: &Task) {
+	if task.canary == 0 {
+		return;
+	}
+
+	for (name, addr) in stack_canaries(task) {
+		let value = unsafe { addr.as_ptr::<u64>().read_volatile() };
+		assert_eq!(
+			value, task.canary,
+			"Stack smashing detected: canary at the bottom of the {name} of task {} has been overwritten",
+			task.id
+		);
+	}
+
+	#[cfg(not(feature = "common-os"))]
+	assert!(
+		task.tls
+			.as_ref()
+			.is_none_or(|tls| tls.is_intact(task.canary)),
+		"TLS smashing detected: guard value around the TLS block of task {} has been overwritten",
+		task.id
+	);
+}
+
+/// Verifies the canaries of `task`, if the kernel checks them on every context switch.
+pub(crate) fn check_on_switch(task: &Task) {
+	if *CHECK_ON_SWITCH {
+		check(task);
+	}
+}
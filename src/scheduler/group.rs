@@ -0,0 +1,221 @@
+//! Task groups
+//!
+//! A task group bundles related tasks, e.g., the workers of a thread pool, so
+//! that they can be re-prioritized or terminated together. A new task joins
+//! the group of the task, which spawns it, unless another group is requested
+//! at spawn time. Tasks, which have never joined a group, belong to
+//! [`NO_GROUP`], which cannot be addressed by the bulk operations.
+//!
+//! Killing a group is deferred: a killed task terminates, when its next
+//! system call returns. Tasks, which wait on a futex, are woken up, so that
+//! they reach this point without delay, and blocking system calls of killed
+//! tasks return `EINTR`.
+//!
+//! Each group has a time slice, after which a running task is preempted by
+//! a task with the same priority. Latency-sensitive groups, e.g., network
+//! pollers, may use shorter slices, while batch groups use longer ones.
+//! The time slice of [`NO_GROUP`] applies to all tasks without a group.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::arch::core_local::core_scheduler;
+use crate::errno::ECANCELED;
+use crate::io;
+use crate::scheduler::task::{Priority, TaskId};
+use crate::scheduler::{PerCoreSchedulerExt, get_task_handle, release_deadline, set_task_priority};
+use crate::synch::futex;
+
+/// Identifier of a task group
+pub type GroupId = u32;
+
+/// Group of the tasks, which do not belong to any group
+pub(crate) const NO_GROUP: GroupId = 0;
+
+/// Exit code of a task, which has been killed
+const KILLED_EXIT_CODE: i32 = -ECANCELED;
+
+/// Time slice of a new group in microseconds
+const DEFAULT_TIMESLICE: u64 = 10_000;
+
+/// Shortest time slice in microseconds, which limits the overhead of the timer interrupts
+const MIN_TIMESLICE: u64 = 100;
+
+/// Longest time slice in microseconds
+const MAX_TIMESLICE: u64 = 1_000_000;
+
+static NEXT_GROUP: AtomicU32 = AtomicU32::new(1);
+
+/// Time slice of the tasks without a group
+static NO_GROUP_TIMESLICE: AtomicU64 = AtomicU64::new(DEFAULT_TIMESLICE);
+
+/// Time slice of each group
+///
+/// Groups are never removed, so their time slices are shared by the tasks
+/// of the group without reference counting.
+static TIMESLICES: InterruptTicketMutex<BTreeMap<GroupId, &'static AtomicU64>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Group of each task, which belongs to a group
+static MEMBERS: InterruptTicketMutex<BTreeMap<TaskId, GroupId>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Tasks, which have been killed, but are not yet finished
+static KILLED: InterruptTicketMutex<BTreeSet<TaskId>> = InterruptTicketMutex::new(BTreeSet::new());
+
+/// Number of entries in [`KILLED`], which allows a cheap check on every system call
+static KILL_PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates a new, empty group.
+pub(crate) fn create() -> GroupId {
+	let group = NEXT_GROUP.fetch_add(1, Ordering::Relaxed);
+	TIMESLICES.lock().insert(
+		group,
+		Box::leak(Box::new(AtomicU64::new(DEFAULT_TIMESLICE))),
+	);
+	group
+}
+
+/// Returns `true`, if `group` has been created.
+pub(crate) fn exists(group: GroupId) -> bool {
+	group != NO_GROUP && group < NEXT_GROUP.load(Ordering::Relaxed)
+}
+
+/// Returns the time slice of `group`, which is read by the scheduler on every dispatch.
+pub(super) fn timeslice_of(group: GroupId) -> &'static AtomicU64 {
+	TIMESLICES
+		.lock()
+		.get(&group)
+		.copied()
+		.unwrap_or(&NO_GROUP_TIMESLICE)
+}
+
+/// Returns the time slice of `group` in microseconds.
+pub(crate) fn timeslice(group: GroupId) -> io::Result<u64> {
+	if group != NO_GROUP && !exists(group) {
+		return Err(io::Error::ESRCH);
+	}
+
+	Ok(timeslice_of(group).load(Ordering::Relaxed))
+}
+
+/// Sets the time slice of `group` to `micros` microseconds, which are clamped
+/// to a sane range, and returns the new time slice.
+///
+/// Running tasks use the new time slice, when their current slice ends.
+pub(crate) fn set_timeslice(group: GroupId, micros: u64) -> io::Result<u64> {
+	if group != NO_GROUP && !exists(group) {
+		return Err(io::Error::ESRCH);
+	}
+
+	let micros = micros.clamp(MIN_TIMESLICE, MAX_TIMESLICE);
+	timeslice_of(group).store(micros, Ordering::Relaxed);
+	debug!("Time slice of group {group} is {micros} us");
+	Ok(micros)
+}
+
+/// Returns the group of the current task.
+pub(crate) fn current() -> GroupId {
+	let id = core_scheduler().get_current_task_id();
+	MEMBERS.lock().get(&id).copied().unwrap_or(NO_GROUP)
+}
+
+/// Adds the new task `id` to `group`.
+pub(super) fn add(id: TaskId, group: GroupId) {
+	if group != NO_GROUP {
+		MEMBERS.lock().insert(id, group);
+	}
+}
+
+/// Removes the finished task `id` from its group.
+pub(super) fn task_exited(id: TaskId) {
+	MEMBERS.lock().remove(&id);
+	let mut killed = KILLED.lock();
+	if killed.remove(&id) {
+		KILL_PENDING.store(killed.len(), Ordering::Relaxed);
+	}
+}
+
+fn members(group: GroupId) -> io::Result<Vec<TaskId>> {
+	let members: Vec<TaskId> = MEMBERS
+		.lock()
+		.iter()
+		.filter(|(_, member_group)| **member_group == group)
+		.map(|(id, _)| *id)
+		.collect();
+
+	if members.is_empty() {
+		Err(io::Error::ESRCH)
+	} else {
+		Ok(members)
+	}
+}
+
+/// Changes the priority of all tasks of `group` and returns their number.
+pub(crate) fn set_priority(group: GroupId, prio: Priority) -> io::Result<usize> {
+	let members = members(group)?;
+	for id in &members {
+		if let Some(handle) = get_task_handle(*id) {
+			set_task_priority(handle, prio);
+		}
+	}
+
+	debug!(
+		"Changed priority of {} tasks of group {group} to {prio}",
+		members.len()
+	);
+	Ok(members.len())
+}
+
+/// Kills all tasks of `group` and returns their number.
+///
+/// If the current task belongs to `group`, it terminates on the return of
+/// the current system call.
+pub(crate) fn kill(group: GroupId) -> io::Result<usize> {
+	let members = members(group)?;
+	{
+		let mut killed = KILLED.lock();
+		killed.extend(members.iter().copied());
+		KILL_PENDING.store(killed.len(), Ordering::Relaxed);
+	}
+
+	for id in &members {
+		// The task gives up its share of the core, even if it does not reach
+		// a cancellation point soon.
+		release_deadline(*id);
+		futex::futex_wake_task(*id);
+	}
+
+	debug!("Killed {} tasks of group {group}", members.len());
+	Ok(members.len())
+}
+
+/// Returns `true`, if the current task has been killed.
+#[inline]
+pub(crate) fn is_current_killed() -> bool {
+	KILL_PENDING.load(Ordering::Relaxed) != 0
+		&& KILLED
+			.lock()
+			.contains(&core_scheduler().get_current_task_id())
+}
+
+/// Terminates the current task, if it has been killed.
+///
+/// Called on the return of every system call.
+#[inline]
+pub(crate) fn cancellation_point() {
+	if KILL_PENDING.load(Ordering::Relaxed) == 0 {
+		return;
+	}
+
+	let scheduler = core_scheduler();
+	let id = scheduler.get_current_task_id();
+	if KILLED.lock().contains(&id) {
+		debug!("Terminating killed task {id}");
+		scheduler.exit(KILLED_EXIT_CODE);
+	}
+}
@@ -0,0 +1,74 @@
+//! Interruption of blocking system calls
+//!
+//! A task, which blocks in a system call, e.g., in `accept`, can be
+//! interrupted by another task with [`interrupt`]. Killing the group of the
+//! task interrupts it as well. The blocking call returns `EINTR` as soon as
+//! the task runs again. If the task does not block, its next blocking call
+//! is interrupted instead. Only system calls, which block by
+//! [`block_on_interruptible`](crate::executor::block_on_interruptible), are
+//! interrupted, internal waits of the kernel are not.
+
+use alloc::collections::BTreeSet;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::arch::core_local::core_scheduler;
+use crate::io;
+use crate::scheduler::task::TaskId;
+use crate::scheduler::{get_task_handle, group};
+use crate::synch::futex;
+
+/// Tasks, whose next blocking system call is interrupted
+static INTERRUPTED: InterruptTicketMutex<BTreeSet<TaskId>> =
+	InterruptTicketMutex::new(BTreeSet::new());
+
+/// Number of entries in [`INTERRUPTED`], which allows a cheap check while blocking
+static INTERRUPT_PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Interrupts the blocking system call of the task `id`.
+pub(crate) fn interrupt(id: TaskId) -> io::Result<()> {
+	if get_task_handle(id).is_none() {
+		return Err(io::Error::ESRCH);
+	}
+
+	{
+		let mut interrupted = INTERRUPTED.lock();
+		interrupted.insert(id);
+		INTERRUPT_PENDING.store(interrupted.len(), Ordering::Relaxed);
+	}
+
+	futex::futex_wake_task(id);
+	debug!("Interrupted task {id}");
+	Ok(())
+}
+
+/// Returns `true`, if the blocking system call of the current task has to
+/// return `EINTR`.
+///
+/// A pending interruption is consumed, while a killed task stays interrupted
+/// until it terminates.
+#[inline]
+pub(crate) fn take() -> bool {
+	if group::is_current_killed() {
+		return true;
+	}
+
+	if INTERRUPT_PENDING.load(Ordering::Relaxed) == 0 {
+		return false;
+	}
+
+	let id = core_scheduler().get_current_task_id();
+	let mut interrupted = INTERRUPTED.lock();
+	let found = interrupted.remove(&id);
+	INTERRUPT_PENDING.store(interrupted.len(), Ordering::Relaxed);
+	found
+}
+
+/// Discards the pending interruption of the finished task `id`.
+pub(super) fn task_exited(id: TaskId) {
+	let mut interrupted = INTERRUPTED.lock();
+	if interrupted.remove(&id) {
+		INTERRUPT_PENDING.store(interrupted.len(), Ordering::Relaxed);
+	}
+}
@@ -0,0 +1,80 @@
+//! Load averages of the scheduler
+//!
+//! As on Linux, the load is the number of tasks, which are running or ready
+//! to run. Each core reports the length of its run queue, whenever it handles
+//! its waiting tasks, i.e., on timer interrupts and, on aarch64, on every
+//! interrupt. A core only adds the change of its length to the global number
+//! of active tasks, so that reporting does not require a lock. Every 5
+//! seconds, the first core, which reports afterwards, updates the 1, 5 and 15
+//! minute averages as exponentially decaying averages in fixed-point
+//! arithmetic with 11 fractional bits.
+//!
+//! The kernel is tickless, so an idle core reports its run queue only, when
+//! it wakes up again. Until then, the last reported length is used.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::arch::kernel::processor;
+
+/// Number of fractional bits of the load averages
+pub(crate) const FSHIFT: u32 = 11;
+const FIXED_1: u64 = 1 << FSHIFT;
+/// `FIXED_1 / exp(5s / 1min)`, `FIXED_1 / exp(5s / 5min)` and `FIXED_1 / exp(5s / 15min)`
+const EXP: [u64; 3] = [1884, 2014, 2037];
+/// Sampling interval in microseconds
+const LOAD_FREQ: u64 = 5_000_000;
+/// Number of missed samples, after which the averages have decayed completely
+const MAX_MISSED: u64 = 15 * 60 * 1_000_000 / LOAD_FREQ;
+
+/// Sum of the reported lengths of the run queues
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+/// Load averages in fixed-point representation
+static AVERAGES: [AtomicU64; 3] = [const { AtomicU64::new(0) }; 3];
+/// Time of the next sample in microseconds
+static NEXT_SAMPLE: AtomicU64 = AtomicU64::new(LOAD_FREQ);
+
+/// Reports the length `len` of the run queue of the current core, whose
+/// previously reported length is `reported`, and updates the averages, if
+/// the sampling interval has elapsed.
+pub(crate) fn update(reported: &mut usize, len: usize) {
+	// A core only subtracts, what it has added before, so that the sum does not underflow.
+	if len > *reported {
+		ACTIVE.fetch_add(len - *reported, Ordering::Relaxed);
+	} else if len < *reported {
+		ACTIVE.fetch_sub(*reported - len, Ordering::Relaxed);
+	}
+	*reported = len;
+
+	let now = processor::get_timer_ticks();
+	let next_sample = NEXT_SAMPLE.load(Ordering::Relaxed);
+	if now < next_sample
+		|| NEXT_SAMPLE
+			.compare_exchange(
+				next_sample,
+				now - now % LOAD_FREQ + LOAD_FREQ,
+				Ordering::Relaxed,
+				Ordering::Relaxed,
+			)
+			.is_err()
+	{
+		return;
+	}
+
+	// only the core, which has claimed the sample, updates the averages
+	let samples = ((now - next_sample) / LOAD_FREQ + 1).min(MAX_MISSED);
+	let active = u64::try_from(ACTIVE.load(Ordering::Relaxed)).unwrap() * FIXED_1;
+	for (average, exp) in AVERAGES.iter().zip(EXP) {
+		let mut value = average.load(Ordering::Relaxed);
+		for _ in 0..samples {
+			value = (value * exp + active * (FIXED_1 - exp)) >> FSHIFT;
+		}
+		average.store(value, Ordering::Relaxed);
+	}
+}
+
+/// Returns the 1, 5 and 15 minute load averages with [`FSHIFT`] fractional bits.
+pub(crate) fn averages() -> [u64; 3] {
+	AVERAGES
+		.each_ref()
+		.map(|average| average.load(Ordering::Relaxed))
+}
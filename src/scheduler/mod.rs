@@ -4,16 +4,15 @@ use alloc::boxed::Box;
 use alloc::collections::{BTreeMap, VecDeque};
 use alloc::rc::Rc;
 use alloc::sync::Arc;
-#[cfg(feature = "smp")]
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::future::{self, Future};
-use core::ptr;
 #[cfg(all(target_arch = "x86_64", feature = "smp"))]
 use core::sync::atomic::AtomicBool;
-use core::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use core::sync::atomic::{AtomicI32, AtomicU32, AtomicU64, Ordering};
 use core::task::Poll::Ready;
 use core::task::ready;
+use core::{mem, ptr};
 
 use ahash::RandomState;
 use crossbeam_utils::Backoff;
@@ -30,9 +29,15 @@ use crate::arch::switch::{switch_to_fpu_owner, switch_to_task};
 use crate::arch::{get_processor_count, interrupts};
 use crate::fd::{FileDescriptor, ObjectInterface};
 use crate::kernel::scheduler::TaskStacks;
+use crate::metrics::{Counter, Gauge};
 use crate::scheduler::task::*;
+use crate::synch::without_interrupts;
 use crate::{arch, io};
 
+pub(crate) mod canary;
+pub(crate) mod group;
+pub(crate) mod interrupt;
+pub(crate) mod loadavg;
 pub mod task;
 
 static NO_TASKS: AtomicU32 = AtomicU32::new(0);
@@ -48,6 +53,30 @@ static WAITING_TASKS: InterruptTicketMutex<BTreeMap<TaskId, VecDeque<TaskHandle>
 /// Map between Task ID and TaskHandle
 static TASKS: InterruptTicketMutex<BTreeMap<TaskId, TaskHandle>> =
 	InterruptTicketMutex::new(BTreeMap::new());
+/// Exit codes of finished tasks, which have not been joined yet
+static EXIT_CODES: InterruptTicketMutex<BTreeMap<TaskId, ExitCode>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+/// Maximum number of exit codes, which are kept for tasks, which are never joined
+const MAX_EXIT_CODES: usize = 4096;
+/// Utilizations of the deadline tasks in parts per million together with
+/// the cores, on which they are reserved
+///
+/// The reservations are kept outside of the per-core schedulers, so that the
+/// reservation of a killed task is released by the killing core.
+static DEADLINE_RESERVATIONS: InterruptTicketMutex<BTreeMap<TaskId, (CoreId, u64)>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+/// Finished tasks, which can be released by any core
+static REAP_LIST: InterruptTicketMutex<Vec<FinishedTask>> = InterruptTicketMutex::new(Vec::new());
+/// Size of the stacks of all finished tasks, which are not yet released
+static PENDING_STACK_BYTES: Gauge = Gauge::new(
+	"scheduler_pending_stack_bytes",
+	"Size of the stacks of all finished tasks, which are not yet released",
+);
+/// Number of released tasks
+static REAPED_TASKS: Counter = Counter::new(
+	"scheduler_reaped_tasks_total",
+	"Finished tasks, which have been released",
+);
 
 /// Unique identifier for a core.
 pub type CoreId = u32;
@@ -58,6 +87,8 @@ pub(crate) struct SchedulerInput {
 	new_tasks: VecDeque<NewTask>,
 	/// Queue of task, which are wakeup by another core
 	wakeup_tasks: VecDeque<TaskHandle>,
+	/// Priority changes of tasks, which are requested by another core
+	priority_changes: VecDeque<(TaskHandle, Priority)>,
 }
 
 #[cfg(feature = "smp")]
@@ -66,10 +97,32 @@ impl SchedulerInput {
 		Self {
 			new_tasks: VecDeque::new(),
 			wakeup_tasks: VecDeque::new(),
+			priority_changes: VecDeque::new(),
 		}
 	}
 }
 
+/// A finished task, which is no longer referenced by its scheduler
+struct FinishedTask {
+	task: Rc<RefCell<Task>>,
+	/// Context switches of the core, which ran the task
+	switches: &'static AtomicU64,
+	/// Value of `switches`, while the core still used the stacks of the task
+	epoch: u64,
+	stack_size: usize,
+}
+
+// SAFETY: A task is only added to the reap list, if no other reference to it exists.
+// Afterwards, only the reap list accesses the task.
+unsafe impl Send for FinishedTask {}
+
+impl FinishedTask {
+	/// The core left the stacks of the task, if it performed another context switch.
+	fn is_released(&self) -> bool {
+		self.switches.load(Ordering::Acquire) > self.epoch
+	}
+}
+
 #[cfg_attr(any(target_arch = "x86_64", target_arch = "aarch64"), repr(align(128)))]
 #[cfg_attr(
 	not(any(target_arch = "x86_64", target_arch = "aarch64")),
@@ -88,17 +141,33 @@ pub(crate) struct PerCoreScheduler {
 	fpu_owner: Rc<RefCell<Task>>,
 	/// Queue of tasks, which are ready
 	ready_queue: PriorityTaskQueue,
-	/// Queue of tasks, which are finished and can be released
-	finished_tasks: VecDeque<Rc<RefCell<Task>>>,
+	/// Number of context switches of this core
+	switches: &'static AtomicU64,
 	/// Queue of blocked tasks, sorted by wakeup time.
 	blocked_tasks: BlockedTaskQueue,
+	/// Task, to which the current task donates the rest of its time slice
+	directed_task: Option<Rc<RefCell<Task>>>,
+	/// Point in time, at which the time slice of the current task ends
+	slice_end: u64,
+	/// Length of the run queue, which has been reported to the load averages
+	reported_load: usize,
 }
 
 pub(crate) trait PerCoreSchedulerExt {
 	/// Triggers the scheduler to reschedule the tasks.
 	/// Interrupt flag will be cleared during the reschedule
+	///
+	/// The current task gives up the rest of its time slice, so that tasks
+	/// with the same priority run in the meantime.
 	fn reschedule(self);
 
+	/// Reschedules the tasks after an interrupt.
+	///
+	/// In contrast to [`reschedule`](Self::reschedule), the current task is
+	/// only preempted by tasks with the same priority, if its time slice has expired.
+	#[cfg(not(target_arch = "aarch64"))]
+	fn preempt(self);
+
 	#[cfg(any(feature = "tcp", feature = "udp"))]
 	fn add_network_timer(self, wakeup_time: Option<u64>);
 
@@ -109,6 +178,12 @@ pub(crate) trait PerCoreSchedulerExt {
 impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 	#[cfg(target_arch = "x86_64")]
 	fn reschedule(self) {
+		without_interrupts(|| self.expire_timeslice());
+		self.preempt();
+	}
+
+	#[cfg(target_arch = "x86_64")]
+	fn preempt(self) {
 		without_interrupts(|| {
 			if let Some(last_stack_pointer) = self.scheduler() {
 				let (new_stack_pointer, is_idle) = {
@@ -131,6 +206,9 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 						switch_to_task(last_stack_pointer, new_stack_pointer.as_u64() as usize);
 					}
 				}
+
+				// We are back in the context of this task.
+				self.finish_task_switch();
 			}
 		});
 	}
@@ -144,6 +222,8 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 
 		use crate::interrupts::SGI_RESCHED;
 
+		without_interrupts(|| self.expire_timeslice());
+
 		unsafe {
 			asm!("dsb nsh", "isb", options(nostack, nomem, preserves_flags));
 		}
@@ -161,6 +241,14 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 
 	#[cfg(target_arch = "riscv64")]
 	fn reschedule(self) {
+		without_interrupts(|| {
+			self.expire_timeslice();
+			self.scheduler();
+		});
+	}
+
+	#[cfg(target_arch = "riscv64")]
+	fn preempt(self) {
 		without_interrupts(|| self.scheduler());
 	}
 
@@ -188,12 +276,38 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 			);
 			current_task_borrowed.status = TaskStatus::Finished;
 			NO_TASKS.fetch_sub(1, Ordering::SeqCst);
+			if current_task_borrowed.deadline.take().is_some() {
+				release_deadline(current_task_borrowed.id);
+			}
+
+			canary::check(&current_task_borrowed);
 
 			let current_id = current_task_borrowed.id;
 			drop(current_task_borrowed);
 
+			#[cfg(any(feature = "tcp", feature = "udp"))]
+			crate::fd::socket::usage::task_exited(current_id);
+			group::task_exited(current_id);
+			interrupt::task_exited(current_id);
+
+			// The exit code has to be available before the waiting tasks are woken up.
+			let queue = {
+				let mut waiting_tasks = WAITING_TASKS.lock();
+				let queue = waiting_tasks.remove(&current_id);
+				let mut exit_codes = EXIT_CODES.lock();
+				if exit_codes.len() >= MAX_EXIT_CODES {
+					// drop the exit code of the oldest task
+					exit_codes.pop_first();
+				}
+				exit_codes.insert(current_id, ExitCode {
+					code: exit_code,
+					waiters: queue.as_ref().map_or(0, VecDeque::len),
+				});
+				queue
+			};
+
 			// wakeup tasks, which are waiting for task with the identifier id
-			if let Some(mut queue) = WAITING_TASKS.lock().remove(&current_id) {
+			if let Some(mut queue) = queue {
 				while let Some(task) = queue.pop_front() {
 					self.custom_wakeup(task);
 				}
@@ -205,12 +319,21 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 	}
 }
 
+/// Exit code of a finished task
+struct ExitCode {
+	code: i32,
+	/// Number of tasks, which have been waiting for the task, when it finished,
+	/// and have not obtained the exit code yet
+	waiters: usize,
+}
+
 struct NewTask {
 	tid: TaskId,
 	func: unsafe extern "C" fn(usize),
 	arg: usize,
 	prio: Priority,
 	core_id: CoreId,
+	group: group::GroupId,
 	stacks: TaskStacks,
 	object_map:
 		Arc<async_lock::RwLock<HashMap<FileDescriptor, Arc<dyn ObjectInterface>, RandomState>>>,
@@ -224,23 +347,34 @@ impl From<NewTask> for Task {
 			arg,
 			prio,
 			core_id,
+			group,
 			stacks,
 			object_map,
 		} = value;
-		let mut task = Self::new(tid, core_id, TaskStatus::Ready, prio, stacks, object_map);
+		let mut task = Self::new(
+			tid,
+			core_id,
+			TaskStatus::Ready,
+			prio,
+			group::timeslice_of(group),
+			stacks,
+			object_map,
+		);
 		task.create_stack_frame(func, arg);
+		canary::install(&task);
 		task
 	}
 }
 
 impl PerCoreScheduler {
-	/// Spawn a new task.
+	/// Spawn a new task, which belongs to `group`.
 	pub unsafe fn spawn(
 		func: unsafe extern "C" fn(usize),
 		arg: usize,
 		prio: Priority,
 		core_id: CoreId,
 		stack_size: usize,
+		group: group::GroupId,
 	) -> TaskId {
 		// Create the new task.
 		let tid = get_tid();
@@ -251,9 +385,11 @@ impl PerCoreScheduler {
 			arg,
 			prio,
 			core_id,
+			group,
 			stacks,
 			object_map: core_scheduler().get_current_task_object_map(),
 		};
+		group::add(tid, group);
 
 		// Add it to the task lists.
 		let wakeup = {
@@ -274,7 +410,7 @@ impl PerCoreScheduler {
 			#[cfg(feature = "smp")]
 			if core_id == core_scheduler().core_id {
 				let task = Rc::new(RefCell::new(Task::from(new_task)));
-				core_scheduler().ready_queue.push(task);
+				core_scheduler().make_ready(task);
 				false
 			} else {
 				input_locked.new_tasks.push_back(new_task);
@@ -283,7 +419,7 @@ impl PerCoreScheduler {
 			#[cfg(not(feature = "smp"))]
 			if core_id == 0 {
 				let task = Rc::new(RefCell::new(Task::from(new_task)));
-				core_scheduler().ready_queue.push(task);
+				core_scheduler().make_ready(task);
 				false
 			} else {
 				panic!("Invalid  core_id {}!", core_id)
@@ -325,15 +461,18 @@ impl PerCoreScheduler {
 
 		// Clone the current task.
 		let tid = get_tid();
+		let group = group::current();
 		let clone_task = NewTask {
 			tid,
 			func,
 			arg,
 			prio: current_task_borrowed.prio,
 			core_id,
+			group,
 			stacks: TaskStacks::new(current_task_borrowed.stacks.get_user_stack_size()),
 			object_map: current_task_borrowed.object_map.clone(),
 		};
+		group::add(tid, group);
 
 		// Add it to the task lists.
 		let wakeup = {
@@ -353,7 +492,7 @@ impl PerCoreScheduler {
 			#[cfg(feature = "smp")]
 			if core_id == core_scheduler().core_id {
 				let clone_task = Rc::new(RefCell::new(Task::from(clone_task)));
-				core_scheduler().ready_queue.push(clone_task);
+				core_scheduler().make_ready(clone_task);
 				false
 			} else {
 				input_locked.new_tasks.push_back(clone_task);
@@ -362,7 +501,7 @@ impl PerCoreScheduler {
 			#[cfg(not(feature = "smp"))]
 			if core_id == 0 {
 				let clone_task = Rc::new(RefCell::new(Task::from(clone_task)));
-				core_scheduler().ready_queue.push(clone_task);
+				core_scheduler().make_ready(clone_task);
 				false
 			} else {
 				panic!("Invalid core_id {}!", core_id);
@@ -386,7 +525,13 @@ impl PerCoreScheduler {
 	#[inline]
 	#[cfg(all(any(target_arch = "x86_64", target_arch = "riscv64"), feature = "smp"))]
 	pub fn is_scheduling(&self) -> bool {
-		self.current_task.borrow().prio < self.ready_queue.get_highest_priority()
+		let current_task = self.current_task.borrow();
+		match (&current_task.deadline, self.ready_queue.earliest_deadline()) {
+			(Some(current), Some(earliest)) => earliest < current.abs_deadline,
+			(Some(_), None) => false,
+			(None, Some(_)) => true,
+			(None, None) => current_task.prio < self.ready_queue.get_highest_priority(),
+		}
 	}
 
 	#[inline]
@@ -394,8 +539,11 @@ impl PerCoreScheduler {
 		without_interrupts(|| {
 			crate::executor::run();
 			for task in self.blocked_tasks.handle_waiting_tasks() {
-				self.ready_queue.push(task);
+				self.make_ready(task);
 			}
+
+			let running = usize::from(self.current_task.borrow().prio != IDLE_PRIO);
+			loadavg::update(&mut self.reported_load, self.ready_queue.len() + running);
 		});
 	}
 
@@ -403,7 +551,7 @@ impl PerCoreScheduler {
 	pub fn custom_wakeup(&mut self, task: TaskHandle) {
 		without_interrupts(|| {
 			let task = self.blocked_tasks.custom_wakeup(task);
-			self.ready_queue.push(task);
+			self.make_ready(task);
 		});
 	}
 
@@ -412,7 +560,7 @@ impl PerCoreScheduler {
 		if task.get_core_id() == self.core_id {
 			without_interrupts(|| {
 				let task = self.blocked_tasks.custom_wakeup(task);
-				self.ready_queue.push(task);
+				self.make_ready(task);
 			});
 		} else {
 			get_scheduler_input(task.get_core_id())
@@ -451,6 +599,30 @@ impl PerCoreScheduler {
 		without_interrupts(|| self.current_task.borrow().id)
 	}
 
+	/// Returns the address ranges of the stacks of the current task.
+	///
+	/// The function is called by fault handlers and does not allocate memory.
+	#[cfg(feature = "coredump")]
+	pub(crate) fn get_current_task_stacks(
+		&self,
+	) -> heapless::Vec<core::ops::Range<memory_addresses::VirtAddr>, 2> {
+		without_interrupts(|| {
+			// the scheduler may have faulted while modifying the current task
+			let Ok(task) = self.current_task.try_borrow() else {
+				return heapless::Vec::new();
+			};
+			let stacks = &task.stacks;
+			[
+				(stacks.get_kernel_stack(), stacks.get_kernel_stack_size()),
+				(stacks.get_user_stack(), stacks.get_user_stack_size()),
+			]
+			.into_iter()
+			.filter(|(_, size)| *size > 0)
+			.map(|(start, size)| start..start + size as u64)
+			.collect()
+		})
+	}
+
 	#[inline]
 	pub fn get_current_task_object_map(
 		&self,
@@ -516,10 +688,13 @@ impl PerCoreScheduler {
 				let mut pinned_obj = core::pin::pin!(borrowed.object_map.write());
 
 				let mut guard = ready!(pinned_obj.as_mut().poll(cx));
+				let fd_limit = crate::rlimit::fd_limit();
 				let new_fd = || -> io::Result<FileDescriptor> {
 					let mut fd: FileDescriptor = 0;
 					loop {
-						if !guard.contains_key(&fd) {
+						if fd >= fd_limit {
+							break Err(io::Error::EMFILE);
+						} else if !guard.contains_key(&fd) {
 							break Ok(fd);
 						} else if fd == FileDescriptor::MAX {
 							break Err(io::Error::EOVERFLOW);
@@ -548,10 +723,13 @@ impl PerCoreScheduler {
 				let mut guard = ready!(pinned_obj.as_mut().poll(cx));
 				let obj = (*(guard.get(&fd).ok_or(io::Error::EINVAL)?)).clone();
 
+				let fd_limit = crate::rlimit::fd_limit();
 				let new_fd = || -> io::Result<FileDescriptor> {
 					let mut fd: FileDescriptor = 0;
 					loop {
-						if !guard.contains_key(&fd) {
+						if fd >= fd_limit {
+							break Err(io::Error::EMFILE);
+						} else if !guard.contains_key(&fd) {
 							break Ok(fd);
 						} else if fd == FileDescriptor::MAX {
 							break Err(io::Error::EOVERFLOW);
@@ -585,6 +763,25 @@ impl PerCoreScheduler {
 		.await
 	}
 
+	/// Remove all IO interfaces with a file descriptor of at least `first`
+	pub async fn remove_objects(
+		&self,
+		first: FileDescriptor,
+	) -> io::Result<Vec<Arc<dyn ObjectInterface>>> {
+		future::poll_fn(|cx| {
+			without_interrupts(|| {
+				let borrowed = self.current_task.borrow();
+				let mut pinned_obj = core::pin::pin!(borrowed.object_map.write());
+				let mut guard = ready!(pinned_obj.as_mut().poll(cx));
+				Ready(Ok(guard
+					.extract_if(|fd, _| *fd >= first)
+					.map(|(_, obj)| obj)
+					.collect()))
+			})
+		})
+		.await
+	}
+
 	#[inline]
 	pub fn get_current_task_prio(&self) -> Priority {
 		without_interrupts(|| self.current_task.borrow().prio)
@@ -620,6 +817,182 @@ impl PerCoreScheduler {
 		});
 	}
 
+	/// Moves the current task into the deadline scheduling class or, if `params`
+	/// is `None`, back to the scheduling by its fixed priority.
+	///
+	/// Returns `EBUSY`, if the deadline tasks would exceed the capacity of the core.
+	pub fn set_current_task_deadline(&mut self, params: Option<DeadlineParams>) -> io::Result<()> {
+		without_interrupts(|| {
+			let now = arch::processor::get_timer_ticks();
+			let core_id = core_id();
+			let mut current_task = self.current_task.borrow_mut();
+			let mut reservations = DEADLINE_RESERVATIONS.lock();
+			let old = reservations
+				.get(&current_task.id)
+				.filter(|(reserved_on, _)| *reserved_on == core_id)
+				.map_or(0, |(_, utilization)| *utilization);
+			let new = params.map_or(0, |params| params.utilization());
+			if deadline_utilization(&reservations, core_id) - old + new > MAX_DEADLINE_UTILIZATION {
+				return Err(io::Error::EBUSY);
+			}
+
+			debug!(
+				"Set deadline parameters of task {} to {params:?}",
+				current_task.id
+			);
+			if let Some(params) = params {
+				reservations.insert(current_task.id, (core_id, params.utilization()));
+			} else {
+				reservations.remove(&current_task.id);
+			}
+			drop(reservations);
+			current_task.deadline = params.map(|params| DeadlineState::new(params, now, core_id));
+			let expiry = current_task
+				.deadline
+				.as_ref()
+				.map(|deadline| now + deadline.remaining);
+			drop(current_task);
+			self.blocked_tasks.set_budget_timer(expiry);
+			Ok(())
+		})
+	}
+
+	/// Donates the rest of the time slice of the current task to the task `id`.
+	///
+	/// If the task is not ready or runs on another core, the current task just yields.
+	pub fn yield_to(&mut self, id: TaskId) -> io::Result<()> {
+		without_interrupts(|| {
+			let task = get_task_handle(id).ok_or(io::Error::ESRCH)?;
+			#[cfg(feature = "smp")]
+			if task.get_core_id() != self.core_id {
+				return Ok(());
+			}
+
+			self.directed_task = self.ready_queue.remove(task.get_id());
+			io::Result::Ok(())
+		})?;
+
+		self.reschedule();
+		Ok(())
+	}
+
+	/// Finishes the current job of a deadline task and blocks the task until its next period.
+	pub fn finish_deadline_job(&mut self) -> io::Result<()> {
+		let next_period = without_interrupts(|| {
+			let mut current_task = self.current_task.borrow_mut();
+			let deadline = current_task.deadline.as_mut().ok_or(io::Error::EINVAL)?;
+			deadline.remaining = 0;
+			io::Result::Ok(deadline.next_period())
+		})?;
+
+		self.block_current_task(Some(next_period));
+		self.reschedule();
+		Ok(())
+	}
+
+	/// Charges the CPU time of the current deadline task to its budget.
+	///
+	/// If the budget is exhausted, the task is throttled until its next period.
+	fn charge_current_task(&mut self, now: u64) {
+		let next_period = {
+			let mut current_task = self.current_task.borrow_mut();
+			let status = current_task.status;
+			let Some(deadline) = current_task.deadline.as_mut() else {
+				return;
+			};
+			if !deadline.charge(now) || status != TaskStatus::Running {
+				return;
+			}
+			deadline.next_period()
+		};
+
+		debug!(
+			"Budget of task {} is exhausted, throttle it until {next_period}",
+			self.current_task.borrow().id
+		);
+		self.blocked_tasks
+			.add(self.current_task.clone(), Some(next_period));
+	}
+
+	/// Programs the timer to fire, when the budget of the deadline task `task`
+	/// or the time slice of any other task is exhausted.
+	///
+	/// A new time slice starts, if `task` replaces the current task or the
+	/// time slice of the current task has expired. The time slice is only
+	/// enforced, if a task with the same priority is ready.
+	fn dispatch(&mut self, task: &Rc<RefCell<Task>>, now: u64) {
+		let mut borrowed = task.borrow_mut();
+		let (id, core_id) = (borrowed.id, borrowed.core_id);
+		let expiry = if let Some(deadline) = borrowed.deadline.as_mut() {
+			// A task, which has been moved to another core, takes its reservation along.
+			if deadline.core_id != core_id {
+				if let Some(reservation) = DEADLINE_RESERVATIONS.lock().get_mut(&id) {
+					reservation.0 = core_id;
+				}
+				deadline.core_id = core_id;
+			}
+			deadline.dispatched_at = now;
+			Some(now + deadline.remaining)
+		} else if borrowed.status == TaskStatus::Idle {
+			None
+		} else {
+			if !Rc::ptr_eq(task, &self.current_task) || now >= self.slice_end {
+				self.slice_end = now + borrowed.timeslice.load(Ordering::Relaxed);
+			}
+			self.ready_queue
+				.has_priority(borrowed.prio)
+				.then_some(self.slice_end)
+		};
+		drop(borrowed);
+		self.blocked_tasks.set_budget_timer(expiry);
+	}
+
+	/// Adds `task` to the ready queue of this core.
+	///
+	/// If `task` has the same priority as the current task, the timer is
+	/// programmed to end the time slice of the current task.
+	fn make_ready(&mut self, task: Rc<RefCell<Task>>) {
+		let peer = {
+			let current = self.current_task.borrow();
+			current.status == TaskStatus::Running
+				&& current.deadline.is_none()
+				&& current.prio == task.borrow().prio
+				&& !self.ready_queue.has_priority(current.prio)
+		};
+		self.ready_queue.push(task);
+
+		if peer {
+			// The current task has run without competitors so far.
+			let now = arch::processor::get_timer_ticks();
+			if now >= self.slice_end {
+				let timeslice = self.current_task.borrow().timeslice.load(Ordering::Relaxed);
+				self.slice_end = now + timeslice;
+			}
+			self.blocked_tasks.set_budget_timer(Some(self.slice_end));
+		}
+	}
+
+	/// Ends the time slice of the current task, so that it is preempted by
+	/// tasks with the same priority on the next reschedule.
+	pub fn expire_timeslice(&mut self) {
+		self.slice_end = 0;
+	}
+
+	/// Changes the priority of the task `handle`, which runs on this core.
+	///
+	/// Returns `false`, if the task is neither running, ready nor blocked.
+	fn set_local_priority(&mut self, handle: TaskHandle, prio: Priority) -> bool {
+		without_interrupts(|| {
+			if self.current_task.borrow().id == handle.get_id() {
+				self.current_task.borrow_mut().prio = prio;
+				true
+			} else {
+				self.ready_queue.set_priority(handle, prio).is_ok()
+					|| self.blocked_tasks.set_priority(handle.get_id(), prio)
+			}
+		})
+	}
+
 	pub fn set_priority(&mut self, id: TaskId, prio: Priority) -> Result<(), ()> {
 		trace!("Change priority of task {} to priority {}", id, prio);
 
@@ -632,6 +1005,7 @@ impl PerCoreScheduler {
 
 			if other_core {
 				warn!("Have to change the priority on another core");
+				return Ok(());
 			} else if self.current_task.borrow().id == task.get_id() {
 				self.current_task.borrow_mut().prio = prio;
 			} else {
@@ -639,6 +1013,7 @@ impl PerCoreScheduler {
 					.set_priority(task, prio)
 					.expect("Do not find valid task in ready queue");
 			}
+			update_task_priority(task.get_id(), prio);
 
 			Ok(())
 		})
@@ -672,12 +1047,28 @@ impl PerCoreScheduler {
 		}
 	}
 
-	/// Check if a finished task could be deleted.
-	fn cleanup_tasks(&mut self) {
-		// Pop the first finished task and remove it from the TASKS list, which implicitly deallocates all associated memory.
-		while let Some(finished_task) = self.finished_tasks.pop_front() {
-			debug!("Cleaning up task {}", finished_task.borrow().id);
-		}
+	/// Notes that this core completed a context switch, so that the stacks of the previous
+	/// task are no longer in use.
+	#[inline]
+	pub(crate) fn finish_task_switch(&self) {
+		self.switches.fetch_add(1, Ordering::Release);
+	}
+
+	/// Hands the finished task over to the reap list, where any core can release it.
+	fn retire_task(&self, task: Rc<RefCell<Task>>) {
+		debug_assert_eq!(Rc::strong_count(&task), 1);
+		let stack_size = {
+			let borrowed = task.borrow();
+			borrowed.stacks.get_user_stack_size() + borrowed.stacks.get_kernel_stack_size()
+		};
+
+		PENDING_STACK_BYTES.add(stack_size.try_into().unwrap());
+		REAP_LIST.lock().push(FinishedTask {
+			task,
+			switches: self.switches,
+			epoch: self.switches.load(Ordering::Relaxed),
+			stack_size,
+		});
 	}
 
 	#[cfg(all(any(target_arch = "x86_64", target_arch = "riscv64"), feature = "smp"))]
@@ -686,12 +1077,16 @@ impl PerCoreScheduler {
 
 		while let Some(task) = input_locked.wakeup_tasks.pop_front() {
 			let task = self.blocked_tasks.custom_wakeup(task);
-			self.ready_queue.push(task);
+			self.make_ready(task);
 		}
 
 		while let Some(new_task) = input_locked.new_tasks.pop_front() {
 			let task = Rc::new(RefCell::new(Task::from(new_task)));
-			self.ready_queue.push(task.clone());
+			self.make_ready(task);
+		}
+
+		while let Some((task, prio)) = input_locked.priority_changes.pop_front() {
+			self.set_local_priority(task, prio);
 		}
 	}
 
@@ -711,7 +1106,7 @@ impl PerCoreScheduler {
 			// do housekeeping
 			#[cfg(all(any(target_arch = "x86_64", target_arch = "riscv64"), feature = "smp"))]
 			core_scheduler.check_input();
-			core_scheduler.cleanup_tasks();
+			reap_tasks();
 
 			if core_scheduler.ready_queue.is_empty() {
 				if backoff.is_completed() {
@@ -741,39 +1136,64 @@ impl PerCoreScheduler {
 		// run background tasks
 		crate::executor::run();
 
+		// load modified watchpoints into the debug registers of this core
+		#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+		crate::watchpoint::sync();
+
+		// Entering the scheduler implies that the previous context switch is completed.
+		self.finish_task_switch();
+
 		// Someone wants to give up the CPU
 		// => we have time to cleanup the system
-		self.cleanup_tasks();
+		reap_tasks();
+
+		// Enforce the budget of a deadline task
+		let now = arch::processor::get_timer_ticks();
+		self.charge_current_task(now);
 
 		// Get information about the current task.
-		let (id, last_stack_pointer, prio, status) = {
+		let (id, last_stack_pointer, prio, deadline, status) = {
 			let mut borrowed = self.current_task.borrow_mut();
 			(
 				borrowed.id,
 				ptr::from_mut(&mut borrowed.last_stack_pointer).cast::<usize>(),
 				borrowed.prio,
+				borrowed
+					.deadline
+					.as_ref()
+					.map(|deadline| deadline.abs_deadline),
 				borrowed.status,
 			)
 		};
 
 		let mut new_task = None;
+		let mut finished_task = None;
 
 		if status == TaskStatus::Running {
 			// A task is currently running.
-			// Check if a task with a equal or higher priority is available.
-			if let Some(task) = self.ready_queue.pop_with_prio(prio) {
+			// Check if the task donates its time slice or if a task with an earlier
+			// deadline or a equal or higher priority is available.
+			if let Some(task) = self.directed_task.take().or_else(|| {
+				self.ready_queue
+					.pop_preempting(prio, deadline, now >= self.slice_end)
+			}) {
 				new_task = Some(task);
 			}
 		} else {
 			if status == TaskStatus::Finished {
-				// Mark the finished task as invalid and add it to the finished tasks for a later cleanup.
+				// Mark the finished task as invalid and release it, after switching to the next task.
 				self.current_task.borrow_mut().status = TaskStatus::Invalid;
-				self.finished_tasks.push_back(self.current_task.clone());
+				finished_task = Some(self.current_task.clone());
+				// The FPU state of the finished task does not need to be saved.
+				#[cfg(target_arch = "x86_64")]
+				if Rc::ptr_eq(&self.current_task, &self.fpu_owner) {
+					self.fpu_owner = self.idle_task.clone();
+				}
 			}
 
 			// No task is currently running.
 			// Check if there is any available task and get the one with the highest priority.
-			if let Some(task) = self.ready_queue.pop() {
+			if let Some(task) = self.directed_task.take().or_else(|| self.ready_queue.pop()) {
 				// This available task becomes the new task.
 				debug!("Task is available.");
 				new_task = Some(task);
@@ -794,6 +1214,8 @@ impl PerCoreScheduler {
 				self.ready_queue.push(self.current_task.clone());
 			}
 
+			self.dispatch(&task, now);
+
 			// Handle the new task and get information about it.
 			let (new_id, new_stack_pointer) = {
 				let mut borrowed = task.borrow_mut();
@@ -814,9 +1236,17 @@ impl PerCoreScheduler {
 					unsafe { *last_stack_pointer },
 					new_stack_pointer
 				);
+				canary::check_on_switch(&self.current_task.borrow());
+				#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+				crate::mitigations::task_switch();
+				#[cfg(all(target_arch = "x86_64", feature = "common-os"))]
+				CoreLocal::get().current_task_id.set(new_id.into());
 				#[cfg(not(target_arch = "riscv64"))]
 				{
 					self.current_task = task;
+					if let Some(finished_task) = finished_task {
+						self.retire_task(finished_task);
+					}
 				}
 
 				// Finally return the context of the new task.
@@ -830,11 +1260,17 @@ impl PerCoreScheduler {
 					}
 					task.borrow().last_fpu_state.restore();
 					self.current_task = task;
+					if let Some(finished_task) = finished_task {
+						self.retire_task(finished_task);
+					}
 					unsafe {
 						switch_to_task(last_stack_pointer, new_stack_pointer.as_usize());
 					}
 				}
 			}
+		} else {
+			let current_task = self.current_task.clone();
+			self.dispatch(&current_task, now);
 		}
 
 		None
@@ -889,8 +1325,11 @@ pub(crate) fn add_current_core() {
 		fpu_owner: idle_task.clone(),
 		idle_task,
 		ready_queue: PriorityTaskQueue::new(),
-		finished_tasks: VecDeque::new(),
+		switches: Box::leak(Box::new(AtomicU64::new(0))),
 		blocked_tasks: BlockedTaskQueue::new(),
+		directed_task: None,
+		slice_end: 0,
+		reported_load: 0,
 	});
 
 	let scheduler = Box::into_raw(boxed_scheduler);
@@ -920,12 +1359,25 @@ fn get_scheduler_input(core_id: CoreId) -> &'static InterruptTicketMutex<Schedul
 	SCHEDULER_INPUTS.lock()[usize::try_from(core_id).unwrap()]
 }
 
+/// Spawns a new task, which belongs to the group of the current task.
 pub unsafe fn spawn(
 	func: unsafe extern "C" fn(usize),
 	arg: usize,
 	prio: Priority,
 	stack_size: usize,
 	selector: isize,
+) -> TaskId {
+	unsafe { spawn_in_group(func, arg, prio, stack_size, selector, group::current()) }
+}
+
+/// Spawns a new task, which belongs to `group`.
+pub(crate) unsafe fn spawn_in_group(
+	func: unsafe extern "C" fn(usize),
+	arg: usize,
+	prio: Priority,
+	stack_size: usize,
+	selector: isize,
+	group: group::GroupId,
 ) -> TaskId {
 	static CORE_COUNTER: AtomicU32 = AtomicU32::new(1);
 
@@ -936,12 +1388,21 @@ pub unsafe fn spawn(
 		selector as u32
 	};
 
-	unsafe { PerCoreScheduler::spawn(func, arg, prio, core_id, stack_size) }
+	let stack_size = crate::rlimit::clamp_stack_size(stack_size);
+
+	unsafe { PerCoreScheduler::spawn(func, arg, prio, core_id, stack_size, group) }
 }
 
-#[allow(clippy::result_unit_err)]
-pub fn join(id: TaskId) -> Result<(), ()> {
+/// Waits until the task `id` is finished and returns its exit code.
+///
+/// Any number of tasks may wait for the same task and all of them obtain the same exit code.
+/// Afterwards, the exit code is discarded. If nobody waits, when the task finishes, the exit
+/// code is kept for the next call. If the timeout (in microseconds) elapses before,
+/// `ETIMEDOUT` is returned.
+pub fn join(id: TaskId, timeout: Option<u64>) -> io::Result<i32> {
 	let core_scheduler = core_scheduler();
+	let handle = core_scheduler.get_current_task_handle();
+	let wakeup_time = timeout.and_then(|t| arch::processor::get_timer_ticks().checked_add(t));
 
 	debug!(
 		"Task {} is waiting for task {}",
@@ -952,27 +1413,132 @@ pub fn join(id: TaskId) -> Result<(), ()> {
 	loop {
 		let mut waiting_tasks_guard = WAITING_TASKS.lock();
 
-		if let Some(queue) = waiting_tasks_guard.get_mut(&id) {
-			queue.push_back(core_scheduler.get_current_task_handle());
-			core_scheduler.block_current_task(None);
+		let Some(queue) = waiting_tasks_guard.get_mut(&id) else {
+			let mut exit_codes = EXIT_CODES.lock();
+			let exit_code = exit_codes.get_mut(&id).ok_or(io::Error::ESRCH)?;
+			let code = exit_code.code;
+			exit_code.waiters = exit_code.waiters.saturating_sub(1);
+			if exit_code.waiters == 0 {
+				exit_codes.remove(&id);
+			}
+			return Ok(code);
+		};
 
-			// Switch to the next task.
-			drop(waiting_tasks_guard);
-			core_scheduler.reschedule();
-		} else {
-			return Ok(());
+		if matches!(wakeup_time, Some(t) if t <= arch::processor::get_timer_ticks()) {
+			queue.retain(|task| task.get_id() != handle.get_id());
+			return Err(io::Error::ETIMEDOUT);
+		}
+
+		// After a spurious wakeup, the task is still part of the queue.
+		if !queue.iter().any(|task| task.get_id() == handle.get_id()) {
+			queue.push_back(handle);
 		}
+		core_scheduler.block_current_task(wakeup_time);
+
+		// Switch to the next task.
+		drop(waiting_tasks_guard);
+		core_scheduler.reschedule();
 	}
 }
 
+/// Releases all finished tasks, whose stacks are no longer in use.
+fn reap_tasks() {
+	let released: Vec<FinishedTask> = {
+		let mut reap_list = REAP_LIST.lock();
+		if reap_list.is_empty() {
+			return;
+		}
+		let (released, pending) = mem::take(&mut *reap_list)
+			.into_iter()
+			.partition(|task| task.is_released());
+		*reap_list = pending;
+		released
+	};
+
+	for finished_task in released {
+		debug!("Cleaning up task {}", finished_task.task.borrow().id);
+		PENDING_STACK_BYTES.add(-i64::try_from(finished_task.stack_size).unwrap());
+		REAPED_TASKS.inc();
+	}
+}
+
+/// Returns the size of the stacks of all finished tasks, which are not yet released.
+pub(crate) fn pending_stack_bytes() -> usize {
+	PENDING_STACK_BYTES.get().try_into().unwrap_or(0)
+}
+
+pub(crate) fn print_statistics() {
+	info!(
+		"Released tasks: {}, stack memory pending reclamation: {} KiB",
+		REAPED_TASKS.get(),
+		pending_stack_bytes() >> 10
+	);
+}
+
 pub fn shutdown(arg: i32) -> ! {
 	crate::syscalls::shutdown(arg)
 }
 
+/// Returns the utilization of `core_id` by the deadline tasks in parts per million.
+fn deadline_utilization(reservations: &BTreeMap<TaskId, (CoreId, u64)>, core_id: CoreId) -> u64 {
+	reservations
+		.values()
+		.filter(|(reserved_on, _)| *reserved_on == core_id)
+		.map(|(_, utilization)| utilization)
+		.sum()
+}
+
+/// Releases the utilization, which is reserved for the deadline task `id`.
+pub(crate) fn release_deadline(id: TaskId) {
+	DEADLINE_RESERVATIONS.lock().remove(&id);
+}
+
 fn get_task_handle(id: TaskId) -> Option<TaskHandle> {
 	TASKS.lock().get(&id).copied()
 }
 
+/// Records the new priority `prio` in the handle of the task `id`.
+fn update_task_priority(id: TaskId, prio: Priority) {
+	if let Some(handle) = TASKS.lock().get_mut(&id) {
+		*handle = TaskHandle::new(
+			id,
+			prio,
+			#[cfg(feature = "smp")]
+			handle.get_core_id(),
+		);
+	}
+}
+
+/// Changes the priority of the task `handle`, which may run on any core.
+fn set_task_priority(handle: TaskHandle, prio: Priority) {
+	update_task_priority(handle.get_id(), prio);
+
+	#[cfg(feature = "smp")]
+	if handle.get_core_id() != core_id() {
+		get_scheduler_input(handle.get_core_id())
+			.lock()
+			.priority_changes
+			.push_back((handle, prio));
+		arch::wakeup_core(handle.get_core_id());
+		return;
+	}
+
+	core_scheduler().set_local_priority(handle, prio);
+}
+
+/// Returns the handles of all tasks, which are not finished, except the idle tasks.
+pub(crate) fn live_tasks() -> Vec<TaskHandle> {
+	let waiting_tasks = WAITING_TASKS.lock();
+	TASKS
+		.lock()
+		.values()
+		.filter(|task| {
+			task.get_priority() != IDLE_PRIO && waiting_tasks.contains_key(&task.get_id())
+		})
+		.copied()
+		.collect()
+}
+
 #[cfg(all(target_arch = "x86_64", feature = "common-os"))]
 pub(crate) static BOOT_ROOT_PAGE_TABLE: OnceCell<usize> = OnceCell::new();
 
@@ -8,6 +8,7 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::num::NonZeroU64;
+use core::sync::atomic::AtomicU64;
 use core::{cmp, fmt};
 
 use ahash::RandomState;
@@ -23,7 +24,7 @@ use crate::arch::scheduler::TaskTLS;
 use crate::executor::poll_on;
 use crate::fd::stdio::*;
 use crate::fd::{FileDescriptor, ObjectInterface, STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO};
-use crate::scheduler::CoreId;
+use crate::scheduler::{CoreId, canary, group};
 use crate::{arch, env, io};
 
 /// Returns the most significant bit.
@@ -223,6 +224,26 @@ impl TaskHandlePriorityQueue {
 		None
 	}
 
+	/// Removes the handle of the task `id` regardless of its priority and returns it.
+	pub fn take(&mut self, id: TaskId) -> Option<TaskHandle> {
+		for queue_index in 0..NO_PRIORITIES {
+			let Some(queue) = &mut self.queues[queue_index] else {
+				continue;
+			};
+			let Some(i) = queue.iter().position(|task| task.id == id) else {
+				continue;
+			};
+
+			let task = queue.remove(i);
+			if queue.is_empty() {
+				*self.prio_bitmap &= !(1 << queue_index as u64);
+			}
+			return task;
+		}
+
+		None
+	}
+
 	/// Remove a specific task handle from the priority queue. Returns `true` if
 	/// the handle was in the queue.
 	pub fn remove(&mut self, task: TaskHandle) -> bool {
@@ -250,10 +271,96 @@ impl TaskHandlePriorityQueue {
 	}
 }
 
+/// Maximum utilization of a core by deadline tasks in parts per million
+pub(crate) const MAX_DEADLINE_UTILIZATION: u64 = 950_000;
+
+/// Longest period of a deadline task in microseconds (like `sched_deadline_period_max_us`
+/// on Linux), which keeps the computations of the utilization and deadlines from overflowing
+pub(crate) const MAX_DEADLINE_PERIOD: u64 = 1 << 22;
+
+/// Parameters of a task in the deadline scheduling class (in microseconds)
+///
+/// In each period, the task receives `runtime` microseconds of CPU time,
+/// which have to be consumed within `deadline` microseconds after the
+/// start of the period.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct DeadlineParams {
+	pub runtime: u64,
+	pub deadline: u64,
+	pub period: u64,
+}
+
+impl DeadlineParams {
+	/// Returns the share of the CPU time, which is reserved for the task, in parts per million.
+	pub fn utilization(&self) -> u64 {
+		self.runtime * 1_000_000 / self.period
+	}
+}
+
+/// State of a task in the deadline scheduling class
+#[derive(Debug)]
+pub(crate) struct DeadlineState {
+	pub params: DeadlineParams,
+	/// Start of the current period
+	pub period_start: u64,
+	/// Absolute deadline of the current job
+	pub abs_deadline: u64,
+	/// Remaining budget of the current job
+	pub remaining: u64,
+	/// Point in time, at which the task was dispatched or charged the last time
+	pub dispatched_at: u64,
+	/// Core, on which the utilization of the task is reserved
+	pub core_id: CoreId,
+}
+
+impl DeadlineState {
+	pub fn new(params: DeadlineParams, now: u64, core_id: CoreId) -> Self {
+		Self {
+			params,
+			period_start: now,
+			abs_deadline: now + params.deadline,
+			remaining: params.runtime,
+			dispatched_at: now,
+			core_id,
+		}
+	}
+
+	/// Returns the start of the next period.
+	pub fn next_period(&self) -> u64 {
+		self.period_start + self.params.period
+	}
+
+	/// Starts a new job, if the deadline of the current job has passed
+	/// or its budget is exhausted and the next period has started.
+	pub fn update(&mut self, now: u64) {
+		if now >= self.abs_deadline || (self.remaining == 0 && now >= self.next_period()) {
+			self.period_start = now;
+			self.abs_deadline = now + self.params.deadline;
+			self.remaining = self.params.runtime;
+		}
+	}
+
+	/// Charges the CPU time since the last dispatch to the budget.
+	///
+	/// Returns `true`, if the budget is exhausted.
+	pub fn charge(&mut self, now: u64) -> bool {
+		self.remaining = self
+			.remaining
+			.saturating_sub(now.saturating_sub(self.dispatched_at));
+		self.dispatched_at = now;
+		self.remaining == 0
+	}
+}
+
 /// Realize a priority queue for tasks
+///
+/// Tasks in the deadline scheduling class are kept in a separate queue,
+/// which is ordered by their deadlines (earliest deadline first). They take
+/// precedence over all tasks with a fixed priority.
 pub(crate) struct PriorityTaskQueue {
 	queues: [LinkedList<Rc<RefCell<Task>>>; NO_PRIORITIES],
 	prio_bitmap: u64,
+	deadline_tasks: VecDeque<Rc<RefCell<Task>>>,
 }
 
 impl PriorityTaskQueue {
@@ -263,11 +370,28 @@ impl PriorityTaskQueue {
 		PriorityTaskQueue {
 			queues: [EMPTY_LIST; NO_PRIORITIES],
 			prio_bitmap: 0,
+			deadline_tasks: VecDeque::new(),
 		}
 	}
 
 	/// Add a task by its priority to the queue
 	pub fn push(&mut self, task: Rc<RefCell<Task>>) {
+		let abs_deadline = task.borrow_mut().deadline.as_mut().map(|deadline| {
+			deadline.update(arch::processor::get_timer_ticks());
+			deadline.abs_deadline
+		});
+		if let Some(abs_deadline) = abs_deadline {
+			let index = self.deadline_tasks.partition_point(|queued| {
+				queued
+					.borrow()
+					.deadline
+					.as_ref()
+					.is_some_and(|deadline| deadline.abs_deadline <= abs_deadline)
+			});
+			self.deadline_tasks.insert(index, task);
+			return;
+		}
+
 		let i = task.borrow().prio.into() as usize;
 		//assert!(i < NO_PRIORITIES, "Priority {} is too high", i);
 
@@ -311,7 +435,19 @@ impl PriorityTaskQueue {
 
 	/// Returns true if the queue is empty.
 	pub fn is_empty(&self) -> bool {
-		self.prio_bitmap == 0
+		self.prio_bitmap == 0 && self.deadline_tasks.is_empty()
+	}
+
+	/// Returns the number of tasks in the queue.
+	pub fn len(&self) -> usize {
+		self.deadline_tasks.len() + self.queues.iter().map(LinkedList::len).sum::<usize>()
+	}
+
+	/// Returns the earliest deadline of all available deadline tasks
+	pub fn earliest_deadline(&self) -> Option<u64> {
+		self.deadline_tasks
+			.front()
+			.and_then(|task| task.borrow().deadline.as_ref().map(|d| d.abs_deadline))
 	}
 
 	/// Returns reference to prio_bitmap
@@ -321,8 +457,12 @@ impl PriorityTaskQueue {
 		&self.prio_bitmap
 	}
 
-	/// Pop the task with the highest priority from the queue
+	/// Pop the task with the earliest deadline or the highest priority from the queue
 	pub fn pop(&mut self) -> Option<Rc<RefCell<Task>>> {
+		if let Some(task) = self.deadline_tasks.pop_front() {
+			return Some(task);
+		}
+
 		if let Some(i) = msb(self.prio_bitmap) {
 			return self.pop_from_queue(i as usize);
 		}
@@ -330,10 +470,30 @@ impl PriorityTaskQueue {
 		None
 	}
 
-	/// Pop the next task, which has a higher or the same priority as `prio`
-	pub fn pop_with_prio(&mut self, prio: Priority) -> Option<Rc<RefCell<Task>>> {
+	/// Pop the next task, which preempts a running task with priority `prio`
+	/// or the absolute deadline `deadline`, if the running task is a deadline task.
+	///
+	/// A deadline task is only preempted by a deadline task with an earlier deadline.
+	/// Otherwise, any deadline task or a task with a higher priority preempts it.
+	/// A task with the same priority preempts it only, if `slice_expired` is set.
+	pub fn pop_preempting(
+		&mut self,
+		prio: Priority,
+		deadline: Option<u64>,
+		slice_expired: bool,
+	) -> Option<Rc<RefCell<Task>>> {
+		match (deadline, self.earliest_deadline()) {
+			(Some(current), Some(earliest)) if earliest < current => {
+				return self.deadline_tasks.pop_front();
+			}
+			(Some(_), _) => return None,
+			(None, Some(_)) => return self.deadline_tasks.pop_front(),
+			(None, None) => {}
+		}
+
 		if let Some(i) = msb(self.prio_bitmap) {
-			if i >= prio.into().into() {
+			let prio = u32::from(prio.into());
+			if i > prio || (i == prio && slice_expired) {
 				return self.pop_from_queue(i as usize);
 			}
 		}
@@ -341,6 +501,11 @@ impl PriorityTaskQueue {
 		None
 	}
 
+	/// Returns `true`, if a task with the priority `prio` is available.
+	pub fn has_priority(&self, prio: Priority) -> bool {
+		self.prio_bitmap & (1 << u32::from(prio.into())) != 0
+	}
+
 	/// Returns the highest priority of all available task
 	#[cfg(all(any(target_arch = "x86_64", target_arch = "riscv64"), feature = "smp"))]
 	pub fn get_highest_priority(&self) -> Priority {
@@ -351,6 +516,28 @@ impl PriorityTaskQueue {
 		}
 	}
 
+	/// Removes the task with the identifier `id` from the queue and returns it.
+	pub fn remove(&mut self, id: TaskId) -> Option<Rc<RefCell<Task>>> {
+		if let Some(index) = self
+			.deadline_tasks
+			.iter()
+			.position(|task| task.borrow().id == id)
+		{
+			return self.deadline_tasks.remove(index);
+		}
+
+		for queue_index in 0..NO_PRIORITIES {
+			if let Some(index) = self.queues[queue_index]
+				.iter()
+				.position(|task| task.borrow().id == id)
+			{
+				return self.remove_from_queue(index, queue_index);
+			}
+		}
+
+		None
+	}
+
 	/// Change priority of specific task
 	pub fn set_priority(&mut self, handle: TaskHandle, prio: Priority) -> Result<(), ()> {
 		let old_priority = handle.get_priority().into() as usize;
@@ -383,6 +570,10 @@ pub(crate) struct Task {
 	pub status: TaskStatus,
 	/// Task priority,
 	pub prio: Priority,
+	/// Parameters and state, if the task belongs to the deadline scheduling class
+	pub deadline: Option<DeadlineState>,
+	/// Time slice of the task group in microseconds
+	pub timeslice: &'static AtomicU64,
 	/// Last stack pointer before a context switch to another task
 	pub last_stack_pointer: VirtAddr,
 	/// Last stack pointer on the user stack before jumping to kernel space
@@ -394,6 +585,8 @@ pub(crate) struct Task {
 	pub core_id: CoreId,
 	/// Stack of the task
 	pub stacks: TaskStacks,
+	/// Canary at the bottom of the stacks and around the TLS block, zero if unprotected
+	pub canary: u64,
 	/// Mapping between file descriptor and the referenced IO interface
 	pub object_map:
 		Arc<async_lock::RwLock<HashMap<FileDescriptor, Arc<dyn ObjectInterface>, RandomState>>>,
@@ -416,6 +609,7 @@ impl Task {
 		core_id: CoreId,
 		task_status: TaskStatus,
 		task_prio: Priority,
+		timeslice: &'static AtomicU64,
 		stacks: TaskStacks,
 		object_map: Arc<
 			async_lock::RwLock<HashMap<FileDescriptor, Arc<dyn ObjectInterface>, RandomState>>,
@@ -427,12 +621,15 @@ impl Task {
 			id: tid,
 			status: task_status,
 			prio: task_prio,
+			deadline: None,
+			timeslice,
 			last_stack_pointer: VirtAddr::zero(),
 			user_stack_pointer: VirtAddr::zero(),
 			#[cfg(any(target_arch = "x86_64", target_arch = "riscv64"))]
 			last_fpu_state: arch::processor::FPUState::new(),
 			core_id,
 			stacks,
+			canary: canary::generate(),
 			object_map,
 			#[cfg(not(feature = "common-os"))]
 			tls: None,
@@ -492,12 +689,15 @@ impl Task {
 			id: tid,
 			status: TaskStatus::Idle,
 			prio: IDLE_PRIO,
+			deadline: None,
+			timeslice: group::timeslice_of(group::NO_GROUP),
 			last_stack_pointer: VirtAddr::zero(),
 			user_stack_pointer: VirtAddr::zero(),
 			#[cfg(any(target_arch = "x86_64", target_arch = "riscv64"))]
 			last_fpu_state: arch::processor::FPUState::new(),
 			core_id,
 			stacks: TaskStacks::from_boot_stacks(),
+			canary: 0,
 			object_map: OBJECT_MAP.get().unwrap().clone(),
 			#[cfg(not(feature = "common-os"))]
 			tls: None,
@@ -528,6 +728,8 @@ pub(crate) struct BlockedTaskQueue {
 	list: LinkedList<BlockedTask>,
 	#[cfg(any(feature = "tcp", feature = "udp"))]
 	network_wakeup_time: Option<u64>,
+	/// Point in time, at which the budget of the running deadline task is exhausted
+	budget_expiry: Option<u64>,
 }
 
 impl BlockedTaskQueue {
@@ -536,6 +738,7 @@ impl BlockedTaskQueue {
 			list: LinkedList::new(),
 			#[cfg(any(feature = "tcp", feature = "udp"))]
 			network_wakeup_time: None,
+			budget_expiry: None,
 		}
 	}
 
@@ -562,18 +765,33 @@ impl BlockedTaskQueue {
 		borrowed.status = TaskStatus::Ready;
 	}
 
-	#[cfg(any(feature = "tcp", feature = "udp"))]
-	pub fn add_network_timer(&mut self, wakeup_time: Option<u64>) {
-		self.network_wakeup_time = wakeup_time;
-
-		let next = self.list.front().and_then(|t| t.wakeup_time);
-
-		let time = match (wakeup_time, next) {
+	/// Programs the One-Shot Timer to fire at the next wakeup time of a task,
+	/// the network timer or the budget expiry, whichever comes first.
+	fn set_oneshot_timer(&self) {
+		let earliest = |a: Option<u64>, b: Option<u64>| match (a, b) {
 			(Some(a), Some(b)) => Some(a.min(b)),
 			(a, b) => a.or(b),
 		};
 
-		arch::set_oneshot_timer(time);
+		// the list is sorted by the wakeup time
+		let time = self.list.front().and_then(|node| node.wakeup_time);
+		#[cfg(any(feature = "tcp", feature = "udp"))]
+		let time = earliest(time, self.network_wakeup_time);
+		arch::set_oneshot_timer(earliest(time, self.budget_expiry));
+	}
+
+	#[cfg(any(feature = "tcp", feature = "udp"))]
+	pub fn add_network_timer(&mut self, wakeup_time: Option<u64>) {
+		self.network_wakeup_time = wakeup_time;
+		self.set_oneshot_timer();
+	}
+
+	/// Sets the point in time, at which the budget of the running deadline task is exhausted.
+	pub fn set_budget_timer(&mut self, expiry: Option<u64>) {
+		if self.budget_expiry != expiry {
+			self.budget_expiry = expiry;
+			self.set_oneshot_timer();
+		}
 	}
 
 	/// Blocks the given task for `wakeup_time` ticks, or indefinitely if None is given.
@@ -597,35 +815,21 @@ impl BlockedTaskQueue {
 		// Shall the task automatically be woken up after a certain time?
 		if let Some(wt) = wakeup_time {
 			let mut cursor = self.list.cursor_front_mut();
-			let set_oneshot_timer = || {
-				#[cfg(not(any(feature = "tcp", feature = "udp")))]
-				arch::set_oneshot_timer(wakeup_time);
-				#[cfg(any(feature = "tcp", feature = "udp"))]
-				match self.network_wakeup_time {
-					Some(time) => {
-						if time > wt {
-							arch::set_oneshot_timer(wakeup_time);
-						} else {
-							arch::set_oneshot_timer(self.network_wakeup_time);
-						}
-					}
-					_ => arch::set_oneshot_timer(wakeup_time),
-				}
-			};
-
 			while let Some(node) = cursor.current() {
 				let node_wakeup_time = node.wakeup_time;
 				if node_wakeup_time.is_none() || wt < node_wakeup_time.unwrap() {
 					cursor.insert_before(new_node);
 
-					set_oneshot_timer();
+					self.set_oneshot_timer();
 					return;
 				}
 
 				cursor.move_next();
 			}
 
-			set_oneshot_timer();
+			self.list.push_back(new_node);
+			self.set_oneshot_timer();
+			return;
 		}
 
 		self.list.push_back(new_node);
@@ -652,29 +856,8 @@ impl BlockedTaskQueue {
 
 				// If this is the first task, adjust the One-Shot Timer to fire at the
 				// next task's wakeup time (if any).
-				#[cfg(any(feature = "tcp", feature = "udp"))]
-				if first_task {
-					arch::set_oneshot_timer(cursor.current().map_or_else(
-						|| self.network_wakeup_time,
-						|node| match node.wakeup_time {
-							Some(wt) => {
-								if let Some(timer) = self.network_wakeup_time {
-									if wt < timer { Some(wt) } else { Some(timer) }
-								} else {
-									Some(wt)
-								}
-							}
-							None => self.network_wakeup_time,
-						},
-					));
-				}
-				#[cfg(not(any(feature = "tcp", feature = "udp")))]
 				if first_task {
-					arch::set_oneshot_timer(
-						cursor
-							.current()
-							.map_or_else(|| None, |node| node.wakeup_time),
-					);
+					self.set_oneshot_timer();
 				}
 
 				// Wake it up.
@@ -690,6 +873,17 @@ impl BlockedTaskQueue {
 		unreachable!();
 	}
 
+	/// Changes the priority of the blocked task `id`. Returns `true`, if the task was found.
+	pub fn set_priority(&mut self, id: TaskId, prio: Priority) -> bool {
+		match self.list.iter().find(|node| node.task.borrow().id == id) {
+			Some(node) => {
+				node.task.borrow_mut().prio = prio;
+				true
+			}
+			None => false,
+		}
+	}
+
 	/// Wakes up all tasks whose wakeup time has elapsed.
 	///
 	/// Should be called by the One-Shot Timer interrupt handler when the wakeup time for
@@ -724,26 +918,7 @@ impl BlockedTaskQueue {
 			cursor.remove_current();
 		}
 
-		#[cfg(any(feature = "tcp", feature = "udp"))]
-		arch::set_oneshot_timer(cursor.current().map_or_else(
-			|| self.network_wakeup_time,
-			|node| match node.wakeup_time {
-				Some(wt) => {
-					if let Some(timer) = self.network_wakeup_time {
-						if wt < timer { Some(wt) } else { Some(timer) }
-					} else {
-						Some(wt)
-					}
-				}
-				None => self.network_wakeup_time,
-			},
-		));
-		#[cfg(not(any(feature = "tcp", feature = "udp")))]
-		arch::set_oneshot_timer(
-			cursor
-				.current()
-				.map_or_else(|| None, |node| node.wakeup_time),
-		);
+		self.set_oneshot_timer();
 
 		for task in tasks.iter().cloned() {
 			Self::wakeup_task(task);
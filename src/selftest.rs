@@ -0,0 +1,281 @@
+//! Diagnostics of the kernel subsystems
+//!
+//! With the kernel argument `selftest`, quick checks are executed before the
+//! application starts. The results are printed on the console, so that
+//! environment issues, e.g., an inaccurate timer or a core, which does not
+//! receive interrupts, are detected early. A failed check does not stop the
+//! boot process.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use core::hint;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use hermit_sync::Lazy;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use smoltcp::phy::RxToken;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use smoltcp::wire::{
+	ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol,
+	EthernetRepr, IpAddress,
+};
+
+use crate::arch::get_processor_count;
+#[cfg(all(any(feature = "tcp", feature = "udp"), not(feature = "pci")))]
+use crate::arch::kernel::mmio as hardware;
+use crate::arch::kernel::processor;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use crate::drivers::net::NetworkDriver;
+#[cfg(all(any(feature = "tcp", feature = "udp"), feature = "pci"))]
+use crate::drivers::pci as hardware;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use crate::executor::network::NIC;
+use crate::percpu::{PerCpu, alloc_per_cpu};
+use crate::scheduler::task::NORMAL_PRIO;
+use crate::syscalls::usleep;
+use crate::{env, scheduler};
+
+/// Result of a check, `Ok` with the details of the measurement
+type CheckResult = Result<String, String>;
+type Check = fn() -> CheckResult;
+
+/// Number of allocations of the allocator stress test
+const ALLOCATIONS: usize = 512;
+/// Duration of the sleeps of the timer test in microseconds
+const SLEEP_DURATION: u64 = 10_000;
+/// Accepted deviation of the timer in microseconds
+const SLEEP_TOLERANCE: u64 = 5_000;
+/// Time, in which all cores have to run a task, in microseconds
+const IPI_TIMEOUT: u64 = 1_000_000;
+/// Time, in which the gateway has to answer the ARP request, in microseconds
+#[cfg(any(feature = "tcp", feature = "udp"))]
+const ARP_TIMEOUT: u64 = 1_000_000;
+
+/// Allocates blocks of pseudo-random sizes, fills them with a pattern and
+/// verifies the pattern before the blocks are freed in a different order.
+fn allocator() -> CheckResult {
+	let mut state: u32 = 0x1234_5678;
+	let mut next = || {
+		// xorshift32
+		state ^= state << 13;
+		state ^= state >> 17;
+		state ^= state << 5;
+		state
+	};
+
+	let mut blocks: Vec<Box<[u8]>> = Vec::with_capacity(ALLOCATIONS);
+	let mut bytes = 0;
+	for i in 0..ALLOCATIONS {
+		let size = 1 << (next() % 16);
+		let size = size + next() as usize % size;
+		blocks.push(vec![i as u8; size].into_boxed_slice());
+		bytes += size;
+	}
+
+	// free every second block first to fragment the heap
+	let (even, odd): (Vec<_>, Vec<_>) = blocks
+		.into_iter()
+		.enumerate()
+		.partition(|(i, _)| i % 2 == 0);
+	for (i, block) in even.into_iter().chain(odd) {
+		if block.iter().any(|byte| *byte != i as u8) {
+			return Err(format!("block {i} of {} bytes is corrupted", block.len()));
+		}
+	}
+
+	Ok(format!("{ALLOCATIONS} blocks, {} KiB", bytes >> 10))
+}
+
+/// Checks that every attached driver has set up its virtqueues.
+///
+/// The round trip through the network device is checked by [`network`].
+#[cfg(feature = "pci")]
+fn devices() -> CheckResult {
+	let count = crate::drivers::pci::get_driver_count();
+	let mut queues = 0;
+	for index in 0..count {
+		let count = crate::drivers::pci::get_queue_count(index);
+		if count == 0 {
+			return Err(format!("driver {index} has no virtqueues"));
+		}
+		queues += count;
+	}
+
+	Ok(format!("{count} drivers, {queues} virtqueues"))
+}
+
+/// Sends an ARP request for the IPv4 gateway through the network device and
+/// waits for the reply, so that the transmit and the receive path of the
+/// device are exercised.
+///
+/// The network interface is locked during the check, so that the network
+/// stack does not consume the reply. Frames, which are received meanwhile,
+/// are dropped.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+fn network() -> CheckResult {
+	let Some(driver) = hardware::get_network_driver() else {
+		return Ok(String::from("no network device"));
+	};
+
+	let mut guard = NIC.lock();
+	let nic = guard.as_nic_mut().map_err(String::from)?;
+	let (Some(IpAddress::Ipv4(address)), Some(gateway)) =
+		(nic.local_address(false), nic.ipv4_gateway())
+	else {
+		return Ok(String::from("no IPv4 gateway, skipped"));
+	};
+
+	let mut driver = driver.lock();
+	let mac = EthernetAddress(driver.get_mac_address());
+	let ethernet = EthernetRepr {
+		src_addr: mac,
+		dst_addr: EthernetAddress::BROADCAST,
+		ethertype: EthernetProtocol::Arp,
+	};
+	let arp = ArpRepr::EthernetIpv4 {
+		operation: ArpOperation::Request,
+		source_hardware_addr: mac,
+		source_protocol_addr: address,
+		target_hardware_addr: EthernetAddress([0; 6]),
+		target_protocol_addr: gateway,
+	};
+	driver.send_packet(ethernet.buffer_len() + arp.buffer_len(), |buffer| {
+		let mut frame = EthernetFrame::new_unchecked(buffer);
+		ethernet.emit(&mut frame);
+		arp.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+	});
+
+	let is_reply = |buffer: &[u8]| {
+		EthernetFrame::new_checked(buffer)
+			.ok()
+			.filter(|frame| frame.ethertype() == EthernetProtocol::Arp)
+			.and_then(|frame| {
+				ArpPacket::new_checked(frame.payload())
+					.and_then(|packet| ArpRepr::parse(&packet))
+					.ok()
+			})
+			.is_some_and(|repr| {
+				matches!(repr, ArpRepr::EthernetIpv4 {
+					operation: ArpOperation::Reply,
+					source_protocol_addr,
+					..
+				} if source_protocol_addr == gateway)
+			})
+	};
+
+	let start = processor::get_timer_ticks();
+	loop {
+		let elapsed = processor::get_timer_ticks() - start;
+		if let Some((rx, _)) = driver.receive_packet() {
+			if rx.consume(is_reply) {
+				return Ok(format!("ARP reply of {gateway} after {elapsed} us"));
+			}
+		} else if elapsed > ARP_TIMEOUT {
+			return Err(format!("no ARP reply of {gateway}"));
+		} else {
+			hint::spin_loop();
+		}
+	}
+}
+
+/// Compares the duration of sleeps with the time measured by the timer.
+fn timer() -> CheckResult {
+	let mut max_deviation = 0;
+	for _ in 0..3 {
+		let start = processor::get_timer_ticks();
+		usleep(SLEEP_DURATION);
+		let elapsed = processor::get_timer_ticks() - start;
+
+		if elapsed < SLEEP_DURATION {
+			return Err(format!(
+				"woke up after {elapsed} us instead of {SLEEP_DURATION} us"
+			));
+		}
+		max_deviation = max_deviation.max(elapsed - SLEEP_DURATION);
+	}
+
+	if max_deviation > SLEEP_TOLERANCE {
+		Err(format!("woke up {max_deviation} us late"))
+	} else {
+		Ok(format!("deviation {max_deviation} us"))
+	}
+}
+
+/// Set on every core, on which a task of the IPI test has been running
+static RESPONDED_CORES: Lazy<PerCpu<AtomicBool>> = Lazy::new(alloc_per_cpu);
+
+extern "C" fn respond(_arg: usize) {
+	RESPONDED_CORES.get().store(true, Ordering::SeqCst);
+}
+
+/// Spawns a task on every core, which wakes up the halted cores by an interrupt.
+fn ipi() -> CheckResult {
+	let cores = get_processor_count();
+	for (_, responded) in RESPONDED_CORES.iter() {
+		responded.store(false, Ordering::SeqCst);
+	}
+
+	for core_id in 0..cores {
+		unsafe {
+			scheduler::spawn(
+				respond,
+				0,
+				NORMAL_PRIO,
+				crate::config::DEFAULT_STACK_SIZE,
+				core_id.try_into().unwrap(),
+			);
+		}
+	}
+
+	let start = processor::get_timer_ticks();
+	let missing = || {
+		RESPONDED_CORES
+			.iter()
+			.filter(|(_, responded)| !responded.load(Ordering::SeqCst))
+			.map(|(core_id, _)| core_id)
+			.collect::<Vec<_>>()
+	};
+	while !missing().is_empty() {
+		if processor::get_timer_ticks() - start > IPI_TIMEOUT {
+			return Err(format!("no response of the cores {:?}", missing()));
+		}
+		usleep(SLEEP_DURATION);
+	}
+
+	Ok(format!("{cores} cores"))
+}
+
+pub(crate) fn init() {
+	if !env::selftest() {
+		return;
+	}
+
+	let checks: &[(&str, Check)] = &[
+		("allocator", allocator),
+		#[cfg(feature = "pci")]
+		("devices", devices),
+		#[cfg(any(feature = "tcp", feature = "udp"))]
+		("network", network),
+		("timer", timer),
+		("ipi", ipi),
+	];
+
+	let mut failed = 0;
+	for (name, check) in checks {
+		match check() {
+			Ok(details) => println!("selftest: {name:<10} PASS ({details})"),
+			Err(details) => {
+				println!("selftest: {name:<10} FAIL ({details})");
+				failed += 1;
+			}
+		}
+	}
+	println!(
+		"selftest: {} of {} checks passed",
+		checks.len() - failed,
+		checks.len()
+	);
+}
@@ -26,6 +26,96 @@ pub(crate) fn init() {
 		},
 		aliases: &["i"],
 	});
+	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+	shell.commands.insert("watch", ShellCommand {
+		help: "Sets a watchpoint: watch <addr> [1|2|4|8] [w|rw]",
+		func: |args, _| {
+			use memory_addresses::VirtAddr;
+
+			use crate::watchpoint::{self, WatchKind};
+
+			if args.is_empty() {
+				for (slot, watchpoint) in watchpoint::list().iter().enumerate() {
+					if let Some(watchpoint) = watchpoint {
+						println!(
+							"{slot}: {:p} ({} bytes, {:?})",
+							watchpoint.addr, watchpoint.len, watchpoint.kind
+						);
+					}
+				}
+				return Ok(());
+			}
+
+			let addr = args[0].trim_start_matches("0x");
+			let addr = u64::from_str_radix(addr, 16).map_err(|_| "invalid address")?;
+			let len = match args.get(1) {
+				Some(len) => len.parse().map_err(|_| "invalid length")?,
+				None => 8,
+			};
+			let kind = match args.get(2).copied() {
+				None | Some("w") => WatchKind::Write,
+				Some("rw") => WatchKind::ReadWrite,
+				Some(_) => return Err("invalid kind"),
+			};
+
+			let slot = watchpoint::set(VirtAddr::new(addr), len, kind)
+				.map_err(|_| "unable to set watchpoint")?;
+			println!("Watchpoint {slot} set");
+			Ok(())
+		},
+		aliases: &["w"],
+	});
+	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+	shell.commands.insert("unwatch", ShellCommand {
+		help: "Removes a watchpoint: unwatch <slot>",
+		func: |args, _| {
+			let slot = args
+				.first()
+				.and_then(|slot| slot.parse().ok())
+				.ok_or("invalid slot")?;
+			crate::watchpoint::clear(slot).map_err(|_| "unknown watchpoint")
+		},
+		aliases: &[],
+	});
+	#[cfg(any(feature = "tcp", feature = "udp"))]
+	shell.commands.insert("route", ShellCommand {
+		help: "Shows or modifies the routes: route [add <cidr> <gateway> [metric] | del <cidr> [gateway]]",
+		func: |args, _| {
+			use core::str::FromStr;
+
+			use smoltcp::wire::{IpAddress, IpCidr};
+
+			use crate::executor::network;
+
+			let cidr = |arg: Option<&&str>| match arg.copied() {
+				Some("default") => Ok(IpCidr::from_str("0.0.0.0/0").unwrap()),
+				Some(arg) => IpCidr::from_str(arg).map_err(|()| "invalid destination"),
+				None => Err("missing destination"),
+			};
+			let gateway = |arg: &str| IpAddress::from_str(arg).map_err(|()| "invalid gateway");
+
+			match args.first().copied() {
+				None => print!("{}", network::route_report()),
+				Some("add") => {
+					let gateway = gateway(args.get(2).ok_or("missing gateway")?)?;
+					let metric = match args.get(3) {
+						Some(metric) => metric.parse().map_err(|_| "invalid metric")?,
+						None => 0,
+					};
+					network::add_route(cidr(args.get(1))?, gateway, metric)
+						.map_err(|_| "unable to add route")?;
+				}
+				Some("del") => {
+					let gateway = args.get(2).map(|arg| gateway(arg)).transpose()?;
+					network::remove_route(cidr(args.get(1))?, gateway)
+						.map_err(|_| "unable to remove route")?;
+				}
+				Some(_) => return Err("unknown subcommand"),
+			}
+			Ok(())
+		},
+		aliases: &[],
+	});
 	shell.commands.insert("shutdown", ShellCommand {
 		help: "Shutdown HermitOS",
 		func: |_, _| crate::scheduler::shutdown(0),
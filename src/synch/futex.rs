@@ -10,7 +10,7 @@ use crate::arch::kernel::core_local::core_scheduler;
 use crate::arch::kernel::processor::get_timer_ticks;
 use crate::errno::{EAGAIN, EINVAL, ETIMEDOUT};
 use crate::scheduler::PerCoreSchedulerExt;
-use crate::scheduler::task::TaskHandlePriorityQueue;
+use crate::scheduler::task::{TaskHandlePriorityQueue, TaskId};
 
 // TODO: Replace with a concurrent hashmap.
 static PARKING_LOT: InterruptTicketMutex<HashMap<usize, TaskHandlePriorityQueue, RandomState>> =
@@ -164,6 +164,23 @@ pub(crate) fn futex_wait_and_set(
 	}
 }
 
+/// Wakes up the task `id`, if it waits on any futex. Returns `true`, if the task was woken up.
+pub(crate) fn futex_wake_task(id: TaskId) -> bool {
+	let mut parking_lot = PARKING_LOT.lock();
+	let Some((address, handle)) = parking_lot
+		.iter_mut()
+		.find_map(|(address, queue)| Some((*address, queue.take(id)?)))
+	else {
+		return false;
+	};
+
+	if parking_lot[&address].is_empty() {
+		parking_lot.remove(&address);
+	}
+	core_scheduler().custom_wakeup(handle);
+	true
+}
+
 /// Wake `count` threads waiting on the futex at address. Returns the number of threads
 /// woken up (saturates to `i32::MAX`). If `count` is `i32::MAX`, wake up all matching
 /// waiting threads. If `count` is negative, returns -EINVAL.
@@ -1,6 +1,32 @@
 //! Synchronization primitives
 
 pub mod futex;
+pub(crate) mod rcu;
 #[cfg(feature = "newlib")]
 pub mod recmutex;
 pub mod semaphore;
+#[cfg(feature = "sync-stats")]
+pub(crate) mod stats;
+
+/// Runs `f` with disabled interrupts.
+///
+/// With the feature `sync-stats`, the time, in which interrupts are disabled, is recorded.
+#[inline]
+pub(crate) fn without_interrupts<F, R>(f: F) -> R
+where
+	F: FnOnce() -> R,
+{
+	#[cfg(feature = "sync-stats")]
+	if crate::arch::kernel::interrupts::are_enabled() {
+		use crate::arch::kernel::processor::get_timestamp;
+
+		return hermit_sync::without_interrupts(|| {
+			let start = get_timestamp();
+			let ret = f();
+			stats::record_irq_disabled(get_timestamp().saturating_sub(start));
+			ret
+		});
+	}
+
+	hermit_sync::without_interrupts(f)
+}
@@ -0,0 +1,98 @@
+//! Read-copy-update for read-mostly data
+//!
+//! Readers access the current version of the data without taking a lock,
+//! which makes [`Rcu`] usable in interrupt handlers. A writer publishes a new
+//! version and frees the old one, after all readers, which might still
+//! access it, have left their read-side critical section.
+//!
+//! Readers are tracked in two epochs. A reader registers itself in the
+//! current epoch. A writer switches to the other epoch before waiting for the
+//! readers of the previous one, so that new readers cannot delay the writer
+//! indefinitely. This is done twice, so that the readers of both epochs
+//! have finished.
+
+use alloc::boxed::Box;
+use core::hint::spin_loop;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use hermit_sync::SpinMutex;
+
+use crate::synch::without_interrupts;
+
+pub(crate) struct Rcu<T> {
+	/// Current version, null if no version has been published
+	current: AtomicPtr<T>,
+	/// Number of readers in each epoch
+	readers: [AtomicUsize; 2],
+	/// Current epoch, whose lowest bit selects the reader counter
+	epoch: AtomicUsize,
+	/// Serializes the writers
+	writer: SpinMutex<()>,
+}
+
+impl<T> Rcu<T> {
+	/// Creates an instance without any published version.
+	pub const fn new() -> Self {
+		Self {
+			current: AtomicPtr::new(ptr::null_mut()),
+			readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+			epoch: AtomicUsize::new(0),
+			writer: SpinMutex::new(()),
+		}
+	}
+
+	/// Runs `f` on the current version.
+	///
+	/// Interrupts are disabled within `f`, so that a reader cannot be
+	/// preempted by a writer on the same core. Hence, `f` must be short
+	/// and must not block.
+	pub fn read<R>(&self, f: impl FnOnce(Option<&T>) -> R) -> R {
+		without_interrupts(|| {
+			let epoch = self.epoch.load(Ordering::SeqCst) & 1;
+			self.readers[epoch].fetch_add(1, Ordering::SeqCst);
+			// A writer, which has checked the readers before the increment,
+			// has already published the new version.
+			let current = self.current.load(Ordering::SeqCst);
+			let ret = f(unsafe { current.as_ref() });
+			self.readers[epoch].fetch_sub(1, Ordering::SeqCst);
+			ret
+		})
+	}
+
+	/// Publishes `value` as the new version and frees the previous one,
+	/// after all of its readers have finished.
+	///
+	/// Must not be called from interrupt handlers or read-side critical sections.
+	pub fn replace(&self, value: T) {
+		let _guard = self.writer.lock();
+		let previous = self
+			.current
+			.swap(Box::into_raw(Box::new(value)), Ordering::SeqCst);
+
+		// A reader may have read the epoch before the last switch, but registered
+		// itself afterwards. Therefore, the readers of both epochs have to finish.
+		for _ in 0..2 {
+			let epoch = self.epoch.fetch_add(1, Ordering::SeqCst) & 1;
+			while self.readers[epoch].load(Ordering::SeqCst) != 0 {
+				spin_loop();
+			}
+		}
+
+		if !previous.is_null() {
+			drop(unsafe { Box::from_raw(previous) });
+		}
+	}
+}
+
+impl<T> Drop for Rcu<T> {
+	fn drop(&mut self) {
+		let current = *self.current.get_mut();
+		if !current.is_null() {
+			drop(unsafe { Box::from_raw(current) });
+		}
+	}
+}
+
+unsafe impl<T: Send + Sync> Sync for Rcu<T> {}
+unsafe impl<T: Send> Send for Rcu<T> {}
@@ -0,0 +1,172 @@
+//! Interrupt latency and lock contention statistics.
+//!
+//! If the kernel is built with the feature `sync-stats`, the kernel records how
+//! long interrupts are disabled by [`without_interrupts`](super::without_interrupts)
+//! and how often locks, which are acquired by [`tracked_lock!`], are contended.
+//! The statistics are readable from `/proc/metrics`.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::fmt::Write;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
+
+use lock_api::{Mutex, MutexGuard, RawMutex};
+
+use crate::arch::kernel::processor::{get_frequency, get_timestamp};
+
+/// Number of buckets of a histogram; bucket `i` counts durations of
+/// less than `2^(i + 1)` CPU cycles and the last bucket counts all longer durations.
+const BUCKETS: usize = 32;
+
+struct Histogram {
+	buckets: [AtomicU64; BUCKETS],
+	max: AtomicU64,
+}
+
+impl Histogram {
+	const fn new() -> Self {
+		Self {
+			buckets: [const { AtomicU64::new(0) }; BUCKETS],
+			max: AtomicU64::new(0),
+		}
+	}
+
+	fn record(&self, cycles: u64) {
+		let bucket = (u64::BITS - cycles.leading_zeros()).saturating_sub(1) as usize;
+		self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+		self.max.fetch_max(cycles, Ordering::Relaxed);
+	}
+
+	fn format(&self, out: &mut String) -> core::fmt::Result {
+		let mhz = u64::from(get_frequency()).max(1);
+		for (i, bucket) in self.buckets.iter().enumerate() {
+			let count = bucket.load(Ordering::Relaxed);
+			if count == 0 {
+				continue;
+			}
+			if i == BUCKETS - 1 {
+				writeln!(out, "  >= {:>10} ns: {count}", (1000 << i) / mhz)?;
+			} else {
+				writeln!(out, "  < {:>11} ns: {count}", (1000 << (i + 1)) / mhz)?;
+			}
+		}
+		writeln!(
+			out,
+			"  max: {} ns",
+			self.max.load(Ordering::Relaxed) * 1000 / mhz
+		)
+	}
+}
+
+/// Durations, in which interrupts were disabled
+static IRQ_DISABLED: Histogram = Histogram::new();
+
+/// Head of the list of all locks, which have been acquired at least once
+static LOCKS: AtomicPtr<LockStats> = AtomicPtr::new(ptr::null_mut());
+
+/// Contention statistics of a single lock
+pub(crate) struct LockStats {
+	name: &'static str,
+	acquisitions: AtomicU64,
+	contentions: AtomicU64,
+	/// Sum of CPU cycles, which were spent waiting for the lock
+	wait_cycles: AtomicU64,
+	registered: AtomicBool,
+	next: AtomicPtr<LockStats>,
+}
+
+impl LockStats {
+	pub const fn new(name: &'static str) -> Self {
+		Self {
+			name,
+			acquisitions: AtomicU64::new(0),
+			contentions: AtomicU64::new(0),
+			wait_cycles: AtomicU64::new(0),
+			registered: AtomicBool::new(false),
+			next: AtomicPtr::new(ptr::null_mut()),
+		}
+	}
+
+	fn register(&'static self) {
+		if self.registered.swap(true, Ordering::AcqRel) {
+			return;
+		}
+
+		let this = ptr::from_ref(self).cast_mut();
+		let mut head = LOCKS.load(Ordering::Acquire);
+		loop {
+			self.next.store(head, Ordering::Relaxed);
+			match LOCKS.compare_exchange_weak(head, this, Ordering::AcqRel, Ordering::Acquire) {
+				Ok(_) => break,
+				Err(current) => head = current,
+			}
+		}
+	}
+
+	/// Acquires `mutex` and counts, whether the lock was contended.
+	pub fn lock<'a, R: RawMutex, T: ?Sized>(
+		&'static self,
+		mutex: &'a Mutex<R, T>,
+	) -> MutexGuard<'a, R, T> {
+		self.register();
+		self.acquisitions.fetch_add(1, Ordering::Relaxed);
+
+		if let Some(guard) = mutex.try_lock() {
+			return guard;
+		}
+
+		self.contentions.fetch_add(1, Ordering::Relaxed);
+		let start = get_timestamp();
+		let guard = mutex.lock();
+		self.wait_cycles
+			.fetch_add(get_timestamp().saturating_sub(start), Ordering::Relaxed);
+		guard
+	}
+}
+
+/// Records that interrupts were disabled for `cycles` CPU cycles.
+pub(crate) fn record_irq_disabled(cycles: u64) {
+	IRQ_DISABLED.record(cycles);
+}
+
+/// Returns the statistics in a human-readable form.
+pub(crate) fn report() -> String {
+	let mut out = String::new();
+	// writing to a `String` does not fail
+	format_report(&mut out).unwrap();
+	out
+}
+
+fn format_report(out: &mut String) -> core::fmt::Result {
+	writeln!(out, "interrupts disabled:")?;
+	IRQ_DISABLED.format(out)?;
+
+	let mhz = u64::from(get_frequency()).max(1);
+	writeln!(out)?;
+	writeln!(
+		out,
+		"{:<24} {:>14} {:>12} {:>14}",
+		"lock", "acquisitions", "contentions", "wait time (ns)"
+	)?;
+	// a lock may be acquired at several places, which are accounted separately
+	let mut locks = BTreeMap::<&str, (u64, u64, u64)>::new();
+	let mut current = LOCKS.load(Ordering::Acquire);
+	while let Some(stats) = unsafe { current.as_ref() } {
+		let entry = locks.entry(stats.name).or_default();
+		entry.0 += stats.acquisitions.load(Ordering::Relaxed);
+		entry.1 += stats.contentions.load(Ordering::Relaxed);
+		entry.2 += stats.wait_cycles.load(Ordering::Relaxed);
+		current = stats.next.load(Ordering::Acquire);
+	}
+
+	for (name, (acquisitions, contentions, wait_cycles)) in locks {
+		writeln!(
+			out,
+			"{name:<24} {acquisitions:>14} {contentions:>12} {:>14}",
+			wait_cycles * 1000 / mhz
+		)?;
+	}
+
+	Ok(())
+}
@@ -0,0 +1,48 @@
+//! Auditing of system calls
+//!
+//! With the feature `syscall-audit`, every system call passes [`enter`] and
+//! [`exit`]. The behavior is configured by the kernel arguments:
+//!
+//! - `syscall.log` logs the entry and exit of each system call together with
+//!   the calling task.
+//! - `syscall.allow=<name>,...` restricts the application to the given system
+//!   calls.
+//! - `syscall.deny=<name>,...` forbids the given system calls.
+//!
+//! The names are given without the `sys_` prefix, e.g.,
+//! `syscall.allow=read,write,exit`. Similar to seccomp, the application is
+//! terminated, if it calls a forbidden system call.
+
+use core::fmt::Debug;
+
+use crate::arch::core_local::core_scheduler;
+use crate::env;
+
+/// Exit code of an application, which has been terminated by the policy (`128 + SIGSYS`)
+const EXIT_CODE: i32 = 128 + 31;
+
+fn is_allowed(name: &str) -> bool {
+	env::syscall_allow().is_none_or(|allow| allow.iter().any(|allowed| allowed == name))
+		&& !env::syscall_deny().iter().any(|denied| denied == name)
+}
+
+/// Called at the entry of the system call `sys_<name>`.
+pub(crate) fn enter(name: &str) {
+	let task_id = core_scheduler().get_current_task_id();
+	if !is_allowed(name) {
+		error!("Task {task_id} called the forbidden system call {name}, terminating");
+		super::shutdown(EXIT_CODE);
+	}
+
+	if env::syscall_log() {
+		info!("Task {task_id} enters {name}");
+	}
+}
+
+/// Called at the exit of the system call `sys_<name>` with its return value.
+pub(crate) fn exit<T: Debug + ?Sized>(name: &str, ret: &T) {
+	if env::syscall_log() {
+		let task_id = core_scheduler().get_current_task_id();
+		info!("Task {task_id} exits {name} = {ret:?}");
+	}
+}
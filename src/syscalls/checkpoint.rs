@@ -0,0 +1,28 @@
+use crate::checkpoint;
+use crate::errno::*;
+use crate::fd::FileDescriptor;
+
+/// Writes a checkpoint of the application state to `fd`, e.g., a file or a vsock stream.
+///
+/// Returns `-EBUSY`, if the application runs more than one task.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_checkpoint(fd: FileDescriptor) -> i32 {
+	checkpoint::checkpoint(fd).map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Adds `len` bytes at `ptr` as region `id` to the checkpoints.
+///
+/// If the kernel was booted with a checkpoint, which contains region `id` with
+/// the same size, the region is filled with the saved data and 1 is returned.
+/// Otherwise, the region is left unchanged and 0 is returned.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_checkpoint_region(id: u32, ptr: *mut u8, len: usize) -> i32 {
+	if ptr.is_null() {
+		return -EFAULT;
+	}
+
+	checkpoint::register_region(id, ptr.addr(), len)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), i32::from)
+}
@@ -0,0 +1,92 @@
+//! Custom system calls, which are provided by the application
+//!
+//! At startup, the application may register handlers for the system call
+//! numbers `CUSTOM_SYSCALL_START..CUSTOM_SYSCALL_START + CUSTOM_SYSCALL_COUNT`.
+//! Afterwards, the application or libraries, which do not know the handlers,
+//! invoke them through `sys_custom_syscall`. Like other system calls, the
+//! handlers run on the kernel stack of the calling task.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::env;
+use crate::errno::*;
+
+/// First system call number, which is reserved for custom system calls
+pub const CUSTOM_SYSCALL_START: usize = 1024;
+
+/// Number of custom system calls
+pub const CUSTOM_SYSCALL_COUNT: usize = 64;
+
+/// Handler of a custom system call, which receives the arguments of `sys_custom_syscall`
+pub type CustomSyscall = unsafe extern "C" fn(usize, usize, usize, usize, usize) -> isize;
+
+/// Addresses of the registered handlers, zero if unused
+static HANDLERS: [AtomicUsize; CUSTOM_SYSCALL_COUNT] =
+	[const { AtomicUsize::new(0) }; CUSTOM_SYSCALL_COUNT];
+
+fn handler_slot(nr: usize) -> Option<&'static AtomicUsize> {
+	HANDLERS.get(nr.checked_sub(CUSTOM_SYSCALL_START)?)
+}
+
+/// Checks that `handler` is code of the kernel image, to which the application is linked.
+fn is_valid_handler(handler: usize) -> bool {
+	let start = env::get_base_address().as_usize();
+	let end = start + env::get_image_size();
+	(start..end).contains(&handler)
+}
+
+/// Registers `handler` for the custom system call `nr`. Without `handler`,
+/// the registered handler is removed.
+///
+/// Returns `-EINVAL` if `nr` is not a custom system call number, `-EFAULT` if
+/// `handler` is not part of the application and `-EBUSY` if another handler
+/// is already registered for `nr`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_register_syscall(nr: usize, handler: Option<CustomSyscall>) -> i32 {
+	let Some(slot) = handler_slot(nr) else {
+		return -EINVAL;
+	};
+
+	let Some(handler) = handler else {
+		slot.store(0, Ordering::Release);
+		return 0;
+	};
+
+	let addr = handler as usize;
+	if !is_valid_handler(addr) {
+		return -EFAULT;
+	}
+
+	match slot.compare_exchange(0, addr, Ordering::AcqRel, Ordering::Acquire) {
+		Ok(_) => {
+			debug!("Register custom system call {nr} at {addr:#x}");
+			0
+		}
+		Err(old) if old == addr => 0,
+		Err(_) => -EBUSY,
+	}
+}
+
+/// Invokes the handler of the custom system call `nr` with the arguments
+/// `arg0` to `arg4` and returns its result.
+///
+/// Returns `-ENOSYS` if no handler is registered for `nr`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_custom_syscall(
+	nr: usize,
+	arg0: usize,
+	arg1: usize,
+	arg2: usize,
+	arg3: usize,
+	arg4: usize,
+) -> isize {
+	let addr = handler_slot(nr).map_or(0, |slot| slot.load(Ordering::Acquire));
+	if addr == 0 {
+		return -ENOSYS as isize;
+	}
+
+	let handler = unsafe { core::mem::transmute::<usize, CustomSyscall>(addr) };
+	unsafe { handler(arg0, arg1, arg2, arg3, arg4) }
+}
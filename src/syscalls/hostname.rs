@@ -0,0 +1,76 @@
+use core::ffi::{CStr, c_char};
+
+use crate::errno::*;
+use crate::hostname::{self, HOST_NAME_MAX};
+use crate::io;
+
+unsafe fn copy_name(name: &str, buf: *mut c_char, len: usize) -> i32 {
+	if buf.is_null() {
+		return -EFAULT;
+	}
+	if name.len() >= len {
+		return -ENAMETOOLONG;
+	}
+
+	let buf = unsafe { core::slice::from_raw_parts_mut(buf.cast::<u8>(), len) };
+	buf[..name.len()].copy_from_slice(name.as_bytes());
+	buf[name.len()] = 0;
+
+	0
+}
+
+unsafe fn read_name<'a>(buf: *const c_char, len: usize) -> Result<&'a str, i32> {
+	if buf.is_null() {
+		return Err(-EFAULT);
+	}
+	if len > HOST_NAME_MAX {
+		return Err(-EINVAL);
+	}
+
+	let bytes = unsafe { core::slice::from_raw_parts(buf.cast::<u8>(), len) };
+	// the name is not required to be null-terminated
+	let bytes = CStr::from_bytes_until_nul(bytes).map_or(bytes, CStr::to_bytes);
+	core::str::from_utf8(bytes).map_err(|_| -EINVAL)
+}
+
+fn into_errno(result: io::Result<()>) -> i32 {
+	result.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Copies the null-terminated host name into the buffer `name` of size `len`.
+///
+/// Returns `-ENAMETOOLONG` if the buffer is too small.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_gethostname(name: *mut c_char, len: usize) -> i32 {
+	unsafe { copy_name(&hostname::hostname(), name, len) }
+}
+
+/// Sets the host name to the `len` bytes in `name`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sethostname(name: *const c_char, len: usize) -> i32 {
+	match unsafe { read_name(name, len) } {
+		Ok(name) => into_errno(hostname::set_hostname(name)),
+		Err(e) => e,
+	}
+}
+
+/// Copies the null-terminated DNS domain name into the buffer `name` of size `len`.
+///
+/// Returns `-ENAMETOOLONG` if the buffer is too small.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_getdomainname(name: *mut c_char, len: usize) -> i32 {
+	unsafe { copy_name(&hostname::domainname(), name, len) }
+}
+
+/// Sets the DNS domain name to the `len` bytes in `name`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_setdomainname(name: *const c_char, len: usize) -> i32 {
+	match unsafe { read_name(name, len) } {
+		Ok(name) => into_errno(hostname::set_domainname(name)),
+		Err(e) => e,
+	}
+}
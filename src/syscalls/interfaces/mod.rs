@@ -3,7 +3,7 @@ use alloc::vec::Vec;
 
 pub use self::generic::*;
 pub use self::uhyve::*;
-use crate::{arch, env};
+use crate::{arch, env, io};
 
 mod generic;
 pub(crate) mod uhyve;
@@ -54,4 +54,8 @@ pub trait SyscallInterface: Send + Sync {
 
 		arch::processor::shutdown(error_code)
 	}
+
+	fn reboot(&self) -> io::Result<!> {
+		arch::processor::reboot()
+	}
 }
@@ -4,9 +4,9 @@ use memory_addresses::VirtAddr;
 use uhyve_interface::parameters::{ExitParams, SerialWriteBufferParams};
 use uhyve_interface::{Hypercall, HypercallAddress};
 
-use crate::arch;
 use crate::arch::mm::paging::{self, virtual_to_physical};
 use crate::syscalls::interfaces::SyscallInterface;
+use crate::{arch, io};
 
 /// perform a SerialWriteBuffer hypercall with `buf` as payload.
 #[inline]
@@ -88,4 +88,9 @@ impl SyscallInterface for Uhyve {
 			arch::processor::halt();
 		}
 	}
+
+	/// Uhyve does not provide a hypercall to reset the virtual machine.
+	fn reboot(&self) -> io::Result<!> {
+		Err(io::Error::ENOSYS)
+	}
 }
@@ -1,4 +1,9 @@
+use alloc::collections::BTreeMap;
+use core::ops::Range;
+use core::ptr;
+
 use align_address::Align;
+use hermit_sync::InterruptTicketMutex;
 use memory_addresses::VirtAddr;
 
 use crate::arch;
@@ -21,6 +26,40 @@ bitflags! {
 	}
 }
 
+/// Sizes of the mappings, which have been created by `sys_mmap`, by their start address
+static MAPPINGS: InterruptTicketMutex<BTreeMap<VirtAddr, usize>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Returns `true`, if `range` lies within a mapping of `sys_mmap`.
+fn is_mapped(range: &Range<VirtAddr>) -> bool {
+	MAPPINGS
+		.lock()
+		.range(..=range.start)
+		.next_back()
+		.is_some_and(|(start, size)| range.end <= *start + *size as u64)
+}
+
+/// Removes `range` from the mappings of `sys_mmap`.
+fn remove_mapping(range: Range<VirtAddr>) {
+	let mut mappings = MAPPINGS.lock();
+	let overlapping: alloc::vec::Vec<_> = mappings
+		.range(..range.end)
+		.filter(|(start, size)| range.start < **start + **size as u64)
+		.map(|(start, size)| (*start, *size))
+		.collect();
+
+	for (start, size) in overlapping {
+		mappings.remove(&start);
+		let end = start + size as u64;
+		if start < range.start {
+			mappings.insert(start, (range.start - start) as usize);
+		}
+		if range.end < end {
+			mappings.insert(range.end, (end - range.end) as usize);
+		}
+	}
+}
+
 /// Creates a new virtual memory mapping of the `size` specified with
 /// protection bits specified in `prot_flags`.
 #[hermit_macro::system]
@@ -28,6 +67,7 @@ bitflags! {
 pub extern "C" fn sys_mmap(size: usize, prot_flags: MemoryProtection, ret: &mut *mut u8) -> i32 {
 	let size = size.align_up(BasePageSize::SIZE as usize);
 	let virtual_address = arch::mm::virtualmem::allocate(size).unwrap();
+	MAPPINGS.lock().insert(virtual_address, size);
 	if prot_flags.is_empty() {
 		*ret = virtual_address.as_mut_ptr();
 		return 0;
@@ -67,6 +107,7 @@ pub extern "C" fn sys_munmap(ptr: *mut u8, size: usize) -> i32 {
 	}
 
 	arch::mm::virtualmem::deallocate(virtual_address, size);
+	remove_mapping(virtual_address..virtual_address + size as u64);
 
 	0
 }
@@ -99,3 +140,58 @@ pub extern "C" fn sys_mprotect(ptr: *mut u8, size: usize, prot_flags: MemoryProt
 		0
 	}
 }
+
+/// No special treatment
+pub const MADV_NORMAL: i32 = 0;
+/// Expect page references in random order
+pub const MADV_RANDOM: i32 = 1;
+/// Expect page references in sequential order
+pub const MADV_SEQUENTIAL: i32 = 2;
+/// Expect access in the near future
+pub const MADV_WILLNEED: i32 = 3;
+/// The pages are not needed anymore and read as zero afterwards
+pub const MADV_DONTNEED: i32 = 4;
+/// The pages may be freed, if memory is needed
+pub const MADV_FREE: i32 = 8;
+
+/// Gives advice about the use of the memory at `ptr` for `size` bytes, which
+/// has to lie within a mapping of `sys_mmap`.
+///
+/// With `MADV_DONTNEED`, the mapped pages are zeroed. As pages are not
+/// released without demand paging, all other supported advices, including
+/// `MADV_FREE`, are ignored.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_madvise(ptr: *mut u8, size: usize, advice: i32) -> i32 {
+	let virtual_address = VirtAddr::from_ptr(ptr);
+	if !virtual_address.is_aligned_to(BasePageSize::SIZE) {
+		return -crate::errno::EINVAL;
+	}
+	if !matches!(
+		advice,
+		MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL | MADV_WILLNEED | MADV_DONTNEED | MADV_FREE
+	) {
+		return -crate::errno::EINVAL;
+	}
+
+	let size = size.align_up(BasePageSize::SIZE as usize);
+	let Some(end) = virtual_address.as_u64().checked_add(size as u64) else {
+		return -crate::errno::ENOMEM;
+	};
+	if !is_mapped(&(virtual_address..VirtAddr::new(end))) {
+		return -crate::errno::ENOMEM;
+	}
+
+	if advice == MADV_DONTNEED {
+		for i in 0..size / BasePageSize::SIZE as usize {
+			let page = virtual_address + i as u64 * BasePageSize::SIZE;
+			if arch::mm::paging::virtual_to_physical(page).is_some() {
+				unsafe {
+					ptr::write_bytes(page.as_mut_ptr::<u8>(), 0, BasePageSize::SIZE as usize);
+				}
+			}
+		}
+	}
+
+	0
+}
@@ -1,19 +1,24 @@
 #![allow(clippy::result_unit_err)]
 
+use alloc::vec::Vec;
 #[cfg(all(target_os = "none", not(feature = "common-os")))]
 use core::alloc::{GlobalAlloc, Layout};
-use core::ffi::{CStr, c_char};
+use core::ffi::{CStr, c_char, c_ulong, c_void};
 use core::marker::PhantomData;
 use core::ptr;
 
 use hermit_sync::Lazy;
 
+pub use self::checkpoint::*;
 pub use self::condvar::*;
+pub use self::custom::*;
 pub use self::entropy::*;
 pub use self::futex::*;
+pub use self::hostname::*;
 pub use self::processor::*;
 #[cfg(feature = "newlib")]
 pub use self::recmutex::*;
+pub use self::rlimit::*;
 pub use self::semaphore::*;
 pub use self::spinlock::*;
 pub use self::system::*;
@@ -21,24 +26,31 @@ pub use self::tasks::*;
 pub use self::timer::*;
 use crate::executor::block_on;
 use crate::fd::{
-	AccessPermission, EventFlags, FileDescriptor, IoCtl, OpenOption, PollFd, dup_object,
-	get_object, remove_object,
+	AccessPermission, EventFlags, FD_SETSIZE, FdSet, FileDescriptor, IoCtl, OpenOption, PollEvent,
+	PollFd, dup_object, get_object, remove_object,
 };
 use crate::fs::{self, FileAttr};
 #[cfg(all(target_os = "none", not(feature = "common-os")))]
 use crate::mm::ALLOCATOR;
 use crate::syscalls::interfaces::SyscallInterface;
+use crate::time::timeval;
 use crate::{env, io};
 
+#[cfg(feature = "syscall-audit")]
+pub(crate) mod audit;
+mod checkpoint;
 mod condvar;
+mod custom;
 mod entropy;
 mod futex;
+mod hostname;
 pub(crate) mod interfaces;
 #[cfg(feature = "mmap")]
 mod mmap;
 mod processor;
 #[cfg(feature = "newlib")]
 mod recmutex;
+mod rlimit;
 mod semaphore;
 #[cfg(any(feature = "tcp", feature = "udp", feature = "vsock"))]
 pub mod socket;
@@ -60,13 +72,41 @@ pub(crate) static SYS: Lazy<&'static dyn SyscallInterface> = Lazy::new(|| {
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 /// Describes  a  region  of  memory, beginning at `iov_base` address and with the size of `iov_len` bytes.
-struct iovec {
+pub struct iovec {
 	/// Starting address
 	pub iov_base: *mut u8,
 	/// Size of the memory pointed to by iov_base.
 	pub iov_len: usize,
 }
 
+impl iovec {
+	/// Returns the described memory region.
+	///
+	/// # Safety
+	///
+	/// `iov_base` must point to `iov_len` readable bytes, unless `iov_len` is zero.
+	unsafe fn as_slice<'a>(&self) -> &'a [u8] {
+		if self.iov_len == 0 {
+			&[]
+		} else {
+			unsafe { core::slice::from_raw_parts(self.iov_base, self.iov_len) }
+		}
+	}
+
+	/// Returns the described memory region.
+	///
+	/// # Safety
+	///
+	/// `iov_base` must point to `iov_len` writable bytes, unless `iov_len` is zero.
+	unsafe fn as_mut_slice<'a>(&self) -> &'a mut [u8] {
+		if self.iov_len == 0 {
+			&mut []
+		} else {
+			unsafe { core::slice::from_raw_parts_mut(self.iov_base, self.iov_len) }
+		}
+	}
+}
+
 const IOV_MAX: usize = 1024;
 
 pub(crate) fn init() {
@@ -104,6 +144,9 @@ pub extern "C" fn sys_alloc(size: usize, align: usize) -> *mut u8 {
 		ptr, size, align
 	);
 
+	#[cfg(feature = "heap-profile")]
+	crate::mm::profile::record_alloc(ptr, size);
+
 	ptr
 }
 
@@ -127,6 +170,9 @@ pub extern "C" fn sys_alloc_zeroed(size: usize, align: usize) -> *mut u8 {
 		ptr, size, align
 	);
 
+	#[cfg(feature = "heap-profile")]
+	crate::mm::profile::record_alloc(ptr, size);
+
 	ptr
 }
 
@@ -150,6 +196,9 @@ pub extern "C" fn sys_malloc(size: usize, align: usize) -> *mut u8 {
 		ptr, size, align
 	);
 
+	#[cfg(feature = "heap-profile")]
+	crate::mm::profile::record_alloc(ptr, size);
+
 	ptr
 }
 
@@ -203,6 +252,12 @@ pub unsafe extern "C" fn sys_realloc(
 				"__sys_realloc: resized memory at {:p}, new address {:p}",
 				ptr, new_ptr
 			);
+
+			#[cfg(feature = "heap-profile")]
+			{
+				crate::mm::profile::record_dealloc(ptr, size);
+				crate::mm::profile::record_alloc(new_ptr, new_size);
+			}
 		}
 		new_ptr
 	}
@@ -239,6 +294,9 @@ pub unsafe extern "C" fn sys_dealloc(ptr: *mut u8, size: usize, align: usize) {
 		}
 		let layout = layout_res.unwrap();
 		ALLOCATOR.dealloc(ptr, layout);
+
+		#[cfg(feature = "heap-profile")]
+		crate::mm::profile::record_dealloc(ptr, size);
 	}
 }
 
@@ -263,20 +321,59 @@ pub unsafe extern "C" fn sys_free(ptr: *mut u8, size: usize, align: usize) {
 		}
 		let layout = layout_res.unwrap();
 		ALLOCATOR.dealloc(ptr, layout);
+
+		#[cfg(feature = "heap-profile")]
+		crate::mm::profile::record_dealloc(ptr, size);
 	}
 }
 
+/// Writes the heap profile of the application to the file `path`.
+///
+/// Requires the kernel to be built with the feature `heap-profile`.
+#[cfg(all(
+	target_os = "none",
+	feature = "heap-profile",
+	not(feature = "common-os")
+))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_heap_profile_dump(path: *const c_char) -> i32 {
+	if path.is_null() {
+		return -crate::errno::EINVAL;
+	}
+
+	let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+		return -crate::errno::EINVAL;
+	};
+
+	crate::mm::profile::dump(path).map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
 pub(crate) fn get_application_parameters() -> (i32, *const *const u8, *const *const u8) {
 	SYS.get_application_parameters()
 }
 
+/// Powers off the system after closing the files and sockets of the
+/// application and tearing down the subsystems.
 pub(crate) fn shutdown(arg: i32) -> ! {
 	// print some performance statistics
 	crate::arch::kernel::print_statistics();
+	crate::scheduler::print_statistics();
+
+	crate::initcall::teardown();
+
+	SYS.shutdown(arg)
+}
 
+/// Powers off the system immediately, so that unwritten data may be lost.
+pub(crate) fn shutdown_immediately(arg: i32) -> ! {
 	SYS.shutdown(arg)
 }
 
+pub(crate) fn reboot() -> io::Result<!> {
+	SYS.reboot()
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_unlink(name: *const c_char) -> i32 {
@@ -285,6 +382,15 @@ pub unsafe extern "C" fn sys_unlink(name: *const c_char) -> i32 {
 	fs::unlink(name).map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
 }
 
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_rename(oldpath: *const c_char, newpath: *const c_char) -> i32 {
+	let oldpath = unsafe { CStr::from_ptr(oldpath) }.to_str().unwrap();
+	let newpath = unsafe { CStr::from_ptr(newpath) }.to_str().unwrap();
+
+	fs::rename(oldpath, newpath).map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_mkdir(name: *const c_char, mode: u32) -> i32 {
@@ -305,6 +411,80 @@ pub unsafe extern "C" fn sys_rmdir(name: *const c_char) -> i32 {
 	crate::fs::remove_dir(name).map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
 }
 
+/// Mounts the file system read-only
+pub const MS_RDONLY: u64 = 1;
+/// Detaches the file system, even if it is busy
+pub const MNT_DETACH: i32 = 2;
+
+/// Mounts the file system `fstype` from `source` at `target`.
+///
+/// Supported file systems are `ramfs` (or `tmpfs`) and `virtiofs`, whose source
+/// is the tag of the device. The only supported flag is `MS_RDONLY`. `data` may
+/// be a string of comma-separated options, which are passed to the file system.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_mount(
+	source: *const c_char,
+	target: *const c_char,
+	fstype: *const c_char,
+	flags: u64,
+	data: *const c_void,
+) -> i32 {
+	if target.is_null() || fstype.is_null() || flags & !MS_RDONLY != 0 {
+		return -crate::errno::EINVAL;
+	}
+
+	let source = if source.is_null() {
+		""
+	} else {
+		let Ok(source) = unsafe { CStr::from_ptr(source) }.to_str() else {
+			return -crate::errno::EINVAL;
+		};
+		source
+	};
+	let options = if data.is_null() {
+		""
+	} else {
+		let Ok(options) = unsafe { CStr::from_ptr(data.cast()) }.to_str() else {
+			return -crate::errno::EINVAL;
+		};
+		options
+	};
+	let (Ok(target), Ok(fstype)) = (
+		unsafe { CStr::from_ptr(target) }.to_str(),
+		unsafe { CStr::from_ptr(fstype) }.to_str(),
+	) else {
+		return -crate::errno::EINVAL;
+	};
+
+	crate::fs::mount(source, target, fstype, flags & MS_RDONLY != 0, options)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Unmounts the file system at `target`.
+///
+/// With `MNT_DETACH`, a busy file system is detached lazily.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_umount2(target: *const c_char, flags: i32) -> i32 {
+	if target.is_null() || flags & !MNT_DETACH != 0 {
+		return -crate::errno::EINVAL;
+	}
+
+	let Ok(target) = unsafe { CStr::from_ptr(target) }.to_str() else {
+		return -crate::errno::EINVAL;
+	};
+
+	crate::fs::umount(target, flags & MNT_DETACH != 0)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_umount(target: *const c_char) -> i32 {
+	unsafe { sys_umount2(target, 0) }
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_stat(name: *const c_char, stat: *mut FileAttr) -> i32 {
@@ -377,21 +557,52 @@ pub unsafe extern "C" fn sys_open(name: *const c_char, flags: i32, mode: u32) ->
 	}
 }
 
+/// Transfers all modified data of the file `fd` to the underlying storage.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_fsync(fd: FileDescriptor) -> i32 {
+	crate::fd::fsync(fd).map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Returns in `ret` a pointer to the directly accessible memory (DAX), which
+/// backs `len` bytes of the file `fd` at `offset`.
+///
+/// Modifications through the pointer become persistent with `sys_fsync`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_dax_map(
+	fd: FileDescriptor,
+	offset: usize,
+	len: usize,
+	ret: &mut *mut u8,
+) -> i32 {
+	match crate::fd::dax_address(fd, offset) {
+		Ok((addr, available)) if len <= available => {
+			*ret = addr.as_mut_ptr();
+			0
+		}
+		Ok(_) => -crate::errno::EINVAL,
+		Err(e) => -num::ToPrimitive::to_i32(&e).unwrap(),
+	}
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
-pub extern "C" fn sys_close(fd: FileDescriptor) -> i32 {
-	let obj = remove_object(fd);
-	obj.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |_| 0)
+pub extern "C" fn sys_close(fd: FileDescriptor) -> io::Result<i32> {
+	remove_object(fd)?;
+	Ok(0)
 }
 
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn sys_read(fd: FileDescriptor, buf: *mut u8, len: usize) -> isize {
+pub unsafe extern "C" fn sys_read(
+	fd: FileDescriptor,
+	buf: *mut u8,
+	len: usize,
+) -> io::Result<isize> {
 	let slice = unsafe { core::slice::from_raw_parts_mut(buf, len) };
-	crate::fd::read(fd, slice).map_or_else(
-		|e| -num::ToPrimitive::to_isize(&e).unwrap(),
-		|v| v.try_into().unwrap(),
-	)
+	let len = crate::fd::read(fd, slice)?;
+	Ok(len.try_into().unwrap())
 }
 
 /// `read()` attempts to read `nbyte` of data to the object referenced by the
@@ -416,29 +627,16 @@ pub unsafe extern "C" fn sys_readv(fd: i32, iov: *const iovec, iovcnt: usize) ->
 		return (-crate::errno::EINVAL).try_into().unwrap();
 	}
 
-	let mut read_bytes: isize = 0;
 	let iovec_buffers = unsafe { core::slice::from_raw_parts(iov, iovcnt) };
+	let mut bufs = iovec_buffers
+		.iter()
+		.map(|iovec_buf| unsafe { iovec_buf.as_mut_slice() })
+		.collect::<Vec<_>>();
 
-	for iovec_buf in iovec_buffers {
-		let buf = unsafe { core::slice::from_raw_parts_mut(iovec_buf.iov_base, iovec_buf.iov_len) };
-
-		let len = crate::fd::read(fd, buf).map_or_else(
-			|e| -num::ToPrimitive::to_isize(&e).unwrap(),
-			|v| v.try_into().unwrap(),
-		);
-
-		if len < 0 {
-			return len;
-		}
-
-		read_bytes += len;
-
-		if len < iovec_buf.iov_len.try_into().unwrap() {
-			return read_bytes;
-		}
-	}
-
-	read_bytes
+	crate::fd::readv(fd, &mut bufs).map_or_else(
+		|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+		|v| v.try_into().unwrap(),
+	)
 }
 
 unsafe fn write(fd: FileDescriptor, buf: *const u8, len: usize) -> isize {
@@ -477,29 +675,16 @@ pub unsafe extern "C" fn sys_writev(fd: FileDescriptor, iov: *const iovec, iovcn
 		return (-crate::errno::EINVAL).try_into().unwrap();
 	}
 
-	let mut written_bytes: isize = 0;
 	let iovec_buffers = unsafe { core::slice::from_raw_parts(iov, iovcnt) };
+	let bufs = iovec_buffers
+		.iter()
+		.map(|iovec_buf| unsafe { iovec_buf.as_slice() })
+		.collect::<Vec<_>>();
 
-	for iovec_buf in iovec_buffers {
-		let buf = unsafe { core::slice::from_raw_parts(iovec_buf.iov_base, iovec_buf.iov_len) };
-
-		let len = crate::fd::write(fd, buf).map_or_else(
-			|e| -num::ToPrimitive::to_isize(&e).unwrap(),
-			|v| v.try_into().unwrap(),
-		);
-
-		if len < 0 {
-			return len;
-		}
-
-		written_bytes += len;
-
-		if len < iovec_buf.iov_len.try_into().unwrap() {
-			return written_bytes;
-		}
-	}
-
-	written_bytes
+	crate::fd::writev(fd, &bufs).map_or_else(
+		|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+		|v| v.try_into().unwrap(),
+	)
 }
 
 #[hermit_macro::system]
@@ -508,55 +693,55 @@ pub unsafe extern "C" fn sys_ioctl(
 	fd: FileDescriptor,
 	cmd: i32,
 	argp: *mut core::ffi::c_void,
-) -> i32 {
+) -> io::Result<i32> {
 	const FIONBIO: i32 = 0x8008_667eu32 as i32;
 
-	if cmd == FIONBIO {
-		let value = unsafe { *(argp as *const i32) };
-
-		let obj = get_object(fd);
-		obj.map_or_else(
-			|e| -num::ToPrimitive::to_i32(&e).unwrap(),
-			|v| {
-				block_on((*v).ioctl(IoCtl::NonBlocking, value != 0), None)
-					.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
-			},
-		)
-	} else {
-		-crate::errno::EINVAL
+	if cmd != FIONBIO {
+		return Err(io::Error::EINVAL);
 	}
+
+	let value = unsafe { *(argp as *const i32) };
+	let obj = get_object(fd)?;
+	block_on(obj.ioctl(IoCtl::NonBlocking, value != 0), None)?;
+	Ok(0)
 }
 
 /// manipulate file descriptor
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
-pub extern "C" fn sys_fcntl(fd: i32, cmd: i32, arg: i32) -> i32 {
+pub extern "C" fn sys_fcntl(fd: i32, cmd: i32, arg: i32) -> io::Result<i32> {
 	const F_SETFD: i32 = 2;
 	const F_SETFL: i32 = 4;
+	const F_SETPIPE_SZ: i32 = 1031;
+	const F_GETPIPE_SZ: i32 = 1032;
 	const FD_CLOEXEC: i32 = 1;
 	const O_NONBLOCK: i32 = 0o4000;
 
-	if cmd == F_SETFD && arg == FD_CLOEXEC {
-		0
+	if cmd == F_SETPIPE_SZ || cmd == F_GETPIPE_SZ {
+		let obj = get_object(fd)?;
+		let size = if cmd == F_GETPIPE_SZ {
+			block_on(obj.pipe_size(), None)?
+		} else {
+			let size = usize::try_from(arg).map_err(|_| io::Error::EINVAL)?;
+			block_on(obj.set_pipe_size(size), None)?
+		};
+		Ok(size.try_into().unwrap())
+	} else if cmd == F_SETFD && arg == FD_CLOEXEC {
+		Ok(0)
 	} else if cmd == F_SETFL && arg == O_NONBLOCK {
-		let obj = get_object(fd);
-		obj.map_or_else(
-			|e| -num::ToPrimitive::to_i32(&e).unwrap(),
-			|v| {
-				block_on((*v).ioctl(IoCtl::NonBlocking, true), None)
-					.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
-			},
-		)
+		let obj = get_object(fd)?;
+		block_on(obj.ioctl(IoCtl::NonBlocking, true), None)?;
+		Ok(0)
 	} else {
-		-crate::errno::EINVAL
+		Err(io::Error::EINVAL)
 	}
 }
 
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
-pub extern "C" fn sys_lseek(fd: FileDescriptor, offset: isize, whence: i32) -> isize {
-	crate::fd::lseek(fd, offset, num::FromPrimitive::from_i32(whence).unwrap())
-		.map_or_else(|e| -num::ToPrimitive::to_isize(&e).unwrap(), |_| 0)
+pub extern "C" fn sys_lseek(fd: FileDescriptor, offset: isize, whence: i32) -> io::Result<isize> {
+	let whence = num::FromPrimitive::from_i32(whence).ok_or(io::Error::EINVAL)?;
+	crate::fd::lseek(fd, offset, whence)
 }
 
 #[repr(C)]
@@ -658,6 +843,131 @@ pub unsafe extern "C" fn sys_poll(fds: *mut PollFd, nfds: usize, timeout: i32) -
 	)
 }
 
+/// Returns a copy of the words of `set`, which cover the file descriptors below `nfds`.
+///
+/// The sets are copied, because the caller may pass the same set several times.
+unsafe fn fd_set_words(set: *const FdSet, nfds: usize) -> Option<Vec<c_ulong>> {
+	(!set.is_null()).then(|| unsafe {
+		core::slice::from_raw_parts(set.cast::<c_ulong>(), nfds.div_ceil(c_ulong::BITS as usize))
+			.to_vec()
+	})
+}
+
+fn fd_set_index(fd: usize) -> (usize, c_ulong) {
+	let bits = c_ulong::BITS as usize;
+	(fd / bits, 1 << (fd % bits))
+}
+
+/// Waits until one of the file descriptors below `nfds` becomes ready.
+///
+/// `select` is mapped onto `poll`: The file descriptors in `readfds`,
+/// `writefds` and `exceptfds` are polled for input, output and priority
+/// data. On return, the sets only contain the ready file descriptors and the
+/// number of set bits is returned. If `timeout` is null, `select` waits
+/// indefinitely.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_select(
+	nfds: i32,
+	readfds: *mut FdSet,
+	writefds: *mut FdSet,
+	exceptfds: *mut FdSet,
+	timeout: *mut timeval,
+) -> i32 {
+	const EVENTS: [PollEvent; 3] = [
+		PollEvent::POLLIN
+			.union(PollEvent::POLLRDNORM)
+			.union(PollEvent::POLLRDBAND)
+			.union(PollEvent::POLLHUP)
+			.union(PollEvent::POLLERR),
+		PollEvent::POLLOUT
+			.union(PollEvent::POLLWRNORM)
+			.union(PollEvent::POLLWRBAND)
+			.union(PollEvent::POLLERR),
+		PollEvent::POLLPRI,
+	];
+
+	let nfds = match usize::try_from(nfds) {
+		Ok(nfds) if nfds <= FD_SETSIZE => nfds,
+		_ => return -crate::errno::EINVAL,
+	};
+
+	let timeout = if timeout.is_null() {
+		None
+	} else {
+		let timeout = unsafe { &*timeout };
+		if timeout.tv_sec < 0 || !(0..1_000_000).contains(&timeout.tv_usec) {
+			return -crate::errno::EINVAL;
+		}
+		// An overflowing timeout is as good as none.
+		timeout
+			.into_usec()
+			.map(|usec| core::time::Duration::from_micros(usec.try_into().unwrap()))
+	};
+
+	let raw_sets = [readfds, writefds, exceptfds];
+	let mut sets = raw_sets.map(|set| unsafe { fd_set_words(set, nfds) });
+
+	let mut fds = Vec::new();
+	for fd in 0..nfds {
+		let (index, bit) = fd_set_index(fd);
+		let events = sets
+			.iter()
+			.zip(EVENTS)
+			.filter(|(set, _)| set.as_ref().is_some_and(|set| set[index] & bit != 0))
+			.fold(PollEvent::empty(), |acc, (_, events)| acc | events);
+		if events.is_empty() {
+			continue;
+		}
+
+		let fd = fd.try_into().unwrap();
+		if get_object(fd).is_err() {
+			return -crate::errno::EBADF;
+		}
+		fds.push(PollFd {
+			fd,
+			events,
+			revents: PollEvent::empty(),
+		});
+	}
+
+	let ready = match crate::fd::poll(&mut fds, timeout) {
+		Ok(ready) => ready,
+		Err(io::Error::ETIME) => 0,
+		Err(e) => return -num::ToPrimitive::to_i32(&e).unwrap(),
+	};
+
+	// Only the file descriptors, which have been requested in a set, are reported in it.
+	let requested = sets.clone();
+	for set in sets.iter_mut().flatten() {
+		set.fill(0);
+	}
+
+	let mut count = 0;
+	if ready > 0 {
+		for pollfd in &fds {
+			let (index, bit) = fd_set_index(pollfd.fd.try_into().unwrap());
+			for ((set, requested), events) in sets.iter_mut().zip(&requested).zip(EVENTS) {
+				if let (Some(set), Some(requested)) = (set, requested) {
+					if requested[index] & bit != 0 && pollfd.revents.intersects(events) {
+						set[index] |= bit;
+						count += 1;
+					}
+				}
+			}
+		}
+	}
+
+	for (raw_set, set) in raw_sets.into_iter().zip(&sets) {
+		if let Some(set) = set {
+			unsafe {
+				core::ptr::copy_nonoverlapping(set.as_ptr(), raw_set.cast(), set.len());
+			}
+		}
+	}
+	count
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_eventfd(initval: u64, flags: i16) -> i32 {
@@ -669,6 +979,82 @@ pub extern "C" fn sys_eventfd(initval: u64, flags: i16) -> i32 {
 	}
 }
 
+/// Creates a pipe and stores the file descriptors of its read and write end in `fds`.
+///
+/// With `O_NONBLOCK` in `flags`, both ends are non-blocking. `O_CLOEXEC` is
+/// accepted and ignored.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_pipe2(fds: *mut [i32; 2], flags: i32) -> i32 {
+	const O_NONBLOCK: i32 = 0o4000;
+	const O_CLOEXEC: i32 = 0o2_000_000;
+
+	if fds.is_null() {
+		return -crate::errno::EFAULT;
+	}
+	if flags & !(O_NONBLOCK | O_CLOEXEC) != 0 {
+		return -crate::errno::EINVAL;
+	}
+
+	match crate::fd::pipe(flags & O_NONBLOCK != 0) {
+		Ok((read_fd, write_fd)) => {
+			unsafe {
+				fds.write([read_fd, write_fd]);
+			}
+			0
+		}
+		Err(e) => -num::ToPrimitive::to_i32(&e).unwrap(),
+	}
+}
+
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_pipe(fds: *mut [i32; 2]) -> i32 {
+	unsafe { sys_pipe2(fds, 0) }
+}
+
+/// Moves up to `len` bytes from `fd_in` to `fd_out` without copying them to
+/// the application. At least one of the descriptors has to refer to a pipe.
+///
+/// Offsets are not supported, so `off_in` and `off_out` have to be null.
+/// With `SPLICE_F_NONBLOCK`, the operations on the pipe do not block.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_splice(
+	fd_in: i32,
+	off_in: *mut i64,
+	fd_out: i32,
+	off_out: *mut i64,
+	len: usize,
+	flags: u32,
+) -> isize {
+	const SPLICE_F_MOVE: u32 = 1;
+	const SPLICE_F_NONBLOCK: u32 = 2;
+	const SPLICE_F_MORE: u32 = 4;
+	const SPLICE_F_GIFT: u32 = 8;
+
+	if flags & !(SPLICE_F_MOVE | SPLICE_F_NONBLOCK | SPLICE_F_MORE | SPLICE_F_GIFT) != 0 {
+		return (-crate::errno::EINVAL).try_into().unwrap();
+	}
+	if !off_in.is_null() || !off_out.is_null() {
+		return (-crate::errno::ESPIPE).try_into().unwrap();
+	}
+
+	crate::fd::splice(fd_in, fd_out, len, flags & SPLICE_F_NONBLOCK != 0).map_or_else(
+		|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+		|v| v.try_into().unwrap(),
+	)
+}
+
+/// Creates a descriptor, which becomes readable, if the kernel is out of memory.
+///
+/// A read returns the number of failed allocations since the last read as `u64`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_oom_eventfd() -> i32 {
+	crate::mm::oom::eventfd().unwrap_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap())
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_image_start_addr() -> usize {
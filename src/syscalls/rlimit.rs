@@ -0,0 +1,37 @@
+use crate::errno::*;
+use crate::rlimit::{getrlimit, rlimit, setrlimit};
+
+/// Stores the soft and hard limit of `resource` in `rlim`.
+///
+/// Supported resources are `RLIMIT_NOFILE` and `RLIMIT_STACK`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_getrlimit(resource: i32, rlim: *mut rlimit) -> i32 {
+	if rlim.is_null() {
+		return -EFAULT;
+	}
+
+	match getrlimit(resource) {
+		Ok(limit) => {
+			unsafe {
+				rlim.write(limit);
+			}
+			0
+		}
+		Err(e) => -num::ToPrimitive::to_i32(&e).unwrap(),
+	}
+}
+
+/// Sets the soft and hard limit of `resource` to the values in `rlim`.
+///
+/// The hard limit can only be lowered. Increasing it returns `-EPERM`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_setrlimit(resource: i32, rlim: *const rlimit) -> i32 {
+	if rlim.is_null() {
+		return -EFAULT;
+	}
+
+	let limit = unsafe { rlim.read() };
+	setrlimit(resource, limit).map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
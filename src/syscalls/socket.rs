@@ -1,14 +1,15 @@
 #![allow(dead_code)]
 #![allow(nonstandard_style)]
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::ffi::{c_char, c_void};
-use core::mem::size_of;
+use core::mem::{align_of, size_of};
 #[allow(unused_imports)]
 use core::ops::DerefMut;
 
 use cfg_if::cfg_if;
 #[cfg(any(feature = "tcp", feature = "udp"))]
-use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, IpEndpoint, IpListenEndpoint};
 
 use crate::errno::*;
 #[cfg(any(feature = "tcp", feature = "udp"))]
@@ -17,12 +18,17 @@ use crate::executor::network::{NIC, NetworkState};
 use crate::fd::socket::tcp;
 #[cfg(feature = "udp")]
 use crate::fd::socket::udp;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use crate::fd::socket::usage::NetUsage;
 #[cfg(feature = "vsock")]
 use crate::fd::socket::vsock::{self, VsockEndpoint, VsockListenEndpoint};
 use crate::fd::{
-	Endpoint, ListenEndpoint, ObjectInterface, SocketOption, get_object, insert_object,
+	Endpoint, ListenEndpoint, ObjectInterface, RecvFlags, SocketOption, block_on_io, get_object,
+	insert_object,
 };
 use crate::syscalls::{IoCtl, block_on};
+use crate::time::{timespec, timeval};
+use crate::{arch, io};
 
 pub const AF_INET: i32 = 0;
 pub const AF_INET6: i32 = 1;
@@ -43,6 +49,7 @@ pub const IP_ADD_MEMBERSHIP: i32 = 3;
 pub const IP_DROP_MEMBERSHIP: i32 = 4;
 pub const SOL_SOCKET: i32 = 4095;
 pub const SO_REUSEADDR: i32 = 0x0004;
+pub const SO_REUSEPORT: i32 = 0x0200;
 pub const SO_KEEPALIVE: i32 = 0x0008;
 pub const SO_BROADCAST: i32 = 0x0020;
 pub const SO_LINGER: i32 = 0x0080;
@@ -51,8 +58,29 @@ pub const SO_RCVBUF: i32 = 0x1002;
 pub const SO_SNDTIMEO: i32 = 0x1005;
 pub const SO_RCVTIMEO: i32 = 0x1006;
 pub const SO_ERROR: i32 = 0x1007;
+/// Returns the network usage of the socket as [`net_usage`] (Hermit-specific).
+pub const SO_NETUSAGE: i32 = 0x1100;
+/// Enables software receive timestamps, which are delivered by `recvmsg`
+/// as [`SCM_TIMESTAMPING`] (Hermit-specific value).
+pub const SO_TIMESTAMPING: i32 = 0x1101;
+pub const SCM_TIMESTAMPING: i32 = SO_TIMESTAMPING;
+pub const SOF_TIMESTAMPING_RX_SOFTWARE: i32 = 1 << 3;
+pub const SOF_TIMESTAMPING_SOFTWARE: i32 = 1 << 4;
 pub const TCP_NODELAY: i32 = 1;
+/// Holds back partial segments until the socket is uncorked or for at most 200 ms.
+pub const TCP_CORK: i32 = 3;
+/// Accepts connections only after data has arrived.
+///
+/// Like on Linux, the value is a timeout in seconds, after which an idle
+/// connection is accepted nevertheless. Zero accepts connections immediately.
+pub const TCP_DEFER_ACCEPT: i32 = 9;
+/// Segments writes into datagrams of the given size, zero disables the segmentation.
+pub const UDP_SEGMENT: i32 = 103;
 pub const MSG_PEEK: i32 = 1;
+pub const MSG_DONTWAIT: i32 = 0x40;
+pub const MSG_WAITALL: i32 = 0x100;
+/// The ancillary data has been truncated.
+pub const MSG_CTRUNC: i32 = 0x8;
 pub const EAI_AGAIN: i32 = 2;
 pub const EAI_BADFLAGS: i32 = 3;
 pub const EAI_FAIL: i32 = 4;
@@ -282,6 +310,78 @@ impl From<IpEndpoint> for sockaddr_in6 {
 	}
 }
 
+/// Bytes and packets, which have been received and sent by a socket
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct net_usage {
+	pub rx_bytes: u64,
+	pub rx_packets: u64,
+	pub tx_bytes: u64,
+	pub tx_packets: u64,
+}
+
+/// Message of `recvmsg`
+#[repr(C)]
+#[derive(Debug)]
+pub struct msghdr {
+	/// Address of the sender
+	pub msg_name: *mut c_void,
+	pub msg_namelen: socklen_t,
+	/// Buffers, which receive the data
+	pub msg_iov: *mut super::iovec,
+	pub msg_iovlen: usize,
+	/// Buffer, which receives the ancillary data
+	pub msg_control: *mut c_void,
+	pub msg_controllen: usize,
+	/// Flags of the received message
+	pub msg_flags: i32,
+}
+
+/// Header of an ancillary data object
+#[repr(C)]
+#[derive(Debug)]
+pub struct cmsghdr {
+	/// Length of the header and the data
+	pub cmsg_len: usize,
+	pub cmsg_level: i32,
+	pub cmsg_type: i32,
+}
+
+/// Timestamps of [`SCM_TIMESTAMPING`]
+///
+/// Only the software timestamp `ts[0]` is set.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct scm_timestamping {
+	pub ts: [timespec; 3],
+}
+
+const fn cmsg_align(len: usize) -> usize {
+	len.next_multiple_of(align_of::<usize>())
+}
+
+/// Returns the length of an ancillary data object with `len` bytes of data (`CMSG_LEN`).
+const fn cmsg_len(len: usize) -> usize {
+	cmsg_align(size_of::<cmsghdr>()) + len
+}
+
+/// Returns the space of an ancillary data object with `len` bytes of data (`CMSG_SPACE`).
+const fn cmsg_space(len: usize) -> usize {
+	cmsg_align(size_of::<cmsghdr>()) + cmsg_align(len)
+}
+
+#[cfg(any(feature = "tcp", feature = "udp"))]
+impl From<NetUsage> for net_usage {
+	fn from(usage: NetUsage) -> Self {
+		Self {
+			rx_bytes: usage.rx_bytes,
+			rx_packets: usage.rx_packets,
+			tx_bytes: usage.tx_bytes,
+			tx_packets: usage.tx_packets,
+		}
+	}
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct ip_mreq {
@@ -316,14 +416,71 @@ pub struct linger {
 	pub l_linger: i32,
 }
 
+/// Resolves names, which refer to this instance, without asking a DNS server.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+fn resolve_local_name(name: &str, ipv6: bool) -> Option<IpAddress> {
+	if !crate::hostname::is_local_name(name) {
+		return None;
+	}
+
+	let loopback = if ipv6 {
+		IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1)
+	} else {
+		IpAddress::v4(127, 0, 0, 1)
+	};
+
+	if name.trim_end_matches('.').eq_ignore_ascii_case("localhost") {
+		return Some(loopback);
+	}
+
+	let address = match &*tracked_lock!(NIC, "network interface") {
+		NetworkState::Initialized(nic) => nic.local_address(ipv6),
+		_ => None,
+	};
+
+	Some(address.unwrap_or(loopback))
+}
+
+#[cfg(any(feature = "tcp", feature = "udp"))]
+fn copy_address(address: IpAddress, inaddr: &mut [u8]) -> i32 {
+	match address {
+		IpAddress::Ipv4(ipv4_addr) if inaddr.len() == size_of::<in_addr>() => {
+			inaddr.copy_from_slice(&ipv4_addr.octets());
+		}
+		IpAddress::Ipv6(ipv6_addr) if inaddr.len() == size_of::<in6_addr>() => {
+			inaddr.copy_from_slice(&ipv6_addr.octets());
+		}
+		_ => return -EINVAL,
+	}
+
+	0
+}
+
 #[cfg(not(feature = "dns"))]
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_getaddrbyname(
-	_name: *const c_char,
-	_inaddr: *mut u8,
-	_len: usize,
+	name: *const c_char,
+	inaddr: *mut u8,
+	len: usize,
 ) -> i32 {
+	#[cfg(any(feature = "tcp", feature = "udp"))]
+	if !name.is_null()
+		&& !inaddr.is_null()
+		&& (len == size_of::<in_addr>() || len == size_of::<in6_addr>())
+	{
+		let name = unsafe { core::ffi::CStr::from_ptr(name) };
+		if let Ok(name) = name.to_str() {
+			if let Some(address) = resolve_local_name(name, len == size_of::<in6_addr>()) {
+				let slice = unsafe { core::slice::from_raw_parts_mut(inaddr, len) };
+				return copy_address(address, slice);
+			}
+		}
+	}
+
+	#[cfg(not(any(feature = "tcp", feature = "udp")))]
+	let _ = (name, inaddr, len);
+
 	error!("Please enable the feature 'dns' to determine the network ip by name.");
 	-ENOSYS
 }
@@ -386,8 +543,13 @@ pub unsafe extern "C" fn sys_getaddrbyname(
 		return -EINVAL;
 	};
 
+	if let Some(address) = resolve_local_name(&name, query_type == DnsQueryType::Aaaa) {
+		let slice = unsafe { core::slice::from_raw_parts_mut(inaddr, len) };
+		return copy_address(address, slice);
+	}
+
 	let query = {
-		let mut guard = NIC.lock();
+		let mut guard = tracked_lock!(NIC, "network interface");
 		let nic = guard.as_nic_mut().unwrap();
 		let query = nic.start_query(&name, query_type).unwrap();
 		nic.poll_common(crate::executor::network::now());
@@ -398,13 +560,7 @@ pub unsafe extern "C" fn sys_getaddrbyname(
 	match block_on(get_query_result(query), None) {
 		Ok(addr_vec) => {
 			let slice = unsafe { core::slice::from_raw_parts_mut(inaddr, len) };
-
-			match addr_vec[0] {
-				IpAddress::Ipv4(ipv4_addr) => slice.copy_from_slice(&ipv4_addr.octets()),
-				IpAddress::Ipv6(ipv6_addr) => slice.copy_from_slice(&ipv6_addr.octets()),
-			}
-
-			0
+			copy_address(addr_vec[0], slice)
 		}
 		Err(e) => -num::ToPrimitive::to_i32(&e).unwrap(),
 	}
@@ -439,7 +595,7 @@ pub extern "C" fn sys_socket(domain: i32, type_: SockType, protocol: i32) -> i32
 	if (domain == AF_INET || domain == AF_INET6)
 		&& type_.intersects(SockType::SOCK_STREAM | SockType::SOCK_DGRAM)
 	{
-		let mut guard = NIC.lock();
+		let mut guard = tracked_lock!(NIC, "network interface");
 
 		if let NetworkState::Initialized(nic) = &mut *guard {
 			#[cfg(feature = "udp")]
@@ -484,7 +640,7 @@ pub unsafe extern "C" fn sys_accept(fd: i32, addr: *mut sockaddr, addrlen: *mut
 	obj.map_or_else(
 		|e| -num::ToPrimitive::to_i32(&e).unwrap(),
 		|v| {
-			block_on((*v).accept(), None).map_or_else(
+			block_on_io(&*v, SocketOption::RecvTimeout, (*v).accept()).map_or_else(
 				|e| -num::ToPrimitive::to_i32(&e).unwrap(),
 				|(obj, endpoint)| match endpoint {
 					#[cfg(any(feature = "tcp", feature = "udp"))]
@@ -516,7 +672,7 @@ pub unsafe extern "C" fn sys_accept(fd: i32, addr: *mut sockaddr, addrlen: *mut
 					}
 					#[cfg(feature = "vsock")]
 					Endpoint::Vsock(endpoint) => {
-						let new_fd = insert_object(v.clone()).unwrap();
+						let new_fd = insert_object(obj).unwrap();
 
 						if !addr.is_null() && !addrlen.is_null() {
 							let addrlen = unsafe { &mut *addrlen };
@@ -634,7 +790,7 @@ pub unsafe extern "C" fn sys_connect(fd: i32, name: *const sockaddr, namelen: so
 	obj.map_or_else(
 		|e| -num::ToPrimitive::to_i32(&e).unwrap(),
 		|v| {
-			block_on((*v).connect(endpoint), None)
+			block_on_io(&*v, SocketOption::SendTimeout, (*v).connect(endpoint))
 				.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
 		},
 	)
@@ -697,6 +853,40 @@ pub unsafe extern "C" fn sys_getsockname(
 	)
 }
 
+/// Returns the socket option `optname` of the protocol level `level`.
+fn socket_option(level: i32, optname: i32) -> Option<SocketOption> {
+	match (level, optname) {
+		(IPPROTO_TCP, TCP_NODELAY) => Some(SocketOption::TcpNoDelay),
+		(IPPROTO_TCP, TCP_CORK) => Some(SocketOption::TcpCork),
+		(IPPROTO_TCP, TCP_DEFER_ACCEPT) => Some(SocketOption::TcpDeferAccept),
+		(SOL_SOCKET, SO_REUSEADDR) => Some(SocketOption::ReuseAddr),
+		(SOL_SOCKET, SO_REUSEPORT) => Some(SocketOption::ReusePort),
+		(SOL_SOCKET, SO_KEEPALIVE) => Some(SocketOption::KeepAlive),
+		(SOL_SOCKET, SO_BROADCAST) => Some(SocketOption::Broadcast),
+		(SOL_SOCKET, SO_LINGER) => Some(SocketOption::Linger),
+		(SOL_SOCKET, SO_SNDBUF) => Some(SocketOption::SendBuffer),
+		(SOL_SOCKET, SO_RCVBUF) => Some(SocketOption::RecvBuffer),
+		(SOL_SOCKET, SO_SNDTIMEO) => Some(SocketOption::SendTimeout),
+		(SOL_SOCKET, SO_RCVTIMEO) => Some(SocketOption::RecvTimeout),
+		(SOL_SOCKET, SO_ERROR) => Some(SocketOption::Error),
+		(SOL_SOCKET, SO_TIMESTAMPING) => Some(SocketOption::Timestamping),
+		(IPPROTO_UDP, UDP_SEGMENT) => Some(SocketOption::UdpSegment),
+		_ => None,
+	}
+}
+
+/// Converts the timeout of `SO_RCVTIMEO` or `SO_SNDTIMEO` into milliseconds.
+///
+/// The timeout is rounded up, so that a short timeout is not disabled.
+fn timeout_to_millis(timeout: timeval) -> Option<i32> {
+	if timeout.tv_sec < 0 || !(0..1_000_000).contains(&timeout.tv_usec) {
+		return None;
+	}
+
+	let micros = u64::try_from(timeout.into_usec()?).ok()?;
+	Some(i32::try_from(micros.div_ceil(1000)).unwrap_or(i32::MAX))
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_setsockopt(
@@ -711,28 +901,60 @@ pub unsafe extern "C" fn sys_setsockopt(
 		fd, level, optname
 	);
 
-	if level == IPPROTO_TCP
-		&& optname == TCP_NODELAY
-		&& optlen == size_of::<i32>().try_into().unwrap()
-	{
-		if optval.is_null() {
-			return -crate::errno::EINVAL;
-		}
+	let Some(opt) = socket_option(level, optname) else {
+		return -crate::errno::EINVAL;
+	};
 
-		let value = unsafe { *optval.cast::<i32>() };
-		let obj = get_object(fd);
-		obj.map_or_else(
-			|e| -num::ToPrimitive::to_i32(&e).unwrap(),
-			|v| {
-				block_on((*v).setsockopt(SocketOption::TcpNoDelay, value != 0), None)
-					.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
-			},
-		)
-	} else if level == SOL_SOCKET && optname == SO_REUSEADDR {
-		0
-	} else {
-		-crate::errno::EINVAL
+	if optval.is_null() {
+		return -crate::errno::EINVAL;
 	}
+
+	let value = match opt {
+		SocketOption::Linger => {
+			if optlen != size_of::<linger>().try_into().unwrap() {
+				return -crate::errno::EINVAL;
+			}
+
+			let linger = unsafe { *optval.cast::<linger>() };
+			if linger.l_onoff != 0 {
+				linger.l_linger.max(0)
+			} else {
+				-1
+			}
+		}
+		SocketOption::SendTimeout | SocketOption::RecvTimeout => {
+			if optlen != size_of::<timeval>().try_into().unwrap() {
+				return -crate::errno::EINVAL;
+			}
+
+			let Some(millis) = timeout_to_millis(unsafe { *optval.cast::<timeval>() }) else {
+				return -crate::errno::EDOM;
+			};
+			millis
+		}
+		_ => {
+			if optlen != size_of::<i32>().try_into().unwrap() {
+				return -crate::errno::EINVAL;
+			}
+
+			let value = unsafe { *optval.cast::<i32>() };
+			// Only software receive timestamps are supported.
+			if opt == SocketOption::Timestamping {
+				i32::from(value & SOF_TIMESTAMPING_RX_SOFTWARE != 0)
+			} else {
+				value
+			}
+		}
+	};
+
+	let obj = get_object(fd);
+	obj.map_or_else(
+		|e| -num::ToPrimitive::to_i32(&e).unwrap(),
+		|v| {
+			block_on((*v).setsockopt(opt, value), None)
+				.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+		},
+	)
 }
 
 #[hermit_macro::system]
@@ -749,35 +971,80 @@ pub unsafe extern "C" fn sys_getsockopt(
 		fd, level, optname
 	);
 
-	if level == IPPROTO_TCP && optname == TCP_NODELAY {
-		if optval.is_null() || optlen.is_null() {
+	#[cfg(any(feature = "tcp", feature = "udp"))]
+	if (level, optname) == (SOL_SOCKET, SO_NETUSAGE) {
+		if optval.is_null()
+			|| optlen.is_null()
+			|| unsafe { *optlen } < size_of::<net_usage>().try_into().unwrap()
+		{
 			return -crate::errno::EINVAL;
 		}
 
-		let optval = unsafe { &mut *optval.cast::<i32>() };
-		let optlen = unsafe { &mut *optlen };
-		let obj = get_object(fd);
-		obj.map_or_else(
-			|e| -num::ToPrimitive::to_i32(&e).unwrap(),
-			|v| {
-				block_on((*v).getsockopt(SocketOption::TcpNoDelay), None).map_or_else(
-					|e| -num::ToPrimitive::to_i32(&e).unwrap(),
-					|value| {
-						if value {
-							*optval = 1;
-						} else {
-							*optval = 0;
-						}
-						*optlen = core::mem::size_of::<i32>().try_into().unwrap();
+		return get_object(fd)
+			.and_then(|v| block_on(v.usage(), None))
+			.map_or_else(
+				|e| -num::ToPrimitive::to_i32(&e).unwrap(),
+				|usage| {
+					unsafe {
+						*optval.cast::<net_usage>() = usage.into();
+						*optlen = size_of::<net_usage>().try_into().unwrap();
+					}
+					0
+				},
+			);
+	}
 
-						0
-					},
-				)
-			},
-		)
-	} else {
-		-crate::errno::EINVAL
+	let Some(opt) = socket_option(level, optname) else {
+		return -crate::errno::EINVAL;
+	};
+
+	if optval.is_null() || optlen.is_null() {
+		return -crate::errno::EINVAL;
+	}
+
+	let optlen = unsafe { &mut *optlen };
+	let size = match opt {
+		SocketOption::Linger => size_of::<linger>(),
+		SocketOption::SendTimeout | SocketOption::RecvTimeout => size_of::<timeval>(),
+		_ => size_of::<i32>(),
+	};
+	if usize::try_from(*optlen).unwrap() < size {
+		return -crate::errno::EINVAL;
 	}
+
+	let obj = get_object(fd);
+	obj.map_or_else(
+		|e| -num::ToPrimitive::to_i32(&e).unwrap(),
+		|v| {
+			block_on((*v).getsockopt(opt), None).map_or_else(
+				|e| -num::ToPrimitive::to_i32(&e).unwrap(),
+				|value| {
+					unsafe {
+						match opt {
+							SocketOption::Linger => {
+								*optval.cast::<linger>() = linger {
+									l_onoff: i32::from(value >= 0),
+									l_linger: value.max(0),
+								};
+							}
+							SocketOption::SendTimeout | SocketOption::RecvTimeout => {
+								*optval.cast::<timeval>() =
+									timeval::from_usec(i64::from(value) * 1000);
+							}
+							SocketOption::Timestamping if value != 0 => {
+								*optval.cast::<i32>() =
+									SOF_TIMESTAMPING_RX_SOFTWARE | SOF_TIMESTAMPING_SOFTWARE;
+							}
+							_ => *optval.cast::<i32>() = value,
+						}
+					}
+					*optlen = size.try_into().unwrap();
+
+					0
+				},
+			)
+		},
+	)
 }
 
 #[hermit_macro::system]
@@ -883,15 +1150,24 @@ pub extern "C" fn sys_shutdown_socket(fd: i32, how: i32) -> i32 {
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_recv(fd: i32, buf: *mut u8, len: usize, flags: i32) -> isize {
-	if flags == 0 {
-		let slice = unsafe { core::slice::from_raw_parts_mut(buf, len) };
-		crate::fd::read(fd, slice).map_or_else(
-			|e| -num::ToPrimitive::to_isize(&e).unwrap(),
-			|v| v.try_into().unwrap(),
-		)
-	} else {
-		(-crate::errno::EINVAL).try_into().unwrap()
+	// Like on Linux, unknown flags are ignored.
+	let flags = RecvFlags::from_bits_truncate(flags);
+
+	if len == 0 {
+		return 0;
 	}
+
+	let slice = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+	let obj = get_object(fd);
+	obj.map_or_else(
+		|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+		|v| {
+			block_on_io(&*v, SocketOption::RecvTimeout, (*v).recv(slice, flags)).map_or_else(
+				|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+				|v| v.try_into().unwrap(),
+			)
+		},
+	)
 }
 
 #[hermit_macro::system]
@@ -904,10 +1180,33 @@ pub unsafe extern "C" fn sys_sendto(
 	addr: *const sockaddr,
 	addr_len: socklen_t,
 ) -> isize {
-	let endpoint;
+	let endpoint = match unsafe { load_endpoint(addr, addr_len) } {
+		Ok(endpoint) => endpoint,
+		Err(e) => return -num::ToPrimitive::to_isize(&e).unwrap(),
+	};
 
-	if addr.is_null() || addr_len == 0 {
-		return (-crate::errno::EINVAL).try_into().unwrap();
+	let slice = unsafe { core::slice::from_raw_parts(buf, len) };
+	let obj = get_object(fd);
+
+	obj.map_or_else(
+		|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+		|v| {
+			block_on_io(&*v, SocketOption::SendTimeout, (*v).sendto(slice, endpoint)).map_or_else(
+				|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+				|v| v.try_into().unwrap(),
+			)
+		},
+	)
+}
+
+/// Loads the endpoint from the socket address `addr` with the size `addrlen`.
+///
+/// # Safety
+///
+/// `addr` must be null or point to `addrlen` readable bytes.
+unsafe fn load_endpoint(addr: *const sockaddr, addrlen: socklen_t) -> io::Result<Endpoint> {
+	if addr.is_null() || addrlen == 0 {
+		return Err(io::Error::EINVAL);
 	}
 
 	cfg_if! {
@@ -915,41 +1214,24 @@ pub unsafe extern "C" fn sys_sendto(
 			let sa_family = unsafe { i32::from((*addr).sa_family) };
 
 			if sa_family == AF_INET {
-				if addr_len < size_of::<sockaddr_in>().try_into().unwrap() {
-					return (-crate::errno::EINVAL).try_into().unwrap();
+				if addrlen < size_of::<sockaddr_in>().try_into().unwrap() {
+					return Err(io::Error::EINVAL);
 				}
 
-				endpoint = Some(Endpoint::Ip(IpEndpoint::from(unsafe {*(addr.cast::<sockaddr_in>())})));
+				Ok(Endpoint::Ip(IpEndpoint::from(unsafe { *(addr.cast::<sockaddr_in>()) })))
 			} else if sa_family == AF_INET6 {
-				if addr_len < size_of::<sockaddr_in6>().try_into().unwrap() {
-					return (-crate::errno::EINVAL).try_into().unwrap();
+				if addrlen < size_of::<sockaddr_in6>().try_into().unwrap() {
+					return Err(io::Error::EINVAL);
 				}
 
-				endpoint = Some(Endpoint::Ip(IpEndpoint::from(unsafe { *(addr.cast::<sockaddr_in6>()) })));
+				Ok(Endpoint::Ip(IpEndpoint::from(unsafe { *(addr.cast::<sockaddr_in6>()) })))
 			} else {
-				endpoint = None;
+				Err(io::Error::EINVAL)
 			}
 		} else {
-			endpoint = None;
+			Err(io::Error::EINVAL)
 		}
 	}
-
-	if let Some(endpoint) = endpoint {
-		let slice = unsafe { core::slice::from_raw_parts(buf, len) };
-		let obj = get_object(fd);
-
-		obj.map_or_else(
-			|e| -num::ToPrimitive::to_isize(&e).unwrap(),
-			|v| {
-				block_on((*v).sendto(slice, endpoint), None).map_or_else(
-					|e| -num::ToPrimitive::to_isize(&e).unwrap(),
-					|v| v.try_into().unwrap(),
-				)
-			},
-		)
-	} else {
-		(-crate::errno::EINVAL).try_into().unwrap()
-	}
 }
 
 #[hermit_macro::system]
@@ -958,54 +1240,433 @@ pub unsafe extern "C" fn sys_recvfrom(
 	fd: i32,
 	buf: *mut u8,
 	len: usize,
-	_flags: i32,
+	flags: i32,
 	addr: *mut sockaddr,
 	addrlen: *mut socklen_t,
 ) -> isize {
+	// Like on Linux, unknown flags are ignored.
+	let flags = RecvFlags::from_bits_truncate(flags);
+
 	let slice = unsafe { core::slice::from_raw_parts_mut(buf, len) };
 	let obj = get_object(fd);
 	obj.map_or_else(
 		|e| -num::ToPrimitive::to_isize(&e).unwrap(),
 		|v| {
-			block_on((*v).recvfrom(slice), None).map_or_else(
+			block_on_io(&*v, SocketOption::RecvTimeout, (*v).recvfrom(slice, flags)).map_or_else(
 				|e| -num::ToPrimitive::to_isize(&e).unwrap(),
 				|(len, endpoint)| {
 					if !addr.is_null() && !addrlen.is_null() {
-						#[allow(unused_variables)]
 						let addrlen = unsafe { &mut *addrlen };
+						if let Err(e) = unsafe { store_endpoint(endpoint, addr, addrlen) } {
+							return -num::ToPrimitive::to_isize(&e).unwrap();
+						}
+					}
 
-						match endpoint {
-							#[cfg(any(feature = "tcp", feature = "udp"))]
-							Endpoint::Ip(endpoint) => match endpoint.addr {
-								IpAddress::Ipv4(_) => {
-									if *addrlen >= size_of::<sockaddr_in>().try_into().unwrap() {
-										let addr = unsafe { &mut *addr.cast() };
-										*addr = sockaddr_in::from(endpoint);
-										*addrlen = size_of::<sockaddr_in>().try_into().unwrap();
-									} else {
-										return (-crate::errno::EINVAL).try_into().unwrap();
-									}
-								}
-								IpAddress::Ipv6(_) => {
-									if *addrlen >= size_of::<sockaddr_in6>().try_into().unwrap() {
-										let addr = unsafe { &mut *addr.cast() };
-										*addr = sockaddr_in6::from(endpoint);
-										*addrlen = size_of::<sockaddr_in6>().try_into().unwrap();
-									} else {
-										return (-crate::errno::EINVAL).try_into().unwrap();
-									}
-								}
-							},
-							#[cfg(feature = "vsock")]
-							_ => {
-								return (-crate::errno::EINVAL).try_into().unwrap();
+					len.try_into().unwrap()
+				},
+			)
+		},
+	)
+}
+
+/// Stores `endpoint` in the socket address `addr` with the size `addrlen`.
+///
+/// # Safety
+///
+/// `addr` must point to `addrlen` writable bytes.
+unsafe fn store_endpoint(
+	endpoint: Endpoint,
+	addr: *mut sockaddr,
+	addrlen: &mut socklen_t,
+) -> io::Result<()> {
+	match endpoint {
+		#[cfg(any(feature = "tcp", feature = "udp"))]
+		Endpoint::Ip(endpoint) => match endpoint.addr {
+			IpAddress::Ipv4(_) => {
+				if *addrlen >= size_of::<sockaddr_in>().try_into().unwrap() {
+					let addr = unsafe { &mut *addr.cast() };
+					*addr = sockaddr_in::from(endpoint);
+					*addrlen = size_of::<sockaddr_in>().try_into().unwrap();
+					Ok(())
+				} else {
+					Err(io::Error::EINVAL)
+				}
+			}
+			IpAddress::Ipv6(_) => {
+				if *addrlen >= size_of::<sockaddr_in6>().try_into().unwrap() {
+					let addr = unsafe { &mut *addr.cast() };
+					*addr = sockaddr_in6::from(endpoint);
+					*addrlen = size_of::<sockaddr_in6>().try_into().unwrap();
+					Ok(())
+				} else {
+					Err(io::Error::EINVAL)
+				}
+			}
+		},
+		#[cfg(feature = "vsock")]
+		_ => Err(io::Error::EINVAL),
+	}
+}
+
+/// Stores the receive time `timestamp` as [`SCM_TIMESTAMPING`] in the
+/// ancillary data of `msg`.
+///
+/// # Safety
+///
+/// `msg.msg_control` must point to `msg.msg_controllen` writable bytes.
+unsafe fn store_timestamp(msg: &mut msghdr, timestamp: Option<u64>) {
+	let Some(timestamp) = timestamp else {
+		msg.msg_controllen = 0;
+		return;
+	};
+
+	let space = cmsg_space(size_of::<scm_timestamping>());
+	if msg.msg_control.is_null() || msg.msg_controllen < space {
+		msg.msg_controllen = 0;
+		msg.msg_flags |= MSG_CTRUNC;
+		return;
+	}
+
+	// convert the time since boot to the time since UNIX epoch
+	let elapsed = arch::processor::get_timer_ticks().saturating_sub(timestamp);
+	let realtime = crate::time::realtime_micros().saturating_sub(elapsed);
+	let mut data = scm_timestamping::default();
+	data.ts[0] = timespec::from_usec(realtime.try_into().unwrap());
+
+	let header = cmsghdr {
+		cmsg_len: cmsg_len(size_of::<scm_timestamping>()),
+		cmsg_level: SOL_SOCKET,
+		cmsg_type: SCM_TIMESTAMPING,
+	};
+	unsafe {
+		let control = msg.msg_control.cast::<u8>();
+		control.cast::<cmsghdr>().write_unaligned(header);
+		control
+			.add(cmsg_align(size_of::<cmsghdr>()))
+			.cast::<scm_timestamping>()
+			.write_unaligned(data);
+	}
+	msg.msg_controllen = space;
+}
+
+/// Receives a message from a socket into the buffers of `msg`.
+///
+/// If the socket has enabled `SO_TIMESTAMPING`, the receive time of the
+/// message is delivered as [`SCM_TIMESTAMPING`] in the ancillary data.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_recvmsg(fd: i32, msg: *mut msghdr, flags: i32) -> isize {
+	// Like on Linux, unknown flags are ignored.
+	let flags = RecvFlags::from_bits_truncate(flags);
+	if msg.is_null() {
+		return (-crate::errno::EINVAL).try_into().unwrap();
+	}
+
+	let msg = unsafe { &mut *msg };
+	if msg.msg_iovlen > super::IOV_MAX || msg.msg_iov.is_null() && msg.msg_iovlen > 0 {
+		return (-crate::errno::EINVAL).try_into().unwrap();
+	}
+
+	let iovec_buffers = if msg.msg_iovlen == 0 {
+		&[]
+	} else {
+		unsafe { core::slice::from_raw_parts(msg.msg_iov, msg.msg_iovlen) }
+	};
+	let mut bufs = iovec_buffers
+		.iter()
+		.map(|iovec_buf| unsafe { iovec_buf.as_mut_slice() })
+		.collect::<Vec<_>>();
+
+	let obj = get_object(fd);
+	obj.map_or_else(
+		|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+		|v| {
+			block_on_io(
+				&*v,
+				SocketOption::RecvTimeout,
+				(*v).recvmsg(&mut bufs, flags),
+			)
+			.map_or_else(
+				|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+				|received| {
+					msg.msg_flags = 0;
+					match received.endpoint {
+						Some(endpoint) if !msg.msg_name.is_null() => {
+							if let Err(e) = unsafe {
+								store_endpoint(endpoint, msg.msg_name.cast(), &mut msg.msg_namelen)
+							} {
+								return -num::ToPrimitive::to_isize(&e).unwrap();
 							}
 						}
+						_ => msg.msg_namelen = 0,
+					}
+					unsafe {
+						store_timestamp(msg, received.timestamp);
 					}
 
-					len.try_into().unwrap()
+					received.len.try_into().unwrap()
 				},
 			)
 		},
 	)
 }
+
+/// Sends a message from the buffers of `msg` to a socket.
+///
+/// The message is sent to the address `msg_name` or, if it is null, to the
+/// connected peer. Ancillary data is not supported and ignored.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sendmsg(fd: i32, msg: *const msghdr, _flags: i32) -> isize {
+	if msg.is_null() {
+		return (-crate::errno::EINVAL).try_into().unwrap();
+	}
+
+	let msg = unsafe { &*msg };
+	if msg.msg_iovlen > super::IOV_MAX || msg.msg_iov.is_null() && msg.msg_iovlen > 0 {
+		return (-crate::errno::EINVAL).try_into().unwrap();
+	}
+
+	let endpoint = if msg.msg_name.is_null() {
+		None
+	} else {
+		match unsafe { load_endpoint(msg.msg_name.cast(), msg.msg_namelen) } {
+			Ok(endpoint) => Some(endpoint),
+			Err(e) => return -num::ToPrimitive::to_isize(&e).unwrap(),
+		}
+	};
+
+	let iovec_buffers = if msg.msg_iovlen == 0 {
+		&[]
+	} else {
+		unsafe { core::slice::from_raw_parts(msg.msg_iov, msg.msg_iovlen) }
+	};
+	let bufs = iovec_buffers
+		.iter()
+		.map(|iovec_buf| unsafe { iovec_buf.as_slice() })
+		.collect::<Vec<_>>();
+
+	let obj = get_object(fd);
+	obj.map_or_else(
+		|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+		|v| {
+			let result = if let Some(endpoint) = endpoint {
+				block_on_io(
+					&*v,
+					SocketOption::SendTimeout,
+					(*v).sendmsg(&bufs, endpoint),
+				)
+			} else {
+				block_on_io(&*v, SocketOption::SendTimeout, (*v).writev(&bufs))
+			};
+			result.map_or_else(
+				|e| -num::ToPrimitive::to_isize(&e).unwrap(),
+				|len| len.try_into().unwrap(),
+			)
+		},
+	)
+}
+
+/// Converts the raw address `inaddr`, which is either an `in_addr` or an `in6_addr`.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+fn read_address(inaddr: &[u8]) -> Option<IpAddress> {
+	if let Ok(octets) = <[u8; 4]>::try_from(inaddr) {
+		Some(IpAddress::v4(octets[0], octets[1], octets[2], octets[3]))
+	} else {
+		<[u8; 16]>::try_from(inaddr)
+			.ok()
+			.map(|octets| IpAddress::Ipv6(octets.into()))
+	}
+}
+
+/// Converts the raw network address `inaddr` with the prefix length `prefix_len`.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+fn read_cidr(inaddr: &[u8], prefix_len: u8) -> Option<IpCidr> {
+	let address = read_address(inaddr)?;
+	let max_prefix_len = match address {
+		IpAddress::Ipv4(_) => 32,
+		IpAddress::Ipv6(_) => 128,
+	};
+
+	(prefix_len <= max_prefix_len).then(|| IpCidr::new(address, prefix_len))
+}
+
+/// Adds the static neighbor with the address `inaddr` and the hardware address `hwaddr`.
+///
+/// `inaddr` is either an `in_addr` or an `in6_addr` and `hwaddr` has a length of 6 bytes.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_neighbor_add(inaddr: *const u8, len: usize, hwaddr: *const u8) -> i32 {
+	if inaddr.is_null() || hwaddr.is_null() {
+		return -EINVAL;
+	}
+
+	let inaddr = unsafe { core::slice::from_raw_parts(inaddr, len) };
+	let hwaddr = unsafe { core::slice::from_raw_parts(hwaddr, 6) };
+	let Some(address) = read_address(inaddr) else {
+		return -EINVAL;
+	};
+
+	crate::executor::network::add_neighbor(address, EthernetAddress::from_bytes(hwaddr))
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Removes the neighbor with the address `inaddr` from the neighbor table.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_neighbor_del(inaddr: *const u8, len: usize) -> i32 {
+	if inaddr.is_null() {
+		return -EINVAL;
+	}
+
+	let inaddr = unsafe { core::slice::from_raw_parts(inaddr, len) };
+	let Some(address) = read_address(inaddr) else {
+		return -EINVAL;
+	};
+
+	crate::executor::network::remove_neighbor(address)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Adds a route to the network `inaddr`/`prefix_len` via the gateway `gateway`.
+///
+/// `inaddr` and `gateway` are both either an `in_addr` or an `in6_addr`. For each
+/// destination, the route with the lowest `metric` is used.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_route_add(
+	inaddr: *const u8,
+	prefix_len: u8,
+	gateway: *const u8,
+	len: usize,
+	metric: u32,
+) -> i32 {
+	if inaddr.is_null() || gateway.is_null() {
+		return -EINVAL;
+	}
+
+	let inaddr = unsafe { core::slice::from_raw_parts(inaddr, len) };
+	let gateway = unsafe { core::slice::from_raw_parts(gateway, len) };
+	let (Some(cidr), Some(gateway)) = (read_cidr(inaddr, prefix_len), read_address(gateway)) else {
+		return -EINVAL;
+	};
+
+	crate::executor::network::add_route(cidr, gateway, metric)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Removes the routes to the network `inaddr`/`prefix_len` via the gateway `gateway`
+/// or, if `gateway` is null, via any gateway.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_route_del(
+	inaddr: *const u8,
+	prefix_len: u8,
+	gateway: *const u8,
+	len: usize,
+) -> i32 {
+	if inaddr.is_null() {
+		return -EINVAL;
+	}
+
+	let inaddr = unsafe { core::slice::from_raw_parts(inaddr, len) };
+	let Some(cidr) = read_cidr(inaddr, prefix_len) else {
+		return -EINVAL;
+	};
+	let gateway = if gateway.is_null() {
+		None
+	} else {
+		let gateway = unsafe { core::slice::from_raw_parts(gateway, len) };
+		let Some(gateway) = read_address(gateway) else {
+			return -EINVAL;
+		};
+		Some(gateway)
+	};
+
+	crate::executor::network::remove_route(cidr, gateway)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Enables the reception of all packets by the network device, if `on` is not zero,
+/// and disables it otherwise.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_net_set_promisc(on: i32) -> i32 {
+	crate::executor::network::set_promiscuous(on != 0)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Replaces the `unicast_count` unicast addresses at `unicast` and the `multicast_count`
+/// multicast addresses at `multicast`, which are received by the network device in addition
+/// to its own address and broadcasts.
+///
+/// Each address consists of 6 bytes. A table may be null, if its count is zero.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_net_set_mac_filter(
+	unicast: *const u8,
+	unicast_count: usize,
+	multicast: *const u8,
+	multicast_count: usize,
+) -> i32 {
+	fn read_table(table: *const u8, count: usize) -> Option<Vec<EthernetAddress>> {
+		if count == 0 {
+			return Some(Vec::new());
+		}
+		if table.is_null() {
+			return None;
+		}
+
+		let table = unsafe { core::slice::from_raw_parts(table, count.checked_mul(6)?) };
+		Some(
+			table
+				.chunks_exact(6)
+				.map(EthernetAddress::from_bytes)
+				.collect(),
+		)
+	}
+
+	let (Some(unicast), Some(multicast)) = (
+		read_table(unicast, unicast_count),
+		read_table(multicast, multicast_count),
+	) else {
+		return -EINVAL;
+	};
+
+	crate::executor::network::set_mac_filter(&unicast, &multicast)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Adds the VLAN `vid` to the VLAN filter of the network device.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_net_vlan_add(vid: u16) -> i32 {
+	crate::executor::network::add_vlan(vid)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Removes the VLAN `vid` from the VLAN filter of the network device.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_net_vlan_del(vid: u16) -> i32 {
+	crate::executor::network::remove_vlan(vid)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Sets the number of queue pairs, which are used by the network device to receive
+/// and send packets.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_net_set_queue_pairs(pairs: u16) -> i32 {
+	crate::executor::network::set_queue_pairs(pairs)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
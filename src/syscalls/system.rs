@@ -1,4 +1,7 @@
 use crate::arch::mm::paging::{BasePageSize, PageSize};
+use crate::errno::*;
+use crate::scheduler::loadavg;
+use crate::{arch, mm, scheduler, time};
 
 /// Returns the base page size, in bytes, of the current system.
 #[hermit_macro::system]
@@ -6,3 +9,185 @@ use crate::arch::mm::paging::{BasePageSize, PageSize};
 pub extern "C" fn sys_getpagesize() -> i32 {
 	BasePageSize::SIZE.try_into().unwrap()
 }
+
+/// Number of fractional bits of the load averages in [`sysinfo`]
+const SI_LOAD_SHIFT: u32 = 16;
+
+/// Overall statistics of the system in the layout of Linux
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct sysinfo {
+	/// Seconds since boot
+	pub uptime: i64,
+	/// 1, 5 and 15 minute load averages with `SI_LOAD_SHIFT` fractional bits
+	pub loads: [u64; 3],
+	/// Total usable memory size
+	pub totalram: u64,
+	/// Available memory size
+	pub freeram: u64,
+	/// Amount of shared memory
+	pub sharedram: u64,
+	/// Memory used by buffers
+	pub bufferram: u64,
+	/// Total swap space size
+	pub totalswap: u64,
+	/// Swap space still available
+	pub freeswap: u64,
+	/// Number of current tasks
+	pub procs: u16,
+	pad: u16,
+	/// Total high memory size
+	pub totalhigh: u64,
+	/// Available high memory size
+	pub freehigh: u64,
+	/// Memory unit size in bytes
+	pub mem_unit: u32,
+}
+
+/// Stores the uptime, the load averages, the memory usage, and the number of tasks in `info`.
+///
+/// The free memory consists of the unallocated physical memory and the free
+/// part of the kernel heap. The kernel does not swap.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sysinfo(info: *mut sysinfo) -> i32 {
+	if info.is_null() {
+		return -EFAULT;
+	}
+
+	#[cfg(target_os = "none")]
+	let heap_free = mm::ALLOCATOR.heap_state().available_bytes;
+	#[cfg(not(target_os = "none"))]
+	let heap_free = 0;
+	let freeram = arch::mm::physicalmem::free_memory_size() + heap_free;
+
+	let result = sysinfo {
+		uptime: (time::boottime_micros() / 1_000_000).try_into().unwrap(),
+		loads: loadavg::averages().map(|load| load << (SI_LOAD_SHIFT - loadavg::FSHIFT)),
+		totalram: arch::mm::physicalmem::total_memory_size() as u64,
+		freeram: freeram as u64,
+		bufferram: mm::pressure::cached_bytes() as u64,
+		procs: scheduler::live_tasks().len().try_into().unwrap_or(u16::MAX),
+		mem_unit: 1,
+		..Default::default()
+	};
+	unsafe {
+		info.write(result);
+	}
+	0
+}
+
+/// First magic number of `sys_reboot`
+pub const LINUX_REBOOT_MAGIC1: u32 = 0xfee1_dead;
+/// Second magic numbers of `sys_reboot`
+pub const LINUX_REBOOT_MAGIC2: u32 = 0x2812_1969;
+pub const LINUX_REBOOT_MAGIC2A: u32 = 0x0512_1996;
+pub const LINUX_REBOOT_MAGIC2B: u32 = 0x1604_1998;
+pub const LINUX_REBOOT_MAGIC2C: u32 = 0x2011_2000;
+
+pub const LINUX_REBOOT_CMD_RESTART: u32 = 0x0123_4567;
+pub const LINUX_REBOOT_CMD_HALT: u32 = 0xcdef_0123;
+pub const LINUX_REBOOT_CMD_POWER_OFF: u32 = 0x4321_fedc;
+pub const LINUX_REBOOT_CMD_CAD_ON: u32 = 0x89ab_cdef;
+pub const LINUX_REBOOT_CMD_CAD_OFF: u32 = 0x0000_0000;
+
+/// Powers off or resets the system.
+///
+/// As on Linux, `magic` and `magic2` have to be valid magic numbers.
+/// Halting the system powers it off. Ctrl-Alt-Del is not supported,
+/// so that the corresponding commands have no effect. If the system
+/// cannot be reset, `-ENOSYS` is returned.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_reboot(magic: u32, magic2: u32, cmd: u32) -> i32 {
+	if magic != LINUX_REBOOT_MAGIC1
+		|| !matches!(
+			magic2,
+			LINUX_REBOOT_MAGIC2
+				| LINUX_REBOOT_MAGIC2A
+				| LINUX_REBOOT_MAGIC2B
+				| LINUX_REBOOT_MAGIC2C
+		) {
+		return -EINVAL;
+	}
+
+	match cmd {
+		LINUX_REBOOT_CMD_RESTART => {
+			info!("Restarting system");
+			let Err(e) = crate::syscalls::reboot();
+			-num::ToPrimitive::to_i32(&e).unwrap()
+		}
+		LINUX_REBOOT_CMD_HALT | LINUX_REBOOT_CMD_POWER_OFF => crate::syscalls::shutdown(0),
+		LINUX_REBOOT_CMD_CAD_ON | LINUX_REBOOT_CMD_CAD_OFF => 0,
+		_ => -EINVAL,
+	}
+}
+
+/// Powers off the system with the exit status `status`.
+///
+/// If `graceful` is non-zero, the files of the application are synchronized and
+/// closed, pending socket data is transmitted according to `SO_LINGER` and
+/// the drivers are quiesced before, as on `exit`. Otherwise, the system is
+/// powered off immediately. `sys_shutdown` is already taken by the socket
+/// interface, hence the name.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_poweroff(status: i32, graceful: i32) -> ! {
+	if graceful != 0 {
+		info!("Shutting down system");
+		crate::syscalls::shutdown(status)
+	} else {
+		crate::syscalls::shutdown_immediately(status)
+	}
+}
+
+/// Maximum length of the name and the version in [`kernel_feature`] including the terminating zero
+const KERNEL_FEATURE_LEN: usize = 32;
+
+/// Component of the kernel as reported by [`sys_kernel_features`]
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct kernel_feature {
+	/// `0` for a Cargo feature, `1` for a device driver, `2` for a library
+	pub kind: u32,
+	/// Zero-terminated name, which is truncated if necessary
+	pub name: [u8; KERNEL_FEATURE_LEN],
+	/// Zero-terminated version, which is empty for Cargo features
+	pub version: [u8; KERNEL_FEATURE_LEN],
+}
+
+/// Stores up to `count` components of the kernel, i.e., the enabled features,
+/// the compiled-in drivers and the libraries with their versions, in `features`.
+///
+/// Returns the total number of components, which may be larger than `count`.
+/// Hence, `features` may be null, if `count` is zero.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_kernel_features(features: *mut kernel_feature, count: usize) -> isize {
+	if features.is_null() && count > 0 {
+		return -EFAULT as isize;
+	}
+
+	let to_array = |s: &str| {
+		let mut array = [0; KERNEL_FEATURE_LEN];
+		let len = s.len().min(KERNEL_FEATURE_LEN - 1);
+		array[..len].copy_from_slice(&s.as_bytes()[..len]);
+		array
+	};
+
+	let components = crate::sbom::components();
+	for (i, component) in components.iter().take(count).enumerate() {
+		let feature = kernel_feature {
+			kind: component.kind as u32,
+			name: to_array(component.name),
+			version: to_array(component.version),
+		};
+		unsafe {
+			features.add(i).write(feature);
+		}
+	}
+
+	components.len().try_into().unwrap()
+}
@@ -11,7 +11,7 @@ const SYSNO_READ: usize = 2;
 /// number of the system call `usleep`
 const SYSNO_USLEEP: usize = 3;
 /// number of the system call `getpid`
-const SYSNO_GETPID: usize = 4;
+pub(crate) const SYSNO_GETPID: usize = 4;
 /// number of the system call `yield`
 const SYSNO_YIELD: usize = 5;
 /// number of the system call `read_entropy`
@@ -26,6 +26,8 @@ const SYSNO_FUTEX_WAIT: usize = 9;
 const SYSNO_FUTEX_WAKE: usize = 10;
 /// number of the system call `open`
 const SYSNO_OPEN: usize = 11;
+/// number of the system call `gettid`
+pub(crate) const SYSNO_GETTID: usize = 12;
 
 /// total number of system calls
 const NO_SYSCALLS: usize = 32;
@@ -72,6 +74,7 @@ impl SyscallTable {
 		table.handle[SYSNO_FUTEX_WAIT] = sys_futex_wait as *const _;
 		table.handle[SYSNO_FUTEX_WAKE] = sys_futex_wake as *const _;
 		table.handle[SYSNO_OPEN] = sys_open as *const _;
+		table.handle[SYSNO_GETTID] = sys_gettid as *const _;
 
 		table
 	}
@@ -1,4 +1,5 @@
 use alloc::collections::BTreeMap;
+use core::{mem, ptr};
 
 use hermit_sync::InterruptTicketMutex;
 
@@ -6,10 +7,13 @@ use crate::arch::core_local::*;
 use crate::arch::processor::{get_frequency, get_timestamp};
 use crate::config::USER_STACK_SIZE;
 use crate::errno::*;
-use crate::scheduler::PerCoreSchedulerExt;
-use crate::scheduler::task::{Priority, TaskHandle, TaskId};
+use crate::scheduler::group::{self, GroupId};
+use crate::scheduler::task::{
+	DeadlineParams, MAX_DEADLINE_PERIOD, NO_PRIORITIES, NORMAL_PRIO, Priority, TaskHandle, TaskId,
+};
+use crate::scheduler::{PerCoreSchedulerExt, interrupt};
 use crate::time::timespec;
-use crate::{arch, scheduler};
+use crate::{arch, io, scheduler};
 
 #[cfg(feature = "newlib")]
 pub type SignalHandler = extern "C" fn(i32);
@@ -21,6 +25,12 @@ pub extern "C" fn sys_getpid() -> Tid {
 	0
 }
 
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_gettid() -> Tid {
+	core_scheduler().get_current_task_id().into()
+}
+
 #[cfg(feature = "newlib")]
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
@@ -65,11 +75,15 @@ pub extern "C" fn sys_abort() -> ! {
 	exit(-1)
 }
 
-pub(super) fn usleep(usecs: u64) {
-	if usecs >= 10_000 {
-		// Enough time to set a wakeup timer and block the current task.
+/// Sleeps shorter than this number of microseconds are busy-waiting, because
+/// blocking the task and handling the timer interrupt take longer.
+const BUSY_WAIT_THRESHOLD: u64 = 50;
+
+pub(crate) fn usleep(usecs: u64) {
+	if usecs >= BUSY_WAIT_THRESHOLD {
+		// The one-shot timer wakes up the task with a resolution of one microsecond.
 		debug!("sys_usleep blocking the task for {} microseconds", usecs);
-		let wakeup_time = arch::processor::get_timer_ticks() + usecs;
+		let wakeup_time = arch::processor::get_timer_ticks().saturating_add(usecs);
 		let core_scheduler = core_scheduler();
 		core_scheduler.block_current_task(Some(wakeup_time));
 
@@ -109,8 +123,9 @@ pub unsafe extern "C" fn sys_nanosleep(rqtp: *const timespec, _rmtp: *mut timesp
 		return -EINVAL;
 	}
 
-	let microseconds =
-		(requested_time.tv_sec as u64) * 1_000_000 + (requested_time.tv_nsec as u64) / 1_000;
+	// round up, so that the task does not wake up too early
+	let microseconds = (requested_time.tv_sec as u64) * 1_000_000
+		+ (requested_time.tv_nsec as u64).div_ceil(1_000);
 	usleep(microseconds);
 
 	0
@@ -191,12 +206,134 @@ pub unsafe extern "C" fn sys_spawn(
 	0
 }
 
+/// Attributes of a new thread for `sys_spawn3`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SpawnAttr {
+	/// Size of this structure in bytes, which allows future extensions
+	pub size: usize,
+	/// Size of the stack in bytes or `0` for the default size
+	pub stack_size: usize,
+	/// Priority of the thread or `0` for the default priority
+	pub prio: u8,
+	/// Core, on which the thread runs, or a negative value to select any core
+	pub core_id: isize,
+	/// Task group of the thread or `0` for the group of the calling thread
+	pub group: GroupId,
+}
+
+/// Spawns a new thread, which executes `func(arg)`, with the attributes `attr`.
+///
+/// If `attr` is null, the default attributes are used. Attributes, which are
+/// not covered by `attr.size`, keep their default values. The identifier of the new
+/// thread is stored in `id`, if it isn't null. Returns `0` on success or `-EINVAL`,
+/// if an attribute is invalid.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_spawn3(
+	id: *mut Tid,
+	func: unsafe extern "C" fn(usize),
+	arg: usize,
+	attr: *const SpawnAttr,
+) -> i32 {
+	let mut attr_buf = SpawnAttr {
+		size: mem::size_of::<SpawnAttr>(),
+		stack_size: 0,
+		prio: 0,
+		core_id: -1,
+		group: group::NO_GROUP,
+	};
+	if !attr.is_null() {
+		// `group` has been added later, so older callers pass a smaller structure.
+		let size = unsafe { (*attr).size };
+		if size < mem::offset_of!(SpawnAttr, group) {
+			return -EINVAL;
+		}
+		unsafe {
+			ptr::copy_nonoverlapping(
+				attr.cast::<u8>(),
+				(&raw mut attr_buf).cast::<u8>(),
+				size.min(mem::size_of::<SpawnAttr>()),
+			);
+		}
+	}
+	let attr = attr_buf;
+
+	if usize::from(attr.prio) >= NO_PRIORITIES
+		|| attr.core_id >= arch::get_processor_count() as isize
+		|| (attr.group != group::NO_GROUP && !group::exists(attr.group))
+	{
+		return -EINVAL;
+	}
+
+	let prio = if attr.prio == 0 {
+		NORMAL_PRIO
+	} else {
+		Priority::from(attr.prio)
+	};
+	let stack_size = if attr.stack_size == 0 {
+		USER_STACK_SIZE
+	} else {
+		attr.stack_size
+	};
+
+	let group = if attr.group == group::NO_GROUP {
+		group::current()
+	} else {
+		attr.group
+	};
+
+	let new_id = unsafe {
+		scheduler::spawn_in_group(func, arg, prio, stack_size, attr.core_id, group).into()
+	};
+
+	if !id.is_null() {
+		unsafe {
+			*id = new_id;
+		}
+	}
+
+	0
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_join(id: Tid) -> i32 {
-	match scheduler::join(TaskId::from(id)) {
-		Ok(()) => 0,
-		_ => -EINVAL,
+	scheduler::join(TaskId::from(id), None)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |_| 0)
+}
+
+/// Waits until the thread `id` is finished and stores its exit code in `exit_code`.
+///
+/// If `timeout` isn't null, it specifies the maximum (relative) time to wait.
+/// Returns `0` on success, `-ETIMEDOUT` if the timeout elapses, `-ESRCH` if the thread
+/// does not exist, or `-EINVAL` if `timeout` is invalid.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_join_timeout(
+	id: Tid,
+	exit_code: *mut i32,
+	timeout: *const timespec,
+) -> i32 {
+	let timeout = if timeout.is_null() {
+		None
+	} else {
+		match unsafe { timeout.read().into_usec() } {
+			Some(usec) if usec >= 0 => Some(usec as u64),
+			_ => return -EINVAL,
+		}
+	};
+
+	match scheduler::join(TaskId::from(id), timeout) {
+		Ok(code) => {
+			if !exit_code.is_null() {
+				unsafe {
+					*exit_code = code;
+				}
+			}
+			0
+		}
+		Err(e) => -num::ToPrimitive::to_i32(&e).unwrap(),
 	}
 }
 
@@ -259,6 +396,90 @@ pub extern "C" fn sys_set_priority(id: Tid, prio: u8) {
 	}
 }
 
+/// Creates a new, empty task group and returns its identifier.
+///
+/// Threads are added to the group by passing the identifier as attribute
+/// `group` to `sys_spawn3`. Threads, which are spawned by a member of the
+/// group, join the group as well.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_task_group_create() -> GroupId {
+	group::create()
+}
+
+/// Changes the priority of all threads of the task group `group`.
+///
+/// Returns the number of threads, `-ESRCH` if the group has no threads,
+/// or `-EINVAL` if `prio` is invalid.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_task_group_set_priority(group: GroupId, prio: u8) -> i32 {
+	if prio == 0 || usize::from(prio) >= NO_PRIORITIES {
+		return -EINVAL;
+	}
+
+	group::set_priority(group, Priority::from(prio)).map_or_else(
+		|e| -num::ToPrimitive::to_i32(&e).unwrap(),
+		|count| i32::try_from(count).unwrap_or(i32::MAX),
+	)
+}
+
+/// Terminates all threads of the task group `group`.
+///
+/// A thread terminates with the exit code `-ECANCELED`, when its next system
+/// call returns. Threads, which wait on a futex, are woken up, and blocking
+/// system calls of the threads return `-EINTR`. If the calling
+/// thread belongs to the group, it terminates on the return of this call.
+/// Returns the number of threads or `-ESRCH`, if the group has no threads.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_task_group_kill(group: GroupId) -> i32 {
+	group::kill(group).map_or_else(
+		|e| -num::ToPrimitive::to_i32(&e).unwrap(),
+		|count| i32::try_from(count).unwrap_or(i32::MAX),
+	)
+}
+
+/// Interrupts the blocking system call of the thread `id`.
+///
+/// The blocking call, e.g., `accept` or `recv`, returns `-EINTR`. If the
+/// thread does not block, its next blocking call is interrupted instead.
+/// Returns `-ESRCH`, if the thread does not exist.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_interrupt(id: Tid) -> io::Result<i32> {
+	interrupt::interrupt(TaskId::from(id))?;
+	Ok(0)
+}
+
+/// Returns the time slice of the task group `group` in microseconds.
+///
+/// The group `0` refers to the threads without a group. Returns `-ESRCH`,
+/// if the group does not exist.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_task_group_get_timeslice(group: GroupId) -> i64 {
+	group::timeslice(group).map_or_else(
+		|e| -num::ToPrimitive::to_i64(&e).unwrap(),
+		|micros| micros.try_into().unwrap(),
+	)
+}
+
+/// Sets the time slice of the task group `group` to `usec` microseconds.
+///
+/// After its time slice, a running thread is preempted by a ready thread with
+/// the same priority. The time slice is clamped to the range from 100 µs to
+/// 1 s. The group `0` refers to the threads without a group.
+/// Returns the new time slice or `-ESRCH`, if the group does not exist.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_task_group_set_timeslice(group: GroupId, usec: u64) -> i64 {
+	group::set_timeslice(group, usec).map_or_else(
+		|e| -num::ToPrimitive::to_i64(&e).unwrap(),
+		|micros| micros.try_into().unwrap(),
+	)
+}
+
 /// Set priority of the current thread
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
@@ -269,3 +490,56 @@ pub extern "C" fn sys_set_current_task_priority(prio: u8) {
 		panic!("Invalid priority {}", prio);
 	}
 }
+
+/// Moves the current thread into the deadline scheduling class.
+///
+/// In each period of `period` microseconds, the thread receives `runtime`
+/// microseconds of CPU time, which are available until `deadline` microseconds
+/// after the start of the period. Deadline threads are scheduled by the earliest
+/// deadline and take precedence over all threads with a fixed priority.
+/// If the budget is exhausted, the thread is throttled until its next period.
+/// A `runtime` of zero moves the thread back to its fixed priority.
+/// The period must not exceed 4194304 microseconds (about 4 seconds).
+///
+/// Returns `-EINVAL` for invalid parameters and `-EBUSY`, if the core cannot
+/// guarantee the requested CPU time.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_set_deadline(runtime: u64, deadline: u64, period: u64) -> i32 {
+	let params = if runtime == 0 {
+		None
+	} else if runtime <= deadline && deadline <= period && period <= MAX_DEADLINE_PERIOD {
+		Some(DeadlineParams {
+			runtime,
+			deadline,
+			period,
+		})
+	} else {
+		return -EINVAL;
+	};
+
+	core_scheduler()
+		.set_current_task_deadline(params)
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Finishes the current job of a deadline thread and waits for its next period.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_deadline_yield() -> i32 {
+	core_scheduler()
+		.finish_deadline_job()
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
+
+/// Donates the rest of the time slice of the current thread to the thread `id`,
+/// e.g., the consumer of a just-filled queue.
+///
+/// If the thread is not ready or runs on another core, this behaves like `sys_yield`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_yield_to(id: Tid) -> i32 {
+	core_scheduler()
+		.yield_to(TaskId::from(id))
+		.map_or_else(|e| -num::ToPrimitive::to_i32(&e).unwrap(), |()| 0)
+}
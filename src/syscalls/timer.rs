@@ -1,7 +1,10 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
 use crate::arch;
 use crate::errno::*;
+use crate::synch::futex::{self, Flags};
 use crate::syscalls::usleep;
-use crate::time::{itimerval, timespec, timeval};
+use crate::time::{self, itimerval, timespec, timeval};
 
 #[allow(non_camel_case_types)]
 pub type clockid_t = i32;
@@ -10,8 +13,13 @@ pub(crate) const CLOCK_REALTIME: clockid_t = 1;
 pub(crate) const CLOCK_PROCESS_CPUTIME_ID: clockid_t = 2;
 pub(crate) const CLOCK_THREAD_CPUTIME_ID: clockid_t = 3;
 pub(crate) const CLOCK_MONOTONIC: clockid_t = 4;
+pub(crate) const CLOCK_BOOTTIME: clockid_t = 7;
 pub(crate) const TIMER_ABSTIME: i32 = 4;
 
+/// Incremented, whenever `CLOCK_REALTIME` is set, to wake up the tasks,
+/// which sleep until a deadline of `CLOCK_REALTIME`
+static REALTIME_GENERATION: AtomicU32 = AtomicU32::new(0);
+
 /// Finds the resolution (or precision) of a clock.
 ///
 /// This function gets the clock resolution of the clock with `clock_id` and stores it in parameter `res`.
@@ -22,6 +30,7 @@ pub(crate) const TIMER_ABSTIME: i32 = 4;
 /// - `CLOCK_PROCESS_CPUTIME_ID`
 /// - `CLOCK_THREAD_CPUTIME_ID`
 /// - `CLOCK_MONOTONIC`
+/// - `CLOCK_BOOTTIME`
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_clock_getres(clock_id: clockid_t, res: *mut timespec) -> i32 {
@@ -32,7 +41,11 @@ pub unsafe extern "C" fn sys_clock_getres(clock_id: clockid_t, res: *mut timespe
 	let result = unsafe { &mut *res };
 
 	match clock_id {
-		CLOCK_REALTIME | CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID | CLOCK_MONOTONIC => {
+		CLOCK_REALTIME
+		| CLOCK_PROCESS_CPUTIME_ID
+		| CLOCK_THREAD_CPUTIME_ID
+		| CLOCK_MONOTONIC
+		| CLOCK_BOOTTIME => {
 			// All clocks in Hermit have 1 microsecond resolution.
 			*result = timespec::from_usec(1);
 			0
@@ -52,6 +65,7 @@ pub unsafe extern "C" fn sys_clock_getres(clock_id: clockid_t, res: *mut timespe
 /// Supported clocks:
 /// - `CLOCK_REALTIME`
 /// - `CLOCK_MONOTONIC`
+/// - `CLOCK_BOOTTIME`
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_clock_gettime(clock_id: clockid_t, tp: *mut timespec) -> i32 {
@@ -63,11 +77,15 @@ pub unsafe extern "C" fn sys_clock_gettime(clock_id: clockid_t, tp: *mut timespe
 
 	match clock_id {
 		CLOCK_REALTIME => {
-			*result = timespec::from_usec(arch::kernel::systemtime::now_micros() as i64);
+			*result = timespec::from_usec(time::realtime_micros() as i64);
 			0
 		}
 		CLOCK_MONOTONIC => {
-			*result = timespec::from_usec(arch::processor::get_timer_ticks() as i64);
+			*result = timespec::from_usec(time::monotonic_micros() as i64);
+			0
+		}
+		CLOCK_BOOTTIME => {
+			*result = timespec::from_usec(time::boottime_micros() as i64);
 			0
 		}
 		_ => {
@@ -80,15 +98,40 @@ pub unsafe extern "C" fn sys_clock_gettime(clock_id: clockid_t, tp: *mut timespe
 	}
 }
 
+/// Sleeps until `CLOCK_REALTIME` reaches `deadline`.
+///
+/// If the clock is set in the meantime, the sleep is re-evaluated.
+fn sleep_until_realtime(deadline: u64) {
+	loop {
+		let generation = REALTIME_GENERATION.load(Ordering::SeqCst);
+		let now = time::realtime_micros();
+		if now >= deadline {
+			return;
+		}
+
+		let wakeup_time = arch::processor::get_timer_ticks().saturating_add(deadline - now);
+		futex::futex_wait(
+			&REALTIME_GENERATION,
+			generation,
+			Some(wakeup_time),
+			Flags::empty(),
+		);
+	}
+}
+
 /// Sleep a clock for a specified number of nanoseconds.
 ///
-/// The requested time (in nanoseconds) must be greater than 0 and less than 1,000,000.
+/// If `flags` contains `TIMER_ABSTIME`, `rqtp` is an absolute time of the
+/// clock. Otherwise, it is relative to the current time. An absolute sleep
+/// on `CLOCK_REALTIME` ends at the requested time, even if the clock is set
+/// in the meantime.
 ///
 /// Returns `0` on success, `-EINVAL` otherwise.
 ///
 /// Supported clocks:
 /// - `CLOCK_REALTIME`
 /// - `CLOCK_MONOTONIC`
+/// - `CLOCK_BOOTTIME`
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_clock_nanosleep(
@@ -102,24 +145,33 @@ pub unsafe extern "C" fn sys_clock_nanosleep(
 		"sys_clock_nanosleep called with a zero rqtp parameter"
 	);
 	let requested_time = unsafe { &*rqtp };
-	if requested_time.tv_sec < 0 || requested_time.tv_nsec > 999_999_999 {
+	if requested_time.tv_sec < 0 || !(0..=999_999_999).contains(&requested_time.tv_nsec) {
 		debug!("sys_clock_nanosleep called with an invalid requested time, returning -EINVAL");
 		return -EINVAL;
 	}
+	// round up, so that the task does not wake up too early
+	let Some(microseconds) = (requested_time.tv_sec as u64)
+		.checked_mul(1_000_000)
+		.and_then(|usec| usec.checked_add((requested_time.tv_nsec as u64).div_ceil(1_000)))
+	else {
+		debug!("sys_clock_nanosleep called with an overflowing requested time, returning -EINVAL");
+		return -EINVAL;
+	};
 
 	match clock_id {
-		CLOCK_REALTIME | CLOCK_MONOTONIC => {
-			let mut microseconds = (requested_time.tv_sec as u64) * 1_000_000
-				+ (requested_time.tv_nsec as u64) / 1_000;
-
-			if flags & TIMER_ABSTIME > 0 {
-				if clock_id == CLOCK_REALTIME {
-					microseconds -= arch::kernel::systemtime::now_micros();
-				} else {
-					microseconds -= arch::processor::get_timer_ticks();
-				}
-			}
-
+		CLOCK_REALTIME if flags & TIMER_ABSTIME != 0 => {
+			sleep_until_realtime(microseconds);
+			0
+		}
+		CLOCK_MONOTONIC if flags & TIMER_ABSTIME != 0 => {
+			usleep(microseconds.saturating_sub(time::monotonic_micros()));
+			0
+		}
+		CLOCK_BOOTTIME if flags & TIMER_ABSTIME != 0 => {
+			usleep(microseconds.saturating_sub(time::boottime_micros()));
+			0
+		}
+		CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_BOOTTIME => {
 			usleep(microseconds);
 			0
 		}
@@ -127,12 +179,35 @@ pub unsafe extern "C" fn sys_clock_nanosleep(
 	}
 }
 
+/// Sets the time of a clock.
+///
+/// Only `CLOCK_REALTIME` can be set. Tasks, which sleep until an absolute
+/// time of `CLOCK_REALTIME`, are woken up to re-evaluate their deadline.
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn sys_clock_settime(_clock_id: clockid_t, _tp: *const timespec) -> i32 {
-	// We don't support setting any clocks yet.
-	debug!("sys_clock_settime is unimplemented, returning -EINVAL");
-	-EINVAL
+pub unsafe extern "C" fn sys_clock_settime(clock_id: clockid_t, tp: *const timespec) -> i32 {
+	let Some(tp) = (unsafe { tp.as_ref() }) else {
+		return -EFAULT;
+	};
+	if tp.tv_sec < 0 || !(0..=999_999_999).contains(&tp.tv_nsec) {
+		return -EINVAL;
+	}
+
+	match clock_id {
+		CLOCK_REALTIME => {
+			let Some(microseconds) = tp.into_usec() else {
+				return -EINVAL;
+			};
+			time::set_realtime_micros(microseconds as u64);
+			REALTIME_GENERATION.fetch_add(1, Ordering::SeqCst);
+			futex::futex_wake(&REALTIME_GENERATION, i32::MAX);
+			0
+		}
+		_ => {
+			debug!("sys_clock_settime called for clock {clock_id}, which cannot be set");
+			-EINVAL
+		}
+	}
 }
 
 /// Get the system's clock time.
@@ -147,7 +222,7 @@ pub unsafe extern "C" fn sys_gettimeofday(tp: *mut timeval, tz: usize) -> i32 {
 	if let Some(result) = unsafe { tp.as_mut() } {
 		// Return the current time based on the wallclock time when we were booted up
 		// plus the current timer ticks.
-		let microseconds = arch::kernel::systemtime::now_micros();
+		let microseconds = time::realtime_micros();
 		*result = timeval::from_usec(microseconds as i64);
 	}
 
@@ -1,5 +1,94 @@
+//! Timekeeping
+//!
+//! All clocks are derived from the timer of the processor and the hardware
+//! clock. Each clock stores its offset to these sources, so that it can be set
+//! or continued after a restore without touching the other clocks:
+//!
+//! - `CLOCK_REALTIME` follows the hardware clock and may be set by `clock_settime`.
+//! - `CLOCK_MONOTONIC` continues at its value of the checkpoint after a
+//!   restore, so it never jumps. It does not include the suspended time.
+//! - `CLOCK_BOOTTIME` is `CLOCK_MONOTONIC` plus the time, in which the
+//!   application was suspended between a checkpoint and its restore.
+
+use core::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
 use crate::arch;
 
+/// Offset of `CLOCK_REALTIME` to the time of the hardware clock in microseconds,
+/// which is changed by `clock_settime`
+static REALTIME_OFFSET: AtomicI64 = AtomicI64::new(0);
+
+/// Offset of `CLOCK_MONOTONIC` to the timer ticks in microseconds,
+/// which is changed by a restore
+static MONOTONIC_OFFSET: AtomicI64 = AtomicI64::new(0);
+
+/// Microseconds, in which the application has been suspended,
+/// i.e., the offset of `CLOCK_BOOTTIME` to `CLOCK_MONOTONIC`
+static SUSPENDED_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the microseconds since the epoch according to `CLOCK_REALTIME`.
+pub(crate) fn realtime_micros() -> u64 {
+	arch::kernel::systemtime::now_micros()
+		.saturating_add_signed(REALTIME_OFFSET.load(Ordering::Relaxed))
+}
+
+/// Steps `CLOCK_REALTIME` to `micros` microseconds since the epoch.
+pub(crate) fn set_realtime_micros(micros: u64) {
+	let offset = i128::from(micros) - i128::from(arch::kernel::systemtime::now_micros());
+	REALTIME_OFFSET.store(
+		offset.clamp(i64::MIN.into(), i64::MAX.into()) as i64,
+		Ordering::Relaxed,
+	);
+}
+
+/// Returns the microseconds according to `CLOCK_MONOTONIC`.
+pub(crate) fn monotonic_micros() -> u64 {
+	arch::processor::get_timer_ticks()
+		.saturating_add_signed(MONOTONIC_OFFSET.load(Ordering::Relaxed))
+}
+
+/// Returns the microseconds according to `CLOCK_BOOTTIME`.
+pub(crate) fn boottime_micros() -> u64 {
+	monotonic_micros().saturating_add(SUSPENDED_MICROS.load(Ordering::Relaxed))
+}
+
+/// Values of the clocks in microseconds, which are stored in a checkpoint
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Clocks {
+	pub realtime: u64,
+	pub monotonic: u64,
+	pub boottime: u64,
+}
+
+/// Returns the current values of the clocks.
+pub(crate) fn clocks() -> Clocks {
+	Clocks {
+		realtime: realtime_micros(),
+		monotonic: monotonic_micros(),
+		boottime: boottime_micros(),
+	}
+}
+
+/// Continues the clocks at the values `saved` of a checkpoint.
+///
+/// `CLOCK_MONOTONIC` continues at its saved value. The time, which has passed
+/// according to `CLOCK_REALTIME` since the checkpoint, is added to `CLOCK_BOOTTIME`.
+pub(crate) fn resume(saved: &Clocks) {
+	let suspended = realtime_micros().saturating_sub(saved.realtime);
+	let offset = i128::from(saved.monotonic) - i128::from(arch::processor::get_timer_ticks());
+	MONOTONIC_OFFSET.store(
+		offset.clamp(i64::MIN.into(), i64::MAX.into()) as i64,
+		Ordering::Relaxed,
+	);
+	SUSPENDED_MICROS.store(
+		saved
+			.boottime
+			.saturating_sub(saved.monotonic)
+			.saturating_add(suspended),
+		Ordering::Relaxed,
+	);
+}
+
 #[allow(non_camel_case_types)]
 pub type time_t = i64;
 #[allow(non_camel_case_types)]
@@ -72,9 +161,7 @@ pub struct SystemTime(timespec);
 impl SystemTime {
 	/// Returns the system time corresponding to "now".
 	pub fn now() -> Self {
-		Self(timespec::from_usec(
-			arch::kernel::systemtime::now_micros() as i64
-		))
+		Self(timespec::from_usec(realtime_micros() as i64))
 	}
 }
 
@@ -0,0 +1,105 @@
+//! Hardware watchpoints for kernel debugging.
+//!
+//! A watchpoint traps accesses to a small, naturally aligned region of kernel
+//! memory, which helps to find stray writes that corrupt kernel state.
+//! Watchpoints are programmed into the debug registers (`DR0`-`DR3` on x86_64,
+//! `DBGWVR<n>_EL1`/`DBGWCR<n>_EL1` on AArch64). The debug registers are per
+//! core; other cores load a modified set of watchpoints on their next pass
+//! through the scheduler.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use hermit_sync::InterruptSpinMutex;
+use memory_addresses::VirtAddr;
+
+use crate::arch::core_local::CoreLocal;
+use crate::arch::kernel::watchpoint as arch_watchpoint;
+use crate::io;
+
+/// Maximum number of watchpoints, which are supported by the kernel
+pub const MAX_WATCHPOINTS: usize = 4;
+
+/// Accesses, which trigger a watchpoint
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WatchKind {
+	Write,
+	ReadWrite,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+	pub addr: VirtAddr,
+	/// Length of the watched region (1, 2, 4 or 8 bytes)
+	pub len: usize,
+	pub kind: WatchKind,
+}
+
+static WATCHPOINTS: InterruptSpinMutex<[Option<Watchpoint>; MAX_WATCHPOINTS]> =
+	InterruptSpinMutex::new([None; MAX_WATCHPOINTS]);
+
+/// Incremented whenever the watchpoints are modified
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// Watches `len` bytes at `addr` and returns the slot of the new watchpoint.
+pub fn set(addr: VirtAddr, len: usize, kind: WatchKind) -> io::Result<usize> {
+	if !matches!(len, 1 | 2 | 4 | 8) || addr.as_u64() % len as u64 != 0 {
+		return Err(io::Error::EINVAL);
+	}
+
+	let slots = arch_watchpoint::slots().min(MAX_WATCHPOINTS);
+	let mut watchpoints = WATCHPOINTS.lock();
+	let slot = watchpoints[..slots]
+		.iter()
+		.position(Option::is_none)
+		.ok_or(io::Error::EBUSY)?;
+	watchpoints[slot] = Some(Watchpoint { addr, len, kind });
+	GENERATION.fetch_add(1, Ordering::AcqRel);
+	drop(watchpoints);
+
+	info!("Set watchpoint {slot} on {len} bytes at {addr:p} ({kind:?})");
+	sync();
+	Ok(slot)
+}
+
+/// Removes the watchpoint in `slot`.
+pub fn clear(slot: usize) -> io::Result<()> {
+	let mut watchpoints = WATCHPOINTS.lock();
+	watchpoints
+		.get_mut(slot)
+		.and_then(Option::take)
+		.ok_or(io::Error::EINVAL)?;
+	GENERATION.fetch_add(1, Ordering::AcqRel);
+	drop(watchpoints);
+
+	sync();
+	Ok(())
+}
+
+/// Returns the current watchpoints.
+pub fn list() -> [Option<Watchpoint>; MAX_WATCHPOINTS] {
+	*WATCHPOINTS.lock()
+}
+
+/// Loads the watchpoints into the debug registers of the current core, if they were modified.
+pub(crate) fn sync() {
+	let generation = GENERATION.load(Ordering::Acquire);
+	let core_local = CoreLocal::get();
+	if core_local.watchpoint_generation.get() == generation {
+		return;
+	}
+
+	let watchpoints = *WATCHPOINTS.lock();
+	arch_watchpoint::install(&watchpoints);
+	core_local.watchpoint_generation.set(generation);
+}
+
+/// Reports an access, which triggered the watchpoint in `slot`.
+pub(crate) fn report_hit(slot: usize, pc: u64) {
+	match WATCHPOINTS.lock().get(slot).copied().flatten() {
+		Some(watchpoint) => error!(
+			"Watchpoint {slot} triggered: {:?} access to {} bytes at {:p}, PC = {pc:#x}",
+			watchpoint.kind, watchpoint.len, watchpoint.addr
+		),
+		None => error!("Unknown watchpoint {slot} triggered, PC = {pc:#x}"),
+	}
+}
@@ -11,16 +11,19 @@ extern crate alloc;
 #[macro_use]
 extern crate hermit;
 
-use core::ptr;
+use core::hint::black_box;
 use core::sync::atomic::AtomicU32;
 use core::sync::atomic::Ordering::Relaxed;
+use core::{mem, ptr};
 
 mod common;
 
 use alloc::vec;
 
 use hermit::errno::{EAGAIN, ETIMEDOUT};
-use hermit::syscalls::{sys_futex_wait, sys_futex_wake, sys_join, sys_spawn2, sys_usleep};
+use hermit::syscalls::{
+	SpawnAttr, sys_futex_wait, sys_futex_wake, sys_join, sys_spawn2, sys_spawn3, sys_usleep,
+};
 use hermit::time::timespec;
 
 const USER_STACK_SIZE: usize = 0x0010_0000;
@@ -49,6 +52,47 @@ pub fn thread_test() {
 	}
 }
 
+/// Size of a frame of [`use_stack`]
+const FRAME_SIZE: usize = 0x1_0000;
+
+/// Occupies `depth` frames of [`FRAME_SIZE`] bytes on the stack.
+#[inline(never)]
+fn use_stack(depth: usize) -> u8 {
+	let mut frame = [0u8; FRAME_SIZE];
+	frame[depth] = depth as u8;
+	black_box(&mut frame);
+	if depth == 0 {
+		frame[0]
+	} else {
+		frame[depth].wrapping_add(use_stack(depth - 1))
+	}
+}
+
+extern "C" fn large_stack_func(size: usize) {
+	// leave a quarter of the stack for the remaining frames
+	black_box(use_stack(size / 4 * 3 / FRAME_SIZE));
+}
+
+#[test_case]
+pub fn test_large_stack() {
+	// A stack larger than the default user stack must not be truncated.
+	let stack_size = 4 * USER_STACK_SIZE;
+	let attr = SpawnAttr {
+		size: mem::size_of::<SpawnAttr>(),
+		stack_size,
+		prio: NORMAL_PRIO,
+		core_id: -1,
+		group: 0,
+	};
+
+	let mut id = 0;
+	let ret = unsafe { sys_spawn3(&mut id, large_stack_func, stack_size, &attr) };
+	assert_eq!(ret, 0);
+
+	let ret = sys_join(id);
+	assert_eq!(ret, 0);
+}
+
 unsafe extern "C" fn waker_func(futex: usize) {
 	let futex = unsafe { &*(futex as *const AtomicU32) };
 
@@ -1,11 +1,13 @@
-use std::collections::HashSet;
-use std::fmt::Write;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::mem;
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
-use goblin::archive::Archive as GoblinArchive;
-use goblin::elf64::header;
-use xshell::cmd;
+use ar_archive_writer::{ArchiveKind, DEFAULT_OBJECT_READER, NewArchiveMember};
+use object::build::elf::{Builder, SectionData};
+use object::read::archive::ArchiveFile;
+use object::{Object, ObjectSymbol, elf};
 
 pub struct Archive(PathBuf);
 
@@ -21,75 +23,147 @@ impl AsRef<Path> for Archive {
 	}
 }
 
+struct Member {
+	name: String,
+	data: Vec<u8>,
+}
+
+impl Member {
+	/// Returns the names of the global symbols defined by this member.
+	fn defined_symbols(&self) -> Result<Vec<&[u8]>> {
+		let file = object::File::parse(&*self.data)?;
+		let symbols = file
+			.symbols()
+			.filter(|symbol| symbol.is_global() && !symbol.is_undefined())
+			.map(|symbol| symbol.name_bytes())
+			.collect::<object::Result<_>>()?;
+
+		Ok(symbols)
+	}
+}
+
 impl Archive {
-	pub fn syscall_symbols(&self) -> Result<Vec<String>> {
+	fn members(&self) -> Result<Vec<Member>> {
 		let sh = crate::sh()?;
-		let archive = self.as_ref();
+		let archive_bytes = sh.read_binary_file(self)?;
+		let archive = ArchiveFile::parse(&*archive_bytes)?;
+
+		archive
+			.members()
+			.map(|member| {
+				let member = member?;
+				Ok(Member {
+					name: String::from_utf8(member.name().to_vec())?,
+					data: member.data(&*archive_bytes)?.to_vec(),
+				})
+			})
+			.collect()
+	}
 
-		let archive_bytes = sh.read_binary_file(archive)?;
-		let archive = GoblinArchive::parse(&archive_bytes)?;
-		let symbols = archive
-			.summarize()
-			.into_iter()
-			.filter(|(member_name, _, _)| member_name.starts_with("hermit-"))
-			.flat_map(|(_, _, symbols)| symbols)
-			.filter(|symbol| symbol.starts_with("sys_"))
-			.map(String::from)
-			.collect();
+	fn write_members(&self, members: &[Member]) -> Result<()> {
+		let sh = crate::sh()?;
+		let members = members
+			.iter()
+			.map(|member| {
+				NewArchiveMember::new(&member.data, &DEFAULT_OBJECT_READER, member.name.clone())
+			})
+			.collect::<Vec<_>>();
+
+		let mut archive_bytes = Cursor::new(Vec::new());
+		ar_archive_writer::write_archive_to_stream(
+			&mut archive_bytes,
+			&members,
+			ArchiveKind::Gnu,
+			false,
+			false,
+		)?;
+		sh.write_file(self, archive_bytes.into_inner())?;
+
+		Ok(())
+	}
+
+	pub fn syscall_symbols(&self) -> Result<Vec<String>> {
+		let mut symbols = Vec::new();
+		for member in self.members()? {
+			if !member.name.starts_with("hermit-") {
+				continue;
+			}
+
+			for symbol in member.defined_symbols()? {
+				let symbol = std::str::from_utf8(symbol)?;
+				if symbol.starts_with("sys_") {
+					symbols.push(symbol.to_string());
+				}
+			}
+		}
 
 		Ok(symbols)
 	}
 
-	pub fn retain_symbols(&self, mut exported_symbols: HashSet<&str>) -> Result<()> {
-		let sh = crate::sh()?;
+	pub fn retain_symbols(&self, exported_symbols: HashSet<&str>) -> Result<()> {
 		let archive = self.as_ref();
 		let prefix = {
 			let file_stem = archive.file_stem().unwrap().to_str().unwrap();
 			file_stem.strip_prefix("lib").unwrap_or(file_stem)
 		};
 
-		let all_symbols = {
-			let nm = crate::binutil("nm").unwrap();
-			let stdout = cmd!(sh, "{nm} --export-symbols {archive}").output()?.stdout;
-			String::from_utf8(stdout)?
-		};
+		let mut members = self.members()?;
 
-		let symbol_renames = all_symbols
-			.lines()
-			.fold(String::new(), |mut output, symbol| {
-				if exported_symbols.remove(symbol) {
-					return output;
+		let mut symbol_renames = HashMap::new();
+		for member in &members {
+			for symbol in member.defined_symbols()? {
+				if std::str::from_utf8(symbol).is_ok_and(|symbol| exported_symbols.contains(symbol))
+				{
+					continue;
 				}
 
-				if let Some(symbol) = symbol.strip_prefix("_ZN") {
-					let prefix_len = prefix.len();
-					let _ = writeln!(output, "_ZN{symbol} _ZN{prefix_len}{prefix}{symbol}",);
+				let renamed = if let Some(symbol) = symbol.strip_prefix(b"_ZN") {
+					[format!("_ZN{}{prefix}", prefix.len()).as_bytes(), symbol].concat()
 				} else {
-					let _ = writeln!(output, "{symbol} {prefix}_{symbol}");
-				}
-				output
-			});
-
-		let rename_path = archive.with_extension("redefine-syms");
-		sh.write_file(&rename_path, symbol_renames)?;
+					[prefix.as_bytes(), b"_", symbol].concat()
+				};
+				symbol_renames.insert(symbol.to_vec(), renamed);
+			}
+		}
 
-		let objcopy = crate::binutil("objcopy").unwrap();
-		cmd!(sh, "{objcopy} --redefine-syms={rename_path} {archive}").run()?;
+		// Rename both the definitions and the references in other members.
+		for member in &mut members {
+			let mut builder = Builder::read(&*member.data)?;
+			let mut renamed = false;
+			for symbol in builder.symbols.iter_mut() {
+				if let Some(name) = symbol_renames.get(&*symbol.name) {
+					symbol.name = name.clone().into();
+					renamed = true;
+				}
+			}
+
+			if renamed {
+				// LLVM stores the section names in `.strtab`, which the builder
+				// reads as the symbol string table only.
+				if !builder
+					.sections
+					.iter()
+					.any(|section| matches!(section.data, SectionData::SectionString))
+				{
+					let section = builder.sections.add();
+					section.name = b".shstrtab"[..].into();
+					section.sh_type = elf::SHT_STRTAB;
+					section.data = SectionData::SectionString;
+				}
 
-		sh.remove_path(&rename_path)?;
+				let mut data = Vec::new();
+				builder.write(&mut data)?;
+				member.data = data;
+			}
+		}
 
-		Ok(())
+		self.write_members(&members)
 	}
 
 	pub fn append(&self, file: &Self) -> Result<()> {
-		let sh = crate::sh()?;
-		let archive = self.as_ref();
-		let file = file.as_ref();
-
-		let ar = crate::binutil("ar").unwrap();
-		cmd!(sh, "{ar} qL {archive} {file}").run()?;
-
-		Ok(())
+		let mut members = self.members()?;
+		members.extend(file.members()?);
+		self.write_members(&members)
 	}
 
 	pub fn set_osabi(&self) -> Result<()> {
@@ -97,15 +171,17 @@ impl Archive {
 		let archive_path = self.as_ref();
 
 		let mut archive_bytes = sh.read_binary_file(archive_path)?;
-		let archive = GoblinArchive::parse(&archive_bytes)?;
+		let archive = ArchiveFile::parse(&*archive_bytes)?;
 
-		let file_offsets = (0..archive.len())
-			.map(|i| archive.get_at(i).unwrap().offset)
-			.collect::<Vec<_>>();
+		let file_offsets = archive
+			.members()
+			.map(|member| Ok(member?.file_range().0))
+			.collect::<Result<Vec<_>>>()?;
 
 		for file_offset in file_offsets {
 			let file_offset = usize::try_from(file_offset).unwrap();
-			archive_bytes[file_offset + header::EI_OSABI] = header::ELFOSABI_STANDALONE;
+			archive_bytes[file_offset + mem::offset_of!(elf::Ident, os_abi)] =
+				elf::ELFOSABI_STANDALONE;
 		}
 
 		sh.write_file(archive_path, archive_bytes)?;